@@ -0,0 +1,35 @@
+//! コマンドラインツールで共有するロガー
+//!
+//! ライブラリは`log`クレートを通じて診断メッセージを出力し、ロガーの設定は
+//! アプリケーションに任せています。このクレートは、同梱のコマンドラインツールが
+//! それらのメッセージを標準エラー出力に表示するためのロガーを提供します。
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// `log`クレートのメッセージを標準エラー出力に書き出すロガー
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// `Info`以上のレベルのログメッセージを標準エラー出力に書き出すロガーを設定します。
+///
+/// すでに別のロガーが設定されている場合は何もしません。
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
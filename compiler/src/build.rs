@@ -5,12 +5,21 @@
 //! matrix.defから構築する方法と、最適化されたbigram情報ファイルから構築する
 //! 2つの方法をサポートしています。
 
-use std::{fs::File, io};
+use std::io;
 use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use vibrato_rkyv::{dictionary::{DictionaryInner, SystemDictionaryBuilder}, errors::VibratoError};
+pub use vibrato_rkyv::dictionary::builder::{BuildOptions, BuildSource, SourceEncoding};
+use vibrato_rkyv::dictionary::metadata::KEY_BUILD_TIMESTAMP;
+use vibrato_rkyv::{
+    dictionary::{DictionaryInner, DictionaryMetadata, SystemDictionaryBuilder, WriteOptions},
+    errors::VibratoError,
+};
 
 use clap::Parser;
+use indicatif::MultiProgress;
+
+use crate::progress::{self, StageProgress};
 
 /// ビルドコマンドの引数
 ///
@@ -61,6 +70,89 @@ pub struct Args {
     /// This option is enabled when bi-gram information is specified.
     #[clap(long)]
     dual_connector: bool,
+
+    /// Folds half-width katakana, full-width ASCII, and other compatibility characters
+    /// into the categories of their canonical characters defined in char.def.
+    /// This affects only unknown word processing; surfaces and offsets are unchanged.
+    #[clap(long)]
+    fold_compatibility_chars: bool,
+
+    /// Stores feature strings separately from the lookup structures so that they are loaded
+    /// only when accessed. Dictionaries built with this option cannot be read by older versions.
+    #[clap(long)]
+    shard_features: bool,
+
+    /// Stores each distinct feature string only once. Implies --shard-features.
+    #[clap(long)]
+    dedup_features: bool,
+
+    /// Deduplicates the feature strings and compresses them with zstd at the given level.
+    /// They are decompressed when first accessed. Implies --shard-features.
+    #[clap(long, value_name = "LEVEL")]
+    compress_features: Option<i32>,
+
+    /// Builds a small dictionary for mobile and WebAssembly deployments: keeps only the first
+    /// K fields of each feature string, uses the raw connector for bi-gram sources, and
+    /// deduplicates the feature strings. Build from bi-gram information to get a small
+    /// connector for dictionaries with a large matrix.def, such as UniDic.
+    #[clap(long, value_name = "K", conflicts_with = "dual_connector", value_parser = clap::value_parser!(u32).range(1..))]
+    minimal_profile: Option<u32>,
+
+    /// Character encoding of the source files (utf-8, euc-jp, or shift_jis).
+    /// The original MeCab IPADIC is distributed in EUC-JP.
+    #[clap(long, default_value = "utf-8", value_parser = parse_encoding)]
+    from_encoding: SourceEncoding,
+
+    /// Number of threads used to build the dictionary.
+    #[clap(long, default_value = "1")]
+    num_threads: usize,
+
+    /// Zstandard compression level of the output dictionary.
+    #[clap(long, default_value = "19")]
+    compression_level: i32,
+
+    /// Number of worker threads for compression. If 0, compression runs in the main thread.
+    #[clap(long, default_value = "0")]
+    compression_workers: u32,
+
+    /// Metadata embedded in the dictionary as KEY=VALUE (e.g., name=mecab-ipadic, license=BSD).
+    /// Can be given multiple times. `build_timestamp` is filled in from SOURCE_DATE_EPOCH
+    /// or the current time unless specified.
+    #[clap(long = "metadata", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    metadata: Vec<(String, String)>,
+}
+
+/// `KEY=VALUE`形式の引数をパースする
+pub fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got {arg}"))
+}
+
+/// 文字コード名の引数をパースする
+fn parse_encoding(arg: &str) -> Result<SourceEncoding, String> {
+    arg.parse().map_err(|e: VibratoError| e.to_string())
+}
+
+/// コマンドライン引数から埋め込むメタデータを作成する
+///
+/// `build_timestamp`が指定されていない場合は、`SOURCE_DATE_EPOCH`環境変数または
+/// 現在時刻（UNIX時間の秒数）を設定します。
+pub fn metadata_from_args(entries: &[(String, String)]) -> DictionaryMetadata {
+    let mut metadata = DictionaryMetadata::new();
+    for (key, value) in entries {
+        metadata.insert(key.as_str(), value.as_str());
+    }
+    if metadata.build_timestamp().is_none() {
+        let timestamp = std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+                .to_string()
+        });
+        metadata.insert(KEY_BUILD_TIMESTAMP, timestamp);
+    }
+    metadata
 }
 
 /// ビルド処理中に発生する可能性のあるエラー
@@ -123,47 +215,6 @@ fn get_source_from_args(args: &Args) -> Result<BuildSource, BuildError> {
     }
 }
 
-/// 辞書ビルドのソースファイル情報
-///
-/// ビルドに使用するファイルの種類と構成を表します。
-pub enum BuildSource {
-    /// matrix.defファイルから構築
-    ///
-    /// 従来の形式のmatrix.defファイルを使用します。
-    FromMatrix {
-        /// 語彙ファイル(lex.csv)のパス
-        lexicon: PathBuf,
-        /// 連接コスト定義ファイル(matrix.def)のパス
-        matrix: PathBuf,
-        /// 文字定義ファイル(char.def)のパス
-        char_def: PathBuf,
-        /// 未知語定義ファイル(unk.def)のパス
-        unk_def: PathBuf,
-    },
-    /// 最適化されたbigram情報ファイルから構築
-    ///
-    /// モデル訓練で生成された最適化済みのbigramファイルを使用します。
-    /// こちらの方が高速ですが、より大きな辞書になります。
-    FromBigram {
-        /// 語彙ファイル(lex.csv)のパス
-        lexicon: PathBuf,
-        /// 右接続ID情報ファイル(bigram.right)のパス
-        bigram_right: PathBuf,
-        /// 左接続ID情報ファイル(bigram.left)のパス
-        bigram_left: PathBuf,
-        /// バイグラムコストファイル(bigram.cost)のパス
-        bigram_cost: PathBuf,
-        /// 文字定義ファイル(char.def)のパス
-        char_def: PathBuf,
-        /// 未知語定義ファイル(unk.def)のパス
-        unk_def: PathBuf,
-        /// デュアルコネクタを使用するかどうか
-        ///
-        /// trueの場合、速度とメモリ使用量のトレードオフを速度優先にします。
-        dual_connector: bool,
-    },
-}
-
 /// ビルドコマンドを実行する
 ///
 /// 指定されたソースファイルから辞書を構築し、zstd圧縮したバイナリ形式で出力します。
@@ -181,23 +232,47 @@ pub enum BuildSource {
 /// ファイルの読み書きや辞書構築に失敗した場合、`BuildError`を返します。
 pub fn run(args: Args) -> Result<(), BuildError> {
     let source = get_source_from_args(&args)?;
+    let start = Instant::now();
+    let multi = MultiProgress::new();
 
     println!("Compiling the system dictionary...");
-    let dict = build_dictionary(&source)?;
+    let options = match args.minimal_profile {
+        Some(num_fields) => SystemDictionaryBuilder::minimal_profile(num_fields as usize),
+        None => BuildOptions::new(),
+    };
+    let options = options
+        .encoding(args.from_encoding)
+        .num_threads(args.num_threads.max(1))
+        .progress(StageProgress::new(&multi));
+    let mut dict = build_dictionary_with_options(&source, &options)?;
+    if args.fold_compatibility_chars {
+        dict = dict.fold_compatibility_chars();
+    }
+    if args.shard_features {
+        dict = dict.shard_features();
+    }
+    if let Some(level) = args.compress_features {
+        dict = dict.compress_features(level);
+    } else if args.dedup_features {
+        dict = dict.dedup_features();
+    }
 
     println!("Writing the system dictionary...");
-    let file = File::create(&args.sysdic_out)?;
-    let mut encoder = zstd::Encoder::new(file, 19)?;
-    dict.write(&mut encoder)?;
-    encoder.finish()?;
+    let metadata = metadata_from_args(&args.metadata);
+    let options = WriteOptions::new()
+        .level(args.compression_level)
+        .workers(args.compression_workers);
+    let sizes = progress::write_zstd(&dict, &args.sysdic_out, &options, Some(&metadata), &multi)?;
 
     println!("Successfully built the dictionary to {}", args.sysdic_out.display());
+    progress::print_report(start, &args.sysdic_out, &sizes);
     Ok(())
 }
 
 /// 指定されたソースファイルから辞書を構築する
 ///
-/// CLIに依存しないコアのビルドロジックです。
+/// CLIに依存しないコアのビルドロジックは
+/// [`SystemDictionaryBuilder::from_source()`]にあります。
 ///
 /// # 引数
 ///
@@ -211,34 +286,47 @@ pub fn run(args: Args) -> Result<(), BuildError> {
 ///
 /// ファイルの読み込みや辞書構築に失敗した場合、`BuildError`を返します。
 pub fn build_dictionary(source: &BuildSource) -> Result<DictionaryInner, BuildError> {
-    let dict = match source {
-        BuildSource::FromMatrix { lexicon, matrix, char_def, unk_def } => {
-            SystemDictionaryBuilder::from_readers(
-                File::open(lexicon)?,
-                File::open(matrix)?,
-                File::open(char_def)?,
-                File::open(unk_def)?,
-            )?
-        }
-        BuildSource::FromBigram {
-            lexicon,
-            bigram_right,
-            bigram_left,
-            bigram_cost,
-            char_def,
-            unk_def,
-            dual_connector,
-        } => {
-            SystemDictionaryBuilder::from_readers_with_bigram_info(
-                File::open(lexicon)?,
-                File::open(bigram_right)?,
-                File::open(bigram_left)?,
-                File::open(bigram_cost)?,
-                File::open(char_def)?,
-                File::open(unk_def)?,
-                *dual_connector,
-            )?
-        }
-    };
-    Ok(dict)
+    build_dictionary_with_encoding(source, SourceEncoding::Utf8)
+}
+
+/// 指定された文字コードのソースファイルから辞書を構築する
+///
+/// # 引数
+///
+/// * `source` - ビルドソース情報(ファイルパスと構築方法)
+/// * `encoding` - ソースファイルの文字コード
+///
+/// # 戻り値
+///
+/// 構築された辞書の内部表現
+///
+/// # エラー
+///
+/// ファイルの読み込みや文字コードの変換、辞書構築に失敗した場合、`BuildError`を返します。
+pub fn build_dictionary_with_encoding(
+    source: &BuildSource,
+    encoding: SourceEncoding,
+) -> Result<DictionaryInner, BuildError> {
+    Ok(SystemDictionaryBuilder::from_source_with_encoding(source, encoding)?)
+}
+
+/// 指定された構築オプションに従ってソースファイルから辞書を構築する
+///
+/// # 引数
+///
+/// * `source` - ビルドソース情報(ファイルパスと構築方法)
+/// * `options` - 文字コードやスレッド数などの構築オプション
+///
+/// # 戻り値
+///
+/// 構築された辞書の内部表現
+///
+/// # エラー
+///
+/// ファイルの読み込みや文字コードの変換、辞書構築に失敗した場合、`BuildError`を返します。
+pub fn build_dictionary_with_options(
+    source: &BuildSource,
+    options: &BuildOptions,
+) -> Result<DictionaryInner, BuildError> {
+    Ok(SystemDictionaryBuilder::from_source_with_options(source, options)?)
 }
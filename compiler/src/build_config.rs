@@ -0,0 +1,240 @@
+//! フルビルドの設定ファイル
+//!
+//! このモジュールは、`full-build`の入力、出力、ハイパーパラメータをTOML形式の
+//! 設定ファイルから読み込む機能を提供します。コマンドライン引数で指定された値は
+//! 設定ファイルの値より優先されます。
+//!
+//! 設定ファイル内の相対パスは、設定ファイルのあるディレクトリからの相対パスとして
+//! 解釈されます。
+//!
+//! ```toml
+//! corpus = "corpus.txt"
+//! seed_lexicon = "seed/lex.csv"
+//! seed_unk = "seed/unk.def"
+//! char_def = "seed/char.def"
+//! feature_def = "seed/feature.def"
+//! rewrite_def = "seed/rewrite.def"
+//! out_dir = "out"
+//!
+//! lambda = 0.01
+//! regularization = "l1"
+//! max_iter = 100
+//! num_threads = 4
+//! compression_level = 19
+//! compression_workers = 8
+//!
+//! [metadata]
+//! name = "my-dictionary"
+//! license = "BSD-3-Clause"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::full_build::{Args, FullBuildError};
+use crate::train::RegularizationKind;
+
+/// 正則化係数の既定値
+const DEFAULT_LAMBDA: f64 = 0.01;
+
+/// 最大イテレーション数の既定値
+const DEFAULT_MAX_ITER: u64 = 100;
+
+/// zstdの圧縮レベルの既定値
+const DEFAULT_COMPRESSION_LEVEL: i32 = 19;
+
+/// 解決済みの設定を記録するメタデータのキー
+pub const KEY_BUILD_CONFIG: &str = "build_config";
+
+/// 設定ファイルの内容
+///
+/// すべての項目は省略可能で、省略された項目はコマンドライン引数または既定値で補われます。
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    corpus: Option<PathBuf>,
+    seed_lexicon: Option<PathBuf>,
+    seed_unk: Option<PathBuf>,
+    char_def: Option<PathBuf>,
+    feature_def: Option<PathBuf>,
+    rewrite_def: Option<PathBuf>,
+    user_lexicon_in: Option<PathBuf>,
+    lambda: Option<f64>,
+    regularization: Option<RegularizationKind>,
+    max_iter: Option<u64>,
+    num_threads: Option<usize>,
+    dual_connector: Option<bool>,
+    in_memory: Option<bool>,
+    cross_validation: Option<usize>,
+    seed: Option<u64>,
+    feature_indices: Option<Vec<usize>>,
+    out_dir: Option<PathBuf>,
+    compression_level: Option<i32>,
+    compression_workers: Option<u32>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+impl ConfigFile {
+    /// 設定ファイルを読み込み、相対パスを設定ファイルのディレクトリを基準に解決する
+    fn read(path: &Path) -> Result<Self, FullBuildError> {
+        let text = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&text)
+            .map_err(|e| FullBuildError::Config(format!("{}: {e}", path.display())))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for path in [
+            &mut config.corpus,
+            &mut config.seed_lexicon,
+            &mut config.seed_unk,
+            &mut config.char_def,
+            &mut config.feature_def,
+            &mut config.rewrite_def,
+            &mut config.user_lexicon_in,
+            &mut config.out_dir,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// コマンドライン引数と設定ファイルを統合した、フルビルドの設定
+///
+/// 出力する辞書のメタデータに[`KEY_BUILD_CONFIG`]としてTOML形式で記録されます。
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildConfig {
+    /// 訓練用コーパスファイルのパス
+    pub corpus: PathBuf,
+    /// シード語彙ファイル(lex.csv)のパス
+    pub seed_lexicon: PathBuf,
+    /// シード未知語ファイル(unk.def)のパス
+    pub seed_unk: PathBuf,
+    /// 文字定義ファイル(char.def)のパス
+    pub char_def: PathBuf,
+    /// 素性定義ファイル(feature.def)のパス
+    pub feature_def: PathBuf,
+    /// 書き換え規則定義ファイル(rewrite.def)のパス
+    pub rewrite_def: PathBuf,
+    /// 辞書に含めるユーザー語彙ファイルのパス
+    pub user_lexicon_in: Option<PathBuf>,
+    /// 正則化係数
+    pub lambda: f64,
+    /// 正則化の種類
+    pub regularization: RegularizationKind,
+    /// 最大イテレーション数
+    pub max_iter: u64,
+    /// 訓練と辞書の構築に使用するスレッド数
+    pub num_threads: usize,
+    /// デュアルコネクタを使用するかどうか
+    pub dual_connector: bool,
+    /// 辞書ソースファイルを生成せずにメモリ上で辞書を構築するかどうか
+    pub in_memory: bool,
+    /// 交差検証の分割数
+    pub cross_validation: Option<usize>,
+    /// 交差検証でコーパスをシャッフルする際のシード
+    pub seed: u64,
+    /// 交差検証で正誤の判定に使用する素性のインデックス
+    pub feature_indices: Vec<usize>,
+    /// 成果物の出力先ディレクトリ
+    pub out_dir: PathBuf,
+    /// 辞書とモデルのzstdの圧縮レベル
+    pub compression_level: i32,
+    /// 辞書の圧縮に使用するワーカースレッド数
+    pub compression_workers: u32,
+    /// 辞書に埋め込むメタデータ
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl BuildConfig {
+    /// コマンドライン引数と、指定されていれば設定ファイルから設定を解決する
+    ///
+    /// # 引数
+    ///
+    /// * `args` - フルビルドコマンドの引数
+    ///
+    /// # エラー
+    ///
+    /// 設定ファイルの読み込みや解析に失敗した場合や、必須の項目が
+    /// どちらにも指定されていない場合、`FullBuildError`を返します。
+    pub fn resolve(args: Args) -> Result<Self, FullBuildError> {
+        let file = match &args.config {
+            Some(path) => ConfigFile::read(path)?,
+            None => ConfigFile::default(),
+        };
+
+        fn required<T>(arg: Option<T>, file: Option<T>, name: &str) -> Result<T, FullBuildError> {
+            arg.or(file).ok_or_else(|| {
+                FullBuildError::Config(format!(
+                    "`{name}` must be specified on the command line or in the config file."
+                ))
+            })
+        }
+
+        let mut metadata = file.metadata;
+        metadata.extend(args.metadata);
+
+        Ok(Self {
+            corpus: required(args.corpus, file.corpus, "corpus")?,
+            seed_lexicon: required(args.seed_lexicon, file.seed_lexicon, "seed_lexicon")?,
+            seed_unk: required(args.seed_unk, file.seed_unk, "seed_unk")?,
+            char_def: required(args.char_def, file.char_def, "char_def")?,
+            feature_def: required(args.feature_def, file.feature_def, "feature_def")?,
+            rewrite_def: required(args.rewrite_def, file.rewrite_def, "rewrite_def")?,
+            user_lexicon_in: args.user_lexicon_in.or(file.user_lexicon_in),
+            lambda: args.lambda.or(file.lambda).unwrap_or(DEFAULT_LAMBDA),
+            regularization: args
+                .regularization
+                .or(file.regularization)
+                .unwrap_or(RegularizationKind::L1),
+            max_iter: args.max_iter.or(file.max_iter).unwrap_or(DEFAULT_MAX_ITER),
+            num_threads: args.num_threads.or(file.num_threads).unwrap_or(1),
+            dual_connector: args.dual_connector || file.dual_connector.unwrap_or(false),
+            in_memory: args.in_memory || file.in_memory.unwrap_or(false),
+            cross_validation: args.cross_validation.or(file.cross_validation),
+            seed: args.seed.or(file.seed).unwrap_or(0),
+            feature_indices: if args.feature_indices.is_empty() {
+                file.feature_indices.unwrap_or_default()
+            } else {
+                args.feature_indices
+            },
+            out_dir: required(args.out_dir, file.out_dir, "out_dir")?,
+            compression_level: args
+                .compression_level
+                .or(file.compression_level)
+                .unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+            compression_workers: args
+                .compression_workers
+                .or(file.compression_workers)
+                .unwrap_or(0),
+            metadata,
+        })
+        .and_then(Self::validate)
+    }
+
+    /// 同時に指定できない項目の組み合わせを確認する
+    fn validate(self) -> Result<Self, FullBuildError> {
+        if self.dual_connector && self.in_memory {
+            return Err(FullBuildError::Config(
+                "`dual_connector` and `in_memory` cannot be enabled at the same time.".to_string(),
+            ));
+        }
+        Ok(self)
+    }
+
+    /// 設定をTOML形式の文字列に変換する
+    ///
+    /// # エラー
+    ///
+    /// シリアライズに失敗した場合、`FullBuildError`を返します。
+    pub fn to_toml(&self) -> Result<String, FullBuildError> {
+        toml::to_string(self).map_err(|e| FullBuildError::Config(e.to_string()))
+    }
+}
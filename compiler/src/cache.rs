@@ -0,0 +1,145 @@
+//! キャッシュ管理モジュール
+//!
+//! このモジュールは、圧縮辞書の展開キャッシュと`TrustCache`モードのプルーフファイルを
+//! 一覧・集計・削除するサブコマンドを提供します。
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use vibrato_rkyv::dictionary::cache::{self, CacheEntry, CacheFileKind};
+use vibrato_rkyv::CacheStrategy;
+
+/// キャッシュ管理コマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "cache",
+    about = "List, measure, or clear decompressed dictionary caches and proof files."
+)]
+pub struct Args {
+    /// Directory containing the dictionary whose local `.cache` is also inspected.
+    #[clap(short = 'd', long, global = true)]
+    dict_dir: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    action: Action,
+}
+
+/// キャッシュに対する操作
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Lists cached dictionaries, proof files, and leftover temporary files.
+    List,
+    /// Prints the total size of the cache files.
+    Size,
+    /// Removes the cache files in the given location.
+    ///
+    /// Lock files, temporary files modified within the last hour, and dictionaries being written
+    /// by another process are kept.
+    Clear {
+        /// Location to clear.
+        #[clap(short = 'l', long, value_enum)]
+        location: Location,
+    },
+}
+
+/// キャッシュの場所
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Location {
+    /// `<dict-dir>/.cache`
+    Local,
+    /// The user cache directory.
+    GlobalCache,
+    /// The user data directory.
+    GlobalData,
+}
+
+impl From<Location> for CacheStrategy {
+    fn from(location: Location) -> Self {
+        match location {
+            Location::Local => Self::Local,
+            Location::GlobalCache => Self::GlobalCache,
+            Location::GlobalData => Self::GlobalData,
+        }
+    }
+}
+
+/// キャッシュ管理中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    /// Vibrato-rkyv ライブラリエラー
+    #[error(transparent)]
+    VibratoRkyv(#[from] vibrato_rkyv::errors::VibratoError),
+}
+
+/// キャッシュ管理コマンドを実行する
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # エラー
+///
+/// キャッシュディレクトリの読み込みやファイルの削除に失敗した場合、`CacheError`を返します。
+pub fn run(args: Args) -> Result<(), CacheError> {
+    let dict_dir = args.dict_dir.as_deref();
+    match args.action {
+        Action::List => {
+            let entries = cache::list_caches(dict_dir)?;
+            for entry in &entries {
+                print_entry(entry);
+            }
+            println!("{} file(s), {}", entries.len(), format_size(total_size(&entries)));
+        }
+        Action::Size => {
+            println!("{}", format_size(cache::cache_size(dict_dir)?));
+        }
+        Action::Clear { location } => {
+            let removed = cache::clear_cache(location.into(), dict_dir)?;
+            println!(
+                "Removed {} file(s), {}",
+                removed.len(),
+                format_size(total_size(&removed)),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &CacheEntry) {
+    let kind = match entry.kind {
+        CacheFileKind::Dictionary => "dic",
+        CacheFileKind::Proof => "proof",
+        CacheFileKind::SourceRecord => "src",
+        CacheFileKind::Lock => "lock",
+        CacheFileKind::Temporary => "tmp",
+    };
+    let location = match entry.location {
+        CacheStrategy::Local => "local",
+        CacheStrategy::GlobalCache => "global-cache",
+        CacheStrategy::GlobalData => "global-data",
+    };
+    println!(
+        "{location:<12} {kind:<5} {:>10} {}",
+        format_size(entry.size),
+        entry.path.display(),
+    );
+}
+
+fn total_size(entries: &[CacheEntry]) -> u64 {
+    entries.iter().map(|entry| entry.size).sum()
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,484 @@
+//! 上流辞書のダウンロードとビルドを一括で行うモジュール
+//!
+//! このモジュールは、MeCab形式の辞書ソースアーカイブ(IPADIC、UniDic、NEologdなど)を
+//! ダウンロードし、チェックサムを検証した上で、必要に応じて文字コードをUTF-8に変換し、
+//! バイナリ形式のシステム辞書(`.dic.zst`)を構築する機能を提供します。
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use sha2::{Digest, Sha256};
+use tempfile::{tempdir, NamedTempFile};
+use vibrato_rkyv::dictionary::builder::SourceEncoding;
+use vibrato_rkyv::dictionary::SystemDictionaryBuilder;
+use vibrato_rkyv::errors::VibratoError;
+
+/// 上流で配布されている辞書ソース
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Preset {
+    /// MeCab IPADIC 2.7.0-20070801 (EUC-JP)
+    Ipadic,
+    /// UniDic-cwj 3.1.1 (UTF-8)
+    UnidicCwj,
+    /// UniDic-csj 3.1.1 (UTF-8)
+    UnidicCsj,
+}
+
+impl Preset {
+    /// ソースアーカイブのURLを返す
+    const fn url(self) -> &'static str {
+        match self {
+            Self::Ipadic => {
+                "https://sourceforge.net/projects/mecab/files/mecab-ipadic/2.7.0-20070801/mecab-ipadic-2.7.0-20070801.tar.gz/download"
+            }
+            Self::UnidicCwj => "https://clrd.ninjal.ac.jp/unidic_archive/cwj/3.1.1/unidic-cwj-3.1.1.zip",
+            Self::UnidicCsj => "https://clrd.ninjal.ac.jp/unidic_archive/csj/3.1.1/unidic-csj-3.1.1.zip",
+        }
+    }
+
+    /// ソースアーカイブの既知のSHA-256チェックサムを16進文字列で返す
+    ///
+    /// `--sha256`が指定されていない場合、ダウンロードしたアーカイブをこの値で検証します。
+    /// チェックサムを確認できていないプリセットでは`None`を返し、`--sha256`または
+    /// `--insecure`の指定が必要です。
+    const fn sha256(self) -> Option<&'static str> {
+        match self {
+            Self::Ipadic => {
+                Some("b62f527d881c504576baed9c6ef6561554658b175ce6ae0096a60307e49e3523")
+            }
+            Self::UnidicCwj | Self::UnidicCsj => None,
+        }
+    }
+
+    /// アーカイブの形式を返す
+    const fn archive_kind(self) -> ArchiveKind {
+        match self {
+            Self::Ipadic => ArchiveKind::TarGz,
+            Self::UnidicCwj | Self::UnidicCsj => ArchiveKind::Zip,
+        }
+    }
+
+    /// ソースファイルの文字コードを返す
+    const fn charset(self) -> Charset {
+        match self {
+            Self::Ipadic => Charset::EucJp,
+            Self::UnidicCwj | Self::UnidicCsj => Charset::Utf8,
+        }
+    }
+}
+
+/// ソースファイルの文字コード
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Charset {
+    /// UTF-8
+    Utf8,
+    /// EUC-JP
+    EucJp,
+    /// Shift_JIS
+    ShiftJis,
+}
+
+impl Charset {
+    /// 対応するソースファイルの文字コードを返す
+    const fn encoding(self) -> SourceEncoding {
+        match self {
+            Self::Utf8 => SourceEncoding::Utf8,
+            Self::EucJp => SourceEncoding::EucJp,
+            Self::ShiftJis => SourceEncoding::ShiftJis,
+        }
+    }
+}
+
+/// ソースアーカイブの形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ArchiveKind {
+    /// .tar.gz
+    TarGz,
+    /// .tar.xz
+    TarXz,
+    /// .zip
+    Zip,
+}
+
+impl ArchiveKind {
+    /// URLまたはパスの拡張子から形式を推定する
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.xz") {
+            Some(Self::TarXz)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// ダウンロード・ビルドコマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "unidic-download-and-build",
+    about = "Download an upstream MeCab dictionary source and build the system dictionary."
+)]
+pub struct Args {
+    /// Upstream dictionary source to download.
+    ///
+    /// Either this, `--url`, or `--archive` must be specified.
+    #[clap(short = 'p', long, conflicts_with_all = ["url", "archive"])]
+    preset: Option<Preset>,
+
+    /// URL of a source archive to download (e.g., a NEologd release built upon IPADIC).
+    #[clap(long, conflicts_with = "archive")]
+    url: Option<String>,
+
+    /// Local source archive used instead of downloading.
+    #[clap(long)]
+    archive: Option<PathBuf>,
+
+    /// Archive format. Inferred from the preset or the file name if not specified.
+    #[clap(long)]
+    archive_kind: Option<ArchiveKind>,
+
+    /// Character encoding of the source files. Defaults to that of the preset, or UTF-8.
+    #[clap(long)]
+    charset: Option<Charset>,
+
+    /// Expected SHA-256 checksum (hex) of the source archive. Defaults to the checksum pinned
+    /// for the preset, if any.
+    #[clap(long)]
+    sha256: Option<String>,
+
+    /// Skips checksum verification when no checksum is pinned (e.g., UniDic presets). The
+    /// archive is NOT verified; the computed checksum is printed so that it can be pinned with
+    /// `--sha256` next time.
+    #[clap(long, alias = "no-verify")]
+    insecure: bool,
+
+    /// File to which the binary dictionary is output (in zstd).
+    #[clap(short = 'o', long)]
+    sysdic_out: PathBuf,
+
+    /// Builds a small dictionary for mobile and WebAssembly deployments by keeping only the
+    /// first K fields of each feature string and deduplicating them (e.g., 7 for IPADIC keeps
+    /// the POS and the base form).
+    #[clap(long, value_name = "K", value_parser = clap::value_parser!(u32).range(1..))]
+    minimal_profile: Option<u32>,
+}
+
+/// ダウンロード・ビルド処理中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadBuildError {
+    /// 不正な引数の組み合わせ
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(&'static str),
+
+    /// チェックサムが指定されていない
+    #[error(
+        "No checksum is pinned, so the download is refused. Specify --sha256, or pass \
+        --insecure to build from an unverified archive (computed: {0})."
+    )]
+    Unpinned(String),
+
+    /// チェックサムの不一致
+    #[error("Checksum mismatch: expected {expected}, but got {actual}.")]
+    ChecksumMismatch {
+        /// 指定されたチェックサム
+        expected: String,
+        /// 計算されたチェックサム
+        actual: String,
+    },
+
+    /// HTTPエラーステータス
+    #[error("Download failed with HTTP status {0}.")]
+    HttpStatus(reqwest::StatusCode),
+
+    /// HTTPクライアントのエラー
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// ZIPアーカイブのエラー
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+
+    /// 辞書ソースが見つからない
+    #[error("matrix.def, char.def, and unk.def were not found in the archive.")]
+    SourceNotFound,
+
+    /// 入出力エラー
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// 辞書構築エラー
+    #[error("Dictionary building failed: {0}")]
+    Vibrato(#[from] VibratoError),
+}
+
+/// ダウンロード・ビルドコマンドを実行する
+///
+/// ソースアーカイブを取得してチェックサムを検証し、展開したソースファイルを
+/// UTF-8に変換した上でシステム辞書を構築します。
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # 戻り値
+///
+/// 成功時は`Ok(())`
+///
+/// # エラー
+///
+/// ダウンロード、検証、展開、変換、構築のいずれかに失敗した場合、`DownloadBuildError`を返します。
+pub fn run(args: Args) -> Result<(), DownloadBuildError> {
+    let expected = args
+        .sha256
+        .as_deref()
+        .or_else(|| args.preset.and_then(Preset::sha256));
+    // Refuses unverifiable downloads before fetching anything.
+    if expected.is_none() && args.archive.is_none() && !args.insecure {
+        return Err(DownloadBuildError::InvalidArguments(
+            "No checksum is pinned for this download. Specify --sha256, or pass --insecure to \
+            build from an unverified archive.",
+        ));
+    }
+
+    let (mut archive_file, name) = if let Some(path) = &args.archive {
+        println!("Using the local archive {}...", path.display());
+        (File::open(path)?, path.to_string_lossy().into_owned())
+    } else {
+        let url = match (&args.url, args.preset) {
+            (Some(url), _) => url.as_str(),
+            (None, Some(preset)) => preset.url(),
+            (None, None) => {
+                return Err(DownloadBuildError::InvalidArguments(
+                    "Either --preset, --url, or --archive must be specified.",
+                ));
+            }
+        };
+        println!("Downloading {url}...");
+        (download(url)?, url.to_string())
+    };
+
+    let actual = sha256_hex(&mut archive_file)?;
+    verify_checksum(expected, actual, args.insecure)?;
+
+    let archive_kind = args
+        .archive_kind
+        .or(args.preset.map(Preset::archive_kind))
+        .or_else(|| ArchiveKind::from_name(&name))
+        .ok_or(DownloadBuildError::InvalidArguments(
+            "The archive format cannot be inferred. Specify --archive-kind.",
+        ))?;
+    let charset = args
+        .charset
+        .or(args.preset.map(Preset::charset))
+        .unwrap_or(Charset::Utf8);
+
+    println!("Extracting the archive...");
+    let unpack_dir = tempdir()?;
+    archive_file.seek(SeekFrom::Start(0))?;
+    unpack(archive_file, archive_kind, unpack_dir.path())?;
+    let source_dir = find_source_dir(unpack_dir.path())?.ok_or(DownloadBuildError::SourceNotFound)?;
+
+    println!("Compiling the system dictionary from {}...", charset.encoding());
+    let mut dict = SystemDictionaryBuilder::from_dir_with_encoding(&source_dir, charset.encoding())?;
+    if let Some(num_fields) = args.minimal_profile {
+        dict = dict.truncate_features(num_fields as usize).dedup_features();
+    }
+
+    println!("Writing the system dictionary...");
+    dict.write_zstd(File::create(&args.sysdic_out)?, 19)?;
+
+    println!("Successfully built the dictionary to {}", args.sysdic_out.display());
+    Ok(())
+}
+
+/// URLからファイルを一時ファイルにダウンロードする
+fn download(url: &str) -> Result<File, DownloadBuildError> {
+    let mut response = reqwest::blocking::get(url)?;
+    if !response.status().is_success() {
+        return Err(DownloadBuildError::HttpStatus(response.status()));
+    }
+    let mut temp_file = NamedTempFile::new()?;
+    response.copy_to(&mut temp_file)?;
+    Ok(temp_file.into_file())
+}
+
+/// 計算したチェックサムを期待される値と照合する
+///
+/// # 引数
+///
+/// * `expected` - 期待されるチェックサム。`None`の場合は固定されていないものとして扱います。
+/// * `actual` - 計算したチェックサム
+/// * `insecure` - チェックサムが固定されていない場合も検証せずに続行するかどうか
+///
+/// # エラー
+///
+/// チェックサムが一致しない場合や、固定されておらず`insecure`が`false`の場合、
+/// `DownloadBuildError`を返します。
+fn verify_checksum(
+    expected: Option<&str>,
+    actual: String,
+    insecure: bool,
+) -> Result<(), DownloadBuildError> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&actual) => {
+            Err(DownloadBuildError::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual,
+            })
+        }
+        Some(_) => {
+            println!("Verified the checksum: {actual}");
+            Ok(())
+        }
+        None if insecure => {
+            println!("WARNING: The archive is not verified (checksum: {actual}).");
+            Ok(())
+        }
+        None => Err(DownloadBuildError::Unpinned(actual)),
+    }
+}
+
+/// ファイル全体のSHA-256を16進文字列で返す
+fn sha256_hex(file: &mut File) -> Result<String, DownloadBuildError> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha256::new();
+    io::copy(file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// アーカイブを指定されたディレクトリに展開する
+fn unpack(file: File, kind: ArchiveKind, dest: &Path) -> Result<(), DownloadBuildError> {
+    let rdr = BufReader::new(file);
+    match kind {
+        ArchiveKind::TarGz => tar::Archive::new(flate2::read::GzDecoder::new(rdr)).unpack(dest)?,
+        ArchiveKind::TarXz => tar::Archive::new(xz2::read::XzDecoder::new(rdr)).unpack(dest)?,
+        ArchiveKind::Zip => zip::ZipArchive::new(rdr)?.extract(dest)?,
+    }
+    Ok(())
+}
+
+/// `matrix.def`、`char.def`、`unk.def`をすべて含むディレクトリを探す
+fn find_source_dir(dir: &Path) -> Result<Option<PathBuf>, DownloadBuildError> {
+    if ["matrix.def", "char.def", "unk.def"].iter().all(|f| dir.join(f).is_file()) {
+        return Ok(Some(dir.to_path_buf()));
+    }
+    let mut children: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_dir())
+        .collect();
+    children.sort_unstable();
+    for child in children {
+        if let Some(found) = find_source_dir(&child)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vibrato_rkyv::Tokenizer;
+
+    /// 最小限の辞書ソースを書き出す
+    fn write_source(dir: &Path, lex_csv: &[u8]) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("matrix.def"), "1 1\n0 0 0\n").unwrap();
+        fs::write(dir.join("char.def"), "DEFAULT 0 1 0\n").unwrap();
+        fs::write(dir.join("unk.def"), "DEFAULT,0,0,100,*\n").unwrap();
+        fs::write(dir.join("lex.csv"), lex_csv).unwrap();
+    }
+
+    #[test]
+    fn test_archive_kind_from_name() {
+        assert!(matches!(ArchiveKind::from_name("a/dic.tar.gz"), Some(ArchiveKind::TarGz)));
+        assert!(matches!(ArchiveKind::from_name("DIC.TGZ"), Some(ArchiveKind::TarGz)));
+        assert!(matches!(ArchiveKind::from_name("dic.tar.xz"), Some(ArchiveKind::TarXz)));
+        assert!(matches!(
+            ArchiveKind::from_name(Preset::UnidicCwj.url()),
+            Some(ArchiveKind::Zip)
+        ));
+        // The IPADIC URL ends with `/download`, so its format comes from the preset.
+        assert!(ArchiveKind::from_name(Preset::Ipadic.url()).is_none());
+        assert!(ArchiveKind::from_name("dic.tar").is_none());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let pinned = Preset::Ipadic.sha256().unwrap();
+        assert_eq!(pinned.len(), 64);
+        assert!(pinned.bytes().all(|b| b.is_ascii_hexdigit()));
+
+        assert!(verify_checksum(Some(pinned), pinned.to_uppercase(), false).is_ok());
+        assert!(matches!(
+            verify_checksum(Some(pinned), "0".repeat(64), true),
+            Err(DownloadBuildError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_checksum(None, "0".repeat(64), false),
+            Err(DownloadBuildError::Unpinned(_))
+        ));
+        assert!(verify_checksum(None, "0".repeat(64), true).is_ok());
+    }
+
+    #[test]
+    fn test_refuse_unpinned_download() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("unidic.dic.zst");
+        let out = out.to_str().unwrap();
+
+        // Refused before downloading, so this test needs no network.
+        let args = Args::try_parse_from(["", "-p", "unidic-cwj", "-o", out]).unwrap();
+        assert!(matches!(run(args), Err(DownloadBuildError::InvalidArguments(_))));
+        let args =
+            Args::try_parse_from(["", "--url", "https://example.com/dic.zip", "-o", out]).unwrap();
+        assert!(matches!(run(args), Err(DownloadBuildError::InvalidArguments(_))));
+
+        let args = Args::try_parse_from(["", "-p", "unidic-cwj", "--no-verify", "-o", out]);
+        assert!(args.unwrap().insecure);
+    }
+
+    #[test]
+    fn test_find_source_dir() {
+        let dir = tempdir().unwrap();
+        assert_eq!(find_source_dir(dir.path()).unwrap(), None);
+
+        let incomplete = dir.path().join("a");
+        fs::create_dir_all(&incomplete).unwrap();
+        fs::write(incomplete.join("matrix.def"), "1 1\n0 0 0\n").unwrap();
+        assert_eq!(find_source_dir(dir.path()).unwrap(), None);
+
+        let source = dir.path().join("b").join("mecab-ipadic");
+        write_source(&source, b"");
+        assert_eq!(find_source_dir(dir.path()).unwrap(), Some(source));
+    }
+
+    #[test]
+    fn test_decode_euc_jp() {
+        let dir = tempdir().unwrap();
+        // "東京,0,0,1,名詞" in EUC-JP
+        write_source(dir.path(), b"\xC5\xEC\xB5\xFE,0,0,1,\xCC\xBE\xBB\xEC\n");
+
+        let dict =
+            SystemDictionaryBuilder::from_dir_with_encoding(dir.path(), Charset::EucJp.encoding())
+                .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("東京");
+        worker.tokenize();
+        let tokens: Vec<_> = worker.tokens().iter().map(|t| (t.surface(), t.feature())).collect();
+        assert_eq!(tokens, [("東京", "名詞")]);
+
+        assert!(
+            SystemDictionaryBuilder::from_dir_with_encoding(dir.path(), Charset::Utf8.encoding())
+                .is_err()
+        );
+    }
+}
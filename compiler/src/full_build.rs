@@ -4,10 +4,14 @@
 //! モデルの訓練、辞書ファイルの生成、バイナリ辞書の構築の3つのステップを
 //! 自動的に実行し、すべての中間ファイルと最終的な辞書を生成します。
 
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf, time::Instant};
 use clap::Parser;
+use indicatif::MultiProgress;
 
-use crate::{build::{self, BuildError}, dictgen::{self, DictgenError, generate_dictionary_files}, train::{self, TrainError, TrainingParams}};
+use vibrato_rkyv::dictionary::WriteOptions;
+use vibrato_rkyv::trainer::{self, Corpus, Score};
+
+use crate::{build::{self, BuildError}, build_config::{BuildConfig, KEY_BUILD_CONFIG}, dictgen::{self, DictgenError, generate_dictionary_files}, progress::{self, StageProgress}, train::{self, RegularizationKind, TrainError, TrainingParams}};
 
 /// フルビルドコマンドの引数
 ///
@@ -19,53 +23,103 @@ use crate::{build::{self, BuildError}, dictgen::{self, DictgenError, generate_di
     about = "Builds a dictionary and all intermediate artifacts from a corpus"
 )]
 pub struct Args {
+    /// TOML file describing the inputs, outputs, and hyperparameters of the build.
+    ///
+    /// Relative paths in the file are resolved against the directory of the file.
+    /// Options given on the command line take precedence over the file.
+    #[clap(long, value_name = "CONFIG_PATH")]
+    pub config: Option<PathBuf>,
+
     /// Corpus file to be trained (e.g., BCCWJ).
-    #[clap(short = 't', long, value_name = "CORPUS_PATH")]
-    pub corpus: PathBuf,
+    #[clap(short = 't', long, value_name = "CORPUS_PATH", required_unless_present = "config")]
+    pub corpus: Option<PathBuf>,
 
     /// Lexicon file (lex.csv) to be weighted. All costs must be 0.
-    #[clap(short = 'l', long, value_name = "SEED_LEXICON_PATH")]
-    pub seed_lexicon: PathBuf,
+    #[clap(short = 'l', long, value_name = "SEED_LEXICON_PATH", required_unless_present = "config")]
+    pub seed_lexicon: Option<PathBuf>,
 
     /// Unknown word file (unk.def) to be weighted. All costs must be 0.
-    #[clap(short = 'u', long, value_name = "SEED_UNK_PATH")]
-    pub seed_unk: PathBuf,
+    #[clap(short = 'u', long, value_name = "SEED_UNK_PATH", required_unless_present = "config")]
+    pub seed_unk: Option<PathBuf>,
 
     /// Character definition file (char.def).
-    #[clap(short = 'c', long, value_name = "FILE_PATH")]
-    pub char_def: PathBuf,
+    #[clap(short = 'c', long, value_name = "FILE_PATH", required_unless_present = "config")]
+    pub char_def: Option<PathBuf>,
 
     /// Feature definition file (feature.def).
-    #[clap(short = 'f', long, value_name = "FILE_PATH")]
-    pub feature_def: PathBuf,
+    #[clap(short = 'f', long, value_name = "FILE_PATH", required_unless_present = "config")]
+    pub feature_def: Option<PathBuf>,
 
     /// Rewrite rule definition file (rewrite.def).
-    #[clap(short = 'r', long, value_name = "FILE_PATH")]
-    pub rewrite_def: PathBuf,
+    #[clap(short = 'r', long, value_name = "FILE_PATH", required_unless_present = "config")]
+    pub rewrite_def: Option<PathBuf>,
 
     /// User-defined lexicon file to include in the dictionary.
     #[clap(long, value_name = "USER_LEXICON_PATH")]
     pub user_lexicon_in: Option<PathBuf>,
 
-    /// Regularization coefficient (L1).
-    #[clap(long, default_value = "0.01")]
-    pub lambda: f64,
+    /// Regularization coefficient. [default: 0.01]
+    #[clap(long)]
+    pub lambda: Option<f64>,
 
-    /// Maximum number of iterations for training.
-    #[clap(long, default_value = "100")]
-    pub max_iter: u64,
+    /// Regularization type. [default: l1]
+    #[clap(long, value_enum)]
+    pub regularization: Option<RegularizationKind>,
+
+    /// Maximum number of iterations for training. [default: 100]
+    #[clap(long)]
+    pub max_iter: Option<u64>,
 
-    /// Number of threads for training.
-    #[clap(long, default_value = "1")]
-    pub num_threads: usize,
+    /// Number of threads for training and building the dictionary. [default: 1]
+    #[clap(long)]
+    pub num_threads: Option<usize>,
 
     /// Enable the dual connector for a faster but larger dictionary.
     #[clap(long)]
     pub dual_connector: bool,
 
+    /// Build the binary dictionary in memory directly from the trained model,
+    /// skipping the generation of the dictionary source files (lex.csv, matrix.def, etc.).
+    ///
+    /// The resulting dictionary uses the matrix connector. The words in the user lexicon
+    /// specified by `--user-lexicon-in` are included as a user dictionary.
+    #[clap(long, conflicts_with = "dual_connector")]
+    pub in_memory: bool,
+
+    /// Number of folds for k-fold cross-validation run before the final training.
+    ///
+    /// If not specified, cross-validation is skipped.
+    #[clap(long, value_name = "K")]
+    pub cross_validation: Option<usize>,
+
+    /// Seed for shuffling the corpus in cross-validation. [default: 0]
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Index of features used to determine the correctness in cross-validation.
+    ///
+    /// Specify comma-separated indices starting from 0.
+    /// If empty, all features are used.
+    #[clap(long, value_delimiter(','))]
+    pub feature_indices: Vec<usize>,
+
     /// Directory to which all artifacts will be output.
-    #[clap(short = 'o', long, value_name = "OUTPUT_DIR")]
-    pub out_dir: PathBuf,
+    #[clap(short = 'o', long, value_name = "OUTPUT_DIR", required_unless_present = "config")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Zstandard compression level of the model and the dictionary. [default: 19]
+    #[clap(long, value_name = "LEVEL")]
+    pub compression_level: Option<i32>,
+
+    /// Number of worker threads for compressing the dictionary.
+    /// If 0, compression runs in the main thread. [default: 0]
+    #[clap(long, value_name = "N")]
+    pub compression_workers: Option<u32>,
+
+    /// Metadata embedded in the dictionary as KEY=VALUE, in addition to the `[metadata]`
+    /// table of the config file. Can be given multiple times.
+    #[clap(long = "metadata", value_name = "KEY=VALUE", value_parser = build::parse_key_value)]
+    pub metadata: Vec<(String, String)>,
 }
 
 /// フルビルド処理中に発生する可能性のあるエラー
@@ -88,6 +142,9 @@ pub enum FullBuildError {
     /// Vibratoライブラリのエラー
     #[error(transparent)]
     Vibrato(#[from] vibrato_rkyv::errors::VibratoError),
+    /// 設定ファイルの読み込みや設定の解決に失敗した場合のエラー
+    #[error("Invalid build configuration: {0}")]
+    Config(String),
 }
 
 /// フルビルドコマンドを実行する
@@ -97,72 +154,185 @@ pub enum FullBuildError {
 /// 2. モデルから辞書ソースファイルを生成
 /// 3. ソースファイルからバイナリ辞書を構築
 ///
+/// `--in-memory`が指定された場合は、2と3の代わりにモデルからバイナリ辞書を直接構築します。
+///
+/// 設定はコマンドライン引数と`--config`で指定された設定ファイルから解決され、
+/// 解決済みの設定は辞書のメタデータにTOML形式で記録されます。
+///
 /// # 引数
 ///
 /// * `args` - フルビルドコマンドの引数
 ///
 /// # 戻り値
 ///
-/// 成功時は`Ok(())`。すべての成果物は`args.out_dir`に出力されます。
+/// 成功時は`Ok(())`。すべての成果物は設定された出力先ディレクトリに出力されます。
 ///
 /// # エラー
 ///
 /// 各フェーズの処理やファイルの入出力に失敗した場合、`FullBuildError`を返します。
 pub fn run(args: Args) -> Result<(), FullBuildError> {
-    std::fs::create_dir_all(&args.out_dir)?;
+    let config = BuildConfig::resolve(args)?;
+    let mut metadata_entries: Vec<_> = config.metadata.clone().into_iter().collect();
+    metadata_entries.push((KEY_BUILD_CONFIG.to_string(), config.to_toml()?));
+    let metadata = build::metadata_from_args(&metadata_entries);
+    let write_options = WriteOptions::new()
+        .level(config.compression_level)
+        .workers(config.compression_workers);
+
+    std::fs::create_dir_all(&config.out_dir)?;
+    let start = Instant::now();
+    let multi = MultiProgress::new();
 
-    println!("[1/3] Training model...");
     let params = TrainingParams {
-        seed_lexicon: args.seed_lexicon,
-        seed_unk: args.seed_unk,
-        corpus: args.corpus,
-        char_def: args.char_def,
-        feature_def: args.feature_def,
-        rewrite_def: args.rewrite_def,
-        lambda: args.lambda,
-        max_iter: args.max_iter,
-        num_threads: args.num_threads,
+        seed_lexicon: config.seed_lexicon,
+        seed_unk: config.seed_unk,
+        corpus: config.corpus,
+        partial_annotation: false,
+        char_def: config.char_def,
+        feature_def: config.feature_def,
+        rewrite_def: config.rewrite_def,
+        lambda: config.lambda,
+        regularization: config.regularization.into(),
+        max_iter: config.max_iter,
+        num_threads: config.num_threads,
     };
+
+    if let Some(k) = config.cross_validation {
+        println!("[0/3] Running {k}-fold cross-validation...");
+        cross_validate(&params, k, config.seed, &config.feature_indices)?;
+    }
+
+    println!("[1/3] Training model...");
+    let bar = progress::spinner(&multi, "Training the model");
     let mut model = train::train_model(&params)?;
+    progress::finish(&bar);
 
-    let model_path = args.out_dir.join("model.bin.zst");
-    let mut model_wtr = zstd::Encoder::new(File::create(&model_path)?, 19)?;
+    let bar = progress::spinner(&multi, "Writing the model");
+    let model_path = config.out_dir.join("model.bin.zst");
+    let mut model_wtr = zstd::Encoder::new(File::create(&model_path)?, config.compression_level)?;
     model.write_model(&mut model_wtr)?;
     model_wtr.finish()?;
+    progress::finish(&bar);
+
+    if config.in_memory {
+        if let Some(path) = &config.user_lexicon_in {
+            model.read_user_lexicon(File::open(path)?)?;
+        }
+
+        println!("[2/2] Building binary dictionary in memory...");
+        let bar = progress::spinner(&multi, "Building the dictionary from the model");
+        let dict_inner = model.build_system_dictionary()?;
+        progress::finish(&bar);
+
+        let sysdic_path = config.out_dir.join("system.dic.zst");
+        let sizes = progress::write_zstd(
+            &dict_inner,
+            &sysdic_path,
+            &write_options,
+            Some(&metadata),
+            &multi,
+        )?;
+
+        println!("Successfully built all artifacts in {}", config.out_dir.display());
+        progress::print_report(start, &sysdic_path, &sizes);
+        return Ok(());
+    }
 
     println!("[2/3] Generating dictionary source files...");
     let mut sources = dictgen::create_dictionary_writers_from_paths(
-        &args.out_dir.join("lex.csv"),
-        &args.out_dir.join("matrix.def"),
-        &args.out_dir.join("unk.def"),
+        &config.out_dir.join("lex.csv"),
+        &config.out_dir.join("matrix.def"),
+        &config.out_dir.join("unk.def"),
         None,
-        Some(&args.out_dir.join("bigram")), // Base name for .left, .right, .cost
+        Some(&config.out_dir.join("bigram")), // Base name for .left, .right, .cost
     )?;
 
-    if let Some(path) = &args.user_lexicon_in {
+    if let Some(path) = &config.user_lexicon_in {
         model.read_user_lexicon(File::open(path)?)?;
     }
 
+    let bar = progress::spinner(&multi, "Generating the dictionary source files");
     generate_dictionary_files(&mut model, &mut sources)?;
+    progress::finish(&bar);
 
     println!("[3/3] Building binary dictionary...");
     let build_source = build::BuildSource::FromBigram {
-        lexicon: args.out_dir.join("lex.csv"),
-        bigram_right: args.out_dir.join("bigram.right"),
-        bigram_left: args.out_dir.join("bigram.left"),
-        bigram_cost: args.out_dir.join("bigram.cost"),
+        lexicon: config.out_dir.join("lex.csv"),
+        bigram_right: config.out_dir.join("bigram.right"),
+        bigram_left: config.out_dir.join("bigram.left"),
+        bigram_cost: config.out_dir.join("bigram.cost"),
         char_def: params.char_def,
-        unk_def: args.out_dir.join("unk.def"),
-        dual_connector: args.dual_connector,
+        unk_def: config.out_dir.join("unk.def"),
+        dual_connector: config.dual_connector,
     };
 
-    let dict_inner = build::build_dictionary(&build_source)?;
+    let options = build::BuildOptions::new()
+        .num_threads(config.num_threads.max(1))
+        .progress(StageProgress::new(&multi));
+    let dict_inner = build::build_dictionary_with_options(&build_source, &options)?;
 
-    let sysdic_path = args.out_dir.join("system.dic.zst");
-    let mut sysdic_wtr = zstd::Encoder::new(File::create(sysdic_path)?, 19)?;
-    dict_inner.write(&mut sysdic_wtr)?;
-    sysdic_wtr.finish()?;
+    let sysdic_path = config.out_dir.join("system.dic.zst");
+    let sizes = progress::write_zstd(
+        &dict_inner,
+        &sysdic_path,
+        &write_options,
+        Some(&metadata),
+        &multi,
+    )?;
 
-    println!("Successfully built all artifacts in {}", args.out_dir.display());
+    println!("Successfully built all artifacts in {}", config.out_dir.display());
+    progress::print_report(start, &sysdic_path, &sizes);
+    Ok(())
+}
+
+/// k分割交差検証を実行し、各分割と全体の評価値を出力する
+///
+/// # 引数
+///
+/// * `params` - 訓練パラメータ
+/// * `k` - 分割数
+/// * `seed` - コーパスをシャッフルする際のシード
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス
+///
+/// # エラー
+///
+/// コーパスの読み込みや訓練、評価に失敗した場合、`FullBuildError`を返します。
+fn cross_validate(
+    params: &TrainingParams,
+    k: usize,
+    seed: u64,
+    feature_indices: &[usize],
+) -> Result<(), FullBuildError> {
+    let mut corpus = Corpus::from_reader(File::open(&params.corpus)?)?;
+    corpus.shuffle(seed);
+    let folds = corpus.split(k)?;
+
+    let mut total = Score::default();
+    for i in 0..folds.len() {
+        let train_corpus = Corpus::concat(
+            folds
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, fold)| fold.clone()),
+        );
+        let mut model = train::train_model_with_corpus(params, train_corpus)?;
+        let score = trainer::evaluate(&mut model, &folds[i], feature_indices)?;
+        println!(
+            "  fold {}/{}: Precision = {}, Recall = {}, F1 = {}",
+            i + 1,
+            k,
+            score.precision(),
+            score.recall(),
+            score.f1(),
+        );
+        total += score;
+    }
+    println!(
+        "  total: Precision = {}, Recall = {}, F1 = {}",
+        total.precision(),
+        total.recall(),
+        total.f1(),
+    );
     Ok(())
 }
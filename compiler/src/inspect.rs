@@ -0,0 +1,95 @@
+//! 辞書ファイルの情報表示モジュール
+//!
+//! このモジュールは、辞書ファイルのメタデータとダイジェストを表示するサブコマンドを
+//! 提供します。zstdで圧縮された辞書では、先頭のヘッダのみを読み込むため、
+//! 大きな辞書でも展開せずに内容を確認できます。
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+use clap::Parser;
+use vibrato_rkyv::dictionary::{CompressedHeader, DictionaryMetadata, MODEL_MAGIC};
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::{Dictionary, LoadMode};
+
+/// 情報表示コマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "inspect",
+    about = "Prints the metadata and digests of a dictionary file without expanding it."
+)]
+pub struct Args {
+    /// Dictionary file (`*.dic` or `*.dic.zst`).
+    path: PathBuf,
+
+    /// Also verifies the digest of the compressed data recorded at the end of the file.
+    /// Uncompressed dictionaries are always validated.
+    #[clap(long)]
+    verify: bool,
+}
+
+/// 情報表示中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum InspectError {
+    /// 入出力エラー
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Vibrato-rkyv ライブラリエラー
+    #[error(transparent)]
+    VibratoRkyv(#[from] VibratoError),
+
+    /// 情報を取得できない形式のファイル
+    #[error(
+        "{0} has no header. It is neither an uncompressed dictionary nor a compressed \
+         dictionary written by this version."
+    )]
+    NoHeader(PathBuf),
+}
+
+/// 情報表示コマンドを実行する
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # エラー
+///
+/// ファイルの読み込みに失敗した場合や、ヘッダを持たない圧縮ファイルの場合、
+/// 検証に失敗した場合に`InspectError`を返します。
+pub fn run(args: Args) -> Result<(), InspectError> {
+    let mut rdr = BufReader::new(File::open(&args.path)?);
+    if let Some(header) = CompressedHeader::read(&mut rdr)? {
+        print_metadata(header.metadata());
+        println!("content_sha256\t{}", header.content_hash());
+        println!("content_size\t{}", header.content_size());
+        if args.verify {
+            println!("compressed_sha256\t{}", header.verify(rdr)?);
+            println!("verified\tok");
+        }
+        return Ok(());
+    }
+
+    let mut magic = vec![0; MODEL_MAGIC.len()];
+    let mut rdr = File::open(&args.path)?;
+    if rdr.read_exact(&mut magic).is_err() || magic != MODEL_MAGIC {
+        return Err(InspectError::NoHeader(args.path));
+    }
+    // Uncompressed dictionaries are always validated, which does not leave proof files
+    // unlike `LoadMode::TrustCache`.
+    let dict = Dictionary::from_path(&args.path, LoadMode::Validate)?;
+    if let Some(metadata) = dict.metadata() {
+        print_metadata(metadata);
+    }
+    println!("content_sha256\t{}", dict.content_hash()?);
+    println!("content_size\t{}", args.path.metadata()?.len());
+    Ok(())
+}
+
+/// メタデータを`key\tvalue`の形式で出力する
+fn print_metadata(metadata: &DictionaryMetadata) {
+    for (key, value) in metadata.iter() {
+        println!("{key}\t{value}");
+    }
+}
@@ -0,0 +1,91 @@
+//! 学習済みモデルの素性表示モジュール
+//!
+//! このモジュールは、`train`コマンドで出力したモデルの素性と重みを表示する
+//! サブコマンドを提供します。辞書を出力してコストを比較することなく、
+//! 解析結果に強く影響している素性を確認できます。
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::trainer::{FeatureKind, FeatureWeight, Model};
+
+/// モデル表示コマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "inspect-model",
+    about = "Prints the features of a trained model with the largest absolute weights."
+)]
+pub struct Args {
+    /// Model file generated by the train command (in zstd).
+    #[clap(short = 'i', long)]
+    model_in: PathBuf,
+
+    /// Number of features to print.
+    #[clap(short = 'n', long, default_value = "20")]
+    num_features: usize,
+
+    /// Prints the weights of the given features instead of the top features.
+    /// A bi-gram feature is given as `left/right`, as in the `.cost` file of dictgen.
+    #[clap(short = 'f', long)]
+    feature: Vec<String>,
+}
+
+/// モデル表示中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum InspectModelError {
+    /// 入出力エラー
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Vibrato-rkyv ライブラリエラー
+    #[error(transparent)]
+    VibratoRkyv(#[from] VibratoError),
+}
+
+/// モデル表示コマンドを実行する
+///
+/// 素性の種類、重み、素性の文字列をタブ区切りで1行ずつ出力します。
+/// `--feature`で指定された素性が存在しない場合は、重みの代わりに`-`を出力します。
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # エラー
+///
+/// モデルの読み込みや出力に失敗した場合に`InspectModelError`を返します。
+pub fn run(args: Args) -> Result<(), InspectModelError> {
+    let model_rdr = zstd::Decoder::new(File::open(args.model_in)?)?;
+    let model = Model::read_model(model_rdr)?;
+
+    let mut wtr = BufWriter::new(io::stdout().lock());
+    if args.feature.is_empty() {
+        for f in model.top_features(args.num_features) {
+            write_feature(&mut wtr, &f)?;
+        }
+    } else {
+        for name in &args.feature {
+            match model.feature_weight(name) {
+                Some(f) => write_feature(&mut wtr, &f)?,
+                None => writeln!(&mut wtr, "-\t-\t{name}")?,
+            }
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// 素性を1行出力する
+fn write_feature<W>(mut wtr: W, f: &FeatureWeight) -> io::Result<()>
+where
+    W: Write,
+{
+    let kind = match f.kind {
+        FeatureKind::Unigram => "unigram",
+        FeatureKind::Bigram => "bigram",
+    };
+    writeln!(wtr, "{kind}\t{}\t{}", f.weight, f.name)
+}
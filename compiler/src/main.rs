@@ -5,15 +5,23 @@
 //! 辞書構築に関する全ての操作を統合したCLIツールです。
 
 mod build;
+mod build_config;
+mod cache;
 mod dictgen;
+mod download_build;
 mod full_build;
+mod inspect;
+mod inspect_model;
+mod progress;
 mod train;
 mod transmute_legacy;
+mod userdic;
+mod verify;
 
 use clap::Parser;
 use thiserror::Error;
 
-use crate::{build::BuildError, dictgen::DictgenError, full_build::FullBuildError, train::TrainError, transmute_legacy::TransmuteLegacyError};
+use crate::{build::BuildError, cache::CacheError, dictgen::DictgenError, download_build::DownloadBuildError, full_build::FullBuildError, inspect::InspectError, inspect_model::InspectModelError, train::TrainError, transmute_legacy::TransmuteLegacyError, userdic::UserdicError, verify::VerifyError};
 
 
 /// コマンドライン引数の構造体
@@ -52,10 +60,42 @@ enum Command {
     /// 辞書ソースファイル(lex.csv, matrix.def等)からバイナリ形式の辞書を生成します。
     Build(build::Args),
 
+    /// ユーザー辞書をシステム辞書に合わせてコンパイルします
+    ///
+    /// システム辞書を再構築せずに、読み込み時に付加できるユーザー辞書のバイナリを生成します。
+    Userdic(userdic::Args),
+
     /// レガシーのVibrato辞書をbincode形式からrkyv形式に変換します
     ///
     /// 古い形式の辞書ファイルを新しいrkyv形式に変換します。
     Transmute(transmute_legacy::Args),
+
+    /// 上流の辞書ソースをダウンロードし、バイナリ辞書を構築します
+    ///
+    /// IPADICやUniDicなどのソースアーカイブを取得してチェックサムを検証し、
+    /// 文字コードを変換した上でビルドします。
+    UnidicDownloadAndBuild(download_build::Args),
+
+    /// 辞書ファイルのメタデータとダイジェストを表示します
+    ///
+    /// zstdで圧縮された辞書は、先頭のヘッダのみを読み込むため展開せずに確認できます。
+    Inspect(inspect::Args),
+
+    /// 学習済みモデルの素性と重みを表示します
+    ///
+    /// 重みの絶対値が大きい素性や、指定された素性の重みを辞書を出力せずに確認できます。
+    InspectModel(inspect_model::Args),
+
+    /// 辞書の整合性を検査し、結果を機械可読な形式で出力します
+    ///
+    /// データ構造の検証、接続IDの範囲の検査、例文のトークン化を行います。
+    /// 検査に失敗した項目がある場合は0以外の終了コードで終了するため、CIで使用できます。
+    Verify(verify::Args),
+
+    /// 辞書の展開キャッシュとプルーフファイルを管理します
+    ///
+    /// ローカルおよびグローバルのキャッシュを一覧・集計・削除します。
+    Cache(cache::Args),
 }
 
 /// コンパイラの実行中に発生する可能性のあるエラー
@@ -75,9 +115,27 @@ pub enum CompileError {
     /// 辞書ビルド中のエラー
     #[error(transparent)]
     BuildError(#[from] BuildError),
+    /// ユーザー辞書コンパイル中のエラー
+    #[error(transparent)]
+    Userdic(#[from] UserdicError),
     /// レガシー辞書変換中のエラー
     #[error(transparent)]
     TransmuteLegacy(#[from] TransmuteLegacyError),
+    /// ダウンロード・ビルド中のエラー
+    #[error(transparent)]
+    DownloadBuild(#[from] DownloadBuildError),
+    /// キャッシュ管理中のエラー
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+    /// 辞書ファイルの情報表示中のエラー
+    #[error(transparent)]
+    Inspect(#[from] InspectError),
+    /// モデルの素性表示中のエラー
+    #[error(transparent)]
+    InspectModel(#[from] InspectModelError),
+    /// 辞書の自己診断中のエラー
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
 }
 
 /// メイン関数
@@ -93,11 +151,18 @@ pub enum CompileError {
 /// 各サブコマンドの実行中にエラーが発生した場合、そのエラーが返されます。
 fn main() -> Result<(), CompileError> {
     let cli = Cli::parse();
+    cli_logger::init();
     match cli.command {
         Command::FullBuild(args) => Ok(full_build::run(args)?),
         Command::Train(args) => Ok(train::run(args)?),
         Command::Dictgen(args) => Ok(dictgen::run(args)?),
         Command::Build(args) => Ok(build::run(args)?),
+        Command::Userdic(args) => Ok(userdic::run(args)?),
         Command::Transmute(args) => Ok(transmute_legacy::run(args)?),
+        Command::UnidicDownloadAndBuild(args) => Ok(download_build::run(args)?),
+        Command::Inspect(args) => Ok(inspect::run(args)?),
+        Command::InspectModel(args) => Ok(inspect_model::run(args)?),
+        Command::Verify(args) => Ok(verify::run(args)?),
+        Command::Cache(args) => Ok(cache::run(args)?),
     }
 }
@@ -0,0 +1,181 @@
+//! 進捗表示と資源使用量の報告
+//!
+//! このモジュールは、辞書の構築やモデルの訓練などの時間のかかる処理の進捗を
+//! スピナーやプログレスバーで表示し、完了時に所要時間と出力サイズを報告する機能を
+//! 提供します。標準エラー出力が端末でない場合、進捗表示は自動的に無効になります。
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use vibrato_rkyv::dictionary::builder::{BuildProgress, BuildStage};
+use vibrato_rkyv::dictionary::{DictionaryInner, DictionaryMetadata, WriteOptions};
+
+use crate::build::BuildError;
+
+/// スピナーの更新間隔
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 処理の開始時に表示するスピナーを作成する
+///
+/// # 引数
+///
+/// * `multi` - スピナーを追加する表示領域
+/// * `message` - 処理の説明
+pub fn spinner(multi: &MultiProgress, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    let bar = multi.add(ProgressBar::new_spinner());
+    bar.set_style(ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed}]").unwrap());
+    bar.set_message(message);
+    bar.enable_steady_tick(TICK_INTERVAL);
+    bar
+}
+
+/// スピナーを完了状態にする
+///
+/// 所要時間をメッセージに付加して表示を残します。
+pub fn finish(bar: &ProgressBar) {
+    let message = format!("{} ({})", bar.message(), HumanDuration(bar.elapsed()));
+    bar.set_style(ProgressStyle::with_template("  {msg}").unwrap());
+    bar.finish_with_message(message);
+}
+
+/// 辞書構築の各段階をスピナーで表示する
+///
+/// [`BuildOptions::progress()`](vibrato_rkyv::dictionary::builder::BuildOptions::progress)に
+/// 渡して使用します。
+pub struct StageProgress {
+    multi: MultiProgress,
+    bars: Mutex<HashMap<BuildStage, ProgressBar>>,
+}
+
+impl StageProgress {
+    /// 新しいインスタンスを作成する
+    ///
+    /// # 引数
+    ///
+    /// * `multi` - スピナーを追加する表示領域
+    pub fn new(multi: &MultiProgress) -> Arc<Self> {
+        Arc::new(Self {
+            multi: multi.clone(),
+            bars: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl BuildProgress for StageProgress {
+    fn start(&self, stage: BuildStage) {
+        let bar = spinner(&self.multi, stage.to_string());
+        self.bars.lock().unwrap().insert(stage, bar);
+    }
+
+    fn finish(&self, stage: BuildStage) {
+        if let Some(bar) = self.bars.lock().unwrap().remove(&stage) {
+            finish(&bar);
+        }
+    }
+}
+
+/// 書き出した辞書のサイズ
+#[derive(Clone, Copy, Debug)]
+pub struct OutputSizes {
+    /// 圧縮前のサイズ
+    pub uncompressed: u64,
+    /// zstdで圧縮したファイルのサイズ
+    pub compressed: u64,
+}
+
+/// 辞書をシリアライズし、進捗を表示しながらzstdで圧縮して書き出す
+///
+/// メタデータは辞書ファイルに埋め込まれるとともに、圧縮ファイルのヘッダにも記録されます。
+/// シリアライズした辞書は一時ファイルに書き出してから圧縮します。プログレスバーは、
+/// ヘッダに記録するダイジェストの計算と圧縮で1回ずつ進みます。
+///
+/// # 引数
+///
+/// * `dict` - 書き出す辞書
+/// * `path` - 出力先のパス
+/// * `options` - zstdの圧縮レベルやワーカースレッド数
+/// * `metadata` - 埋め込むメタデータ
+/// * `multi` - 進捗を表示する表示領域
+///
+/// # 戻り値
+///
+/// 圧縮前後のサイズ
+///
+/// # エラー
+///
+/// シリアライズやファイルの書き込みに失敗した場合、`BuildError`を返します。
+pub fn write_zstd(
+    dict: &DictionaryInner,
+    path: &Path,
+    options: &WriteOptions,
+    metadata: Option<&DictionaryMetadata>,
+    multi: &MultiProgress,
+) -> Result<OutputSizes, BuildError> {
+    let bar = spinner(multi, "Serializing the dictionary");
+    let mut spool = BufWriter::new(tempfile::tempfile()?);
+    match metadata {
+        Some(metadata) => dict.write_with_metadata(&mut spool, metadata)?,
+        None => dict.write(&mut spool)?,
+    }
+    let mut spool = spool.into_inner().map_err(|e| e.into_error())?;
+    let uncompressed = spool.stream_position()?;
+    spool.seek(SeekFrom::Start(0))?;
+    finish(&bar);
+
+    let bar = multi.add(ProgressBar::new(uncompressed));
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} {msg} [{elapsed}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+    bar.set_message("Compressing with zstd");
+    bar.enable_steady_tick(TICK_INTERVAL);
+    let mut wtr = BufWriter::new(File::create(path)?);
+    options.compress(bar.wrap_read(BufReader::new(spool)), &mut wtr, metadata)?;
+    wtr.into_inner().map_err(|e| e.into_error())?;
+    finish(&bar);
+
+    Ok(OutputSizes {
+        uncompressed,
+        compressed: path.metadata()?.len(),
+    })
+}
+
+/// 所要時間、出力サイズ、最大メモリ使用量を出力する
+///
+/// # 引数
+///
+/// * `start` - 処理の開始時刻
+/// * `path` - 出力した辞書のパス
+/// * `sizes` - 出力した辞書のサイズ
+pub fn print_report(start: Instant, path: &Path, sizes: &OutputSizes) {
+    println!("Build time:      {}", HumanDuration(start.elapsed()));
+    println!("Dictionary size: {} (uncompressed)", HumanBytes(sizes.uncompressed));
+    println!(
+        "Output size:     {} ({:.1}% of uncompressed) {}",
+        HumanBytes(sizes.compressed),
+        sizes.compressed as f64 / sizes.uncompressed.max(1) as f64 * 100.0,
+        path.display(),
+    );
+    if let Some(peak) = peak_memory() {
+        println!("Peak memory:     {}", HumanBytes(peak));
+    }
+}
+
+/// プロセスの最大常駐メモリサイズを取得する
+///
+/// Linux以外では`None`を返します。
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
@@ -1,18 +1,22 @@
 //! モデル訓練モジュール
 //!
 //! このモジュールは、コーパスから形態素解析モデルを訓練する機能を提供します。
-//! 教師データとなるコーパスと各種定義ファイルを読み込み、L1正則化を用いた
+//! 教師データとなるコーパスと各種定義ファイルを読み込み、L1またはL2正則化を用いた
 //! 確率的勾配降下法により重みパラメータを学習します。
 
 use std::fs::File;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use vibrato_rkyv::errors::VibratoError;
-use vibrato_rkyv::trainer::{Corpus, Model, Trainer, TrainerConfig};
+use vibrato_rkyv::trainer::{
+    self, Corpus, Model, Regularization, Score, StreamingCorpus, Trainer, TrainerConfig,
+};
 
 /// 訓練コマンドの引数
 ///
@@ -37,6 +41,11 @@ pub struct Args {
     #[clap(short = 't', long)]
     corpus: PathBuf,
 
+    /// Treats tokens whose feature is `*` as unannotated. Runs of unannotated tokens are not
+    /// constrained during training. Without this option, `*` is an ordinary feature.
+    #[clap(long)]
+    partial_annotation: bool,
+
     /// Character definition file (char.def).
     #[clap(short = 'c', long)]
     char_def: PathBuf,
@@ -53,10 +62,14 @@ pub struct Args {
     #[clap(short = 'o', long)]
     model_out: PathBuf,
 
-    /// Regularization coefficient. The larger the value, the stronger the L1-regularization.
+    /// Regularization coefficient. The larger the value, the stronger the regularization.
     #[clap(long, default_value = "0.01")]
     lambda: f64,
 
+    /// Regularization type.
+    #[clap(long, value_enum, default_value = "l1")]
+    regularization: RegularizationKind,
+
     /// Maximum number of iterations.
     #[clap(long, default_value = "100")]
     max_iter: u64,
@@ -64,6 +77,41 @@ pub struct Args {
     /// Number of threads.
     #[clap(long, default_value = "1")]
     num_threads: usize,
+
+    /// A file to which the training metrics are written for plotting training curves.
+    /// The format is JSON Lines if the extension is `.jsonl`, or CSV otherwise.
+    ///
+    /// The CRF trainer does not report intermediate states, so the model is retrained from scratch
+    /// up to each checkpoint. The total training time grows accordingly.
+    #[clap(long)]
+    metrics_out: Option<PathBuf>,
+
+    /// Number of iterations between the checkpoints written to the metrics file.
+    #[clap(long, default_value = "10")]
+    metrics_interval: u64,
+
+    /// Development corpus evaluated at each checkpoint. The format is the same as the corpus.
+    #[clap(long, requires = "metrics_out")]
+    dev_corpus: Option<PathBuf>,
+}
+
+/// 正則化の種類
+#[derive(Clone, Copy, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegularizationKind {
+    /// L1 regularization, which produces a sparse model.
+    L1,
+    /// L2 regularization, which produces a dense model but often converges faster.
+    L2,
+}
+
+impl From<RegularizationKind> for Regularization {
+    fn from(kind: RegularizationKind) -> Self {
+        match kind {
+            RegularizationKind::L1 => Self::L1,
+            RegularizationKind::L2 => Self::L2,
+        }
+    }
 }
 
 /// 訓練処理中に発生する可能性のあるエラー
@@ -89,16 +137,20 @@ pub struct TrainingParams {
     pub seed_unk: PathBuf,
     /// 訓練用コーパスファイルのパス
     pub corpus: PathBuf,
+    /// 素性が`*`のトークンを注釈されていないものとして扱うかどうか
+    pub partial_annotation: bool,
     /// 文字定義ファイル(char.def)のパス
     pub char_def: PathBuf,
     /// 素性定義ファイル(feature.def)のパス
     pub feature_def: PathBuf,
     /// 書き換え規則定義ファイル(rewrite.def)のパス
     pub rewrite_def: PathBuf,
-    /// L1正則化係数
+    /// 正則化係数
     ///
-    /// 値が大きいほど正則化が強くなり、スパース性が高まります。
+    /// 値が大きいほど正則化が強くなります。
     pub lambda: f64,
+    /// 正則化の種類
+    pub regularization: Regularization,
     /// 最大イテレーション数
     pub max_iter: u64,
     /// 並列処理に使用するスレッド数
@@ -125,16 +177,26 @@ pub fn run(args: Args) -> Result<(), TrainError> {
         seed_lexicon: args.seed_lexicon,
         seed_unk: args.seed_unk,
         corpus: args.corpus,
+        partial_annotation: args.partial_annotation,
         char_def: args.char_def,
         feature_def: args.feature_def,
         rewrite_def: args.rewrite_def,
         lambda: args.lambda,
+        regularization: args.regularization.into(),
         max_iter: args.max_iter,
         num_threads: args.num_threads,
     };
 
     println!("Starting model training...");
-    let model = train_model(&params)?;
+    let model = match &args.metrics_out {
+        Some(metrics_out) => train_model_with_metrics(
+            &params,
+            metrics_out,
+            args.metrics_interval,
+            args.dev_corpus.as_deref(),
+        )?,
+        None => train_model(&params)?,
+    };
 
     println!("Writing model to {}...", args.model_out.display());
     let file = File::create(&args.model_out)?;
@@ -149,6 +211,7 @@ pub fn run(args: Args) -> Result<(), TrainError> {
 /// 指定されたパラメータでモデルを訓練する
 ///
 /// CLIに依存しないコアの訓練ロジックです。
+/// コーパスは全体をメモリに読み込まず、逐次的に読み込みます。
 ///
 /// # 引数
 ///
@@ -162,6 +225,218 @@ pub fn run(args: Args) -> Result<(), TrainError> {
 ///
 /// ファイルの読み込みや訓練処理に失敗した場合、`TrainError`を返します。
 pub fn train_model(params: &TrainingParams) -> Result<Model, TrainError> {
+    let corpus = open_corpus(params)?;
+
+    let model = build_trainer(params)?.train_streaming(&corpus)?;
+    Ok(model)
+}
+
+/// 訓練用コーパスを逐次的に読み込むよう開く
+///
+/// # 引数
+///
+/// * `params` - 訓練パラメータ
+///
+/// # エラー
+///
+/// コーパスファイルを開けない場合、`VibratoError`を返します。
+fn open_corpus(params: &TrainingParams) -> Result<StreamingCorpus, VibratoError> {
+    if params.partial_annotation {
+        Corpus::open_streaming_partial(&params.corpus)
+    } else {
+        Corpus::open_streaming(&params.corpus)
+    }
+}
+
+/// 訓練の経過を記録するファイルの形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricsFormat {
+    /// カンマ区切り
+    Csv,
+    /// 1行に1つのJSONオブジェクト
+    JsonLines,
+}
+
+impl MetricsFormat {
+    /// ファイルの拡張子から形式を判定する
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("jsonl") => Self::JsonLines,
+            _ => Self::Csv,
+        }
+    }
+}
+
+/// チェックポイントごとの訓練の経過
+struct Metrics {
+    /// 反復回数
+    iteration: u64,
+    /// 重みが0でない素性の数
+    num_features: usize,
+    /// 開発コーパスでの評価値
+    dev: Option<Score>,
+    /// このチェックポイントまでの訓練に要した秒数
+    train_secs: f64,
+}
+
+impl Metrics {
+    /// CSV形式のヘッダーを書き出す
+    fn write_header<W: Write>(mut wtr: W, format: MetricsFormat) -> io::Result<()> {
+        match format {
+            MetricsFormat::Csv => writeln!(
+                wtr,
+                "iteration,num_features,dev_precision,dev_recall,dev_f1,train_secs"
+            ),
+            MetricsFormat::JsonLines => Ok(()),
+        }
+    }
+
+    /// 1つのチェックポイントを書き出す
+    ///
+    /// 開発コーパスが指定されていない場合や、評価値が定義されない場合は、CSVでは空欄、
+    /// JSONでは`null`となります。
+    fn write<W: Write>(&self, mut wtr: W, format: MetricsFormat) -> io::Result<()> {
+        let value = |x: Option<f64>| x.filter(|x| x.is_finite());
+        let precision = value(self.dev.map(|s| s.precision()));
+        let recall = value(self.dev.map(|s| s.recall()));
+        let f1 = value(self.dev.map(|s| s.f1()));
+        match format {
+            MetricsFormat::Csv => {
+                let field = |x: Option<f64>| x.map_or_else(String::new, |x| x.to_string());
+                writeln!(
+                    wtr,
+                    "{},{},{},{},{},{}",
+                    self.iteration,
+                    self.num_features,
+                    field(precision),
+                    field(recall),
+                    field(f1),
+                    self.train_secs,
+                )
+            }
+            MetricsFormat::JsonLines => {
+                let field = |x: Option<f64>| x.map_or_else(|| "null".to_string(), |x| x.to_string());
+                writeln!(
+                    wtr,
+                    "{{\"iteration\":{},\"num_features\":{},\"dev_precision\":{},\"dev_recall\":{},\"dev_f1\":{},\"train_secs\":{}}}",
+                    self.iteration,
+                    self.num_features,
+                    field(precision),
+                    field(recall),
+                    field(f1),
+                    self.train_secs,
+                )
+            }
+        }
+    }
+}
+
+/// 訓練の経過をファイルに記録しながらモデルを訓練する
+///
+/// 学習器は途中の状態を報告しないため、`interval`回ごとのチェックポイントまでの訓練を
+/// 最初からやり直し、各モデルの素性数と開発コーパスでの評価値を記録します。
+/// 訓練は決定的なため、各チェックポイントのモデルは最大反復回数まで訓練した場合の
+/// 途中の状態と一致します。最後のチェックポイントのモデルを返します。
+///
+/// # 引数
+///
+/// * `params` - 訓練パラメータ
+/// * `metrics_out` - 経過の出力先
+/// * `interval` - チェックポイントの間隔（反復回数）
+/// * `dev_corpus` - 開発コーパスのパス
+///
+/// # 戻り値
+///
+/// 訓練されたモデル
+///
+/// # エラー
+///
+/// ファイルの読み書きや訓練処理に失敗した場合、`TrainError`を返します。
+pub fn train_model_with_metrics(
+    params: &TrainingParams,
+    metrics_out: &Path,
+    interval: u64,
+    dev_corpus: Option<&Path>,
+) -> Result<Model, TrainError> {
+    let format = MetricsFormat::from_path(metrics_out);
+    let dev_corpus = dev_corpus
+        .map(|path| {
+            let rdr = File::open(path)?;
+            if params.partial_annotation {
+                Corpus::from_reader_partial(rdr)
+            } else {
+                Corpus::from_reader(rdr)
+            }
+        })
+        .transpose()?;
+    let corpus = open_corpus(params)?;
+
+    let mut wtr = BufWriter::new(File::create(metrics_out)?);
+    Metrics::write_header(&mut wtr, format)?;
+
+    let interval = interval.max(1);
+    let mut checkpoints: Vec<u64> = (interval..params.max_iter).step_by(interval as usize).collect();
+    checkpoints.push(params.max_iter);
+
+    let mut model = None;
+    for iteration in checkpoints {
+        let start = Instant::now();
+        let params = TrainingParams {
+            max_iter: iteration,
+            ..params.clone()
+        };
+        let mut checkpoint = build_trainer(&params)?.train_streaming(&corpus)?;
+        let train_secs = start.elapsed().as_secs_f64();
+        let dev = dev_corpus
+            .as_ref()
+            .map(|dev_corpus| trainer::evaluate(&mut checkpoint, dev_corpus, &[]))
+            .transpose()?;
+        let metrics = Metrics {
+            iteration,
+            num_features: checkpoint.num_features(),
+            dev,
+            train_secs,
+        };
+        metrics.write(&mut wtr, format)?;
+        // Flushes each line so that the curves can be plotted while training.
+        wtr.flush()?;
+        match dev {
+            Some(score) => println!(
+                "  iteration {iteration}: {} features, dev F1 = {}",
+                metrics.num_features,
+                score.f1(),
+            ),
+            None => println!("  iteration {iteration}: {} features", metrics.num_features),
+        }
+        model = Some(checkpoint);
+    }
+    Ok(model.expect("at least one checkpoint"))
+}
+
+/// 読み込み済みのコーパスを使ってモデルを訓練する
+///
+/// `params.corpus`は使用されません。交差検証などで、分割したコーパスごとに
+/// 訓練する場合に使用します。
+///
+/// # 引数
+///
+/// * `params` - 訓練パラメータ
+/// * `corpus` - 訓練用コーパス
+///
+/// # 戻り値
+///
+/// 訓練されたモデル
+///
+/// # エラー
+///
+/// ファイルの読み込みや訓練処理に失敗した場合、`TrainError`を返します。
+pub fn train_model_with_corpus(params: &TrainingParams, corpus: Corpus) -> Result<Model, TrainError> {
+    let model = build_trainer(params)?.train(corpus)?;
+    Ok(model)
+}
+
+/// 訓練パラメータからトレーナーを作成する
+fn build_trainer(params: &TrainingParams) -> Result<Trainer, TrainError> {
     let lexicon_rdr = File::open(&params.seed_lexicon)?;
     let char_prop_rdr = File::open(&params.char_def)?;
     let unk_handler_rdr = File::open(&params.seed_unk)?;
@@ -177,13 +452,10 @@ pub fn train_model(params: &TrainingParams) -> Result<Model, TrainError> {
     )?;
 
     let trainer = Trainer::new(config)?
+        .regularization(params.regularization)
         .regularization_cost(params.lambda)
         .max_iter(params.max_iter)
         .num_threads(params.num_threads);
 
-    let corpus_rdr = File::open(&params.corpus)?;
-    let corpus = Corpus::from_reader(corpus_rdr)?;
-
-    let model = trainer.train(corpus)?;
-    Ok(model)
+    Ok(trainer)
 }
\ No newline at end of file
@@ -96,7 +96,7 @@ pub fn run(args: Args) -> Result<(), TransmuteLegacyError> {
     }
 
     let reader = get_reader(&bincode_path)?;
-    let dictionary = unsafe { Dictionary::from_legacy_reader(reader)? };
+    let dictionary = Dictionary::from_legacy_reader(reader)?;
 
     let out_path = args.out_dir.join("system.dic");
     println!("Writing rkyv dictionary to: {}", out_path.display());
@@ -0,0 +1,75 @@
+//! ユーザー辞書のコンパイルモジュール
+//!
+//! このモジュールは、CSV形式のユーザー辞書をシステム辞書に合わせてコンパイルし、
+//! `Dictionary::load_user_dictionary`で読み込み時に付加できるバイナリを出力します。
+//! システム辞書を再シリアライズせずにユーザー辞書を差し替えることができます。
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use vibrato_rkyv::dictionary::UserDictionary;
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::{CacheStrategy, Dictionary};
+
+/// ユーザー辞書コンパイルコマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "userdic",
+    about = "A program to compile a user lexicon that can be attached to a system dictionary at load time."
+)]
+pub struct Args {
+    /// User lexicon file (user.csv).
+    #[clap(short = 'l', long)]
+    lexicon: PathBuf,
+
+    /// System dictionary to which the user lexicon is attached (plain or compressed).
+    #[clap(short = 's', long)]
+    sysdic: PathBuf,
+
+    /// File to which the compiled user lexicon is output (e.g., user.dicu).
+    #[clap(short = 'o', long)]
+    out: PathBuf,
+}
+
+/// ユーザー辞書のコンパイル中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum UserdicError {
+    /// 入出力エラー
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// 辞書の読み込みやコンパイルのエラー
+    #[error("Compiling the user lexicon failed: {0}")]
+    Vibrato(#[from] VibratoError),
+}
+
+/// ユーザー辞書コンパイルコマンドを実行する
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # 戻り値
+///
+/// 成功時は`Ok(())`
+///
+/// # エラー
+///
+/// ファイルの読み書きや、ユーザー辞書とシステム辞書の接続IDの不整合があった場合、
+/// `UserdicError`を返します。
+pub fn run(args: Args) -> Result<(), UserdicError> {
+    println!("Loading the system dictionary...");
+    let dict = Dictionary::from_compressed(&args.sysdic, CacheStrategy::GlobalCache)?;
+
+    println!("Compiling the user lexicon...");
+    let user = UserDictionary::from_reader(BufReader::new(File::open(&args.lexicon)?), &dict)?;
+
+    let mut wtr = BufWriter::new(File::create(&args.out)?);
+    user.write(&mut wtr)?;
+    wtr.flush()?;
+
+    println!("Successfully compiled the user lexicon to {}", args.out.display());
+    Ok(())
+}
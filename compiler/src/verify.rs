@@ -0,0 +1,184 @@
+//! 辞書の自己診断モジュール
+//!
+//! このモジュールは、コンパイル済みの辞書を検査するサブコマンドを提供します。
+//! データ構造の検証、接続IDの範囲の検査、組み込みの例文によるトークン化の確認を行い、
+//! 結果を`key\tvalue`形式で出力します。辞書の配布元がCIで使用することを想定しています。
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use vibrato_rkyv::dictionary::{CompressedHeader, LexType};
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::{Dictionary, LoadMode, Tokenizer};
+
+/// トークン化を確認する組み込みの例文
+///
+/// 漢字・仮名・英数字・記号・全角文字・絵文字・空白を含み、辞書に載っていない
+/// 文字種でも未知語処理で解析できることを確認します。
+const SAMPLE_SENTENCES: &[&str] = &[
+    "本日は晴天なり。",
+    "すもももももももものうち",
+    "東京都に住んでいます",
+    "アイスクリームを食べた",
+    "Hello, world! 123",
+    "ＡＢＣ１２３ｱｲｳ",
+    "今日は🍣を食べたい",
+    "  空白 を 含む 文  ",
+    "\u{200b}\u{fffd}\u{3000}",
+];
+
+/// 自己診断コマンドの引数
+#[derive(Parser, Debug)]
+#[clap(
+    name = "verify",
+    about = "Checks the integrity of a compiled dictionary and prints a machine-readable report."
+)]
+pub struct Args {
+    /// System dictionary (`*.dic` or `*.dic.zst`).
+    #[clap(short = 'i', long)]
+    sysdic: PathBuf,
+
+    /// Additional sentences to tokenize in the smoke test.
+    #[clap(short = 's', long)]
+    sentence: Vec<String>,
+}
+
+/// 自己診断中に発生する可能性のあるエラー
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    /// 入出力エラー
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Vibrato-rkyv ライブラリエラー
+    #[error(transparent)]
+    VibratoRkyv(#[from] VibratoError),
+
+    /// 検査に失敗した項目がある
+    #[error("{0} check(s) failed.")]
+    Failed(usize),
+}
+
+/// 自己診断コマンドを実行する
+///
+/// 各検査の結果を`key\tvalue`形式で1行ずつ出力します。最終行は`result\tok`または
+/// `result\tfailed`です。
+///
+/// # 引数
+///
+/// * `args` - コマンドの引数
+///
+/// # エラー
+///
+/// 辞書の読み込みに失敗した場合や、検査に失敗した項目がある場合に`VerifyError`を返します。
+pub fn run(args: Args) -> Result<(), VerifyError> {
+    let mut wtr = BufWriter::new(io::stdout().lock());
+    let mut num_failures = 0;
+
+    let dict = match load(&args.sysdic, &mut wtr) {
+        Ok(dict) => dict,
+        Err(e) => {
+            writeln!(wtr, "structure\tfailed\t{e}")?;
+            writeln!(wtr, "result\tfailed")?;
+            wtr.flush()?;
+            return Err(e);
+        }
+    };
+    writeln!(wtr, "structure\tok")?;
+
+    let report = dict.check_integrity();
+    writeln!(wtr, "num_left_ids\t{}", report.num_left_ids)?;
+    writeln!(wtr, "num_right_ids\t{}", report.num_right_ids)?;
+    writeln!(wtr, "num_system_words\t{}", report.num_system_words)?;
+    writeln!(wtr, "num_user_words\t{}", report.num_user_words)?;
+    writeln!(wtr, "num_unk_words\t{}", report.num_unk_words)?;
+    if report.has_bos_eos() {
+        writeln!(wtr, "bos_eos\tok")?;
+    } else {
+        writeln!(wtr, "bos_eos\tfailed")?;
+        num_failures += 1;
+    }
+    if report.invalid_words.is_empty() {
+        writeln!(wtr, "connection_ids\tok")?;
+    } else {
+        writeln!(wtr, "connection_ids\tfailed\t{}", report.invalid_words.len())?;
+        num_failures += 1;
+    }
+    for word in &report.invalid_words {
+        let lex_type = match word.word_idx.lex_type {
+            LexType::System => "system",
+            LexType::User => "user",
+            LexType::Unknown => "unk",
+        };
+        writeln!(
+            wtr,
+            "invalid_word\t{lex_type}\t{}\t{}\t{}",
+            word.word_idx.word_id, word.left_id, word.right_id,
+        )?;
+    }
+    writeln!(wtr, "unused_left_ids\t{}", report.unused_left_ids.len())?;
+    writeln!(wtr, "unused_right_ids\t{}", report.unused_right_ids.len())?;
+
+    // Out-of-range connection IDs can make tokenization panic.
+    if report.is_ok() {
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        let sentences = SAMPLE_SENTENCES.iter().copied().chain(args.sentence.iter().map(String::as_str));
+        for sentence in sentences {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                worker.reset_sentence(sentence);
+                worker.tokenize();
+                let surfaces: String = worker.token_iter().map(|t| t.surface().to_string()).collect();
+                (surfaces == sentence).then(|| worker.num_tokens())
+            }));
+            match result {
+                Ok(Some(num_tokens)) => writeln!(wtr, "smoke\tok\t{num_tokens}\t{sentence}")?,
+                Ok(None) => {
+                    writeln!(wtr, "smoke\tfailed\tmismatch\t{sentence}")?;
+                    num_failures += 1;
+                }
+                Err(_) => {
+                    writeln!(wtr, "smoke\tfailed\tpanic\t{sentence}")?;
+                    num_failures += 1;
+                    worker = tokenizer.new_worker();
+                }
+            }
+        }
+    } else {
+        writeln!(wtr, "smoke\tskipped")?;
+    }
+
+    if num_failures == 0 {
+        writeln!(wtr, "result\tok")?;
+        wtr.flush()?;
+        Ok(())
+    } else {
+        writeln!(wtr, "result\tfailed")?;
+        wtr.flush()?;
+        Err(VerifyError::Failed(num_failures))
+    }
+}
+
+/// 辞書を検証しながら読み込む
+///
+/// zstdで圧縮された辞書は、ヘッダに記録されたダイジェストを検証してから展開します。
+/// キャッシュやプルーフファイルは使用しません。
+fn load<W>(path: &Path, mut wtr: W) -> Result<Dictionary, VerifyError>
+where
+    W: Write,
+{
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        let mut rdr = BufReader::new(File::open(path)?);
+        if let Some(header) = CompressedHeader::read(&mut rdr)? {
+            header.verify(rdr)?;
+            writeln!(wtr, "compressed_sha256\tok")?;
+        }
+        let decoder = zstd::Decoder::new(File::open(path)?)?;
+        Ok(Dictionary::read(decoder)?)
+    } else {
+        Ok(Dictionary::from_path(path, LoadMode::Validate)?)
+    }
+}
@@ -2,18 +2,20 @@
 //!
 //! このバイナリは、訓練済みの形態素解析モデルの精度を評価します。
 //! テストコーパスと比較して、適合率（Precision）、再現率（Recall）、F1スコアを計算します。
+//! 品詞ごとの内訳、境界のみの評価値、頻出する誤りの組もあわせて出力します。
+//! `--bootstrap`を指定すると、F1スコアの信頼区間と、基準の辞書との差の有意性も出力します。
 
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
 use std::path::PathBuf;
 
-use csv_core::ReadFieldResult;
 use vibrato_rkyv::dictionary::Dictionary;
-use vibrato_rkyv::trainer::Corpus;
+use vibrato_rkyv::trainer::{self, Bootstrap, Comparison, ConfidenceInterval, Corpus, Evaluation, Score};
 use vibrato_rkyv::{CacheStrategy, Tokenizer};
 
 use clap::Parser;
+use serde::Serialize;
 
 /// コマンドライン引数
 #[derive(Parser, Debug)]
@@ -37,36 +39,44 @@ struct Args {
     /// If empty, all features are used.
     #[clap(long, value_delimiter(','))]
     feature_indices: Vec<usize>,
+
+    /// Index of the feature regarded as the part of speech in the per-POS breakdown.
+    #[clap(long, default_value = "0")]
+    pos_index: usize,
+
+    /// Number of the most frequent error pairs (reference vs system features) to output.
+    #[clap(long, default_value = "10")]
+    top_confusions: usize,
+
+    /// Outputs the results in JSON.
+    #[clap(long)]
+    json: bool,
+
+    /// Number of bootstrap resamples over sentences used to estimate the confidence
+    /// interval of F1.
+    #[clap(long)]
+    bootstrap: Option<usize>,
+
+    /// Confidence level of the bootstrap confidence intervals.
+    #[clap(long, default_value = "0.95")]
+    confidence: f64,
+
+    /// Random seed for the bootstrap resampling.
+    #[clap(long, default_value = "0")]
+    seed: u64,
+
+    /// Baseline system dictionary (in zstd) compared with the paired bootstrap test.
+    /// Requires --bootstrap.
+    #[clap(long, requires = "bootstrap")]
+    baseline_sysdic_in: Option<PathBuf>,
 }
 
-/// CSV行をパースして素性のベクトルに変換する
-///
-/// # 引数
-///
-/// * `row` - パース対象のCSV行文字列
-///
-/// # 戻り値
-///
-/// パースされた素性の文字列ベクトル
-fn parse_csv_row(row: &str) -> Vec<String> {
-    let mut features = vec![];
-    let mut rdr = csv_core::Reader::new();
-    let mut bytes = row.as_bytes();
-    let mut output = [0; 4096];
-    loop {
-        let (result, nin, nout) = rdr.read_field(bytes, &mut output);
-        let end = match result {
-            ReadFieldResult::InputEmpty => true,
-            ReadFieldResult::Field { .. } => false,
-            _ => unreachable!(),
-        };
-        features.push(std::str::from_utf8(&output[..nout]).unwrap().to_string());
-        if end {
-            break;
-        }
-        bytes = &bytes[nin..];
-    }
-    features
+/// ブートストラップ法による評価結果
+enum BootstrapResult {
+    /// F1スコアの信頼区間
+    Interval(ConfidenceInterval),
+    /// 基準の辞書との比較結果
+    Comparison(Comparison),
 }
 
 /// メイン関数
@@ -79,74 +89,227 @@ fn parse_csv_row(row: &str) -> Vec<String> {
 /// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    cli_logger::init();
 
     eprintln!("Loading the dictionary...");
     let dict = Dictionary::from_zstd(args.sysdic_in, CacheStrategy::GlobalCache)?;
 
     let tokenizer = Tokenizer::new(dict).max_grouping_len(args.max_grouping_len.unwrap_or(0));
-    let mut worker = tokenizer.new_worker();
 
     eprintln!("Tokenizing...");
 
     let rdr = File::open(args.test_in)?;
     let corpus = Corpus::from_reader(rdr)?;
 
-    let mut num_ref = 0;
-    let mut num_sys = 0;
-    let mut num_cor = 0;
-    for example in corpus.iter() {
-        let mut input_str = String::new();
-        let mut refs = HashSet::new();
-        let mut syss = HashSet::new();
-        let mut start = 0;
-        for token in example.tokens() {
-            input_str.push_str(token.surface());
-            let len = token.surface().chars().count();
-            let features = parse_csv_row(token.feature());
-            if args.feature_indices.is_empty() {
-                refs.insert((start..start + len, features));
-            } else {
-                let mut features_chose = vec![];
-                for &i in &args.feature_indices {
-                    features_chose.push(
-                        features
-                            .get(i)
-                            .map_or_else(|| "*".to_string(), |x| x.to_string()),
-                    );
-                }
-                refs.insert((start..start + len, features_chose));
-            }
-            start += len;
-        }
-        worker.reset_sentence(input_str);
-        worker.tokenize();
-        for token in worker.token_iter() {
-            let features = parse_csv_row(token.feature());
-            if args.feature_indices.is_empty() {
-                syss.insert((token.range_char(), features));
-            } else {
-                let mut features_chose = vec![];
-                for &i in &args.feature_indices {
-                    features_chose.push(
-                        features
-                            .get(i)
-                            .map_or_else(|| "*".to_string(), |x| x.to_string()),
-                    );
+    let result = trainer::evaluate_tokenizer_detailed(
+        &tokenizer,
+        &corpus,
+        &args.feature_indices,
+        args.pos_index,
+    );
+
+    let bootstrap = match args.bootstrap {
+        Some(num_samples) => {
+            eprintln!("Resampling...");
+            let bootstrap = Bootstrap::new()
+                .num_samples(num_samples)
+                .confidence(args.confidence)
+                .seed(args.seed);
+            Some(match &args.baseline_sysdic_in {
+                Some(path) => {
+                    let dict = Dictionary::from_zstd(path, CacheStrategy::GlobalCache)?;
+                    let baseline = Tokenizer::new(dict)
+                        .max_grouping_len(args.max_grouping_len.unwrap_or(0));
+                    BootstrapResult::Comparison(bootstrap.compare(
+                        &baseline,
+                        &tokenizer,
+                        &corpus,
+                        &args.feature_indices,
+                    )?)
                 }
-                syss.insert((token.range_char(), features_chose));
-            }
+                None => BootstrapResult::Interval(bootstrap.interval(
+                    &tokenizer,
+                    &corpus,
+                    &args.feature_indices,
+                )?),
+            })
         }
-        num_ref += refs.len();
-        num_sys += syss.len();
-        num_cor += refs.intersection(&syss).count();
+        None => None,
+    };
+
+    if args.json {
+        let report = Report::new(&result, args.top_confusions, bootstrap.as_ref());
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &report)?;
+        println!();
+        return Ok(());
     }
 
-    let precision = num_cor as f64 / num_sys as f64;
-    let recall = num_cor as f64 / num_ref as f64;
-    let f1 = 2.0 * precision * recall / (precision + recall);
-    println!("Precision = {precision}");
-    println!("Recall = {recall}");
-    println!("F1 = {f1}");
+    println!("Precision = {}", result.total.precision());
+    println!("Recall = {}", result.total.recall());
+    println!("F1 = {}", result.total.f1());
+
+    println!();
+    println!("[Boundary]");
+    println!("Precision = {}", result.boundary.precision());
+    println!("Recall = {}", result.boundary.recall());
+    println!("F1 = {}", result.boundary.f1());
+
+    println!();
+    println!("[Per POS]");
+    println!("POS\tRef\tSys\tCor\tPrecision\tRecall\tF1");
+    for (pos, score) in &result.per_pos {
+        println!(
+            "{pos}\t{}\t{}\t{}\t{}\t{}\t{}",
+            score.num_ref,
+            score.num_sys,
+            score.num_cor,
+            score.precision(),
+            score.recall(),
+            score.f1()
+        );
+    }
+
+    println!();
+    println!("[Top confusions]");
+    println!("Count\tReference\tSystem");
+    for (r, s, count) in result.top_confusions(args.top_confusions) {
+        println!("{count}\t{r}\t{s}");
+    }
+
+    match bootstrap {
+        Some(BootstrapResult::Interval(interval)) => {
+            println!();
+            println!("[Bootstrap]");
+            println!("F1 = {} [{}, {}]", interval.f1, interval.lower, interval.upper);
+        }
+        Some(BootstrapResult::Comparison(comparison)) => {
+            println!();
+            println!("[Bootstrap]");
+            let Comparison { baseline, candidate, difference, p_value } = comparison;
+            println!("Baseline F1 = {} [{}, {}]", baseline.f1, baseline.lower, baseline.upper);
+            println!("F1 = {} [{}, {}]", candidate.f1, candidate.lower, candidate.upper);
+            println!("Difference = {} [{}, {}]", difference.f1, difference.lower, difference.upper);
+            println!("p-value = {p_value}");
+        }
+        None => {}
+    }
 
     Ok(())
 }
+
+/// JSON形式で出力する評価結果
+#[derive(Serialize)]
+struct Report<'a> {
+    total: ScoreReport,
+    boundary: ScoreReport,
+    per_pos: BTreeMap<&'a str, ScoreReport>,
+    confusions: Vec<ConfusionReport<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bootstrap: Option<BootstrapReport>,
+}
+
+impl<'a> Report<'a> {
+    /// 評価結果から出力する内容を作成する
+    ///
+    /// # 引数
+    ///
+    /// * `result` - 評価結果
+    /// * `top_confusions` - 出力する誤りの組の最大数
+    /// * `bootstrap` - ブートストラップ法による評価結果
+    fn new(
+        result: &'a Evaluation,
+        top_confusions: usize,
+        bootstrap: Option<&BootstrapResult>,
+    ) -> Self {
+        Self {
+            total: ScoreReport::from(&result.total),
+            boundary: ScoreReport::from(&result.boundary),
+            per_pos: result
+                .per_pos
+                .iter()
+                .map(|(pos, score)| (pos.as_str(), ScoreReport::from(score)))
+                .collect(),
+            confusions: result
+                .top_confusions(top_confusions)
+                .into_iter()
+                .map(|(reference, system, count)| ConfusionReport { reference, system, count })
+                .collect(),
+            bootstrap: bootstrap.map(|bootstrap| match bootstrap {
+                BootstrapResult::Interval(interval) => BootstrapReport::Interval {
+                    f1: IntervalReport::from(interval),
+                },
+                BootstrapResult::Comparison(comparison) => BootstrapReport::Comparison {
+                    baseline_f1: IntervalReport::from(&comparison.baseline),
+                    f1: IntervalReport::from(&comparison.candidate),
+                    difference: IntervalReport::from(&comparison.difference),
+                    p_value: comparison.p_value,
+                },
+            }),
+        }
+    }
+}
+
+/// JSON形式で出力する評価値
+#[derive(Serialize)]
+struct ScoreReport {
+    num_ref: usize,
+    num_sys: usize,
+    num_cor: usize,
+    precision: f64,
+    recall: f64,
+    f1: f64,
+}
+
+impl From<&Score> for ScoreReport {
+    fn from(score: &Score) -> Self {
+        Self {
+            num_ref: score.num_ref,
+            num_sys: score.num_sys,
+            num_cor: score.num_cor,
+            precision: score.precision(),
+            recall: score.recall(),
+            f1: score.f1(),
+        }
+    }
+}
+
+/// JSON形式で出力する誤りの組
+#[derive(Serialize)]
+struct ConfusionReport<'a> {
+    reference: &'a str,
+    system: &'a str,
+    count: usize,
+}
+
+/// JSON形式で出力する信頼区間
+#[derive(Serialize)]
+struct IntervalReport {
+    value: f64,
+    lower: f64,
+    upper: f64,
+}
+
+impl From<&ConfidenceInterval> for IntervalReport {
+    fn from(interval: &ConfidenceInterval) -> Self {
+        Self {
+            value: interval.f1,
+            lower: interval.lower,
+            upper: interval.upper,
+        }
+    }
+}
+
+/// JSON形式で出力するブートストラップ法による評価結果
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BootstrapReport {
+    /// F1スコアの信頼区間
+    Interval { f1: IntervalReport },
+    /// 基準の辞書との比較結果
+    Comparison {
+        baseline_f1: IntervalReport,
+        f1: IntervalReport,
+        difference: IntervalReport,
+        p_value: f64,
+    },
+}
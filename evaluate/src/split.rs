@@ -6,7 +6,6 @@
 use std::path::PathBuf;
 
 use clap::{error::ErrorKind, CommandFactory, Parser};
-use rand::seq::SliceRandom;
 use vibrato_rkyv::trainer::Corpus;
 
 /// 比率文字列をパースする
@@ -57,6 +56,10 @@ struct Args {
     /// Ratio of testing data. (0.0 to 1.0)
     #[clap(long, default_value = "0.1", value_parser = parse_ratio)]
     test_ratio: f64,
+
+    /// Seed for shuffling. If not specified, a random seed is used.
+    #[clap(long)]
+    seed: Option<u64>,
 }
 
 /// メイン関数
@@ -69,6 +72,7 @@ struct Args {
 /// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    cli_logger::init();
 
     let rdr = std::fs::File::open(args.corpus_in)?;
     let mut corpus = Corpus::from_reader(rdr)?;
@@ -83,8 +87,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .exit();
     }
 
-    let mut rng = rand::thread_rng();
-    corpus.shuffle(&mut rng);
+    corpus.shuffle(args.seed.unwrap_or_else(rand::random));
 
     let mut train_wtr = std::fs::File::create(args.train_out)?;
     let mut valid_wtr = std::fs::File::create(args.valid_out)?;
@@ -5,12 +5,9 @@
 
 use std::error::Error;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
 use std::path::PathBuf;
 
-use vibrato_rkyv::dictionary::{DictionaryInner, MODEL_MAGIC};
-use vibrato_rkyv::dictionary::ArchivedDictionaryInner;
-use rkyv::{access, deserialize, rancor::Error as RError};
+use vibrato_rkyv::dictionary::{DictionaryInner, WriteOptions};
 
 use clap::Parser;
 
@@ -33,6 +30,14 @@ struct Args {
     /// File to which the edited dictionary is output (in zstd).
     #[clap(short = 'o', long)]
     sysdic_out: PathBuf,
+
+    /// Zstandard compression level of the output dictionary.
+    #[clap(long, default_value = "19")]
+    compression_level: i32,
+
+    /// Number of worker threads for compression. If 0, compression runs in the main thread.
+    #[clap(long, default_value = "0")]
+    compression_workers: u32,
 }
 
 /// メイン関数
@@ -45,68 +50,32 @@ struct Args {
 /// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    cli_logger::init();
 
     eprintln!("Loading and deserializing the dictionary...");
-    let mut reader = zstd::Decoder::new(File::open(args.sysdic_in)?)?;
-    let mut magic = [0; MODEL_MAGIC.len()];
-    reader.read_exact(&mut magic)?;
-    if magic != MODEL_MAGIC {
-        return Err("The magic number of the input model mismatches.".into());
-    }
-    let mut dict_bytes = vec![];
-    reader.read_to_end(&mut dict_bytes)?;
-
-    let archived = access::<ArchivedDictionaryInner, RError>(&dict_bytes)?;
-    let mut dict_inner: DictionaryInner = deserialize::<_, RError>(archived)?;
+    let dict_inner = DictionaryInner::read_zstd(File::open(args.sysdic_in)?)?;
 
     eprintln!("Loading and doing the mapping...");
     let lmap = {
         let mut filename = args.mapping_in.clone();
         filename.set_extension("lmap");
-        load_mapping(File::open(filename)?)?
+        File::open(filename)?
     };
     let rmap = {
         let mut filename = args.mapping_in.clone();
         filename.set_extension("rmap");
-        load_mapping(File::open(filename)?)?
+        File::open(filename)?
     };
-
-    dict_inner = dict_inner.map_connection_ids_from_iter(lmap, rmap)?;
+    let dict_inner = dict_inner.map_connection_ids_from_readers(lmap, rmap)?;
 
     eprintln!(
         "Writing the mapped system dictionary...: {:?}",
         &args.sysdic_out
     );
-    let mut f = zstd::Encoder::new(File::create(args.sysdic_out)?, 19)?;
-
-    dict_inner.write(&mut f)?;
-    f.finish()?;
+    let options = WriteOptions::new()
+        .level(args.compression_level)
+        .workers(args.compression_workers);
+    dict_inner.write_zstd_with_options(File::create(args.sysdic_out)?, &options, None)?;
 
     Ok(())
 }
-
-/// マッピングファイルをロードする
-///
-/// タブ区切りファイルから接続IDマッピングを読み込みます。
-///
-/// # 引数
-///
-/// * `rdr` - マッピングファイルのリーダー
-///
-/// # 戻り値
-///
-/// 読み込まれた接続IDのベクトル、またはエラー
-fn load_mapping<R>(rdr: R) -> Result<Vec<u16>, Box<dyn Error>>
-where
-    R: Read,
-{
-    let reader = BufReader::new(rdr);
-    let lines = reader.lines();
-    let mut ids = vec![];
-    for line in lines {
-        let line = line?;
-        let cols: Vec<_> = line.split('\t').collect();
-        ids.push(cols[0].parse()?);
-    }
-    Ok(ids)
-}
@@ -0,0 +1,164 @@
+//! gRPCでの形態素解析サービス
+//!
+//! `proto/vibrato.proto`で定義されたサービスを実装します。解析はtokioのブロッキングスレッドで
+//! 行い、各スレッドはHTTPのスレッドと同じく自身のワーカーを保持します。
+//! `TokenizeStream`は受け取った順に結果を返すため、音声認識の結果などを逐次解析できます。
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use vibrato_rkyv::dictionary::LexType;
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::token::TokenBuf;
+
+use crate::{State, ThreadWorker};
+
+/// `proto/vibrato.proto`から生成された型
+mod pb {
+    tonic::include_proto!("vibrato");
+}
+
+use pb::tokenizer_server::{Tokenizer, TokenizerServer};
+
+thread_local! {
+    /// ブロッキングスレッドごとのワーカー
+    static WORKER: RefCell<Option<ThreadWorker>> = const { RefCell::new(None) };
+}
+
+/// トークンをgRPCのメッセージに変換する
+fn convert_token(token: TokenBuf) -> pb::Token {
+    let lex_type = match token.lex_type {
+        LexType::System => "system",
+        LexType::User => "user",
+        LexType::Unknown => "unknown",
+    };
+    pb::Token {
+        surface: token.surface,
+        feature: token.feature,
+        start_char: token.range_char.start as u32,
+        end_char: token.range_char.end as u32,
+        start_byte: token.range_byte.start as u32,
+        end_byte: token.range_byte.end as u32,
+        lex_type: lex_type.to_string(),
+        left_id: token.left_id.into(),
+        right_id: token.right_id.into(),
+        word_cost: token.word_cost.into(),
+        total_cost: token.total_cost,
+    }
+}
+
+/// ブロッキングスレッドで1つのリクエストを解析する
+async fn analyze(
+    state: Arc<State>,
+    request: pb::TokenizeRequest,
+) -> Result<pb::TokenizeResponse, Status> {
+    let n = usize::try_from(request.nbest).unwrap_or(usize::MAX);
+    if state.args.max_nbest < n {
+        let msg = format!("nbest must be at most {}.", state.args.max_nbest);
+        return Err(Status::invalid_argument(msg));
+    }
+    tokio::task::spawn_blocking(move || {
+        WORKER.with_borrow_mut(|worker| {
+            let worker = worker.get_or_insert_with(|| ThreadWorker::new(&state));
+            if n <= 1 {
+                let tokens = worker.tokenize(&state, request.text);
+                return Ok(pb::TokenizeResponse {
+                    tokens: tokens.into_iter().map(convert_token).collect(),
+                    paths: vec![],
+                });
+            }
+            let paths: Vec<_> = worker
+                .tokenize_nbest(&state, request.text, n)
+                .map_err(|e| match e {
+                    VibratoError::ResourceLimit(e) => Status::resource_exhausted(e.to_string()),
+                    e => Status::internal(e.to_string()),
+                })?
+                .into_iter()
+                .map(|path| pb::Path {
+                    cost: path.cost,
+                    tokens: path.tokens.into_iter().map(convert_token).collect(),
+                })
+                .collect();
+            Ok(pb::TokenizeResponse {
+                tokens: paths.first().map(|path| path.tokens.clone()).unwrap_or_default(),
+                paths,
+            })
+        })
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+}
+
+/// gRPCサービスの実装
+struct Service {
+    state: Arc<State>,
+}
+
+#[tonic::async_trait]
+impl Tokenizer for Service {
+    async fn tokenize(
+        &self,
+        request: Request<pb::TokenizeRequest>,
+    ) -> Result<Response<pb::TokenizeResponse>, Status> {
+        analyze(Arc::clone(&self.state), request.into_inner()).await.map(Response::new)
+    }
+
+    type TokenizeStreamStream = ReceiverStream<Result<pb::TokenizeResponse, Status>>;
+
+    async fn tokenize_stream(
+        &self,
+        request: Request<Streaming<pb::TokenizeRequest>>,
+    ) -> Result<Response<Self::TokenizeStreamStream>, Status> {
+        let mut inbound = request.into_inner();
+        let state = Arc::clone(&self.state);
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(request) = inbound.next().await {
+                let response = match request {
+                    Ok(request) => analyze(Arc::clone(&state), request).await,
+                    Err(status) => Err(status),
+                };
+                // An error status terminates the stream.
+                let failed = response.is_err();
+                if tx.send(response).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_dictionary_info(
+        &self,
+        _request: Request<pb::GetDictionaryInfoRequest>,
+    ) -> Result<Response<pb::DictionaryInfo>, Status> {
+        let current = self.state.current.read().unwrap();
+        Ok(Response::new(pb::DictionaryInfo {
+            generation: current.generation,
+            metadata: current.metadata.iter().cloned().collect(),
+        }))
+    }
+}
+
+/// 指定されたアドレスでgRPCサービスを提供する
+///
+/// サービスが停止するまで戻りません。
+///
+/// # エラー
+///
+/// ランタイムの作成やアドレスへのバインドに失敗した場合、エラーを返します。
+pub fn serve(addr: SocketAddr, state: Arc<State>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(
+        tonic::transport::Server::builder()
+            .add_service(TokenizerServer::new(Service { state }))
+            .serve(addr),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,578 @@
+//! 形態素解析をHTTPで提供するサーバー
+//!
+//! このバイナリは、起動時に読み込んだ辞書を使って、JSONで受け取ったテキストを
+//! 形態素解析し、結果をJSONで返します。CPUコアごとにスレッドを起動し、
+//! 各スレッドが自身のワーカーを保持するため、リクエスト間でロックを取り合いません。
+//!
+//! # エンドポイント
+//!
+//! | メソッド | パス | 内容 |
+//! |---|---|---|
+//! | `POST` | `/tokenize` | `{"text": "..."}`を解析し、`{"tokens": [...]}`を返します |
+//! | `POST` | `/nbest` | `{"text": "...", "n": 3}`を解析し、`{"paths": [{"cost": ..., "tokens": [...]}]}`を返します |
+//! | `POST` | `/reload` | 辞書を読み込み直します。処理中のリクエストは古い辞書で完了します。`--reload-token`を指定した場合のみ有効です |
+//! | `GET` | `/info` | 辞書に埋め込まれたメタデータを返します |
+//! | `GET` | `/health` | `ok`を返します |
+//!
+//! `grpc`フィーチャーを有効にしてビルドすると、`--grpc-addr`で`proto/vibrato.proto`の
+//! gRPCサービスも同時に提供できます。
+//!
+//! `/reload`は、`Authorization: Bearer <トークン>`ヘッダで`--reload-token`と同じトークンを
+//! 送ったリクエストのみを受け付けます。`/nbest`のラティスとA*探索に使用するメモリは
+//! `--max-nbest-bytes`で制限され、上限を超える入力には`413`を返します。
+
+use std::error::Error;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+use vibrato_rkyv::dictionary::PresetDictionaryKind;
+use vibrato_rkyv::errors::VibratoError;
+use vibrato_rkyv::token::TokenBuf;
+use vibrato_rkyv::tokenizer::TokenizerOptions;
+use vibrato_rkyv::tokenizer::worker::Worker;
+use vibrato_rkyv::{CacheStrategy, Dictionary, LoadMode, Tokenizer};
+
+use clap::Parser;
+
+#[cfg(feature = "grpc")]
+mod grpc;
+
+/// コマンドライン引数
+#[derive(Parser, Debug)]
+#[clap(name = "server", about = "Serves morphological analysis over HTTP")]
+struct Args {
+    /// System dictionary. Files ending with `.zst` are decompressed into the user cache.
+    #[clap(short = 'i', long, conflicts_with = "preset", required_unless_present = "preset")]
+    sysdic: Option<PathBuf>,
+
+    /// Preset dictionary downloaded into `--cache-dir` (e.g., mecab-ipadic, unidic-cwj).
+    #[clap(long, value_parser = parse_preset)]
+    preset: Option<PresetDictionaryKind>,
+
+    /// Directory in which preset dictionaries are stored.
+    #[clap(long, default_value = ".cache/vibrato-rkyv")]
+    cache_dir: PathBuf,
+
+    /// Address to listen on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Address on which the gRPC service listens (e.g., 127.0.0.1:50051).
+    #[cfg(feature = "grpc")]
+    #[clap(long)]
+    grpc_addr: Option<std::net::SocketAddr>,
+
+    /// Number of worker threads. Defaults to the number of available CPU cores.
+    #[clap(long)]
+    threads: Option<usize>,
+
+    /// Maximum size of a request body in bytes.
+    #[clap(long, default_value = "1048576")]
+    max_body_bytes: u64,
+
+    /// Maximum number of paths returned by /nbest.
+    #[clap(long, default_value = "16")]
+    max_nbest: usize,
+
+    /// Approximate maximum memory in bytes used by /nbest, counting the lattice and the A* search
+    /// state. Inputs that need more are rejected with 413.
+    #[clap(long, default_value = "67108864")]
+    max_nbest_bytes: usize,
+
+    /// Enables /reload for requests with the header `Authorization: Bearer <TOKEN>`.
+    /// /reload is disabled if this is not specified.
+    #[clap(long, value_name = "TOKEN")]
+    reload_token: Option<String>,
+
+    /// Ignores white spaces in input strings.
+    #[clap(short = 'S', long)]
+    ignore_space: bool,
+
+    /// Maximum length of unknown words.
+    #[clap(short = 'M', long)]
+    max_grouping_len: Option<usize>,
+
+    /// JSON file of tokenizer options (e.g., {"ignore_space": true, "tie_break": "LexTypePriority"}).
+    /// `--ignore-space` and `--max-grouping-len` override the values in the file.
+    #[clap(long)]
+    tokenizer_options: Option<PathBuf>,
+}
+
+/// プリセット辞書の名前をパースする
+fn parse_preset(name: &str) -> Result<PresetDictionaryKind, String> {
+    PresetDictionaryKind::ALL
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = PresetDictionaryKind::ALL.iter().map(|kind| kind.name()).collect();
+            format!("unknown preset {name}, expected one of: {}", names.join(", "))
+        })
+}
+
+/// `/tokenize`と`/nbest`のリクエスト
+#[derive(Deserialize)]
+struct TokenizeRequest {
+    /// 解析するテキスト
+    text: String,
+    /// 返すパスの最大数（`/nbest`のみ）
+    #[serde(default = "default_nbest")]
+    n: usize,
+}
+
+const fn default_nbest() -> usize {
+    1
+}
+
+/// `/tokenize`のレスポンス
+#[derive(Serialize)]
+struct TokenizeResponse {
+    tokens: Vec<TokenBuf>,
+}
+
+/// `/nbest`の各パス
+#[derive(Serialize)]
+struct NbestPath {
+    cost: i32,
+    tokens: Vec<TokenBuf>,
+}
+
+/// `/nbest`のレスポンス
+#[derive(Serialize)]
+struct NbestResponse {
+    paths: Vec<NbestPath>,
+}
+
+/// エラーレスポンス
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// すべてのスレッドで共有される状態
+struct State {
+    args: Args,
+    /// 現在読み込まれている辞書
+    current: RwLock<Loaded>,
+}
+
+/// 読み込まれた辞書とトークナイザー
+struct Loaded {
+    /// 世代番号
+    ///
+    /// 辞書を読み込み直すたびに増え、各スレッドは次のリクエストで自身のワーカーを作り直します。
+    generation: u64,
+    tokenizer: Tokenizer,
+    /// 辞書に埋め込まれたメタデータ
+    metadata: Vec<(String, String)>,
+}
+
+impl State {
+    /// 引数で指定された辞書を読み込み、トークナイザーを作成する
+    fn load(args: &Args, generation: u64) -> Result<Loaded, Box<dyn Error + Send + Sync>> {
+        let dict = match (&args.sysdic, args.preset) {
+            (Some(path), _) if path.extension().is_some_and(|ext| ext == "zst") => {
+                Dictionary::from_zstd(path, CacheStrategy::GlobalCache)?
+            }
+            (Some(path), _) => Dictionary::from_path(path, LoadMode::TrustCache)?,
+            (None, Some(preset)) => Dictionary::from_preset_with_download(preset, &args.cache_dir)?,
+            (None, None) => unreachable!("clap requires either --sysdic or --preset"),
+        };
+        let metadata = dict
+            .metadata()
+            .into_iter()
+            .flat_map(|metadata| metadata.iter())
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut options = match &args.tokenizer_options {
+            Some(path) => serde_json::from_reader(std::fs::File::open(path)?)?,
+            None => TokenizerOptions::new(),
+        };
+        if args.ignore_space {
+            options = options.ignore_space(true);
+        }
+        if let Some(max_grouping_len) = args.max_grouping_len {
+            options = options.max_grouping_len(max_grouping_len);
+        }
+        let tokenizer = Tokenizer::with_options(dict, &options)?;
+        Ok(Loaded {
+            generation,
+            tokenizer,
+            metadata,
+        })
+    }
+
+    /// 辞書を読み込み直し、新しい世代のトークナイザーに差し替える
+    ///
+    /// 読み込みに失敗した場合は、現在のトークナイザーを使い続けます。
+    fn reload(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let mut loaded = Self::load(&self.args, 0)?;
+        let mut current = self.current.write().unwrap();
+        loaded.generation = current.generation + 1;
+        *current = loaded;
+        Ok(current.generation)
+    }
+
+    /// 現在の世代番号とトークナイザーを返す
+    fn snapshot(&self) -> (u64, Tokenizer) {
+        let current = self.current.read().unwrap();
+        (current.generation, current.tokenizer.clone())
+    }
+}
+
+/// 各スレッドが保持するワーカー
+struct ThreadWorker {
+    generation: u64,
+    worker: Worker,
+}
+
+impl ThreadWorker {
+    fn new(state: &State) -> Self {
+        let (generation, tokenizer) = state.snapshot();
+        Self {
+            generation,
+            worker: tokenizer
+                .new_worker()
+                .with_nbest_budget(state.args.max_nbest_bytes),
+        }
+    }
+
+    /// 辞書が読み込み直されていれば、新しいトークナイザーでワーカーを作り直す
+    fn refresh(&mut self, state: &State) {
+        if state.current.read().unwrap().generation != self.generation {
+            *self = Self::new(state);
+        }
+    }
+
+    /// 最新の辞書でテキストを解析し、最良のトークン列を返す
+    fn tokenize(&mut self, state: &State, text: String) -> Vec<TokenBuf> {
+        self.refresh(state);
+        let worker = &mut self.worker;
+        worker.reset_sentence(text);
+        worker.tokenize();
+        worker.token_iter().map(|t| t.to_buf()).collect()
+    }
+
+    /// 最新の辞書でテキストを解析し、コストの小さい順に最大`n`個のパスを返す
+    ///
+    /// ラティスの大きさが`--max-nbest-bytes`を超えた場合は
+    /// [`VibratoError::ResourceLimit`]を返します。
+    fn tokenize_nbest(
+        &mut self,
+        state: &State,
+        text: String,
+        n: usize,
+    ) -> Result<Vec<NbestPath>, VibratoError> {
+        self.refresh(state);
+        let worker = &mut self.worker;
+        worker.reset_sentence(text);
+        worker.try_tokenize_nbest(n)?;
+        Ok((0..worker.num_nbest_paths())
+            .map(|i| NbestPath {
+                cost: worker.path_cost(i).unwrap(),
+                tokens: worker.nbest_token_iter(i).unwrap().map(|t| t.to_buf()).collect(),
+            })
+            .collect())
+    }
+}
+
+/// レスポンスの型
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+/// JSONのレスポンスを作成する
+fn json_response<T: Serialize>(status: u16, body: &T) -> HttpResponse {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    Response::from_data(serde_json::to_vec(body).unwrap())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+/// エラーのレスポンスを作成する
+fn error_response(status: u16, msg: impl Into<String>) -> HttpResponse {
+    json_response(status, &ErrorResponse { error: msg.into() })
+}
+
+/// リクエストボディをJSONとして読み込む
+///
+/// 失敗した場合は、ステータスコードとエラーメッセージを返します。
+fn read_json<T: for<'de> Deserialize<'de>>(
+    request: &mut Request,
+    max_body_bytes: u64,
+) -> Result<T, (u16, String)> {
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(max_body_bytes + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| (400, e.to_string()))?;
+    if body.len() as u64 > max_body_bytes {
+        return Err((413, "The request body is too large.".to_string()));
+    }
+    serde_json::from_slice(&body).map_err(|e| (400, e.to_string()))
+}
+
+/// `/reload`のリクエストが`--reload-token`で認可されているかを返す
+///
+/// 失敗した場合は、ステータスコードとエラーメッセージを返します。
+fn authorize_reload(request: &Request, reload_token: Option<&str>) -> Result<(), (u16, String)> {
+    let Some(reload_token) = reload_token else {
+        let msg = "/reload is disabled. Start the server with --reload-token.";
+        return Err((403, msg.to_string()));
+    };
+    let authorized = request
+        .headers()
+        .iter()
+        .filter(|header| header.field.equiv("Authorization"))
+        .filter_map(|header| header.value.as_str().strip_prefix("Bearer "))
+        .any(|token| constant_time_eq(token.as_bytes(), reload_token.as_bytes()));
+    if authorized {
+        Ok(())
+    } else {
+        Err((401, "A valid bearer token is required.".to_string()))
+    }
+}
+
+/// 2つのバイト列が等しいかを返す
+///
+/// トークンの推測に処理時間を使われないよう、長さが等しい場合は内容によらず
+/// すべてのバイトを比較します。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+/// 1つのリクエストを処理し、レスポンスを返す
+fn handle(
+    request: &mut Request,
+    state: &State,
+    worker: &mut ThreadWorker,
+) -> HttpResponse {
+    let args = &state.args;
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    // The query string does not affect routing.
+    let path = url.split_once('?').map_or(url.as_str(), |(path, _)| path);
+    match (&method, path) {
+        (Method::Post, "/tokenize") => {
+            let req: TokenizeRequest = match read_json(request, args.max_body_bytes) {
+                Ok(req) => req,
+                Err((status, msg)) => return error_response(status, msg),
+            };
+            let tokens = worker.tokenize(state, req.text);
+            json_response(200, &TokenizeResponse { tokens })
+        }
+        (Method::Post, "/nbest") => {
+            let req: TokenizeRequest = match read_json(request, args.max_body_bytes) {
+                Ok(req) => req,
+                Err((status, msg)) => return error_response(status, msg),
+            };
+            if req.n == 0 || args.max_nbest < req.n {
+                return error_response(400, format!("n must be in 1..={}.", args.max_nbest));
+            }
+            match worker.tokenize_nbest(state, req.text, req.n) {
+                Ok(paths) => json_response(200, &NbestResponse { paths }),
+                Err(VibratoError::ResourceLimit(e)) => error_response(
+                    413,
+                    format!("The text is too long for the n-best analysis: {e}"),
+                ),
+                Err(e) => error_response(500, e.to_string()),
+            }
+        }
+        (Method::Post, "/reload") => {
+            if let Err((status, msg)) = authorize_reload(request, args.reload_token.as_deref()) {
+                return error_response(status, msg);
+            }
+            match state.reload() {
+                Ok(generation) => {
+                    eprintln!("Reloaded the dictionary (generation {generation})");
+                    worker.refresh(state);
+                    json_response(200, &serde_json::json!({ "generation": generation }))
+                }
+                Err(e) => error_response(500, format!("Failed to reload the dictionary: {e}")),
+            }
+        }
+        (Method::Get, "/info") => {
+            let current = state.current.read().unwrap();
+            let metadata: serde_json::Map<_, _> = current
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_str().into()))
+                .collect();
+            json_response(
+                200,
+                &serde_json::json!({ "generation": current.generation, "metadata": metadata }),
+            )
+        }
+        (Method::Get, "/health") => Response::from_string("ok").with_status_code(200),
+        _ => error_response(404, "Not found."),
+    }
+}
+
+/// メイン関数
+///
+/// 辞書をロードし、CPUコアの数だけスレッドを起動してリクエストを待ち受けます。
+///
+/// # 戻り値
+///
+/// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+    cli_logger::init();
+
+    eprintln!("Loading the dictionary...");
+    let loaded = State::load(&args, 0)?;
+    let num_threads = args
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let server = Arc::new(Server::http(&args.addr)?);
+    eprintln!("Listening on http://{} with {num_threads} threads", args.addr);
+    let state = Arc::new(State {
+        args,
+        current: RwLock::new(loaded),
+    });
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = state.args.grpc_addr {
+        let state = Arc::clone(&state);
+        eprintln!("Serving gRPC on {addr}");
+        thread::spawn(move || {
+            if let Err(e) = grpc::serve(addr, state) {
+                eprintln!("The gRPC service stopped: {e}");
+            }
+        });
+    }
+
+    let handles: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                let mut worker = ThreadWorker::new(&state);
+                while let Ok(mut request) = server.recv() {
+                    let response = handle(&mut request, &state, &mut worker);
+                    if let Err(e) = request.respond(response) {
+                        eprintln!("Failed to send a response: {e}");
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().expect("worker thread panicked");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tiny_http::TestRequest;
+    use vibrato_rkyv::SystemDictionaryBuilder;
+
+    /// テスト用の辞書と引数で状態を作成する
+    fn test_state(extra_args: &[&str]) -> State {
+        let mut argv = vec!["server", "--sysdic", "system.dic"];
+        argv.extend_from_slice(extra_args);
+        let args = Args::try_parse_from(argv).unwrap();
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        State {
+            args,
+            current: RwLock::new(Loaded {
+                generation: 0,
+                tokenizer: Tokenizer::from_inner(dict),
+                metadata: vec![],
+            }),
+        }
+    }
+
+    /// リクエストを処理し、レスポンスのステータスコードを返す
+    fn status(state: &State, request: TestRequest) -> u16 {
+        let mut worker = ThreadWorker::new(state);
+        let mut request = Request::from(request);
+        handle(&mut request, state, &mut worker).status_code().0
+    }
+
+    fn bearer(token: &str) -> Header {
+        Header::from_bytes(&b"Authorization"[..], format!("Bearer {token}")).unwrap()
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_route_ignores_query_string() {
+        let state = test_state(&[]);
+        let health = TestRequest::new()
+            .with_method(Method::Get)
+            .with_path("/health?verbose=1");
+        assert_eq!(status(&state, health), 200);
+
+        let tokenize = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/tokenize?format=json")
+            .with_body(r#"{"text": "自然言語"}"#);
+        assert_eq!(status(&state, tokenize), 200);
+
+        let unknown = TestRequest::new()
+            .with_method(Method::Get)
+            .with_path("/unknown?path=/health");
+        assert_eq!(status(&state, unknown), 404);
+    }
+
+    #[test]
+    fn test_reload_requires_token() {
+        let state = test_state(&[]);
+        let request = TestRequest::new().with_method(Method::Post).with_path("/reload");
+        assert_eq!(status(&state, request), 403);
+
+        let state = test_state(&["--reload-token", "secret"]);
+        let missing = TestRequest::new().with_method(Method::Post).with_path("/reload");
+        assert_eq!(status(&state, missing), 401);
+
+        let wrong = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/reload")
+            .with_header(bearer("guess"));
+        assert_eq!(status(&state, wrong), 401);
+
+        // The token is accepted; reloading fails because the dictionary file does not exist.
+        let valid = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/reload")
+            .with_header(bearer("secret"));
+        assert_eq!(status(&state, valid), 500);
+    }
+
+    #[test]
+    fn test_nbest_budget() {
+        let request = || {
+            TestRequest::new()
+                .with_method(Method::Post)
+                .with_path("/nbest")
+                .with_body(r#"{"text": "自然言語自然言語", "n": 3}"#)
+        };
+        assert_eq!(status(&test_state(&[]), request()), 200);
+        assert_eq!(status(&test_state(&["--max-nbest-bytes", "1"]), request()), 413);
+    }
+}
@@ -1,14 +1,26 @@
 //! 形態素解析を実行するユーティリティ
 //!
-//! このバイナリは、標準入力から読み込んだテキストを形態素解析し、
-//! 指定された出力形式（mecab、wakati、detail）で結果を出力します。
+//! このバイナリは、標準入力または入力ファイルから読み込んだテキストを形態素解析し、
+//! 指定された出力形式（mecab、wakati、detail、conllu）で結果を出力します。
+//! `--jobs`を指定すると、入力を行単位のバッチに分けて複数のスレッドで解析し、
+//! 入力と同じ順序で結果を出力します。
+//! `--tsv-column`を指定すると、TSV形式の各行の指定された列のみを解析し、
+//! 他の列はそのまま出力します。
 
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::io::{BufRead, BufWriter, Write};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
-use vibrato_rkyv::dictionary::Dictionary;
+use vibrato_rkyv::conllu::{self, ConlluSchema};
+use vibrato_rkyv::dictionary::{Dictionary, DictionaryInner, LoadMode, SystemDictionaryBuilder};
+use vibrato_rkyv::format::OutputFormatter;
+use vibrato_rkyv::tokenizer::worker::Worker;
 use vibrato_rkyv::{CacheStrategy, Tokenizer};
 
 use clap::Parser;
@@ -19,6 +31,7 @@ enum OutputMode {
     Mecab,
     Wakati,
     Detail,
+    Conllu(ConlluSchema),
 }
 
 /// `OutputMode` の `FromStr` 実装
@@ -29,7 +42,7 @@ impl FromStr for OutputMode {
     ///
     /// # 引数
     ///
-    /// * `mode` - パース対象の文字列（"mecab"、"wakati"、"detail"のいずれか）
+    /// * `mode` - パース対象の文字列（"mecab"、"wakati"、"detail"、"conllu"のいずれか）
     ///
     /// # 戻り値
     ///
@@ -39,23 +52,44 @@ impl FromStr for OutputMode {
             "mecab" => Ok(Self::Mecab),
             "wakati" => Ok(Self::Wakati),
             "detail" => Ok(Self::Detail),
+            "conllu" => Ok(Self::Conllu(ConlluSchema::new(None))),
             _ => Err("Could not parse a mode"),
         }
     }
 }
 
 /// コマンドライン引数
+///
+/// MeCabからの置き換えを容易にするため、`-d`、`-u`、`-N`、`-F`、`-E`、`-b`の
+/// 各オプションはMeCabと同じ意味で使用できます。
 #[derive(Parser, Debug)]
 #[clap(name = "tokenize", about = "Predicts morphemes")]
 struct Args {
     /// System dictionary (in zstd).
-    #[clap(short = 'i', long)]
-    sysdic: PathBuf,
+    #[clap(short = 'i', long, required_unless_present = "dicdir", conflicts_with = "dicdir")]
+    sysdic: Option<PathBuf>,
+
+    /// Dictionary directory, as in MeCab's -d.
+    /// Loads system.dic.zst or system.dic in the directory if present;
+    /// otherwise, builds the dictionary from the MeCab sources
+    /// (*.csv, matrix.def, char.def, and unk.def) in the directory.
+    #[clap(short = 'd', long)]
+    dicdir: Option<PathBuf>,
+
+    /// User lexicon file (in csv), as in MeCab's -u.
+    #[clap(short = 'u', long)]
+    userdic: Option<PathBuf>,
 
-    /// Output mode. Choices are mecab, wakati, and detail.
+    /// Output mode. Choices are mecab, wakati, detail, and conllu.
+    /// The conllu mode fills the UPOS and LEMMA columns if the POS tagset (ipadic or unidic)
+    /// is found in the dictionary metadata.
     #[clap(short = 'O', long, default_value = "mecab")]
     output_mode: OutputMode,
 
+    /// Outputs the N best results, as in MeCab's -N.
+    #[clap(short = 'N', long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    nbest: u32,
+
     /// Ignores white spaces in input strings.
     #[clap(short = 'S', long)]
     ignore_space: bool,
@@ -63,69 +97,237 @@ struct Args {
     /// Maximum length of unknown words.
     #[clap(short = 'M', long)]
     max_grouping_len: Option<usize>,
+
+    /// MeCab-compatible format of each token (e.g., "%m\t%f[6]\n").
+    /// Overrides the output mode if specified.
+    #[clap(short = 'F', long)]
+    node_format: Option<String>,
+
+    /// MeCab-compatible format printed at the end of each sentence.
+    /// Used together with --node-format.
+    #[clap(short = 'E', long, default_value = "EOS\\n")]
+    eos_format: String,
+
+    /// Maximum length of an input line in bytes, as in MeCab's -b.
+    /// Longer lines are split at character boundaries and tokenized separately.
+    #[clap(short = 'b', long)]
+    input_buffer_size: Option<usize>,
+
+    /// Prints the lattice of each sentence in GraphViz DOT format instead of the tokens.
+    /// The best path is highlighted.
+    #[clap(long)]
+    dump_lattice: bool,
+
+    /// Prints the metadata embedded in the dictionary (name, version, license, etc.) and exits.
+    #[clap(long)]
+    dict_info: bool,
+
+    /// Prints the char.def categories of each character in the given string and exits.
+    /// Useful for finding out why characters are grouped into an unknown word.
+    #[clap(long, value_name = "STRING")]
+    explain_char: Option<String>,
+
+    /// Input files tokenized in the given order instead of stdin.
+    #[clap(long, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Tokenizes only the N-th (0-based) tab-separated column of each line and prints the line
+    /// with the column replaced by the result, keeping the other columns (e.g., IDs) untouched.
+    /// Requires the wakati output mode or --node-format; the EOS format is not printed.
+    #[clap(long, value_name = "N", conflicts_with_all = ["nbest", "dump_lattice", "input_buffer_size"])]
+    tsv_column: Option<usize>,
+
+    /// Number of threads for tokenization.
+    /// Lines are tokenized in batches in parallel, and the results are printed in the input order.
+    #[clap(short = 'j', long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: u32,
 }
 
-/// メイン関数
-///
-/// 辞書をロードし、標準入力から読み込んだテキストを形態素解析して、
-/// 指定された形式で結果を標準出力に出力します。
-///
-/// # 戻り値
-///
-/// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+/// 並列処理で1つのバッチにまとめる行数
+const BATCH_LINES: usize = 1024;
 
-    eprintln!("Loading the dictionary...");
-    let dict = Dictionary::from_zstd(args.sysdic, CacheStrategy::GlobalCache)?;
+/// スレッド間で受け渡すエラー
+type SendError = Box<dyn Error + Send + Sync>;
 
-    let tokenizer = Tokenizer::new(dict)
-        .ignore_space(args.ignore_space)?
-        .max_grouping_len(args.max_grouping_len.unwrap_or(0));
-    let mut worker = tokenizer.new_worker();
+/// 読み込むシステム辞書
+enum SystemDictionary {
+    /// zstdで圧縮されたバイナリ辞書
+    Zstd(PathBuf),
+    /// 非圧縮のバイナリ辞書
+    Raw(PathBuf),
+    /// MeCab形式のソースファイルを含むディレクトリ
+    Sources(PathBuf),
+}
 
-    eprintln!("Ready to tokenize");
+impl SystemDictionary {
+    /// コマンドライン引数から読み込むシステム辞書を決定する
+    ///
+    /// `--dicdir`が指定された場合は、`system.dic.zst`、`system.dic`の順に探し、
+    /// どちらも存在しなければソースファイルから構築します。
+    fn from_args(args: &Args) -> Self {
+        if let Some(sysdic) = &args.sysdic {
+            return Self::Zstd(sysdic.clone());
+        }
+        let dicdir = args.dicdir.as_ref().unwrap();
+        let zstd = dicdir.join("system.dic.zst");
+        if zstd.is_file() {
+            return Self::Zstd(zstd);
+        }
+        let raw = dicdir.join("system.dic");
+        if raw.is_file() {
+            return Self::Raw(raw);
+        }
+        Self::Sources(dicdir.clone())
+    }
 
-    let is_tty = atty::is(atty::Stream::Stdout);
+    /// 辞書をロードする
+    ///
+    /// ユーザー辞書が指定された場合は、辞書を展開してユーザー辞書を設定します。
+    fn load(self, userdic: Option<&Path>) -> Result<Dictionary, Box<dyn Error>> {
+        let Some(userdic) = userdic else {
+            let dict = match self {
+                Self::Zstd(path) => Dictionary::from_zstd(path, CacheStrategy::GlobalCache)?,
+                Self::Raw(path) => Dictionary::from_path(path, LoadMode::TrustCache)?,
+                Self::Sources(dir) => Dictionary::from_inner(SystemDictionaryBuilder::from_dir(dir)?),
+            };
+            return Ok(dict);
+        };
+        let inner = match self {
+            Self::Zstd(path) => DictionaryInner::read_zstd(File::open(path)?)?,
+            Self::Raw(path) => DictionaryInner::read(File::open(path)?)?,
+            Self::Sources(dir) => SystemDictionaryBuilder::from_dir(dir)?,
+        };
+        let inner = inner.reset_user_lexicon_from_reader(Some(File::open(userdic)?))?;
+        Ok(Dictionary::from_inner(inner))
+    }
+}
 
-    let out = std::io::stdout();
-    let mut out = BufWriter::new(out.lock());
-    let lines = std::io::stdin().lock().lines();
-    for line in lines {
-        let line = line?;
-        worker.reset_sentence(line);
-        worker.tokenize();
-        match args.output_mode {
+/// 入力行を`max_bytes`バイト以下の断片に文字境界で分割する
+///
+/// 1文字が`max_bytes`を超える場合は、その文字のみを1つの断片とします。
+fn split_line(line: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = line;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().unwrap().len_utf8();
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// 1-bestの解析結果を書き出す
+fn write_best<W>(
+    out: &mut W,
+    worker: &Worker,
+    mode: &OutputMode,
+    formatter: Option<&OutputFormatter>,
+) -> std::io::Result<()>
+where
+    W: Write,
+{
+    if let Some(formatter) = formatter {
+        for t in worker.token_iter() {
+            formatter.write_token(&mut *out, &t)?;
+        }
+        return out.write_all(formatter.eos().as_bytes());
+    }
+    match mode {
+        OutputMode::Mecab => {
+            for i in 0..worker.num_tokens() {
+                let t = worker.token(i);
+                out.write_all(t.surface().as_bytes())?;
+                out.write_all(b"\t")?;
+                out.write_all(t.feature().as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            out.write_all(b"EOS\n")?;
+        }
+        OutputMode::Wakati => {
+            for i in 0..worker.num_tokens() {
+                if i != 0 {
+                    out.write_all(b" ")?;
+                }
+                out.write_all(worker.token(i).surface().as_bytes())?;
+            }
+            out.write_all(b"\n")?;
+        }
+        OutputMode::Detail => {
+            for i in 0..worker.num_tokens() {
+                let t = worker.token(i);
+                writeln!(
+                    out,
+                    "{}\t{}\tlex_type={:?}\tleft_id={}\tright_id={}\tword_cost={}\ttotal_cost={}",
+                    t.surface(),
+                    t.feature(),
+                    t.lex_type(),
+                    t.left_id(),
+                    t.right_id(),
+                    t.word_cost(),
+                    t.total_cost(),
+                )?;
+            }
+            out.write_all(b"EOS\n")?;
+        }
+        OutputMode::Conllu(schema) => conllu::write_sentence(worker, &mut *out, schema)?,
+    }
+    Ok(())
+}
+
+/// N-bestの解析結果を書き出す
+///
+/// MeCabと同様に、パスごとに1-bestと同じ形式で書き出します。
+fn write_nbest<W>(
+    out: &mut W,
+    worker: &Worker,
+    mode: &OutputMode,
+    formatter: Option<&OutputFormatter>,
+) -> std::io::Result<()>
+where
+    W: Write,
+{
+    for path_idx in 0..worker.num_nbest_paths() {
+        let tokens = worker.nbest_token_iter(path_idx).unwrap();
+        if let Some(formatter) = formatter {
+            for t in tokens {
+                formatter.write_nbest_token(&mut *out, &t)?;
+            }
+            out.write_all(formatter.eos().as_bytes())?;
+            continue;
+        }
+        match mode {
             OutputMode::Mecab => {
-                for i in 0..worker.num_tokens() {
-                    let t = worker.token(i);
+                for t in tokens {
                     out.write_all(t.surface().as_bytes())?;
                     out.write_all(b"\t")?;
                     out.write_all(t.feature().as_bytes())?;
                     out.write_all(b"\n")?;
                 }
                 out.write_all(b"EOS\n")?;
-                if is_tty {
-                    out.flush()?;
-                }
             }
             OutputMode::Wakati => {
-                for i in 0..worker.num_tokens() {
+                for (i, t) in tokens.enumerate() {
                     if i != 0 {
                         out.write_all(b" ")?;
                     }
-                    out.write_all(worker.token(i).surface().as_bytes())?;
+                    out.write_all(t.surface().as_bytes())?;
                 }
                 out.write_all(b"\n")?;
-                if is_tty {
-                    out.flush()?;
-                }
             }
             OutputMode::Detail => {
-                for i in 0..worker.num_tokens() {
-                    let t = worker.token(i);
+                for t in tokens {
                     writeln!(
-                        &mut out,
+                        out,
                         "{}\t{}\tlex_type={:?}\tleft_id={}\tright_id={}\tword_cost={}\ttotal_cost={}",
                         t.surface(),
                         t.feature(),
@@ -137,12 +339,298 @@ fn main() -> Result<(), Box<dyn Error>> {
                     )?;
                 }
                 out.write_all(b"EOS\n")?;
+            }
+            OutputMode::Conllu(_) => unreachable!("-O conllu is rejected with -N"),
+        }
+    }
+    Ok(())
+}
+
+/// TSV形式の行の列に、解析結果を1行で書き出す
+///
+/// 書式が指定された場合は各トークンを書式に従って連結し、指定されない場合は
+/// 表層形を空白で区切ります。
+fn write_tsv_column<W>(
+    out: &mut W,
+    worker: &Worker,
+    formatter: Option<&OutputFormatter>,
+) -> std::io::Result<()>
+where
+    W: Write,
+{
+    for (i, t) in worker.token_iter().enumerate() {
+        if let Some(formatter) = formatter {
+            formatter.write_token(&mut *out, &t)?;
+            continue;
+        }
+        if i != 0 {
+            out.write_all(b" ")?;
+        }
+        out.write_all(t.surface().as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 各行の解析と出力の設定
+struct Options {
+    output_mode: OutputMode,
+    formatter: Option<OutputFormatter>,
+    nbest: usize,
+    max_bytes: usize,
+    dump_lattice: bool,
+    tsv_column: Option<usize>,
+    /// 入力行の分割を警告済みかどうか
+    warned: AtomicBool,
+}
+
+/// 1行を解析し、結果を書き出す
+fn process_line<W>(
+    out: &mut W,
+    worker: &mut Worker,
+    line: &str,
+    opts: &Options,
+) -> Result<(), SendError>
+where
+    W: Write,
+{
+    if let Some(column) = opts.tsv_column {
+        let tsv = worker.tokenize_tsv_line(line, column)?;
+        out.write_all(tsv.prefix.as_bytes())?;
+        write_tsv_column(out, worker, opts.formatter.as_ref())?;
+        out.write_all(tsv.suffix.as_bytes())?;
+        out.write_all(b"\n")?;
+        return Ok(());
+    }
+    let chunks = split_line(line, opts.max_bytes);
+    if chunks.len() > 1 && !opts.warned.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "Warning: input-buffer overflow. The line is split. Use -b to enlarge the buffer."
+        );
+    }
+    for chunk in chunks {
+        worker.reset_sentence(chunk);
+        if opts.nbest == 1 {
+            worker.tokenize();
+        } else {
+            worker.tokenize_nbest(opts.nbest);
+        }
+        if opts.dump_lattice {
+            worker.dump_lattice_dot(&mut *out)?;
+        } else if opts.nbest == 1 {
+            write_best(out, worker, &opts.output_mode, opts.formatter.as_ref())?;
+        } else {
+            write_nbest(out, worker, &opts.output_mode, opts.formatter.as_ref())?;
+        }
+    }
+    Ok(())
+}
+
+/// 入力ファイルの各行を順に`f`に渡す
+///
+/// 入力ファイルが指定されていない場合は標準入力を読み込みます。
+/// `f`が`false`を返した場合は読み込みを中断します。
+fn for_each_line<F>(inputs: &[PathBuf], mut f: F) -> Result<(), SendError>
+where
+    F: FnMut(String) -> Result<bool, SendError>,
+{
+    if inputs.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            if !f(line?)? {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    for path in inputs {
+        let rdr = BufReader::new(File::open(path)?);
+        for line in rdr.lines() {
+            if !f(line?)? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 入力を行単位のバッチに分けて複数のスレッドで解析する
+///
+/// 各スレッドは独立した[`Worker`]でバッチを解析し、結果は入力の順序に並べ直して
+/// 書き出されます。いずれかのスレッドでエラーが発生した場合は、処理を中断します。
+fn tokenize_parallel<W>(
+    out: W,
+    tokenizer: &Tokenizer,
+    inputs: &[PathBuf],
+    opts: &Options,
+    jobs: usize,
+    is_tty: bool,
+) -> Result<(), SendError>
+where
+    W: Write + Send,
+{
+    let (job_tx, job_rx) = mpsc::sync_channel::<(usize, Vec<String>)>(jobs * 2);
+    // The receiver is dropped when all the workers exit, which stops the reader.
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (result_tx, result_rx) = mpsc::channel::<(usize, Result<Vec<u8>, SendError>)>();
+
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            s.spawn(move || {
+                let mut worker = tokenizer.new_worker();
+                loop {
+                    let job = job_rx.lock().unwrap().recv();
+                    let Ok((batch_idx, lines)) = job else {
+                        break;
+                    };
+                    let mut buf = vec![];
+                    let result = lines
+                        .iter()
+                        .try_for_each(|line| process_line(&mut buf, &mut worker, line, opts))
+                        .map(|()| buf);
+                    if result_tx.send((batch_idx, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(job_rx);
+        drop(result_tx);
+
+        let writer = s.spawn(move || -> Result<(), SendError> {
+            let mut out = BufWriter::new(out);
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (batch_idx, result) in result_rx {
+                pending.insert(batch_idx, result?);
+                while let Some(buf) = pending.remove(&next) {
+                    out.write_all(&buf)?;
+                    next += 1;
+                }
                 if is_tty {
                     out.flush()?;
                 }
             }
+            out.flush()?;
+            Ok(())
+        });
+
+        let mut batch = Vec::with_capacity(BATCH_LINES);
+        let mut batch_idx = 0;
+        let read_result = for_each_line(inputs, |line| {
+            batch.push(line);
+            if batch.len() < BATCH_LINES {
+                return Ok(true);
+            }
+            let lines = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_LINES));
+            let sent = job_tx.send((batch_idx, lines)).is_ok();
+            batch_idx += 1;
+            Ok(sent)
+        });
+        if !batch.is_empty() {
+            // A failure here is reported by the writer.
+            let _ = job_tx.send((batch_idx, batch));
+        }
+        drop(job_tx);
+
+        let write_result = writer.join().unwrap();
+        read_result.and(write_result)
+    })
+}
+
+/// メイン関数
+///
+/// 辞書をロードし、標準入力または入力ファイルから読み込んだテキストを形態素解析して、
+/// 指定された形式で結果を標準出力に出力します。
+///
+/// # 戻り値
+///
+/// 実行が成功した場合は `Ok(())`、エラーが発生した場合はエラー情報
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    cli_logger::init();
+
+    eprintln!("Loading the dictionary...");
+    let dict = SystemDictionary::from_args(&args).load(args.userdic.as_deref())?;
+
+    if args.dict_info {
+        match dict.metadata() {
+            Some(metadata) if !metadata.is_empty() => {
+                for (key, value) in metadata.iter() {
+                    println!("{key}\t{value}");
+                }
+            }
+            _ => println!("The dictionary has no embedded metadata."),
+        }
+        return Ok(());
+    }
+
+    if let Some(chars) = &args.explain_char {
+        for c in chars.chars() {
+            println!("{c}\tU+{:04X}\t{}", u32::from(c), dict.char_category(c));
+        }
+        return Ok(());
+    }
+
+    let mut output_mode = args.output_mode;
+    if let OutputMode::Conllu(schema) = &mut output_mode {
+        if args.nbest != 1 {
+            return Err("-O conllu does not support -N.".into());
+        }
+        if let Some(metadata) = dict.metadata() {
+            *schema = ConlluSchema::from_metadata(metadata);
         }
     }
 
+    let tokenizer = Tokenizer::new(dict)
+        .ignore_space(args.ignore_space)?
+        .max_grouping_len(args.max_grouping_len.unwrap_or(0));
+
+    if args.tsv_column.is_some()
+        && args.node_format.is_none()
+        && !matches!(output_mode, OutputMode::Wakati)
+    {
+        return Err("--tsv-column requires -O wakati or --node-format.".into());
+    }
+
+    let formatter = match &args.node_format {
+        Some(node_format) => {
+            Some(OutputFormatter::new(node_format)?.eos_format(&args.eos_format)?)
+        }
+        None => None,
+    };
+    let opts = Options {
+        output_mode,
+        formatter,
+        nbest: args.nbest as usize,
+        max_bytes: args.input_buffer_size.unwrap_or(usize::MAX).max(1),
+        dump_lattice: args.dump_lattice,
+        tsv_column: args.tsv_column,
+        warned: AtomicBool::new(false),
+    };
+
+    eprintln!("Ready to tokenize");
+
+    let is_tty = atty::is(atty::Stream::Stdout);
+
+    let jobs = args.jobs as usize;
+    if jobs > 1 {
+        return tokenize_parallel(std::io::stdout(), &tokenizer, &args.input, &opts, jobs, is_tty)
+            .map_err(|e| e as Box<dyn Error>);
+    }
+
+    let mut worker = tokenizer.new_worker();
+    let out = std::io::stdout();
+    let mut out = BufWriter::new(out.lock());
+    for_each_line(&args.input, |line| {
+        process_line(&mut out, &mut worker, &line, &opts)?;
+        if is_tty {
+            out.flush()?;
+        }
+        Ok(true)
+    })
+    .map_err(|e| e as Box<dyn Error>)?;
+    out.flush()?;
+
     Ok(())
 }
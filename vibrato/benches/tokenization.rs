@@ -1,7 +1,8 @@
 //! プリセット辞書を用いた形態素解析のベンチマーク
 //!
 //! 複数のプリセット辞書(IPAdic、UniDic-CWJ、BCCWJ-UniDic等)を使用して、
-//! デフォルト設定とMeCab互換設定での形態素解析速度を計測します。
+//! デフォルト設定とMeCab互換設定での形態素解析速度と、分かち書き専用の解析
+//! ([`Tokenizer::segment()`])とワーカーを使った境界の取得の速度を計測します。
 
 use std::path::Path;
 use std::sync::Arc;
@@ -68,6 +69,34 @@ fn benchmark_preset(
         );
     });
 
+    group.bench_function(BenchmarkId::new("Worker-Ranges", "Corpus"), |b| {
+        b.iter_with_setup(
+            || {
+                let tokenizer = Tokenizer::from_shared_dictionary(dict.clone());
+                (tokenizer.new_worker(), vec![])
+            },
+            |(mut worker, mut ranges)| {
+                for line in lines {
+                    worker.reset_sentence(line);
+                    worker.tokenize();
+                    ranges.clear();
+                    ranges.extend(worker.token_iter().map(|t| t.range_byte()));
+                }
+            },
+        );
+    });
+
+    group.bench_function(BenchmarkId::new("Segment", "Corpus"), |b| {
+        b.iter_with_setup(
+            || (Tokenizer::from_shared_dictionary(dict.clone()), vec![]),
+            |(tokenizer, mut ranges)| {
+                for line in lines {
+                    tokenizer.segment(line, &mut ranges);
+                }
+            },
+        );
+    });
+
     group.finish();
 }
 
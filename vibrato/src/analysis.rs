@@ -0,0 +1,173 @@
+//! 文書単位の解析結果
+//!
+//! このモジュールは、文書を文に分割してトークン化した結果を保持する
+//! [`Document`]型を提供します。各トークンの位置は文書の先頭からの位置で表されるため、
+//! 文ごとの結果を結合し直すことなく元のテキストと対応付けられます。
+//!
+//! `serde`フィーチャーを有効にすると、[`Document`]をシリアライズできます。
+//!
+//! また、正規化、トークン化、フィルタを組み合わせた解析パイプラインのための
+//! [`Analyzer`]トレイトと[`AnalyzerBuilder`]を提供します。
+//!
+//! # 例
+//!
+//! ```
+//! # use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+//! # let dict = SystemDictionaryBuilder::from_readers(
+//! #     "東京,0,0,1,名詞\n京都,0,0,1,名詞\n".as_bytes(),
+//! #     "1 1\n0 0 0".as_bytes(),
+//! #     "DEFAULT 0 1 0".as_bytes(),
+//! #     "DEFAULT,0,0,100,*".as_bytes(),
+//! # ).unwrap();
+//! # let tokenizer = Tokenizer::from_inner(dict);
+//! let mut worker = tokenizer.new_worker();
+//! let doc = worker.tokenize_document("東京。\n京都。");
+//!
+//! assert_eq!(doc.sentences.len(), 2);
+//! assert_eq!(doc.sentence_text(1), "京都。");
+//! assert_eq!(doc.sentences[1].tokens[0].range_byte, 10..16);
+//! ```
+
+mod analyzer;
+
+use std::ops::Range;
+
+use crate::dictionary::WordIdx;
+use crate::token::TokenBuf;
+
+pub use analyzer::{
+    Analyzer, AnalyzerBuilder, Nfkc, Normalizer, Pipeline, PosStoplist, TokenFilter, TokenStream,
+};
+
+/// 文末とみなす文字
+const SENTENCE_TERMINATORS: &[char] = &['。', '．', '！', '？', '!', '?'];
+
+/// 文書の解析結果
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    /// 文書全体のテキスト
+    pub text: String,
+
+    /// 文書中の文（出現順）
+    pub sentences: Vec<Sentence>,
+}
+
+impl Document {
+    /// `i`番目の文のテキストを取得します。
+    ///
+    /// # パニック
+    ///
+    /// `i`が文の数以上の場合、パニックします。
+    #[inline(always)]
+    pub fn sentence_text(&self, i: usize) -> &str {
+        &self.text[self.sentences[i].range_byte.clone()]
+    }
+
+    /// 文書中のすべてのトークンを出現順に返すイテレータを作成します。
+    pub fn tokens(&self) -> impl Iterator<Item = &TokenBuf> {
+        self.sentences.iter().flat_map(|sentence| sentence.tokens.iter())
+    }
+
+    /// 文書中のトークンの総数を取得します。
+    pub fn num_tokens(&self) -> usize {
+        self.sentences.iter().map(|sentence| sentence.tokens.len()).sum()
+    }
+}
+
+/// 文書中の1文の解析結果
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sentence {
+    /// 文書の先頭からの文字単位の位置範囲
+    pub range_char: Range<usize>,
+
+    /// 文書の先頭からのバイト単位の位置範囲
+    pub range_byte: Range<usize>,
+
+    /// 文中のトークン
+    ///
+    /// 各トークンの位置範囲は文書の先頭からの位置で表されます。
+    pub tokens: Vec<TokenBuf>,
+}
+
+/// 文書中のトークンのスタンドオフ注釈
+///
+/// [`Worker::annotate_document()`](crate::tokenizer::worker::Worker::annotate_document)が
+/// 返します。表層形は保持しないため、位置範囲で元の文書から取り出してください。
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpanAnnotation {
+    /// 文書の先頭からのバイト単位の位置範囲
+    pub range_byte: Range<usize>,
+
+    /// 文書の先頭からの文字単位の位置範囲
+    pub range_char: Range<usize>,
+
+    /// トークンを含む文の番号（0始まり）
+    pub sentence: usize,
+
+    /// トークンの単語インデックス
+    pub word_idx: WordIdx,
+
+    /// トークンの素性
+    pub feature: String,
+}
+
+/// テキストを文に分割し、各文のバイト単位の位置範囲を返します。
+///
+/// 改行は文の区切りとして扱われ、どの文にも含まれません。また、
+/// 句点や感嘆符、疑問符（`。．！？!?`、連続する場合はそのすべて）の直後でも文を区切ります。
+/// 空白のみからなる文は除かれます。
+///
+/// # 引数
+///
+/// * `text` - 分割するテキスト
+///
+/// # 戻り値
+///
+/// 各文のバイト単位の位置範囲
+pub fn split_sentences(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut push = |range: Range<usize>| {
+        if !text[range.clone()].trim().is_empty() {
+            ranges.push(range);
+        }
+    };
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' || c == '\r' {
+            push(start..i);
+            start = i + c.len_utf8();
+        } else if SENTENCE_TERMINATORS.contains(&c) {
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, d)) = chars.peek() {
+                if !SENTENCE_TERMINATORS.contains(&d) {
+                    break;
+                }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            push(start..end);
+            start = end;
+        }
+    }
+    push(start..text.len());
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sentences() {
+        let text = "今日は。晴れ！？\r\nでも\n\n  \n明日は雨";
+        let sentences: Vec<_> = split_sentences(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(sentences, ["今日は。", "晴れ！？", "でも", "明日は雨"]);
+
+        assert!(split_sentences("").is_empty());
+        assert!(split_sentences(" \n").is_empty());
+    }
+}
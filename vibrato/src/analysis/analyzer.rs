@@ -0,0 +1,417 @@
+//! 正規化、トークン化、フィルタを組み合わせた解析パイプライン
+//!
+//! [`Analyzer`]は、テキストを受け取ってトークン列を返す処理の共通のインターフェースです。
+//! [`Worker`]をそのまま使うことも、[`AnalyzerBuilder`]で正規化とフィルタを組み合わせた
+//! [`Pipeline`]を作ることもできます。検索エンジンなどに組み込む際は、このトレイトだけに
+//! 依存させることで、解析の設定を差し替えやすくなります。
+
+use std::iter::FusedIterator;
+
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::dictionary::Dictionary;
+use crate::postprocess::TokenMerger;
+use crate::token::TokenBuf;
+use crate::tokenizer::worker::Worker;
+use crate::tokenizer::Tokenizer;
+
+/// テキストを解析してトークン列を返す処理
+pub trait Analyzer {
+    /// テキストを解析します。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 解析するテキスト
+    ///
+    /// # 戻り値
+    ///
+    /// 出現順のトークン列。各トークンの位置範囲は`text`の中の位置を表します。
+    fn analyze(&mut self, text: &str) -> TokenStream;
+}
+
+impl Analyzer for Worker {
+    fn analyze(&mut self, text: &str) -> TokenStream {
+        self.reset_sentence(text);
+        self.tokenize();
+        self.token_iter().map(|t| t.to_buf()).collect::<Vec<_>>().into()
+    }
+}
+
+/// [`Analyzer::analyze()`]が返すトークン列
+#[derive(Clone, Debug, Default)]
+pub struct TokenStream {
+    tokens: std::vec::IntoIter<TokenBuf>,
+}
+
+impl TokenStream {
+    /// まだ取り出されていないトークンを返します。
+    pub fn as_slice(&self) -> &[TokenBuf] {
+        self.tokens.as_slice()
+    }
+}
+
+impl From<Vec<TokenBuf>> for TokenStream {
+    fn from(tokens: Vec<TokenBuf>) -> Self {
+        Self {
+            tokens: tokens.into_iter(),
+        }
+    }
+}
+
+impl Iterator for TokenStream {
+    type Item = TokenBuf;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next()
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.tokens.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for TokenStream {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.tokens.next_back()
+    }
+}
+
+impl ExactSizeIterator for TokenStream {}
+
+impl FusedIterator for TokenStream {}
+
+/// トークン化の前にテキストを書き換える正規化処理
+///
+/// 正規化は、基底文字とそれに続く結合文字（濁点や異体字セレクタなど）からなる
+/// 分割できない文字のまとまりごとに適用されます。そのため、正規化で文字数が変わっても、
+/// 各トークンの位置範囲を元のテキストの位置に対応付けられます。
+///
+/// `Fn(&str, &mut String)`を満たすクロージャも正規化処理として使えます。
+pub trait Normalizer: Send + Sync {
+    /// 文字のまとまり`chunk`を正規化し、結果を`out`に追加します。
+    fn normalize(&self, chunk: &str, out: &mut String);
+}
+
+impl<F> Normalizer for F
+where
+    F: Fn(&str, &mut String) + Send + Sync,
+{
+    fn normalize(&self, chunk: &str, out: &mut String) {
+        self(chunk, out);
+    }
+}
+
+/// Unicode正規化形式KC（NFKC）による正規化
+///
+/// 半角カタカナや全角英数字などの互換文字を標準的な文字に変換します。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nfkc;
+
+impl Normalizer for Nfkc {
+    fn normalize(&self, chunk: &str, out: &mut String) {
+        out.extend(chunk.nfkc());
+    }
+}
+
+/// トークン化の後にトークンを取り除いたり書き換えたりするフィルタ
+///
+/// `Fn(&mut TokenBuf) -> bool`を満たすクロージャもフィルタとして使えます。
+pub trait TokenFilter: Send + Sync {
+    /// トークンを残す場合は`true`を返します。
+    ///
+    /// `token`を書き換えると、書き換えた結果が後続のフィルタと出力に渡されます。
+    fn filter(&self, token: &mut TokenBuf) -> bool;
+}
+
+impl<F> TokenFilter for F
+where
+    F: Fn(&mut TokenBuf) -> bool + Send + Sync,
+{
+    fn filter(&self, token: &mut TokenBuf) -> bool {
+        self(token)
+    }
+}
+
+/// 品詞によるストップリスト
+///
+/// 素性の先頭の項目が指定された品詞と一致するトークンを取り除きます。
+/// `"名詞,数"`のように複数の項目を指定すると、より細かい分類で取り除けます。
+#[derive(Clone, Debug, Default)]
+pub struct PosStoplist {
+    prefixes: Vec<String>,
+}
+
+impl PosStoplist {
+    /// 新しいストップリストを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `prefixes` - 取り除く品詞（素性の先頭からのカンマ区切りの項目）
+    pub fn new<I, S>(prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            prefixes: prefixes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TokenFilter for PosStoplist {
+    fn filter(&self, token: &mut TokenBuf) -> bool {
+        !self.prefixes.iter().any(|prefix| {
+            token
+                .feature
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(','))
+        })
+    }
+}
+
+/// [`Pipeline`]のビルダー
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::analysis::{Analyzer, AnalyzerBuilder, Nfkc, PosStoplist};
+/// # use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+/// # let dict = SystemDictionaryBuilder::from_readers(
+/// #     "ガス,0,0,1,名詞\nは,0,0,1,助詞\n".as_bytes(),
+/// #     "1 1\n0 0 0".as_bytes(),
+/// #     "DEFAULT 0 1 0".as_bytes(),
+/// #     "DEFAULT,0,0,100,*".as_bytes(),
+/// # ).unwrap();
+/// # let tokenizer = Tokenizer::from_inner(dict);
+///
+/// let mut analyzer = AnalyzerBuilder::from_tokenizer(tokenizer)
+///     .normalize(Nfkc)
+///     .filter(PosStoplist::new(["助詞"]))
+///     .build();
+///
+/// let tokens: Vec<_> = analyzer.analyze("ｶﾞｽは").collect();
+/// assert_eq!(tokens.len(), 1);
+/// assert_eq!(tokens[0].surface, "ガス");
+/// assert_eq!(tokens[0].range_byte, 0..9);
+/// ```
+pub struct AnalyzerBuilder {
+    tokenizer: Tokenizer,
+    normalizers: Vec<Box<dyn Normalizer>>,
+    merger: TokenMerger,
+    filters: Vec<Box<dyn TokenFilter>>,
+}
+
+impl AnalyzerBuilder {
+    /// 辞書から新しいビルダーを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `dict` - 解析に使用する辞書
+    pub fn new(dict: Dictionary) -> Self {
+        Self::from_tokenizer(Tokenizer::new(dict))
+    }
+
+    /// 設定済みのトークナイザーから新しいビルダーを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `tokenizer` - 解析に使用するトークナイザー
+    pub fn from_tokenizer(tokenizer: Tokenizer) -> Self {
+        Self {
+            tokenizer,
+            normalizers: vec![],
+            merger: TokenMerger::new(),
+            filters: vec![],
+        }
+    }
+
+    /// 正規化処理を追加します。
+    ///
+    /// 複数追加した場合は、追加した順に適用されます。
+    pub fn normalize<N>(mut self, normalizer: N) -> Self
+    where
+        N: Normalizer + 'static,
+    {
+        self.normalizers.push(Box::new(normalizer));
+        self
+    }
+
+    /// トークン列をまとめる後処理を設定します。
+    ///
+    /// 後処理はフィルタより前に適用されます。
+    pub fn merge(mut self, merger: TokenMerger) -> Self {
+        self.merger = merger;
+        self
+    }
+
+    /// フィルタを追加します。
+    ///
+    /// 複数追加した場合は、追加した順に適用されます。
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: TokenFilter + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// パイプラインを作成します。
+    pub fn build(self) -> Pipeline {
+        Pipeline {
+            worker: self.tokenizer.new_worker(),
+            normalizers: self.normalizers,
+            merger: self.merger,
+            filters: self.filters,
+            normalized: String::new(),
+            alignment: vec![],
+            c2b: vec![],
+        }
+    }
+}
+
+/// 正規化、トークン化、後処理、フィルタを順に適用する [`Analyzer`]
+///
+/// 正規化した場合、各トークンの表層形は正規化後の文字列になり、位置範囲は元のテキストの
+/// 位置を表します。1つの文字のまとまりが複数のトークンに分かれた場合、それらのトークンは
+/// 同じ位置範囲を持ちます。
+pub struct Pipeline {
+    worker: Worker,
+    normalizers: Vec<Box<dyn Normalizer>>,
+    merger: TokenMerger,
+    filters: Vec<Box<dyn TokenFilter>>,
+    normalized: String,
+    /// 正規化後の各文字に対応する元のテキストの文字単位の位置範囲
+    alignment: Vec<(usize, usize)>,
+    /// 元のテキストの文字位置からバイト位置への対応
+    c2b: Vec<usize>,
+}
+
+impl Pipeline {
+    /// `text`を正規化し、正規化後の各文字と元の位置を対応付けます。
+    fn normalize(&mut self, text: &str) {
+        self.normalized.clear();
+        self.alignment.clear();
+        self.c2b.clear();
+        self.c2b.extend(text.char_indices().map(|(i, _)| i));
+        self.c2b.push(text.len());
+
+        let chars: Vec<_> = text.chars().collect();
+        let mut buf = String::new();
+        let mut tmp = String::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = start + 1;
+            while end < chars.len() && is_continuation(chars[end]) {
+                end += 1;
+            }
+            buf.clear();
+            buf.push_str(&text[self.c2b[start]..self.c2b[end]]);
+            for normalizer in &self.normalizers {
+                tmp.clear();
+                normalizer.normalize(&buf, &mut tmp);
+                std::mem::swap(&mut buf, &mut tmp);
+            }
+            self.alignment.extend(buf.chars().map(|_| (start, end)));
+            self.normalized.push_str(&buf);
+            start = end;
+        }
+    }
+
+    /// 正規化後のテキストでの位置範囲を、元のテキストでの位置範囲に置き換えます。
+    fn align(&self, token: &mut TokenBuf) {
+        let start = self.alignment[token.range_char.start].0;
+        let end = self.alignment[token.range_char.end - 1].1;
+        token.range_char = start..end;
+        token.range_byte = self.c2b[start]..self.c2b[end];
+    }
+}
+
+/// 直前の文字と結合しうる文字の場合に`true`を返します。
+fn is_continuation(c: char) -> bool {
+    std::iter::once(c)
+        .nfkd()
+        .next()
+        .is_some_and(|d| canonical_combining_class(d) != 0)
+}
+
+impl Analyzer for Pipeline {
+    fn analyze(&mut self, text: &str) -> TokenStream {
+        let tokens: Vec<_> = if self.normalizers.is_empty() {
+            self.worker.analyze(text).collect()
+        } else {
+            self.normalize(text);
+            self.worker.reset_sentence(&self.normalized);
+            self.worker.tokenize();
+            self.worker
+                .token_iter()
+                .map(|t| {
+                    let mut token = t.to_buf();
+                    self.align(&mut token);
+                    token
+                })
+                .collect()
+        };
+        let mut tokens = self.merger.merge(tokens);
+        tokens.retain_mut(|token| self.filters.iter().all(|filter| filter.filter(token)));
+        tokens.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SystemDictionaryBuilder;
+
+    fn tokenizer() -> Tokenizer {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "ガス,0,0,1,名詞,普通名詞\nは,0,0,1,助詞,係助詞\n株式,0,0,1,名詞\n会社,0,0,1,名詞\n"
+                .as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        Tokenizer::from_inner(dict)
+    }
+
+    #[test]
+    fn test_pipeline_alignment() {
+        let mut analyzer = AnalyzerBuilder::from_tokenizer(tokenizer())
+            .normalize(Nfkc)
+            .build();
+
+        let text = "ｶﾞｽは㍿";
+        let tokens: Vec<_> = analyzer.analyze(text).collect();
+        let surfaces: Vec<_> = tokens.iter().map(|t| t.surface.as_str()).collect();
+        assert_eq!(surfaces, ["ガス", "は", "株式", "会社"]);
+        assert_eq!(tokens[0].range_char, 0..3);
+        assert_eq!(&text[tokens[0].range_byte.clone()], "ｶﾞｽ");
+        assert_eq!(&text[tokens[1].range_byte.clone()], "は");
+        assert_eq!(&text[tokens[2].range_byte.clone()], "㍿");
+        assert_eq!(tokens[3].range_char, 4..5);
+    }
+
+    #[test]
+    fn test_pipeline_filters() {
+        let mut analyzer = AnalyzerBuilder::from_tokenizer(tokenizer())
+            .filter(PosStoplist::new(["助詞"]))
+            .filter(|token: &mut TokenBuf| {
+                token.surface.push('!');
+                true
+            })
+            .build();
+
+        let tokens: Vec<_> = analyzer.analyze("ガスは").collect();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].surface, "ガス!");
+        assert_eq!(tokens[0].range_byte, 0..6);
+
+        assert!(!PosStoplist::new(["名詞,普通名詞"]).filter(&mut tokens[0].clone()));
+        assert!(PosStoplist::new(["名"]).filter(&mut tokens[0].clone()));
+    }
+}
@@ -0,0 +1,188 @@
+//! 本家vibratoとの互換レイヤー
+//!
+//! このモジュールは、本家[vibrato](https://github.com/daac-tools/vibrato)の公開APIに
+//! 合わせた互換メソッドを提供します。`Cargo.toml`で依存関係を
+//!
+//! ```toml
+//! vibrato = { package = "vibrato-rkyv", version = "0.7", features = ["compat-vibrato"] }
+//! ```
+//!
+//! のように差し替えることで、既存のコードの多くをそのままビルドでき、
+//! 非推奨警告に従って少しずつ移行できます。
+//!
+//! 互換メソッドはすべて`#[deprecated]`で、移行先のAPIを案内します。
+//! 所有型の辞書に対する操作は、内部で辞書を再構築するため本来のAPIより低速です。
+//!
+//! | 本家vibrato | vibrato-rkyv |
+//! |------|------|
+//! | `Dictionary::read` (bincode) | [`Dictionary::from_path`]、`Dictionary::from_legacy_reader`（`legacy`フィーチャー） |
+//! | `Dictionary::read_unchecked` | [`Dictionary::from_path_unchecked`] |
+//! | `Dictionary::reset_user_lexicon_from_reader` | [`DictionaryInner::reset_user_lexicon_from_reader`] |
+//! | `Dictionary::map_connection_ids_from_iter` | [`DictionaryInner::map_connection_ids_from_iter`] |
+//! | `Tokenizer::new(SystemDictionaryBuilder::from_readers(..)?)` | [`Tokenizer::from_inner`](crate::Tokenizer::from_inner) |
+//!
+//! [`SystemDictionaryBuilder::from_readers`](crate::SystemDictionaryBuilder::from_readers)は
+//! [`DictionaryInner`]を返すため、そのまま`Dictionary`が必要な箇所には`.into()`を追加してください。
+//! 同名で戻り値の型が異なるAPIは互換メソッドで置き換えられないため、この点だけは移行時に
+//! 修正が必要です。
+
+use std::io::Read;
+
+use crate::dictionary::{Dictionary, DictionaryInner};
+use crate::errors::Result;
+
+impl From<DictionaryInner> for Dictionary {
+    fn from(dict: DictionaryInner) -> Self {
+        Self::from_inner(dict)
+    }
+}
+
+impl Dictionary {
+    /// 辞書を所有型の内部データに変換します。
+    fn into_owned_inner(self) -> Result<DictionaryInner> {
+        let mut buffer = vec![];
+        self.write(&mut buffer)?;
+        DictionaryInner::read(buffer.as_slice())
+    }
+
+    /// ユーザー辞書をリセットします。
+    ///
+    /// 本家vibratoの`Dictionary::reset_user_lexicon_from_reader`に対応する互換メソッドです。
+    ///
+    /// # 引数
+    ///
+    /// * `user_lexicon_rdr` - ユーザー辞書データを含むリーダー。`None`の場合、ユーザー辞書が削除されます。
+    ///
+    /// # エラー
+    ///
+    /// [`DictionaryInner::reset_user_lexicon_from_reader`]と同じ場合にエラーを返します。
+    #[deprecated(
+        since = "0.7.2",
+        note = "build the dictionary with `DictionaryInner::reset_user_lexicon_from_reader` before loading it"
+    )]
+    pub fn reset_user_lexicon_from_reader<R>(self, user_lexicon_rdr: Option<R>) -> Result<Self>
+    where
+        R: Read,
+    {
+        self.into_owned_inner()?
+            .reset_user_lexicon_from_reader(user_lexicon_rdr)
+            .map(Self::from_inner)
+    }
+
+    /// 指定されたマッピングを使用して接続IDを編集します。
+    ///
+    /// 本家vibratoの`Dictionary::map_connection_ids_from_iter`に対応する互換メソッドです。
+    ///
+    /// # 引数
+    ///
+    /// * `lmap` - 左接続IDのマッピングを含むイテレータ。
+    /// * `rmap` - 右接続IDのマッピングを含むイテレータ。
+    ///
+    /// # エラー
+    ///
+    /// [`DictionaryInner::map_connection_ids_from_iter`]と同じ場合にエラーを返します。
+    #[deprecated(
+        since = "0.7.2",
+        note = "use `DictionaryInner::map_connection_ids_from_iter` before loading the dictionary"
+    )]
+    pub fn map_connection_ids_from_iter<L, R>(self, lmap: L, rmap: R) -> Result<Self>
+    where
+        L: IntoIterator<Item = u16>,
+        R: IntoIterator<Item = u16>,
+    {
+        self.into_owned_inner()?
+            .map_connection_ids_from_iter(lmap, rmap)
+            .map(Self::from_inner)
+    }
+
+    /// 検証を省略してリーダーから辞書を作成します。
+    ///
+    /// 本家vibratoの`Dictionary::read_unchecked`に対応する互換メソッドです。
+    /// `legacy`フィーチャーが有効な場合は、本家vibratoのbincode形式の辞書も読み込めます。
+    /// rkyv形式の辞書は[`Dictionary::read`]と同様に検証されます。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 辞書データのリーダー
+    ///
+    /// # エラー
+    ///
+    /// データの読み込みに失敗した場合や、辞書の形式が不正な場合にエラーを返します。
+    ///
+    /// # Safety
+    ///
+    /// 本家vibratoとのシグネチャの互換性のために`unsafe`になっていますが、
+    /// 呼び出し側が満たすべき前提はありません。
+    #[deprecated(
+        since = "0.7.2",
+        note = "use `Dictionary::from_path_unchecked` or `Dictionary::read`"
+    )]
+    pub unsafe fn read_unchecked<R: Read>(mut rdr: R) -> Result<Self> {
+        let mut buffer = vec![];
+        rdr.read_to_end(&mut buffer)?;
+        #[cfg(feature = "legacy")]
+        if buffer.starts_with(crate::dictionary::LEGACY_MODEL_MAGIC_PREFIX) {
+            return Self::from_legacy_reader(buffer.as_slice());
+        }
+        Self::read(buffer.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(deprecated)]
+
+    use super::*;
+    use crate::{SystemDictionaryBuilder, Tokenizer};
+
+    const LEX_CSV: &str = include_str!("tests/resources/lex.csv");
+    const USER_CSV: &str = include_str!("tests/resources/user.csv");
+    const MATRIX_DEF: &str = include_str!("tests/resources/matrix.def");
+    const CHAR_DEF: &str = include_str!("tests/resources/char.def");
+    const UNK_DEF: &str = include_str!("tests/resources/unk.def");
+
+    fn build() -> DictionaryInner {
+        SystemDictionaryBuilder::from_readers(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn tokenize(dict: Dictionary, sentence: &str) -> Vec<String> {
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence(sentence);
+        worker.tokenize();
+        worker.token_iter().map(|t| t.feature().to_string()).collect()
+    }
+
+    #[test]
+    fn test_reset_user_lexicon_from_reader() {
+        let expected = build()
+            .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+            .unwrap();
+        let expected = tokenize(expected.into(), "京都東京都に行った");
+        assert_eq!(expected[0], "カスタム名詞");
+
+        let dict: Dictionary = build().into();
+        let dict = dict.reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes())).unwrap();
+        assert_eq!(tokenize(dict, "京都東京都に行った"), expected);
+
+        let mut buffer = vec![];
+        build().write(&mut buffer).unwrap();
+        let dict = Dictionary::read(buffer.as_slice()).unwrap();
+        let dict = dict.reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes())).unwrap();
+        assert_eq!(tokenize(dict, "京都東京都に行った"), expected);
+    }
+
+    #[test]
+    fn test_read_unchecked() {
+        let mut buffer = vec![];
+        build().write(&mut buffer).unwrap();
+        let dict = unsafe { Dictionary::read_unchecked(buffer.as_slice()) }.unwrap();
+        assert!(matches!(dict, Dictionary::Archived(_)));
+    }
+}
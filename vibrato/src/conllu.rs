@@ -0,0 +1,200 @@
+//! CoNLL-U形式の出力
+//!
+//! このモジュールは、トークン化の結果をUniversal DependenciesのCoNLL-U形式で書き出す
+//! 機能を提供します。ID、FORM、LEMMA、UPOS、XPOSの各列を素性から埋め、依存構造などの
+//! 残りの列は`_`とするため、出力をそのまま係り受け解析器や評価ツールに入力できます。
+//!
+//! 各列の取り出し方は[`ConlluSchema`]で指定します。UPOSは[`posmap`](crate::posmap)の
+//! 対応表で変換し、XPOSは品詞階層の項目を`-`で連結したもの（例: `名詞-固有名詞-地域`）です。
+//! 次のトークンと間に空白がない場合は、MISC列に`SpaceAfter=No`を出力します。
+//!
+//! # 例
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use vibrato_rkyv::conllu::{self, ConlluSchema};
+//! use vibrato_rkyv::posmap::PosTagset;
+//! use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+//!
+//! let dict = SystemDictionaryBuilder::from_readers(
+//!     "東京,0,0,1,名詞,固有名詞,地域,一般,*,*,東京\nへ,0,0,1,助詞,格助詞,一般,*,*,*,へ".as_bytes(),
+//!     "1 1\n0 0 0".as_bytes(),
+//!     "DEFAULT 0 1 0".as_bytes(),
+//!     "DEFAULT,0,0,100,*".as_bytes(),
+//! )?;
+//! let tokenizer = Tokenizer::from_inner(dict);
+//! let mut worker = tokenizer.new_worker();
+//! worker.reset_sentence("東京へ");
+//! worker.tokenize();
+//!
+//! let mut buf = vec![];
+//! conllu::write_sentence(&worker, &mut buf, &ConlluSchema::new(Some(PosTagset::Ipadic)))?;
+//! assert_eq!(
+//!     String::from_utf8(buf)?,
+//!     "1\t東京\t東京\tPROPN\t名詞-固有名詞-地域-一般\t_\t_\t_\t_\tSpaceAfter=No\n\
+//!      2\tへ\tへ\tADP\t助詞-格助詞-一般\t_\t_\t_\t_\t_\n\n",
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+
+use crate::dictionary::metadata::DictionaryMetadata;
+use crate::posmap::PosTagset;
+use crate::token::PosSchema;
+use crate::tokenizer::worker::Worker;
+use crate::utils;
+
+/// 素性からCoNLL-Uの各列を取り出す方法
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConlluSchema {
+    tagset: Option<PosTagset>,
+    lemma_field: Option<usize>,
+    xpos: PosSchema,
+}
+
+impl ConlluSchema {
+    /// 品詞体系に応じた設定を作成します。
+    ///
+    /// LEMMAはIPADICでは素性の6番目（0始まり）、UniDicでは7番目の項目から取り出します。
+    /// 品詞体系が`None`の場合、UPOSとLEMMAは`_`となります。
+    ///
+    /// # 引数
+    ///
+    /// * `tagset` - 辞書の品詞体系
+    pub const fn new(tagset: Option<PosTagset>) -> Self {
+        let lemma_field = match tagset {
+            Some(PosTagset::Ipadic) => Some(6),
+            Some(PosTagset::Unidic) => Some(7),
+            None => None,
+        };
+        Self {
+            tagset,
+            lemma_field,
+            xpos: PosSchema::Hierarchy,
+        }
+    }
+
+    /// 辞書のメタデータから判定した品詞体系に応じた設定を作成します。
+    ///
+    /// 品詞体系の判定については[`PosTagset::from_metadata()`]を参照してください。
+    ///
+    /// # 引数
+    ///
+    /// * `metadata` - 辞書のメタデータ
+    pub fn from_metadata(metadata: &DictionaryMetadata) -> Self {
+        Self::new(PosTagset::from_metadata(metadata))
+    }
+
+    /// LEMMAを取り出す素性の項目を指定します。
+    ///
+    /// # 引数
+    ///
+    /// * `lemma_field` - 素性の項目の位置（0始まり）。`None`の場合、LEMMAは`_`となります。
+    pub const fn lemma_field(mut self, lemma_field: Option<usize>) -> Self {
+        self.lemma_field = lemma_field;
+        self
+    }
+
+    /// XPOSの取り出し方を指定します。
+    ///
+    /// デフォルトは[`PosSchema::Hierarchy`]です。
+    ///
+    /// # 引数
+    ///
+    /// * `xpos` - 素性から品詞を取り出す方法
+    pub const fn xpos(mut self, xpos: PosSchema) -> Self {
+        self.xpos = xpos;
+        self
+    }
+}
+
+/// 1-bestの解析結果を1文のCoNLL-U形式で書き出します。
+///
+/// 各トークンを1行で書き出し、最後に文の区切りの空行を書き出します。
+/// `# text = ...`などのコメント行は書き出さないため、必要に応じて呼び出し側で
+/// 先に書き出してください。
+///
+/// # 引数
+///
+/// * `worker` - [`Worker::tokenize()`]を実行したワーカー
+/// * `wtr` - 書き込み先
+/// * `schema` - 素性から各列を取り出す方法
+///
+/// # エラー
+///
+/// 書き込みに失敗した場合、[`std::io::Error`]が返されます。
+pub fn write_sentence<W>(worker: &Worker, mut wtr: W, schema: &ConlluSchema) -> std::io::Result<()>
+where
+    W: Write,
+{
+    let num_tokens = worker.num_tokens();
+    for (i, t) in worker.token_iter().enumerate() {
+        let feature = t.feature();
+        let lemma = schema.lemma_field.and_then(|idx| {
+            let mut fields = utils::parse_csv_row(feature);
+            (idx < fields.len()).then(|| fields.swap_remove(idx))
+        });
+        let lemma = match lemma.as_deref() {
+            Some("*" | "") | None => "_",
+            Some(lemma) => lemma,
+        };
+        let upos = schema.tagset.map_or("_", |tagset| t.upos(tagset).as_str());
+        let xpos = schema.xpos.extract(feature);
+        let xpos = if xpos.is_empty() { "_".to_string() } else { xpos.replace(',', "-") };
+        let space_after =
+            i + 1 < num_tokens && worker.token(i + 1).range_byte().start == t.range_byte().end;
+        let misc = if space_after { "SpaceAfter=No" } else { "_" };
+        writeln!(
+            wtr,
+            "{}\t{}\t{lemma}\t{upos}\t{xpos}\t_\t_\t_\t_\t{misc}",
+            i + 1,
+            t.surface(),
+        )?;
+    }
+    wtr.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::SystemDictionaryBuilder;
+    use crate::Tokenizer;
+
+    #[test]
+    fn test_write_sentence() {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "本,0,0,1,名詞,一般,*,*,*,*,本\n読む,0,0,1,動詞,自立,*,*,五段・マ行,基本形,読む"
+                .as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0\nSPACE 0 1 0\n0x0020 SPACE".as_bytes(),
+            "DEFAULT,0,0,100,記号\nSPACE,0,0,100,記号".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict).ignore_space(true).unwrap();
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("本 読む!");
+        worker.tokenize();
+
+        let mut buf = vec![];
+        write_sentence(&worker, &mut buf, &ConlluSchema::new(Some(PosTagset::Ipadic))).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "1\t本\t本\tNOUN\t名詞-一般\t_\t_\t_\t_\t_\n\
+             2\t読む\t読む\tVERB\t動詞-自立\t_\t_\t_\t_\tSpaceAfter=No\n\
+             3\t!\t_\tSYM\t記号\t_\t_\t_\t_\t_\n\n",
+        );
+
+        let mut buf = vec![];
+        let schema = ConlluSchema::new(None).lemma_field(Some(4)).xpos(PosSchema::Major);
+        write_sentence(&worker, &mut buf, &schema).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "1\t本\t一般\t_\t名詞\t_\t_\t_\t_\t_\n\
+             2\t読む\t自立\t_\t動詞\t_\t_\t_\t_\tSpaceAfter=No\n\
+             3\t!\t_\t_\t記号\t_\t_\t_\t_\t_\n\n",
+        );
+    }
+}
@@ -6,7 +6,8 @@
 //! - システム辞書とユーザー辞書の読み込み
 //! - ゼロコピーデシリアライゼーションによる高速な辞書アクセス
 //! - メモリマップドファイルによる効率的なメモリ使用
-//! - Zstandard圧縮辞書の透過的な展開とキャッシング
+//! - 圧縮辞書（Zstandard、xz、LZ4、tarアーカイブ）の透過的な展開とキャッシング
+//! - 展開キャッシュとプルーフファイルの管理（[`cache`]モジュール）
 //! - プリセット辞書の自動ダウンロード機能
 //!
 //! # 辞書の読み込み方法
@@ -14,55 +15,106 @@
 //! 辞書は複数の方法で読み込むことができます:
 //!
 //! - [`Dictionary::from_path`]: ファイルパスから辞書を読み込む(推奨)
+//! - [`Dictionary::from_path_with_report`]: 読み込み時のメタデータとともに辞書を読み込む
 //! - [`Dictionary::read`]: リーダーから辞書を読み込む
+//! - [`Dictionary::from_compressed`]: 圧縮形式を自動判別して辞書を読み込む
 //! - [`Dictionary::from_zstd`]: Zstandard圧縮辞書を読み込む
 //! - [`Dictionary::from_preset_with_download`]: プリセット辞書をダウンロードして読み込む
 //!
+//! 読み込んだ辞書が配布物と一致することは、[`Dictionary::content_hash`]と[`hash_file`]で
+//! 確認できます。
+//!
+//! 圧縮辞書の読み込み、展開キャッシュ、ダイジェストの計算は`loaders`フィーチャーが、
+//! プリセット辞書のダウンロードは`download`フィーチャーが有効な場合にのみ使用できます。
+//!
 //! # 辞書のビルド
 //!
 //! [`SystemDictionaryBuilder`]を使用して、CSV形式のソースデータから辞書を構築できます。
 pub mod builder;
+pub mod cache;
 pub(crate) mod character;
+pub(crate) mod codec;
+pub(crate) mod compression;
 pub(crate) mod config;
 pub(crate) mod connector;
+pub(crate) mod feature_pool;
 pub(crate) mod fetch;
+pub(crate) mod header;
+pub(crate) mod integrity;
 pub(crate) mod lexicon;
+pub(crate) mod loaders;
 pub(crate) mod mapper;
+pub mod metadata;
+pub(crate) mod preset;
+pub(crate) mod report;
+pub(crate) mod surface;
 pub(crate) mod unknown;
+pub(crate) mod user;
 pub(crate) mod word_idx;
 
-use std::fs::{self, File, Metadata, create_dir_all};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "download")]
+pub use fetch::{DownloadOptions, ENV_MIRROR, ENV_OFFLINE, ENV_PROXY};
+#[cfg(feature = "download")]
+pub use preset::{
+    PresetManifest, register_preset, register_presets_from_file, registered_preset,
+    registered_presets,
+};
+
+#[cfg(feature = "loaders")]
+pub use loaders::{CacheStrategy, GLOBAL_CACHE_DIR, GLOBAL_DATA_DIR, hash_file};
+#[cfg(feature = "loaders")]
+pub(crate) use loaders::{compute_metadata_hash, sha256_hex};
+
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
 use std::ops::Deref;
 
-use std::path::PathBuf;
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
 use memmap2::Mmap;
 use rkyv::{Archived, access_unchecked};
 use rkyv::rancor::Error;
+use rkyv::with::Skip;
 use rkyv::util::AlignedVec;
 use rkyv::{
     access, api::serialize_using, ser::allocator::Arena, ser::sharing::Share,
-    ser::writer::IoWriter, ser::Serializer, util::with_arena, Archive, Deserialize,
+    ser::Serializer, util::with_arena, Archive, Deserialize,
     Serialize,
 };
-use sha2::{Digest, Sha256};
 
-use crate::dictionary::character::{ArchivedCharProperty, CharProperty};
-use crate::dictionary::connector::{ArchivedConnectorWrapper, Connector, ConnectorWrapper};
-use crate::dictionary::lexicon::{ArchivedLexicon, Lexicon};
+use crate::dictionary::character::ArchivedCharProperty;
+use crate::dictionary::connector::{
+    ArchivedConnectorWrapper, Connector, ConnectorCost, ConnectorView,
+};
+use crate::dictionary::feature_pool::{FeatureEncoding, FeaturePool};
+use crate::dictionary::header::SectionHeader;
+use crate::dictionary::lexicon::ArchivedLexicon;
 use crate::dictionary::mapper::ConnIdMapper;
-use crate::dictionary::unknown::{ArchivedUnkHandler, UnkHandler};
+use crate::dictionary::unknown::ArchivedUnkHandler;
+use crate::dictionary::surface::Surfaces;
+use crate::dictionary::user::LoadedUserDictionary;
 use crate::errors::{Result, VibratoError};
 
 pub use crate::dictionary::builder::SystemDictionaryBuilder;
+pub use crate::dictionary::character::{CategoryInfoView, CharProperty};
+#[cfg(feature = "loaders")]
+pub use crate::dictionary::compression::{CompressedHeader, WriteOptions};
+pub use crate::dictionary::connector::{
+    ConnectorWrapper, DualConnector, MatrixConnector, RawConnector,
+};
+pub use crate::dictionary::integrity::{IntegrityReport, InvalidWord};
+pub use crate::dictionary::lexicon::Lexicon;
+pub use crate::dictionary::report::{BufferKind, LoadReport, ProofLocation};
+pub use crate::dictionary::unknown::UnkHandler;
+pub use crate::dictionary::user::{USER_DICTIONARY_MAGIC, UserDictionary};
 pub use crate::dictionary::word_idx::WordIdx;
 
 pub(crate) use crate::dictionary::lexicon::WordParam;
 
 #[cfg(feature = "download")]
 pub use crate::dictionary::config::PresetDictionaryKind;
+pub use crate::dictionary::metadata::DictionaryMetadata;
 
 /// Vibratoトークナイザーを識別するマジックバイト。
 ///
@@ -83,34 +135,6 @@ const DATA_START: usize = MODEL_MAGIC_LEN + PADDING_LEN;
 /// プレフィックスです。
 pub const LEGACY_MODEL_MAGIC_PREFIX: &[u8] = b"VibratoTokenizer 0.";
 
-/// グローバルキャッシュディレクトリのパス。
-///
-/// ユーザー固有のシステムキャッシュディレクトリ内の`vibrato-rkyv`サブディレクトリを指します。
-/// 各プラットフォームでの標準的なキャッシュディレクトリ:
-/// - Linux: `$XDG_CACHE_HOME/vibrato-rkyv` または `$HOME/.cache/vibrato-rkyv`
-/// - macOS: `$HOME/Library/Caches/vibrato-rkyv`
-/// - Windows: `{FOLDERID_LocalAppData}/vibrato-rkyv`
-pub static GLOBAL_CACHE_DIR: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
-    let path = dirs::cache_dir()?.join("vibrato-rkyv");
-    fs::create_dir_all(&path).ok()?;
-
-    Some(path)
-});
-
-/// グローバルデータディレクトリのパス。
-///
-/// ユーザー固有のローカルデータディレクトリ内の`vibrato-rkyv`サブディレクトリを指します。
-/// 各プラットフォームでの標準的なデータディレクトリ:
-/// - Linux: `$XDG_DATA_HOME/vibrato-rkyv` または `$HOME/.local/share/vibrato-rkyv`
-/// - macOS: `$HOME/Library/Application Support/vibrato-rkyv`
-/// - Windows: `{FOLDERID_LocalAppData}/vibrato-rkyv`
-pub static GLOBAL_DATA_DIR: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
-    let path = dirs::data_local_dir()?.join("vibrato-rkyv");
-    fs::create_dir_all(&path).ok()?;
-
-    Some(path)
-});
-
 /// 辞書の読み込みモード。
 ///
 /// 辞書ファイルを読み込む際の検証戦略を指定します。
@@ -128,45 +152,10 @@ pub enum LoadMode {
     /// このモードでは、ファイルメタデータに基づくハッシュを使用して、
     /// 検証済みであることを確認します。高速な読み込みが可能ですが、
     /// ファイルが置き換えられるTOCTOU攻撃に対して脆弱です。
-    TrustCache,
-}
-
-/// Zstandardアーカイブから展開された辞書のキャッシング戦略を指定します。
-///
-/// 辞書ファイルが圧縮されている場合、展開後のデータをどこにキャッシュするかを制御します。
-pub enum CacheStrategy {
-    /// 圧縮辞書と同じディレクトリに`.cache`サブディレクトリを作成します。
     ///
-    /// この戦略は、キャッシュデータを元のファイルと並べて保持します。
-    /// 親ディレクトリが書き込み可能でない場合は失敗します。
-    Local,
-
-    /// オペレーティングシステムに適した、共有のユーザー固有キャッシュディレクトリを使用します。
-    ///
-    /// ほとんどのアプリケーションに適したデフォルトの選択肢です。
-    /// 特に辞書ファイルが読み取り専用の場所に保存されている場合に有用です。
-    /// パスは`dirs::cache_dir()`によって決定されます。
-    ///
-    /// | プラットフォーム | 値                             | 例                               |
-    /// | -------- | --------------------------------- | ------------------------------------- |
-    /// | Linux    | `$XDG_CACHE_HOME` または `$HOME/.cache` | `/home/alice/.cache`                  |
-    /// | macOS    | `$HOME/Library/Caches`            | `/Users/Alice/Library/Caches`         |
-    /// | Windows  | `{FOLDERID_LocalAppData}`         | `C:\Users\Alice\AppData\Local`        |
-    ///
-    GlobalCache,
-
-    /// オペレーティングシステムに適した、共有のユーザー固有データディレクトリを使用します。
-    ///
-    /// `GlobalCache`に似ていますが、永続的で非ローミングのアプリケーションデータ用の
-    /// ディレクトリを使用します。パスは`dirs::data_local_dir()`によって決定されます。
-    ///
-    /// | プラットフォーム | 値                                     | 例                               |
-    /// | -------- | ----------------------------------------- | ------------------------------------- |
-    /// | Linux    | `$XDG_DATA_HOME` または `$HOME/.local/share`  | `/home/alice/.local/share`            |
-    /// | macOS    | `$HOME/Library/Application Support`       | `/Users/Alice/Library/Application Support` |
-    /// | Windows  | `{FOLDERID_LocalAppData}`                 | `C:\Users\Alice\AppData\Local`        |
-    ///
-    GlobalData,
+    /// プルーフファイルは`loaders`フィーチャーが有効な場合にのみ使用されます。
+    /// 無効な場合は`Validate`と同様に毎回検証します。
+    TrustCache,
 }
 
 /// [`Dictionary`]の内部データ。
@@ -182,17 +171,28 @@ pub struct DictionaryInner {
     mapper: Option<ConnIdMapper>,
     char_prop: CharProperty,
     unk_handler: UnkHandler,
+    // How the detached features are written. See `dictionary::feature_pool`.
+    #[rkyv(with = Skip)]
+    feature_encoding: FeatureEncoding,
+    // User lexicons layered after `user_lexicon` by `Dictionary::from_paths()`. They are not
+    // written to the dictionary file.
+    #[rkyv(with = Skip)]
+    user_layers: Vec<Lexicon>,
 }
 
 /// メモリバッファ(mmapまたはヒープ)を所有し、アーカイブされた辞書へのアクセスを提供するラッパー。
 ///
 /// この列挙型は、辞書データを保持するための2つの異なるメモリ戦略を表します:
 /// - `Mmap`: メモリマップドファイルによるゼロコピーアクセス
-/// - `Aligned`: ヒープ上のアライメント済みバッファ
+/// - `Aligned`: ヒープ上のアライメント済みバッファと、読み込んだファイルのヘッダ
+/// - `AlignedFile`: 呼び出し側から受け取った、ファイル全体を保持するヒープ上のバッファ
+/// - `Static`: 呼び出し側が提供する`'static`なメモリ領域上のファイル全体
 #[allow(dead_code)]
 enum DictBuffer {
     Mmap(Mmap),
-    Aligned(AlignedVec<16>),
+    Aligned(AlignedVec<16>, SectionHeader),
+    AlignedFile(AlignedVec<16>),
+    Static(&'static [u8]),
 }
 
 /// トークン化のための読み取り専用辞書。
@@ -206,6 +206,8 @@ pub enum Dictionary {
     Owned {
         dict: Arc<DictionaryInner>,
         _caching_handle: Option<Arc<std::thread::JoinHandle<Result<()>>>>,
+        #[cfg(feature = "loaders")]
+        content_hash: OnceLock<String>,
     },
 }
 
@@ -216,6 +218,156 @@ pub enum Dictionary {
 pub struct ArchivedDictionary {
     _buffer: DictBuffer,
     data: &'static ArchivedDictionaryInner,
+    metadata: Option<DictionaryMetadata>,
+    #[cfg(feature = "loaders")]
+    content_hash: OnceLock<String>,
+    // User dictionaries loaded separately, replacing the one in the file when not empty
+    users: Vec<LoadedUserDictionary>,
+    surfaces: OnceLock<Option<Surfaces>>,
+    features: OnceLock<Option<FeaturePool<'static>>>,
+}
+
+impl ArchivedDictionary {
+    #[inline(always)]
+    fn new(
+        buffer: DictBuffer,
+        data: &'static ArchivedDictionaryInner,
+        metadata: Option<DictionaryMetadata>,
+    ) -> Self {
+        Self {
+            _buffer: buffer,
+            data,
+            metadata,
+            #[cfg(feature = "loaders")]
+            content_hash: OnceLock::new(),
+            users: vec![],
+            surfaces: OnceLock::new(),
+            features: OnceLock::new(),
+        }
+    }
+
+    /// 辞書ファイルに追記された素性を取得します。
+    ///
+    /// 素性は最初に参照されたときに検証され、圧縮されている場合は展開されます。
+    fn feature_pool(&self) -> Option<&FeaturePool<'static>> {
+        self.features
+            .get_or_init(|| {
+                let bytes = match &self._buffer {
+                    DictBuffer::Mmap(mmap) => mmap.get(DATA_START..)?,
+                    DictBuffer::Aligned(bytes, _) => &bytes[..],
+                    DictBuffer::AlignedFile(bytes) => bytes.get(DATA_START..)?,
+                    DictBuffer::Static(bytes) => bytes.get(DATA_START..)?,
+                };
+                // SAFETY: The buffer is owned by `self` and never moved or modified while
+                // `self` is alive, as with `data`.
+                let bytes: &'static [u8] = unsafe { &*(bytes as *const [u8]) };
+                feature_pool::read_trailer(bytes).ok().flatten()
+            })
+            .as_ref()
+    }
+
+    /// 辞書ファイルに追記された表層形を取得します。
+    ///
+    /// 表層形は最初に参照されたときに読み込まれます。
+    fn surfaces(&self) -> Option<&Surfaces> {
+        self.surfaces
+            .get_or_init(|| {
+                let bytes = match &self._buffer {
+                    DictBuffer::Mmap(mmap) => mmap.get(DATA_START..)?,
+                    DictBuffer::Aligned(bytes, _) => &bytes[..],
+                    DictBuffer::AlignedFile(bytes) => bytes.get(DATA_START..)?,
+                    DictBuffer::Static(bytes) => bytes.get(DATA_START..)?,
+                };
+                surface::read_trailer(bytes).ok().flatten()
+            })
+            .as_ref()
+    }
+
+    /// 指定された単語の表層形への参照を取得します。
+    #[inline(always)]
+    pub(crate) fn word_surface(&self, word_idx: WordIdx) -> Option<&str> {
+        let word_id = usize::try_from(word_idx.word_id).ok()?;
+        match (self.users.is_empty(), word_idx.lex_type) {
+            (_, LexType::System) => self.surfaces()?.system.get(word_id).map(String::as_str),
+            (false, LexType::User) => {
+                let (user, word_idx) = self.user_word(word_idx)?;
+                user.word_surface(word_idx.word_id as usize)
+            }
+            (true, LexType::User) => {
+                self.surfaces()?.user.as_ref()?.get(word_id).map(String::as_str)
+            }
+            (_, LexType::Unknown) => None,
+        }
+    }
+
+    /// ユーザー辞書を優先順に取得します。
+    ///
+    /// [`Dictionary::load_user_dictionary`]や[`Dictionary::from_paths`]で付加された
+    /// ユーザー辞書がある場合は、辞書ファイルに含まれるユーザー辞書の代わりにそれらを返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 各ユーザー辞書の単語IDのオフセットと語彙の組のイテレータ。
+    /// [`DictionaryInner::user_lexicons()`]を参照してください。
+    #[inline(always)]
+    pub(crate) fn user_lexicons(&self) -> impl Iterator<Item = (u32, &ArchivedLexicon)> {
+        let embedded = if self.users.is_empty() {
+            self.data.user_lexicon().as_ref()
+        } else {
+            None
+        };
+        let mut offset = 0;
+        embedded
+            .into_iter()
+            .chain(self.users.iter().map(LoadedUserDictionary::lexicon))
+            .map(move |lexicon| {
+                let layer = (offset, lexicon);
+                offset += lexicon.num_words() as u32;
+                layer
+            })
+    }
+
+    /// 付加されたユーザー辞書のうち単語を含むものと、辞書内の単語インデックスを取得します。
+    #[inline(always)]
+    fn user_word(&self, word_idx: WordIdx) -> Option<(&LoadedUserDictionary, WordIdx)> {
+        let mut offset = 0;
+        for user in &self.users {
+            let num_words = user.lexicon().num_words() as u32;
+            if let Some(word_id) = word_idx.word_id.checked_sub(offset).filter(|&id| id < num_words) {
+                return Some((user, WordIdx::new(LexType::User, word_id)));
+            }
+            offset += num_words;
+        }
+        None
+    }
+
+    /// 指定された単語のパラメータを取得します。
+    #[inline(always)]
+    pub(crate) fn word_param(&self, word_idx: WordIdx) -> WordParam {
+        match (self.users.is_empty(), word_idx.lex_type) {
+            (false, LexType::User) => {
+                let (user, word_idx) = self.user_word(word_idx).unwrap();
+                user.lexicon().word_param(word_idx)
+            }
+            _ => self.data.word_param(word_idx),
+        }
+    }
+
+    /// 指定された単語の素性文字列への参照を取得します。
+    #[inline(always)]
+    pub fn word_feature(&self, word_idx: WordIdx) -> &str {
+        match (self.users.is_empty(), word_idx.lex_type) {
+            (false, LexType::User) => {
+                let (user, word_idx) = self.user_word(word_idx).unwrap();
+                user.lexicon().word_feature(word_idx)
+            }
+            (_, LexType::Unknown) => self.data.word_feature(word_idx),
+            (_, lex_type) => self
+                .feature_pool()
+                .and_then(|pool| pool.get(lex_type, word_idx.word_id))
+                .unwrap_or_else(|| self.data.word_feature(word_idx)),
+        }
+    }
 }
 
 /// 辞書内部データへの参照(アーカイブ版または所有版)。
@@ -223,7 +375,7 @@ pub struct ArchivedDictionary {
 /// 辞書の実装の詳細を隠蔽し、アーカイブ版と所有版の両方に対して
 /// 統一的なインターフェースを提供します。
 pub(crate) enum DictionaryInnerRef<'a> {
-    Archived(&'a ArchivedDictionaryInner),
+    Archived(&'a ArchivedDictionary),
     Owned(&'a DictionaryInner),
 }
 
@@ -256,6 +408,7 @@ impl Deref for ArchivedDictionary {
 )]
 #[repr(u8)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LexType {
     /// システム辞書。
     ///
@@ -287,6 +440,51 @@ impl ArchivedLexType {
     }
 }
 
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::LexType> for LexType {
+    fn from(lex_type: crate::legacy::dictionary::LexType) -> Self {
+        use crate::legacy::dictionary::LexType as Legacy;
+
+        match lex_type {
+            Legacy::System => Self::System,
+            Legacy::User => Self::User,
+            Legacy::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// レガシー形式（`VibratoTokenizer 0.5`）の辞書を変換します。
+///
+/// 各コンポーネントをフィールドごとに対応付けて変換するため、メモリレイアウトには
+/// 依存しません。レガシー形式は表層形を保持しないため、変換後の辞書にも表層形は
+/// 含まれません。
+#[cfg(feature = "legacy")]
+impl TryFrom<crate::legacy::dictionary::DictionaryInner> for DictionaryInner {
+    type Error = VibratoError;
+
+    fn try_from(dict: crate::legacy::dictionary::DictionaryInner) -> Result<Self> {
+        // Destructured exhaustively so that a change on either side fails to compile here.
+        let crate::legacy::dictionary::DictionaryInner {
+            system_lexicon,
+            user_lexicon,
+            connector,
+            mapper,
+            char_prop,
+            unk_handler,
+        } = dict;
+        Ok(Self {
+            system_lexicon: system_lexicon.into(),
+            user_lexicon: user_lexicon.map(Lexicon::from),
+            connector: connector.into(),
+            mapper: mapper.map(ConnIdMapper::from),
+            char_prop: char_prop.try_into()?,
+            unk_handler: unk_handler.into(),
+            feature_encoding: FeatureEncoding::default(),
+            user_layers: vec![],
+        })
+    }
+}
+
 impl Drop for Dictionary {
     fn drop(&mut self) {
         if let Dictionary::Owned { _caching_handle, .. } = self
@@ -299,6 +497,119 @@ impl Drop for Dictionary {
 }
 
 impl DictionaryInner {
+    /// 個別に構築したコンポーネントから辞書を組み立てます。
+    ///
+    /// CSVを経由せずに、独自に構築した語彙や学習済みのコネクタなどを
+    /// 組み合わせて辞書を作成したい場合に使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `system_lexicon` - [`LexType::System`]として構築されたシステム辞書
+    /// * `user_lexicon` - [`LexType::User`]として構築されたユーザー辞書
+    /// * `connector` - 接続コスト計算に使用するコネクタ
+    /// * `char_prop` - 文字プロパティ
+    /// * `unk_handler` - `char_prop`に基づいて構築された未知語ハンドラ
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`DictionaryInner`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - 語彙の種類が引数の役割と一致しない場合。
+    /// - 語彙または未知語ハンドラに、コネクタの範囲外の接続IDが含まれている場合。
+    /// - 未知語ハンドラのカテゴリ数が文字プロパティと一致しない場合。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use vibrato_rkyv::dictionary::{
+    ///     CharProperty, ConnectorWrapper, DictionaryInner, LexType, Lexicon, MatrixConnector,
+    ///     UnkHandler,
+    /// };
+    /// use vibrato_rkyv::Tokenizer;
+    ///
+    /// let system_lexicon = Lexicon::from_reader("自然,0,0,1,sizen".as_bytes(), LexType::System)?;
+    /// let connector = MatrixConnector::from_reader("1 1\n0 0 0".as_bytes())?;
+    /// let char_prop = CharProperty::from_reader("DEFAULT 0 1 0".as_bytes())?;
+    /// let unk_handler = UnkHandler::from_reader("DEFAULT,0,0,100,*".as_bytes(), &char_prop)?;
+    ///
+    /// let dict = DictionaryInner::from_parts(
+    ///     system_lexicon,
+    ///     None,
+    ///     ConnectorWrapper::Matrix(connector),
+    ///     char_prop,
+    ///     unk_handler,
+    /// )?;
+    ///
+    /// let tokenizer = Tokenizer::from_inner(dict);
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("自然");
+    /// worker.tokenize();
+    /// assert_eq!(worker.token(0).feature(), "sizen");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_parts(
+        system_lexicon: Lexicon,
+        user_lexicon: Option<Lexicon>,
+        connector: ConnectorWrapper,
+        char_prop: CharProperty,
+        unk_handler: UnkHandler,
+    ) -> Result<Self> {
+        if system_lexicon.lex_type() != LexType::System {
+            return Err(VibratoError::invalid_argument(
+                "system_lexicon",
+                "must be built with LexType::System.",
+            ));
+        }
+        if !system_lexicon.verify(&connector) {
+            return Err(VibratoError::invalid_argument(
+                "system_lexicon",
+                "includes invalid connection ids.",
+            ));
+        }
+        if let Some(user_lexicon) = user_lexicon.as_ref() {
+            if user_lexicon.lex_type() != LexType::User {
+                return Err(VibratoError::invalid_argument(
+                    "user_lexicon",
+                    "must be built with LexType::User.",
+                ));
+            }
+            if !user_lexicon.verify(&connector) {
+                return Err(VibratoError::invalid_argument(
+                    "user_lexicon",
+                    "includes invalid connection ids.",
+                ));
+            }
+        }
+        if !unk_handler.verify(&connector) {
+            return Err(VibratoError::invalid_argument(
+                "unk_handler",
+                "includes invalid connection ids.",
+            ));
+        }
+        if unk_handler.num_categories() != char_prop.num_categories() {
+            return Err(VibratoError::invalid_argument(
+                "unk_handler",
+                "was not built with the given char_prop.",
+            ));
+        }
+
+        Ok(Self {
+            system_lexicon,
+            user_lexicon,
+            connector,
+            mapper: None,
+            char_prop,
+            unk_handler,
+            feature_encoding: FeatureEncoding::default(),
+            user_layers: vec![],
+        })
+    }
+
     /// システム辞書への参照を取得します。
     ///
     /// # 戻り値
@@ -309,14 +620,30 @@ impl DictionaryInner {
         &self.system_lexicon
     }
 
-    /// ユーザー辞書への参照を取得します。
+    /// ユーザー辞書を優先順に取得します。
     ///
     /// # 戻り値
     ///
-    /// ユーザー辞書が存在する場合は`Some(&Lexicon)`、存在しない場合は`None`。
+    /// 各ユーザー辞書の単語IDのオフセットと語彙の組のイテレータ。ユーザー辞書の単語IDは
+    /// すべての辞書を通した通し番号で、各辞書内の単語IDにオフセットを加えた値です。
+    #[inline(always)]
+    pub(crate) fn user_lexicons(&self) -> impl Iterator<Item = (u32, &Lexicon)> {
+        let mut offset = 0;
+        self.user_lexicon.iter().chain(&self.user_layers).map(move |lexicon| {
+            let layer = (offset, lexicon);
+            offset += lexicon.num_words() as u32;
+            layer
+        })
+    }
+
+    /// ユーザー辞書の単語を含む語彙と、語彙内の単語インデックスを取得します。
     #[inline(always)]
-    pub(crate) const fn user_lexicon(&self) -> Option<&Lexicon> {
-        self.user_lexicon.as_ref()
+    fn user_word(&self, word_idx: WordIdx) -> Option<(&Lexicon, WordIdx)> {
+        self.user_lexicons().find_map(|(offset, lexicon)| {
+            let word_id = word_idx.word_id.checked_sub(offset)?;
+            ((word_id as usize) < lexicon.num_words())
+                .then(|| (lexicon, WordIdx::new(LexType::User, word_id)))
+        })
     }
 
     /// 接続ID用のマッパーへの参照を取得します。
@@ -363,11 +690,138 @@ impl DictionaryInner {
     pub fn word_feature(&self, word_idx: WordIdx) -> &str {
         match word_idx.lex_type {
             LexType::System => self.system_lexicon().word_feature(word_idx),
-            LexType::User => self.user_lexicon().unwrap().word_feature(word_idx),
+            LexType::User => {
+                let (lexicon, word_idx) = self.user_word(word_idx).unwrap();
+                lexicon.word_feature(word_idx)
+            }
             LexType::Unknown => self.unk_handler().word_feature(word_idx),
         }
     }
 
+    /// 指定された単語の表層形への参照を取得します。
+    ///
+    /// # 引数
+    ///
+    /// * `word_idx` - 単語のインデックス。辞書の種類と位置を含みます。
+    ///
+    /// # 戻り値
+    ///
+    /// 表層形への参照。未知語の場合や、表層形を持たない辞書の場合は`None`。
+    #[inline(always)]
+    pub fn word_surface(&self, word_idx: WordIdx) -> Option<&str> {
+        match word_idx.lex_type {
+            LexType::System => self.system_lexicon().word_surface(word_idx),
+            LexType::User => {
+                let (lexicon, word_idx) = self.user_word(word_idx)?;
+                lexicon.word_surface(word_idx)
+            }
+            LexType::Unknown => None,
+        }
+    }
+
+    /// 素性を辞書データから切り離して書き込むように設定します。
+    ///
+    /// 素性は[`write()`](Self::write)で辞書データとは別の領域に書き込まれ、
+    /// 読み込んだ辞書で最初に参照されたときに初めてメモリに読み込まれます。
+    /// 素性を参照しない分割処理で使用するメモリの範囲が小さくなるため、
+    /// 大きな辞書をメモリマップで読み込む場合に有効です。
+    ///
+    /// 出力された辞書ファイルは、この形式に対応していない以前のバージョンでは
+    /// 読み込めません。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新された`DictionaryInner`インスタンス。
+    pub fn shard_features(mut self) -> Self {
+        self.system_lexicon.detach_features();
+        if let Some(lexicon) = self.user_lexicon.as_mut() {
+            lexicon.detach_features();
+        }
+        self
+    }
+
+    /// 語彙と未知語定義の素性を、先頭から指定された数の項目までに切り詰めます。
+    ///
+    /// 読みや発音などの後方の項目を使用しない場合に、辞書を小さくするために使用します。
+    /// 引用符で囲まれた項目内のカンマは区切りとして扱いません。
+    ///
+    /// # 引数
+    ///
+    /// * `num_fields` - 残す素性の項目数
+    ///
+    /// # 戻り値
+    ///
+    /// 素性が切り詰められた`DictionaryInner`インスタンス。
+    pub fn truncate_features(mut self, num_fields: usize) -> Self {
+        self.system_lexicon.truncate_features(num_fields);
+        if let Some(lexicon) = self.user_lexicon.as_mut() {
+            lexicon.truncate_features(num_fields);
+        }
+        self.unk_handler.truncate_features(num_fields);
+        self
+    }
+
+    /// 素性を切り離し、同一の素性を一度だけ格納するように設定します。
+    ///
+    /// [`shard_features()`](Self::shard_features)の設定に加えて、同じ素性を持つ単語の間で
+    /// 素性文字列を共有します。UniDicのように多くの単語が同じ素性を持つ辞書では、
+    /// 辞書ファイルが大幅に小さくなります。
+    ///
+    /// 出力された辞書ファイルは、この形式に対応していない以前のバージョンでは
+    /// 読み込めません。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新された`DictionaryInner`インスタンス。
+    pub fn dedup_features(mut self) -> Self {
+        self.feature_encoding = FeatureEncoding::Dedup;
+        self.shard_features()
+    }
+
+    /// 素性を切り離し、重複を除いた上でzstdで圧縮するように設定します。
+    ///
+    /// [`dedup_features()`](Self::dedup_features)の設定に加えて、素性文字列をまとめて
+    /// 圧縮します。圧縮された素性は、読み込んだ辞書で最初に参照されたときに
+    /// ヒープ上に展開されます。素性を参照しない分割処理では展開されません。
+    ///
+    /// 出力された辞書ファイルは、この形式に対応していない以前のバージョンでは
+    /// 読み込めません。
+    ///
+    /// # 引数
+    ///
+    /// * `level` - zstdの圧縮レベル
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新された`DictionaryInner`インスタンス。
+    #[cfg(feature = "loaders")]
+    pub fn compress_features(mut self, level: i32) -> Self {
+        self.feature_encoding = FeatureEncoding::Compressed(level);
+        self.shard_features()
+    }
+
+    /// 辞書ファイルから読み込まれた、切り離された素性を設定します。
+    ///
+    /// 素性の書き込み形式も読み込まれた辞書ファイルに合わせます。
+    pub(crate) fn set_detached_features(&mut self, features: &FeaturePool) {
+        self.feature_encoding = features.encoding();
+        let (system, user) = features.to_vecs();
+        if let Some(system) = system {
+            self.system_lexicon.set_detached_features(system);
+        }
+        if let (Some(lexicon), Some(user)) = (self.user_lexicon.as_mut(), user) {
+            lexicon.set_detached_features(user);
+        }
+    }
+
+    /// 辞書ファイルから読み込まれた表層形を設定します。
+    pub(crate) fn set_surfaces(&mut self, surfaces: Surfaces) {
+        self.system_lexicon.set_surfaces(surfaces.system);
+        if let (Some(lexicon), Some(user)) = (self.user_lexicon.as_mut(), surfaces.user) {
+            lexicon.set_surfaces(user);
+        }
+    }
+
     /// コネクタへの参照を取得します。
     ///
     /// # 戻り値
@@ -390,7 +844,10 @@ impl DictionaryInner {
     pub(crate) fn word_param(&self, word_idx: WordIdx) -> WordParam {
         match word_idx.lex_type {
             LexType::System => self.system_lexicon().word_param(word_idx),
-            LexType::User => self.user_lexicon().as_ref().unwrap().word_param(word_idx),
+            LexType::User => {
+                let (lexicon, word_idx) = self.user_word(word_idx).unwrap();
+                lexicon.word_param(word_idx)
+            }
             LexType::Unknown => self.unk_handler().word_param(word_idx),
         }
     }
@@ -400,6 +857,14 @@ impl DictionaryInner {
     /// この関数の出力バイナリは、`Dictionary::from_path`などの`vibrato-rkyv`の
     /// 読み込みメソッドが期待する形式です。
     ///
+    /// # 再現性
+    ///
+    /// [`SystemDictionaryBuilder`]で同じソースファイルから構築した辞書は、構築に使用した
+    /// スレッド数や実行環境によらず、常に同じバイト列にシリアライズされます。
+    /// 出力には構築日時などの実行ごとに変わる値は含まれないため、配布された辞書の
+    /// ダイジェストを、ソースファイルから再構築して確認できます。ビルド日時を記録する場合は、
+    /// [`write_with_metadata`](Self::write_with_metadata)で明示的に埋め込んでください。
+    ///
     /// # Examples
     ///
     /// この例では、メモリ内のCSVデータから辞書を構築し、
@@ -435,27 +900,63 @@ impl DictionaryInner {
     where
         W: Write,
     {
-        wtr.write_all(MODEL_MAGIC)?;
-
-        let padding_bytes = vec![0xFF; PADDING_LEN];
-        wtr.write_all(&padding_bytes)?;
-
-        with_arena(|arena: &mut Arena| {
-            let writer = IoWriter::new(&mut wtr);
-            let mut serializer = Serializer::new(writer, arena.acquire(), Share::new());
-            serialize_using::<_, rkyv::rancor::Error>(self, &mut serializer)
+        // The archive is serialized in memory first so that its length can be recorded in
+        // the header.
+        let archive = with_arena(|arena: &mut Arena| {
+            let mut serializer =
+                Serializer::new(AlignedVec::<16>::new(), arena.acquire(), Share::new());
+            serialize_using::<_, rkyv::rancor::Error>(self, &mut serializer)?;
+            Ok::<_, rkyv::rancor::Error>(serializer.into_writer())
         })
         .map_err(|e| {
             VibratoError::invalid_state("rkyv serialization failed".to_string(), e.to_string())
         })?;
 
+        wtr.write_all(MODEL_MAGIC)?;
+        wtr.write_all(&SectionHeader::new(archive.len())?.to_bytes())?;
+        wtr.write_all(&archive)?;
+
+        let system = self.system_lexicon.detached_features();
+        let user = self.user_lexicon.as_ref().and_then(Lexicon::detached_features);
+        if system.is_some() || user.is_some() {
+            feature_pool::write_trailer(&mut wtr, system, user, self.feature_encoding)?;
+        }
+
+        if let Some(system) = self.system_lexicon.surfaces() {
+            let user = self.user_lexicon.as_ref().and_then(Lexicon::surfaces);
+            surface::write_trailer(wtr, system, user)?;
+        }
+
         Ok(())
     }
 
+    /// メタデータを埋め込んで辞書データをライターにシリアライズします。
+    ///
+    /// メタデータは辞書データの後ろに追記され、読み込んだ辞書の
+    /// [`Dictionary::metadata`]で取得できます。出力は[`write`](Self::write)と同様に
+    /// `Dictionary::from_path`などで読み込めます。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `metadata` - 埋め込むメタデータ
+    ///
+    /// # エラー
+    ///
+    /// 書き込みやシリアライズに失敗した場合に[`VibratoError`]を返します。
+    pub fn write_with_metadata<W>(&self, mut wtr: W, metadata: &DictionaryMetadata) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write(&mut wtr)?;
+        metadata.write_trailer(wtr)
+    }
+
     /// リーダーからユーザー辞書をリセットします。
     ///
     /// この関数は、辞書をシリアライズする前に呼び出す必要があります。
     /// ユーザー辞書を新しいデータで置き換えるか、削除します。
+    /// [`Dictionary::from_paths()`]で重ねられたユーザー辞書はすべて削除されます。
     ///
     /// # 引数
     ///
@@ -489,6 +990,7 @@ impl DictionaryInner {
         } else {
             self.user_lexicon = None;
         }
+        self.user_layers.clear();
         Ok(self)
     }
 
@@ -517,7 +1019,7 @@ impl DictionaryInner {
     {
         let mapper = ConnIdMapper::from_iter(lmap, rmap)?;
         self.system_lexicon.map_connection_ids(&mapper);
-        if let Some(user_lexicon) = self.user_lexicon.as_mut() {
+        for user_lexicon in self.user_lexicon.iter_mut().chain(&mut self.user_layers) {
             user_lexicon.map_connection_ids(&mapper);
         }
         self.connector.map_connection_ids(&mapper);
@@ -538,7 +1040,12 @@ impl Dictionary {
     ///
     /// 新しい`Dictionary`インスタンス。
     pub fn from_inner(dict: DictionaryInner) -> Self {
-        Self::Owned{ dict: Arc::new(dict), _caching_handle: None }
+        Self::Owned {
+            dict: Arc::new(dict),
+            _caching_handle: None,
+            #[cfg(feature = "loaders")]
+            content_hash: OnceLock::new(),
+        }
     }
 
     /// 辞書データを`rkyv`フォーマットを使用してライターにシリアライズします。
@@ -594,43 +1101,383 @@ impl Dictionary {
     }
 
 
-    /// すべてのデータをヒープバッファに読み込むことで、リーダーから辞書を作成します。
+    /// 辞書ファイルに埋め込まれたメタデータを取得します。
     ///
-    /// これは、ファイルパスが利用できない場合(例: メモリ内バッファからの読み込み)の
-    /// フォールバックです。すべてのコンテンツをメモリに読み込むため、
-    /// `from_path`よりもメモリ効率が低くなります。
+    /// メタデータは[`DictionaryInner::write_with_metadata`]で埋め込まれます。
+    /// メタデータを持たない辞書ファイルや、所有型の辞書では`None`を返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, LoadMode};
+    /// let dict = Dictionary::from_path("path/to/system.dic", LoadMode::TrustCache).unwrap();
+    /// if let Some(metadata) = dict.metadata() {
+    ///     println!("{:?} ({:?})", metadata.name(), metadata.license());
+    /// }
+    /// ```
+    pub fn metadata(&self) -> Option<&DictionaryMetadata> {
+        match self {
+            Dictionary::Archived(dict) => dict.metadata.as_ref(),
+            Dictionary::Owned { .. } => None,
+        }
+    }
+
+    /// 指定された単語の素性文字列への参照を取得します。
+    ///
+    /// [`Token::word_idx`](crate::token::Token::word_idx)で得られる[`WordIdx`]を保存しておけば、
+    /// 素性文字列を複製せずに後から参照できます。[`WordIdx`]が有効である条件は、
+    /// [`WordIdx`]のドキュメントを参照してください。
     ///
     /// # 引数
     ///
-    /// * `rdr` - `std::io::Read`を実装するリーダー。
+    /// * `word_idx` - 単語のインデックス
     ///
     /// # 戻り値
     ///
-    /// 新しい`Dictionary`インスタンス。
+    /// 素性文字列への参照
     ///
-    /// # エラー
+    /// # パニック
     ///
-    /// この関数は以下の場合にエラーを返します:
-    /// - データを読み込めない場合。
-    /// - コンテンツが無効な場合。
-    pub fn read<R: Read>(mut rdr: R) -> Result<Self> {
-        let mut magic = [0; MODEL_MAGIC_LEN];
-        rdr.read_exact(&mut magic)?;
-
-        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
-            return Err(VibratoError::invalid_argument(
-                "rdr",
-                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
-            ));
-        }else if !magic.starts_with(MODEL_MAGIC) {
-            return Err(VibratoError::invalid_argument(
-                "rdr",
-                "The magic number of the input model mismatches.",
-            ));
+    /// `word_idx`がこの辞書に存在しない単語や[`WordIdx::SPACE`]、
+    /// [`UnkProvider`](crate::tokenizer::UnkProvider)が生成した語を指す場合にパニックします。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use vibrato_rkyv::{Dictionary, LoadMode, Tokenizer};
+    /// let dict = Arc::new(Dictionary::from_path("path/to/system.dic", LoadMode::TrustCache).unwrap());
+    /// let tokenizer = Tokenizer::from_shared_dictionary(dict.clone());
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("東京都に行った");
+    /// worker.tokenize();
+    /// let word_idx = worker.token(0).word_idx();
+    ///
+    /// println!("{:?} {}", dict.word_surface(word_idx), dict.word_feature(word_idx));
+    /// ```
+    #[inline(always)]
+    pub fn word_feature(&self, word_idx: WordIdx) -> &str {
+        match self {
+            Dictionary::Archived(dict) => dict.word_feature(word_idx),
+            Dictionary::Owned { dict, .. } => dict.word_feature(word_idx),
+        }
+    }
+
+    /// 指定された単語の表層形への参照を取得します。
+    ///
+    /// 表層形は辞書ファイルの末尾に追記されるため、このバージョンより前に書き出された
+    /// 辞書ファイルでは取得できません。未知語は辞書に表層形を持たないため、
+    /// 常に`None`を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `word_idx` - 単語のインデックス
+    ///
+    /// # 戻り値
+    ///
+    /// 表層形への参照。未知語の場合、単語が存在しない場合、または表層形を持たない
+    /// 辞書の場合は`None`。
+    #[inline(always)]
+    pub fn word_surface(&self, word_idx: WordIdx) -> Option<&str> {
+        match self {
+            Dictionary::Archived(dict) => dict.word_surface(word_idx),
+            Dictionary::Owned { dict, .. } => dict.word_surface(word_idx),
+        }
+    }
+
+    /// 左接続IDの数を取得します。
+    ///
+    /// 左接続IDは`0..num_left_ids()`の範囲の値をとります。
+    #[inline(always)]
+    pub fn num_left_ids(&self) -> usize {
+        match self {
+            Dictionary::Archived(dict) => dict.connector().num_left(),
+            Dictionary::Owned { dict, .. } => dict.connector().num_left(),
+        }
+    }
+
+    /// 右接続IDの数を取得します。
+    ///
+    /// 右接続IDは`0..num_right_ids()`の範囲の値をとります。
+    #[inline(always)]
+    pub fn num_right_ids(&self) -> usize {
+        match self {
+            Dictionary::Archived(dict) => dict.connector().num_right(),
+            Dictionary::Owned { dict, .. } => dict.connector().num_right(),
+        }
+    }
+
+    /// 語彙と未知語の定義が接続表と整合しているかを検査します。
+    ///
+    /// 読み込み時の検証はデータ構造の妥当性のみを確認するため、接続表の範囲外の接続IDを
+    /// 参照する単語は、その単語が解析に使われるまで検出されません。辞書を配布する前や
+    /// CIでの検査に使用します。
+    ///
+    /// # 戻り値
+    ///
+    /// 検査結果
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, LoadMode};
+    /// let dict = Dictionary::from_path("path/to/system.dic", LoadMode::Validate)?;
+    /// let report = dict.check_integrity();
+    /// for word in &report.invalid_words {
+    ///     eprintln!("{:?}: {}/{}", word.word_idx, word.left_id, word.right_id);
+    /// }
+    /// assert!(report.is_ok());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_integrity(&self) -> IntegrityReport {
+        let dict = match self {
+            Dictionary::Archived(dict) => DictionaryInnerRef::Archived(dict),
+            Dictionary::Owned { dict, .. } => DictionaryInnerRef::Owned(dict),
+        };
+        integrity::check(dict, self.num_left_ids(), self.num_right_ids())
+    }
+
+    /// 2つの接続IDの間の接続コストを取得します。
+    ///
+    /// 行列形式の辞書では`matrix.def`の値を、素性形式の辞書では素性の重みから
+    /// 計算された値を返します。接続ID`0`は文頭・文末(BOS/EOS)を表します。
+    ///
+    /// 接続IDのマッピングが適用された辞書では、接続IDはマッピング後の値です。
+    /// これはトークンの左右の接続IDと同じ値です。
+    ///
+    /// # 引数
+    ///
+    /// * `right_id` - 前の単語の右接続ID
+    /// * `left_id` - 後の単語の左接続ID
+    ///
+    /// # 戻り値
+    ///
+    /// 接続コスト
+    ///
+    /// # パニック
+    ///
+    /// `right_id`が[`num_right_ids()`](Self::num_right_ids)以上の場合、または
+    /// `left_id`が[`num_left_ids()`](Self::num_left_ids)以上の場合にパニックします。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, LoadMode};
+    /// let dict = Dictionary::from_path("path/to/system.dic", LoadMode::TrustCache).unwrap();
+    /// for right_id in 0..dict.num_right_ids().min(10) {
+    ///     let costs: Vec<_> = (0..dict.num_left_ids().min(10))
+    ///         .map(|left_id| dict.connection_cost(right_id as u16, left_id as u16))
+    ///         .collect();
+    ///     println!("{right_id}: {costs:?}");
+    /// }
+    /// ```
+    pub fn connection_cost(&self, right_id: u16, left_id: u16) -> i32 {
+        assert!(
+            usize::from(right_id) < self.num_right_ids(),
+            "right_id {right_id} is out of range",
+        );
+        assert!(
+            usize::from(left_id) < self.num_left_ids(),
+            "left_id {left_id} is out of range",
+        );
+        match self {
+            Dictionary::Archived(dict) => dict.connector().cost(right_id, left_id),
+            Dictionary::Owned { dict, .. } => dict.connector().cost(right_id, left_id),
+        }
+    }
+
+    /// 指定された文字のカテゴリ情報を取得します。
+    ///
+    /// コンパイル済みの辞書で`char.def`の定義がどのように適用されたかを確認できます。
+    /// ある文字が未知語としてまとめられた理由を調べる場合などに使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `c` - 文字
+    ///
+    /// # 戻り値
+    ///
+    /// 文字が属するカテゴリと、未知語処理の設定
+    ///
+    /// # 例
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use vibrato_rkyv::{Dictionary, SystemDictionaryBuilder};
+    ///
+    /// let dict = SystemDictionaryBuilder::from_readers(
+    ///     Cursor::new("東京,0,0,0,名詞\n"),
+    ///     Cursor::new("1 1\n0 0 0\n"),
+    ///     Cursor::new("DEFAULT 0 1 0\nKANJI 0 0 2\n0x4E00..0x9FFF KANJI\n"),
+    ///     Cursor::new("DEFAULT,0,0,0,補助記号\nKANJI,0,0,0,名詞\n"),
+    /// )
+    /// .unwrap();
+    /// let dict = Dictionary::from_inner(dict);
+    ///
+    /// let info = dict.char_category('京');
+    /// assert_eq!(info.base_category(), "KANJI");
+    /// assert!(!info.group());
+    /// assert_eq!(info.length(), 2);
+    /// ```
+    pub fn char_category(&self, c: char) -> CategoryInfoView<'_> {
+        match self {
+            Dictionary::Archived(dict) => dict.char_prop().category_info(c),
+            Dictionary::Owned { dict, .. } => dict.char_prop().category_info(c),
+        }
+    }
+
+    /// [`UserDictionary`]でコンパイルされたユーザー辞書を付加します。
+    ///
+    /// システム辞書を再シリアライズすることなく、読み込み時にユーザー辞書を追加できます。
+    /// 付加されたユーザー辞書は、辞書ファイルに含まれるユーザー辞書を置き換えます。
+    /// [`content_hash`](Self::content_hash)はシステム辞書のファイルのみから計算され、
+    /// 付加されたユーザー辞書の影響を受けません。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - コンパイル済みのユーザー辞書ファイルへのパス
+    ///
+    /// # 戻り値
+    ///
+    /// ユーザー辞書が付加された`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - ファイルを読み込めない場合、またはファイルが破損している場合。
+    /// - ユーザー辞書が別のシステム辞書に対してコンパイルされている場合。
+    /// - 所有型の辞書が[`Tokenizer`](crate::Tokenizer)などと共有されている場合。
+    pub fn load_user_dictionary<P: AsRef<std::path::Path>>(self, path: P) -> Result<Self> {
+        let user = LoadedUserDictionary::read(BufReader::new(File::open(path)?))?;
+        user.check(&self)?;
+        self.attach_user_dictionary(user, true)
+    }
+
+    /// システム辞書と、[`UserDictionary`]でコンパイルされた複数のユーザー辞書を読み込みます。
+    ///
+    /// ユーザー辞書は指定された順に重ねられ、辞書ファイルに含まれるユーザー辞書を置き換えます。
+    /// ラティスの構築時には、各開始位置でユーザー辞書が指定された順に照合され、
+    /// 一致した語はその順にシステム辞書の語より先に追加されます。同じコストの候補が
+    /// 複数ある場合の選び方は[`TieBreak`](crate::tokenizer::TieBreak)に従います。
+    ///
+    /// ユーザー辞書の単語IDは、すべてのユーザー辞書を通した通し番号になります。
+    /// 2番目以降の辞書の単語IDは、それより前の辞書の単語数の合計だけずれます。
+    ///
+    /// # 引数
+    ///
+    /// * `system` - システム辞書ファイルへのパス
+    /// * `users` - コンパイル済みのユーザー辞書ファイルへのパス。優先順に指定します。
+    /// * `mode` - システム辞書の読み込みモード
+    ///
+    /// # 戻り値
+    ///
+    /// ユーザー辞書が付加された`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// [`Dictionary::from_path()`]と[`Dictionary::load_user_dictionary()`]と同じ条件で
+    /// エラーを返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, LoadMode};
+    ///
+    /// let dict = Dictionary::from_paths(
+    ///     "system.dic",
+    ///     ["company.dicu", "project.dicu"],
+    ///     LoadMode::TrustCache,
+    /// )?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_paths<P, I, Q>(system: P, users: I, mode: LoadMode) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+        I: IntoIterator<Item = Q>,
+        Q: AsRef<std::path::Path>,
+    {
+        let mut dict = Self::from_path(system, mode)?;
+        for (i, path) in users.into_iter().enumerate() {
+            let user = LoadedUserDictionary::read(BufReader::new(File::open(path)?))?;
+            user.check(&dict)?;
+            dict = dict.attach_user_dictionary(user, i == 0)?;
+        }
+        Ok(dict)
+    }
+
+    /// 読み込んだユーザー辞書を付加します。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 検証済みのユーザー辞書
+    /// * `replace` - `true`の場合は既存のユーザー辞書を置き換え、`false`の場合は後ろに重ねます。
+    fn attach_user_dictionary(self, user: LoadedUserDictionary, replace: bool) -> Result<Self> {
+        match self {
+            Dictionary::Archived(mut dict) => {
+                if replace {
+                    dict.users.clear();
+                }
+                dict.users.push(user);
+                Ok(Dictionary::Archived(dict))
+            }
+            Dictionary::Owned { dict, .. } => {
+                let mut dict = Arc::try_unwrap(dict).map_err(|_| {
+                    VibratoError::invalid_state(
+                        "The dictionary is shared and cannot be modified.",
+                        "Load the user dictionary before creating a tokenizer.",
+                    )
+                })?;
+                let mut lexicon = rkyv::deserialize::<Lexicon, Error>(user.lexicon())?;
+                lexicon.set_surfaces(user.surfaces());
+                if replace || dict.user_lexicon.is_none() {
+                    dict.user_lexicon = Some(lexicon);
+                    dict.user_layers.clear();
+                } else {
+                    dict.user_layers.push(lexicon);
+                }
+                Ok(Dictionary::from_inner(dict))
+            }
+        }
+    }
+
+    /// すべてのデータをヒープバッファに読み込むことで、リーダーから辞書を作成します。
+    ///
+    /// これは、ファイルパスが利用できない場合(例: メモリ内バッファからの読み込み)の
+    /// フォールバックです。すべてのコンテンツをメモリに読み込むため、
+    /// `from_path`よりもメモリ効率が低くなります。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - `std::io::Read`を実装するリーダー。
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - データを読み込めない場合。
+    /// - コンテンツが無効な場合。
+    pub fn read<R: Read>(mut rdr: R) -> Result<Self> {
+        let mut magic = [0; MODEL_MAGIC_LEN];
+        rdr.read_exact(&mut magic)?;
+
+        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
+            ));
+        }else if !magic.starts_with(MODEL_MAGIC) {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "The magic number of the input model mismatches.",
+            ));
         }
 
         let mut padding_buf = vec![0; PADDING_LEN];
         rdr.read_exact(&mut padding_buf)?;
+        let header = SectionHeader::parse(&padding_buf)?;
 
         let mut buffer = Vec::new();
         rdr.read_to_end(&mut buffer)?;
@@ -638,7 +1485,9 @@ impl Dictionary {
         let mut aligned_bytes = AlignedVec::with_capacity(buffer.len());
         aligned_bytes.extend_from_slice(&buffer);
 
-        let archived = access::<ArchivedDictionaryInner, Error>(&aligned_bytes).map_err(|e| {
+        let (data_bytes, dict_metadata) = metadata::split_metadata(&aligned_bytes)?;
+        header.check(&aligned_bytes, data_bytes)?;
+        let archived = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
             VibratoError::invalid_state(
                 "rkyv validation failed. The dictionary file may be corrupted or incompatible."
                     .to_string(),
@@ -651,11 +1500,138 @@ impl Dictionary {
 
         Ok(
             Self::Archived(
-                ArchivedDictionary { _buffer: DictBuffer::Aligned(aligned_bytes), data }
+                ArchivedDictionary::new(
+                    DictBuffer::Aligned(aligned_bytes, header),
+                    data,
+                    dict_metadata,
+                )
             )
         )
     }
 
+    /// アライメントされたヒープバッファ上の辞書ファイルから辞書を作成します。
+    ///
+    /// ネットワーク経由で受け取った辞書など、ファイルシステムを使わずに読み込む場合に
+    /// 使用します。バッファはコピーされずにそのまま辞書が所有し、データは完全に
+    /// 検証されます。
+    ///
+    /// # 引数
+    ///
+    /// * `bytes` - [`DictionaryInner::write`]で書き出された辞書ファイル全体
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// マジックナンバーが一致しない場合や、データが破損している場合に[`VibratoError`]を
+    /// 返します。
+    ///
+    /// # 例
+    ///
+    /// ```
+    /// use rkyv::util::AlignedVec;
+    /// use vibrato_rkyv::{Dictionary, SystemDictionaryBuilder, Tokenizer};
+    ///
+    /// let mut buffer = vec![];
+    /// SystemDictionaryBuilder::from_readers(
+    ///     "京都,0,0,1,名詞".as_bytes(),
+    ///     "1 1\n0 0 0".as_bytes(),
+    ///     "DEFAULT 0 1 0".as_bytes(),
+    ///     "DEFAULT,0,0,100,*".as_bytes(),
+    /// )?
+    /// .write(&mut buffer)?;
+    ///
+    /// let mut bytes = AlignedVec::<16>::with_capacity(buffer.len());
+    /// bytes.extend_from_slice(&buffer);
+    /// let dict = Dictionary::from_aligned_owned(bytes)?;
+    ///
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("京都");
+    /// worker.tokenize();
+    /// assert_eq!(worker.token(0).feature(), "名詞");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_aligned_owned(bytes: AlignedVec<16>) -> Result<Self> {
+        let (data_bytes, dict_metadata) = split_file_bytes(&bytes)?;
+        let archived = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
+            VibratoError::invalid_state(
+                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
+                    .to_string(),
+                e.to_string(),
+            )
+        })?;
+
+        // SAFETY: The heap buffer of `bytes` is not moved when `bytes` is moved into the
+        // dictionary, and it is never modified while the dictionary is alive.
+        let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
+
+        Ok(Self::Archived(ArchivedDictionary::new(
+            DictBuffer::AlignedFile(bytes),
+            data,
+            dict_metadata,
+        )))
+    }
+
+    /// `'static`なメモリ領域上の辞書ファイルから、検証なしで辞書を作成します。
+    ///
+    /// `include_bytes!`で実行ファイルに埋め込んだ小さな辞書を、ファイルシステムや
+    /// ヒープへのコピーなしに読み込む場合に使用します。ヘッダに記録された長さと
+    /// アライメントは確認されますが、データ自体は検証されません。
+    ///
+    /// 辞書データは16バイト境界に配置されている必要があります。`include_bytes!`の
+    /// 結果はアライメントが保証されないため、次の例のようにラップしてください。
+    ///
+    /// # 引数
+    ///
+    /// * `bytes` - [`DictionaryInner::write`]で書き出された辞書ファイル全体
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - マジックナンバーが一致しない場合。
+    /// - ヘッダに記録された辞書データの長さと`bytes`の大きさが一致しない場合。
+    /// - `bytes`が16バイト境界に配置されていない場合。
+    ///
+    /// # Safety
+    ///
+    /// [`from_path_unchecked`](Self::from_path_unchecked)と同様に、`rkyv`の検証を
+    /// 省略します。呼び出し側は、`bytes`が辞書の有効で破損していない表現であることを
+    /// 保証する必要があります。
+    ///
+    /// # 例
+    ///
+    /// `include_bytes!`にはビルド時に存在する辞書ファイルのパスを指定します。
+    ///
+    /// ```ignore
+    /// use vibrato_rkyv::Dictionary;
+    ///
+    /// #[repr(C, align(16))]
+    /// struct Aligned<T: ?Sized>(T);
+    ///
+    /// static DICT: &Aligned<[u8]> = &Aligned(*include_bytes!("path/to/system.dic"));
+    ///
+    /// // SAFETY: The dictionary was written by `DictionaryInner::write` at build time.
+    /// let dict = unsafe { Dictionary::from_static_bytes(&DICT.0)? };
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub unsafe fn from_static_bytes(bytes: &'static [u8]) -> Result<Self> {
+        let (data_bytes, dict_metadata) = split_file_bytes(bytes)?;
+        let data = unsafe { access_unchecked::<ArchivedDictionaryInner>(data_bytes) };
+
+        Ok(Self::Archived(ArchivedDictionary::new(
+            DictBuffer::Static(bytes),
+            data,
+            dict_metadata,
+        )))
+    }
+
     /// メモリマッピングを使用してファイルパスから辞書を作成します。
     ///
     /// この関数は、辞書ファイルをメモリにマップしてゼロコピーアクセスを実現し、
@@ -685,7 +1661,8 @@ impl Dictionary {
     ///     (例: Linux上の`~/.cache/vibrato-rkyv`)。
     ///
     /// いずれかの場所で有効なプルーフファイルが見つかった場合、辞書は追加の検証なしで
-    /// 即座に読み込まれます。
+    /// 即座に読み込まれます。ただし、ヘッダに記録された辞書データの長さとファイルの
+    /// 大きさの照合はこの場合も行われるため、切り詰められたファイルはエラーになります。
     ///
     /// プルーフファイルが見つからない場合、関数は完全な検証を実行します。成功した場合、
     /// **グローバルキャッシュディレクトリに新しいプルーフファイルを作成**して、
@@ -720,6 +1697,46 @@ impl Dictionary {
     /// - ファイルが互換性のないバージョンのvibratoで作成された場合。
     /// - (`legacy`フィーチャーが無効)レガシーbincodeベースの辞書が提供された場合。
     pub fn from_path<P: AsRef<std::path::Path>>(path: P, mode: LoadMode) -> Result<Self> {
+        Self::from_path_with_report(path, mode).map(|(dict, _)| dict)
+    }
+
+    /// [`Dictionary::from_path`]と同様に辞書を読み込み、読み込み時のメタデータを返します。
+    ///
+    /// 返される[`LoadReport`]には、メモリマップとヒープのどちらが使われたか、
+    /// 検証が実行されたか、どの場所のプルーフファイルが使われたか、各段階の所要時間、
+    /// 辞書のメタデータが含まれます。アプリケーションの起動ログやヘルスチェックでの
+    /// 利用を想定しています。
+    ///
+    /// # 引数
+    ///
+    /// - `path` - 辞書ファイルへのパス。
+    /// - `mode` - 検証戦略を指定する[`LoadMode`]。
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンスと[`LoadReport`]の組。
+    ///
+    /// # エラー
+    ///
+    /// [`Dictionary::from_path`]と同じ条件でエラーを返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, LoadMode};
+    ///
+    /// let (dict, report) = Dictionary::from_path_with_report("path/to/dict", LoadMode::TrustCache)?;
+    /// eprintln!(
+    ///     "loaded in {:?} (buffer: {:?}, validated: {})",
+    ///     report.total_duration, report.buffer, report.validated,
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_path_with_report<P: AsRef<std::path::Path>>(
+        path: P,
+        mode: LoadMode,
+    ) -> Result<(Self, LoadReport)> {
+        let start = Instant::now();
         let path = path.as_ref();
         let mut file = File::open(path).map_err(|e| {
             VibratoError::invalid_argument("path", format!("Failed to open dictionary file: {}", e))
@@ -728,6 +1745,20 @@ impl Dictionary {
         let mut magic = [0u8; MODEL_MAGIC_LEN];
         file.read_exact(&mut magic)?;
 
+        let mut report = LoadReport {
+            buffer: BufferKind::Mmap,
+            validated: false,
+            proof_hit: None,
+            proof_created: false,
+            open_duration: Default::default(),
+            validation_duration: Default::default(),
+            total_duration: Default::default(),
+            file_size: meta.len(),
+            num_left_ids: 0,
+            num_right_ids: 0,
+            has_user_lexicon: false,
+        };
+
         if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
             #[cfg(not(feature = "legacy"))]
             return Err(VibratoError::invalid_argument(
@@ -737,20 +1768,26 @@ impl Dictionary {
 
             #[cfg(feature = "legacy")]
             {
-                use std::io::Seek;
+                use std::io::{Seek, SeekFrom};
                 use crate::legacy;
 
-                file.seek(io::SeekFrom::Start(0))?;
+                file.seek(SeekFrom::Start(0))?;
+                report.open_duration = start.elapsed();
 
                 let dict = legacy::Dictionary::read(file)?.data;
 
-                let dict = unsafe {
-                    use std::mem::transmute;
+                let dict = Arc::new(DictionaryInner::try_from(dict)?);
 
-                    Arc::new(transmute::<legacy::dictionary::DictionaryInner, DictionaryInner>(dict))
+                report.buffer = BufferKind::Owned;
+                report.validated = true;
+                report.validation_duration = start.elapsed() - report.open_duration;
+                let dict = Self::Owned {
+                    dict,
+                    _caching_handle: None,
+                    #[cfg(feature = "loaders")]
+                    content_hash: OnceLock::new(),
                 };
-
-                return Ok(Self::Owned{ dict, _caching_handle: None });
+                return Ok(dict.with_report(report, start));
             }
         } else if !magic.starts_with(MODEL_MAGIC) {
             return Err(VibratoError::invalid_argument(
@@ -767,74 +1804,96 @@ impl Dictionary {
                 "Dictionary file too small or corrupted.",
             ));
         };
-
-        let current_hash = compute_metadata_hash(meta);
-        let hash_name = format!("{}.sha256", current_hash);
-        let hash_path = path.parent().unwrap().join(".cache").join(&hash_name);
-
-        if mode == LoadMode::TrustCache
-            && hash_path.exists() {
-                let archived = unsafe { access_unchecked::<ArchivedDictionaryInner>(data_bytes) };
-                let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
-                return {
-                    Ok(
-                        Dictionary::Archived(ArchivedDictionary { _buffer: DictBuffer::Mmap(mmap), data })
-                    )
-                };
-            }
-
-        let global_cache_dir = GLOBAL_CACHE_DIR.as_ref().ok_or_else(|| {
-            VibratoError::invalid_state("Could not determine system cache directory.", "")
-        })?;
-
-        let hash_path = global_cache_dir.join(&hash_name);
-
-        if mode == LoadMode::TrustCache
-            && hash_path.exists() {
+        let full_bytes: &[u8] = data_bytes;
+        let (data_bytes, dict_metadata) = metadata::split_metadata(full_bytes)?;
+        // This costs only a few comparisons, so it runs even if the proof file exists.
+        let header = SectionHeader::parse(&mmap[MODEL_MAGIC_LEN..DATA_START])?;
+        header.check(full_bytes, data_bytes)?;
+
+        report.open_duration = start.elapsed();
+
+        #[cfg(feature = "loaders")]
+        let proof_path = if mode == LoadMode::TrustCache {
+            let (proof_hit, proof_path) = loaders::find_proof(path, meta)?;
+            if proof_hit.is_some() {
                 let archived = unsafe { access_unchecked::<ArchivedDictionaryInner>(data_bytes) };
                 let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
+                report.proof_hit = proof_hit;
                 return {
                     Ok(
-                        Dictionary::Archived(ArchivedDictionary { _buffer: DictBuffer::Mmap(mmap), data })
+                        Dictionary::Archived(
+                            ArchivedDictionary::new(DictBuffer::Mmap(mmap), data, dict_metadata)
+                        )
+                        .with_report(report, start)
                     )
                 };
             }
+            Some(proof_path)
+        } else {
+            None
+        };
+        // Proof files are only available with the loaders feature; TrustCache always validates without it.
+        #[cfg(not(feature = "loaders"))]
+        let _ = mode;
 
+        let validation_start = Instant::now();
+        report.validated = true;
         match access::<ArchivedDictionaryInner, Error>(data_bytes) {
             Ok(archived) => {
-                if mode == LoadMode::TrustCache {
-                    create_dir_all(global_cache_dir)?;
-                    File::create_new(hash_path)?;
+                report.validation_duration = validation_start.elapsed();
+                #[cfg(feature = "loaders")]
+                if let Some(proof_path) = proof_path {
+                    loaders::create_proof(&proof_path)?;
+                    report.proof_created = true;
                 }
 
                 let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
                 Ok(Self::Archived(
-                    ArchivedDictionary {
-                        _buffer: DictBuffer::Mmap(mmap),
-                        data,
-                    }
-                ))
+                    ArchivedDictionary::new(DictBuffer::Mmap(mmap), data, dict_metadata)
+                ).with_report(report, start))
             }
             Err(_) => {
-                let mut aligned_bytes = AlignedVec::with_capacity(data_bytes.len());
-                aligned_bytes.extend_from_slice(data_bytes);
+                // Keep the metadata trailer in the buffer so that the content hash covers the whole file.
+                let mut aligned_bytes = AlignedVec::with_capacity(full_bytes.len());
+                aligned_bytes.extend_from_slice(full_bytes);
 
-                let archived = access::<ArchivedDictionaryInner, Error>(&aligned_bytes).map_err(|e| {
+                let archived = access::<ArchivedDictionaryInner, Error>(&aligned_bytes[..data_bytes.len()]).map_err(|e| {
                     VibratoError::invalid_state(
                         "rkyv validation failed. The dictionary file may be corrupted or incompatible.".to_string(),
                         e.to_string(),
                     )
                 })?;
+                report.validation_duration = validation_start.elapsed();
+                report.buffer = BufferKind::Heap;
 
                 let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
                 Ok(Self::Archived(
-                    ArchivedDictionary {
-                        _buffer: DictBuffer::Aligned(aligned_bytes),
+                    ArchivedDictionary::new(
+                        DictBuffer::Aligned(aligned_bytes, header),
                         data,
-                    }
-                ))
+                        dict_metadata,
+                    )
+                ).with_report(report, start))
+            }
+        }
+    }
+
+    /// 読み込んだ辞書のメタデータと全体の所要時間を`report`に記録します。
+    fn with_report(self, mut report: LoadReport, start: Instant) -> (Self, LoadReport) {
+        match &self {
+            Dictionary::Archived(dict) => {
+                report.num_left_ids = dict.connector().num_left();
+                report.num_right_ids = dict.connector().num_right();
+                report.has_user_lexicon = dict.user_lexicons().next().is_some();
+            }
+            Dictionary::Owned { dict, .. } => {
+                report.num_left_ids = dict.connector().num_left();
+                report.num_right_ids = dict.connector().num_right();
+                report.has_user_lexicon = dict.user_lexicons().next().is_some();
             }
         }
+        report.total_duration = start.elapsed();
+        (self, report)
     }
 
     /// 検証なしでメモリマッピングを使用してファイルパスから辞書を作成します。
@@ -858,6 +1917,8 @@ impl Dictionary {
     /// - ファイルが小さすぎる場合。
     /// - マジックナンバーが不正な場合。
     ///
+    /// - ヘッダに記録された辞書データの長さとファイルの大きさが一致しない場合。
+    ///
     /// この関数は、シリアライズされたデータ自体の整合性を検証しません。
     ///
     /// # Safety
@@ -890,21 +1951,22 @@ impl Dictionary {
 
             #[cfg(feature = "legacy")]
             {
-                use std::io::Seek;
+                use std::io::{Seek, SeekFrom};
 
                 use crate::legacy;
 
-                file.seek(io::SeekFrom::Start(0))?;
+                file.seek(SeekFrom::Start(0))?;
 
                 let dict = legacy::Dictionary::read(file)?.data;
 
-                let dict = unsafe {
-                    use std::mem::transmute;
+                let dict = Arc::new(DictionaryInner::try_from(dict)?);
 
-                    Arc::new(transmute::<legacy::dictionary::DictionaryInner, DictionaryInner>(dict))
-                };
-
-                return Ok(Self::Owned{ dict, _caching_handle: None });
+                return Ok(Self::Owned {
+                    dict,
+                    _caching_handle: None,
+                    #[cfg(feature = "loaders")]
+                    content_hash: OnceLock::new(),
+                });
             }
         } else if !magic.starts_with(MODEL_MAGIC) {
             return Err(VibratoError::invalid_argument(
@@ -922,277 +1984,20 @@ impl Dictionary {
             ));
         };
 
+        let full_bytes: &[u8] = data_bytes;
+        let (data_bytes, dict_metadata) = metadata::split_metadata(full_bytes)?;
+        let header = SectionHeader::parse(&mmap[MODEL_MAGIC_LEN..DATA_START])?;
+        header.check(full_bytes, data_bytes)?;
+
         let archived = unsafe { access_unchecked::<ArchivedDictionaryInner>(data_bytes) };
         let data: &'static ArchivedDictionaryInner = unsafe { &*(archived as *const _) };
         Ok(
             Self::Archived(
-                ArchivedDictionary {
-                    _buffer: DictBuffer::Mmap(mmap),
-                    data,
-                }
+                ArchivedDictionary::new(DictBuffer::Mmap(mmap), data, dict_metadata)
             )
         )
     }
 
-    /// 指定されたキャッシング戦略を使用してZstandard圧縮ファイルから辞書を読み込みます。
-    ///
-    /// この関数は、最も一般的なキャッシングシナリオに対してユーザーフレンドリーな
-    /// インターフェースを提供します。より細かい制御が必要な場合は、
-    /// [`from_zstd_with_options`]を参照してください。
-    ///
-    /// # 引数
-    ///
-    /// * `path` - Zstandard圧縮辞書ファイルへのパス。
-    /// * `strategy` - [`CacheStrategy`]列挙型で定義される希望のキャッシング戦略。
-    #[cfg_attr(feature = "legacy", doc = r"
-    `legacy`フィーチャーが有効な場合、この関数はキャッシングがバックグラウンドで
-    実行されている間に即座に戻り、応答性の高いユーザーエクスペリエンスを提供します。")]
-    ///
-    /// # 戻り値
-    ///
-    /// 新しい`Dictionary`インスタンス。
-    ///
-    /// # エラー
-    ///
-    /// この関数は、[`from_zstd_with_options`]のエラーに加えて、
-    /// (`strategy`によって決定される)`cache_dir`が作成できない、
-    /// または書き込めない場合にエラーを返します。
-    pub fn from_zstd<P: AsRef<std::path::Path>>(path: P, strategy: CacheStrategy) -> Result<Self> {
-        let path = path.as_ref();
-
-        let cache_dir = match strategy {
-            CacheStrategy::Local => {
-                let parent = path.parent().ok_or_else(|| {
-                    VibratoError::invalid_argument(
-                        "path",
-                        "Input path must have a parent directory for the Local cache strategy.",
-                    )
-                })?;
-                let local_cache = parent.join(".cache");
-                std::fs::create_dir_all(&local_cache)?;
-                local_cache
-            }
-            CacheStrategy::GlobalCache => {
-                let global_cache = GLOBAL_CACHE_DIR.as_ref().ok_or_else(|| {
-                    VibratoError::invalid_state("Could not determine system cache directory.", "")
-                })?;
-                global_cache.to_path_buf()
-            }
-            CacheStrategy::GlobalData => {
-                let local_data = GLOBAL_DATA_DIR.as_ref().ok_or_else(|| {
-                    VibratoError::invalid_state("Could not determine local data directory.", "")
-                })?;
-                local_data.to_path_buf()
-            }
-        };
-
-        Self::from_zstd_with_options(
-            path,
-            cache_dir,
-            #[cfg(feature = "legacy")]
-            false,
-        )
-    }
-
-    /// 設定可能なキャッシングオプションを使用してZstandard圧縮ファイルから辞書を読み込みます。
-    ///
-    /// これは[`from_zstd`]の高度なバージョンで、キャッシュディレクトリの細かい制御を
-    /// 可能にします。特定のディレクトリ構造や制限的なファイルシステム権限を持つ環境で
-    /// 有用です。
-    ///
-    /// ## キャッシングメカニズム
-    ///
-    /// 実行ごとにファイルを展開するのを避けるため、この関数はキャッシュメカニズムを
-    /// 採用しています。入力`.zst`ファイルのメタデータ(サイズや更新時刻など)から
-    /// 一意のハッシュを生成します。このハッシュは、展開されたキャッシュのファイル名として
-    /// 使用されます。
-    ///
-    /// 後続の実行時に、現在のメタデータハッシュに対応するキャッシュファイルが存在する場合、
-    /// 展開ステップが完全にスキップされ、ほぼ瞬時の読み込みが可能になります。
-    /// `.zst`ファイルが変更されると、そのメタデータハッシュが変更され、新しいキャッシュが
-    /// 自動的に生成されます。
-    ///
-    /// # 引数
-    ///
-    /// * `path` - Zstandard圧縮辞書ファイルへのパス。
-    /// * `cache_dir` - 展開された辞書キャッシュが保存されるディレクトリ。
-    #[cfg_attr(feature = "legacy", doc = r" * `wait_for_cache` - (legacyフィーチャーのみ) `true`でレガシー(bincode)辞書が
-    提供された場合、関数は新しい形式への変換とキャッシングが完了するまでブロックします。
-    `false`の場合、完全に機能する辞書ですぐに戻り、キャッシングプロセスは
-    バックグラウンドスレッドで実行されます。")]
-    ///
-    /// # 戻り値
-    ///
-    /// 新しい`Dictionary`インスタンス。
-    ///
-    /// # エラー
-    ///
-    /// この関数は以下の場合にエラーを返します:
-    /// - `path`で指定されたファイルを開けない、または読み込めない場合(例: I/Oエラー)。
-    /// - ファイルが有効なZstandard圧縮アーカイブでない場合。
-    /// - 展開されたデータが有効な辞書ファイルでない場合(例: 破損データまたは不正なマジックナンバー)。
-    /// - `cache_dir`で指定されたキャッシュディレクトリが作成できない、または書き込めない場合。
-    #[cfg_attr(feature = "legacy", doc = r" - (legacyフィーチャーのみ) `wait_for_cache`が`true`のときにバックグラウンドキャッシングスレッドがパニックした場合。")]
-    ///
-    /// # Examples
-    ///
-    /// ### カスタムキャッシュディレクトリの指定
-    ///
-    /// ```no_run
-    /// # use vibrato_rkyv::{Dictionary, errors::Result};
-    /// # fn main() -> Result<()> {
-    /// let dict = Dictionary::from_zstd_with_options(
-    ///     "path/to/system.dic.zst",
-    ///     "/tmp/my_app_cache",
-    #[cfg_attr(feature = "legacy", doc = r"true, // バックグラウンドキャッシュ生成の完了を待つ")]
-    /// )?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    #[inline(always)]
-    pub fn from_zstd_with_options<P, Q>(
-        path: P,
-        cache_dir: Q,
-        #[cfg(feature = "legacy")]
-        wait_for_cache: bool,
-    ) -> Result<Self>
-    where
-        P: AsRef<std::path::Path>,
-        Q: AsRef<std::path::Path>,
-    {
-        let zstd_path = path.as_ref();
-        let zstd_file = File::open(zstd_path)?;
-        let meta = zstd_file.metadata()?;
-
-        let dict_hash = compute_metadata_hash(&meta);
-        let decompressed_dir = cache_dir.as_ref().to_path_buf();
-
-        let decompressed_dict_path = decompressed_dir.join(format!("{}.dic", dict_hash));
-
-        if decompressed_dict_path.exists() {
-            return Self::from_path(decompressed_dict_path, LoadMode::TrustCache);
-        }
-
-        if !decompressed_dir.exists() {
-            create_dir_all(&decompressed_dir)?;
-        }
-
-        let mut temp_file = tempfile::NamedTempFile::new_in(&decompressed_dir)?;
-
-        {
-            let mut decoder = zstd::Decoder::new(zstd_file)?;
-
-            io::copy(&mut decoder, &mut temp_file)?;
-            temp_file.as_file().sync_all()?;
-        }
-        temp_file.seek(SeekFrom::Start(0))?;
-
-        let mut magic = [0; MODEL_MAGIC_LEN];
-        temp_file.read_exact(&mut magic)?;
-
-        #[cfg(feature = "legacy")]
-        'l: {
-            use std::thread;
-
-            use crate::legacy;
-
-            if !magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
-                break 'l;
-            }
-
-            let dict = legacy::Dictionary::read(
-                zstd::Decoder::new(File::open(zstd_path)?)?
-            )?.data;
-
-            let dict = unsafe {
-                use std::mem::transmute;
-
-                Arc::new(transmute::<legacy::dictionary::DictionaryInner, DictionaryInner>(dict))
-            };
-
-
-            let dict_for_cache = Arc::clone(&dict);
-            let handle = thread::spawn(move || -> Result<()> {
-                let mut temp_file = tempfile::NamedTempFile::new_in(&decompressed_dir)?;
-
-                dict_for_cache.write(&mut temp_file)?;
-
-                temp_file.persist(&decompressed_dict_path)?;
-
-                let dict_file = File::open(decompressed_dict_path)?;
-                let decompressed_dict_hash = compute_metadata_hash(&dict_file.metadata()?);
-                let decompressed_dict_hash_path = decompressed_dir.join(format!("{}.sha256", decompressed_dict_hash));
-
-                File::create_new(decompressed_dict_hash_path)?;
-
-                Ok(())
-            });
-
-            let _caching_handle = if wait_for_cache {
-                handle.join().map_err(|e| {
-                    let panic_msg = if let Some(s) = e.downcast_ref::<&'static str>() {
-                        s.to_string()
-                    } else if let Some(s) = e.downcast_ref::<String>() {
-                        s.clone()
-                    } else {
-                        "Unknown panic".to_string()
-                    };
-                    VibratoError::ThreadPanic(panic_msg)
-                })??;
-
-                None
-            } else {
-                Some(std::sync::Arc::new(handle))
-            };
-
-            return Ok(Self::Owned { dict, _caching_handle });
-        }
-
-        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
-            ));
-        } else if !magic.starts_with(MODEL_MAGIC) {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "The magic number of the input model mismatches.",
-            ));
-        }
-
-        temp_file.seek(SeekFrom::Start(0))?;
-
-        let mut data_bytes = Vec::new();
-        temp_file.as_file_mut().read_to_end(&mut data_bytes)?;
-
-        let mut aligned_bytes: AlignedVec = AlignedVec::with_capacity(data_bytes.len());
-        aligned_bytes.extend_from_slice(&data_bytes);
-
-        let Some(data_bytes) = &aligned_bytes.get(DATA_START..) else {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "Dictionary file too small or corrupted.",
-            ));
-        };
-
-        let _ = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
-            VibratoError::invalid_state(
-                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
-                    .to_string(),
-                e.to_string(),
-            )
-        })?;
-
-        temp_file.persist(&decompressed_dict_path)?;
-
-        let decompressed_dict_hash = compute_metadata_hash(&File::open(&decompressed_dict_path)?.metadata()?);
-        let decompressed_dict_hash_path = decompressed_dir.join(format!("{}.sha256", decompressed_dict_hash));
-
-        File::create_new(decompressed_dict_hash_path)?;
-
-        Self::from_path(decompressed_dict_path, LoadMode::TrustCache)
-    }
-
     /// レガシー`bincode`ベースの辞書のリーダーから[`Dictionary`]インスタンスを作成します。
     ///
     /// この関数は、古い辞書形式を変換するための`compiler`などの内部ツールを
@@ -1213,294 +2018,46 @@ impl Dictionary {
     /// この関数は以下の場合にエラーを返します:
     /// - リーダーからのデータ読み込みに失敗した場合。
     /// - レガシー辞書のデシリアライゼーションに失敗した場合。
-    ///
-    /// # Safety
-    ///
-    /// この関数は`unsafe`です。なぜなら、[`std::mem::transmute`]を使用して
-    /// `bincode`でデシリアライズされた辞書構造をキャストするためです。
-    /// このフォークは同一のメモリレイアウトを維持しているため、現在は安全です。
+    /// - レガシー辞書の内容が不正な場合。
     #[cfg(feature = "legacy")]
-    pub unsafe fn from_legacy_reader<R: std::io::Read>(reader: R) -> Result<Self> {
+    pub fn from_legacy_reader<R: std::io::Read>(reader: R) -> Result<Self> {
         let legacy_dict_inner = crate::legacy::Dictionary::read(reader)?.data;
 
-        let rkyv_dict_inner = unsafe {
-            std::mem::transmute::<
-                crate::legacy::dictionary::DictionaryInner,
-                DictionaryInner,
-            >(legacy_dict_inner)
-        };
-
-        Ok(Self::Owned { dict: Arc::new(rkyv_dict_inner), _caching_handle: None })
-    }
-
-    /// プリセット辞書から`Dictionary`インスタンスを作成し、存在しない場合はダウンロードします。
-    ///
-    /// これは、プリコンパイル済み辞書を使い始めるための最も便利な方法です。
-    /// この関数は、まず指定されたプリセット辞書が指定のディレクトリに既に存在するかを
-    /// 確認します。存在し、整合性が検証された場合は直接読み込みます。
-    /// それ以外の場合は、公式リポジトリから辞書をディレクトリにダウンロードし、
-    /// その後読み込みます。
-    ///
-    /// ダウンロードされた辞書はZstandard圧縮されています。この関数は、
-    /// メモリマッピングによる高速な後続読み込みのために、展開とキャッシングを
-    /// 透過的に処理します。
-    ///
-    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
-    ///
-    /// # 引数
-    ///
-    /// * `kind` - 使用するプリセット辞書(例: `PresetDictionaryKind::Ipadic`)。
-    /// * `dir` - 辞書が保存およびキャッシュされるディレクトリ。
-    ///   永続的な場所を使用することを推奨します。
-    ///
-    /// # 戻り値
-    ///
-    /// 新しい`Dictionary`インスタンス。
-    ///
-    /// # エラー
-    ///
-    /// この関数は以下の場合にエラーを返します:
-    /// - ダウンロードが失敗した場合(例: ネットワークの問題)。
-    /// - ダウンロードされたファイルが破損している場合(ハッシュの不一致)。
-    /// - キャッシュディレクトリの作成時にファイルシステム権限エラーがある場合。
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// # use std::path::Path;
-    /// # use vibrato_rkyv::{Dictionary, Tokenizer, dictionary::PresetDictionaryKind};
-    /// # let dir = Path::new("./cache_dir");
-    /// // IPADICプリセット辞書をダウンロードして読み込みます。
-    /// // 最初の呼び出しではファイルをダウンロードし、後続の呼び出しではキャッシュを使用します。
-    /// let dictionary = Dictionary::from_preset_with_download(
-    ///     PresetDictionaryKind::Ipadic,
-    ///     dir,
-    /// ).unwrap();
-    ///
-    /// let mut tokenizer = Tokenizer::new(dictionary);
-    /// ```
-    #[cfg(feature = "download")]
-    pub fn from_preset_with_download<P: AsRef<std::path::Path>>(kind: PresetDictionaryKind, dir: P) -> Result<Self> {
-        let dict_path = fetch::download_dictionary(kind, dir.as_ref())?;
-
-        Self::from_zstd_with_options(
-            dict_path,
-            dir,
-            #[cfg(feature = "legacy")]
-            true,
-        )
-    }
-
-    /// プリセット辞書ファイルをダウンロードし、そのパスを返します。
-    ///
-    /// ダウンロード後、辞書は[`Dictionary::from_zstd`]を使用して読み込むことができます。
-    ///
-    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
-    ///
-    /// # 引数
-    ///
-    /// * `kind` - ダウンロードするプリセット辞書(例: `PresetDictionaryKind::Ipadic`)。
-    /// * `dir` - 辞書ファイルが保存されるディレクトリ。
-    ///
-    /// # 戻り値
-    ///
-    /// ダウンロードされたZstandard圧縮辞書ファイルへの`PathBuf`を含む`Result`。
-    ///
-    /// # エラー
-    ///
-    /// この関数は以下の場合にエラーを返します:
-    /// - ダウンロードが失敗した場合。
-    /// - ファイルが破損している場合。
-    /// - ファイルシステム権限エラーがある場合。
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// # use std::path::Path;
-    /// # use vibrato_rkyv::{Dictionary, dictionary::PresetDictionaryKind, CacheStrategy};
-    /// # let dir = Path::new("./cache_dir");
-    /// let dict_path = Dictionary::download_dictionary(
-    ///     PresetDictionaryKind::UnidicCwj,
-    ///     dir,
-    /// ).unwrap();
-    ///
-    /// println!("辞書のダウンロード先: {:?}", dict_path);
-    ///
-    /// let dictionary = Dictionary::from_zstd(dict_path, CacheStrategy::Local).unwrap();
-    /// ```
-    #[cfg(feature = "download")]
-    pub fn download_dictionary<P: AsRef<std::path::Path>>(kind: PresetDictionaryKind, dir: P) -> Result<std::path::PathBuf> {
-        Ok(fetch::download_dictionary(kind, dir)?)
-    }
-
-    /// Zstandard圧縮辞書を指定されたパスに展開します。
-    ///
-    /// この関数は、`.zst`圧縮辞書を読み込み、その内容を検証し、
-    /// 展開された辞書を`output_path`に書き込みます。
-    ///
-    /// これは、アプリケーションのセットアップ、テスト、または
-    /// カスタムキャッシュ管理に有用な低レベルユーティリティです。
-    ///
-    /// # 引数
-    ///
-    /// * `input_path` - Zstandard圧縮辞書ファイルへのパス。
-    /// * `output_path` - 展開された辞書が保存されるパス。
-    ///
-    /// # 戻り値
-    ///
-    /// 成功時は`Ok(())`。
-    ///
-    /// # エラー
-    ///
-    /// この関数は以下の場合にエラーを返します:
-    /// - 入力ファイルを読み込めない場合。
-    /// - 有効なZstandard圧縮アーカイブでない場合。
-    /// - 展開されたデータが有効な辞書でない場合。
-    /// - 出力パスに書き込めない場合。
-    pub fn decompress_zstd<P, Q>(input_path: P, output_path: Q) -> Result<()>
-    where
-        P: AsRef<std::path::Path>,
-        Q: AsRef<std::path::Path>,
-    {
-        let input_path = input_path.as_ref();
-        let output_path = output_path.as_ref();
-
-        let output_dir = output_path.parent().ok_or_else(|| {
-            VibratoError::invalid_argument("output_path", "Output path must have a parent directory.")
-        })?;
-        std::fs::create_dir_all(output_dir)?;
-
-        let zstd_file = File::open(input_path)?;
-        let mut temp_file = tempfile::NamedTempFile::new_in(output_dir)?;
-
-        let mut decoder = zstd::Decoder::new(zstd_file)?;
-        io::copy(&mut decoder, &mut temp_file)?;
-
-        temp_file.seek(SeekFrom::Start(0))?;
-        let mut magic = [0; MODEL_MAGIC_LEN];
-        temp_file.read_exact(&mut magic)?;
-
-        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
-            ));
-        } else if !magic.starts_with(MODEL_MAGIC) {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "The magic number of the input model mismatches.",
-            ));
-        }
-
-        temp_file.seek(SeekFrom::Start(0))?;
-        let mut data_bytes = Vec::new();
-        temp_file.as_file_mut().read_to_end(&mut data_bytes)?;
-
-        let mut aligned_bytes: AlignedVec = AlignedVec::with_capacity(data_bytes.len());
-        aligned_bytes.extend_from_slice(&data_bytes);
-
-        let Some(data_bytes) = &aligned_bytes.get(DATA_START..) else {
-            return Err(VibratoError::invalid_argument(
-                "path",
-                "Dictionary file too small or corrupted.",
-            ));
-        };
-
-        let _ = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
-            VibratoError::invalid_state(
-                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
-                    .to_string(),
-                e.to_string(),
-            )
-        })?;
-
-        temp_file.persist(output_path)?;
-
-        Ok(())
+        Ok(Self::Owned {
+            dict: Arc::new(DictionaryInner::try_from(legacy_dict_inner)?),
+            _caching_handle: None,
+            #[cfg(feature = "loaders")]
+            content_hash: OnceLock::new(),
+        })
     }
 }
 
-/// ファイルメタデータからハッシュを計算します。
-///
-/// この関数は、ファイルのメタデータ(サイズ、更新時刻、iノードなど)から
-/// 一意のSHA256ハッシュを生成します。このハッシュは、キャッシュファイルの
-/// 命名とファイルの同一性確認に使用されます。
+/// 辞書ファイル全体のバイト列から、rkyvでシリアライズされた辞書データを取り出します。
 ///
-/// # 引数
-///
-/// * `meta` - ハッシュを計算するファイルのメタデータ。
-///
-/// # 戻り値
-///
-/// メタデータのSHA256ハッシュの16進数表現文字列。
-///
-/// # プラットフォーム固有の動作
-///
-/// - Unix: デバイスID、iノード、サイズ、変更時刻を使用
-/// - Windows: ファイルサイズ、最終書き込み時刻、作成時刻、ファイル属性を使用
-/// - その他: ファイルタイプ、読み取り専用フラグ、サイズ、変更時刻、作成時刻を使用
-#[inline(always)]
-pub(crate) fn compute_metadata_hash(meta: &Metadata) -> String {
-    let mut hasher = Sha256::new();
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::MetadataExt;
-        hasher.update(meta.dev().to_le_bytes());
-        hasher.update(meta.ino().to_le_bytes());
-        hasher.update(meta.size().to_le_bytes());
-        hasher.update(meta.mtime().to_le_bytes());
-        hasher.update(meta.mtime_nsec().to_le_bytes());
-    }
-
-    #[cfg(windows)]
-    {
-        use std::os::windows::fs::MetadataExt;
-        hasher.update(meta.file_size().to_le_bytes());
-        hasher.update(meta.last_write_time().to_le_bytes());
-        hasher.update(meta.creation_time().to_le_bytes());
-        hasher.update(meta.file_attributes().to_le_bytes());
-    }
-
-    #[cfg(not(any(unix, windows)))]
-    {
-        use std::time::SystemTime;
-
-        fn update_system_time(
-            time: Result<SystemTime, std::io::Error>,
-            hasher: &mut Sha256,
-        ) {
-            match time.and_then(|t| {
-                t.duration_since(SystemTime::UNIX_EPOCH)
-                    .map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))
-            }) {
-                Ok(duration) => {
-                    hasher.update(duration.as_secs().to_le_bytes());
-                    hasher.update(duration.subsec_nanos().to_le_bytes());
-                }
-                Err(_) => {
-                    hasher.update([0u8; 12]);
-                }
-            }
-        }
-
-        let file_type = meta.file_type();
-        let type_byte: u8 = if file_type.is_file() { 0x01 }
-        else if file_type.is_dir() { 0x02 }
-        else if file_type.is_symlink() { 0x03 }
-        else { 0x00 };
-        hasher.update([type_byte]);
-
-        let readonly_byte: u8 = if meta.permissions().readonly() { 0x01 } else { 0x00 };
-        hasher.update([readonly_byte]);
-
-        hasher.update(meta.len().to_le_bytes());
-
-        update_system_time(meta.modified(), &mut hasher);
-
-        update_system_time(meta.created(), &mut hasher);
+/// マジックナンバーとヘッダを確認し、追記されたデータを切り離します。
+/// 辞書データ自体は検証しません。
+fn split_file_bytes(bytes: &[u8]) -> Result<(&[u8], Option<DictionaryMetadata>)> {
+    if bytes.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
+        return Err(VibratoError::invalid_argument(
+            "bytes",
+            "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
+        ));
+    } else if !bytes.starts_with(MODEL_MAGIC) {
+        return Err(VibratoError::invalid_argument(
+            "bytes",
+            "The magic number of the input model mismatches.",
+        ));
     }
-
-    hex::encode(hasher.finalize())
+    let Some(full_bytes) = bytes.get(DATA_START..) else {
+        return Err(VibratoError::invalid_argument(
+            "bytes",
+            "Dictionary file too small or corrupted.",
+        ));
+    };
+    let header = SectionHeader::parse(&bytes[MODEL_MAGIC_LEN..DATA_START])?;
+    let (data_bytes, dict_metadata) = metadata::split_metadata(full_bytes)?;
+    header.check(full_bytes, data_bytes)?;
+    Ok((data_bytes, dict_metadata))
 }
 
 impl<'a> DictionaryInnerRef<'a> {
@@ -1537,6 +2094,22 @@ impl<'a> DictionaryInnerRef<'a> {
             },
         }
     }
+
+    /// すべてのユーザー辞書の単語数の合計を取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// ユーザー辞書が存在する場合は`Some(単語数)`、存在しない場合は`None`。
+    pub(crate) fn num_user_words(&self) -> Option<usize> {
+        match self {
+            DictionaryInnerRef::Archived(dict) => {
+                dict.user_lexicons().map(|(_, lexicon)| lexicon.num_words()).reduce(|a, b| a + b)
+            }
+            DictionaryInnerRef::Owned(dict) => {
+                dict.user_lexicons().map(|(_, lexicon)| lexicon.num_words()).reduce(|a, b| a + b)
+            }
+        }
+    }
 }
 
 impl ArchivedDictionaryInner {
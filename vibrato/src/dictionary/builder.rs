@@ -2,13 +2,33 @@
 //!
 //! このモジュールは、MeCab形式の辞書ファイルから [`DictionaryInner`] を構築するための
 //! ビルダーを提供します。
+//!
+//! コンパイラの各サブコマンドが行う処理（bigram情報からの構築、デュアルコネクタの組み立て、
+//! 接続IDマッピングの適用、zstdでの圧縮）はすべてこのモジュールから利用できるため、
+//! サーバーアプリケーションなどで辞書をプロセス内で再構築し、差し替えることができます。
+//!
+//! 構築した辞書には、[`DictionaryInner::write_with_metadata()`]で名前やライセンスなどの
+//! [`DictionaryMetadata`]を埋め込めます。
 
-use std::io::Read;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::dictionary::connector::{DualConnector, MatrixConnector, RawConnector};
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+
+use crate::dictionary::connector::{
+    DualConnector, MatrixConnector, RawConnector, RawConnectorBuilder,
+};
 use crate::dictionary::{
-    CharProperty, ConnectorWrapper, DictionaryInner, LexType, Lexicon, UnkHandler,
+    feature_pool, metadata, surface, ArchivedDictionaryInner, CharProperty, ConnectorWrapper,
+    DictionaryInner, DictionaryMetadata, LexType, Lexicon, UnkHandler, MODEL_MAGIC, PADDING_LEN,
 };
+#[cfg(feature = "loaders")]
+use crate::dictionary::WriteOptions;
 use crate::errors::{Result, VibratoError};
 
 use super::lexicon::RawWordEntry;
@@ -16,6 +36,432 @@ use super::lexicon::RawWordEntry;
 /// システム辞書エントリから [`DictionaryInner`] を構築するビルダー
 pub struct SystemDictionaryBuilder {}
 
+/// ファイルから辞書を構築する際のソースファイル情報
+///
+/// [`SystemDictionaryBuilder::from_source()`]に渡して使用します。
+#[derive(Clone, Debug)]
+pub enum BuildSource {
+    /// matrix.defファイルから構築
+    FromMatrix {
+        /// 語彙ファイル(lex.csv)のパス
+        lexicon: PathBuf,
+        /// 連接コスト定義ファイル(matrix.def)のパス
+        matrix: PathBuf,
+        /// 文字定義ファイル(char.def)のパス
+        char_def: PathBuf,
+        /// 未知語定義ファイル(unk.def)のパス
+        unk_def: PathBuf,
+    },
+    /// モデルの学習で生成されたbigram情報ファイルから構築
+    FromBigram {
+        /// 語彙ファイル(lex.csv)のパス
+        lexicon: PathBuf,
+        /// 右接続ID情報ファイル(bigram.right)のパス
+        bigram_right: PathBuf,
+        /// 左接続ID情報ファイル(bigram.left)のパス
+        bigram_left: PathBuf,
+        /// バイグラムコストファイル(bigram.cost)のパス
+        bigram_cost: PathBuf,
+        /// 文字定義ファイル(char.def)のパス
+        char_def: PathBuf,
+        /// 未知語定義ファイル(unk.def)のパス
+        unk_def: PathBuf,
+        /// `true`の場合、デュアルコネクタを使用します
+        dual_connector: bool,
+    },
+}
+
+/// bigram情報から辞書を構築する際に使用するコネクターの種類
+///
+/// どちらもMeCab形式の`matrix.def`を展開するより小さな辞書になります。
+/// 速度とメモリ使用量のトレードオフに応じて選択してください。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BigramConnectorKind {
+    /// [`RawConnector`]
+    ///
+    /// すべての素性の重みを接続のたびにSIMDで合計します。最もメモリ効率が良い一方、
+    /// 解析速度は素性テンプレート数に比例して遅くなります。
+    #[default]
+    Raw,
+    /// [`DualConnector`]
+    ///
+    /// [`DualConnector::MIN_FEATURE_TEMPLATES`]個の素性テンプレートのみを都度計算し、
+    /// 残りを接続コスト行列に展開します。行列の分だけ大きくなりますが、[`Raw`](Self::Raw)より高速です。
+    /// 素性テンプレート数がこの閾値より少ない場合は、[`Raw`](Self::Raw)で構築されます。
+    Dual,
+}
+
+/// システム辞書の構築オプション
+///
+/// [`SystemDictionaryBuilder::from_source_with_options()`]に渡して使用します。
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::dictionary::builder::{BuildOptions, SourceEncoding};
+///
+/// let options = BuildOptions::new()
+///     .encoding(SourceEncoding::Utf8)
+///     .num_threads(4);
+/// ```
+#[derive(Clone)]
+pub struct BuildOptions {
+    encoding: SourceEncoding,
+    num_threads: usize,
+    progress: Option<Arc<dyn BuildProgress>>,
+    max_feature_fields: Option<usize>,
+    connector_kind: Option<BigramConnectorKind>,
+    dedup_features: bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            encoding: SourceEncoding::Utf8,
+            num_threads: 1,
+            progress: None,
+            max_feature_fields: None,
+            connector_kind: None,
+            dedup_features: false,
+        }
+    }
+}
+
+impl fmt::Debug for BuildOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildOptions")
+            .field("encoding", &self.encoding)
+            .field("num_threads", &self.num_threads)
+            .field("progress", &self.progress.is_some())
+            .field("max_feature_fields", &self.max_feature_fields)
+            .field("connector_kind", &self.connector_kind)
+            .field("dedup_features", &self.dedup_features)
+            .finish()
+    }
+}
+
+impl BuildOptions {
+    /// デフォルトのオプションを作成します。
+    ///
+    /// ソースファイルはUTF-8として読み込まれ、単一のスレッドで構築されます。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ソースファイルの文字コードを設定します。
+    pub const fn encoding(mut self, encoding: SourceEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// 構築に使用するスレッド数を設定します。
+    ///
+    /// 2以上の場合、語彙ファイル、接続コスト、文字定義と未知語定義の読み込みと構築を
+    /// 並行して行い、語彙のトライの構築に使用する単語の整列も指定されたスレッド数で
+    /// 行います。構築される辞書はスレッド数に依存しません。
+    ///
+    /// # パニック
+    ///
+    /// `num_threads`が0の場合にパニックします。
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        assert!(num_threads >= 1, "num_threads must be at least 1");
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// 構築の進捗を受け取るオブジェクトを設定します。
+    ///
+    /// 複数のスレッドで構築する場合、各段階は並行して開始・終了します。
+    pub fn progress(mut self, progress: Arc<dyn BuildProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// 素性を先頭から指定された数の項目までに切り詰めます。
+    ///
+    /// [`DictionaryInner::truncate_features()`]を参照してください。
+    ///
+    /// # パニック
+    ///
+    /// `num_fields`が0の場合にパニックします。
+    pub fn max_feature_fields(mut self, num_fields: usize) -> Self {
+        assert!(num_fields >= 1, "num_fields must be at least 1");
+        self.max_feature_fields = Some(num_fields);
+        self
+    }
+
+    /// bigram情報から構築する際のコネクターの種類を設定します。
+    ///
+    /// [`BuildSource::FromBigram`]の`dual_connector`より優先されます。
+    /// [`BuildSource::FromMatrix`]から構築する場合は無視されます。
+    pub const fn bigram_connector(mut self, kind: BigramConnectorKind) -> Self {
+        self.connector_kind = Some(kind);
+        self
+    }
+
+    /// 構築した辞書で、同一の素性を一度だけ格納するかを設定します。
+    ///
+    /// [`DictionaryInner::dedup_features()`]を参照してください。
+    pub const fn dedup_features(mut self, yes: bool) -> Self {
+        self.dedup_features = yes;
+        self
+    }
+
+    /// 段階の開始と終了を通知しながら`f`を実行します。
+    fn run_stage<T>(&self, stage: BuildStage, f: impl FnOnce() -> T) -> T {
+        if let Some(progress) = &self.progress {
+            progress.start(stage);
+        }
+        let result = f();
+        if let Some(progress) = &self.progress {
+            progress.finish(stage);
+        }
+        result
+    }
+}
+
+/// システム辞書の構築の段階
+///
+/// [`BuildProgress`]に通知されます。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BuildStage {
+    /// 語彙ファイル(lex.csv)の解析
+    ParseLexicon,
+    /// 語彙のトライの構築
+    BuildTrie,
+    /// 接続コスト(matrix.defまたはbigram情報)の読み込みと構築
+    BuildConnector,
+    /// 文字定義(char.def)と未知語定義(unk.def)の読み込み
+    BuildUnkHandler,
+}
+
+impl fmt::Display for BuildStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::ParseLexicon => "Parsing the lexicon",
+            Self::BuildTrie => "Building the trie",
+            Self::BuildConnector => "Building the connector",
+            Self::BuildUnkHandler => "Reading char.def and unk.def",
+        };
+        f.write_str(name)
+    }
+}
+
+/// システム辞書の構築の進捗を受け取るトレイト
+///
+/// [`BuildOptions::progress()`]で設定すると、各[`BuildStage`]の開始時と終了時に
+/// 呼び出されます。構築が失敗した段階についても`finish`は呼び出されます。
+///
+/// # 例
+///
+/// ```
+/// use std::sync::Arc;
+/// use vibrato_rkyv::dictionary::builder::{BuildOptions, BuildProgress, BuildStage};
+///
+/// struct Logger;
+///
+/// impl BuildProgress for Logger {
+///     fn start(&self, stage: BuildStage) {
+///         eprintln!("{stage}...");
+///     }
+/// }
+///
+/// let options = BuildOptions::new().progress(Arc::new(Logger));
+/// ```
+pub trait BuildProgress: Send + Sync {
+    /// 段階の開始時に呼び出されます。
+    fn start(&self, stage: BuildStage) {
+        let _ = stage;
+    }
+
+    /// 段階の終了時に呼び出されます。
+    fn finish(&self, stage: BuildStage) {
+        let _ = stage;
+    }
+}
+
+/// 辞書ソースファイルの文字コード
+///
+/// UTF-8以外の文字コードを扱うには`encoding`フィーチャーを有効にする必要があります。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceEncoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// EUC-JP（MeCab IPADICの配布形式）
+    #[cfg(feature = "encoding")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encoding")))]
+    EucJp,
+    /// Shift_JIS
+    #[cfg(feature = "encoding")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encoding")))]
+    ShiftJis,
+}
+
+impl SourceEncoding {
+    /// ファイルを読み込み、この文字コードからUTF-8の文字列に変換します。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - 読み込むファイルのパス
+    /// * `arg` - エラーメッセージに使用するファイルの種別
+    ///
+    /// # エラー
+    ///
+    /// ファイルを読み込めない場合や、指定された文字コードとして不正なバイト列を含む場合に
+    /// [`VibratoError`] を返します。
+    pub(crate) fn read_to_string(self, path: &Path, arg: &'static str) -> Result<String> {
+        let bytes = fs::read(path)?;
+        let text = match self {
+            Self::Utf8 => String::from_utf8(bytes).ok(),
+            #[cfg(feature = "encoding")]
+            Self::EucJp => decode_legacy(encoding_rs::EUC_JP, &bytes),
+            #[cfg(feature = "encoding")]
+            Self::ShiftJis => decode_legacy(encoding_rs::SHIFT_JIS, &bytes),
+        };
+        text.ok_or_else(|| {
+            VibratoError::invalid_format(arg, format!("{} is not valid {self}.", path.display()))
+        })
+    }
+}
+
+impl fmt::Display for SourceEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "UTF-8"),
+            #[cfg(feature = "encoding")]
+            Self::EucJp => write!(f, "EUC-JP"),
+            #[cfg(feature = "encoding")]
+            Self::ShiftJis => write!(f, "Shift_JIS"),
+        }
+    }
+}
+
+impl FromStr for SourceEncoding {
+    type Err = VibratoError;
+
+    /// 文字コード名から [`SourceEncoding`] を作成します。
+    ///
+    /// 大文字と小文字は区別しません。`utf-8`、`euc-jp`、`shift_jis`のほか、
+    /// `utf8`、`eucjp`、`sjis`、`cp932`などの別名も受け付けます。
+    ///
+    /// # エラー
+    ///
+    /// 未知の文字コード名の場合や、`encoding`フィーチャーが無効でUTF-8以外が指定された場合に
+    /// [`VibratoError`] を返します。
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            #[cfg(feature = "encoding")]
+            "euc-jp" | "eucjp" | "euc_jp" => Ok(Self::EucJp),
+            #[cfg(feature = "encoding")]
+            "shift_jis" | "shift-jis" | "sjis" | "cp932" | "windows-31j" => Ok(Self::ShiftJis),
+            #[cfg(not(feature = "encoding"))]
+            "euc-jp" | "eucjp" | "euc_jp" | "shift_jis" | "shift-jis" | "sjis" | "cp932"
+            | "windows-31j" => Err(VibratoError::invalid_argument(
+                "encoding",
+                format!("{s} requires the `encoding` feature."),
+            )),
+            _ => Err(VibratoError::invalid_argument(
+                "encoding",
+                format!("Unknown encoding: {s}"),
+            )),
+        }
+    }
+}
+
+/// バイト列を指定された文字コードからUTF-8に変換します。
+///
+/// 不正なバイト列を含む場合は`None`を返します。
+#[cfg(feature = "encoding")]
+fn decode_legacy(encoding: &'static encoding_rs::Encoding, bytes: &[u8]) -> Option<String> {
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    (!had_errors).then(|| text.into_owned())
+}
+
+/// MeCab形式の辞書ディレクトリに含まれるソースファイル
+///
+/// `mecab-dict-index`と同様に、ディレクトリ内の`*.csv`をシステム辞書の語彙として扱います。
+/// [`SystemDictionaryBuilder::from_dir()`]はこの情報を使って辞書を構築します。
+#[derive(Clone, Debug)]
+pub struct SourceDir {
+    /// 語彙ファイル(`*.csv`)のパス
+    ///
+    /// ファイル名順に並んでおり、`user.csv`は含みません。
+    pub lexicons: Vec<PathBuf>,
+    /// 連接コスト定義ファイル(matrix.def)のパス
+    pub matrix: PathBuf,
+    /// 文字定義ファイル(char.def)のパス
+    pub char_def: PathBuf,
+    /// 未知語定義ファイル(unk.def)のパス
+    pub unk_def: PathBuf,
+    /// ユーザ辞書ファイル(user.csv)のパス
+    pub user_lexicon: Option<PathBuf>,
+    /// 素性の書き換え規則ファイル(rewrite.def)のパス
+    ///
+    /// 辞書の構築には使用されません。モデルの学習に渡す場合などに利用してください。
+    pub rewrite_def: Option<PathBuf>,
+}
+
+impl SourceDir {
+    /// ディレクトリからソースファイルを探します。
+    ///
+    /// # 引数
+    ///
+    /// * `dir` - MeCab形式の辞書ディレクトリ
+    ///
+    /// # エラー
+    ///
+    /// ディレクトリを読み込めない場合や、`matrix.def`、`char.def`、`unk.def`、
+    /// または語彙ファイルが見つからない場合に [`VibratoError`] を返します。
+    pub fn discover<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let required = |name: &str| {
+            let path = dir.join(name);
+            if path.is_file() {
+                Ok(path)
+            } else {
+                Err(VibratoError::invalid_argument(
+                    "dir",
+                    format!("{name} was not found in {}.", dir.display()),
+                ))
+            }
+        };
+        let optional = |name: &str| Some(dir.join(name)).filter(|path| path.is_file());
+
+        let mut lexicons = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file()
+                && path.extension().is_some_and(|ext| ext == "csv")
+                && path.file_name().is_some_and(|name| name != "user.csv")
+            {
+                lexicons.push(path);
+            }
+        }
+        if lexicons.is_empty() {
+            return Err(VibratoError::invalid_argument(
+                "dir",
+                format!("No lexicon file (*.csv) was found in {}.", dir.display()),
+            ));
+        }
+        lexicons.sort_unstable();
+
+        Ok(Self {
+            lexicons,
+            matrix: required("matrix.def")?,
+            char_def: required("char.def")?,
+            unk_def: required("unk.def")?,
+            user_lexicon: optional("user.csv"),
+            rewrite_def: optional("rewrite.def"),
+        })
+    }
+}
+
 impl SystemDictionaryBuilder {
     /// パースされたコンポーネントから `DictionaryInner` を構築します。
     ///
@@ -40,7 +486,16 @@ impl SystemDictionaryBuilder {
         unk_handler: UnkHandler,
     ) -> Result<DictionaryInner> {
         let system_lexicon = Lexicon::from_entries(system_word_entries, LexType::System)?;
+        Self::from_parts(system_lexicon, connector, char_prop, unk_handler)
+    }
 
+    /// 構築済みのコンポーネントを検証し、`DictionaryInner`にまとめます。
+    fn from_parts(
+        system_lexicon: Lexicon,
+        connector: ConnectorWrapper,
+        char_prop: CharProperty,
+        unk_handler: UnkHandler,
+    ) -> Result<DictionaryInner> {
         if !system_lexicon.verify(&connector) {
             return Err(VibratoError::invalid_argument(
                 "system_lexicon_rdr",
@@ -61,6 +516,7 @@ impl SystemDictionaryBuilder {
             mapper: None,
             char_prop,
             unk_handler,
+            feature_encoding: feature_pool::FeatureEncoding::default(),
         })
     }
 
@@ -121,13 +577,13 @@ impl SystemDictionaryBuilder {
     ///  - `bigram_cost_rdr`: バイグラムコストファイル `bigram.cost` のリーダー
     ///  - `char_prop_rdr`: 文字定義ファイル `char.def` のリーダー
     ///  - `unk_handler_rdr`: 未知語定義ファイル `unk.def` のリーダー
-    ///  - `dual_connector`: `true` の場合、辞書は速度低下を制御します
+    ///  - `dual_connector`: `true` の場合、[`BigramConnectorKind::Dual`]を使用して速度低下を抑えます
     ///
     /// # エラー
     ///
     /// 入力フォーマットが不正な場合に [`VibratoError`] を返します。
     pub fn from_readers_with_bigram_info<S, R, L, C, P, U>(
-        mut system_lexicon_rdr: S,
+        system_lexicon_rdr: S,
         bigram_right_rdr: R,
         bigram_left_rdr: L,
         bigram_cost_rdr: C,
@@ -135,6 +591,57 @@ impl SystemDictionaryBuilder {
         unk_handler_rdr: U,
         dual_connector: bool,
     ) -> Result<DictionaryInner>
+    where
+        S: Read,
+        R: Read,
+        L: Read,
+        C: Read,
+        P: Read,
+        U: Read,
+    {
+        let kind = if dual_connector {
+            BigramConnectorKind::Dual
+        } else {
+            BigramConnectorKind::Raw
+        };
+        Self::from_readers_with_bigram_connector(
+            system_lexicon_rdr,
+            bigram_right_rdr,
+            bigram_left_rdr,
+            bigram_cost_rdr,
+            char_prop_rdr,
+            unk_handler_rdr,
+            kind,
+        )
+    }
+
+    /// システムエントリから、指定された種類のコネクターを使用する新しい [`DictionaryInner`] を作成します。
+    ///
+    /// モデルの学習で出力されたbigram情報（`Model::write_bigram_details()`を参照）から、
+    /// 学習器を経由せずに辞書を構築できます。
+    ///
+    /// # 引数
+    ///
+    ///  - `system_lexicon_rdr`: 辞書ファイル `*.csv` のリーダー
+    ///  - `bigram_right_rdr`: 右IDに関連付けられたバイグラム情報ファイル `bigram.right` のリーダー
+    ///  - `bigram_left_rdr`: 左IDに関連付けられたバイグラム情報ファイル `bigram.left` のリーダー
+    ///  - `bigram_cost_rdr`: バイグラムコストファイル `bigram.cost` のリーダー
+    ///  - `char_prop_rdr`: 文字定義ファイル `char.def` のリーダー
+    ///  - `unk_handler_rdr`: 未知語定義ファイル `unk.def` のリーダー
+    ///  - `kind`: 使用するコネクターの種類
+    ///
+    /// # エラー
+    ///
+    /// 入力フォーマットが不正な場合に [`VibratoError`] を返します。
+    pub fn from_readers_with_bigram_connector<S, R, L, C, P, U>(
+        mut system_lexicon_rdr: S,
+        bigram_right_rdr: R,
+        bigram_left_rdr: L,
+        bigram_cost_rdr: C,
+        char_prop_rdr: P,
+        unk_handler_rdr: U,
+        kind: BigramConnectorKind,
+    ) -> Result<DictionaryInner>
     where
         S: Read,
         R: Read,
@@ -146,24 +653,584 @@ impl SystemDictionaryBuilder {
         let mut system_lexicon_buf = vec![];
         system_lexicon_rdr.read_to_end(&mut system_lexicon_buf)?;
         let system_word_entries = Lexicon::parse_csv(&system_lexicon_buf, "lex.csv")?;
-        let connector = if dual_connector {
-            ConnectorWrapper::Dual(DualConnector::from_readers(
-                bigram_right_rdr,
-                bigram_left_rdr,
-                bigram_cost_rdr,
-            )?)
-        } else {
-            ConnectorWrapper::Raw(RawConnector::from_readers(
-                bigram_right_rdr,
-                bigram_left_rdr,
-                bigram_cost_rdr,
-            )?)
-        };
+        let raw_builder =
+            RawConnectorBuilder::from_readers(bigram_right_rdr, bigram_left_rdr, bigram_cost_rdr)?;
+        let connector = Self::bigram_connector(raw_builder, kind)?;
         let char_prop = CharProperty::from_reader(char_prop_rdr)?;
         let unk_handler = UnkHandler::from_reader(unk_handler_rdr, &char_prop)?;
 
         Self::build(&system_word_entries, connector, char_prop, unk_handler)
     }
+
+    /// bigram情報から指定された種類のコネクターを構築します。
+    fn bigram_connector(
+        raw_builder: RawConnectorBuilder,
+        kind: BigramConnectorKind,
+    ) -> Result<ConnectorWrapper> {
+        let connector = match kind {
+            BigramConnectorKind::Dual
+                if DualConnector::MIN_FEATURE_TEMPLATES <= raw_builder.feat_template_size =>
+            {
+                ConnectorWrapper::Dual(DualConnector::from_builder(raw_builder)?)
+            }
+            BigramConnectorKind::Dual => {
+                log::warn!(
+                    "[vibrato-rkyv] The dual connector requires at least {} feature templates, \
+                     but got {}. Falling back to the raw connector.",
+                    DualConnector::MIN_FEATURE_TEMPLATES,
+                    raw_builder.feat_template_size,
+                );
+                ConnectorWrapper::Raw(RawConnector::from_builder(raw_builder))
+            }
+            BigramConnectorKind::Raw => {
+                ConnectorWrapper::Raw(RawConnector::from_builder(raw_builder))
+            }
+        };
+        Ok(connector)
+    }
+
+    /// [`BuildSource`]で指定されたファイルから新しい [`DictionaryInner`] を作成します。
+    ///
+    /// # 引数
+    ///
+    ///  - `source`: ソースファイル情報
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合や、入力フォーマットが不正な場合に [`VibratoError`] を返します。
+    pub fn from_source(source: &BuildSource) -> Result<DictionaryInner> {
+        match source {
+            BuildSource::FromMatrix { lexicon, matrix, char_def, unk_def } => Self::from_readers(
+                File::open(lexicon)?,
+                File::open(matrix)?,
+                File::open(char_def)?,
+                File::open(unk_def)?,
+            ),
+            BuildSource::FromBigram {
+                lexicon,
+                bigram_right,
+                bigram_left,
+                bigram_cost,
+                char_def,
+                unk_def,
+                dual_connector,
+            } => Self::from_readers_with_bigram_info(
+                File::open(lexicon)?,
+                File::open(bigram_right)?,
+                File::open(bigram_left)?,
+                File::open(bigram_cost)?,
+                File::open(char_def)?,
+                File::open(unk_def)?,
+                *dual_connector,
+            ),
+        }
+    }
+
+    /// [`BuildSource`]で指定された、指定の文字コードのファイルから新しい [`DictionaryInner`] を作成します。
+    ///
+    /// EUC-JPで配布されているMeCab IPADICなどを、事前に`iconv`や`nkf`で変換せずに構築できます。
+    /// UTF-8の場合は [`from_source()`](Self::from_source) と同じです。
+    ///
+    /// # 引数
+    ///
+    ///  - `source`: ソースファイル情報
+    ///  - `encoding`: ソースファイルの文字コード
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合、指定された文字コードとして不正なバイト列を含む場合、
+    /// または入力フォーマットが不正な場合に [`VibratoError`] を返します。
+    pub fn from_source_with_encoding(
+        source: &BuildSource,
+        encoding: SourceEncoding,
+    ) -> Result<DictionaryInner> {
+        if encoding == SourceEncoding::Utf8 {
+            return Self::from_source(source);
+        }
+        match source {
+            BuildSource::FromMatrix { lexicon, matrix, char_def, unk_def } => Self::from_readers(
+                encoding.read_to_string(lexicon, "lex.csv")?.as_bytes(),
+                encoding.read_to_string(matrix, "matrix.def")?.as_bytes(),
+                encoding.read_to_string(char_def, "char.def")?.as_bytes(),
+                encoding.read_to_string(unk_def, "unk.def")?.as_bytes(),
+            ),
+            BuildSource::FromBigram {
+                lexicon,
+                bigram_right,
+                bigram_left,
+                bigram_cost,
+                char_def,
+                unk_def,
+                dual_connector,
+            } => Self::from_readers_with_bigram_info(
+                encoding.read_to_string(lexicon, "lex.csv")?.as_bytes(),
+                encoding.read_to_string(bigram_right, "bigram.right")?.as_bytes(),
+                encoding.read_to_string(bigram_left, "bigram.left")?.as_bytes(),
+                encoding.read_to_string(bigram_cost, "bigram.cost")?.as_bytes(),
+                encoding.read_to_string(char_def, "char.def")?.as_bytes(),
+                encoding.read_to_string(unk_def, "unk.def")?.as_bytes(),
+                *dual_connector,
+            ),
+        }
+    }
+
+    /// [`BuildSource`]で指定されたファイルから、[`BuildOptions`]に従って新しい
+    /// [`DictionaryInner`] を作成します。
+    ///
+    /// 構築される辞書は [`from_source_with_encoding()`](Self::from_source_with_encoding)
+    /// と同じです。複数のスレッドを指定すると、UniDicのような大きな辞書の構築時間を
+    /// 短縮できます。
+    ///
+    /// # 引数
+    ///
+    ///  - `source`: ソースファイル情報
+    ///  - `options`: 構築オプション
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合、指定された文字コードとして不正なバイト列を含む場合、
+    /// または入力フォーマットが不正な場合に [`VibratoError`] を返します。
+    pub fn from_source_with_options(
+        source: &BuildSource,
+        options: &BuildOptions,
+    ) -> Result<DictionaryInner> {
+        let encoding = options.encoding;
+        let num_threads = options.num_threads;
+        let parallel = num_threads > 1;
+        let (lexicon_path, char_def, unk_def) = match source {
+            BuildSource::FromMatrix { lexicon, char_def, unk_def, .. }
+            | BuildSource::FromBigram { lexicon, char_def, unk_def, .. } => {
+                (lexicon, char_def, unk_def)
+            }
+        };
+
+        let build_lexicon = || -> Result<Lexicon> {
+            // The entries borrow the lexicon text, so it must outlive the stage.
+            let lexicon = encoding.read_to_string(lexicon_path, "lex.csv")?;
+            let entries = options.run_stage(BuildStage::ParseLexicon, || {
+                Lexicon::parse_csv(lexicon.as_bytes(), "lex.csv")
+            })?;
+            options.run_stage(BuildStage::BuildTrie, || {
+                Lexicon::from_entries_with_threads(&entries, LexType::System, num_threads)
+            })
+        };
+        let build_connector = || -> Result<ConnectorWrapper> {
+            options.run_stage(BuildStage::BuildConnector, || match source {
+                BuildSource::FromMatrix { matrix, .. } => {
+                    let matrix = encoding.read_to_string(matrix, "matrix.def")?;
+                    Ok(ConnectorWrapper::Matrix(MatrixConnector::from_reader(matrix.as_bytes())?))
+                }
+                BuildSource::FromBigram {
+                    bigram_right,
+                    bigram_left,
+                    bigram_cost,
+                    dual_connector,
+                    ..
+                } => {
+                    let raw_builder = RawConnectorBuilder::from_readers(
+                        encoding.read_to_string(bigram_right, "bigram.right")?.as_bytes(),
+                        encoding.read_to_string(bigram_left, "bigram.left")?.as_bytes(),
+                        encoding.read_to_string(bigram_cost, "bigram.cost")?.as_bytes(),
+                    )?;
+                    let kind = options.connector_kind.unwrap_or(if *dual_connector {
+                        BigramConnectorKind::Dual
+                    } else {
+                        BigramConnectorKind::Raw
+                    });
+                    Self::bigram_connector(raw_builder, kind)
+                }
+            })
+        };
+        let build_unk = || -> Result<(CharProperty, UnkHandler)> {
+            options.run_stage(BuildStage::BuildUnkHandler, || {
+                let char_def = encoding.read_to_string(char_def, "char.def")?;
+                let unk_def = encoding.read_to_string(unk_def, "unk.def")?;
+                let char_prop = CharProperty::from_reader(char_def.as_bytes())?;
+                let unk_handler = UnkHandler::from_reader(unk_def.as_bytes(), &char_prop)?;
+                Ok((char_prop, unk_handler))
+            })
+        };
+
+        let (system_lexicon, (connector, unk)) = crate::utils::join(parallel, build_lexicon, || {
+            crate::utils::join(parallel, build_connector, build_unk)
+        });
+        let (char_prop, unk_handler) = unk?;
+        let mut dict = Self::from_parts(system_lexicon?, connector?, char_prop, unk_handler)?;
+        if let Some(num_fields) = options.max_feature_fields {
+            dict = dict.truncate_features(num_fields);
+        }
+        if options.dedup_features {
+            dict = dict.dedup_features();
+        }
+        Ok(dict)
+    }
+
+    /// モバイルやWebAssemblyでの使用に向けた、小さな辞書を構築するためのオプションを返します。
+    ///
+    /// 素性を先頭の`num_feature_fields`項目までに切り詰め、bigram情報から構築する場合は
+    /// 最もメモリ効率の良い[`BigramConnectorKind::Raw`]を使用し、同一の素性を一度だけ
+    /// 格納します。素性の圧縮はzstdの展開を必要とするため使用しません。
+    ///
+    /// UniDicのように接続表が大きい辞書では、`matrix.def`ではなく学習で出力された
+    /// bigram情報から構築しなければ、辞書は十分に小さくなりません。
+    ///
+    /// # 引数
+    ///
+    /// * `num_feature_fields` - 残す素性の項目数（例: IPADICの品詞4項目と原形までであれば7）
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::dictionary::builder::BuildSource;
+    /// use vibrato_rkyv::SystemDictionaryBuilder;
+    ///
+    /// let source = BuildSource::FromMatrix {
+    ///     lexicon: "ipadic/lex.csv".into(),
+    ///     matrix: "ipadic/matrix.def".into(),
+    ///     char_def: "ipadic/char.def".into(),
+    ///     unk_def: "ipadic/unk.def".into(),
+    /// };
+    /// let options = SystemDictionaryBuilder::minimal_profile(7).num_threads(4);
+    /// let dict = SystemDictionaryBuilder::from_source_with_options(&source, &options)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # パニック
+    ///
+    /// `num_feature_fields`が0の場合にパニックします。
+    pub fn minimal_profile(num_feature_fields: usize) -> BuildOptions {
+        BuildOptions::new()
+            .max_feature_fields(num_feature_fields)
+            .bigram_connector(BigramConnectorKind::Raw)
+            .dedup_features(true)
+    }
+
+    /// MeCab形式の辞書ディレクトリから新しい [`DictionaryInner`] を作成します。
+    ///
+    /// ソースファイルはUTF-8として読み込みます。
+    /// 他の文字コードについては [`from_dir_with_encoding()`](Self::from_dir_with_encoding)
+    /// を参照してください。
+    ///
+    /// # 引数
+    ///
+    ///  - `dir`: `*.csv`、`matrix.def`、`char.def`、`unk.def`を含むディレクトリ
+    ///
+    /// # エラー
+    ///
+    /// ソースファイルが見つからない場合や、入力フォーマットが不正な場合に
+    /// [`VibratoError`] を返します。
+    pub fn from_dir<P>(dir: P) -> Result<DictionaryInner>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_dir_with_encoding(dir, SourceEncoding::Utf8)
+    }
+
+    /// 指定された文字コードのMeCab形式の辞書ディレクトリから新しい [`DictionaryInner`] を作成します。
+    ///
+    /// [`SourceDir::discover()`]で見つかった語彙ファイルをファイル名順に連結してシステム辞書を構築し、
+    /// `user.csv`が存在する場合はユーザ辞書として追加します。
+    ///
+    /// # 引数
+    ///
+    ///  - `dir`: `*.csv`、`matrix.def`、`char.def`、`unk.def`を含むディレクトリ
+    ///  - `encoding`: ソースファイルの文字コード
+    ///
+    /// # エラー
+    ///
+    /// ソースファイルが見つからない場合、指定された文字コードとして不正なバイト列を含む場合、
+    /// または入力フォーマットが不正な場合に [`VibratoError`] を返します。
+    pub fn from_dir_with_encoding<P>(dir: P, encoding: SourceEncoding) -> Result<DictionaryInner>
+    where
+        P: AsRef<Path>,
+    {
+        let source = SourceDir::discover(dir)?;
+
+        let mut lexicon = String::new();
+        for path in &source.lexicons {
+            lexicon.push_str(&encoding.read_to_string(path, "lex.csv")?);
+            if !lexicon.is_empty() && !lexicon.ends_with('\n') {
+                lexicon.push('\n');
+            }
+        }
+        let matrix = encoding.read_to_string(&source.matrix, "matrix.def")?;
+        let char_def = encoding.read_to_string(&source.char_def, "char.def")?;
+        let unk_def = encoding.read_to_string(&source.unk_def, "unk.def")?;
+
+        let dict = Self::from_readers(
+            lexicon.as_bytes(),
+            matrix.as_bytes(),
+            char_def.as_bytes(),
+            unk_def.as_bytes(),
+        )?;
+        match &source.user_lexicon {
+            Some(path) => {
+                let user_lexicon = encoding.read_to_string(path, "user.csv")?;
+                dict.reset_user_lexicon_from_reader(Some(user_lexicon.as_bytes()))
+            }
+            None => Ok(dict),
+        }
+    }
+}
+
+/// 接続IDのマッピングファイル（`*.lmap`または`*.rmap`）を読み込みます。
+///
+/// 各行の1列目（タブ区切り）を新しい接続IDとして読み込みます。
+///
+/// # 引数
+///
+/// * `rdr` - マッピングファイルのリーダー
+///
+/// # 戻り値
+///
+/// 接続IDのベクター
+///
+/// # エラー
+///
+/// 読み込みに失敗した場合や、接続IDが整数として解釈できない場合に [`VibratoError`] を返します。
+pub fn read_mapping<R>(rdr: R) -> Result<Vec<u16>>
+where
+    R: Read,
+{
+    let mut ids = vec![];
+    for line in BufReader::new(rdr).lines() {
+        let line = line?;
+        let id = line.split('\t').next().unwrap_or_default();
+        ids.push(id.parse()?);
+    }
+    Ok(ids)
+}
+
+impl DictionaryInner {
+    /// [`DictionaryInner::write()`]で出力された辞書を検証して読み込みます。
+    ///
+    /// 読み込んだ辞書は所有されたデータとなるため、マッピングの適用やユーザー辞書の
+    /// 差し替えを行ってから再び書き出すことができます。
+    /// 埋め込まれたメタデータは読み込まれません。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 辞書ファイルのリーダー
+    ///
+    /// # エラー
+    ///
+    /// マジックナンバーが一致しない場合や、データが破損している場合に [`VibratoError`] を返します。
+    pub fn read<R>(mut rdr: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut magic = vec![0; MODEL_MAGIC.len()];
+        rdr.read_exact(&mut magic)?;
+        if magic != MODEL_MAGIC {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "The magic number of the input model mismatches.",
+            ));
+        }
+        let mut padding = vec![0; PADDING_LEN];
+        rdr.read_exact(&mut padding)?;
+
+        let mut buffer = vec![];
+        rdr.read_to_end(&mut buffer)?;
+        let mut aligned_bytes = AlignedVec::<16>::with_capacity(buffer.len());
+        aligned_bytes.extend_from_slice(&buffer);
+
+        let (data_bytes, _) = metadata::split_metadata(&aligned_bytes)?;
+        let archived = rkyv::access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
+            VibratoError::invalid_state(
+                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
+                    .to_string(),
+                e.to_string(),
+            )
+        })?;
+        let mut dict = rkyv::deserialize::<Self, Error>(archived)?;
+        if let Some(features) = feature_pool::read_trailer(&aligned_bytes)? {
+            dict.set_detached_features(&features);
+        }
+        if let Some(surfaces) = surface::read_trailer(&aligned_bytes)? {
+            dict.set_surfaces(surfaces);
+        }
+        Ok(dict)
+    }
+
+    /// zstdで圧縮された辞書を検証して読み込みます。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 圧縮された辞書ファイルのリーダー
+    ///
+    /// # エラー
+    ///
+    /// 展開に失敗した場合や、[`DictionaryInner::read()`]と同じ条件で [`VibratoError`] を返します。
+    #[cfg(feature = "loaders")]
+    pub fn read_zstd<R>(rdr: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        Self::read(zstd::Decoder::new(rdr)?)
+    }
+
+    /// 辞書をzstdで圧縮して書き出します。
+    ///
+    /// 出力は[`Dictionary::from_zstd()`](crate::Dictionary::from_zstd)で読み込めます。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `level` - zstdの圧縮レベル（コンパイラは`19`を使用します）
+    ///
+    /// # エラー
+    ///
+    /// 書き込みや圧縮に失敗した場合に [`VibratoError`] を返します。
+    #[cfg(feature = "loaders")]
+    pub fn write_zstd<W>(&self, wtr: W, level: i32) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_zstd_with_options(wtr, &WriteOptions::new().level(level), None)
+    }
+
+    /// メタデータを埋め込んだ辞書をzstdで圧縮して書き出します。
+    ///
+    /// [`DictionaryInner::write_with_metadata()`]の出力をzstdで圧縮したものです。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `level` - zstdの圧縮レベル
+    /// * `metadata` - 埋め込むメタデータ
+    ///
+    /// # エラー
+    ///
+    /// 書き込みや圧縮に失敗した場合に [`VibratoError`] を返します。
+    #[cfg(feature = "loaders")]
+    pub fn write_zstd_with_metadata<W>(
+        &self,
+        wtr: W,
+        level: i32,
+        metadata: &DictionaryMetadata,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_zstd_with_options(wtr, &WriteOptions::new().level(level), Some(metadata))
+    }
+
+    /// 圧縮レベルやワーカースレッド数を指定して、辞書をzstdで圧縮して書き出します。
+    ///
+    /// 出力の先頭には、メタデータとダイジェストを記録した
+    /// [`CompressedHeader`](super::CompressedHeader)が置かれます。シリアライズした辞書は
+    /// 一時ファイルに書き出してから圧縮するため、シリアライズ後の辞書や圧縮データの全体が
+    /// メモリ上に保持されることはありません。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `options` - 圧縮のオプション
+    /// * `metadata` - 埋め込むメタデータ。`None`の場合は埋め込みません。
+    ///
+    /// # エラー
+    ///
+    /// 圧縮レベルが範囲外の場合や、書き込みや圧縮に失敗した場合に [`VibratoError`] を返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use vibrato_rkyv::SystemDictionaryBuilder;
+    /// # use vibrato_rkyv::dictionary::WriteOptions;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dict = SystemDictionaryBuilder::from_readers(
+    ///     File::open("lex.csv")?,
+    ///     File::open("matrix.def")?,
+    ///     File::open("char.def")?,
+    ///     File::open("unk.def")?,
+    /// )?;
+    /// let options = WriteOptions::new().level(19).workers(8);
+    /// dict.write_zstd_with_options(File::create("system.dic.zst")?, &options, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "loaders")]
+    pub fn write_zstd_with_options<W>(
+        &self,
+        wtr: W,
+        options: &WriteOptions,
+        metadata: Option<&DictionaryMetadata>,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        use std::io::{BufWriter, Seek, SeekFrom};
+
+        // The serialized dictionary is spooled to a temporary file rather than memory because
+        // `WriteOptions::compress` reads it twice to put its digest in the header.
+        let mut spool = BufWriter::new(tempfile::tempfile()?);
+        match metadata {
+            Some(metadata) => self.write_with_metadata(&mut spool, metadata)?,
+            None => self.write(&mut spool)?,
+        }
+        let mut spool = spool.into_inner().map_err(|e| e.into_error())?;
+        spool.seek(SeekFrom::Start(0))?;
+        options.compress(BufReader::new(spool), wtr, metadata)?;
+        Ok(())
+    }
+
+    /// マッピングファイルのリーダーから接続IDのマッピングを読み込み、適用します。
+    ///
+    /// [`read_mapping()`]と[`DictionaryInner::map_connection_ids_from_iter()`]を
+    /// 組み合わせたものです。
+    ///
+    /// # 引数
+    ///
+    /// * `lmap_rdr` - 左接続IDのマッピングファイル（`*.lmap`）のリーダー
+    /// * `rmap_rdr` - 右接続IDのマッピングファイル（`*.rmap`）のリーダー
+    ///
+    /// # エラー
+    ///
+    /// マッピングの読み込みや適用に失敗した場合に [`VibratoError`] を返します。
+    pub fn map_connection_ids_from_readers<L, R>(self, lmap_rdr: L, rmap_rdr: R) -> Result<Self>
+    where
+        L: Read,
+        R: Read,
+    {
+        let lmap = read_mapping(lmap_rdr)?;
+        let rmap = read_mapping(rmap_rdr)?;
+        self.map_connection_ids_from_iter(lmap, rmap)
+    }
+
+    /// 半角カナや全角英数字などの互換文字を、対応する正規の文字と同じ文字カテゴリに統一します。
+    ///
+    /// Webテキストのように半角・全角が混在する入力に対して、別途正規化を行わなくても
+    /// 未知語処理が一貫して動作するようにするためのものです。変換の対象と、トークンの
+    /// 位置との関係については[`CharProperty::fold_compatibility_chars()`]を参照してください。
+    ///
+    /// # 例
+    ///
+    /// ```
+    /// use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+    ///
+    /// let dict = SystemDictionaryBuilder::from_readers(
+    ///     "東京,0,0,0,東京".as_bytes(),
+    ///     "1 1\n0 0 0".as_bytes(),
+    ///     "DEFAULT 0 1 0\nKATAKANA 1 1 0\n0x30A1..0x30FA KATAKANA".as_bytes(),
+    ///     "DEFAULT,0,0,100,*\nKATAKANA,0,0,10,カタカナ".as_bytes(),
+    /// )?
+    /// .fold_compatibility_chars();
+    ///
+    /// let tokenizer = Tokenizer::from_inner(dict);
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("ｶﾀｶﾅ");
+    /// worker.tokenize();
+    /// assert_eq!(worker.num_tokens(), 1);
+    /// assert_eq!(worker.token(0).feature(), "カタカナ");
+    /// assert_eq!(worker.token(0).range_byte(), 0..12);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn fold_compatibility_chars(mut self) -> Self {
+        self.char_prop.fold_compatibility_chars();
+        self
+    }
 }
 
 #[cfg(test)]
@@ -203,4 +1270,343 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.csv"), "自然,0,0,1,sizen").unwrap();
+        fs::write(dir.path().join("a.csv"), "言語,0,0,1,gengo\n").unwrap();
+        fs::write(dir.path().join("matrix.def"), "1 1\n0 0 0").unwrap();
+        fs::write(dir.path().join("char.def"), "DEFAULT 0 1 0").unwrap();
+        fs::write(dir.path().join("unk.def"), "DEFAULT,0,0,100,*").unwrap();
+
+        let source = SourceDir::discover(dir.path()).unwrap();
+        assert_eq!(
+            source.lexicons,
+            vec![dir.path().join("a.csv"), dir.path().join("b.csv")],
+        );
+        assert!(source.user_lexicon.is_none());
+        assert!(source.rewrite_def.is_none());
+
+        let dict = SystemDictionaryBuilder::from_dir(dir.path()).unwrap();
+        let expected = SystemDictionaryBuilder::from_readers(
+            "言語,0,0,1,gengo\n自然,0,0,1,sizen\n".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let mut actual_bytes = vec![];
+        dict.write(&mut actual_bytes).unwrap();
+        let mut expected_bytes = vec![];
+        expected.write(&mut expected_bytes).unwrap();
+        assert_eq!(actual_bytes, expected_bytes);
+
+        fs::write(dir.path().join("user.csv"), "言語学,0,0,1,gengogaku").unwrap();
+        let dict = SystemDictionaryBuilder::from_dir(dir.path()).unwrap();
+        assert!(dict.user_lexicon.is_some());
+
+        fs::remove_file(dir.path().join("unk.def")).unwrap();
+        assert!(SystemDictionaryBuilder::from_dir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_source_encoding_from_str() {
+        assert_eq!("UTF-8".parse::<SourceEncoding>().unwrap(), SourceEncoding::Utf8);
+        assert!("latin1".parse::<SourceEncoding>().is_err());
+        #[cfg(feature = "encoding")]
+        {
+            assert_eq!("euc-jp".parse::<SourceEncoding>().unwrap(), SourceEncoding::EucJp);
+            assert_eq!("SJIS".parse::<SourceEncoding>().unwrap(), SourceEncoding::ShiftJis);
+        }
+        #[cfg(not(feature = "encoding"))]
+        assert!("euc-jp".parse::<SourceEncoding>().is_err());
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_from_source_with_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        // "自然" in Shift_JIS
+        fs::write(path("lex.csv"), b"\x8e\xa9\x91\x52,0,0,1,sizen").unwrap();
+        fs::write(path("matrix.def"), "1 1\n0 0 0").unwrap();
+        fs::write(path("char.def"), "DEFAULT 0 1 0").unwrap();
+        fs::write(path("unk.def"), "DEFAULT,0,0,100,*").unwrap();
+        let source = BuildSource::FromMatrix {
+            lexicon: path("lex.csv"),
+            matrix: path("matrix.def"),
+            char_def: path("char.def"),
+            unk_def: path("unk.def"),
+        };
+
+        let dict =
+            SystemDictionaryBuilder::from_source_with_encoding(&source, SourceEncoding::ShiftJis)
+                .unwrap();
+        let expected = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let mut actual_bytes = vec![];
+        dict.write(&mut actual_bytes).unwrap();
+        let mut expected_bytes = vec![];
+        expected.write(&mut expected_bytes).unwrap();
+        assert_eq!(actual_bytes, expected_bytes);
+    }
+
+    #[test]
+    fn test_from_source_with_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        let lexicon: String = (0..100)
+            .map(|i| format!("{}語,0,0,{i},w{i}\n", i % 7))
+            .collect();
+        fs::write(path("lex.csv"), &lexicon).unwrap();
+        fs::write(path("matrix.def"), "1 1\n0 0 0").unwrap();
+        fs::write(path("char.def"), "DEFAULT 0 1 0").unwrap();
+        fs::write(path("unk.def"), "DEFAULT,0,0,100,*").unwrap();
+        let source = BuildSource::FromMatrix {
+            lexicon: path("lex.csv"),
+            matrix: path("matrix.def"),
+            char_def: path("char.def"),
+            unk_def: path("unk.def"),
+        };
+
+        let mut expected_bytes = vec![];
+        SystemDictionaryBuilder::from_source(&source)
+            .unwrap()
+            .write(&mut expected_bytes)
+            .unwrap();
+        for num_threads in [1, 4] {
+            let progress = Arc::new(RecordProgress::default());
+            let options = BuildOptions::new()
+                .num_threads(num_threads)
+                .progress(Arc::clone(&progress) as Arc<dyn BuildProgress>);
+            let dict = SystemDictionaryBuilder::from_source_with_options(&source, &options).unwrap();
+            let mut actual_bytes = vec![];
+            dict.write(&mut actual_bytes).unwrap();
+            assert_eq!(actual_bytes, expected_bytes);
+
+            let events = progress.events.lock().unwrap();
+            assert_eq!(events.len(), 8);
+            let position = |event| events.iter().position(|e| *e == event).unwrap();
+            for stage in [
+                BuildStage::ParseLexicon,
+                BuildStage::BuildTrie,
+                BuildStage::BuildConnector,
+                BuildStage::BuildUnkHandler,
+            ] {
+                assert!(position((true, stage)) < position((false, stage)));
+            }
+            assert!(
+                position((false, BuildStage::ParseLexicon)) < position((true, BuildStage::BuildTrie))
+            );
+        }
+    }
+
+    #[test]
+    fn test_minimal_profile() {
+        use crate::dictionary::word_idx::WordIdx;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(
+            path("lex.csv"),
+            "東京,0,0,1,名詞,固有名詞,地域,一般,*,*,東京,トウキョウ,トーキョー\n\
+             京都,0,0,1,名詞,固有名詞,地域,一般,*,*,京都,キョウト,キョート\n",
+        )
+        .unwrap();
+        fs::write(path("matrix.def"), "1 1\n0 0 0").unwrap();
+        fs::write(path("char.def"), "DEFAULT 0 1 0").unwrap();
+        fs::write(path("unk.def"), "DEFAULT,0,0,100,名詞,一般,*,*,*,*,*").unwrap();
+        let source = BuildSource::FromMatrix {
+            lexicon: path("lex.csv"),
+            matrix: path("matrix.def"),
+            char_def: path("char.def"),
+            unk_def: path("unk.def"),
+        };
+
+        let options = SystemDictionaryBuilder::minimal_profile(2);
+        let dict = SystemDictionaryBuilder::from_source_with_options(&source, &options).unwrap();
+        assert_eq!(dict.word_feature(WordIdx::new(LexType::System, 0)), "名詞,固有名詞");
+        assert_eq!(dict.word_feature(WordIdx::new(LexType::Unknown, 0)), "名詞,一般");
+
+        let mut minimal_bytes = vec![];
+        dict.write(&mut minimal_bytes).unwrap();
+        let mut full_bytes = vec![];
+        SystemDictionaryBuilder::from_source(&source)
+            .unwrap()
+            .write(&mut full_bytes)
+            .unwrap();
+        assert!(minimal_bytes.len() < full_bytes.len());
+    }
+
+    #[derive(Default)]
+    struct RecordProgress {
+        events: std::sync::Mutex<Vec<(bool, BuildStage)>>,
+    }
+
+    impl BuildProgress for RecordProgress {
+        fn start(&self, stage: BuildStage) {
+            self.events.lock().unwrap().push((true, stage));
+        }
+
+        fn finish(&self, stage: BuildStage) {
+            self.events.lock().unwrap().push((false, stage));
+        }
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_from_dir_euc_jp() {
+        let dir = tempfile::tempdir().unwrap();
+        // "自然" in EUC-JP
+        fs::write(dir.path().join("lex.csv"), b"\xbc\xab\xc1\xb3,0,0,1,sizen").unwrap();
+        fs::write(dir.path().join("matrix.def"), "1 1\n0 0 0").unwrap();
+        fs::write(dir.path().join("char.def"), "DEFAULT 0 1 0").unwrap();
+        fs::write(dir.path().join("unk.def"), "DEFAULT,0,0,100,*").unwrap();
+
+        assert!(SystemDictionaryBuilder::from_dir(dir.path()).is_err());
+        let dict =
+            SystemDictionaryBuilder::from_dir_with_encoding(dir.path(), SourceEncoding::EucJp)
+                .unwrap();
+        let expected = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let mut actual_bytes = vec![];
+        dict.write(&mut actual_bytes).unwrap();
+        let mut expected_bytes = vec![];
+        expected.write(&mut expected_bytes).unwrap();
+        assert_eq!(actual_bytes, expected_bytes);
+    }
+
+    #[test]
+    fn test_bigram_connector_kind() {
+        use crate::dictionary::connector::{ConnectorCost, ConnectorView};
+
+        let row = |prefix: &str, id: usize| {
+            let feats: Vec<_> = (0..9).map(|k| format!("{prefix}{k}")).collect();
+            format!("{id}\t{}\n", feats.join(","))
+        };
+        let bigram_right = row("a", 1) + &row("b", 2);
+        let bigram_left = row("x", 1) + &row("y", 2);
+        let mut bigram_cost = String::new();
+        for k in 0..9 {
+            bigram_cost.push_str(&format!("a{k}/x{k}\t{}\n", k + 1));
+            bigram_cost.push_str(&format!("b{k}/y{k}\t-{k}\n"));
+            bigram_cost.push_str(&format!("a{k}/y{k}\t{}\n", 2 * k));
+        }
+        let build = |right: &str, left: &str, cost: &str, kind| {
+            SystemDictionaryBuilder::from_readers_with_bigram_connector(
+                "自然,1,1,0".as_bytes(),
+                right.as_bytes(),
+                left.as_bytes(),
+                cost.as_bytes(),
+                "DEFAULT 0 1 0".as_bytes(),
+                "DEFAULT,0,0,100,*".as_bytes(),
+                kind,
+            )
+            .unwrap()
+        };
+
+        let raw = build(&bigram_right, &bigram_left, &bigram_cost, BigramConnectorKind::Raw);
+        let dual = build(&bigram_right, &bigram_left, &bigram_cost, BigramConnectorKind::Dual);
+        assert!(matches!(raw.connector, ConnectorWrapper::Raw(_)));
+        assert!(matches!(dual.connector, ConnectorWrapper::Dual(_)));
+        assert_eq!(raw.connector.cost(1, 1), 45);
+        for right_id in 0..3 {
+            for left_id in 0..3 {
+                assert_eq!(
+                    raw.connector.cost(right_id, left_id),
+                    dual.connector.cost(right_id, left_id),
+                );
+            }
+        }
+        assert_eq!(raw.connector.num_right(), dual.connector.num_right());
+
+        // Falls back to the raw connector with fewer feature templates than the threshold.
+        let dual = build("1\ta0\n", "1\tx0\n", "a0/x0\t1\n", BigramConnectorKind::Dual);
+        assert!(matches!(dual.connector, ConnectorWrapper::Raw(_)));
+        assert_eq!(dual.connector.cost(1, 1), 1);
+    }
+
+    #[test]
+    fn test_read_mapping() {
+        let mapping = "0\n2\t10\n1\t5\n";
+        assert_eq!(read_mapping(mapping.as_bytes()).unwrap(), vec![0, 2, 1]);
+        assert!(read_mapping("x\n".as_bytes()).is_err());
+    }
+
+    #[cfg(feature = "loaders")]
+    #[test]
+    fn test_zstd_roundtrip() {
+        let lexicon_csv = "自然,0,0,1,sizen";
+        let matrix_def = "1 1\n0 0 0";
+        let char_def = "DEFAULT 0 1 0";
+        let unk_def = "DEFAULT,0,0,100,*";
+
+        let dict = SystemDictionaryBuilder::from_readers(
+            lexicon_csv.as_bytes(),
+            matrix_def.as_bytes(),
+            char_def.as_bytes(),
+            unk_def.as_bytes(),
+        )
+        .unwrap();
+
+        let mut compressed = vec![];
+        dict.write_zstd(&mut compressed, 3).unwrap();
+        let dict = DictionaryInner::read_zstd(&*compressed).unwrap();
+
+        let mut original = vec![];
+        dict.write(&mut original).unwrap();
+        let mut restored = vec![];
+        DictionaryInner::read(&*original).unwrap().write(&mut restored).unwrap();
+        assert_eq!(original, restored);
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let metadata = DictionaryMetadata::new()
+            .with(metadata::KEY_NAME, "test-dic")
+            .with(metadata::KEY_LICENSE, "MIT");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.dic");
+        dict.write_with_metadata(File::create(&path).unwrap(), &metadata).unwrap();
+
+        let loaded = crate::Dictionary::from_path(&path, crate::LoadMode::Validate).unwrap();
+        assert_eq!(loaded.metadata(), Some(&metadata));
+        #[cfg(feature = "loaders")]
+        assert_eq!(
+            loaded.content_hash().unwrap(),
+            crate::dictionary::hash_file(&path).unwrap(),
+        );
+
+        let bytes = std::fs::read(&path).unwrap();
+        let read = crate::Dictionary::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.metadata(), Some(&metadata));
+
+        let mut original = vec![];
+        dict.write(&mut original).unwrap();
+        let mut restored = vec![];
+        DictionaryInner::read(bytes.as_slice()).unwrap().write(&mut restored).unwrap();
+        assert_eq!(original, restored);
+
+        let without = crate::Dictionary::read(original.as_slice()).unwrap();
+        assert_eq!(without.metadata(), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,496 @@
+//! 展開キャッシュとプルーフファイルの管理
+//!
+//! [`Dictionary::from_compressed`](crate::Dictionary::from_compressed)が作成する展開済み辞書
+//! （`<hash>.dic`）と、[`LoadMode::TrustCache`](crate::LoadMode::TrustCache)で作成される
+//! プルーフファイル（`<hash>.sha256`）は、元のファイルが更新されるたびに新しいハッシュ名で
+//! 作成されるため、古いファイルが残り続けます。このモジュールは、それらのファイルを
+//! 一覧・集計・削除する関数を提供します。
+//!
+//! 展開済み辞書には、展開元ファイルの[`CompressedHeader`]に記録された内容のダイジェストと
+//! 辞書形式のバージョンを記録したファイル（`<hash>.source`）が併せて作成されます。
+//! ファイル名のハッシュはメタデータから計算されるため、サイズと更新時刻が同じ別のファイルで
+//! 展開元が上書きされた場合でも、この記録と照合することで古いキャッシュを検出して作り直せます。
+//! ヘッダを持たないファイルでは、サイズと更新時刻のみで照合します。
+//!
+//! 複数のプロセスが同じキャッシュを同時に作成しようとした場合に備えて、キャッシュの作成は
+//! ロックファイル（`<hash>.lock`）への排他的なアドバイザリロックで直列化されます。
+//!
+//! 対象となるのは、ハッシュ名を持つファイルと、書き込み途中で中断された一時ファイルのみです。
+//! キャッシュディレクトリ内のその他のファイルには触れません。[`clear_cache`]は、作成中の
+//! キャッシュを壊さないように、ロックファイルと作成から間もない一時ファイルを削除せず、
+//! 他のプロセスがロックを保持している展開済み辞書も削除しません。
+
+#![cfg(feature = "loaders")]
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dictionary::{
+    CacheStrategy, CompressedHeader, GLOBAL_CACHE_DIR, GLOBAL_DATA_DIR, MODEL_MAGIC,
+    compute_metadata_hash,
+};
+use crate::errors::{Result, VibratoError};
+
+/// キャッシュファイル名に含まれるハッシュの長さ（SHA-256の16進表記）
+const HASH_HEX_LEN: usize = 64;
+
+/// 一時ファイル名の接頭辞（`tempfile`クレートの既定値）
+const TEMP_PREFIX: &str = ".tmp";
+
+/// 展開元の記録ファイルの拡張子
+const SOURCE_RECORD_EXT: &str = "source";
+
+/// ロックファイルの拡張子
+const LOCK_EXT: &str = "lock";
+
+/// [`clear_cache`]が削除する一時ファイルの最終更新からの経過時間の下限
+///
+/// これより新しい一時ファイルは、書き込み中の可能性があるため削除しません。
+const TEMP_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// キャッシュファイルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFileKind {
+    /// 展開済みの辞書（`<hash>.dic`）
+    Dictionary,
+    /// 検証済みであることを示すプルーフファイル（`<hash>.sha256`）
+    Proof,
+    /// 展開済み辞書の展開元を記録したファイル（`<hash>.source`）
+    SourceRecord,
+    /// キャッシュの作成を直列化するためのロックファイル（`<hash>.lock`）
+    Lock,
+    /// 書き込み途中で残された一時ファイル
+    Temporary,
+}
+
+/// キャッシュディレクトリ内の1つのファイル
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// ファイルのパス
+    pub path: PathBuf,
+    /// ファイルの種類
+    pub kind: CacheFileKind,
+    /// ファイルが置かれている場所
+    pub location: CacheStrategy,
+    /// ファイルサイズ（バイト）
+    pub size: u64,
+    /// 最終更新時刻（取得できない場合は`None`）
+    pub modified: Option<SystemTime>,
+}
+
+/// キャッシング戦略に対応するキャッシュディレクトリのパスを取得します。
+///
+/// ディレクトリは作成しません。
+///
+/// # 引数
+///
+/// * `strategy` - キャッシング戦略
+/// * `dict_dir` - 辞書ファイルが置かれているディレクトリ。[`CacheStrategy::Local`]の場合に必要です。
+///
+/// # 戻り値
+///
+/// キャッシュディレクトリのパス。[`CacheStrategy::Local`]の場合は`<dict_dir>/.cache`です。
+///
+/// # エラー
+///
+/// [`CacheStrategy::Local`]で`dict_dir`が`None`の場合や、
+/// グローバルディレクトリを決定できない場合に[`VibratoError`]を返します。
+pub fn cache_dir(strategy: CacheStrategy, dict_dir: Option<&Path>) -> Result<PathBuf> {
+    match strategy {
+        CacheStrategy::Local => {
+            let dict_dir = dict_dir.ok_or_else(|| {
+                VibratoError::invalid_argument(
+                    "dict_dir",
+                    "A dictionary directory is required for the Local cache strategy.",
+                )
+            })?;
+            Ok(dict_dir.join(".cache"))
+        }
+        CacheStrategy::GlobalCache => GLOBAL_CACHE_DIR.clone().ok_or_else(|| {
+            VibratoError::invalid_state("Could not determine system cache directory.", "")
+        }),
+        CacheStrategy::GlobalData => GLOBAL_DATA_DIR.clone().ok_or_else(|| {
+            VibratoError::invalid_state("Could not determine local data directory.", "")
+        }),
+    }
+}
+
+/// ローカルとグローバルのキャッシュファイルを列挙します。
+///
+/// グローバルキャッシュとグローバルデータディレクトリは常に対象となり、
+/// `dict_dir`が指定された場合は`<dict_dir>/.cache`も対象に加わります。
+/// 同じディレクトリを指す場所は一度だけ走査されます。
+///
+/// # 引数
+///
+/// * `dict_dir` - ローカルキャッシュを調べる辞書ディレクトリ
+///
+/// # 戻り値
+///
+/// キャッシュファイルの一覧（場所、パスの順にソート済み）
+///
+/// # エラー
+///
+/// ディレクトリの読み込みに失敗した場合に[`VibratoError`]を返します。
+pub fn list_caches(dict_dir: Option<&Path>) -> Result<Vec<CacheEntry>> {
+    let mut strategies = vec![];
+    if dict_dir.is_some() {
+        strategies.push(CacheStrategy::Local);
+    }
+    strategies.extend([CacheStrategy::GlobalCache, CacheStrategy::GlobalData]);
+
+    let mut visited: Vec<PathBuf> = vec![];
+    let mut entries = vec![];
+    for strategy in strategies {
+        let Ok(dir) = cache_dir(strategy, dict_dir) else {
+            continue;
+        };
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        if visited.contains(&canonical) {
+            continue;
+        }
+        visited.push(canonical);
+        entries.extend(scan_dir(&dir, strategy)?);
+    }
+    Ok(entries)
+}
+
+/// ローカルとグローバルのキャッシュファイルの合計サイズを取得します。
+///
+/// 対象となるディレクトリは[`list_caches`]と同じです。
+///
+/// # 引数
+///
+/// * `dict_dir` - ローカルキャッシュを調べる辞書ディレクトリ
+///
+/// # 戻り値
+///
+/// 合計サイズ（バイト）
+///
+/// # エラー
+///
+/// ディレクトリの読み込みに失敗した場合に[`VibratoError`]を返します。
+pub fn cache_size(dict_dir: Option<&Path>) -> Result<u64> {
+    Ok(list_caches(dict_dir)?.iter().map(|entry| entry.size).sum())
+}
+
+/// 指定した場所のキャッシュファイルを削除します。
+///
+/// 削除後は、次回の読み込み時に展開や検証が再度行われます。
+///
+/// 実行中の読み込みを妨げないように、次のファイルは削除しません。
+///
+/// * ロックファイル。削除すると、次に読み込むプロセスが別のロックファイルを作成し、
+///   ロックを保持しているプロセスと同時にキャッシュを作成してしまいます。
+/// * 他のプロセスがロックを保持している展開済み辞書とその記録ファイル
+/// * 最終更新から1時間以内の一時ファイル
+///
+/// # 引数
+///
+/// * `strategy` - 削除対象の場所
+/// * `dict_dir` - 辞書ファイルが置かれているディレクトリ。[`CacheStrategy::Local`]の場合に必要です。
+///
+/// # 戻り値
+///
+/// 削除したファイルの一覧
+///
+/// # エラー
+///
+/// キャッシュディレクトリを決定できない場合や、ファイルの削除に失敗した場合に
+/// [`VibratoError`]を返します。
+pub fn clear_cache(strategy: CacheStrategy, dict_dir: Option<&Path>) -> Result<Vec<CacheEntry>> {
+    let dir = cache_dir(strategy, dict_dir)?;
+    let now = SystemTime::now();
+    let mut removed = vec![];
+    for entry in scan_dir(&dir, strategy)? {
+        // Holds the lock while removing the files written under it.
+        let _lock = match entry.kind {
+            CacheFileKind::Lock => continue,
+            CacheFileKind::Temporary => {
+                let age = entry.modified.and_then(|t| now.duration_since(t).ok());
+                if age.is_none_or(|age| age < TEMP_MIN_AGE) {
+                    continue;
+                }
+                None
+            }
+            CacheFileKind::Dictionary | CacheFileKind::SourceRecord => {
+                match CacheLock::try_acquire(&entry.path.with_extension("dic"))? {
+                    Some(lock) => Some(lock),
+                    None => continue,
+                }
+            }
+            CacheFileKind::Proof => None,
+        };
+        match fs::remove_file(&entry.path) {
+            // Another process may have evicted the file in the meantime.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            result => result?,
+        }
+        removed.push(entry);
+    }
+    Ok(removed)
+}
+
+/// 展開済み辞書の展開元の記録
+///
+/// 展開元ファイルを識別する値と、辞書形式のバージョンを保持します。
+pub(crate) struct SourceRecord(String);
+
+impl SourceRecord {
+    /// 展開元ファイルのヘッダとメタデータから記録を作成します。
+    ///
+    /// ヘッダを持つファイルでは展開後の内容のダイジェストを、ヘッダを持たないファイルでは
+    /// サイズと更新時刻を記録します。キャッシュが有効な場合の読み込みを速く保つため、
+    /// ファイル全体は読み込みません。
+    pub(crate) fn new(header: Option<&CompressedHeader>, meta: &fs::Metadata) -> Self {
+        let source = match header {
+            Some(header) => format!("content-sha256={}", header.content_hash()),
+            None => {
+                let modified = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or_else(|| "unknown".to_string(), |d| d.as_nanos().to_string());
+                format!("source-size={}\nsource-modified={modified}", meta.len())
+            }
+        };
+        let format = String::from_utf8_lossy(MODEL_MAGIC);
+        Self(format!("{source}\nformat={}\n", format.trim_end()))
+    }
+
+    /// 展開済み辞書に対応する記録ファイルのパスを取得します。
+    pub(crate) fn path_for(dict_path: &Path) -> PathBuf {
+        dict_path.with_extension(SOURCE_RECORD_EXT)
+    }
+
+    /// 展開済み辞書に記録された内容と一致するかどうかを判定します。
+    ///
+    /// 記録ファイルが存在しない場合は一致しないものとみなします。
+    pub(crate) fn matches(&self, dict_path: &Path) -> bool {
+        fs::read_to_string(Self::path_for(dict_path)).is_ok_and(|record| record == self.0)
+    }
+
+    /// 展開済み辞書に対応する記録ファイルを書き込みます。
+    pub(crate) fn write(&self, dict_path: &Path) -> Result<()> {
+        Ok(fs::write(Self::path_for(dict_path), &self.0)?)
+    }
+}
+
+/// キャッシュの作成中に保持する排他ロック
+///
+/// ドロップ時にロックが解放されます。
+pub(crate) struct CacheLock(File);
+
+impl CacheLock {
+    /// 展開済み辞書に対応するロックファイルの排他ロックを取得します。
+    ///
+    /// 他のプロセスがロックを保持している場合は、解放されるまでブロックします。
+    pub(crate) fn acquire(dict_path: &Path) -> Result<Self> {
+        let file = Self::open(dict_path)?;
+        file.lock()?;
+        Ok(Self(file))
+    }
+
+    /// 展開済み辞書に対応するロックファイルの排他ロックの取得を試みます。
+    ///
+    /// 他のプロセスがロックを保持している場合は、ブロックせずに`None`を返します。
+    pub(crate) fn try_acquire(dict_path: &Path) -> Result<Option<Self>> {
+        let file = Self::open(dict_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self(file))),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+
+    /// ロックファイルを開きます。存在しない場合は作成します。
+    fn open(dict_path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dict_path.with_extension(LOCK_EXT))
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+/// プルーフファイルを作成します。
+///
+/// すでに存在する場合は何もしないため、複数のプロセスが同時に呼び出しても失敗しません。
+pub(crate) fn create_proof(path: &Path) -> Result<()> {
+    OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+    Ok(())
+}
+
+/// 展開済み辞書と、そのプルーフファイルおよび記録ファイルを削除します。
+pub(crate) fn evict(dict_path: &Path) -> Result<()> {
+    let proof_hash = compute_metadata_hash(&File::open(dict_path)?.metadata()?);
+    let proof_path = dict_path.with_file_name(format!("{proof_hash}.sha256"));
+    fs::remove_file(dict_path)?;
+    for path in [proof_path, SourceRecord::path_for(dict_path)] {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// ファイル名からキャッシュファイルの種類を判別します。
+fn classify(file_name: &str) -> Option<CacheFileKind> {
+    if file_name.starts_with(TEMP_PREFIX) {
+        return Some(CacheFileKind::Temporary);
+    }
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    if stem.len() != HASH_HEX_LEN || !stem.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    match ext {
+        "dic" => Some(CacheFileKind::Dictionary),
+        "sha256" => Some(CacheFileKind::Proof),
+        SOURCE_RECORD_EXT => Some(CacheFileKind::SourceRecord),
+        LOCK_EXT => Some(CacheFileKind::Lock),
+        _ => None,
+    }
+}
+
+/// ディレクトリ直下のキャッシュファイルを列挙します。
+fn scan_dir(dir: &Path, location: CacheStrategy) -> Result<Vec<CacheEntry>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let Some(kind) = dir_entry.file_name().to_str().and_then(classify) else {
+            continue;
+        };
+        let meta = dir_entry.metadata()?;
+        if !meta.is_file() {
+            continue;
+        }
+        entries.push(CacheEntry {
+            path: dir_entry.path(),
+            kind,
+            location,
+            size: meta.len(),
+            modified: meta.modified().ok(),
+        });
+    }
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_cache() {
+        let dict_dir = tempfile::tempdir().unwrap();
+        let cache = dict_dir.path().join(".cache");
+        fs::create_dir_all(&cache).unwrap();
+
+        let hash = "0123456789abcdef".repeat(4);
+        fs::write(cache.join(format!("{hash}.dic")), b"dictionary").unwrap();
+        fs::write(cache.join(format!("{hash}.sha256")), b"").unwrap();
+        fs::write(cache.join(format!("{hash}.source")), b"").unwrap();
+        fs::write(cache.join(format!("{hash}.lock")), b"").unwrap();
+        fs::write(cache.join(".tmpAbCdEf"), b"partial").unwrap();
+        File::options()
+            .write(true)
+            .open(cache.join(".tmpAbCdEf"))
+            .unwrap()
+            .set_modified(SystemTime::now() - 2 * TEMP_MIN_AGE)
+            .unwrap();
+        fs::write(cache.join(".tmpWriting"), b"partial").unwrap();
+        fs::write(cache.join("notes.txt"), b"unrelated").unwrap();
+
+        let entries = scan_dir(&cache, CacheStrategy::Local).unwrap();
+        let mut kinds: Vec<_> = entries.iter().map(|e| e.kind).collect();
+        kinds.sort_unstable_by_key(|k| *k as u8);
+        assert_eq!(
+            kinds,
+            [
+                CacheFileKind::Dictionary,
+                CacheFileKind::Proof,
+                CacheFileKind::SourceRecord,
+                CacheFileKind::Lock,
+                CacheFileKind::Temporary,
+                CacheFileKind::Temporary,
+            ],
+        );
+        assert_eq!(entries.iter().map(|e| e.size).sum::<u64>(), 24);
+
+        let removed = clear_cache(CacheStrategy::Local, Some(dict_dir.path())).unwrap();
+        assert_eq!(removed.len(), 4);
+        let remaining: Vec<_> = scan_dir(&cache, CacheStrategy::Local)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(remaining, [cache.join(".tmpWriting"), cache.join(format!("{hash}.lock"))]);
+        assert!(cache.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_clear_cache_skips_locked() {
+        let dict_dir = tempfile::tempdir().unwrap();
+        let cache = dict_dir.path().join(".cache");
+        fs::create_dir_all(&cache).unwrap();
+
+        let dict_path = cache.join(format!("{}.dic", "0".repeat(HASH_HEX_LEN)));
+        fs::write(&dict_path, b"dictionary").unwrap();
+        fs::write(dict_path.with_extension(SOURCE_RECORD_EXT), b"").unwrap();
+
+        let lock = CacheLock::acquire(&dict_path).unwrap();
+        assert!(CacheLock::try_acquire(&dict_path).unwrap().is_none());
+        let removed = clear_cache(CacheStrategy::Local, Some(dict_dir.path())).unwrap();
+        assert!(removed.is_empty());
+        assert!(dict_path.exists());
+
+        drop(lock);
+        let removed = clear_cache(CacheStrategy::Local, Some(dict_dir.path())).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(!dict_path.exists());
+        assert!(dict_path.with_extension(LOCK_EXT).exists());
+    }
+
+    #[test]
+    fn test_lock_serializes_writers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dict_path = dir.path().join(format!("{}.dic", "0".repeat(HASH_HEX_LEN)));
+        let active = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let dict_path = dict_path.clone();
+                let active = Arc::clone(&active);
+                std::thread::spawn(move || {
+                    let _lock = CacheLock::acquire(&dict_path).unwrap();
+                    assert_eq!(active.fetch_add(1, Ordering::SeqCst), 0);
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    create_proof(&dict_path.with_extension("sha256")).unwrap();
+                    active.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(dict_path.with_extension("sha256").exists());
+    }
+
+    #[test]
+    fn test_local_requires_dir() {
+        assert!(cache_dir(CacheStrategy::Local, None).is_err());
+    }
+}
@@ -138,6 +138,89 @@ impl CharInfo {
     }
 }
 
+/// 文字が属するカテゴリの情報
+///
+/// コンパイル済みの辞書で、`char.def`の定義が文字にどのように適用されたかを確認するために
+/// 使用します。[`Dictionary::char_category()`](crate::Dictionary::char_category)で取得できます。
+///
+/// 未知語処理の動作(`invoke`、`group`、`length`)は基本カテゴリの定義に従います。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CategoryInfoView<'a> {
+    base_category: &'a str,
+    categories: Vec<&'a str>,
+    invoke: bool,
+    group: bool,
+    length: u16,
+}
+
+impl<'a> CategoryInfoView<'a> {
+    fn new<F>(cinfo: CharInfo, cate_str: F) -> Self
+    where
+        F: Fn(u32) -> &'a str,
+    {
+        let cate_idset = cinfo.cate_idset();
+        let categories = (0..u32::try_from(CATE_IDSET_BITS).unwrap())
+            .filter(|&cate_id| cate_idset & (1 << cate_id) != 0)
+            .map(&cate_str)
+            .collect();
+        Self {
+            base_category: cate_str(cinfo.base_id()),
+            categories,
+            invoke: cinfo.invoke(),
+            group: cinfo.group(),
+            length: cinfo.length(),
+        }
+    }
+
+    /// 基本カテゴリの名前を取得します。
+    ///
+    /// `char.def`で文字に最初に割り当てられたカテゴリで、未知語の素性に使用されます。
+    #[inline(always)]
+    pub const fn base_category(&self) -> &'a str {
+        self.base_category
+    }
+
+    /// 文字が属するすべてのカテゴリの名前を、カテゴリIDの順に取得します。
+    ///
+    /// 互換カテゴリを含み、基本カテゴリも含まれます。
+    #[inline(always)]
+    pub fn categories(&self) -> &[&'a str] {
+        &self.categories
+    }
+
+    /// 既知語が存在する場合にも未知語処理を行うかどうかを取得します。
+    #[inline(always)]
+    pub const fn invoke(&self) -> bool {
+        self.invoke
+    }
+
+    /// 同じカテゴリの文字をまとめて未知語とするかどうかを取得します。
+    #[inline(always)]
+    pub const fn group(&self) -> bool {
+        self.group
+    }
+
+    /// 先頭から1文字ずつ長くして生成する未知語の最大文字数を取得します。
+    #[inline(always)]
+    pub const fn length(&self) -> u16 {
+        self.length
+    }
+}
+
+impl fmt::Display for CategoryInfoView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} invoke={} group={} length={} categories={}",
+            self.base_category,
+            u8::from(self.invoke),
+            u8::from(self.group),
+            self.length,
+            self.categories.join(","),
+        )
+    }
+}
+
 /// 文字範囲とそのカテゴリを表す構造体
 struct CharRange {
     start: usize,
@@ -146,7 +229,7 @@ struct CharRange {
 }
 
 /// 文字から文字情報へのマッピング
-#[derive(Archive, Serialize, Deserialize)]
+#[derive(Clone, Archive, Serialize, Deserialize)]
 pub struct CharProperty {
     chr2inf: Vec<CharInfo>,
     categories: Vec<String>, // indexed by category id
@@ -163,7 +246,7 @@ impl CharProperty {
     ///
     /// 文字情報
     #[inline(always)]
-    pub fn char_info(&self, c: char) -> CharInfo {
+    pub(crate) fn char_info(&self, c: char) -> CharInfo {
         self.chr2inf
             .get(usize::from_u32(u32::from(c)))
             .map_or_else(|| self.chr2inf[0], |cinfo| *cinfo)
@@ -203,6 +286,13 @@ impl CharProperty {
             .map(|c| c.as_str())
     }
 
+    /// 指定された文字のカテゴリ情報を取得します。
+    pub(crate) fn category_info(&self, c: char) -> CategoryInfoView<'_> {
+        CategoryInfoView::new(self.char_info(c), |cate_id| {
+            self.categories[usize::from_u32(cate_id)].as_str()
+        })
+    }
+
     /// カテゴリの総数を取得します。
     ///
     /// # 戻り値
@@ -213,6 +303,27 @@ impl CharProperty {
         self.categories.len()
     }
 
+    /// 半角カナや全角英数字などの互換文字を、対応する正規の文字と同じカテゴリに統一します。
+    ///
+    /// 対象は、全角ASCII（U+FF01..U+FF5E）、全角スペース（U+3000）、半角カナと半角の
+    /// 句読点（U+FF61..U+FF9F）、および全角・半角の記号（U+FFE0..U+FFEE）です。
+    /// これらの文字には、`char.def`での割り当てに関わらず、対応する文字（例えば`ｶ`には`カ`、
+    /// `Ａ`には`A`）の文字情報がそのまま割り当てられます。半角の濁点・半濁点は、
+    /// 結合文字ではなく`゛`（U+309B）と`゜`（U+309C）に対応付けます。
+    ///
+    /// この変換は文字カテゴリの割り当てのみを変更し、入力文そのものは書き換えません。
+    /// そのため、トークンの表層形や[`range_byte()`](crate::token::Token::range_byte)などの
+    /// 位置は元の入力文に対するものとなり、辞書の単語との照合も元の文字のまま行われます。
+    /// 変換されるのは未知語処理での文字種の判定とグループ化です。
+    pub fn fold_compatibility_chars(&mut self) {
+        for i in 0..self.chr2inf.len() {
+            let canonical = u32::try_from(i).ok().and_then(char::from_u32).and_then(canonical_char);
+            if let Some(canonical) = canonical {
+                self.chr2inf[i] = self.char_info(canonical);
+            }
+        }
+    }
+
     /// `char.def` ファイルから新しいインスタンスを作成します。
     ///
     /// # 引数
@@ -370,6 +481,45 @@ impl CharProperty {
     }
 }
 
+/// 半角・全角形の互換文字に対応する正規の文字を返します。
+fn canonical_char(c: char) -> Option<char> {
+    /// U+FF61..U+FF9Fに対応する文字
+    const HALFWIDTH_KATAKANA: [char; 63] = [
+        '。', '「', '」', '、', '・', 'ヲ', 'ァ', 'ィ', 'ゥ', 'ェ', 'ォ', 'ャ', 'ュ', 'ョ', 'ッ',
+        'ー', 'ア', 'イ', 'ウ', 'エ', 'オ', 'カ', 'キ', 'ク', 'ケ', 'コ', 'サ', 'シ', 'ス', 'セ',
+        'ソ', 'タ', 'チ', 'ツ', 'テ', 'ト', 'ナ', 'ニ', 'ヌ', 'ネ', 'ノ', 'ハ', 'ヒ', 'フ', 'ヘ',
+        'ホ', 'マ', 'ミ', 'ム', 'メ', 'モ', 'ヤ', 'ユ', 'ヨ', 'ラ', 'リ', 'ル', 'レ', 'ロ', 'ワ',
+        'ン', '゛', '゜',
+    ];
+    /// U+FFE0..U+FFEEに対応する文字（`None`は未割り当て）
+    const FULLWIDTH_SYMBOLS: [Option<char>; 15] = [
+        Some('\u{A2}'),
+        Some('\u{A3}'),
+        Some('\u{AC}'),
+        Some('\u{AF}'),
+        Some('\u{A6}'),
+        Some('\u{A5}'),
+        Some('\u{20A9}'),
+        None,
+        Some('\u{2502}'),
+        Some('\u{2190}'),
+        Some('\u{2191}'),
+        Some('\u{2192}'),
+        Some('\u{2193}'),
+        Some('\u{25A0}'),
+        Some('\u{25CB}'),
+    ];
+
+    let code = u32::from(c);
+    match code {
+        0x3000 => Some(' '),
+        0xFF01..=0xFF5E => char::from_u32(code - 0xFF01 + 0x21),
+        0xFF61..=0xFF9F => Some(HALFWIDTH_KATAKANA[usize::from_u32(code - 0xFF61)]),
+        0xFFE0..=0xFFEE => FULLWIDTH_SYMBOLS[usize::from_u32(code - 0xFFE0)],
+        _ => None,
+    }
+}
+
 impl ArchivedCharProperty {
     /// カテゴリ名からカテゴリIDを取得します。
     ///
@@ -389,6 +539,15 @@ impl ArchivedCharProperty {
             .map(|id| u32::try_from(id).unwrap())
     }
 
+    /// 指定された文字のカテゴリ情報を取得します。
+    ///
+    /// このメソッドはアーカイブ版 `CharProperty` 用の実装です。
+    pub(crate) fn category_info(&self, c: char) -> CategoryInfoView<'_> {
+        CategoryInfoView::new(self.char_info(c), |cate_id| {
+            self.categories[usize::from_u32(cate_id)].as_str()
+        })
+    }
+
     /// 指定された文字の文字情報を取得します。
     ///
     /// # 引数
@@ -407,6 +566,26 @@ impl ArchivedCharProperty {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl TryFrom<crate::legacy::dictionary::character::CharProperty> for CharProperty {
+    type Error = VibratoError;
+
+    fn try_from(prop: crate::legacy::dictionary::character::CharProperty) -> Result<Self> {
+        let crate::legacy::dictionary::character::CharProperty { chr2inf, categories } = prop;
+        let chr2inf = chr2inf
+            .into_iter()
+            .map(|c| {
+                CharInfo::new(c.cate_idset(), c.base_id(), c.invoke(), c.group(), c.length())
+                    .ok_or_else(|| {
+                        VibratoError::invalid_format("char.def", "Invalid character information.")
+                    })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { chr2inf, categories })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -490,4 +669,46 @@ mod tests {
         let result = CharProperty::from_reader(data.as_bytes());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_fold_compatibility_chars() {
+        let data = "DEFAULT 0 1 0\nSPACE 0 1 0\nALPHA 1 1 0\nKATAKANA 1 1 2\n\
+                    0x0020 SPACE\n0x0041..0x005A ALPHA\n0x30A1..0x30FA KATAKANA\n\
+                    0xFF21..0xFF3A KATAKANA";
+        let mut prop = CharProperty::from_reader(data.as_bytes()).unwrap();
+        let alpha = prop.cate_id("ALPHA").unwrap();
+        let katakana = prop.cate_id("KATAKANA").unwrap();
+        assert_eq!(prop.char_info('ｶ').base_id(), 0);
+        assert_eq!(prop.char_info('Ａ').base_id(), katakana);
+
+        prop.fold_compatibility_chars();
+        assert_eq!(prop.char_info('ｶ').base_id(), katakana);
+        assert_eq!(prop.char_info('ｧ').base_id(), katakana);
+        assert_eq!(prop.char_info('Ａ').base_id(), alpha);
+        assert_eq!(prop.char_info('\u{3000}').base_id(), prop.cate_id("SPACE").unwrap());
+        assert_eq!(prop.char_info('ﾞ').base_id(), 0);
+        assert_eq!(prop.char_info('カ').base_id(), katakana);
+    }
+
+    #[test]
+    fn test_category_info() {
+        let data = "DEFAULT 0 1 0\nKANJI 0 0 2\nKANJINUMERIC 1 1 0\n\
+                    0x4E00..0x9FFF KANJI\n0x4E00 KANJINUMERIC KANJI";
+        let prop = CharProperty::from_reader(data.as_bytes()).unwrap();
+
+        let info = prop.category_info('一');
+        assert_eq!(info.base_category(), "KANJINUMERIC");
+        assert_eq!(info.categories(), &["KANJI", "KANJINUMERIC"]);
+        assert!(info.invoke());
+        assert!(info.group());
+        assert_eq!(info.length(), 0);
+        assert_eq!(
+            info.to_string(),
+            "KANJINUMERIC invoke=1 group=1 length=0 categories=KANJI,KANJINUMERIC",
+        );
+
+        let info = prop.category_info('a');
+        assert_eq!(info.base_category(), "DEFAULT");
+        assert_eq!(info.categories(), &["DEFAULT"]);
+    }
 }
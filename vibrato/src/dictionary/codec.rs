@@ -0,0 +1,209 @@
+//! 圧縮形式の判別と展開
+//!
+//! このモジュールは、辞書ファイルの先頭のマジックナンバーから圧縮形式
+//! （zstd、xz、lz4、tar）を判別し、辞書データを展開する機能を提供します。
+//! 圧縮されたtarアーカイブのように形式が入れ子になっている場合も、
+//! 辞書データに到達するまで順に展開します。
+
+#![cfg(feature = "loaders")]
+use std::io::{self, Cursor, Read, Write};
+
+use crate::errors::{Result, VibratoError};
+
+/// 判別に使用する先頭のバイト数（tarヘッダーのブロック長）
+const HEADER_LEN: usize = 512;
+
+/// 入れ子になった形式を展開する最大の深さ
+const MAX_DEPTH: usize = 4;
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+/// zstdのスキップ可能フレームのマジックナンバーの上位3バイト（下位4ビットは任意）
+const ZSTD_SKIPPABLE_MAGIC: &[u8] = &[0x2A, 0x4D, 0x18];
+const XZ_MAGIC: &[u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4D, 0x18];
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// 辞書ファイルの格納形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Codec {
+    /// Zstandard圧縮
+    Zstd,
+    /// xz圧縮
+    Xz,
+    /// LZ4フレーム圧縮
+    Lz4,
+    /// tarアーカイブ
+    Tar,
+    /// 非圧縮
+    Uncompressed,
+}
+
+impl Codec {
+    /// 先頭のバイト列から形式を判別します。
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(ZSTD_MAGIC)
+            || header.first().is_some_and(|&b| b & 0xF0 == 0x50)
+                && header.get(1..4).is_some_and(|m| m == ZSTD_SKIPPABLE_MAGIC)
+        {
+            Self::Zstd
+        } else if header.starts_with(XZ_MAGIC) {
+            Self::Xz
+        } else if header.starts_with(LZ4_MAGIC) {
+            Self::Lz4
+        } else if header
+            .get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len())
+            .is_some_and(|m| m == TAR_MAGIC)
+        {
+            Self::Tar
+        } else {
+            Self::Uncompressed
+        }
+    }
+}
+
+/// 入力を必要なだけ展開し、辞書データを`wtr`に書き出します。
+///
+/// tarアーカイブの場合は、名前が`.dic`で終わるか`.dic.`を含む最初のエントリを
+/// 辞書データとして扱います。
+///
+/// # 引数
+///
+/// * `rdr` - 入力のリーダー
+/// * `wtr` - 展開された辞書データの書き込み先
+///
+/// # エラー
+///
+/// 展開に失敗した場合や、tarアーカイブ内に辞書が見つからない場合、
+/// 必要なフィーチャーが無効な場合に[`VibratoError`]を返します。
+pub(crate) fn extract<R, W>(rdr: R, wtr: &mut W) -> Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    extract_inner(Box::new(rdr), wtr, 0)
+}
+
+fn extract_inner<W>(rdr: Box<dyn Read + '_>, wtr: &mut W, depth: usize) -> Result<()>
+where
+    W: Write,
+{
+    let mut rdr = rdr;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut rdr).take(HEADER_LEN as u64).read_to_end(&mut header)?;
+    let codec = Codec::detect(&header);
+    let rdr = Cursor::new(header).chain(rdr);
+
+    if codec != Codec::Uncompressed && depth == MAX_DEPTH {
+        return Err(VibratoError::invalid_argument(
+            "path",
+            "The input is nested too deeply in compressed formats.",
+        ));
+    }
+
+    match codec {
+        Codec::Zstd => extract_inner(Box::new(zstd::Decoder::new(rdr)?), wtr, depth + 1),
+        #[cfg(feature = "codecs")]
+        Codec::Xz => extract_inner(Box::new(xz2::read::XzDecoder::new(rdr)), wtr, depth + 1),
+        #[cfg(feature = "codecs")]
+        Codec::Lz4 => extract_inner(
+            Box::new(lz4_flex::frame::FrameDecoder::new(rdr)),
+            wtr,
+            depth + 1,
+        ),
+        #[cfg(feature = "codecs")]
+        Codec::Tar => {
+            let mut archive = tar::Archive::new(rdr);
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let is_dict = entry.path()?.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name.ends_with(".dic") || name.contains(".dic.")
+                });
+                if is_dict {
+                    return extract_inner(Box::new(entry), wtr, depth + 1);
+                }
+            }
+            Err(VibratoError::invalid_argument(
+                "path",
+                "No dictionary file (*.dic) was found in the tar archive.",
+            ))
+        }
+        #[cfg(not(feature = "codecs"))]
+        Codec::Xz | Codec::Lz4 | Codec::Tar => Err(VibratoError::invalid_argument(
+            "path",
+            "Loading xz, lz4, or tar inputs requires the `codecs` feature.",
+        )),
+        Codec::Uncompressed => {
+            let mut rdr = rdr;
+            io::copy(&mut rdr, wtr)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Codec::detect(&[0x28, 0xB5, 0x2F, 0xFD, 0x00]), Codec::Zstd);
+        assert_eq!(Codec::detect(&[0x5E, 0x2A, 0x4D, 0x18, 0x00]), Codec::Zstd);
+        assert_eq!(Codec::detect(b"\xFD7zXZ\x00\x00"), Codec::Xz);
+        assert_eq!(Codec::detect(&[0x04, 0x22, 0x4D, 0x18]), Codec::Lz4);
+        let mut tar_header = vec![0; HEADER_LEN];
+        tar_header[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5].copy_from_slice(b"ustar");
+        assert_eq!(Codec::detect(&tar_header), Codec::Tar);
+        assert_eq!(Codec::detect(b"VibratoTokenizerRkyv"), Codec::Uncompressed);
+        assert_eq!(Codec::detect(&[]), Codec::Uncompressed);
+    }
+
+    #[test]
+    fn test_extract_nested() {
+        let data = b"dictionary data".repeat(100);
+
+        let compressed = zstd::encode_all(&data[..], 3).unwrap();
+        let mut output = vec![];
+        extract(&compressed[..], &mut output).unwrap();
+        assert_eq!(output, data);
+
+        // A zstd-compressed dictionary inside a zstd-compressed stream
+        let nested = zstd::encode_all(&compressed[..], 3).unwrap();
+        let mut output = vec![];
+        extract(&nested[..], &mut output).unwrap();
+        assert_eq!(output, data);
+
+        let mut output = vec![];
+        extract(&data[..], &mut output).unwrap();
+        assert_eq!(output, data);
+    }
+
+    #[cfg(feature = "codecs")]
+    #[test]
+    fn test_extract_tar() {
+        let data = b"dictionary data".repeat(100);
+        let compressed = zstd::encode_all(&data[..], 3).unwrap();
+
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, content) in [("README", &b"readme"[..]), ("dict/system.dic.zst", &compressed[..])] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        }
+        let archive = builder.into_inner().unwrap();
+
+        let mut output = vec![];
+        extract(&archive[..], &mut output).unwrap();
+        assert_eq!(output, data);
+
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(vec![]);
+        lz4.write_all(&archive).unwrap();
+        let lz4 = lz4.finish().unwrap();
+        let mut output = vec![];
+        extract(&lz4[..], &mut output).unwrap();
+        assert_eq!(output, data);
+    }
+}
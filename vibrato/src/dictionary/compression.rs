@@ -0,0 +1,708 @@
+//! 辞書ファイルのzstd圧縮
+//!
+//! このモジュールは、辞書ファイルをzstdで圧縮して書き出す際の圧縮レベル、
+//! ワーカースレッド数、ウィンドウサイズを指定する[`WriteOptions`]を提供します。
+//! UniDicのような大規模な辞書では、複数のワーカースレッドで圧縮することで
+//! 書き出しの所要時間を大幅に短縮できます。
+//!
+//! 圧縮された辞書の先頭には、zstdのスキップ可能フレームとして[`CompressedHeader`]が
+//! 置かれます。ヘッダには辞書のメタデータと、展開後の辞書ファイルのダイジェストとサイズが
+//! 記録され、展開せずに辞書の内容を確認できます。圧縮データのダイジェストは、
+//! 圧縮データ全体をメモリ上に保持せずに書き出せるよう、末尾のスキップ可能フレームに
+//! 記録されます。これにより、展開前に圧縮データの破損を検出できます。
+//! スキップ可能フレームはzstdのデコーダによって読み飛ばされるため、
+//! これらのフレームを持つファイルも通常のzstdファイルとして展開できます。
+//!
+//! ```text
+//! [スキップ可能フレームのマジックナンバー (u32 LE)][本体の長さ (u32 LE)]
+//! [HEADER_MAGIC][メタデータ (key=value の行)]
+//! [zstdフレーム...]
+//! [スキップ可能フレームのマジックナンバー (u32 LE)][本体の長さ (u32 LE)]
+//! [DIGEST_MAGIC][圧縮データのSHA-256ダイジェスト (32バイト)]
+//! ```
+
+#![cfg(feature = "loaders")]
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::dictionary::DictionaryMetadata;
+use crate::errors::{Result, VibratoError};
+
+/// ヘッダに使用するzstdのスキップ可能フレームのマジックナンバー
+///
+/// zstdは`0x184D2A50`から`0x184D2A5F`までの16個をスキップ可能フレームに割り当てています。
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+
+/// ヘッダの本体の先頭に置かれるマジックバイト
+///
+/// 他のツールが書き込んだスキップ可能フレームと区別するために使用します。
+const HEADER_MAGIC: &[u8] = b"VibratoZstdHeader\n";
+
+/// 末尾のダイジェストのフレームの本体の先頭に置かれるマジックバイト
+const DIGEST_MAGIC: &[u8] = b"VibratoZstdDigest\n";
+
+/// SHA-256ダイジェストのバイト数
+const DIGEST_LEN: usize = 32;
+
+/// 末尾のダイジェストのフレームの本体のバイト数
+const DIGEST_BODY_LEN: usize = DIGEST_MAGIC.len() + DIGEST_LEN;
+
+/// 末尾のダイジェストのフレーム全体のバイト数
+const DIGEST_FRAME_LEN: usize = 8 + DIGEST_BODY_LEN;
+
+/// ヘッダの本体の長さの上限
+const MAX_HEADER_LEN: u32 = 1 << 20;
+
+/// 展開後の辞書ファイルのダイジェストを表すキー
+const KEY_CONTENT_SHA256: &str = "vibrato.content_sha256";
+
+/// 展開後の辞書ファイルのサイズを表すキー
+const KEY_CONTENT_SIZE: &str = "vibrato.content_size";
+
+/// zstdの圧縮レベルの既定値
+const DEFAULT_LEVEL: i32 = 19;
+
+/// 読み込み時に展開できるウィンドウサイズの最大値（2の累乗の指数）
+///
+/// [`Dictionary::from_zstd()`](crate::Dictionary::from_zstd)などで使用される
+/// zstdのデコーダは、既定ではこれを超えるウィンドウサイズのフレームを拒否します。
+const MAX_WINDOW_LOG: u32 = 27;
+
+/// zstdが受け付けるウィンドウサイズの最小値（2の累乗の指数）
+const MIN_WINDOW_LOG: u32 = 10;
+
+/// 辞書をzstdで圧縮して書き出す際のオプション
+///
+/// [`Dictionary::write_zstd()`](crate::Dictionary::write_zstd)や
+/// [`DictionaryInner::write_zstd_with_options()`](super::DictionaryInner::write_zstd_with_options)に
+/// 渡して使用します。
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::dictionary::WriteOptions;
+///
+/// let options = WriteOptions::new()
+///     .level(19)
+///     .workers(8)
+///     .window_log(27);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteOptions {
+    level: i32,
+    workers: u32,
+    window_log: Option<u32>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_LEVEL,
+            workers: 0,
+            window_log: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// デフォルトのオプションを作成します。
+    ///
+    /// 圧縮レベル19で、呼び出し元のスレッドのみを使用して圧縮します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// zstdの圧縮レベルを設定します。
+    ///
+    /// 範囲外の値は、書き出し時にエラーとなります。
+    pub const fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// 圧縮に使用するワーカースレッド数を設定します。
+    ///
+    /// `0`の場合は呼び出し元のスレッドのみで圧縮します。1以上の場合はzstdの
+    /// マルチスレッド圧縮を使用し、入力の読み込みと並行して圧縮を行います。
+    /// 圧縮結果はワーカースレッド数が1以上であれば同じになります。
+    ///
+    /// `zstdmt`フィーチャーが無効な場合、この設定は無視されます。
+    pub const fn workers(mut self, workers: u32) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// 圧縮に使用するウィンドウサイズを2の累乗の指数で設定し、長距離一致探索を有効にします。
+    ///
+    /// 辞書データのように離れた位置に類似した内容が現れる入力では、圧縮率が向上します。
+    /// 既定のデコーダで読み込めるよう、指数は27以下に制限されます。
+    /// 10以上27以下でない値は、書き出し時にエラーとなります。
+    pub const fn window_log(mut self, window_log: u32) -> Self {
+        self.window_log = Some(window_log);
+        self
+    }
+
+    /// 圧縮レベルとウィンドウサイズがzstdの受け付ける範囲内であることを確認します。
+    fn check(&self) -> Result<()> {
+        if !zstd::compression_level_range().contains(&self.level) {
+            return Err(VibratoError::invalid_argument(
+                "level",
+                format!(
+                    "The compression level must be in {:?}, but got {}.",
+                    zstd::compression_level_range(),
+                    self.level
+                ),
+            ));
+        }
+        if let Some(window_log) = self.window_log
+            && !(MIN_WINDOW_LOG..=MAX_WINDOW_LOG).contains(&window_log)
+        {
+            return Err(VibratoError::invalid_argument(
+                "window_log",
+                format!(
+                    "The window log must be in {MIN_WINDOW_LOG}..={MAX_WINDOW_LOG}, but got {window_log}.",
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 設定に従ってzstdのエンコーダを作成します。
+    fn encoder<'a, W>(&self, wtr: W) -> Result<zstd::Encoder<'a, W>>
+    where
+        W: Write,
+    {
+        let mut encoder = zstd::Encoder::new(wtr, self.level)?;
+        encoder.include_checksum(true)?;
+        #[cfg(feature = "zstdmt")]
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+        if let Some(window_log) = self.window_log {
+            encoder.window_log(window_log)?;
+            encoder.long_distance_matching(true)?;
+        }
+        Ok(encoder)
+    }
+
+    /// リーダーから読み込んだ辞書ファイルを、設定に従ってzstdで圧縮して書き出します。
+    ///
+    /// 圧縮データの前には、メタデータと展開後の辞書ファイルのダイジェストを記録した
+    /// [`CompressedHeader`]が、後ろには圧縮データのダイジェストが書き込まれます。
+    /// ダイジェストを先頭に置くため、リーダーは2回読み込まれます。圧縮データは
+    /// メモリ上に保持されずに書き込み先へ順次書き出されます。一時ファイルに書き出した
+    /// 辞書データを、進捗を表示しながら圧縮する場合などに使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 圧縮する辞書ファイルのリーダー。現在の位置から末尾までを圧縮します。
+    /// * `wtr` - 書き込み先
+    /// * `metadata` - ヘッダに記録するメタデータ。通常は辞書ファイルに埋め込んだものと同じです。
+    ///
+    /// # 戻り値
+    ///
+    /// 書き込んだヘッダ
+    ///
+    /// # エラー
+    ///
+    /// 圧縮レベルやウィンドウサイズが範囲外の場合や、読み書きや圧縮に失敗した場合、
+    /// 2回の読み込みで内容が異なる場合に [`VibratoError`] を返します。
+    pub fn compress<R, W>(
+        &self,
+        mut rdr: R,
+        wtr: W,
+        metadata: Option<&DictionaryMetadata>,
+    ) -> Result<CompressedHeader>
+    where
+        R: Read + Seek,
+        W: Write,
+    {
+        self.check()?;
+        let start = rdr.stream_position()?;
+        let (content_hash, content_size) = digest(&mut rdr)?;
+        rdr.seek(SeekFrom::Start(start))?;
+        self.compress_with_digest(rdr, wtr, metadata, content_hash, content_size)
+    }
+
+    /// ダイジェストとサイズが計算済みの辞書ファイルを、設定に従ってzstdで圧縮して書き出します。
+    ///
+    /// 読み込んだ内容が`content_hash`と`content_size`に一致しない場合はエラーを返します。
+    pub(crate) fn compress_with_digest<R, W>(
+        &self,
+        rdr: R,
+        mut wtr: W,
+        metadata: Option<&DictionaryMetadata>,
+        content_hash: String,
+        content_size: u64,
+    ) -> Result<CompressedHeader>
+    where
+        R: Read,
+        W: Write,
+    {
+        self.check()?;
+        let header = CompressedHeader {
+            metadata: metadata.cloned().unwrap_or_default(),
+            content_hash,
+            content_size,
+        };
+        header.write(&mut wtr)?;
+
+        let mut rdr = HashingReader::new(rdr);
+        let mut encoder = self.encoder(HashingWriter::new(&mut wtr))?;
+        io::copy(&mut rdr, &mut encoder)?;
+        let compressed_hash = encoder.finish()?.hasher.finalize();
+
+        let (actual_hash, actual_size) = rdr.finish();
+        if actual_hash != header.content_hash || actual_size != header.content_size {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "The dictionary data changed while it was being compressed.",
+            ));
+        }
+
+        wtr.write_all(&SKIPPABLE_MAGIC.to_le_bytes())?;
+        wtr.write_all(&(DIGEST_BODY_LEN as u32).to_le_bytes())?;
+        wtr.write_all(DIGEST_MAGIC)?;
+        wtr.write_all(&compressed_hash)?;
+        Ok(header)
+    }
+}
+
+/// リーダーの末尾までを読み込み、SHA-256ダイジェストの16進数表現とバイト数を計算します。
+pub(crate) fn digest<R: Read>(rdr: R) -> io::Result<(String, u64)> {
+    let mut rdr = HashingReader::new(rdr);
+    io::copy(&mut rdr, &mut io::sink())?;
+    Ok(rdr.finish())
+}
+
+/// 圧縮された辞書の先頭に置かれるヘッダ
+///
+/// 辞書を展開せずにメタデータを確認したり、末尾に記録されたダイジェストを使って
+/// 展開前に圧縮データの破損を検出したりするために使用します。
+///
+/// # 例
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use vibrato_rkyv::dictionary::CompressedHeader;
+///
+/// # fn main() -> vibrato_rkyv::errors::Result<()> {
+/// let mut rdr = BufReader::new(File::open("system.dic.zst")?);
+/// if let Some(header) = CompressedHeader::read(&mut rdr)? {
+///     println!("name: {:?}", header.metadata().name());
+///     println!("sha256: {}", header.content_hash());
+///     header.verify(rdr)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedHeader {
+    metadata: DictionaryMetadata,
+    content_hash: String,
+    content_size: u64,
+}
+
+impl CompressedHeader {
+    /// リーダーの先頭からヘッダを読み込みます。
+    ///
+    /// ヘッダが存在する場合、リーダーはヘッダの直後（圧縮データの先頭）まで進みます。
+    ///
+    /// # 戻り値
+    ///
+    /// ヘッダが存在する場合はその内容。ヘッダを持たない古い形式のファイルや、
+    /// zstd以外のファイルでは`None`。
+    ///
+    /// # エラー
+    ///
+    /// 読み込みに失敗した場合や、ヘッダの形式が不正な場合に [`VibratoError`] を返します。
+    pub fn read<R: Read>(mut rdr: R) -> Result<Option<Self>> {
+        let mut frame_header = [0; 8];
+        match rdr.read_exact(&mut frame_header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let (magic, len) = frame_header.split_at(4);
+        if u32::from_le_bytes(magic.try_into().unwrap()) != SKIPPABLE_MAGIC {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(len.try_into().unwrap());
+        if len > MAX_HEADER_LEN {
+            return Err(VibratoError::invalid_format(
+                "header",
+                format!("The compressed dictionary header is too large: {len} bytes."),
+            ));
+        }
+        let mut body = vec![0; len as usize];
+        rdr.read_exact(&mut body)?;
+        let Some(body) = body.strip_prefix(HEADER_MAGIC) else {
+            return Ok(None);
+        };
+
+        let mut metadata = DictionaryMetadata::parse(body)?;
+        let mut take = |key: &str| {
+            metadata.remove(key).ok_or_else(|| {
+                VibratoError::invalid_format(
+                    "header",
+                    format!("The compressed dictionary header lacks `{key}`."),
+                )
+            })
+        };
+        let content_hash = take(KEY_CONTENT_SHA256)?;
+        let content_size = take(KEY_CONTENT_SIZE)?.parse()?;
+        Ok(Some(Self {
+            metadata,
+            content_hash,
+            content_size,
+        }))
+    }
+
+    /// ファイルの先頭からヘッダを読み込みます。
+    ///
+    /// # エラー
+    ///
+    /// [`CompressedHeader::read()`]と同じ条件で [`VibratoError`] を返します。
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// 圧縮された辞書に記録されたメタデータを取得します。
+    pub fn metadata(&self) -> &DictionaryMetadata {
+        &self.metadata
+    }
+
+    /// 展開後の辞書ファイルのSHA-256ダイジェストを16進数表現で取得します。
+    ///
+    /// 展開した辞書の[`Dictionary::content_hash()`](crate::Dictionary::content_hash)と一致します。
+    pub fn content_hash(&self) -> &str {
+        &self.content_hash
+    }
+
+    /// 展開後の辞書ファイルのバイト数を取得します。
+    pub fn content_size(&self) -> u64 {
+        self.content_size
+    }
+
+    /// ヘッダに続く圧縮データが、末尾に記録されたダイジェストと一致することを検証します。
+    ///
+    /// 圧縮データはメモリ上に保持されず、末尾まで順次読み込まれます。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - [`CompressedHeader::read()`]でヘッダを読み込んだ後のリーダー
+    ///
+    /// # 戻り値
+    ///
+    /// 圧縮データのSHA-256ダイジェストの16進数表現
+    ///
+    /// # エラー
+    ///
+    /// 読み込みに失敗した場合や、末尾のダイジェストが存在しない場合、
+    /// ダイジェストが一致しない場合に [`VibratoError`] を返します。
+    pub fn verify<R: Read>(&self, mut rdr: R) -> Result<String> {
+        let mut hasher = Sha256::new();
+        // The last DIGEST_FRAME_LEN bytes read so far are held back from the hasher because
+        // they may be the trailing digest frame.
+        let mut pending = Vec::with_capacity(DIGEST_FRAME_LEN + 8192);
+        let mut buf = [0; 8192];
+        loop {
+            let n = match rdr.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            pending.extend_from_slice(&buf[..n]);
+            if pending.len() > DIGEST_FRAME_LEN {
+                let hashed = pending.len() - DIGEST_FRAME_LEN;
+                hasher.update(&pending[..hashed]);
+                pending.drain(..hashed);
+            }
+        }
+
+        let expected = (pending.len() == DIGEST_FRAME_LEN)
+            .then(|| pending.split_at(8))
+            .filter(|(frame_header, _)| {
+                frame_header[..4] == SKIPPABLE_MAGIC.to_le_bytes()
+                    && frame_header[4..] == (DIGEST_BODY_LEN as u32).to_le_bytes()
+            })
+            .and_then(|(_, body)| body.strip_prefix(DIGEST_MAGIC))
+            .ok_or_else(|| {
+                VibratoError::invalid_format(
+                    "rdr",
+                    "The compressed dictionary lacks the trailing digest. It may be truncated.",
+                )
+            })?;
+        let expected = hex::encode(expected);
+        let actual = hex::encode(hasher.finalize());
+        if actual != expected {
+            return Err(VibratoError::invalid_format(
+                "rdr",
+                format!(
+                    "The compressed dictionary is corrupted: expected sha256 {expected}, got {actual}."
+                ),
+            ));
+        }
+        Ok(actual)
+    }
+
+    /// 展開後の辞書ファイルが、ヘッダに記録されたダイジェストとサイズに一致することを検証します。
+    ///
+    /// 展開した辞書をキャッシュとして保存する前に、展開処理の誤りや入力の差し替えを
+    /// 検出するために使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 展開後の辞書ファイルのリーダー
+    ///
+    /// # エラー
+    ///
+    /// 読み込みに失敗した場合や、サイズまたはダイジェストが一致しない場合に
+    /// [`VibratoError`] を返します。
+    pub(crate) fn verify_content<R: Read>(&self, rdr: R) -> Result<()> {
+        let (hash, size) = digest(rdr)?;
+        if size != self.content_size {
+            return Err(VibratoError::invalid_format(
+                "rdr",
+                format!(
+                    "The expanded dictionary is {size} bytes, but the header records {} bytes.",
+                    self.content_size
+                ),
+            ));
+        }
+        if hash != self.content_hash {
+            return Err(VibratoError::invalid_format(
+                "rdr",
+                format!(
+                    "The expanded dictionary does not match the header: expected sha256 {}, got {hash}.",
+                    self.content_hash
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// スキップ可能フレームとしてヘッダを書き出します。
+    fn write<W: Write>(&self, mut wtr: W) -> Result<()> {
+        let metadata = self
+            .metadata
+            .clone()
+            .with(KEY_CONTENT_SHA256, &self.content_hash)
+            .with(KEY_CONTENT_SIZE, self.content_size.to_string());
+        let mut body = HEADER_MAGIC.to_vec();
+        body.extend_from_slice(metadata.to_body().as_bytes());
+        let len = u32::try_from(body.len())
+            .ok()
+            .filter(|&len| len <= MAX_HEADER_LEN)
+            .ok_or_else(|| {
+                VibratoError::invalid_argument("metadata", "The metadata is too large.")
+            })?;
+        wtr.write_all(&SKIPPABLE_MAGIC.to_le_bytes())?;
+        wtr.write_all(&len.to_le_bytes())?;
+        wtr.write_all(&body)?;
+        Ok(())
+    }
+}
+
+/// 読み込んだデータのダイジェストと長さを計算するリーダー
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    /// ダイジェストの16進数表現と、読み込んだバイト数を返します。
+    fn finish(self) -> (String, u64) {
+        (hex::encode(self.hasher.finalize()), self.len)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+/// 書き込んだデータのダイジェストを計算するライター
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::dictionary::SystemDictionaryBuilder;
+    use crate::{CacheStrategy, Dictionary};
+
+    fn sample_data() -> Vec<u8> {
+        (0..100_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let data = sample_data();
+
+        let mut single = vec![];
+        let options = WriteOptions::new().level(3);
+        let header = options.compress(Cursor::new(&data), &mut single, None).unwrap();
+        assert_eq!(header.content_size(), data.len() as u64);
+        assert_eq!(zstd::decode_all(&*single).unwrap(), data);
+
+        let mut multi = vec![];
+        WriteOptions::new()
+            .level(3)
+            .workers(2)
+            .window_log(MAX_WINDOW_LOG)
+            .compress(Cursor::new(&data), &mut multi, None)
+            .unwrap();
+        assert_eq!(zstd::decode_all(&*multi).unwrap(), data);
+    }
+
+    #[test]
+    fn test_header() {
+        let data = sample_data();
+        let metadata = DictionaryMetadata::new().with("name", "acme").with("version", "1.0");
+
+        let mut compressed = vec![];
+        let written = WriteOptions::new()
+            .level(3)
+            .compress(Cursor::new(&data), &mut compressed, Some(&metadata))
+            .unwrap();
+
+        let mut rdr = compressed.as_slice();
+        let header = CompressedHeader::read(&mut rdr).unwrap().unwrap();
+        assert_eq!(header, written);
+        assert_eq!(header.metadata(), &metadata);
+        assert_eq!(header.content_hash(), hex::encode(Sha256::digest(&data)));
+        assert_eq!(header.content_size(), data.len() as u64);
+        let header_len = compressed.len() - rdr.len();
+        let compressed_hash = header.verify(rdr).unwrap();
+
+        // The digest of the compressed frames is stored in the trailing frame.
+        let trailer_start = compressed.len() - DIGEST_FRAME_LEN;
+        let frames = &compressed[header_len..trailer_start];
+        assert_eq!(compressed_hash, hex::encode(Sha256::digest(frames)));
+        assert!(compressed[trailer_start + 8..].starts_with(DIGEST_MAGIC));
+
+        let mut corrupted = compressed.clone();
+        let middle = compressed.len() / 2;
+        corrupted[middle] ^= 1;
+        let mut rdr = corrupted.as_slice();
+        let header = CompressedHeader::read(&mut rdr).unwrap().unwrap();
+        assert!(header.verify(rdr).is_err());
+
+        let truncated = &compressed[..compressed.len() - 1];
+        let mut rdr = truncated;
+        let header = CompressedHeader::read(&mut rdr).unwrap().unwrap();
+        assert!(header.verify(rdr).is_err());
+
+        header.verify_content(data.as_slice()).unwrap();
+        assert!(header.verify_content(&data[1..]).is_err());
+        let mut altered = data.clone();
+        altered[0] ^= 1;
+        assert!(header.verify_content(altered.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_without_header() {
+        let plain = zstd::encode_all(&b"dictionary"[..], 3).unwrap();
+        assert_eq!(CompressedHeader::read(plain.as_slice()).unwrap(), None);
+        assert_eq!(CompressedHeader::read(&b"abc"[..]).unwrap(), None);
+
+        // A skippable frame written by another tool.
+        let mut other = SKIPPABLE_MAGIC.to_le_bytes().to_vec();
+        other.extend_from_slice(&4u32.to_le_bytes());
+        other.extend_from_slice(b"data");
+        assert_eq!(CompressedHeader::read(other.as_slice()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_from_compressed_verifies_header() {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.dic.zst");
+        let metadata = DictionaryMetadata::new().with("name", "test");
+        let mut compressed = vec![];
+        let options = WriteOptions::new().level(3);
+        dict.write_zstd_with_options(&mut compressed, &options, Some(&metadata)).unwrap();
+        std::fs::write(&path, &compressed).unwrap();
+
+        let header = CompressedHeader::from_path(&path).unwrap().unwrap();
+        assert_eq!(header.metadata().name(), Some("test"));
+        let loaded = Dictionary::from_compressed(&path, CacheStrategy::Local).unwrap();
+        assert_eq!(loaded.content_hash().unwrap(), header.content_hash());
+        assert_eq!(loaded.metadata(), Some(&metadata));
+
+        let last = compressed.len() - 1;
+        compressed[last] ^= 1;
+        std::fs::write(&path, &compressed).unwrap();
+        assert!(Dictionary::from_compressed(&path, CacheStrategy::Local).is_err());
+    }
+
+    #[test]
+    fn test_invalid_level() {
+        let options = WriteOptions::new().level(i32::MAX);
+        let mut compressed = vec![];
+        assert!(options.compress(Cursor::new([0u8; 16]), &mut compressed, None).is_err());
+        assert!(compressed.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_window_log() {
+        for window_log in [MIN_WINDOW_LOG - 1, MAX_WINDOW_LOG + 1] {
+            let options = WriteOptions::new().level(3).window_log(window_log);
+            let mut compressed = vec![];
+            assert!(options.compress(Cursor::new([0u8; 16]), &mut compressed, None).is_err());
+            assert!(compressed.is_empty());
+        }
+    }
+}
@@ -47,6 +47,27 @@ pub enum PresetDictionaryKind {
 }
 
 impl PresetDictionaryKind {
+    /// 組み込みのすべてのプリセット辞書
+    pub const ALL: &'static [Self] = &[
+        Self::Ipadic,
+        Self::UnidicCwj,
+        Self::UnidicCsj,
+        #[cfg(feature = "legacy")]
+        Self::UnidicCwjCompact,
+        #[cfg(feature = "legacy")]
+        Self::UnidicCwjCompactDual,
+        #[cfg(feature = "legacy")]
+        Self::BccwjUnidic,
+        #[cfg(feature = "legacy")]
+        Self::BccwjUnidicCompact,
+        #[cfg(feature = "legacy")]
+        Self::BccwjUnidicCompactDual,
+        #[cfg(feature = "legacy")]
+        Self::BccwjUnidicExtractedCompact,
+        #[cfg(feature = "legacy")]
+        Self::BccwjUnidicExtractedCompactDual,
+    ];
+
     /// 辞書のメタデータを取得します。
     pub(crate) fn meta(&self) -> &'static DictionaryMeta {
         use PresetDictionaryKind::*;
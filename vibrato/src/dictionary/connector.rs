@@ -12,6 +12,7 @@ use rkyv::{Archive, Deserialize, Serialize};
 pub use crate::dictionary::connector::dual_connector::DualConnector;
 pub use crate::dictionary::connector::matrix_connector::MatrixConnector;
 pub use crate::dictionary::connector::raw_connector::RawConnector;
+pub(crate) use crate::dictionary::connector::raw_connector::RawConnectorBuilder;
 use crate::dictionary::mapper::ConnIdMapper;
 
 /// コネクターのビュー機能を提供するトレイト
@@ -119,4 +120,17 @@ impl ConnectorCost for ArchivedConnectorWrapper {
             Self::Dual(c) => c.cost(right_id, left_id),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::ConnectorWrapper> for ConnectorWrapper {
+    fn from(connector: crate::legacy::dictionary::connector::ConnectorWrapper) -> Self {
+        use crate::legacy::dictionary::connector::ConnectorWrapper as Legacy;
+
+        match connector {
+            Legacy::Matrix(c) => Self::Matrix(c.into()),
+            Legacy::Raw(c) => Self::Raw(c.into()),
+            Legacy::Dual(c) => Self::Dual(c.into()),
+        }
+    }
+}
@@ -5,6 +5,8 @@
 
 use std::io::Read;
 
+use std::collections::BTreeSet;
+
 use hashbrown::{HashMap, HashSet};
 use rkyv::{Archive, Deserialize, Serialize};
 
@@ -14,7 +16,7 @@ use crate::dictionary::connector::raw_connector::scorer::{
 use crate::dictionary::connector::raw_connector::{RawConnectorBuilder, INVALID_FEATURE_ID};
 use crate::dictionary::connector::{Connector, ConnectorCost, ConnectorView, MatrixConnector};
 use crate::dictionary::mapper::ConnIdMapper;
-use crate::errors::Result;
+use crate::errors::{Result, VibratoError};
 use crate::num::U31;
 
 /// 行列コネクターと生コネクターを組み合わせたデュアルコネクター
@@ -29,17 +31,28 @@ pub struct DualConnector {
 }
 
 impl DualConnector {
+    /// デュアルコネクターの構築に必要な素性テンプレートの最小数
+    ///
+    /// デュアルコネクターは、行列サイズが最小になるように選んだこの数の素性テンプレートを
+    /// SIMDで都度計算し、残りの素性テンプレートを接続コスト行列に展開します。
+    /// `bigram.right`と`bigram.left`の素性テンプレート数がこれより少ない場合は構築できないため、
+    /// [`RawConnector`](super::RawConnector)を使用してください。
+    pub const MIN_FEATURE_TEMPLATES: usize = SIMD_SIZE;
+
     /// 貪欲探索を使用して行列サイズが小さくなるように特徴テンプレートを削除し、
     /// 残りのIDのセットを返します。
+    ///
+    /// 行列サイズが同じになる候補が複数ある場合は、最も大きいIDのテンプレートを削除します。
+    /// 候補を昇順に調べるため、結果は実行ごとに変わりません。
     pub fn remove_feature_templates_greedy(
         raw_feat_template_size: usize,
         right_feat_ids_tmp: &[Vec<U31>],
         left_feat_ids_tmp: &[Vec<U31>],
         total_feat_template_size: usize,
-    ) -> HashSet<usize> {
-        let mut matrix_indices: HashSet<usize> = (0..total_feat_template_size).collect();
-        eprintln!(
-            "Initial matrix size: {}",
+    ) -> BTreeSet<usize> {
+        let mut matrix_indices: BTreeSet<usize> = (0..total_feat_template_size).collect();
+        log::info!(
+            "[vibrato-rkyv] Initial matrix size: {}",
             left_feat_ids_tmp.len() * right_feat_ids_tmp.len()
         );
         for _ in 0..raw_feat_template_size {
@@ -67,7 +80,9 @@ impl DualConnector {
                     candidate_idx = trial_idx;
                 }
             }
-            eprintln!("Removed feature template: #{candidate_idx}, matrix size: {min_matrix_size}");
+            log::info!(
+                "[vibrato-rkyv] Removed feature template: #{candidate_idx}, matrix size: {min_matrix_size}"
+            );
             matrix_indices.remove(&candidate_idx);
         }
         matrix_indices
@@ -162,19 +177,39 @@ impl DualConnector {
     ///
     /// # エラー
     ///
-    /// ファイルフォーマットが不正な場合にエラーを返します。
+    /// ファイルフォーマットが不正な場合や、素性テンプレート数が
+    /// [`MIN_FEATURE_TEMPLATES`](Self::MIN_FEATURE_TEMPLATES)より少ない場合にエラーを返します。
     pub fn from_readers<R, L, C>(right_rdr: R, left_rdr: L, cost_rdr: C) -> Result<Self>
     where
         R: Read,
         L: Read,
         C: Read,
     {
+        Self::from_builder(RawConnectorBuilder::from_readers(
+            right_rdr, left_rdr, cost_rdr,
+        )?)
+    }
+
+    /// パース済みのbigram情報から新しいインスタンスを作成します。
+    ///
+    /// # エラー
+    ///
+    /// 素性テンプレート数が[`MIN_FEATURE_TEMPLATES`](Self::MIN_FEATURE_TEMPLATES)より
+    /// 少ない場合にエラーを返します。
+    pub(crate) fn from_builder(builder: RawConnectorBuilder) -> Result<Self> {
         let RawConnectorBuilder {
             right_feat_ids_tmp,
             left_feat_ids_tmp,
             feat_template_size,
             mut scorer_builder,
-        } = RawConnectorBuilder::from_readers(right_rdr, left_rdr, cost_rdr)?;
+        } = builder;
+        if feat_template_size < Self::MIN_FEATURE_TEMPLATES {
+            let msg = format!(
+                "The dual connector requires at least {} feature templates, but got {feat_template_size}.",
+                Self::MIN_FEATURE_TEMPLATES,
+            );
+            return Err(VibratoError::invalid_argument("bigram_right_rdr", msg));
+        }
         let scorer = scorer_builder.build();
 
         // Split features into RawConnector and MatrixConnector
@@ -325,6 +360,29 @@ impl ConnectorCost for ArchivedDualConnector {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::DualConnector> for DualConnector {
+    fn from(connector: crate::legacy::dictionary::connector::DualConnector) -> Self {
+        let crate::legacy::dictionary::connector::DualConnector {
+            matrix_connector,
+            right_conn_id_map,
+            left_conn_id_map,
+            right_feat_ids,
+            left_feat_ids,
+            raw_scorer,
+        } = connector;
+        Self {
+            matrix_connector: matrix_connector.into(),
+            right_conn_id_map,
+            left_conn_id_map,
+            right_feat_ids: right_feat_ids.into_iter().map(U31x8::from).collect(),
+            left_feat_ids: left_feat_ids.into_iter().map(U31x8::from).collect(),
+            raw_scorer: raw_scorer.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,6 +36,12 @@ impl MatrixConnector {
 
     /// `matrix.def` ファイルから新しいインスタンスを作成します。
     ///
+    /// ファイルは1行ずつ読み込まれ、ヘッダーの大きさに合わせて確保した行列に直接書き込まれるため、
+    /// 大きな行列でもファイル全体を保持することはありません。
+    /// 記述されていない組の接続コストは0になります。
+    /// すべての組が記述されていることを検証するには
+    /// [`from_reader_strict()`](Self::from_reader_strict) を使用してください。
+    ///
     /// # 引数
     ///
     /// * `rdr` - `matrix.def` ファイルのリーダー
@@ -46,33 +52,111 @@ impl MatrixConnector {
     ///
     /// # エラー
     ///
-    /// ファイルフォーマットが不正な場合にエラーを返します。
+    /// ファイルフォーマットが不正な場合や、同じ組が複数回記述されている場合に、
+    /// 行番号を含むエラーを返します。
     pub fn from_reader<R>(rdr: R) -> Result<Self>
     where
         R: Read,
     {
-        let reader = BufReader::new(rdr);
-        let mut lines = reader.lines();
-
-        let (num_right, num_left) = Self::parse_header(&lines.next().unwrap()?)?;
-        let mut data = vec![0; num_right * num_left];
-
-        for line in lines {
-            let line = line?;
-            if !line.is_empty() {
-                let (right_id, left_id, conn_cost) = Self::parse_body(&line)?;
-                if num_right <= right_id || num_left <= left_id {
-                    return Err(VibratoError::invalid_format(
-                        "matrix.def",
-                        "left/right_id must be within num_left/right.",
-                    ));
-                }
-                data[left_id * num_right + right_id] = conn_cost;
+        Self::parse(rdr, false)
+    }
+
+    /// `matrix.def` ファイルから、すべての組が記述されていることを検証して新しいインスタンスを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - `matrix.def` ファイルのリーダー
+    ///
+    /// # 戻り値
+    ///
+    /// 成功時は `Ok(MatrixConnector)` を返します。
+    ///
+    /// # エラー
+    ///
+    /// [`from_reader()`](Self::from_reader) のエラーに加えて、
+    /// 記述されていない組がある場合にエラーを返します。
+    pub fn from_reader_strict<R>(rdr: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        Self::parse(rdr, true)
+    }
+
+    fn parse<R>(rdr: R, require_complete: bool) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut reader = BufReader::new(rdr);
+        let mut line = vec![];
+
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(VibratoError::invalid_format("matrix.def", "The header is missing."));
+        }
+        let (num_right, num_left) = Self::parse_header(Self::trim_line(&line, 1)?)?;
+        let num_entries = num_right * num_left;
+        let mut data = vec![0; num_entries];
+        let mut seen = vec![0u64; num_entries.div_ceil(64)];
+        let progress_step = (num_entries / 10).max(1);
+        let mut num_read = 0;
+
+        let mut line_no = 1;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            line_no += 1;
+            let body = Self::trim_line(&line, line_no)?;
+            if body.is_empty() {
+                continue;
+            }
+            let (right_id, left_id, conn_cost) = Self::parse_body(body, line_no)?;
+            if num_right <= right_id || num_left <= left_id {
+                let msg = format!(
+                    "line {line_no}: left/right_id must be within num_left/right, {body}"
+                );
+                return Err(VibratoError::invalid_format("matrix.def", msg));
+            }
+            let index = left_id * num_right + right_id;
+            let (block, bit) = (index / 64, 1 << (index % 64));
+            if seen[block] & bit != 0 {
+                let msg = format!(
+                    "line {line_no}: the pair of right_id {right_id} and left_id {left_id} is duplicated."
+                );
+                return Err(VibratoError::invalid_format("matrix.def", msg));
+            }
+            seen[block] |= bit;
+            data[index] = conn_cost;
+
+            num_read += 1;
+            if num_read % progress_step == 0 {
+                log::info!("Read {num_read}/{num_entries} connection costs from matrix.def");
             }
         }
+
+        if require_complete && num_read != num_entries {
+            let index = (0..num_entries)
+                .find(|&i| seen[i / 64] & (1 << (i % 64)) == 0)
+                .unwrap();
+            let msg = format!(
+                "{} of {num_entries} pairs are missing, e.g., right_id {} and left_id {}.",
+                num_entries - num_read,
+                index % num_right,
+                index / num_right,
+            );
+            return Err(VibratoError::invalid_format("matrix.def", msg));
+        }
         Ok(Self::new(data, num_right, num_left))
     }
 
+    /// 行末の改行文字を取り除き、UTF-8の文字列として返します。
+    fn trim_line(line: &[u8], line_no: usize) -> Result<&str> {
+        let line = std::str::from_utf8(line).map_err(|_| {
+            VibratoError::invalid_format("matrix.def", format!("line {line_no}: invalid UTF-8."))
+        })?;
+        Ok(line.trim_end_matches(['\n', '\r']))
+    }
+
     fn parse_header(line: &str) -> Result<(usize, usize)> {
         let cols: Vec<_> = line.split(' ').collect();
         if cols.len() != 2 {
@@ -86,16 +170,24 @@ impl MatrixConnector {
         }
     }
 
-    fn parse_body(line: &str) -> Result<(usize, usize, i16)> {
-        let cols: Vec<_> = line.split(' ').collect();
-        if cols.len() != 3 {
+    fn parse_body(line: &str, line_no: usize) -> Result<(usize, usize, i16)> {
+        let invalid = || {
             let msg = format!(
-                "A row other than the header must consists of three integers separated by spaces, {line}"
+                "line {line_no}: a row other than the header must consists of three integers separated by spaces, {line}"
             );
-            Err(VibratoError::invalid_format("matrix.def", msg))
-        } else {
-            Ok((cols[0].parse()?, cols[1].parse()?, cols[2].parse()?))
-        }
+            VibratoError::invalid_format("matrix.def", msg)
+        };
+        let mut cols = line.split(' ');
+        let (Some(right_id), Some(left_id), Some(conn_cost), None) =
+            (cols.next(), cols.next(), cols.next(), cols.next())
+        else {
+            return Err(invalid());
+        };
+        Ok((
+            right_id.parse().map_err(|_| invalid())?,
+            left_id.parse().map_err(|_| invalid())?,
+            conn_cost.parse().map_err(|_| invalid())?,
+        ))
     }
 
     #[inline(always)]
@@ -182,6 +274,19 @@ impl ConnectorCost for ArchivedMatrixConnector {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::MatrixConnector> for MatrixConnector {
+    fn from(connector: crate::legacy::dictionary::connector::MatrixConnector) -> Self {
+        let crate::legacy::dictionary::connector::MatrixConnector {
+            data,
+            num_right,
+            num_left,
+        } = connector;
+        Self::new(data, num_right, num_left)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,6 +413,46 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_duplicated_entry() {
+        let data = "2 2
+0 0 0
+0 1 1
+0 0 -2
+1 1 -3";
+        let result = MatrixConnector::from_reader(data.as_bytes());
+
+        assert!(result.err().unwrap().to_string().contains("line 4"));
+    }
+
+    #[test]
+    fn test_missing_entry() {
+        let data = "2 2
+0 0 0
+0 1 1
+
+1 1 -3";
+        let conn = MatrixConnector::from_reader(data.as_bytes()).unwrap();
+        assert_eq!(conn.cost(1, 0), 0);
+        assert_eq!(conn.cost(1, 1), -3);
+
+        let result = MatrixConnector::from_reader_strict(data.as_bytes());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_crlf() {
+        let data = "1 2\r\n0 0 1\r\n0 1 -1\r\n";
+        let conn = MatrixConnector::from_reader_strict(data.as_bytes()).unwrap();
+        assert_eq!(conn.cost(0, 0), 1);
+        assert_eq!(conn.cost(0, 1), -1);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(MatrixConnector::from_reader("".as_bytes()).is_err());
+    }
+
     #[test]
     fn test_larger_right_id() {
         let data = "2 2
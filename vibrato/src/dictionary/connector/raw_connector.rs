@@ -43,7 +43,7 @@ impl RawConnector {
     /// * `left_feat_ids` - 左特徴ID
     /// * `feat_template_size` - 特徴テンプレートのサイズ
     /// * `scorer` - スコアラー
-    pub const fn new(
+    pub(crate) const fn new(
         right_feat_ids: Vec<U31x8>,
         left_feat_ids: Vec<U31x8>,
         feat_template_size: usize,
@@ -78,12 +78,19 @@ impl RawConnector {
         L: Read,
         C: Read,
     {
+        Ok(Self::from_builder(RawConnectorBuilder::from_readers(
+            right_rdr, left_rdr, cost_rdr,
+        )?))
+    }
+
+    /// パース済みのbigram情報から新しいインスタンスを作成します。
+    pub(crate) fn from_builder(builder: RawConnectorBuilder) -> Self {
         let RawConnectorBuilder {
             right_feat_ids_tmp,
             left_feat_ids_tmp,
             mut feat_template_size,
             scorer_builder,
-        } = RawConnectorBuilder::from_readers(right_rdr, left_rdr, cost_rdr)?;
+        } = builder;
 
         // Adjusts to a multiple of SIMD_SIZE for AVX2 compatibility.
         //
@@ -118,12 +125,12 @@ impl RawConnector {
             trg[..src.len()].copy_from_slice(src);
         }
 
-        Ok(Self::new(
+        Self::new(
             U31x8::to_simd_vec(&right_feat_ids),
             U31x8::to_simd_vec(&left_feat_ids),
             feat_template_size / SIMD_SIZE,
             scorer_builder.build(),
-        ))
+        )
     }
 
     #[inline(always)]
@@ -403,6 +410,25 @@ impl ConnectorCost for ArchivedRawConnector {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::RawConnector> for RawConnector {
+    fn from(connector: crate::legacy::dictionary::connector::RawConnector) -> Self {
+        let crate::legacy::dictionary::connector::RawConnector {
+            right_feat_ids,
+            left_feat_ids,
+            feat_template_size,
+            scorer,
+        } = connector;
+        Self {
+            right_feat_ids: right_feat_ids.into_iter().map(U31x8::from).collect(),
+            left_feat_ids: left_feat_ids.into_iter().map(U31x8::from).collect(),
+            feat_template_size,
+            scorer: scorer.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,21 +120,7 @@ impl ScorerBuilder {
             }
         }
 
-        #[cfg(target_feature = "avx2")]
-        let bases_len = unsafe { x86_64::_mm256_set1_epi32(i32::try_from(bases.len()).unwrap()) };
-        #[cfg(target_feature = "avx2")]
-        let checks_len = unsafe { x86_64::_mm256_set1_epi32(i32::try_from(checks.len()).unwrap()) };
-
-        Scorer {
-            bases,
-            checks,
-            costs,
-
-            #[cfg(target_feature = "avx2")]
-            bases_len: M256i(bases_len),
-            #[cfg(target_feature = "avx2")]
-            checks_len: M256i(checks_len),
-        }
+        Scorer::from_parts(bases, checks, costs)
     }
 }
 
@@ -188,6 +174,33 @@ impl Default for Scorer {
 }
 
 impl Scorer {
+    /// ダブル配列の各配列からスコアラーを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `bases` - ベース配列
+    /// * `checks` - チェック配列
+    /// * `costs` - `checks`と同じ長さのコスト配列
+    pub(crate) fn from_parts(bases: Vec<u32>, checks: Vec<u32>, costs: Vec<i32>) -> Self {
+        debug_assert_eq!(checks.len(), costs.len());
+
+        #[cfg(target_feature = "avx2")]
+        let bases_len = unsafe { x86_64::_mm256_set1_epi32(i32::try_from(bases.len()).unwrap()) };
+        #[cfg(target_feature = "avx2")]
+        let checks_len = unsafe { x86_64::_mm256_set1_epi32(i32::try_from(checks.len()).unwrap()) };
+
+        Self {
+            bases,
+            checks,
+            costs,
+
+            #[cfg(target_feature = "avx2")]
+            bases_len: M256i(bases_len),
+            #[cfg(target_feature = "avx2")]
+            checks_len: M256i(checks_len),
+        }
+    }
+
     /// キーペアからコストを取得します（AVX2なし版）。
     #[cfg(not(target_feature = "avx2"))]
     #[inline(always)]
@@ -445,6 +458,22 @@ impl ArchivedU31x8 {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::raw_connector::scorer::U31x8> for U31x8 {
+    fn from(x: crate::legacy::dictionary::connector::raw_connector::scorer::U31x8) -> Self {
+        // The values were valid U31s in the legacy dictionary.
+        Self(x.to_array().map(U31))
+    }
+}
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::connector::raw_connector::scorer::Scorer> for Scorer {
+    fn from(scorer: crate::legacy::dictionary::connector::raw_connector::scorer::Scorer) -> Self {
+        Self::from_parts(scorer.bases, scorer.checks, scorer.costs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
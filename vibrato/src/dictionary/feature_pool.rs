@@ -0,0 +1,629 @@
+//! 辞書データから切り離された素性
+//!
+//! 素性文字列は辞書の大部分を占めますが、分割処理では参照されません。
+//! [`DictionaryInner::shard_features()`](super::DictionaryInner::shard_features)を
+//! 呼び出した辞書では、素性は辞書データに含まれず、表層形の前に次の形式で追記されます。
+//! 素性は[`Token::feature()`](crate::token::Token::feature)などで最初に参照されたときに
+//! 初めて読み込まれるため、分割処理で使用するメモリの範囲を小さく保てます。
+//!
+//! ```text
+//! [辞書データ][素性の本体][本体長 (u64 LE)][マジックバイト][表層形][メタデータ]
+//! ```
+//!
+//! マジックバイトが`FEATURES_MAGIC`の場合、本体には、システム辞書とユーザー辞書の順に、
+//! 単語数 (u32 LE)、各素性の終了位置 (u32 LE)、素性を連結したUTF-8の文字列が並びます。
+//! 終了位置は連結した文字列の先頭からのバイト数です。素性を切り離していない辞書の
+//! 単語数は`u32::MAX`となり、その素性は辞書データから読み込まれます。
+//!
+//! [`DictionaryInner::dedup_features()`](super::DictionaryInner::dedup_features)または
+//! [`DictionaryInner::compress_features()`](super::DictionaryInner::compress_features)を
+//! 呼び出した辞書では、マジックバイトは`DEDUP_FEATURES_MAGIC`となり、本体は次の形式です。
+//!
+//! ```text
+//! [フラグ (u32 LE)][圧縮レベル (i32 LE)]
+//! [システム辞書の単語数 (u32 LE)][各単語の素性番号 (u32 LE)]
+//! [ユーザー辞書の単語数 (u32 LE)][各単語の素性番号 (u32 LE)]
+//! [異なり素性数 (u32 LE)][各素性の終了位置 (u32 LE)][素性を連結した文字列]
+//! ```
+//!
+//! 同一の素性は一度だけ格納され、辞書順に並べられます。フラグの最下位ビットが
+//! 立っている場合、連結した文字列はzstdで圧縮されており、最初に参照されたときに
+//! 展開されます。
+//!
+//! 素性を切り離した辞書ファイルは、この形式に対応していない以前のバージョンでは
+//! 読み込めません。
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use crate::dictionary::{LexType, metadata, surface};
+use crate::errors::{Result, VibratoError};
+
+/// 素性の本体の末尾に置かれるマジックバイト
+const FEATURES_MAGIC: &[u8] = b"VibratoFeatures\n";
+
+/// 重複を除いた素性の本体の末尾に置かれるマジックバイト
+const DEDUP_FEATURES_MAGIC: &[u8] = b"VibratoFeatDedup";
+
+/// 本体長の格納に使用するバイト数
+const LEN_BYTES: usize = 8;
+
+/// 素性を切り離していないことを表す単語数
+const NOT_DETACHED: u32 = u32::MAX;
+
+/// 連結した文字列がzstdで圧縮されていることを表すフラグ
+const FLAG_ZSTD: u32 = 1;
+
+/// 切り離された素性の書き込み形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum FeatureEncoding {
+    /// 単語ごとに素性を格納します。
+    #[default]
+    Plain,
+
+    /// 同一の素性を一度だけ格納します。
+    Dedup,
+
+    /// 同一の素性を一度だけ格納し、指定されたレベルでzstd圧縮します。
+    Compressed(i32),
+}
+
+/// 辞書ファイルから切り離した素性の本体
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum FeatureBody<'a> {
+    /// `FEATURES_MAGIC`で終わる本体
+    Plain(&'a [u8]),
+
+    /// `DEDUP_FEATURES_MAGIC`で終わる本体
+    Dedup(&'a [u8]),
+}
+
+/// 辞書ファイル上の素性を参照するプール
+#[derive(Debug)]
+pub(crate) struct FeaturePool<'a> {
+    system: Option<PoolLexicon<'a>>,
+    user: Option<PoolLexicon<'a>>,
+    tables: Vec<StringTable<'a>>,
+    encoding: FeatureEncoding,
+}
+
+/// 一つの辞書の素性
+#[derive(Debug)]
+struct PoolLexicon<'a> {
+    /// 各単語の素性番号。`None`の場合は単語IDをそのまま素性番号とします。
+    ids: Option<&'a [u8]>,
+    /// 参照する文字列テーブルの位置
+    table: usize,
+}
+
+/// 終了位置と連結した文字列からなる文字列テーブル
+#[derive(Debug)]
+struct StringTable<'a> {
+    ends: &'a [u8],
+    strings: Cow<'a, str>,
+}
+
+impl<'a> FeaturePool<'a> {
+    /// 追記された素性の本体を解析します。
+    ///
+    /// 終了位置、素性番号、文字列はここで検証されるため、以降の参照では検証を行いません。
+    /// 圧縮された素性はここで展開されます。
+    pub(crate) fn parse(body: FeatureBody<'a>) -> Result<Self> {
+        let pool = match body {
+            FeatureBody::Plain(body) => Self::parse_plain(body)?,
+            FeatureBody::Dedup(body) => Self::parse_dedup(body)?,
+        };
+        Ok(pool)
+    }
+
+    fn parse_plain(mut body: &'a [u8]) -> Result<Self> {
+        let mut tables = vec![];
+        let mut lexicons = [None, None];
+        for lexicon in &mut lexicons {
+            let num_words = read_u32(&mut body)?;
+            if num_words == NOT_DETACHED {
+                continue;
+            }
+            let (table, rest) = StringTable::parse(body, num_words, false)?;
+            body = rest;
+            *lexicon = Some(PoolLexicon {
+                ids: None,
+                table: tables.len(),
+            });
+            tables.push(table);
+        }
+        check_empty(body)?;
+        let [system, user] = lexicons;
+        Ok(Self {
+            system,
+            user,
+            tables,
+            encoding: FeatureEncoding::Plain,
+        })
+    }
+
+    fn parse_dedup(mut body: &'a [u8]) -> Result<Self> {
+        let flags = read_u32(&mut body)?;
+        let level = read_u32(&mut body)? as i32;
+        let mut lexicons = [None, None];
+        for lexicon in &mut lexicons {
+            let num_words = read_u32(&mut body)?;
+            if num_words == NOT_DETACHED {
+                continue;
+            }
+            let (ids, rest) = split_u32s(body, num_words)?;
+            body = rest;
+            *lexicon = Some(PoolLexicon {
+                ids: Some(ids),
+                table: 0,
+            });
+        }
+        let num_features = read_u32(&mut body)?;
+        let compressed = flags & FLAG_ZSTD != 0;
+        let (table, rest) = StringTable::parse(body, num_features, compressed)?;
+        check_empty(rest)?;
+        for ids in lexicons.iter().flatten().filter_map(|lexicon| lexicon.ids) {
+            if ids
+                .chunks_exact(4)
+                .any(|id| u32::from_le_bytes(id.try_into().unwrap()) >= num_features)
+            {
+                return Err(VibratoError::invalid_format(
+                    "features",
+                    "A feature index is out of range.",
+                ));
+            }
+        }
+        let [system, user] = lexicons;
+        Ok(Self {
+            system,
+            user,
+            tables: vec![table],
+            encoding: if compressed {
+                FeatureEncoding::Compressed(level)
+            } else {
+                FeatureEncoding::Dedup
+            },
+        })
+    }
+
+    /// 指定された単語の素性を取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// 辞書の素性が切り離されていない場合や、単語IDが範囲外の場合は`None`
+    #[inline(always)]
+    pub(crate) fn get(&self, lex_type: LexType, word_id: u32) -> Option<&str> {
+        let lexicon = match lex_type {
+            LexType::System => self.system.as_ref()?,
+            LexType::User => self.user.as_ref()?,
+            LexType::Unknown => return None,
+        };
+        self.lexicon_feature(lexicon, usize::try_from(word_id).ok()?)
+    }
+
+    /// 素性の書き込み形式を取得します。
+    pub(crate) const fn encoding(&self) -> FeatureEncoding {
+        self.encoding
+    }
+
+    /// 素性を所有する文字列のベクターに変換します。
+    pub(crate) fn to_vecs(&self) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        let to_vec = |lexicon: &PoolLexicon| {
+            (0..self.lexicon_len(lexicon))
+                .map(|i| self.lexicon_feature(lexicon, i).unwrap().to_string())
+                .collect()
+        };
+        (
+            self.system.as_ref().map(to_vec),
+            self.user.as_ref().map(to_vec),
+        )
+    }
+
+    #[inline(always)]
+    fn lexicon_len(&self, lexicon: &PoolLexicon) -> usize {
+        match lexicon.ids {
+            Some(ids) => ids.len() / 4,
+            None => self.tables[lexicon.table].len(),
+        }
+    }
+
+    #[inline(always)]
+    fn lexicon_feature(&self, lexicon: &PoolLexicon, i: usize) -> Option<&str> {
+        let table = &self.tables[lexicon.table];
+        match lexicon.ids {
+            Some(ids) => {
+                let id = ids.get(i * 4..i * 4 + 4)?;
+                table.get(u32::from_le_bytes(id.try_into().unwrap()) as usize)
+            }
+            None => table.get(i),
+        }
+    }
+}
+
+impl<'a> StringTable<'a> {
+    /// 終了位置と連結した文字列を解析し、残りのバイト列とともに返します。
+    ///
+    /// 圧縮されていない場合、連結した文字列は最後の終了位置までとなり、
+    /// 圧縮されている場合は残りのすべてとなります。
+    fn parse(body: &'a [u8], num_strings: u32, compressed: bool) -> Result<(Self, &'a [u8])> {
+        let (ends, rest) = split_u32s(body, num_strings)?;
+        let mut total = 0;
+        for end in ends.chunks_exact(4) {
+            let end = usize::try_from(u32::from_le_bytes(end.try_into().unwrap()))?;
+            if end < total {
+                return Err(VibratoError::invalid_format(
+                    "features",
+                    "The feature offsets must be in ascending order.",
+                ));
+            }
+            total = end;
+        }
+        let (strings, rest) = if compressed {
+            let strings = decompress(rest, total)?;
+            if strings.len() != total {
+                return Err(VibratoError::invalid_format(
+                    "features",
+                    "The decompressed features do not match the offsets.",
+                ));
+            }
+            let strings = String::from_utf8(strings).map_err(|e| e.utf8_error())?;
+            (Cow::Owned(strings), &rest[rest.len()..])
+        } else {
+            if rest.len() < total {
+                return Err(unexpected_end());
+            }
+            let (strings, rest) = rest.split_at(total);
+            (Cow::Borrowed(std::str::from_utf8(strings)?), rest)
+        };
+        let table = Self { ends, strings };
+        for i in 0..table.len() {
+            if !table.strings.is_char_boundary(table.end(i)) {
+                return Err(VibratoError::invalid_format(
+                    "features",
+                    "A feature offset is not on a character boundary.",
+                ));
+            }
+        }
+        Ok((table, rest))
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.ends.len() / 4
+    }
+
+    #[inline(always)]
+    fn end(&self, i: usize) -> usize {
+        let bytes = self.ends[i * 4..i * 4 + 4].try_into().unwrap();
+        u32::from_le_bytes(bytes) as usize
+    }
+
+    #[inline(always)]
+    fn get(&self, i: usize) -> Option<&str> {
+        if i >= self.len() {
+            return None;
+        }
+        let start = if i == 0 { 0 } else { self.end(i - 1) };
+        // The offsets are verified in `parse()`.
+        Some(&self.strings[start..self.end(i)])
+    }
+}
+
+/// 切り離された素性を辞書データの後ろに追記します。
+///
+/// # 引数
+///
+/// * `wtr` - 書き込み先
+/// * `system` - システム辞書の素性。切り離していない場合は`None`
+/// * `user` - ユーザー辞書の素性。切り離していない場合は`None`
+/// * `encoding` - 書き込み形式
+pub(crate) fn write_trailer<W>(
+    mut wtr: W,
+    system: Option<&[String]>,
+    user: Option<&[String]>,
+    encoding: FeatureEncoding,
+) -> Result<()>
+where
+    W: Write,
+{
+    let mut body = vec![];
+    let magic = match encoding {
+        FeatureEncoding::Plain => {
+            write_lexicon(&mut body, system)?;
+            write_lexicon(&mut body, user)?;
+            FEATURES_MAGIC
+        }
+        FeatureEncoding::Dedup => {
+            write_dedup(&mut body, system, user, None)?;
+            DEDUP_FEATURES_MAGIC
+        }
+        FeatureEncoding::Compressed(level) => {
+            write_dedup(&mut body, system, user, Some(level))?;
+            DEDUP_FEATURES_MAGIC
+        }
+    };
+    wtr.write_all(&body)?;
+    wtr.write_all(&(body.len() as u64).to_le_bytes())?;
+    wtr.write_all(magic)?;
+    Ok(())
+}
+
+/// 辞書データの後ろに追記された素性を切り離します。
+///
+/// # 引数
+///
+/// * `data` - メタデータと表層形を除いた辞書ファイルの内容
+///
+/// # 戻り値
+///
+/// 辞書データ部分と、素性が存在する場合はその本体
+///
+/// # エラー
+///
+/// 本体長が範囲外の場合に[`VibratoError`]を返します。
+pub(crate) fn split_features(data: &[u8]) -> Result<(&[u8], Option<FeatureBody<'_>>)> {
+    let (rest, dedup) = if let Some(rest) = data.strip_suffix(FEATURES_MAGIC) {
+        (rest, false)
+    } else if let Some(rest) = data.strip_suffix(DEDUP_FEATURES_MAGIC) {
+        (rest, true)
+    } else {
+        return Ok((data, None));
+    };
+    let Some(len_start) = rest.len().checked_sub(LEN_BYTES) else {
+        return Ok((data, None));
+    };
+    let len = u64::from_le_bytes(rest[len_start..].try_into().unwrap());
+    let body_start = usize::try_from(len)
+        .ok()
+        .and_then(|len| len_start.checked_sub(len))
+        .ok_or_else(|| {
+            VibratoError::invalid_format("features", "The features length is out of range.")
+        })?;
+    let body = &rest[body_start..len_start];
+    let body = if dedup {
+        FeatureBody::Dedup(body)
+    } else {
+        FeatureBody::Plain(body)
+    };
+    Ok((&rest[..body_start], Some(body)))
+}
+
+/// 辞書ファイルに追記された素性を参照します。
+///
+/// # 引数
+///
+/// * `data` - マジックナンバーとパディングを除いた辞書ファイルの内容
+///
+/// # 戻り値
+///
+/// 素性が追記されている場合はそのプール
+///
+/// # エラー
+///
+/// メタデータ、表層形、素性の形式が不正な場合に[`VibratoError`]を返します。
+pub(crate) fn read_trailer(data: &[u8]) -> Result<Option<FeaturePool<'_>>> {
+    let (data, _) = metadata::split_trailer(data)?;
+    let (data, _) = surface::split_surfaces(data)?;
+    let (_, body) = split_features(data)?;
+    body.map(FeaturePool::parse).transpose()
+}
+
+fn write_lexicon(body: &mut Vec<u8>, features: Option<&[String]>) -> Result<()> {
+    let Some(features) = features else {
+        body.extend_from_slice(&NOT_DETACHED.to_le_bytes());
+        return Ok(());
+    };
+    body.extend_from_slice(&u32::try_from(features.len())?.to_le_bytes());
+    write_strings(body, features.iter().map(String::as_str))
+}
+
+fn write_dedup(
+    body: &mut Vec<u8>,
+    system: Option<&[String]>,
+    user: Option<&[String]>,
+    level: Option<i32>,
+) -> Result<()> {
+    let flags = if level.is_some() { FLAG_ZSTD } else { 0 };
+    body.extend_from_slice(&flags.to_le_bytes());
+    body.extend_from_slice(&level.unwrap_or(0).to_le_bytes());
+
+    // Sorting places similar features next to each other, which also helps compression.
+    let unique: BTreeSet<&str> = system
+        .into_iter()
+        .chain(user)
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let ids: BTreeMap<&str, u32> = unique
+        .iter()
+        .enumerate()
+        .map(|(i, &feature)| Ok((feature, u32::try_from(i)?)))
+        .collect::<Result<_>>()?;
+    for features in [system, user] {
+        let Some(features) = features else {
+            body.extend_from_slice(&NOT_DETACHED.to_le_bytes());
+            continue;
+        };
+        body.extend_from_slice(&u32::try_from(features.len())?.to_le_bytes());
+        for feature in features {
+            body.extend_from_slice(&ids[feature.as_str()].to_le_bytes());
+        }
+    }
+
+    body.extend_from_slice(&u32::try_from(unique.len())?.to_le_bytes());
+    let Some(level) = level else {
+        return write_strings(body, unique.iter().copied());
+    };
+    let mut strings = vec![];
+    write_strings(&mut strings, unique.iter().copied())?;
+    let (ends, strings) = strings.split_at(unique.len() * 4);
+    body.extend_from_slice(ends);
+    body.extend_from_slice(&compress(strings, level)?);
+    Ok(())
+}
+
+/// 連結した文字列をzstdで圧縮します。
+#[cfg(feature = "loaders")]
+fn compress(strings: &[u8], level: i32) -> Result<Vec<u8>> {
+    Ok(zstd::bulk::compress(strings, level)?)
+}
+
+#[cfg(not(feature = "loaders"))]
+fn compress(_strings: &[u8], _level: i32) -> Result<Vec<u8>> {
+    Err(VibratoError::invalid_state(
+        "Compressing features requires the loaders feature.",
+        "",
+    ))
+}
+
+/// zstdで圧縮された連結した文字列を展開します。
+#[cfg(feature = "loaders")]
+fn decompress(strings: &[u8], capacity: usize) -> Result<Vec<u8>> {
+    Ok(zstd::bulk::decompress(strings, capacity)?)
+}
+
+#[cfg(not(feature = "loaders"))]
+fn decompress(_strings: &[u8], _capacity: usize) -> Result<Vec<u8>> {
+    Err(VibratoError::invalid_format(
+        "features",
+        "The features are compressed with zstd, which requires the loaders feature.",
+    ))
+}
+
+/// 各文字列の終了位置と、連結した文字列を書き込みます。
+fn write_strings<'a, I>(body: &mut Vec<u8>, strings: I) -> Result<()>
+where
+    I: Iterator<Item = &'a str> + Clone,
+{
+    let mut end = 0u32;
+    for s in strings.clone() {
+        end = end.checked_add(u32::try_from(s.len())?).ok_or_else(|| {
+            VibratoError::invalid_argument("features", "The features are too large.")
+        })?;
+        body.extend_from_slice(&end.to_le_bytes());
+    }
+    for s in strings {
+        body.extend_from_slice(s.as_bytes());
+    }
+    Ok(())
+}
+
+fn check_empty(body: &[u8]) -> Result<()> {
+    if body.is_empty() {
+        Ok(())
+    } else {
+        Err(VibratoError::invalid_format(
+            "features",
+            "Trailing bytes after the features.",
+        ))
+    }
+}
+
+fn unexpected_end() -> VibratoError {
+    VibratoError::invalid_format("features", "Unexpected end of the features.")
+}
+
+fn read_u32(body: &mut &[u8]) -> Result<u32> {
+    let Some((bytes, rest)) = body.split_first_chunk::<4>() else {
+        return Err(unexpected_end());
+    };
+    *body = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+/// `num`個のu32値を先頭から切り出します。
+fn split_u32s(body: &[u8], num: u32) -> Result<(&[u8], &[u8])> {
+    let len = usize::try_from(num)?
+        .checked_mul(4)
+        .filter(|&len| len <= body.len())
+        .ok_or_else(unexpected_end)?;
+    Ok(body.split_at(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(encoding: FeatureEncoding) {
+        let system = vec![
+            "名詞,地名".to_string(),
+            String::new(),
+            "動詞".to_string(),
+            "名詞,地名".to_string(),
+        ];
+        let user = vec!["動詞".to_string(), "カスタム".to_string()];
+
+        let mut data = b"dictionary data".to_vec();
+        write_trailer(&mut data, Some(&system), Some(&user), encoding).unwrap();
+        let (dict, body) = split_features(&data).unwrap();
+        assert_eq!(dict, b"dictionary data");
+        let pool = FeaturePool::parse(body.unwrap()).unwrap();
+        assert_eq!(pool.encoding(), encoding);
+        assert_eq!(pool.get(LexType::System, 0), Some("名詞,地名"));
+        assert_eq!(pool.get(LexType::System, 1), Some(""));
+        assert_eq!(pool.get(LexType::System, 2), Some("動詞"));
+        assert_eq!(pool.get(LexType::System, 3), Some("名詞,地名"));
+        assert_eq!(pool.get(LexType::System, 4), None);
+        assert_eq!(pool.get(LexType::User, 1), Some("カスタム"));
+        assert_eq!(pool.get(LexType::User, 2), None);
+        assert_eq!(pool.to_vecs(), (Some(system), Some(user)));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(FeatureEncoding::Plain);
+    }
+
+    #[test]
+    fn test_round_trip_dedup() {
+        round_trip(FeatureEncoding::Dedup);
+        #[cfg(feature = "loaders")]
+        round_trip(FeatureEncoding::Compressed(3));
+    }
+
+    #[test]
+    fn test_dedup_is_smaller() {
+        let system = vec!["名詞,一般,*,*,*,*".to_string(); 100];
+        let size = |encoding| {
+            let mut data = vec![];
+            write_trailer(&mut data, Some(&system), None, encoding).unwrap();
+            data.len()
+        };
+        assert!(size(FeatureEncoding::Dedup) < size(FeatureEncoding::Plain));
+    }
+
+    #[test]
+    fn test_invalid_body() {
+        let mut body = vec![];
+        write_lexicon(&mut body, Some(&["名詞".to_string()])).unwrap();
+        write_lexicon(&mut body, None).unwrap();
+        assert!(FeaturePool::parse(FeatureBody::Plain(&body)).is_ok());
+
+        // Splits a multi-byte character.
+        let mut broken = body.clone();
+        broken[4] = 1;
+        assert!(FeaturePool::parse(FeatureBody::Plain(&broken)).is_err());
+
+        assert!(FeaturePool::parse(FeatureBody::Plain(&body[..body.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn test_invalid_dedup_body() {
+        let mut body = vec![];
+        write_dedup(&mut body, Some(&["名詞".to_string()]), None, None).unwrap();
+        assert!(FeaturePool::parse(FeatureBody::Dedup(&body)).is_ok());
+
+        // Points to a feature out of range.
+        let mut broken = body.clone();
+        broken[12] = 1;
+        assert!(FeaturePool::parse(FeatureBody::Dedup(&broken)).is_err());
+    }
+
+    #[test]
+    fn test_without_features() {
+        let (dict, body) = split_features(b"dictionary data").unwrap();
+        assert_eq!(dict, b"dictionary data");
+        assert!(body.is_none());
+    }
+}
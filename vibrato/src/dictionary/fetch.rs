@@ -1,16 +1,119 @@
 //! プリセット辞書のダウンロード機能
 //!
 //! このモジュールは、プリセット辞書をダウンロードして検証する機能を提供します。
+//! [`DownloadOptions`]で、社内ミラーの使用、プロキシの指定、オフライン動作を設定できます。
 
 #![cfg(feature = "download")]
-use std::{fs::{self, File}, io::{self, Seek, SeekFrom}, path::{Path, PathBuf}};
+use std::{env, fs::{self, File}, io::{self, Seek, SeekFrom}, path::{Path, PathBuf}, time::Duration};
 
 use sha2::{Digest, Sha256};
 use tempfile::tempdir_in;
 use walkdir::WalkDir;
 use xz2::read::XzDecoder;
 
-use crate::{dictionary::{PresetDictionaryKind, config::FileType}, errors::DownloadError};
+use crate::{dictionary::{PresetDictionaryKind, config::FileType, preset::PresetManifest}, errors::DownloadError};
+
+/// オフラインモードを有効にする環境変数（`1`または`true`で有効）
+pub const ENV_OFFLINE: &str = "VIBRATO_RKYV_OFFLINE";
+
+/// ミラーのベースURLを指定する環境変数
+pub const ENV_MIRROR: &str = "VIBRATO_RKYV_MIRROR";
+
+/// プロキシのURLを指定する環境変数
+pub const ENV_PROXY: &str = "VIBRATO_RKYV_PROXY";
+
+/// プリセット辞書のダウンロード設定
+///
+/// デフォルトでは、GitHubのリリースページから直接ダウンロードします。
+/// [`DownloadOptions::from_env`]を使うと、環境変数から設定を読み込めます。
+#[derive(Clone, Debug, Default)]
+pub struct DownloadOptions {
+    offline: bool,
+    base_url: Option<String>,
+    proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+}
+
+impl DownloadOptions {
+    /// デフォルトの設定を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 環境変数から設定を作成します。
+    ///
+    /// 次の環境変数を参照します。
+    ///
+    /// - [`ENV_OFFLINE`]（`VIBRATO_RKYV_OFFLINE`）: `1`または`true`でオフラインモードを有効にします。
+    /// - [`ENV_MIRROR`]（`VIBRATO_RKYV_MIRROR`）: ミラーのベースURL
+    /// - [`ENV_PROXY`]（`VIBRATO_RKYV_PROXY`）: プロキシのURL
+    ///
+    /// `HTTPS_PROXY`などの標準的なプロキシの環境変数も、これまでどおり参照されます。
+    pub fn from_env() -> Self {
+        let var = |key| env::var(key).ok().filter(|v| !v.is_empty());
+        Self {
+            offline: var(ENV_OFFLINE)
+                .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+            base_url: var(ENV_MIRROR),
+            proxy: var(ENV_PROXY),
+            connect_timeout: None,
+        }
+    }
+
+    /// オフラインモードを設定します。
+    ///
+    /// オフラインモードでは、ネットワークに接続せず、保存先ディレクトリに
+    /// 検証済みの辞書がない場合は即座に[`DownloadError::Offline`]を返します。
+    pub const fn offline(mut self, yes: bool) -> Self {
+        self.offline = yes;
+        self
+    }
+
+    /// ダウンロード元のベースURLを設定します。
+    ///
+    /// 各プリセットのアーカイブは`<base_url>/<アーカイブのファイル名>`から取得されます。
+    /// ミラーにはリリースページと同じファイル名でアーカイブを配置してください。
+    /// チェックサムの検証は通常どおり行われます。
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// すべてのリクエストに使用するプロキシのURLを設定します。
+    pub fn proxy<S: Into<String>>(mut self, proxy: S) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 接続のタイムアウトを設定します。
+    pub const fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// アーカイブのダウンロードURLを決定します。
+    fn resolve_url(&self, download_url: &str) -> Result<String, DownloadError> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(download_url.to_string());
+        };
+        let file_name = download_url.rsplit('/').next().unwrap_or(download_url);
+        let url = format!("{}/{}", base_url.trim_end_matches('/'), file_name);
+        reqwest::Url::parse(&url).map_err(|_| DownloadError::InvalidUrl(url.clone()))?;
+        Ok(url)
+    }
+
+    /// 設定に従ってHTTPクライアントを作成します。
+    fn client(&self) -> Result<reqwest::blocking::Client, DownloadError> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        Ok(builder.build()?)
+    }
+}
 
 /// 辞書をダウンロードして指定されたディレクトリに保存します。
 ///
@@ -18,6 +121,7 @@ use crate::{dictionary::{PresetDictionaryKind, config::FileType}, errors::Downlo
 ///
 /// * `kind` - ダウンロードする辞書の種類
 /// * `dest_dir` - 保存先ディレクトリ
+/// * `options` - ダウンロード設定
 ///
 /// # 戻り値
 ///
@@ -25,8 +129,13 @@ use crate::{dictionary::{PresetDictionaryKind, config::FileType}, errors::Downlo
 ///
 /// # エラー
 ///
-/// ダウンロードや検証に失敗した場合にエラーを返します。
-pub(crate) fn download_dictionary<P: AsRef<Path>>(kind: PresetDictionaryKind, dest_dir: P) -> Result<PathBuf, DownloadError> {
+/// ダウンロードや検証に失敗した場合、またはオフラインモードで辞書が保存されていない場合に
+/// エラーを返します。
+pub(crate) fn download_dictionary<P: AsRef<Path>>(
+    kind: PresetDictionaryKind,
+    dest_dir: P,
+    options: &DownloadOptions,
+) -> Result<PathBuf, DownloadError> {
     let preset_meta = kind.meta();
     let dest_dir = dest_dir.as_ref();
 
@@ -44,6 +153,10 @@ pub(crate) fn download_dictionary<P: AsRef<Path>>(kind: PresetDictionaryKind, de
         }
     }
 
+    if options.offline {
+        return Err(DownloadError::Offline(preset_meta.name.to_string()));
+    }
+
     fs::create_dir_all(dest_dir)?;
 
     let archive_path = match preset_meta.file_type {
@@ -51,7 +164,8 @@ pub(crate) fn download_dictionary<P: AsRef<Path>>(kind: PresetDictionaryKind, de
         FileType::TarXz => dest_dir.join(format!("{}.tar.xz", preset_meta.name)),
     };
 
-    let mut response = reqwest::blocking::get(preset_meta.download_url)?;
+    let url = options.resolve_url(preset_meta.download_url)?;
+    let mut response = options.client()?.get(url).send()?;
     if !response.status().is_success() {
         return Err(DownloadError::HttpStatus(response.status()));
     }
@@ -104,3 +218,98 @@ pub(crate) fn download_dictionary<P: AsRef<Path>>(kind: PresetDictionaryKind, de
 
     Ok(dict_path)
 }
+
+/// マニフェストで定義されたプリセット辞書をダウンロードして指定されたディレクトリに保存します。
+///
+/// ダウンロードしたファイルは展開せずに`<sha256>-<ファイル名>`として保存されます。
+/// 同名のファイルが既に存在し、ハッシュが一致する場合はダウンロードを省略します。
+///
+/// # 引数
+///
+/// * `manifest` - ダウンロードする辞書のマニフェスト
+/// * `dest_dir` - 保存先ディレクトリ
+/// * `options` - ダウンロード設定
+///
+/// # 戻り値
+///
+/// 成功時はダウンロードされたファイルのパスを返します。
+///
+/// # エラー
+///
+/// ダウンロードや検証に失敗した場合、またはオフラインモードでファイルが保存されていない場合に
+/// エラーを返します。
+pub(crate) fn download_manifest<P: AsRef<Path>>(
+    manifest: &PresetManifest,
+    dest_dir: P,
+    options: &DownloadOptions,
+) -> Result<PathBuf, DownloadError> {
+    let dest_dir = dest_dir.as_ref();
+    let file_name = manifest.url.rsplit('/').next().unwrap_or(&manifest.name);
+    let dict_path = dest_dir.join(format!("{}-{}", manifest.sha256, file_name));
+
+    if dict_path.exists() {
+        let mut dict = File::open(&dict_path)?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut dict, &mut hasher)?;
+        if hex::encode(hasher.finalize()) == manifest.sha256 {
+            return Ok(dict_path);
+        }
+    }
+
+    if options.offline {
+        return Err(DownloadError::Offline(manifest.name.clone()));
+    }
+
+    fs::create_dir_all(dest_dir)?;
+
+    let url = options.resolve_url(&manifest.url)?;
+    let mut response = options.client()?.get(url).send()?;
+    if !response.status().is_success() {
+        return Err(DownloadError::HttpStatus(response.status()));
+    }
+
+    let mut temp_file = tempfile::NamedTempFile::new_in(dest_dir)?;
+    response.copy_to(&mut temp_file)?;
+
+    temp_file.seek(SeekFrom::Start(0))?;
+    let calculated_hash = {
+        let mut hasher = Sha256::new();
+        io::copy(&mut temp_file, &mut hasher)?;
+        hex::encode(hasher.finalize())
+    };
+
+    if calculated_hash != manifest.sha256 {
+        return Err(DownloadError::HashMismatch);
+    }
+
+    temp_file.persist(&dict_path)?;
+
+    Ok(dict_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_url() {
+        let url = "https://github.com/owner/repo/releases/download/v1.0.0/mecab-ipadic.tar";
+        assert_eq!(DownloadOptions::new().resolve_url(url).unwrap(), url);
+        assert_eq!(
+            DownloadOptions::new()
+                .base_url("https://mirror.example.com/vibrato/")
+                .resolve_url(url)
+                .unwrap(),
+            "https://mirror.example.com/vibrato/mecab-ipadic.tar",
+        );
+        assert!(DownloadOptions::new().base_url("not a url").resolve_url(url).is_err());
+    }
+
+    #[test]
+    fn test_offline_fails_fast() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = DownloadOptions::new().offline(true);
+        let err = download_dictionary(PresetDictionaryKind::Ipadic, dir.path(), &options);
+        assert!(matches!(err, Err(DownloadError::Offline(name)) if name == "mecab-ipadic"));
+    }
+}
@@ -0,0 +1,219 @@
+//! 辞書ファイルのヘッダ
+//!
+//! 辞書ファイルは[`MODEL_MAGIC`](super::MODEL_MAGIC)で始まり、rkyvのアライメントに
+//! 合わせるためのパディングが続きます。このパディング領域に、rkyvでシリアライズされた
+//! 辞書データの長さを記録します。
+//!
+//! ```text
+//! [MODEL_MAGIC][ヘッダのバージョン (u8)][辞書データ長 (u48 LE)][辞書データ][追記データ]
+//! ```
+//!
+//! 長さを記録していない古い辞書ファイルでは、パディングはすべて`0xFF`です。
+//! このようなファイルも引き続き読み込めますが、[`SectionHeader::check`]による検査は
+//! 限定的になります。
+
+use crate::dictionary::{ArchivedDictionaryInner, PADDING_LEN, RKYV_ALIGNMENT};
+use crate::errors::{Result, VibratoError};
+
+/// 長さを記録していないヘッダのパディング値
+const UNSIZED_PADDING: u8 = 0xFF;
+
+/// 現在のヘッダのバージョン
+const HEADER_VERSION: u8 = 1;
+
+/// 辞書データ長の格納に使用するバイト数
+const LEN_BYTES: usize = PADDING_LEN - 1;
+
+const _: () = assert!(LEN_BYTES >= 6, "the padding cannot hold the section sizes");
+
+/// 辞書ファイルのヘッダに記録された各セクションの大きさ
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SectionHeader {
+    /// rkyvでシリアライズされた辞書データの長さ。古い辞書ファイルでは`None`。
+    archive_len: Option<u64>,
+}
+
+impl SectionHeader {
+    /// 辞書データの長さを記録したヘッダを作成します。
+    ///
+    /// # エラー
+    ///
+    /// 長さがヘッダに収まらない場合に[`VibratoError`]を返します。
+    pub(crate) fn new(archive_len: usize) -> Result<Self> {
+        let archive_len = u64::try_from(archive_len)?;
+        if archive_len >> (LEN_BYTES * 8) != 0 {
+            return Err(VibratoError::invalid_argument(
+                "archive_len",
+                "The dictionary is too large to be recorded in the header.",
+            ));
+        }
+        Ok(Self { archive_len: Some(archive_len) })
+    }
+
+    /// パディング領域からヘッダを解析します。
+    ///
+    /// # エラー
+    ///
+    /// 未知のバージョンのヘッダである場合に[`VibratoError`]を返します。
+    pub(crate) fn parse(padding: &[u8]) -> Result<Self> {
+        if padding.iter().all(|&b| b == UNSIZED_PADDING) {
+            return Ok(Self::default());
+        }
+        if padding.len() != PADDING_LEN || padding[0] != HEADER_VERSION {
+            return Err(VibratoError::invalid_format(
+                "header",
+                "Unsupported dictionary header. The dictionary may be built by a newer version.",
+            ));
+        }
+        let mut len = [0; 8];
+        len[..LEN_BYTES].copy_from_slice(&padding[1..]);
+        Ok(Self { archive_len: Some(u64::from_le_bytes(len)) })
+    }
+
+    /// パディング領域に書き込むバイト列を返します。
+    pub(crate) fn to_bytes(self) -> [u8; PADDING_LEN] {
+        let mut bytes = [UNSIZED_PADDING; PADDING_LEN];
+        if let Some(len) = self.archive_len {
+            bytes[0] = HEADER_VERSION;
+            bytes[1..].copy_from_slice(&len.to_le_bytes()[..LEN_BYTES]);
+        }
+        bytes
+    }
+
+    /// 辞書データがヘッダの記録と矛盾しないことを検査します。
+    ///
+    /// rkyvによる検証とは異なり、データの中身は読まずに長さと配置のみを確認します。
+    /// 検証を省略して辞書データにアクセスする前に呼び出すことで、切り詰められた
+    /// ファイルによる範囲外アクセスをエラーに変換します。
+    ///
+    /// # 引数
+    ///
+    /// * `data` - マジックナンバーとパディングを除いた辞書ファイルの内容
+    /// * `archive` - `data`から追記データを切り離した辞書データ部分
+    ///
+    /// # エラー
+    ///
+    /// ファイルが切り詰められている場合や、辞書データの長さや配置が不正な場合に
+    /// [`VibratoError`]を返します。
+    pub(crate) fn check(&self, data: &[u8], archive: &[u8]) -> Result<()> {
+        if let Some(len) = self.archive_len {
+            if len > data.len() as u64 {
+                return Err(VibratoError::invalid_format(
+                    "header",
+                    format!(
+                        "The dictionary file is truncated: {len} bytes of data are expected, \
+                         but only {} bytes remain.",
+                        data.len()
+                    ),
+                ));
+            }
+            if len != archive.len() as u64 {
+                return Err(VibratoError::invalid_format(
+                    "header",
+                    format!(
+                        "The dictionary data is {} bytes, but the header records {len} bytes.",
+                        archive.len()
+                    ),
+                ));
+            }
+        }
+        if archive.len() < size_of::<ArchivedDictionaryInner>() {
+            return Err(VibratoError::invalid_format(
+                "header",
+                "Dictionary file too small or corrupted.",
+            ));
+        }
+        if archive.as_ptr() as usize % RKYV_ALIGNMENT != 0 {
+            return Err(VibratoError::invalid_format(
+                "header",
+                "The dictionary data is not aligned.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rkyv::util::AlignedVec;
+
+    #[test]
+    fn test_round_trip() {
+        let header = SectionHeader::new(0x1234_5678_9a).unwrap();
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[0], HEADER_VERSION);
+        assert_eq!(SectionHeader::parse(&bytes).unwrap(), header);
+
+        let unsized_header = SectionHeader::default();
+        assert_eq!(unsized_header.to_bytes(), [0xFF; PADDING_LEN]);
+        assert_eq!(SectionHeader::parse(&[0xFF; PADDING_LEN]).unwrap(), unsized_header);
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let mut bytes = SectionHeader::new(16).unwrap().to_bytes();
+        bytes[0] = HEADER_VERSION + 1;
+        assert!(SectionHeader::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_too_large() {
+        assert!(SectionHeader::new(1 << (LEN_BYTES * 8)).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let len = size_of::<ArchivedDictionaryInner>();
+        let mut data = AlignedVec::<16>::new();
+        data.extend_from_slice(&vec![0; len + 8]);
+        let header = SectionHeader::new(len).unwrap();
+
+        assert!(header.check(&data, &data[..len]).is_ok());
+        // Truncated in the middle of the trailers.
+        assert!(header.check(&data[..len - 1], &data[..len - 1]).is_err());
+        // Truncated so that the trailers are lost.
+        assert!(header.check(&data[..len + 4], &data[..len + 4]).is_err());
+
+        let unsized_header = SectionHeader::default();
+        assert!(unsized_header.check(&data, &data[..len]).is_ok());
+        assert!(unsized_header.check(&data[..len - 1], &data[..len - 1]).is_err());
+    }
+
+    #[cfg(feature = "loaders")]
+    #[test]
+    fn test_trust_cache_truncated() {
+        use std::fs::{self, OpenOptions};
+
+        use crate::dictionary::{
+            DATA_START, Dictionary, LoadMode, MODEL_MAGIC_LEN, SystemDictionaryBuilder, cache,
+            compute_metadata_hash,
+        };
+
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.dic");
+        dict.write(fs::File::create(&path).unwrap()).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let header = SectionHeader::parse(&bytes[MODEL_MAGIC_LEN..DATA_START]).unwrap();
+        assert!(header.archive_len.is_some());
+
+        // Create a proof file for the truncated dictionary so that the validation is skipped.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(bytes.len() as u64 - 16).unwrap();
+        let proof_dir = dir.path().join(".cache");
+        fs::create_dir_all(&proof_dir).unwrap();
+        let hash = compute_metadata_hash(&file.metadata().unwrap());
+        cache::create_proof(&proof_dir.join(format!("{hash}.sha256"))).unwrap();
+
+        assert!(Dictionary::from_path(&path, LoadMode::TrustCache).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! 辞書の整合性検査
+//!
+//! このモジュールは、[`Dictionary::check_integrity`](crate::Dictionary::check_integrity)
+//! が返す検査結果を定義します。辞書の配布前や読み込み後に、語彙と未知語の定義が
+//! 接続表と整合していることを確認するために使用します。
+
+use crate::dictionary::word_idx::WordIdx;
+use crate::dictionary::{DictionaryInnerRef, LexType};
+
+/// 接続表の範囲外の接続IDを参照している単語。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidWord {
+    /// 単語のインデックス。
+    pub word_idx: WordIdx,
+    /// 単語の左接続ID。
+    pub left_id: u16,
+    /// 単語の右接続ID。
+    pub right_id: u16,
+}
+
+/// 辞書の整合性検査の結果。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// 接続表の左接続IDの数。
+    pub num_left_ids: usize,
+    /// 接続表の右接続IDの数。
+    pub num_right_ids: usize,
+    /// システム辞書の単語数。
+    pub num_system_words: usize,
+    /// ユーザー辞書の単語数の合計。
+    pub num_user_words: usize,
+    /// 未知語の定義の数。
+    pub num_unk_words: usize,
+    /// 接続表の範囲外の接続IDを参照している単語と未知語の定義。
+    pub invalid_words: Vec<InvalidWord>,
+    /// 接続表に定義されているが、BOS/EOSとどの単語からも参照されない左接続ID。
+    ///
+    /// ラティス上で到達できない行であり、誤りではありませんが、接続IDの割り当ての
+    /// 誤りの兆候である場合があります。
+    pub unused_left_ids: Vec<u16>,
+    /// 接続表に定義されているが、BOS/EOSとどの単語からも参照されない右接続ID。
+    pub unused_right_ids: Vec<u16>,
+}
+
+impl IntegrityReport {
+    /// 接続表がBOS/EOSの接続IDを含むかを返します。
+    ///
+    /// 含まない場合、文頭と文末のノードは他のノードと接続できません。
+    pub const fn has_bos_eos(&self) -> bool {
+        self.num_left_ids != 0 && self.num_right_ids != 0
+    }
+
+    /// 辞書が整合しているかを返します。
+    ///
+    /// 未使用の接続IDは誤りとして扱いません。
+    pub fn is_ok(&self) -> bool {
+        self.has_bos_eos() && self.invalid_words.is_empty()
+    }
+}
+
+/// 辞書の整合性を検査します。
+pub(crate) fn check(dict: DictionaryInnerRef<'_>, num_left_ids: usize, num_right_ids: usize) -> IntegrityReport {
+    let (num_system_words, num_unk_words) = match &dict {
+        DictionaryInnerRef::Archived(dict) => {
+            (dict.system_lexicon().num_words(), dict.unk_handler().len())
+        }
+        DictionaryInnerRef::Owned(dict) => {
+            (dict.system_lexicon().num_words(), dict.unk_handler().len())
+        }
+    };
+    let num_user_words = dict.num_user_words().unwrap_or(0);
+
+    let mut report = IntegrityReport {
+        num_left_ids,
+        num_right_ids,
+        num_system_words,
+        num_user_words,
+        num_unk_words,
+        ..Default::default()
+    };
+
+    // The BOS/EOS connection ID is always referenced.
+    let mut used_left = vec![false; num_left_ids];
+    let mut used_right = vec![false; num_right_ids];
+    if let Some(used) = used_left.first_mut() {
+        *used = true;
+    }
+    if let Some(used) = used_right.first_mut() {
+        *used = true;
+    }
+
+    let words = [
+        (LexType::System, num_system_words),
+        (LexType::User, num_user_words),
+        (LexType::Unknown, num_unk_words),
+    ];
+    for (lex_type, num_words) in words {
+        for word_id in 0..num_words {
+            let word_idx = WordIdx::new(lex_type, u32::try_from(word_id).unwrap());
+            let param = dict.word_param(word_idx);
+            let left = used_left.get_mut(usize::from(param.left_id));
+            let right = used_right.get_mut(usize::from(param.right_id));
+            match (left, right) {
+                (Some(left), Some(right)) => {
+                    *left = true;
+                    *right = true;
+                }
+                _ => report.invalid_words.push(InvalidWord {
+                    word_idx,
+                    left_id: param.left_id,
+                    right_id: param.right_id,
+                }),
+            }
+        }
+    }
+
+    let unused = |used: Vec<bool>| {
+        used.into_iter()
+            .enumerate()
+            .filter(|&(_, used)| !used)
+            .map(|(id, _)| u16::try_from(id).unwrap())
+            .collect()
+    };
+    report.unused_left_ids = unused(used_left);
+    report.unused_right_ids = unused(used_right);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dictionary::SystemDictionaryBuilder;
+    use crate::Dictionary;
+
+    #[test]
+    fn test_check_integrity() {
+        let lexicon_csv = "\
+自然,1,1,1,名詞
+言語,1,2,1,名詞";
+        let dict = SystemDictionaryBuilder::from_readers(
+            lexicon_csv.as_bytes(),
+            "4 3\n0 0 0\n0 1 0\n0 2 0\n1 0 0\n1 1 0\n1 2 0\n2 0 0\n2 1 0\n2 2 0\n3 0 0\n3 1 0\n3 2 0"
+                .as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,1,1,100,未知".as_bytes(),
+        )
+        .unwrap();
+        let dict = Dictionary::from_inner(dict);
+
+        let report = dict.check_integrity();
+        assert!(report.is_ok());
+        assert_eq!((report.num_left_ids, report.num_right_ids), (3, 4));
+        assert_eq!(report.num_system_words, 2);
+        assert_eq!(report.num_user_words, 0);
+        assert_eq!(report.num_unk_words, 1);
+        assert!(report.invalid_words.is_empty());
+        assert_eq!(report.unused_left_ids, vec![2]);
+        assert_eq!(report.unused_right_ids, vec![3]);
+    }
+}
@@ -10,9 +10,10 @@ mod param;
 use std::io::Read;
 
 use csv_core::ReadFieldResult;
+use rkyv::with::Skip;
 use rkyv::{Archive, Deserialize, Serialize};
 
-use crate::dictionary::connector::Connector;
+use crate::dictionary::connector::ConnectorView;
 use crate::dictionary::lexicon::feature::WordFeatures;
 use crate::dictionary::lexicon::map::WordMap;
 use crate::dictionary::lexicon::param::WordParams;
@@ -31,6 +32,14 @@ pub struct Lexicon {
     params: WordParams,
     features: WordFeatures,
     lex_type: LexType,
+    // Surfaces are kept out of the archive to preserve the dictionary format and are
+    // written as a trailer instead. See `dictionary::surface`.
+    #[rkyv(with = Skip)]
+    surfaces: Vec<String>,
+    // Features moved out of the archive by `detach_features()`. They are written as a trailer
+    // instead. See `dictionary::feature_pool`.
+    #[rkyv(with = Skip)]
+    detached_features: Option<WordFeatures>,
 }
 
 impl Lexicon {
@@ -44,7 +53,7 @@ impl Lexicon {
     ///
     /// 一致する単語のイテレータ
     #[inline(always)]
-    pub fn common_prefix_iterator<'a>(
+    pub(crate) fn common_prefix_iterator<'a>(
         &'a self,
         input: &'a [char],
     ) -> impl Iterator<Item = LexMatch> + 'a {
@@ -65,7 +74,7 @@ impl Lexicon {
     ///
     /// `Dictionary` のメンバー間で接続IDマッピングの一貫性を保つため、
     /// この関数は公開しないでください。一貫性は `Dictionary` で管理されます。
-    pub fn map_connection_ids(&mut self, mapper: &ConnIdMapper) {
+    pub(crate) fn map_connection_ids(&mut self, mapper: &ConnIdMapper) {
         self.params.map_connection_ids(mapper);
     }
 
@@ -79,7 +88,7 @@ impl Lexicon {
     ///
     /// 単語パラメータ
     #[inline(always)]
-    pub fn word_param(&self, word_idx: WordIdx) -> WordParam {
+    pub(crate) fn word_param(&self, word_idx: WordIdx) -> WordParam {
         debug_assert_eq!(word_idx.lex_type, self.lex_type);
         self.params.get(usize::from_u32(word_idx.word_id))
     }
@@ -96,7 +105,83 @@ impl Lexicon {
     #[inline(always)]
     pub fn word_feature(&self, word_idx: WordIdx) -> &str {
         debug_assert_eq!(word_idx.lex_type, self.lex_type);
-        self.features.get(usize::from_u32(word_idx.word_id))
+        self.detached_features
+            .as_ref()
+            .unwrap_or(&self.features)
+            .get(usize::from_u32(word_idx.word_id))
+    }
+
+    /// 素性を辞書データから切り離します。
+    ///
+    /// 切り離された素性はシリアライズ時に辞書データに含まれず、
+    /// トレーラーとして書き込まれます。
+    pub(crate) fn detach_features(&mut self) {
+        if self.detached_features.is_none() {
+            self.detached_features = Some(std::mem::take(&mut self.features));
+        }
+    }
+
+    /// 各単語の素性を、先頭から指定された数の項目のみに切り詰めます。
+    pub(crate) fn truncate_features(&mut self, num_fields: usize) {
+        self.features.truncate(num_fields);
+        if let Some(features) = self.detached_features.as_mut() {
+            features.truncate(num_fields);
+        }
+    }
+
+    /// 辞書データから切り離された素性を取得します。
+    ///
+    /// 切り離されていない場合は`None`を返します。
+    #[inline(always)]
+    pub(crate) fn detached_features(&self) -> Option<&[String]> {
+        self.detached_features.as_ref().map(WordFeatures::as_slice)
+    }
+
+    /// 辞書ファイルのトレーラーから読み込まれた素性を設定します。
+    ///
+    /// 単語数と一致しない場合は無視されます。
+    pub(crate) fn set_detached_features(&mut self, features: Vec<String>) {
+        if features.len() == self.num_words() {
+            self.detached_features = Some(WordFeatures::new(features));
+        }
+    }
+
+    /// 単語数を取得します。
+    #[inline(always)]
+    pub(crate) fn num_words(&self) -> usize {
+        self.params.len()
+    }
+
+    /// 単語の表層形を取得します。
+    ///
+    /// 表層形を持たない場合は`None`を返します。
+    #[inline(always)]
+    pub(crate) fn word_surface(&self, word_idx: WordIdx) -> Option<&str> {
+        debug_assert_eq!(word_idx.lex_type, self.lex_type);
+        self.surfaces.get(usize::from_u32(word_idx.word_id)).map(String::as_str)
+    }
+
+    /// すべての単語の表層形を取得します。
+    ///
+    /// 表層形を持たない場合は`None`を返します。
+    #[inline(always)]
+    pub(crate) fn surfaces(&self) -> Option<&[String]> {
+        (!self.surfaces.is_empty() || self.num_words() == 0).then_some(self.surfaces.as_slice())
+    }
+
+    /// 表層形を設定します。
+    ///
+    /// 単語数と一致しない場合は無視されます。
+    pub(crate) fn set_surfaces(&mut self, surfaces: Vec<String>) {
+        if surfaces.len() == self.num_words() {
+            self.surfaces = surfaces;
+        }
+    }
+
+    /// 辞書の種類を取得します。
+    #[inline(always)]
+    pub(crate) const fn lex_type(&self) -> LexType {
+        self.lex_type
     }
 
     /// 左右IDがコネクターで有効かどうかをチェックします。
@@ -108,9 +193,9 @@ impl Lexicon {
     /// # 戻り値
     ///
     /// すべてのIDが有効な場合は `true`
-    pub fn verify<C>(&self, conn: &C) -> bool
+    pub(crate) fn verify<C>(&self, conn: &C) -> bool
     where
-        C: Connector,
+        C: ConnectorView,
     {
         for i in 0..self.params.len() {
             let p = self.params.get(i);
@@ -138,16 +223,49 @@ impl Lexicon {
     /// # エラー
     ///
     /// 構築に失敗した場合にエラーを返します。
-    pub fn from_entries(entries: &[RawWordEntry], lex_type: LexType) -> Result<Self> {
-        let map = WordMap::new(entries.iter().map(|e| &e.surface))?;
-        let params = WordParams::new(entries.iter().map(|e| e.param));
-        let features = WordFeatures::new(entries.iter().map(|e| &e.feature));
+    pub(crate) fn from_entries(entries: &[RawWordEntry], lex_type: LexType) -> Result<Self> {
+        Self::from_entries_with_threads(entries, lex_type, 1)
+    }
+
+    /// エントリのリストから、指定されたスレッド数で新しいインスタンスを構築します。
+    ///
+    /// 複数のスレッドを使用する場合、トライの構築と素性などの格納を並行して行い、
+    /// トライの構築に使用する単語の整列も並行して行います。
+    ///
+    /// # 引数
+    ///
+    /// * `entries` - 単語エントリのスライス
+    /// * `lex_type` - 辞書の種類
+    /// * `num_threads` - 使用するスレッド数
+    ///
+    /// # エラー
+    ///
+    /// 構築に失敗した場合にエラーを返します。
+    pub(crate) fn from_entries_with_threads(
+        entries: &[RawWordEntry],
+        lex_type: LexType,
+        num_threads: usize,
+    ) -> Result<Self> {
+        let (map, (params, features, surfaces)) = crate::utils::join(
+            num_threads > 1,
+            || WordMap::with_threads(entries.iter().map(|e| &e.surface), num_threads),
+            || {
+                (
+                    WordParams::new(entries.iter().map(|e| e.param)),
+                    WordFeatures::new(entries.iter().map(|e| &e.feature)),
+                    entries.iter().map(|e| e.surface.clone()).collect::<Vec<_>>(),
+                )
+            },
+        );
+        let map = map?;
 
         Ok(Self {
             map,
             params,
             features,
             lex_type,
+            surfaces,
+            detached_features: None,
         })
     }
 
@@ -246,8 +364,8 @@ impl Lexicon {
                 }
                 let feature = std::str::from_utf8(&features_bytes[..features_len - 1])?;
                 if surface.is_empty() {
-                    eprintln!(
-                        "Skipped an empty surface, {:?}",
+                    log::warn!(
+                        "[vibrato-rkyv] Skipped an empty surface in {name}: {:?}",
                         std::str::from_utf8(&record_bytes[..record_end_pos])?,
                     );
                 } else {
@@ -308,7 +426,7 @@ impl ArchivedLexicon {
     ///
     /// 一致する単語のイテレータ
     #[inline(always)]
-    pub fn common_prefix_iterator<'a>(
+    pub(crate) fn common_prefix_iterator<'a>(
         &'a self,
         input: &'a [char],
     ) -> impl Iterator<Item = LexMatch> + 'a {
@@ -325,20 +443,73 @@ impl ArchivedLexicon {
 
     /// 単語のパラメータを取得します（アーカイブ版）。
     #[inline(always)]
-    pub fn word_param(&self, word_idx: WordIdx) -> WordParam {
+    pub(crate) fn word_param(&self, word_idx: WordIdx) -> WordParam {
         debug_assert_eq!(word_idx.lex_type, self.lex_type);
         self.params.get(usize::from_u32(word_idx.word_id))
     }
 
+    /// 単語数を取得します（アーカイブ版）。
+    #[inline(always)]
+    pub(crate) fn num_words(&self) -> usize {
+        self.params.len()
+    }
+
     /// 単語の素性を取得します（アーカイブ版）。
     #[inline(always)]
     pub fn word_feature(&self, word_idx: WordIdx) -> &str {
         debug_assert_eq!(word_idx.lex_type, self.lex_type);
         self.features.get(usize::from_u32(word_idx.word_id))
     }
+
+    /// 辞書の種類を取得します（アーカイブ版）。
+    #[inline(always)]
+    pub(crate) fn lex_type(&self) -> LexType {
+        self.lex_type.to_native()
+    }
+
+    /// 左右IDがコネクターで有効かどうかをチェックします（アーカイブ版）。
+    ///
+    /// # 引数
+    ///
+    /// * `conn` - コネクター
+    ///
+    /// # 戻り値
+    ///
+    /// すべてのIDが有効な場合は `true`
+    pub(crate) fn verify<C>(&self, conn: &C) -> bool
+    where
+        C: ConnectorView,
+    {
+        (0..self.params.len()).all(|i| {
+            let p = self.params.get(i);
+            usize::from(p.left_id) < conn.num_left() && usize::from(p.right_id) < conn.num_right()
+        })
+    }
 }
 
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::Lexicon> for Lexicon {
+    fn from(lexicon: crate::legacy::dictionary::lexicon::Lexicon) -> Self {
+        let crate::legacy::dictionary::lexicon::Lexicon {
+            map,
+            params,
+            features,
+            lex_type,
+        } = lexicon;
+        Self {
+            map: map.into(),
+            params: params.into(),
+            features: features.into(),
+            lex_type: lex_type.into(),
+            // The legacy format does not store surfaces.
+            surfaces: vec![],
+            detached_features: None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -355,6 +526,8 @@ mod tests {
             ]),
             features: WordFeatures::default(),
             lex_type: LexType::System,
+            surfaces: vec![],
+            detached_features: None,
         };
         let input: Vec<_> = "東京都".chars().collect();
         let mut it = lexicon.common_prefix_iterator(&input);
@@ -385,6 +558,20 @@ mod tests {
         assert_eq!(it.next(), None);
     }
 
+    #[test]
+    fn test_word_map_with_threads() {
+        let words: Vec<String> = (0..1000).map(|i| format!("{}語", i % 37)).collect();
+        let single = WordMap::new(&words).unwrap();
+        let multi = WordMap::with_threads(&words, 4).unwrap();
+        for i in 0..37 {
+            let input: Vec<_> = format!("{i}語").chars().collect();
+            assert_eq!(
+                single.common_prefix_iterator(&input).collect::<Vec<_>>(),
+                multi.common_prefix_iterator(&input).collect::<Vec<_>>(),
+            );
+        }
+    }
+
     #[test]
     fn test_from_reader_system() {
         let data = "自然,0,2,1,sizen\n言語,1,0,-4,gengo,げんご";
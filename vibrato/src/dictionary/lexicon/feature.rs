@@ -29,6 +29,20 @@ impl WordFeatures {
     pub fn get(&self, word_id: usize) -> &str {
         &self.features[word_id]
     }
+
+    /// すべての素性を取得します。
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[String] {
+        &self.features
+    }
+
+    /// 各素性を、先頭から指定された数の項目のみに切り詰めます。
+    pub(crate) fn truncate(&mut self, num_fields: usize) {
+        for feature in &mut self.features {
+            let len = crate::utils::truncate_csv_row(feature, num_fields).len();
+            feature.truncate(len);
+        }
+    }
 }
 
 impl ArchivedWordFeatures {
@@ -38,3 +52,11 @@ impl ArchivedWordFeatures {
         &self.features[word_id]
     }
 }
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::feature::WordFeatures> for WordFeatures {
+    fn from(features: crate::legacy::dictionary::lexicon::feature::WordFeatures) -> Self {
+        let crate::legacy::dictionary::lexicon::feature::WordFeatures { features } = features;
+        Self { features }
+    }
+}
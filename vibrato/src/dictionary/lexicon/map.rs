@@ -6,7 +6,6 @@
 pub mod posting;
 pub mod trie;
 
-use std::collections::BTreeMap;
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::dictionary::lexicon::map::posting::{Postings, PostingsBuilder};
@@ -24,6 +23,18 @@ pub struct WordMap {
 impl WordMap {
     /// 単語のイテレータから新しいインスタンスを作成します。
     pub fn new<I, W>(words: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = W>,
+        W: AsRef<str>,
+    {
+        Self::with_threads(words, 1)
+    }
+
+    /// 単語のイテレータから、指定されたスレッド数で新しいインスタンスを作成します。
+    ///
+    /// 単語の整列を`num_threads`個のまとまりに分けて並行に行います。
+    /// 構築される単語マップはスレッド数に依存しません。
+    pub fn with_threads<I, W>(words: I, num_threads: usize) -> Result<Self>
     where
         I: IntoIterator<Item = W>,
         W: AsRef<str>,
@@ -32,7 +43,7 @@ impl WordMap {
         for (i, w) in words.into_iter().enumerate() {
             b.add_record(w.as_ref().to_string(), u32::try_from(i)?);
         }
-        b.build()
+        b.build_with_threads(num_threads)
     }
 
     #[inline(always)]
@@ -51,7 +62,7 @@ impl WordMap {
 /// 単語マップを構築するビルダー
 #[derive(Default)]
 pub struct WordMapBuilder {
-    map: BTreeMap<String, Vec<u32>>,
+    records: Vec<(String, u32)>,
 }
 
 impl WordMapBuilder {
@@ -62,15 +73,23 @@ impl WordMapBuilder {
 
     #[inline(always)]
     pub fn add_record(&mut self, word: String, id: u32) {
-        self.map.entry(word).or_default().push(id);
+        self.records.push((word, id));
     }
 
     pub fn build(self) -> Result<WordMap> {
+        self.build_with_threads(1)
+    }
+
+    /// 指定されたスレッド数で単語を整列し、単語マップを構築します。
+    pub fn build_with_threads(mut self, num_threads: usize) -> Result<WordMap> {
+        sort_records(&mut self.records, num_threads);
+
         let mut entries = vec![];
         let mut builder = PostingsBuilder::new();
-        for (word, ids) in self.map {
+        for group in self.records.chunk_by(|(a, _), (b, _)| a == b) {
+            let ids: Vec<u32> = group.iter().map(|&(_, id)| id).collect();
             let offset = builder.push(&ids)?;
-            entries.push((word, u32::try_from(offset)?));
+            entries.push((group[0].0.as_str(), u32::try_from(offset)?));
         }
         Ok(WordMap {
             trie: Trie::from_records(&entries)?,
@@ -79,6 +98,24 @@ impl WordMapBuilder {
     }
 }
 
+/// レコードを単語順に安定に整列します。
+///
+/// 同じ単語のレコードは追加された順に並ぶため、単語IDの並びはスレッド数に依存しません。
+/// 複数のスレッドを使用する場合は、まとまりごとに並行して整列した後、
+/// 整列済みの区間を利用する安定ソートで全体を併合します。
+fn sort_records(records: &mut [(String, u32)], num_threads: usize) {
+    let compare = |a: &(String, u32), b: &(String, u32)| a.0.cmp(&b.0);
+    if num_threads > 1 && records.len() >= num_threads * 2 {
+        let chunk_size = records.len().div_ceil(num_threads);
+        std::thread::scope(|s| {
+            for chunk in records.chunks_mut(chunk_size) {
+                s.spawn(move || chunk.sort_by(compare));
+            }
+        });
+    }
+    records.sort_by(compare);
+}
+
 impl ArchivedWordMap {
     #[inline(always)]
     pub fn common_prefix_iterator<'a>(
@@ -92,3 +129,14 @@ impl ArchivedWordMap {
         })
     }
 }
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::map::WordMap> for WordMap {
+    fn from(map: crate::legacy::dictionary::lexicon::map::WordMap) -> Self {
+        let crate::legacy::dictionary::lexicon::map::WordMap { trie, postings } = map;
+        Self {
+            trie: trie.into(),
+            postings: postings.into(),
+        }
+    }
+}
@@ -63,4 +63,12 @@ impl ArchivedPostings {
         let len = usize::from_u32(self.data[i].to_native());
         self.data[i + 1..i + 1 + len].iter().cloned()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::map::posting::Postings> for Postings {
+    fn from(postings: crate::legacy::dictionary::lexicon::map::posting::Postings) -> Self {
+        let crate::legacy::dictionary::lexicon::map::posting::Postings { data } = postings;
+        Self { data }
+    }
+}
@@ -62,3 +62,13 @@ impl ArchivedTrie {
             .map(move |(value, end_char)| TrieMatch::new(value, end_char))
     }
 }
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::map::trie::Trie> for Trie {
+    fn from(trie: crate::legacy::dictionary::lexicon::map::trie::Trie) -> Self {
+        // crawdad-rkyv is a fork of crawdad 0.4 and keeps its serialization format.
+        let bytes = trie.da.serialize_to_vec();
+        let (da, _) = crawdad_rkyv::Trie::deserialize_from_slice(&bytes);
+        Self { da }
+    }
+}
@@ -81,4 +81,22 @@ impl ArchivedWordParams {
     pub fn get(&self, word_id: usize) -> WordParam {
         self.params[word_id].to_native()
     }
-}
\ No newline at end of file
+
+    /// パラメータの数を取得します（アーカイブ版）。
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+}
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::lexicon::param::WordParams> for WordParams {
+    fn from(params: crate::legacy::dictionary::lexicon::param::WordParams) -> Self {
+        let crate::legacy::dictionary::lexicon::param::WordParams { params } = params;
+        Self::new(
+            params
+                .into_iter()
+                .map(|p| WordParam::new(p.left_id, p.right_id, p.word_cost)),
+        )
+    }
+}
@@ -0,0 +1,1041 @@
+//! 圧縮辞書の読み込み、展開キャッシュ、ダウンロードのためのモジュール。
+//!
+//! このモジュールは`loaders`フィーチャーが有効な場合にのみコンパイルされます。
+//! 無効にすると、zstdやSHA-256などの依存関係なしに、非圧縮の辞書を
+//! [`Dictionary::from_path`]や[`Dictionary::read`]で読み込むトークナイザーのみが
+//! 残ります。組み込み環境などで依存関係を最小限にしたい場合に使用します。
+
+#![cfg(feature = "loaders")]
+
+use std::fs::{self, File, Metadata, create_dir_all};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, OnceLock};
+
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+use rkyv::access;
+use sha2::{Digest, Sha256};
+
+use crate::dictionary::{
+    cache, codec, compression, metadata, ArchivedDictionary, ArchivedDictionaryInner, CompressedHeader,
+    DictBuffer, Dictionary, DictionaryInner, LoadMode, WriteOptions, DATA_START,
+    LEGACY_MODEL_MAGIC_PREFIX, MODEL_MAGIC, MODEL_MAGIC_LEN, ProofLocation,
+};
+use crate::errors::{Result, VibratoError};
+
+#[cfg(feature = "download")]
+use crate::dictionary::{
+    fetch, registered_preset, DownloadOptions, PresetDictionaryKind, PresetManifest,
+};
+
+/// グローバルキャッシュディレクトリのパス。
+///
+/// ユーザー固有のシステムキャッシュディレクトリ内の`vibrato-rkyv`サブディレクトリを指します。
+/// 各プラットフォームでの標準的なキャッシュディレクトリ:
+/// - Linux: `$XDG_CACHE_HOME/vibrato-rkyv` または `$HOME/.cache/vibrato-rkyv`
+/// - macOS: `$HOME/Library/Caches/vibrato-rkyv`
+/// - Windows: `{FOLDERID_LocalAppData}/vibrato-rkyv`
+pub static GLOBAL_CACHE_DIR: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let path = dirs::cache_dir()?.join("vibrato-rkyv");
+    fs::create_dir_all(&path).ok()?;
+
+    Some(path)
+});
+
+/// グローバルデータディレクトリのパス。
+///
+/// ユーザー固有のローカルデータディレクトリ内の`vibrato-rkyv`サブディレクトリを指します。
+/// 各プラットフォームでの標準的なデータディレクトリ:
+/// - Linux: `$XDG_DATA_HOME/vibrato-rkyv` または `$HOME/.local/share/vibrato-rkyv`
+/// - macOS: `$HOME/Library/Application Support/vibrato-rkyv`
+/// - Windows: `{FOLDERID_LocalAppData}/vibrato-rkyv`
+pub static GLOBAL_DATA_DIR: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let path = dirs::data_local_dir()?.join("vibrato-rkyv");
+    fs::create_dir_all(&path).ok()?;
+
+    Some(path)
+});
+
+/// Zstandardアーカイブから展開された辞書のキャッシング戦略を指定します。
+///
+/// 辞書ファイルが圧縮されている場合、展開後のデータをどこにキャッシュするかを制御します。
+/// キャッシュされたファイルは[`cache`]モジュールの関数で一覧・削除できます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStrategy {
+    /// 圧縮辞書と同じディレクトリに`.cache`サブディレクトリを作成します。
+    ///
+    /// この戦略は、キャッシュデータを元のファイルと並べて保持します。
+    /// 親ディレクトリが書き込み可能でない場合は失敗します。
+    Local,
+
+    /// オペレーティングシステムに適した、共有のユーザー固有キャッシュディレクトリを使用します。
+    ///
+    /// ほとんどのアプリケーションに適したデフォルトの選択肢です。
+    /// 特に辞書ファイルが読み取り専用の場所に保存されている場合に有用です。
+    /// パスは`dirs::cache_dir()`によって決定されます。
+    ///
+    /// | プラットフォーム | 値                             | 例                               |
+    /// | -------- | --------------------------------- | ------------------------------------- |
+    /// | Linux    | `$XDG_CACHE_HOME` または `$HOME/.cache` | `/home/alice/.cache`                  |
+    /// | macOS    | `$HOME/Library/Caches`            | `/Users/Alice/Library/Caches`         |
+    /// | Windows  | `{FOLDERID_LocalAppData}`         | `C:\Users\Alice\AppData\Local`        |
+    ///
+    GlobalCache,
+
+    /// オペレーティングシステムに適した、共有のユーザー固有データディレクトリを使用します。
+    ///
+    /// `GlobalCache`に似ていますが、永続的で非ローミングのアプリケーションデータ用の
+    /// ディレクトリを使用します。パスは`dirs::data_local_dir()`によって決定されます。
+    ///
+    /// | プラットフォーム | 値                                     | 例                               |
+    /// | -------- | ----------------------------------------- | ------------------------------------- |
+    /// | Linux    | `$XDG_DATA_HOME` または `$HOME/.local/share`  | `/home/alice/.local/share`            |
+    /// | macOS    | `$HOME/Library/Application Support`       | `/Users/Alice/Library/Application Support` |
+    /// | Windows  | `{FOLDERID_LocalAppData}`                 | `C:\Users\Alice\AppData\Local`        |
+    ///
+    GlobalData,
+}
+
+impl ArchivedDictionary {
+    /// シリアライズされた辞書全体のSHA-256ダイジェストを計算します。
+    fn compute_content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        // Reading from memory and writing to a hasher never fail.
+        io::copy(&mut self.file_reader(), &mut hasher).unwrap();
+        hex::encode(hasher.finalize())
+    }
+
+    /// 読み込み元の辞書ファイルと同じバイト列を返すリーダーを作成します。
+    fn file_reader(&self) -> Box<dyn Read + '_> {
+        match &self._buffer {
+            DictBuffer::Mmap(mmap) => Box::new(&mmap[..]),
+            DictBuffer::AlignedFile(bytes) => Box::new(&bytes[..]),
+            DictBuffer::Static(bytes) => Box::new(*bytes),
+            DictBuffer::Aligned(bytes, header) => Box::new(
+                MODEL_MAGIC
+                    .chain(io::Cursor::new(header.to_bytes()))
+                    .chain(&bytes[..]),
+            ),
+        }
+    }
+}
+
+impl Dictionary {
+    /// 辞書をzstdで圧縮して書き出します。
+    ///
+    /// 所有型の辞書は[`write`](Self::write)と同じ形式にシリアライズしてから圧縮します。
+    /// ファイルから読み込んだ辞書は、埋め込まれたメタデータを含め、読み込み元の
+    /// 辞書ファイルと同じ内容を圧縮します。出力は[`Dictionary::from_zstd()`]で読み込めます。
+    ///
+    /// 出力の先頭には、メタデータとダイジェストを記録した[`CompressedHeader`]が置かれます。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `options` - 圧縮レベルやワーカースレッド数などのオプション
+    ///
+    /// # エラー
+    ///
+    /// 圧縮レベルが範囲外の場合や、書き込みや圧縮に失敗した場合に[`VibratoError`]を返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use vibrato_rkyv::{Dictionary, LoadMode};
+    /// # use vibrato_rkyv::dictionary::WriteOptions;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dict = Dictionary::from_path("path/to/system.dic", LoadMode::TrustCache)?;
+    /// let options = WriteOptions::new().level(19).workers(8);
+    /// dict.write_zstd(File::create("path/to/system.dic.zst")?, &options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_zstd<W>(&self, wtr: W, options: &WriteOptions) -> Result<()>
+    where
+        W: Write,
+    {
+        match self {
+            Dictionary::Owned { dict, .. } => dict.write_zstd_with_options(wtr, options, None),
+            Dictionary::Archived(dict) => {
+                // The dictionary is read twice from memory instead of being copied.
+                let (content_hash, content_size) = compression::digest(dict.file_reader())?;
+                options.compress_with_digest(
+                    dict.file_reader(),
+                    wtr,
+                    dict.metadata.as_ref(),
+                    content_hash,
+                    content_size,
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// シリアライズされた辞書のSHA-256ダイジェストを16進数表現で取得します。
+    ///
+    /// ダイジェストは初回の呼び出し時に計算され、以降はキャッシュされた値が返されます。
+    /// メモリマップで読み込んだ辞書では、辞書ファイル全体のダイジェストとなるため、
+    /// [`hash_file`]の結果やリリースマニフェストに記載された値と直接比較できます。
+    /// 所有型の辞書では、[`write`](Self::write)で出力される内容のダイジェストとなります。
+    ///
+    /// # 戻り値
+    ///
+    /// SHA-256ダイジェストの16進数表現
+    ///
+    /// # エラー
+    ///
+    /// 所有型の辞書のシリアライズに失敗した場合にエラーを返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, LoadMode, errors::Result};
+    /// # use vibrato_rkyv::dictionary::hash_file;
+    /// # fn main() -> Result<()> {
+    /// let dict = Dictionary::from_path("path/to/system.dic", LoadMode::TrustCache)?;
+    /// assert_eq!(dict.content_hash()?, hash_file("path/to/system.dic")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_hash(&self) -> Result<&str> {
+        let (lock, hash) = match self {
+            Dictionary::Archived(dict) => {
+                if let Some(hash) = dict.content_hash.get() {
+                    return Ok(hash);
+                }
+                (&dict.content_hash, dict.compute_content_hash())
+            }
+            Dictionary::Owned { dict, content_hash, .. } => {
+                if let Some(hash) = content_hash.get() {
+                    return Ok(hash);
+                }
+                let mut hasher = Sha256::new();
+                dict.write(&mut hasher)?;
+                (content_hash, hex::encode(hasher.finalize()))
+            }
+        };
+        Ok(lock.get_or_init(|| hash))
+    }
+
+    /// 指定されたキャッシング戦略を使用して圧縮された辞書ファイルを読み込みます。
+    ///
+    /// 入力の形式は先頭のマジックナンバーから自動的に判別されます。対応する形式は
+    /// 次のとおりです。
+    ///
+    /// - Zstandard（`.zst`）
+    /// - xz（`.xz`、`codecs`フィーチャーが必要）
+    /// - LZ4フレーム（`.lz4`、`codecs`フィーチャーが必要）
+    /// - `.dic`を含むtarアーカイブとその圧縮形式（`.tar.gz`を除く、`codecs`フィーチャーが必要）
+    ///
+    /// 非圧縮の辞書ファイルが与えられた場合は、[`from_path`](Self::from_path)で
+    /// 直接読み込みます。より細かい制御が必要な場合は、
+    /// [`from_compressed_with_options`](Self::from_compressed_with_options)を参照してください。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - 圧縮された辞書ファイルへのパス。
+    /// * `strategy` - [`CacheStrategy`]列挙型で定義される希望のキャッシング戦略。
+    #[cfg_attr(feature = "legacy", doc = r"
+    `legacy`フィーチャーが有効な場合、この関数はキャッシングがバックグラウンドで
+    実行されている間に即座に戻り、応答性の高いユーザーエクスペリエンスを提供します。")]
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は、[`from_compressed_with_options`](Self::from_compressed_with_options)のエラーに加えて、
+    /// (`strategy`によって決定される)`cache_dir`が作成できない、
+    /// または書き込めない場合にエラーを返します。
+    pub fn from_compressed<P: AsRef<std::path::Path>>(
+        path: P,
+        strategy: CacheStrategy,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let cache_dir = cache::cache_dir(strategy, path.parent())?;
+
+        Self::from_compressed_with_options(
+            path,
+            cache_dir,
+            #[cfg(feature = "legacy")]
+            false,
+        )
+    }
+
+    /// 設定可能なキャッシングオプションを使用して圧縮された辞書ファイルを読み込みます。
+    ///
+    /// これは[`from_compressed`](Self::from_compressed)の高度なバージョンで、キャッシュディレクトリの細かい制御を
+    /// 可能にします。特定のディレクトリ構造や制限的なファイルシステム権限を持つ環境で
+    /// 有用です。
+    ///
+    /// ## キャッシングメカニズム
+    ///
+    /// 実行ごとにファイルを展開するのを避けるため、この関数はキャッシュメカニズムを
+    /// 採用しています。入力ファイルのメタデータ(サイズや更新時刻など)から
+    /// 一意のハッシュを生成します。このハッシュは、展開されたキャッシュのファイル名として
+    /// 使用されます。
+    ///
+    /// 後続の実行時に、現在のメタデータハッシュに対応するキャッシュファイルが存在する場合、
+    /// 展開ステップが完全にスキップされ、ほぼ瞬時の読み込みが可能になります。
+    /// 入力ファイルが変更されると、そのメタデータハッシュが変更され、新しいキャッシュが
+    /// 自動的に生成されます。
+    ///
+    /// メタデータハッシュだけでは、サイズと更新時刻が同じ別のファイルで入力が上書きされた
+    /// 場合を区別できません。そのため、キャッシュには入力ファイルの[`CompressedHeader`]に
+    /// 記録された内容のダイジェストと辞書形式のバージョンが記録され（`<hash>.source`）、
+    /// 読み込みのたびに照合されます。一致しないキャッシュは削除され、作り直されます。
+    /// 照合にはヘッダのみを読み込むため、キャッシュが有効な場合に入力ファイル全体を
+    /// 読み込むことはありません。ヘッダを持たない入力では、サイズと更新時刻のみで照合します。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - 圧縮された辞書ファイルへのパス。
+    /// * `cache_dir` - 展開された辞書キャッシュが保存されるディレクトリ。
+    #[cfg_attr(feature = "legacy", doc = r" * `wait_for_cache` - (legacyフィーチャーのみ) `true`でレガシー(bincode)辞書が
+    提供された場合、関数は新しい形式への変換とキャッシングが完了するまでブロックします。
+    `false`の場合、完全に機能する辞書ですぐに戻り、キャッシングプロセスは
+    バックグラウンドスレッドで実行されます。")]
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - `path`で指定されたファイルを開けない、または読み込めない場合(例: I/Oエラー)。
+    /// - ファイルの展開に失敗した場合、またはtarアーカイブ内に辞書が見つからない場合。
+    /// - 圧縮された辞書の[`CompressedHeader`]に記録されたダイジェストやサイズと、
+    ///   実際の内容が一致しない場合。
+    /// - `codecs`フィーチャーが無効な状態でxz、LZ4、tar形式の入力が与えられた場合。
+    /// - 展開されたデータが有効な辞書ファイルでない場合(例: 破損データまたは不正なマジックナンバー)。
+    /// - `cache_dir`で指定されたキャッシュディレクトリが作成できない、または書き込めない場合。
+    #[cfg_attr(feature = "legacy", doc = r" - (legacyフィーチャーのみ) `wait_for_cache`が`true`のときにバックグラウンドキャッシングスレッドがパニックした場合。")]
+    ///
+    /// # Examples
+    ///
+    /// ### カスタムキャッシュディレクトリの指定
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, errors::Result};
+    /// # fn main() -> Result<()> {
+    /// let dict = Dictionary::from_compressed_with_options(
+    ///     "path/to/system.dic.tar.xz",
+    ///     "/tmp/my_app_cache",
+    #[cfg_attr(feature = "legacy", doc = r"true, // バックグラウンドキャッシュ生成の完了を待つ")]
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_compressed_with_options<P, Q>(
+        path: P,
+        cache_dir: Q,
+        #[cfg(feature = "legacy")]
+        wait_for_cache: bool,
+    ) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+        Q: AsRef<std::path::Path>,
+    {
+        let compressed_path = path.as_ref();
+        let mut compressed_file = File::open(compressed_path)?;
+
+        let mut magic = [0; MODEL_MAGIC_LEN];
+        let magic_len = compressed_file.read(&mut magic)?;
+        if magic[..magic_len].starts_with(MODEL_MAGIC) {
+            return Self::from_path(compressed_path, LoadMode::Validate);
+        }
+        compressed_file.seek(SeekFrom::Start(0))?;
+
+        let meta = compressed_file.metadata()?;
+        // Only the leading header is read here, so a cache hit does not scan the whole file.
+        let mut rdr = BufReader::new(compressed_file);
+        let header = CompressedHeader::read(&mut rdr)?;
+        let source_record = cache::SourceRecord::new(header.as_ref(), &meta);
+
+        let dict_hash = compute_metadata_hash(&meta);
+        let decompressed_dir = cache_dir.as_ref().to_path_buf();
+
+        let decompressed_dict_path = decompressed_dir.join(format!("{}.dic", dict_hash));
+
+        if decompressed_dict_path.exists() && source_record.matches(&decompressed_dict_path) {
+            return Self::from_path(decompressed_dict_path, LoadMode::TrustCache);
+        }
+
+        if !decompressed_dir.exists() {
+            create_dir_all(&decompressed_dir)?;
+        }
+
+        // Another process may have created the cache while this one was waiting for the lock.
+        let cache_lock = cache::CacheLock::acquire(&decompressed_dict_path)?;
+        if decompressed_dict_path.exists() {
+            if source_record.matches(&decompressed_dict_path) {
+                return Self::from_path(decompressed_dict_path, LoadMode::TrustCache);
+            }
+            log::info!(
+                "[vibrato-rkyv] The cached dictionary for {} is stale. Regenerating it.",
+                compressed_path.display(),
+            );
+            cache::evict(&decompressed_dict_path)?;
+        }
+
+        // Detect corruption of the compressed data before expanding it.
+        if let Some(header) = &header {
+            header.verify(&mut rdr)?;
+        }
+        let mut compressed_file = rdr.into_inner();
+        compressed_file.seek(SeekFrom::Start(0))?;
+
+        let mut temp_file = tempfile::NamedTempFile::new_in(&decompressed_dir)?;
+
+        codec::extract(BufReader::new(compressed_file), temp_file.as_file_mut())?;
+        temp_file.as_file().sync_all()?;
+        // The expanded data must match the header before it is persisted to the cache.
+        if let Some(header) = &header {
+            temp_file.seek(SeekFrom::Start(0))?;
+            header.verify_content(BufReader::new(temp_file.as_file_mut()))?;
+        }
+        temp_file.seek(SeekFrom::Start(0))?;
+
+        temp_file.read_exact(&mut magic)?;
+
+        #[cfg(feature = "legacy")]
+        'l: {
+            use std::thread;
+
+            use crate::legacy;
+
+            if !magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
+                break 'l;
+            }
+
+            temp_file.seek(SeekFrom::Start(0))?;
+            let dict = legacy::Dictionary::read(BufReader::new(temp_file.as_file_mut()))?.data;
+
+            let dict = Arc::new(DictionaryInner::try_from(dict)?);
+
+
+            let dict_for_cache = Arc::clone(&dict);
+            let handle = thread::spawn(move || -> Result<()> {
+                let _cache_lock = cache_lock;
+                let mut temp_file = tempfile::NamedTempFile::new_in(&decompressed_dir)?;
+
+                dict_for_cache.write(&mut temp_file)?;
+
+                source_record.write(&decompressed_dict_path)?;
+                temp_file.persist(&decompressed_dict_path)?;
+
+                let dict_file = File::open(decompressed_dict_path)?;
+                let decompressed_dict_hash = compute_metadata_hash(&dict_file.metadata()?);
+                let decompressed_dict_hash_path = decompressed_dir.join(format!("{}.sha256", decompressed_dict_hash));
+
+                cache::create_proof(&decompressed_dict_hash_path)?;
+
+                Ok(())
+            });
+
+            let _caching_handle = if wait_for_cache {
+                handle.join().map_err(|e| {
+                    let panic_msg = if let Some(s) = e.downcast_ref::<&'static str>() {
+                        s.to_string()
+                    } else if let Some(s) = e.downcast_ref::<String>() {
+                        s.clone()
+                    } else {
+                        "Unknown panic".to_string()
+                    };
+                    VibratoError::ThreadPanic(panic_msg)
+                })??;
+
+                None
+            } else {
+                Some(std::sync::Arc::new(handle))
+            };
+
+            return Ok(Self::Owned { dict, _caching_handle, content_hash: OnceLock::new() });
+        }
+
+        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
+            ));
+        } else if !magic.starts_with(MODEL_MAGIC) {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "The magic number of the input model mismatches.",
+            ));
+        }
+
+        temp_file.seek(SeekFrom::Start(0))?;
+
+        let mut data_bytes = Vec::new();
+        temp_file.as_file_mut().read_to_end(&mut data_bytes)?;
+
+        let mut aligned_bytes: AlignedVec = AlignedVec::with_capacity(data_bytes.len());
+        aligned_bytes.extend_from_slice(&data_bytes);
+
+        let Some(data_bytes) = &aligned_bytes.get(DATA_START..) else {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "Dictionary file too small or corrupted.",
+            ));
+        };
+        let (data_bytes, _) = metadata::split_metadata(data_bytes)?;
+
+        let _ = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
+            VibratoError::invalid_state(
+                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
+                    .to_string(),
+                e.to_string(),
+            )
+        })?;
+
+        source_record.write(&decompressed_dict_path)?;
+        temp_file.persist(&decompressed_dict_path)?;
+
+        let decompressed_dict_hash = compute_metadata_hash(&File::open(&decompressed_dict_path)?.metadata()?);
+        let decompressed_dict_hash_path = decompressed_dir.join(format!("{}.sha256", decompressed_dict_hash));
+
+        cache::create_proof(&decompressed_dict_hash_path)?;
+        drop(cache_lock);
+
+        Self::from_path(decompressed_dict_path, LoadMode::TrustCache)
+    }
+
+    /// 指定されたキャッシング戦略を使用してZstandard圧縮ファイルから辞書を読み込みます。
+    ///
+    /// この関数は[`from_compressed`](Self::from_compressed)の別名です。入力の形式は
+    /// 先頭のマジックナンバーから判別されるため、Zstandard以外の形式も読み込めます。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - Zstandard圧縮辞書ファイルへのパス。
+    /// * `strategy` - [`CacheStrategy`]列挙型で定義される希望のキャッシング戦略。
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// [`from_compressed`](Self::from_compressed)と同じ条件でエラーを返します。
+    #[inline(always)]
+    pub fn from_zstd<P: AsRef<std::path::Path>>(path: P, strategy: CacheStrategy) -> Result<Self> {
+        Self::from_compressed(path, strategy)
+    }
+
+    /// 設定可能なキャッシングオプションを使用してZstandard圧縮ファイルから辞書を読み込みます。
+    ///
+    /// この関数は[`from_compressed_with_options`](Self::from_compressed_with_options)の
+    /// 別名です。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - Zstandard圧縮辞書ファイルへのパス。
+    /// * `cache_dir` - 展開された辞書キャッシュが保存されるディレクトリ。
+    #[cfg_attr(feature = "legacy", doc = r" * `wait_for_cache` - (legacyフィーチャーのみ) レガシー辞書のキャッシング完了を待つかどうか。")]
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// [`from_compressed_with_options`](Self::from_compressed_with_options)と同じ条件で
+    /// エラーを返します。
+    #[inline(always)]
+    pub fn from_zstd_with_options<P, Q>(
+        path: P,
+        cache_dir: Q,
+        #[cfg(feature = "legacy")]
+        wait_for_cache: bool,
+    ) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+        Q: AsRef<std::path::Path>,
+    {
+        Self::from_compressed_with_options(
+            path,
+            cache_dir,
+            #[cfg(feature = "legacy")]
+            wait_for_cache,
+        )
+    }
+
+    /// プリセット辞書から`Dictionary`インスタンスを作成し、存在しない場合はダウンロードします。
+    ///
+    /// これは、プリコンパイル済み辞書を使い始めるための最も便利な方法です。
+    /// この関数は、まず指定されたプリセット辞書が指定のディレクトリに既に存在するかを
+    /// 確認します。存在し、整合性が検証された場合は直接読み込みます。
+    /// それ以外の場合は、公式リポジトリから辞書をディレクトリにダウンロードし、
+    /// その後読み込みます。
+    ///
+    /// ダウンロードされた辞書はZstandard圧縮されています。この関数は、
+    /// メモリマッピングによる高速な後続読み込みのために、展開とキャッシングを
+    /// 透過的に処理します。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `kind` - 使用するプリセット辞書(例: `PresetDictionaryKind::Ipadic`)。
+    /// * `dir` - 辞書が保存およびキャッシュされるディレクトリ。
+    ///   永続的な場所を使用することを推奨します。
+    ///
+    /// # 戻り値
+    ///
+    /// 新しい`Dictionary`インスタンス。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - ダウンロードが失敗した場合(例: ネットワークの問題)。
+    /// - ダウンロードされたファイルが破損している場合(ハッシュの不一致)。
+    /// - キャッシュディレクトリの作成時にファイルシステム権限エラーがある場合。
+    /// - オフラインモードで、辞書がまだ保存されていない場合。
+    ///
+    /// ダウンロード設定は[`DownloadOptions::from_env`]により環境変数から読み込まれます。
+    /// 設定を明示する場合は[`Dictionary::from_preset_with_download_options`]を使用してください。
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use vibrato_rkyv::{Dictionary, Tokenizer, dictionary::PresetDictionaryKind};
+    /// # let dir = Path::new("./cache_dir");
+    /// // IPADICプリセット辞書をダウンロードして読み込みます。
+    /// // 最初の呼び出しではファイルをダウンロードし、後続の呼び出しではキャッシュを使用します。
+    /// let dictionary = Dictionary::from_preset_with_download(
+    ///     PresetDictionaryKind::Ipadic,
+    ///     dir,
+    /// ).unwrap();
+    ///
+    /// let mut tokenizer = Tokenizer::new(dictionary);
+    /// ```
+    #[cfg(feature = "download")]
+    pub fn from_preset_with_download<P: AsRef<std::path::Path>>(kind: PresetDictionaryKind, dir: P) -> Result<Self> {
+        Self::from_preset_with_download_options(kind, dir, &DownloadOptions::from_env())
+    }
+
+    /// ダウンロード設定を指定して、プリセット辞書をダウンロードして読み込みます。
+    ///
+    /// 社内ミラーやプロキシを経由してダウンロードする場合や、ネットワークに接続できない環境で
+    /// 保存済みの辞書がなければ即座に失敗させたい場合に使用します。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `kind` - 使用するプリセット辞書
+    /// * `dir` - 辞書が保存およびキャッシュされるディレクトリ
+    /// * `options` - ダウンロード設定
+    ///
+    /// # エラー
+    ///
+    /// [`Dictionary::from_preset_with_download`]と同じ場合にエラーを返します。
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use vibrato_rkyv::{Dictionary, dictionary::{DownloadOptions, PresetDictionaryKind}};
+    /// # let dir = Path::new("./cache_dir");
+    /// let options = DownloadOptions::new()
+    ///     .base_url("https://mirror.example.com/vibrato-rkyv")
+    ///     .proxy("http://proxy.example.com:8080");
+    /// let dictionary = Dictionary::from_preset_with_download_options(
+    ///     PresetDictionaryKind::Ipadic,
+    ///     dir,
+    ///     &options,
+    /// ).unwrap();
+    /// ```
+    #[cfg(feature = "download")]
+    pub fn from_preset_with_download_options<P: AsRef<std::path::Path>>(
+        kind: PresetDictionaryKind,
+        dir: P,
+        options: &DownloadOptions,
+    ) -> Result<Self> {
+        let dict_path = fetch::download_dictionary(kind, dir.as_ref(), options)?;
+
+        Self::from_zstd_with_options(
+            dict_path,
+            dir,
+            #[cfg(feature = "legacy")]
+            true,
+        )
+    }
+
+    /// マニフェストで定義されたプリセット辞書をダウンロードして読み込みます。
+    ///
+    /// ダウンロードしたファイルはハッシュで検証したうえで`dir`に保存され、
+    /// [`Dictionary::from_compressed`]と同様に形式を判別して展開・キャッシュされます。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `manifest` - 辞書のマニフェスト
+    /// * `dir` - 辞書が保存およびキャッシュされるディレクトリ
+    /// * `options` - ダウンロード設定
+    ///
+    /// # エラー
+    ///
+    /// マニフェストが不正な場合や、ダウンロード、検証、展開に失敗した場合にエラーを返します。
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use vibrato_rkyv::{Dictionary, dictionary::{DownloadOptions, PresetManifest}};
+    /// # let dir = Path::new("./cache_dir");
+    /// let manifest = PresetManifest::new(
+    ///     "acme-ipadic",
+    ///     "https://dict.example.com/acme-ipadic.dic.zst",
+    ///     "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+    /// );
+    /// let dictionary = Dictionary::from_preset_manifest(
+    ///     &manifest,
+    ///     dir,
+    ///     &DownloadOptions::from_env(),
+    /// ).unwrap();
+    /// ```
+    #[cfg(feature = "download")]
+    pub fn from_preset_manifest<P: AsRef<std::path::Path>>(
+        manifest: &PresetManifest,
+        dir: P,
+        options: &DownloadOptions,
+    ) -> Result<Self> {
+        manifest.validate()?;
+        let dict_path = fetch::download_manifest(manifest, dir.as_ref(), options)?;
+
+        Self::from_compressed_with_options(
+            dict_path,
+            dir,
+            #[cfg(feature = "legacy")]
+            true,
+        )
+    }
+
+    /// [`register_preset`]で登録されたプリセット辞書をダウンロードして読み込みます。
+    ///
+    /// ダウンロード設定は[`DownloadOptions::from_env`]により環境変数から読み込まれます。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `name` - 登録された辞書の名前
+    /// * `dir` - 辞書が保存およびキャッシュされるディレクトリ
+    ///
+    /// # エラー
+    ///
+    /// `name`の辞書が登録されていない場合や、[`Dictionary::from_preset_manifest`]が
+    /// 失敗した場合にエラーを返します。
+    #[cfg(feature = "download")]
+    pub fn from_registered_preset<P: AsRef<std::path::Path>>(name: &str, dir: P) -> Result<Self> {
+        let manifest = registered_preset(name).ok_or_else(|| {
+            VibratoError::invalid_argument(
+                "name",
+                format!("No preset named {name} is registered."),
+            )
+        })?;
+        Self::from_preset_manifest(&manifest, dir, &DownloadOptions::from_env())
+    }
+
+    /// プリセット辞書ファイルをダウンロードし、そのパスを返します。
+    ///
+    /// ダウンロード後、辞書は[`Dictionary::from_zstd`]を使用して読み込むことができます。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `kind` - ダウンロードするプリセット辞書(例: `PresetDictionaryKind::Ipadic`)。
+    /// * `dir` - 辞書ファイルが保存されるディレクトリ。
+    ///
+    /// # 戻り値
+    ///
+    /// ダウンロードされたZstandard圧縮辞書ファイルへの`PathBuf`を含む`Result`。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - ダウンロードが失敗した場合。
+    /// - ファイルが破損している場合。
+    /// - ファイルシステム権限エラーがある場合。
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use vibrato_rkyv::{Dictionary, dictionary::PresetDictionaryKind, CacheStrategy};
+    /// # let dir = Path::new("./cache_dir");
+    /// let dict_path = Dictionary::download_dictionary(
+    ///     PresetDictionaryKind::UnidicCwj,
+    ///     dir,
+    /// ).unwrap();
+    ///
+    /// println!("辞書のダウンロード先: {:?}", dict_path);
+    ///
+    /// let dictionary = Dictionary::from_zstd(dict_path, CacheStrategy::Local).unwrap();
+    /// ```
+    #[cfg(feature = "download")]
+    pub fn download_dictionary<P: AsRef<std::path::Path>>(kind: PresetDictionaryKind, dir: P) -> Result<std::path::PathBuf> {
+        Self::download_dictionary_with_options(kind, dir, &DownloadOptions::from_env())
+    }
+
+    /// ダウンロード設定を指定して、プリセット辞書ファイルをダウンロードし、そのパスを返します。
+    ///
+    /// この関数は、`download`フィーチャーが有効な場合にのみ使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `kind` - ダウンロードするプリセット辞書
+    /// * `dir` - 辞書ファイルが保存されるディレクトリ
+    /// * `options` - ダウンロード設定
+    ///
+    /// # エラー
+    ///
+    /// [`Dictionary::download_dictionary`]と同じ場合に加え、オフラインモードで
+    /// 辞書がまだ保存されていない場合にエラーを返します。
+    #[cfg(feature = "download")]
+    pub fn download_dictionary_with_options<P: AsRef<std::path::Path>>(
+        kind: PresetDictionaryKind,
+        dir: P,
+        options: &DownloadOptions,
+    ) -> Result<std::path::PathBuf> {
+        Ok(fetch::download_dictionary(kind, dir, options)?)
+    }
+
+    /// Zstandard圧縮辞書を指定されたパスに展開します。
+    ///
+    /// この関数は、`.zst`圧縮辞書を読み込み、その内容を検証し、
+    /// 展開された辞書を`output_path`に書き込みます。
+    ///
+    /// これは、アプリケーションのセットアップ、テスト、または
+    /// カスタムキャッシュ管理に有用な低レベルユーティリティです。
+    ///
+    /// # 引数
+    ///
+    /// * `input_path` - Zstandard圧縮辞書ファイルへのパス。
+    /// * `output_path` - 展開された辞書が保存されるパス。
+    ///
+    /// # 戻り値
+    ///
+    /// 成功時は`Ok(())`。
+    ///
+    /// # エラー
+    ///
+    /// この関数は以下の場合にエラーを返します:
+    /// - 入力ファイルを読み込めない場合。
+    /// - 有効なZstandard圧縮アーカイブでない場合。
+    /// - 展開されたデータが有効な辞書でない場合。
+    /// - 出力パスに書き込めない場合。
+    pub fn decompress_zstd<P, Q>(input_path: P, output_path: Q) -> Result<()>
+    where
+        P: AsRef<std::path::Path>,
+        Q: AsRef<std::path::Path>,
+    {
+        let input_path = input_path.as_ref();
+        let output_path = output_path.as_ref();
+
+        let output_dir = output_path.parent().ok_or_else(|| {
+            VibratoError::invalid_argument("output_path", "Output path must have a parent directory.")
+        })?;
+        std::fs::create_dir_all(output_dir)?;
+
+        let zstd_file = File::open(input_path)?;
+        let mut temp_file = tempfile::NamedTempFile::new_in(output_dir)?;
+
+        let mut decoder = zstd::Decoder::new(zstd_file)?;
+        io::copy(&mut decoder, &mut temp_file)?;
+
+        temp_file.seek(SeekFrom::Start(0))?;
+        let mut magic = [0; MODEL_MAGIC_LEN];
+        temp_file.read_exact(&mut magic)?;
+
+        if magic.starts_with(LEGACY_MODEL_MAGIC_PREFIX) {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "This appears to be a legacy bincode-based dictionary file. Please use a dictionary compiled for the rkyv version of vibrato.",
+            ));
+        } else if !magic.starts_with(MODEL_MAGIC) {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "The magic number of the input model mismatches.",
+            ));
+        }
+
+        temp_file.seek(SeekFrom::Start(0))?;
+        let mut data_bytes = Vec::new();
+        temp_file.as_file_mut().read_to_end(&mut data_bytes)?;
+
+        let mut aligned_bytes: AlignedVec = AlignedVec::with_capacity(data_bytes.len());
+        aligned_bytes.extend_from_slice(&data_bytes);
+
+        let Some(data_bytes) = &aligned_bytes.get(DATA_START..) else {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "Dictionary file too small or corrupted.",
+            ));
+        };
+        let (data_bytes, _) = metadata::split_metadata(data_bytes)?;
+
+        let _ = access::<ArchivedDictionaryInner, Error>(data_bytes).map_err(|e| {
+            VibratoError::invalid_state(
+                "rkyv validation failed. The dictionary file may be corrupted or incompatible."
+                    .to_string(),
+                e.to_string(),
+            )
+        })?;
+
+        temp_file.persist(output_path)?;
+
+        Ok(())
+    }
+}
+
+/// [`LoadMode::TrustCache`]で使用するプルーフファイルを探します。
+///
+/// 辞書ファイルと同じディレクトリの`.cache`、グローバルキャッシュディレクトリの順に探し、
+/// 見つかったプルーフファイルの場所と、検証後にプルーフファイルを作成するパスを返します。
+///
+/// # エラー
+///
+/// グローバルキャッシュディレクトリを特定できない場合にエラーを返します。
+pub(crate) fn find_proof(path: &Path, meta: &Metadata) -> Result<(Option<ProofLocation>, PathBuf)> {
+    let hash_name = format!("{}.sha256", compute_metadata_hash(meta));
+    let local_path = path.parent().unwrap().join(".cache").join(&hash_name);
+    if local_path.exists() {
+        return Ok((Some(ProofLocation::Local), local_path));
+    }
+
+    let global_cache_dir = GLOBAL_CACHE_DIR.as_ref().ok_or_else(|| {
+        VibratoError::invalid_state("Could not determine system cache directory.", "")
+    })?;
+    let global_path = global_cache_dir.join(&hash_name);
+    let proof_hit = global_path.exists().then_some(ProofLocation::Global);
+    Ok((proof_hit, global_path))
+}
+
+/// 検証済みであることを示すプルーフファイルを、親ディレクトリとともに作成します。
+pub(crate) fn create_proof(proof_path: &Path) -> Result<()> {
+    if let Some(dir) = proof_path.parent() {
+        create_dir_all(dir)?;
+    }
+    cache::create_proof(proof_path)
+}
+
+/// ファイルの内容のSHA-256ダイジェストを16進数表現で計算します。
+///
+/// デプロイ時に、配布物のマニフェストに記載されたダイジェストと辞書ファイルを
+/// 照合する用途を想定しています。辞書ファイルに対しては、メモリマップで読み込んだ
+/// [`Dictionary::content_hash`]と同じ値になります。
+///
+/// # 引数
+///
+/// * `path` - ダイジェストを計算するファイルのパス
+///
+/// # 戻り値
+///
+/// SHA-256ダイジェストの16進数表現
+///
+/// # エラー
+///
+/// ファイルを開けない、または読み込めない場合にエラーを返します。
+pub fn hash_file<P: AsRef<std::path::Path>>(path: P) -> Result<String> {
+    sha256_hex(File::open(path)?)
+}
+
+/// リーダーの内容のSHA-256ダイジェストを16進数表現で計算します。
+pub(crate) fn sha256_hex<R: Read>(rdr: R) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(rdr), &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// ファイルメタデータからハッシュを計算します。
+///
+/// この関数は、ファイルのメタデータ(サイズ、更新時刻、iノードなど)から
+/// 一意のSHA256ハッシュを生成します。このハッシュは、キャッシュファイルの
+/// 命名とファイルの同一性確認に使用されます。
+///
+/// # 引数
+///
+/// * `meta` - ハッシュを計算するファイルのメタデータ。
+///
+/// # 戻り値
+///
+/// メタデータのSHA256ハッシュの16進数表現文字列。
+///
+/// # プラットフォーム固有の動作
+///
+/// - Unix: デバイスID、iノード、サイズ、変更時刻を使用
+/// - Windows: ファイルサイズ、最終書き込み時刻、作成時刻、ファイル属性を使用
+/// - その他: ファイルタイプ、読み取り専用フラグ、サイズ、変更時刻、作成時刻を使用
+#[inline(always)]
+pub(crate) fn compute_metadata_hash(meta: &Metadata) -> String {
+    let mut hasher = Sha256::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        hasher.update(meta.dev().to_le_bytes());
+        hasher.update(meta.ino().to_le_bytes());
+        hasher.update(meta.size().to_le_bytes());
+        hasher.update(meta.mtime().to_le_bytes());
+        hasher.update(meta.mtime_nsec().to_le_bytes());
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        hasher.update(meta.file_size().to_le_bytes());
+        hasher.update(meta.last_write_time().to_le_bytes());
+        hasher.update(meta.creation_time().to_le_bytes());
+        hasher.update(meta.file_attributes().to_le_bytes());
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        use std::time::SystemTime;
+
+        fn update_system_time(
+            time: Result<SystemTime, std::io::Error>,
+            hasher: &mut Sha256,
+        ) {
+            match time.and_then(|t| {
+                t.duration_since(SystemTime::UNIX_EPOCH)
+                    .map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))
+            }) {
+                Ok(duration) => {
+                    hasher.update(duration.as_secs().to_le_bytes());
+                    hasher.update(duration.subsec_nanos().to_le_bytes());
+                }
+                Err(_) => {
+                    hasher.update([0u8; 12]);
+                }
+            }
+        }
+
+        let file_type = meta.file_type();
+        let type_byte: u8 = if file_type.is_file() { 0x01 }
+        else if file_type.is_dir() { 0x02 }
+        else if file_type.is_symlink() { 0x03 }
+        else { 0x00 };
+        hasher.update([type_byte]);
+
+        let readonly_byte: u8 = if meta.permissions().readonly() { 0x01 } else { 0x00 };
+        hasher.update([readonly_byte]);
+
+        hasher.update(meta.len().to_le_bytes());
+
+        update_system_time(meta.modified(), &mut hasher);
+
+        update_system_time(meta.created(), &mut hasher);
+    }
+
+    hex::encode(hasher.finalize())
+}
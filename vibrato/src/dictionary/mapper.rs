@@ -162,6 +162,15 @@ impl ConnIdCounter {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::mapper::ConnIdMapper> for ConnIdMapper {
+    fn from(mapper: crate::legacy::dictionary::mapper::ConnIdMapper) -> Self {
+        let crate::legacy::dictionary::mapper::ConnIdMapper { left, right } = mapper;
+        Self::new(left, right)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,310 @@
+//! 辞書のメタデータ
+//!
+//! このモジュールは、辞書ファイルに埋め込む名前、バージョン、ライセンス、ビルド日時、
+//! 元のコーパスなどの自由形式のメタデータを扱います。
+//!
+//! メタデータは、rkyvでシリアライズされた辞書データの後ろに次の形式で追記されます。
+//! メタデータを持たない辞書ファイルとの互換性は保たれ、追記された部分は
+//! 辞書データの検証やアクセスの対象から除外されます。
+//!
+//! ```text
+//! [辞書データ][メタデータ (key=value の行)][メタデータ長 (u64 LE)][METADATA_MAGIC]
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::dictionary::{feature_pool, surface};
+use crate::errors::{Result, VibratoError};
+
+/// メタデータの末尾に置かれるマジックバイト
+const METADATA_MAGIC: &[u8] = b"VibratoMetadata\n";
+
+/// メタデータ長の格納に使用するバイト数
+const LEN_BYTES: usize = 8;
+
+/// 辞書の名前を表すキー
+pub const KEY_NAME: &str = "name";
+
+/// 辞書のバージョンを表すキー
+pub const KEY_VERSION: &str = "version";
+
+/// 辞書のライセンスを表すキー
+pub const KEY_LICENSE: &str = "license";
+
+/// ビルド日時を表すキー
+pub const KEY_BUILD_TIMESTAMP: &str = "build_timestamp";
+
+/// 辞書の元になったコーパスや配布元を表すキー
+pub const KEY_SOURCE: &str = "source";
+
+/// 品詞体系（`ipadic`、`unidic`など）を表すキー
+///
+/// [`PosTagset::from_metadata()`](crate::posmap::PosTagset::from_metadata)が参照します。
+pub const KEY_POS_TAGSET: &str = "pos_tagset";
+
+/// 辞書ファイルに埋め込まれるメタデータ
+///
+/// 任意のキーと値の組を保持します。よく使われる項目には専用のアクセサがあります。
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::dictionary::DictionaryMetadata;
+///
+/// let metadata = DictionaryMetadata::new()
+///     .with("name", "mecab-ipadic")
+///     .with("version", "2.7.0")
+///     .with("license", "BSD-3-Clause");
+///
+/// assert_eq!(metadata.name(), Some("mecab-ipadic"));
+/// assert_eq!(metadata.get("license"), Some("BSD-3-Clause"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DictionaryMetadata {
+    entries: BTreeMap<String, String>,
+}
+
+impl DictionaryMetadata {
+    /// 空のメタデータを作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 項目を追加したメタデータを返します。
+    ///
+    /// 同じキーの項目が既に存在する場合は上書きされます。
+    pub fn with<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.insert(key, value);
+        self
+    }
+
+    /// 項目を追加します。
+    ///
+    /// # 戻り値
+    ///
+    /// 同じキーの項目が既に存在した場合は、その値を返します。
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.entries.insert(key.into(), value.into())
+    }
+
+    /// 指定されたキーの値を取得します。
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// 辞書の名前を取得します。
+    pub fn name(&self) -> Option<&str> {
+        self.get(KEY_NAME)
+    }
+
+    /// 辞書のバージョンを取得します。
+    pub fn version(&self) -> Option<&str> {
+        self.get(KEY_VERSION)
+    }
+
+    /// 辞書のライセンスを取得します。
+    pub fn license(&self) -> Option<&str> {
+        self.get(KEY_LICENSE)
+    }
+
+    /// ビルド日時を取得します。
+    pub fn build_timestamp(&self) -> Option<&str> {
+        self.get(KEY_BUILD_TIMESTAMP)
+    }
+
+    /// 辞書の元になったコーパスや配布元を取得します。
+    pub fn source(&self) -> Option<&str> {
+        self.get(KEY_SOURCE)
+    }
+
+    /// 品詞体系を取得します。
+    pub fn pos_tagset(&self) -> Option<&str> {
+        self.get(KEY_POS_TAGSET)
+    }
+
+    /// すべての項目をキーの順に返すイテレータを作成します。
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// 項目の数を取得します。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 項目が1つもない場合に`true`を返します。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 辞書データの後ろに追記する形式でメタデータを書き出します。
+    pub(crate) fn write_trailer<W: Write>(&self, mut wtr: W) -> Result<()> {
+        let body = self.to_body();
+        wtr.write_all(body.as_bytes())?;
+        wtr.write_all(&(body.len() as u64).to_le_bytes())?;
+        wtr.write_all(METADATA_MAGIC)?;
+        Ok(())
+    }
+
+    /// メタデータの本体（`key=value`の行）を作成します。
+    pub(crate) fn to_body(&self) -> String {
+        let mut body = String::new();
+        for (key, value) in &self.entries {
+            escape_into(key, &mut body);
+            body.push('=');
+            escape_into(value, &mut body);
+            body.push('\n');
+        }
+        body
+    }
+
+    /// 指定されたキーの項目を取り除き、その値を返します。
+    pub(crate) fn remove(&mut self, key: &str) -> Option<String> {
+        self.entries.remove(key)
+    }
+
+    /// メタデータの本体を解析します。
+    pub(crate) fn parse(body: &[u8]) -> Result<Self> {
+        let body = std::str::from_utf8(body).map_err(|_| {
+            VibratoError::invalid_format("metadata", "The metadata is not valid UTF-8.")
+        })?;
+        let mut entries = BTreeMap::new();
+        for line in body.lines() {
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                VibratoError::invalid_format("metadata", format!("Invalid metadata line: {line}"))
+            })?;
+            entries.insert(unescape(key)?, unescape(value)?);
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// 辞書データの後ろに追記されたメタデータを切り離します。
+///
+/// メタデータの前に追記された表層形（[`surface`](super::surface)を参照）と
+/// 素性（[`feature_pool`](super::feature_pool)を参照）も切り離されます。
+///
+/// # 引数
+///
+/// * `data` - マジックナンバーとパディングを除いた辞書ファイルの内容
+///
+/// # 戻り値
+///
+/// 辞書データ部分と、メタデータが存在する場合はその内容
+///
+/// # エラー
+///
+/// メタデータや表層形の形式が不正な場合に[`VibratoError`]を返します。
+pub(crate) fn split_metadata(data: &[u8]) -> Result<(&[u8], Option<DictionaryMetadata>)> {
+    let (data, metadata) = split_trailer(data)?;
+    let (data, _) = surface::split_surfaces(data)?;
+    let (data, _) = feature_pool::split_features(data)?;
+    Ok((data, metadata))
+}
+
+/// メタデータのみを切り離します。
+///
+/// 戻り値の辞書データ部分には、追記された表層形が含まれる場合があります。
+pub(crate) fn split_trailer(data: &[u8]) -> Result<(&[u8], Option<DictionaryMetadata>)> {
+    let Some(rest) = data.strip_suffix(METADATA_MAGIC) else {
+        return Ok((data, None));
+    };
+    let Some(len_start) = rest.len().checked_sub(LEN_BYTES) else {
+        return Ok((data, None));
+    };
+    let len = u64::from_le_bytes(rest[len_start..].try_into().unwrap());
+    let body_start = usize::try_from(len)
+        .ok()
+        .and_then(|len| len_start.checked_sub(len))
+        .ok_or_else(|| {
+            VibratoError::invalid_format("metadata", "The metadata length is out of range.")
+        })?;
+    let metadata = DictionaryMetadata::parse(&rest[body_start..len_start])?;
+    Ok((&rest[..body_start], Some(metadata)))
+}
+
+fn escape_into(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '=' => out.push_str("\\e"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('e') => out.push('='),
+            _ => {
+                return Err(VibratoError::invalid_format(
+                    "metadata",
+                    format!("Invalid escape sequence in metadata: {s}"),
+                ));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = DictionaryMetadata::new()
+            .with(KEY_NAME, "acme")
+            .with(KEY_LICENSE, "a=b\\c\nd")
+            .with("key=with\rspecial", "");
+
+        let mut data = b"dictionary data".to_vec();
+        metadata.write_trailer(&mut data).unwrap();
+
+        let (dict, parsed) = split_metadata(&data).unwrap();
+        assert_eq!(dict, b"dictionary data");
+        assert_eq!(parsed, Some(metadata));
+    }
+
+    #[test]
+    fn test_without_metadata() {
+        let (dict, parsed) = split_metadata(b"dictionary data").unwrap();
+        assert_eq!(dict, b"dictionary data");
+        assert_eq!(parsed, None);
+
+        let mut data = vec![];
+        DictionaryMetadata::new().write_trailer(&mut data).unwrap();
+        let (dict, parsed) = split_metadata(&data).unwrap();
+        assert!(dict.is_empty());
+        assert_eq!(parsed, Some(DictionaryMetadata::new()));
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let mut data = b"short".to_vec();
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(METADATA_MAGIC);
+        assert!(split_metadata(&data).is_err());
+    }
+}
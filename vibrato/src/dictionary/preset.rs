@@ -0,0 +1,269 @@
+//! ユーザー定義のプリセット辞書
+//!
+//! このモジュールは、[`PresetDictionaryKind`]に含まれない辞書を、組み込みのプリセット辞書と
+//! 同じ方法で配布・読み込みするための機能を提供します。
+//! 辞書は[`PresetManifest`]として定義し、プログラムから、またはTOMLファイルから登録します。
+//!
+//! マニフェストファイルは、次のように`[[preset]]`テーブルの配列として記述します。
+//!
+//! ```toml
+//! [[preset]]
+//! name = "acme-ipadic"
+//! url = "https://dict.example.com/acme-ipadic.dic.zst"
+//! sha256 = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"
+//! license = "Proprietary"
+//! ```
+//!
+//! `url`が指すファイルは、[`Dictionary::from_compressed`](crate::Dictionary::from_compressed)で
+//! 読み込める形式（圧縮された辞書、または辞書を含むtarアーカイブ）である必要があります。
+
+#![cfg(feature = "download")]
+
+use std::fs;
+use std::path::Path;
+use std::sync::{LazyLock, RwLock};
+
+use serde::Deserialize;
+
+use crate::dictionary::PresetDictionaryKind;
+use crate::errors::{Result, VibratoError};
+
+/// 登録されたユーザー定義のプリセット辞書
+static REGISTRY: LazyLock<RwLock<Vec<PresetManifest>>> = LazyLock::new(Default::default);
+
+/// ユーザー定義のプリセット辞書のマニフェスト
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct PresetManifest {
+    /// 辞書の名前
+    pub name: String,
+
+    /// 辞書ファイルのダウンロードURL
+    pub url: String,
+
+    /// ダウンロードされるファイルのSHA-256ハッシュ（16進数）
+    pub sha256: String,
+
+    /// ライセンスの表記
+    #[serde(default)]
+    pub license: Option<String>,
+}
+
+/// マニフェストファイルの内容
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    preset: Vec<PresetManifest>,
+}
+
+impl PresetManifest {
+    /// 新しいマニフェストを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `name` - 辞書の名前
+    /// * `url` - 辞書ファイルのダウンロードURL
+    /// * `sha256` - ダウンロードされるファイルのSHA-256ハッシュ
+    pub fn new<N, U, H>(name: N, url: U, sha256: H) -> Self
+    where
+        N: Into<String>,
+        U: Into<String>,
+        H: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            sha256: sha256.into(),
+            license: None,
+        }
+    }
+
+    /// ライセンスの表記を設定します。
+    pub fn license<S: Into<String>>(mut self, license: S) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    /// TOML形式の文字列からマニフェストを読み込みます。
+    ///
+    /// # 引数
+    ///
+    /// * `s` - `[[preset]]`テーブルの配列を含むTOML文字列
+    ///
+    /// # エラー
+    ///
+    /// TOMLの解析に失敗した場合や、いずれかのマニフェストが不正な場合に
+    /// [`VibratoError`]を返します。
+    pub fn from_toml_str(s: &str) -> Result<Vec<Self>> {
+        let file: ManifestFile = toml::from_str(s)
+            .map_err(|e| VibratoError::invalid_format("manifest", e.to_string()))?;
+        for manifest in &file.preset {
+            manifest.validate()?;
+        }
+        Ok(file.preset)
+    }
+
+    /// TOMLファイルからマニフェストを読み込みます。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - マニフェストファイルのパス
+    ///
+    /// # エラー
+    ///
+    /// ファイルの読み込みに失敗した場合や、[`PresetManifest::from_toml_str`]が
+    /// 失敗した場合に[`VibratoError`]を返します。
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Vec<Self>> {
+        Self::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    /// マニフェストの内容を検証します。
+    ///
+    /// # エラー
+    ///
+    /// 名前が空またはパス区切り文字を含む場合、URLが空の場合、
+    /// ハッシュが64文字の16進数でない場合に[`VibratoError`]を返します。
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains(['/', '\\']) {
+            return Err(VibratoError::invalid_argument(
+                "name",
+                format!("Invalid preset name: {:?}", self.name),
+            ));
+        }
+        if self.url.is_empty() {
+            return Err(VibratoError::invalid_argument(
+                "url",
+                format!("The URL of the preset {} is empty.", self.name),
+            ));
+        }
+        if self.sha256.len() != 64
+            || !self.sha256.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Err(VibratoError::invalid_argument(
+                "sha256",
+                format!(
+                    "The SHA-256 hash of the preset {} must be 64 lowercase hex digits.",
+                    self.name,
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// ユーザー定義のプリセット辞書を登録します。
+///
+/// 登録した辞書は、[`Dictionary::from_registered_preset`](crate::Dictionary::from_registered_preset)
+/// で名前を指定して読み込めます。
+///
+/// # 引数
+///
+/// * `manifest` - 登録する辞書のマニフェスト
+///
+/// # エラー
+///
+/// マニフェストが不正な場合や、組み込みのプリセット辞書または登録済みの辞書と
+/// 名前が重複する場合に[`VibratoError`]を返します。
+pub fn register_preset(manifest: PresetManifest) -> Result<()> {
+    manifest.validate()?;
+    if PresetDictionaryKind::ALL.iter().any(|kind| kind.name() == manifest.name) {
+        return Err(VibratoError::invalid_argument(
+            "name",
+            format!("{} is the name of a built-in preset.", manifest.name),
+        ));
+    }
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if registry.iter().any(|m| m.name == manifest.name) {
+        return Err(VibratoError::invalid_argument(
+            "name",
+            format!("The preset {} is already registered.", manifest.name),
+        ));
+    }
+    registry.push(manifest);
+    Ok(())
+}
+
+/// TOMLファイルに記述されたすべてのプリセット辞書を登録します。
+///
+/// # 引数
+///
+/// * `path` - マニフェストファイルのパス
+///
+/// # 戻り値
+///
+/// 登録された辞書の数
+///
+/// # エラー
+///
+/// ファイルの読み込みや登録に失敗した場合に[`VibratoError`]を返します。
+/// エラーが発生する前に登録された辞書は、登録されたまま残ります。
+pub fn register_presets_from_file<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let manifests = PresetManifest::from_toml_file(path)?;
+    let len = manifests.len();
+    for manifest in manifests {
+        register_preset(manifest)?;
+    }
+    Ok(len)
+}
+
+/// 名前を指定して、登録されたプリセット辞書のマニフェストを取得します。
+pub fn registered_preset(name: &str) -> Option<PresetManifest> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.iter().find(|m| m.name == name).cloned()
+}
+
+/// 登録されたすべてのプリセット辞書のマニフェストを登録順に取得します。
+pub fn registered_presets() -> Vec<PresetManifest> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_from_toml_str() {
+        let toml = format!(
+            r#"
+[[preset]]
+name = "acme-a"
+url = "https://dict.example.com/a.dic.zst"
+sha256 = "{HASH}"
+license = "Proprietary"
+
+[[preset]]
+name = "acme-b"
+url = "https://dict.example.com/b.tar.xz"
+sha256 = "{HASH}"
+"#
+        );
+        let manifests = PresetManifest::from_toml_str(&toml).unwrap();
+        assert_eq!(
+            manifests,
+            [
+                PresetManifest::new("acme-a", "https://dict.example.com/a.dic.zst", HASH)
+                    .license("Proprietary"),
+                PresetManifest::new("acme-b", "https://dict.example.com/b.tar.xz", HASH),
+            ]
+        );
+
+        let invalid = toml.replace(HASH, "0123");
+        assert!(PresetManifest::from_toml_str(&invalid).is_err());
+        assert!(PresetManifest::from_toml_str("[[preset]]\nname = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_register_preset() {
+        let manifest = PresetManifest::new("test-register", "https://dict.example.com/t.dic.zst", HASH);
+        register_preset(manifest.clone()).unwrap();
+        assert_eq!(registered_preset("test-register"), Some(manifest.clone()));
+        assert!(registered_presets().contains(&manifest));
+        assert!(register_preset(manifest).is_err());
+
+        let builtin = PresetManifest::new("mecab-ipadic", "https://dict.example.com/i.dic.zst", HASH);
+        assert!(register_preset(builtin).is_err());
+        assert_eq!(registered_preset("nonexistent"), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! 辞書の読み込み結果のレポート
+//!
+//! このモジュールは、[`Dictionary::from_path_with_report`](crate::Dictionary::from_path_with_report)
+//! が返す読み込み時のメタデータを定義します。アプリケーションの起動ログや
+//! ヘルスチェックで、辞書がどのように読み込まれたかを確認するために使用します。
+
+use std::time::Duration;
+
+/// 辞書データを保持しているメモリの種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferKind {
+    /// メモリマップされたファイルから直接参照しています。
+    Mmap,
+    /// アライメントのためにヒープ上のバッファへコピーしています。
+    Heap,
+    /// レガシー形式から変換され、ヒープ上に所有されています。
+    Owned,
+}
+
+/// 検証をスキップする根拠となったプルーフファイルの場所。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofLocation {
+    /// 辞書ファイルと同じディレクトリの`.cache`内。
+    Local,
+    /// グローバルキャッシュディレクトリ内。
+    Global,
+}
+
+/// 辞書の読み込み時に収集されたメタデータ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    /// 辞書データを保持しているメモリの種類。
+    pub buffer: BufferKind,
+
+    /// `rkyv`による完全な検証が実行されたかどうか。
+    pub validated: bool,
+
+    /// 検証をスキップした場合、見つかったプルーフファイルの場所。
+    pub proof_hit: Option<ProofLocation>,
+
+    /// 今回の読み込みで新しいプルーフファイルを作成したかどうか。
+    pub proof_created: bool,
+
+    /// ファイルを開き、マジックナンバーの確認とメモリマップを行うのに要した時間。
+    pub open_duration: Duration,
+
+    /// 検証（またはレガシー形式の変換）に要した時間。
+    pub validation_duration: Duration,
+
+    /// 読み込み全体に要した時間。
+    pub total_duration: Duration,
+
+    /// 辞書ファイルのサイズ（バイト）。
+    pub file_size: u64,
+
+    /// 左接続IDの数。
+    pub num_left_ids: usize,
+
+    /// 右接続IDの数。
+    pub num_right_ids: usize,
+
+    /// ユーザー辞書を含むかどうか。
+    pub has_user_lexicon: bool,
+}
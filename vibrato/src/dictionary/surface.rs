@@ -0,0 +1,204 @@
+//! 単語の表層形
+//!
+//! 辞書データの形式を変えずに単語の表層形を保持するため、表層形はrkyvでシリアライズされた
+//! 辞書データとメタデータの間に次の形式で追記されます。表層形を持たない辞書ファイルとの
+//! 互換性は保たれ、追記された部分は辞書データの検証やアクセスの対象から除外されます。
+//!
+//! ```text
+//! [辞書データ][表層形の本体][本体長 (u64 LE)][SURFACES_MAGIC][メタデータ]
+//! ```
+//!
+//! 本体には、システム辞書とユーザー辞書の順に、単語数 (u32 LE)、各表層形のバイト長
+//! (u32 LE)、表層形を連結したUTF-8の文字列が並びます。ユーザー辞書を持たない場合、
+//! その単語数は`u32::MAX`となります。
+
+use std::io::Write;
+
+use crate::dictionary::metadata;
+use crate::errors::{Result, VibratoError};
+
+/// 表層形の本体の末尾に置かれるマジックバイト
+const SURFACES_MAGIC: &[u8] = b"VibratoSurfaces\n";
+
+/// 本体長の格納に使用するバイト数
+const LEN_BYTES: usize = 8;
+
+/// ユーザー辞書を持たないことを表す単語数
+const NO_LEXICON: u32 = u32::MAX;
+
+/// 辞書ファイルから読み込まれた表層形
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Surfaces {
+    pub(crate) system: Vec<String>,
+    pub(crate) user: Option<Vec<String>>,
+}
+
+impl Surfaces {
+    /// 追記された表層形の本体を解析します。
+    pub(crate) fn parse(mut body: &[u8]) -> Result<Self> {
+        let system = read_lexicon(&mut body)?.ok_or_else(|| {
+            VibratoError::invalid_format("surfaces", "The system surfaces are missing.")
+        })?;
+        let user = read_lexicon(&mut body)?;
+        if !body.is_empty() {
+            return Err(VibratoError::invalid_format(
+                "surfaces",
+                "Trailing bytes after the surfaces.",
+            ));
+        }
+        Ok(Self { system, user })
+    }
+}
+
+/// 表層形を辞書データの後ろに追記します。
+///
+/// # 引数
+///
+/// * `wtr` - 書き込み先
+/// * `system` - システム辞書の表層形
+/// * `user` - ユーザー辞書の表層形
+pub(crate) fn write_trailer<W>(
+    mut wtr: W,
+    system: &[String],
+    user: Option<&[String]>,
+) -> Result<()>
+where
+    W: Write,
+{
+    let mut body = vec![];
+    write_lexicon(&mut body, Some(system))?;
+    write_lexicon(&mut body, user)?;
+    wtr.write_all(&body)?;
+    wtr.write_all(&(body.len() as u64).to_le_bytes())?;
+    wtr.write_all(SURFACES_MAGIC)?;
+    Ok(())
+}
+
+/// 辞書データの後ろに追記された表層形を切り離します。
+///
+/// # 引数
+///
+/// * `data` - メタデータを除いた辞書ファイルの内容
+///
+/// # 戻り値
+///
+/// 辞書データ部分と、表層形が存在する場合はその本体
+///
+/// # エラー
+///
+/// 本体長が範囲外の場合に[`VibratoError`]を返します。
+pub(crate) fn split_surfaces(data: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let Some(rest) = data.strip_suffix(SURFACES_MAGIC) else {
+        return Ok((data, None));
+    };
+    let Some(len_start) = rest.len().checked_sub(LEN_BYTES) else {
+        return Ok((data, None));
+    };
+    let len = u64::from_le_bytes(rest[len_start..].try_into().unwrap());
+    let body_start = usize::try_from(len)
+        .ok()
+        .and_then(|len| len_start.checked_sub(len))
+        .ok_or_else(|| {
+            VibratoError::invalid_format("surfaces", "The surfaces length is out of range.")
+        })?;
+    Ok((&rest[..body_start], Some(&rest[body_start..len_start])))
+}
+
+/// 辞書ファイルに追記された表層形を読み込みます。
+///
+/// # 引数
+///
+/// * `data` - マジックナンバーとパディングを除いた辞書ファイルの内容
+///
+/// # 戻り値
+///
+/// 表層形が追記されている場合はその内容
+///
+/// # エラー
+///
+/// メタデータや表層形の形式が不正な場合に[`VibratoError`]を返します。
+pub(crate) fn read_trailer(data: &[u8]) -> Result<Option<Surfaces>> {
+    let (data, _) = metadata::split_trailer(data)?;
+    let (_, body) = split_surfaces(data)?;
+    body.map(Surfaces::parse).transpose()
+}
+
+fn write_lexicon(body: &mut Vec<u8>, surfaces: Option<&[String]>) -> Result<()> {
+    let Some(surfaces) = surfaces else {
+        body.extend_from_slice(&NO_LEXICON.to_le_bytes());
+        return Ok(());
+    };
+    body.extend_from_slice(&u32::try_from(surfaces.len())?.to_le_bytes());
+    for surface in surfaces {
+        body.extend_from_slice(&u32::try_from(surface.len())?.to_le_bytes());
+    }
+    for surface in surfaces {
+        body.extend_from_slice(surface.as_bytes());
+    }
+    Ok(())
+}
+
+fn read_u32(body: &mut &[u8]) -> Result<u32> {
+    let Some((bytes, rest)) = body.split_first_chunk::<4>() else {
+        return Err(VibratoError::invalid_format(
+            "surfaces",
+            "Unexpected end of the surfaces.",
+        ));
+    };
+    *body = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+fn read_lexicon(body: &mut &[u8]) -> Result<Option<Vec<String>>> {
+    let num_words = read_u32(body)?;
+    if num_words == NO_LEXICON {
+        return Ok(None);
+    }
+    let lens = (0..num_words)
+        .map(|_| read_u32(body))
+        .collect::<Result<Vec<_>>>()?;
+    let mut surfaces = Vec::with_capacity(lens.len());
+    for len in lens {
+        let len = usize::try_from(len)?;
+        if body.len() < len {
+            return Err(VibratoError::invalid_format(
+                "surfaces",
+                "Unexpected end of the surfaces.",
+            ));
+        }
+        let (surface, rest) = body.split_at(len);
+        surfaces.push(std::str::from_utf8(surface)?.to_string());
+        *body = rest;
+    }
+    Ok(Some(surfaces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let system = vec!["東京".to_string(), "京都".to_string(), String::new()];
+        let user = vec!["ヴェネツィア".to_string()];
+
+        let mut data = b"dictionary data".to_vec();
+        write_trailer(&mut data, &system, Some(user.as_slice())).unwrap();
+        let (dict, body) = split_surfaces(&data).unwrap();
+        assert_eq!(dict, b"dictionary data");
+        let surfaces = Surfaces::parse(body.unwrap()).unwrap();
+        assert_eq!(surfaces, Surfaces { system: system.clone(), user: Some(user) });
+
+        let mut data = b"dictionary data".to_vec();
+        write_trailer(&mut data, &system, None).unwrap();
+        let (_, body) = split_surfaces(&data).unwrap();
+        assert_eq!(Surfaces::parse(body.unwrap()).unwrap(), Surfaces { system, user: None });
+    }
+
+    #[test]
+    fn test_without_surfaces() {
+        let (dict, body) = split_surfaces(b"dictionary data").unwrap();
+        assert_eq!(dict, b"dictionary data");
+        assert!(body.is_none());
+    }
+}
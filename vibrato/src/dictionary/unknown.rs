@@ -15,10 +15,7 @@ use crate::dictionary::word_idx::WordIdx;
 use crate::dictionary::LexType;
 use crate::errors::{Result, VibratoError};
 use crate::sentence::Sentence;
-use crate::utils::FromU32;
-
-#[cfg(feature = "train")]
-use crate::utils;
+use crate::utils::{self, FromU32};
 
 use crate::common::MAX_SENTENCE_LENGTH;
 
@@ -81,13 +78,16 @@ impl UnkHandler {
     /// * `start_char` - 開始文字位置
     /// * `has_matched` - マッチした単語があるかどうか
     /// * `max_grouping_len` - グループ化の最大長
+    /// * `grouping` - `false`の場合、グループ化と長さ指定による生成を行わず、
+    ///   1文字の未知語のみを生成します
     /// * `f` - 生成された未知語を処理するクロージャ
-    pub fn gen_unk_words<F>(
+    pub(crate) fn gen_unk_words<F>(
         &self,
         sent: &Sentence,
         start_char: usize,
         mut has_matched: bool,
         max_grouping_len: Option<usize>,
+        grouping: bool,
         mut f: F,
     ) where
         F: FnMut(UnkWord),
@@ -97,7 +97,13 @@ impl UnkHandler {
             return;
         }
 
+        if !grouping {
+            self.scan_entries(start_char, sent.align_end(start_char + 1), cinfo, f);
+            return;
+        }
+
         let mut grouped = false;
+        let mut group_end = None;
         let groupable = sent.groupable(start_char);
         debug_assert_ne!(groupable, 0);
 
@@ -109,25 +115,32 @@ impl UnkHandler {
             // Note: Do NOT write `max_grouping_len+1` to avoid overflow.
             if groupable - 1 <= max_grouping_len {
                 f = self.scan_entries(start_char, start_char + groupable, cinfo, f);
+                group_end = Some(start_char + groupable);
                 has_matched = true;
             }
         }
 
+        let mut prev_end = start_char;
         for i in 1..=usize::from(cinfo.length()).min(groupable) {
             if grouped && i == groupable {
                 continue;
             }
-            let end_char = start_char + i;
+            let end_char = sent.align_end(start_char + i);
             if sent.len_char() < end_char {
                 break;
             }
+            // Skips the ends already generated by aligning to grapheme boundaries.
+            if end_char == prev_end || Some(end_char) == group_end {
+                continue;
+            }
+            prev_end = end_char;
             f = self.scan_entries(start_char, end_char, cinfo, f);
             has_matched = true;
         }
 
         // Generates at least one unknown word.
         if !has_matched {
-            self.scan_entries(start_char, start_char + 1, cinfo, f);
+            self.scan_entries(start_char, sent.align_end(start_char + 1), cinfo, f);
         }
     }
 
@@ -156,7 +169,7 @@ impl UnkHandler {
     ///
     /// 互換性のあるエントリが存在しない場合は `None` を返します。
     #[cfg(feature = "train")]
-    pub fn compatible_unk_index(
+    pub(crate) fn compatible_unk_index(
         &self,
         sent: &Sentence,
         start_char: usize,
@@ -191,7 +204,7 @@ impl UnkHandler {
     }
 
     #[inline(always)]
-    pub fn word_param(&self, word_idx: WordIdx) -> WordParam {
+    pub(crate) fn word_param(&self, word_idx: WordIdx) -> WordParam {
         debug_assert_eq!(word_idx.lex_type, LexType::Unknown);
         let e = &self.entries[usize::from_u32(word_idx.word_id)];
         WordParam::new(e.left_id, e.right_id, e.word_cost)
@@ -210,9 +223,8 @@ impl UnkHandler {
         self.entries[usize::from_u32(word_idx.word_id)].cate_id
     }
 
-    #[cfg(feature = "train")]
     #[inline(always)]
-    pub fn len(&self) -> usize {
+    pub(crate) fn len(&self) -> usize {
         self.entries.len()
     }
 
@@ -222,7 +234,7 @@ impl UnkHandler {
     ///
     /// `Dictionary` のメンバー間で接続IDマッピングの一貫性を保つため、
     /// この関数は公開しないでください。一貫性は `Dictionary` で管理されます。
-    pub fn map_connection_ids(&mut self, mapper: &ConnIdMapper) {
+    pub(crate) fn map_connection_ids(&mut self, mapper: &ConnIdMapper) {
         for e in &mut self.entries {
             e.left_id = mapper.left(e.left_id);
             e.right_id = mapper.right(e.right_id);
@@ -238,7 +250,7 @@ impl UnkHandler {
     /// # 戻り値
     ///
     /// すべてのIDが有効な場合は `true`
-    pub fn verify<C>(&self, conn: &C) -> bool
+    pub(crate) fn verify<C>(&self, conn: &C) -> bool
     where
         C: Connector,
     {
@@ -253,6 +265,18 @@ impl UnkHandler {
         true
     }
 
+    /// 定義されているカテゴリ数を返します。
+    #[inline(always)]
+    pub(crate) fn num_categories(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// 指定されたカテゴリに未知語エントリが定義されているかどうかを返します。
+    pub(crate) fn has_entries(&self, cate_id: u32) -> bool {
+        let cate_id = usize::from_u32(cate_id);
+        cate_id + 1 < self.offsets.len() && self.offsets[cate_id] < self.offsets[cate_id + 1]
+    }
+
     /// `unk.def` ファイルから新しいインスタンスを作成します。
     ///
     /// # 引数
@@ -292,6 +316,42 @@ impl UnkHandler {
             map[usize::from(cate_id)].push(e);
         }
 
+        Ok(Self::from_category_map(map))
+    }
+
+    /// 各エントリの素性を、先頭から指定された数の項目のみに切り詰めます。
+    pub(crate) fn truncate_features(&mut self, num_fields: usize) {
+        for e in &mut self.entries {
+            let len = utils::truncate_csv_row(&e.feature, num_fields).len();
+            e.feature.truncate(len);
+        }
+    }
+
+    /// 未知語エントリから新しいインスタンスを作成します。
+    ///
+    /// エントリはカテゴリIDごとにまとめられ、同じカテゴリ内では元の順序が保たれます。
+    ///
+    /// # 引数
+    ///
+    /// * `entries` - 未知語エントリ
+    /// * `num_categories` - 文字カテゴリの総数
+    ///
+    /// # パニック
+    ///
+    /// エントリのカテゴリIDが`num_categories`以上の場合、パニックします。
+    #[cfg(feature = "train")]
+    pub(crate) fn from_entries<I>(entries: I, num_categories: usize) -> Self
+    where
+        I: IntoIterator<Item = UnkEntry>,
+    {
+        let mut map = vec![vec![]; num_categories];
+        for e in entries {
+            map[usize::from(e.cate_id)].push(e);
+        }
+        Self::from_category_map(map)
+    }
+
+    fn from_category_map(map: Vec<Vec<UnkEntry>>) -> Self {
         let mut offsets = vec![];
         let mut entries = vec![];
         for mut v in map {
@@ -299,17 +359,31 @@ impl UnkHandler {
             entries.append(&mut v);
         }
         offsets.push(entries.len());
-        Ok(Self { offsets, entries })
+        Self { offsets, entries }
     }
 }
 
 impl ArchivedUnkHandler {
+    /// 未知語エントリの数を返します。
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 指定されたカテゴリに未知語エントリが定義されているかどうかを返します。
+    pub(crate) fn has_entries(&self, cate_id: u32) -> bool {
+        let cate_id = usize::from_u32(cate_id);
+        cate_id + 1 < self.offsets.len()
+            && self.offsets[cate_id].to_native() < self.offsets[cate_id + 1].to_native()
+    }
+
     pub fn gen_unk_words<F>(
         &self,
         sent: &Sentence,
         start_char: usize,
         mut has_matched: bool,
         max_grouping_len: Option<usize>,
+        grouping: bool,
         mut f: F,
     ) where
         F: FnMut(UnkWord),
@@ -319,7 +393,13 @@ impl ArchivedUnkHandler {
             return;
         }
 
+        if !grouping {
+            self.scan_entries(start_char, sent.align_end(start_char + 1), cinfo, f);
+            return;
+        }
+
         let mut grouped = false;
+        let mut group_end = None;
         let groupable = sent.groupable(start_char);
         debug_assert_ne!(groupable, 0);
 
@@ -331,25 +411,32 @@ impl ArchivedUnkHandler {
             // Note: Do NOT write `max_grouping_len+1` to avoid overflow.
             if groupable - 1 <= max_grouping_len {
                 f = self.scan_entries(start_char, start_char + groupable, cinfo, f);
+                group_end = Some(start_char + groupable);
                 has_matched = true;
             }
         }
 
+        let mut prev_end = start_char;
         for i in 1..=usize::from(cinfo.length()).min(groupable) {
             if grouped && i == groupable {
                 continue;
             }
-            let end_char = start_char + i;
+            let end_char = sent.align_end(start_char + i);
             if sent.len_char() < end_char {
                 break;
             }
+            // Skips the ends already generated by aligning to grapheme boundaries.
+            if end_char == prev_end || Some(end_char) == group_end {
+                continue;
+            }
+            prev_end = end_char;
             f = self.scan_entries(start_char, end_char, cinfo, f);
             has_matched = true;
         }
 
         // Generates at least one unknown word.
         if !has_matched {
-            self.scan_entries(start_char, start_char + 1, cinfo, f);
+            self.scan_entries(start_char, sent.align_end(start_char + 1), cinfo, f);
         }
     }
 
@@ -395,6 +482,28 @@ impl ArchivedUnkHandler {
     }
 }
 
+
+#[cfg(feature = "legacy")]
+impl From<crate::legacy::dictionary::unknown::UnkHandler> for UnkHandler {
+    fn from(handler: crate::legacy::dictionary::unknown::UnkHandler) -> Self {
+        let crate::legacy::dictionary::unknown::UnkHandler { offsets, entries } = handler;
+        let entries = entries
+            .into_iter()
+            .map(|e| {
+                let crate::legacy::dictionary::unknown::UnkEntry {
+                    cate_id,
+                    left_id,
+                    right_id,
+                    word_cost,
+                    feature,
+                } = e;
+                UnkEntry { cate_id, left_id, right_id, word_cost, feature }
+            })
+            .collect();
+        Self { offsets, entries }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
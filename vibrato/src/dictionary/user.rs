@@ -0,0 +1,432 @@
+//! 事前コンパイルされたユーザー辞書
+//!
+//! 数件の単語を追加するためだけに巨大なシステム辞書全体を再シリアライズせずに済むよう、
+//! ユーザー辞書のみを独立したファイルにコンパイルし、読み込み時に
+//! [`Dictionary::load_user_dictionary`]でシステム辞書に付加します。
+//! 複数のユーザー辞書は[`Dictionary::from_paths`]で優先順に重ねられます。
+//!
+//! コンパイル時にはシステム辞書の接続IDのマッピングが適用され、ファイルには
+//! システム辞書の接続IDの数と単語数が記録されます。付加先の辞書とこれらが
+//! 一致しない場合はエラーとなります。
+
+use std::io::{Read, Write};
+
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+use rkyv::{
+    access, api::serialize_using, ser::allocator::Arena, ser::sharing::Share,
+    ser::writer::IoWriter, ser::Serializer, util::with_arena, Archive, Deserialize, Serialize,
+};
+
+use crate::dictionary::connector::ConnectorView;
+use crate::dictionary::lexicon::ArchivedLexicon;
+use crate::dictionary::mapper::ConnIdMapper;
+use crate::dictionary::{Dictionary, LexType, Lexicon, RKYV_ALIGNMENT};
+use crate::errors::{Result, VibratoError};
+
+/// コンパイル済みユーザー辞書を識別するマジックバイト。
+pub const USER_DICTIONARY_MAGIC: &[u8] = b"VibratoUserDictionaryRkyv 0.6\n";
+
+const PADDING_LEN: usize =
+    (RKYV_ALIGNMENT - (USER_DICTIONARY_MAGIC.len() % RKYV_ALIGNMENT)) % RKYV_ALIGNMENT;
+
+/// ユーザー辞書ファイルの内容
+#[derive(Archive, Serialize, Deserialize)]
+struct UserDictionaryInner {
+    num_left: u32,
+    num_right: u32,
+    num_system_words: u32,
+    lexicon: Lexicon,
+    surfaces: Vec<String>,
+}
+
+/// 付加先のシステム辞書の形状
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct SystemShape {
+    num_left: u32,
+    num_right: u32,
+    num_system_words: u32,
+}
+
+impl SystemShape {
+    /// システム辞書の形状を取得します。
+    fn of(dict: &Dictionary) -> Self {
+        let (num_left, num_right, num_system_words) = match dict {
+            Dictionary::Archived(dict) => (
+                dict.connector().num_left(),
+                dict.connector().num_right(),
+                dict.system_lexicon().num_words(),
+            ),
+            Dictionary::Owned { dict, .. } => (
+                dict.connector().num_left(),
+                dict.connector().num_right(),
+                dict.system_lexicon().num_words(),
+            ),
+        };
+        Self {
+            num_left: u32::try_from(num_left).unwrap(),
+            num_right: u32::try_from(num_right).unwrap(),
+            num_system_words: u32::try_from(num_system_words).unwrap(),
+        }
+    }
+}
+
+impl ConnectorView for SystemShape {
+    fn num_left(&self) -> usize {
+        self.num_left as usize
+    }
+
+    fn num_right(&self) -> usize {
+        self.num_right as usize
+    }
+}
+
+/// システム辞書とは別のファイルに書き出せるコンパイル済みのユーザー辞書
+///
+/// # 例
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::fs::File;
+/// use vibrato_rkyv::dictionary::UserDictionary;
+/// use vibrato_rkyv::{Dictionary, LoadMode};
+///
+/// let dict = Dictionary::from_path("system.dic", LoadMode::TrustCache)?;
+/// UserDictionary::from_reader(File::open("user.csv")?, &dict)?
+///     .write(File::create("user.dicu")?)?;
+///
+/// let dict = dict.load_user_dictionary("user.dicu")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct UserDictionary {
+    inner: UserDictionaryInner,
+}
+
+impl UserDictionary {
+    /// CSV形式のユーザー辞書を、付加先のシステム辞書に合わせてコンパイルします。
+    ///
+    /// 入力の形式は[`DictionaryInner::reset_user_lexicon_from_reader`](super::DictionaryInner::reset_user_lexicon_from_reader)
+    /// と同じです。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - ユーザー辞書ファイル `*.csv` のリーダー
+    /// * `dict` - ユーザー辞書を付加するシステム辞書
+    ///
+    /// # エラー
+    ///
+    /// 入力フォーマットが不正な場合や、システム辞書にない接続IDが含まれる場合に
+    /// [`VibratoError`]を返します。
+    pub fn from_reader<R>(rdr: R, dict: &Dictionary) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut lexicon = Lexicon::from_reader(rdr, LexType::User)?;
+        match dict {
+            Dictionary::Archived(dict) => {
+                if let Some(mapper) = dict.mapper.as_ref() {
+                    let mapper = rkyv::deserialize::<ConnIdMapper, Error>(mapper)?;
+                    lexicon.map_connection_ids(&mapper);
+                }
+            }
+            Dictionary::Owned { dict, .. } => {
+                if let Some(mapper) = dict.mapper() {
+                    lexicon.map_connection_ids(mapper);
+                }
+            }
+        }
+        let shape = SystemShape::of(dict);
+        if !lexicon.verify(&shape) {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "includes invalid connection ids.",
+            ));
+        }
+        let surfaces = lexicon.surfaces().map(<[String]>::to_vec).unwrap_or_default();
+        Ok(Self {
+            inner: UserDictionaryInner {
+                num_left: shape.num_left,
+                num_right: shape.num_right,
+                num_system_words: shape.num_system_words,
+                lexicon,
+                surfaces,
+            },
+        })
+    }
+
+    /// コンパイル済みのユーザー辞書をライターに書き出します。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    ///
+    /// # エラー
+    ///
+    /// 書き込みやシリアライズに失敗した場合に[`VibratoError`]を返します。
+    pub fn write<W>(&self, mut wtr: W) -> Result<()>
+    where
+        W: Write,
+    {
+        wtr.write_all(USER_DICTIONARY_MAGIC)?;
+        wtr.write_all(&[0xFF; PADDING_LEN])?;
+        with_arena(|arena: &mut Arena| {
+            let writer = IoWriter::new(&mut wtr);
+            let mut serializer = Serializer::new(writer, arena.acquire(), Share::new());
+            serialize_using::<_, Error>(&self.inner, &mut serializer)
+        })
+        .map_err(|e| {
+            VibratoError::invalid_state("rkyv serialization failed".to_string(), e.to_string())
+        })?;
+        Ok(())
+    }
+}
+
+/// 読み込まれたユーザー辞書
+///
+/// アーカイブ形式の辞書と同様に、検証済みのバッファを保持してゼロコピーで参照します。
+pub(crate) struct LoadedUserDictionary {
+    _buffer: AlignedVec<16>,
+    data: &'static ArchivedUserDictionaryInner,
+}
+
+impl LoadedUserDictionary {
+    /// コンパイル済みのユーザー辞書を読み込みます。
+    pub(crate) fn read<R>(mut rdr: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut magic = [0; USER_DICTIONARY_MAGIC.len()];
+        rdr.read_exact(&mut magic)?;
+        if magic[..] != *USER_DICTIONARY_MAGIC {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "The magic number of the input user dictionary mismatches.",
+            ));
+        }
+        let mut padding = [0; PADDING_LEN];
+        rdr.read_exact(&mut padding)?;
+
+        let mut buffer = vec![];
+        rdr.read_to_end(&mut buffer)?;
+        let mut aligned_bytes = AlignedVec::<16>::with_capacity(buffer.len());
+        aligned_bytes.extend_from_slice(&buffer);
+
+        let archived =
+            access::<ArchivedUserDictionaryInner, Error>(&aligned_bytes).map_err(|e| {
+                VibratoError::invalid_state(
+                    "rkyv validation failed. The user dictionary file may be corrupted or incompatible."
+                        .to_string(),
+                    e.to_string(),
+                )
+            })?;
+        if archived.lexicon.lex_type() != LexType::User {
+            return Err(VibratoError::invalid_argument(
+                "rdr",
+                "The lexicon is not a user lexicon.",
+            ));
+        }
+
+        // SAFETY: AlignedVec ensures correct alignment and the buffer is owned by Self.
+        let data: &'static ArchivedUserDictionaryInner = unsafe { &*(archived as *const _) };
+        Ok(Self { _buffer: aligned_bytes, data })
+    }
+
+    /// 付加先のシステム辞書とコンパイル時のシステム辞書が一致するかを検証します。
+    pub(crate) fn check(&self, dict: &Dictionary) -> Result<()> {
+        let shape = SystemShape::of(dict);
+        let compiled = SystemShape {
+            num_left: self.data.num_left.to_native(),
+            num_right: self.data.num_right.to_native(),
+            num_system_words: self.data.num_system_words.to_native(),
+        };
+        if shape != compiled {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "The user dictionary was compiled against a different system dictionary.",
+            ));
+        }
+        if !self.data.lexicon.verify(&shape) {
+            return Err(VibratoError::invalid_argument(
+                "path",
+                "The user dictionary includes invalid connection ids.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// ユーザー辞書の語彙を取得します。
+    #[inline(always)]
+    pub(crate) fn lexicon(&self) -> &ArchivedLexicon {
+        &self.data.lexicon
+    }
+
+    /// ユーザー辞書の単語の表層形を取得します。
+    #[inline(always)]
+    pub(crate) fn word_surface(&self, word_id: usize) -> Option<&str> {
+        self.data.surfaces.get(word_id).map(|s| s.as_str())
+    }
+
+    /// ユーザー辞書の全単語の表層形を取得します。
+    pub(crate) fn surfaces(&self) -> Vec<String> {
+        self.data.surfaces.iter().map(|s| s.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use crate::dictionary::{DictionaryInner, WordIdx};
+    use crate::{LoadMode, SystemDictionaryBuilder, Tokenizer};
+
+    const LEX_CSV: &str = include_str!("../tests/resources/lex.csv");
+    const USER_CSV: &str = include_str!("../tests/resources/user.csv");
+    const MATRIX_DEF: &str = include_str!("../tests/resources/matrix.def");
+    const CHAR_DEF: &str = include_str!("../tests/resources/char.def");
+    const UNK_DEF: &str = include_str!("../tests/resources/unk.def");
+
+    fn build() -> DictionaryInner {
+        SystemDictionaryBuilder::from_readers(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn archived() -> Dictionary {
+        let mut buffer = vec![];
+        build().write(&mut buffer).unwrap();
+        Dictionary::read(buffer.as_slice()).unwrap()
+    }
+
+    fn tokenize(dict: Dictionary, sentence: &str) -> Vec<String> {
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence(sentence);
+        worker.tokenize();
+        worker.token_iter().map(|t| t.feature().to_string()).collect()
+    }
+
+    #[test]
+    fn test_load_user_dictionary() {
+        let expected = build()
+            .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+            .unwrap();
+        let expected = tokenize(Dictionary::from_inner(expected), "京都東京都に行った");
+        assert_eq!(expected[0], "カスタム名詞");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        UserDictionary::from_reader(USER_CSV.as_bytes(), &archived())
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+
+        let dict = archived().load_user_dictionary(file.path()).unwrap();
+        assert_eq!(tokenize(dict, "京都東京都に行った"), expected);
+
+        let dict = Dictionary::from_inner(build()).load_user_dictionary(file.path()).unwrap();
+        assert_eq!(tokenize(dict, "京都東京都に行った"), expected);
+    }
+
+    #[test]
+    fn test_word_surface() {
+        let system = WordIdx::new(LexType::System, 3);
+        let user = WordIdx::new(LexType::User, 0);
+
+        let dict = archived();
+        assert_eq!(dict.word_surface(system), Some("京都"));
+        assert!(dict.word_feature(system).starts_with("京都,名詞"));
+        assert_eq!(dict.word_surface(WordIdx::SPACE), None);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        UserDictionary::from_reader(USER_CSV.as_bytes(), &dict)
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+        let dict = dict.load_user_dictionary(file.path()).unwrap();
+        assert_eq!(dict.word_surface(user), Some("京都東京都"));
+        assert_eq!(dict.word_feature(user), "カスタム名詞");
+
+        let mut buffer = vec![];
+        build()
+            .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+            .unwrap()
+            .write(&mut buffer)
+            .unwrap();
+        let dict = Dictionary::read(buffer.as_slice()).unwrap();
+        assert_eq!(dict.word_surface(system), Some("京都"));
+        assert_eq!(dict.word_surface(user), Some("京都東京都"));
+
+        let dict = Dictionary::from_inner(DictionaryInner::read(buffer.as_slice()).unwrap());
+        assert_eq!(dict.word_surface(system), Some("京都"));
+        assert_eq!(dict.word_surface(user), Some("京都東京都"));
+        assert_eq!(dict.word_feature(user), "カスタム名詞");
+    }
+
+    #[test]
+    fn test_from_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.dic");
+        build().write(File::create(&system).unwrap()).unwrap();
+        let first = dir.path().join("first.dicu");
+        UserDictionary::from_reader(USER_CSV.as_bytes(), &archived())
+            .unwrap()
+            .write(File::create(&first).unwrap())
+            .unwrap();
+        let second = dir.path().join("second.dicu");
+        UserDictionary::from_reader("行った,5,5,-5000,レイヤー2".as_bytes(), &archived())
+            .unwrap()
+            .write(File::create(&second).unwrap())
+            .unwrap();
+
+        let dict = Dictionary::from_paths(&system, [&first, &second], LoadMode::Validate).unwrap();
+        // Word ids of the second dictionary follow those of the first one.
+        let layered = WordIdx::new(LexType::User, 3);
+        assert_eq!(dict.word_surface(layered), Some("行った"));
+        assert_eq!(dict.word_feature(layered), "レイヤー2");
+        assert_eq!(dict.word_feature(WordIdx::new(LexType::User, 0)), "カスタム名詞");
+        let features = tokenize(dict, "京都東京都に行った");
+        assert_eq!(features.first().unwrap(), "カスタム名詞");
+        assert_eq!(features.last().unwrap(), "レイヤー2");
+
+        let dict = Dictionary::from_inner(build())
+            .load_user_dictionary(&first)
+            .unwrap()
+            .attach_user_dictionary(
+                LoadedUserDictionary::read(File::open(&second).unwrap()).unwrap(),
+                false,
+            )
+            .unwrap();
+        assert_eq!(dict.word_feature(layered), "レイヤー2");
+        assert_eq!(tokenize(dict, "行った").last().unwrap(), "レイヤー2");
+
+        let dict = Dictionary::from_paths(&system, [&second], LoadMode::Validate).unwrap();
+        assert_eq!(dict.word_feature(WordIdx::new(LexType::User, 0)), "レイヤー2");
+    }
+
+    #[test]
+    fn test_load_user_dictionary_mismatch() {
+        let other = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,名詞".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let other = Dictionary::from_inner(other);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        UserDictionary::from_reader("東京,0,0,1,カスタム名詞".as_bytes(), &other)
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+        assert!(archived().load_user_dictionary(file.path()).is_err());
+
+        assert!(UserDictionary::from_reader("東京,1,1,1,カスタム名詞".as_bytes(), &other).is_err());
+    }
+}
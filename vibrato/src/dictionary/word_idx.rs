@@ -8,7 +8,25 @@ use rkyv::{Archive, Deserialize, Serialize};
 use crate::dictionary::LexType;
 
 /// 単語の識別子
+///
+/// 素性文字列などを複製する代わりにこの識別子を保存しておき、
+/// [`Dictionary::word_feature`](crate::Dictionary::word_feature)や
+/// [`Dictionary::word_surface`](crate::Dictionary::word_surface)で後から参照できます。
+///
+/// # 識別子の安定性
+///
+/// 識別子は同一の辞書ファイルに対してのみ有効です。
+///
+/// - システム辞書の単語IDは語彙CSVの行の順序で割り当てられます。ディレクトリから
+///   ビルドする場合はファイル名順に連結されるため、行やファイルの追加・削除・並べ替えを
+///   伴う再ビルドで変化します。
+/// - ユーザー辞書の単語IDはユーザー辞書CSVの行の順序で割り当てられます。
+/// - 未知語の単語IDは`unk.def`の定義に依存します。
+///   [`UnkProvider`](crate::tokenizer::UnkProvider)が生成した語には、[`WordIdx::SPACE`]の
+///   直前から降順に、プロバイダの登録順で単語IDが割り当てられます。
+/// - 接続IDのマッピングの適用は単語IDを変化させません。
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Archive, Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WordIdx {
     /// この単語を含む辞書の種類
     pub lex_type: LexType,
@@ -24,6 +42,10 @@ impl Default for WordIdx {
 }
 
 impl WordIdx {
+    /// [`Tokenizer::keep_space_tokens()`](crate::Tokenizer::keep_space_tokens)で出力される
+    /// 空白トークンの単語インデックス
+    pub const SPACE: Self = Self::new(LexType::Unknown, u32::MAX);
+
     /// 新しいインスタンスを作成します。
     #[inline(always)]
     pub(crate) const fn new(lex_type: LexType, word_id: u32) -> Self {
@@ -0,0 +1,368 @@
+//! 複数のトークナイザーによるアンサンブル解析
+//!
+//! このモジュールは、同じ入力を複数の[`Tokenizer`]（例えばIPADICとUniDic）で解析し、
+//! 単語境界ごとの投票で結果を統合する[`Ensemble`]を提供します。各境界には
+//! トークナイザー間の一致度を表すスコアが付くため、頑健な分割を得る用途や、
+//! 一致度の高い部分だけを学習用のシルバーデータとして使う用途に使えます。
+//!
+//! 辞書ごとに文字種の定義が異なるため、入力文の内部表現はトークナイザーごとに
+//! 構築されます。位置はすべて同じ入力文の文字単位で比較されます。
+//!
+//! # 例
+//!
+//! ```
+//! use vibrato_rkyv::ensemble::Ensemble;
+//! # use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+//! # let build = |lex: &str| {
+//! #     let dict = SystemDictionaryBuilder::from_readers(
+//! #         lex.as_bytes(),
+//! #         "1 1\n0 0 0".as_bytes(),
+//! #         "DEFAULT 0 1 0".as_bytes(),
+//! #         "DEFAULT,0,0,100,*".as_bytes(),
+//! #     ).unwrap();
+//! #     Tokenizer::from_inner(dict)
+//! # };
+//! # let ipadic = build("自然言語,0,0,1,*\n処理,0,0,1,*");
+//! # let unidic = build("自然,0,0,1,*\n言語,0,0,1,*\n処理,0,0,1,*");
+//!
+//! let mut ensemble = Ensemble::new([ipadic, unidic])?.weights(&[2.0, 1.0])?;
+//! let result = ensemble.tokenize("自然言語処理");
+//!
+//! let segments: Vec<_> = result.segments.iter().map(|s| s.range_char.clone()).collect();
+//! assert_eq!(segments, [0..4, 4..6]);
+//! assert!((result.boundary_scores[2] - 1.0 / 3.0).abs() < 1e-9);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::ops::Range;
+
+use crate::errors::{Result, VibratoError};
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::worker::Worker;
+
+/// 各トークナイザーの解析結果から境界のスコアを求める方法
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Voting {
+    /// 各トークナイザーの1-best解の境界で投票します。
+    ///
+    /// デフォルトの方法です。
+    #[default]
+    Boundary,
+
+    /// 各トークナイザーのN-best解のコストを確率に正規化し、境界を含む解の確率の和を
+    /// そのトークナイザーの票とします。
+    ///
+    /// 解`i`の確率は`exp(-cost_i / temperature)`を正規化したものです。コストの尺度は
+    /// 辞書ごとに異なるため、辞書ごとに正規化してから統合します。
+    CostNormalized {
+        /// 各トークナイザーで求める解の数
+        nbest: usize,
+        /// コストを確率に変換する際の温度。大きいほど確率が平坦になります
+        temperature: f64,
+    },
+}
+
+/// 統合された分割の単語
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// 文字単位の位置範囲
+    pub range_char: Range<usize>,
+
+    /// バイト単位の位置範囲
+    pub range_byte: Range<usize>,
+
+    /// この位置範囲を1語とする票の重み付き割合（0から1）
+    pub agreement: f64,
+}
+
+/// アンサンブル解析の結果
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnsembleResult {
+    /// 各文字位置の境界スコア（0から1）
+    ///
+    /// `i`番目の値は、`i`文字目の直前を単語境界とする票の重み付き割合です。
+    /// 長さは入力文の文字数に1を足したものです。
+    pub boundary_scores: Vec<f64>,
+
+    /// 境界スコアがしきい値以上の位置で区切った単語列
+    ///
+    /// 空白のみからなる単語は含まれません。
+    pub segments: Vec<Segment>,
+}
+
+/// 複数のトークナイザーによるアンサンブル解析器
+pub struct Ensemble {
+    workers: Vec<Worker>,
+    weights: Vec<f64>,
+    voting: Voting,
+    threshold: f64,
+}
+
+impl Ensemble {
+    /// 新しいインスタンスを作成します。
+    ///
+    /// 重みはすべて`1.0`、投票の方法は[`Voting::Boundary`]、しきい値は`0.5`に設定されます。
+    ///
+    /// # 引数
+    ///
+    /// * `tokenizers` - 統合するトークナイザー
+    ///
+    /// # エラー
+    ///
+    /// `tokenizers`が空の場合に[`VibratoError`]が返されます。
+    pub fn new<I>(tokenizers: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = Tokenizer>,
+    {
+        let workers: Vec<_> = tokenizers.into_iter().map(|t| t.new_worker()).collect();
+        if workers.is_empty() {
+            return Err(VibratoError::invalid_argument(
+                "tokenizers",
+                "At least one tokenizer is required.",
+            ));
+        }
+        let weights = vec![1.0; workers.len()];
+        Ok(Self {
+            workers,
+            weights,
+            voting: Voting::Boundary,
+            threshold: 0.5,
+        })
+    }
+
+    /// 各トークナイザーの票の重みを設定します。
+    ///
+    /// # 引数
+    ///
+    /// * `weights` - トークナイザーと同じ順序の重み
+    ///
+    /// # エラー
+    ///
+    /// 重みの数がトークナイザーの数と異なる場合や、負または有限でない重みを含む場合、
+    /// 重みの和が0の場合に[`VibratoError`]が返されます。
+    pub fn weights(mut self, weights: &[f64]) -> Result<Self> {
+        if weights.len() != self.workers.len() {
+            return Err(VibratoError::invalid_argument(
+                "weights",
+                "The number of weights must match the number of tokenizers.",
+            ));
+        }
+        if weights.iter().any(|&w| !w.is_finite() || w < 0.0) || weights.iter().sum::<f64>() <= 0.0
+        {
+            return Err(VibratoError::invalid_argument(
+                "weights",
+                "The weights must be non-negative and finite, and their sum must be positive.",
+            ));
+        }
+        self.weights = weights.to_vec();
+        Ok(self)
+    }
+
+    /// 投票の方法を設定します。
+    ///
+    /// # エラー
+    ///
+    /// [`Voting::CostNormalized`]の`nbest`が0の場合や、`temperature`が正の有限値でない
+    /// 場合に[`VibratoError`]が返されます。
+    pub fn voting(mut self, voting: Voting) -> Result<Self> {
+        if let Voting::CostNormalized { nbest, temperature } = voting {
+            if nbest == 0 {
+                return Err(VibratoError::invalid_argument(
+                    "voting",
+                    "nbest must be at least 1.",
+                ));
+            }
+            if !temperature.is_finite() || temperature <= 0.0 {
+                return Err(VibratoError::invalid_argument(
+                    "voting",
+                    "temperature must be positive and finite.",
+                ));
+            }
+        }
+        self.voting = voting;
+        Ok(self)
+    }
+
+    /// 単語境界とみなす境界スコアのしきい値を設定します。
+    ///
+    /// デフォルトは`0.5`で、重み付きの票の半数以上が境界とする位置で区切ります。
+    pub const fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// 文を解析し、結果を統合します。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 解析する文
+    ///
+    /// # 戻り値
+    ///
+    /// 統合された解析結果
+    pub fn tokenize(&mut self, text: &str) -> EnsembleResult {
+        let len_char = text.chars().count();
+        if len_char == 0 {
+            return EnsembleResult::default();
+        }
+        let mut c2b: Vec<_> = text.char_indices().map(|(i, _)| i).collect();
+        c2b.push(text.len());
+
+        let total_weight: f64 = self.weights.iter().sum();
+        let mut boundary_scores = vec![0.0; len_char + 1];
+        let mut spans = vec![];
+        for (worker, &weight) in self.workers.iter_mut().zip(&self.weights) {
+            for (path, prob) in paths(worker, text, self.voting) {
+                let vote = weight * prob / total_weight;
+                let mut bounds: Vec<_> = path.iter().flat_map(|r| [r.start, r.end]).collect();
+                // Adjacent tokens share a boundary, which is voted for once.
+                bounds.dedup();
+                for pos in bounds {
+                    boundary_scores[pos] += vote;
+                }
+                spans.push((path, vote));
+            }
+        }
+        // Start and end of a sentence are always boundaries.
+        boundary_scores[0] = 1.0;
+        boundary_scores[len_char] = 1.0;
+
+        let mut segments = vec![];
+        let mut start = 0;
+        for end in 1..=len_char {
+            if boundary_scores[end] < self.threshold && end != len_char {
+                continue;
+            }
+            let range_byte = c2b[start]..c2b[end];
+            if !text[range_byte.clone()].trim().is_empty() {
+                let agreement = spans
+                    .iter()
+                    .filter(|(path, _)| path.contains(&(start..end)))
+                    .map(|(_, vote)| vote)
+                    .sum();
+                segments.push(Segment {
+                    range_char: start..end,
+                    range_byte,
+                    agreement,
+                });
+            }
+            start = end;
+        }
+
+        EnsembleResult {
+            boundary_scores,
+            segments,
+        }
+    }
+}
+
+/// トークナイザーの解析結果を、各解のトークンの位置範囲とその確率の組として返します。
+fn paths(worker: &mut Worker, text: &str, voting: Voting) -> Vec<(Vec<Range<usize>>, f64)> {
+    worker.reset_sentence(text);
+    match voting {
+        Voting::Boundary => {
+            worker.tokenize();
+            vec![(worker.token_iter().map(|t| t.range_char()).collect(), 1.0)]
+        }
+        Voting::CostNormalized { nbest, temperature } => {
+            worker.tokenize_nbest(nbest);
+            let costs: Vec<_> = (0..worker.num_nbest_paths())
+                .map(|i| f64::from(worker.path_cost(i).unwrap()))
+                .collect();
+            let min_cost = costs.iter().copied().fold(f64::INFINITY, f64::min);
+            let scores: Vec<_> = costs
+                .iter()
+                .map(|c| (-(c - min_cost) / temperature).exp())
+                .collect();
+            let total: f64 = scores.iter().sum();
+            scores
+                .iter()
+                .enumerate()
+                .map(|(i, score)| {
+                    let ranges = worker
+                        .nbest_token_iter(i)
+                        .unwrap()
+                        .map(|t| t.range_char())
+                        .collect();
+                    (ranges, score / total)
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SystemDictionaryBuilder;
+
+    fn tokenizer(lexicon_csv: &str) -> Tokenizer {
+        let dict = SystemDictionaryBuilder::from_readers(
+            lexicon_csv.as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0\nSPACE 0 1 0\n0x0020 SPACE".as_bytes(),
+            "DEFAULT,0,0,100,*\nSPACE,0,0,0,*".as_bytes(),
+        )
+        .unwrap();
+        Tokenizer::from_inner(dict)
+    }
+
+    #[test]
+    fn test_boundary_voting() {
+        let coarse = tokenizer("自然言語,0,0,1,*\n処理,0,0,1,*");
+        let fine = tokenizer("自然,0,0,1,*\n言語,0,0,1,*\n処理,0,0,1,*");
+
+        let mut ensemble = Ensemble::new([coarse.clone(), fine.clone(), fine.clone()]).unwrap();
+        let result = ensemble.tokenize("自然言語処理");
+        assert_eq!(result.boundary_scores.len(), 7);
+        assert!((result.boundary_scores[2] - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(result.boundary_scores[1], 0.0);
+        let segments: Vec<_> = result.segments.iter().map(|s| s.range_char.clone()).collect();
+        assert_eq!(segments, [0..2, 2..4, 4..6]);
+        assert!((result.segments[0].agreement - 2.0 / 3.0).abs() < 1e-9);
+        assert!((result.segments[2].agreement - 1.0).abs() < 1e-9);
+
+        let mut ensemble = ensemble.threshold(0.9);
+        let result = ensemble.tokenize("自然言語 処理");
+        let segments: Vec<_> = result.segments.iter().map(|s| s.range_byte.clone()).collect();
+        assert_eq!(segments, [0..12, 13..19]);
+
+        let mut ensemble = Ensemble::new([coarse.ignore_space(true).unwrap(), fine]).unwrap();
+        assert!(ensemble.tokenize("").segments.is_empty());
+        let result = ensemble.tokenize("自然言語 処理");
+        assert_eq!(result.segments.last().unwrap().range_char, 5..7);
+    }
+
+    #[test]
+    fn test_cost_normalized_voting() {
+        let coarse = tokenizer("自然言語,0,0,1,*\n自然,0,0,1,*\n言語,0,0,1,*");
+        let mut ensemble = Ensemble::new([coarse])
+            .unwrap()
+            .voting(Voting::CostNormalized {
+                nbest: 2,
+                temperature: 1.0,
+            })
+            .unwrap();
+        let result = ensemble.tokenize("自然言語");
+        let p = 1.0 / (1.0 + (-1.0f64).exp());
+        assert!((result.boundary_scores[2] - (1.0 - p)).abs() < 1e-9);
+        assert_eq!(result.segments.len(), 1);
+        assert!((result.segments[0].agreement - p).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(Ensemble::new(Vec::<Tokenizer>::new()).is_err());
+        let ensemble = || Ensemble::new([tokenizer("自然,0,0,1,*")]).unwrap();
+        assert!(ensemble().weights(&[1.0, 1.0]).is_err());
+        assert!(ensemble().weights(&[0.0]).is_err());
+        assert!(ensemble().weights(&[f64::NAN]).is_err());
+        assert!(ensemble()
+            .voting(Voting::CostNormalized { nbest: 0, temperature: 1.0 })
+            .is_err());
+        assert!(ensemble()
+            .voting(Voting::CostNormalized { nbest: 1, temperature: 0.0 })
+            .is_err());
+    }
+}
@@ -37,6 +37,12 @@ pub enum VibratoError {
     #[error(transparent)]
     InvalidState(InvalidStateError),
 
+    /// 資源の上限超過エラー
+    ///
+    /// [`ResourceLimitError`]のエラーバリアント。
+    #[error(transparent)]
+    ResourceLimit(ResourceLimitError),
+
     /// 整数変換エラー
     ///
     /// [`TryFromIntError`](std::num::TryFromIntError)のエラーバリアント。
@@ -118,6 +124,7 @@ pub enum VibratoError {
     /// 一時ファイルの永続化エラー
     ///
     /// [`tempfile::PathPersistError`](tempfile::PathPersistError)のエラーバリアント。
+    #[cfg(feature = "loaders")]
     #[error(transparent)]
     PathPersist(#[from] tempfile::PersistError),
 }
@@ -171,6 +178,16 @@ impl VibratoError {
             cause: cause.into(),
         })
     }
+
+    /// 資源の上限超過エラーを生成します
+    ///
+    /// # 引数
+    ///
+    /// * `resource` - 資源の名前
+    /// * `limit` - 設定された上限
+    pub(crate) const fn resource_limit(resource: &'static str, limit: usize) -> Self {
+        Self::ResourceLimit(ResourceLimitError { resource, limit })
+    }
 }
 
 /// 引数が無効な場合に使用されるエラー
@@ -227,6 +244,36 @@ impl fmt::Display for InvalidStateError {
 
 impl Error for InvalidStateError {}
 
+/// 設定された資源の上限を超えた場合に使用されるエラー
+#[derive(Debug)]
+pub struct ResourceLimitError {
+    /// 資源の名前
+    pub(crate) resource: &'static str,
+
+    /// 設定された上限
+    pub(crate) limit: usize,
+}
+
+impl ResourceLimitError {
+    /// 上限を超えた資源の名前を返します
+    pub const fn resource(&self) -> &'static str {
+        self.resource
+    }
+
+    /// 設定された上限を返します
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for ResourceLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceLimitError: {} exceeded the limit of {}", self.resource, self.limit)
+    }
+}
+
+impl Error for ResourceLimitError {}
+
 /// ダウンロード関連のエラー
 ///
 /// `download`フィーチャーが有効な場合のみ利用可能です。
@@ -265,6 +312,14 @@ pub enum DownloadError {
     /// パスの永続化エラー
     #[error(transparent)]
     PathPersist(#[from] tempfile::PersistError),
+
+    /// オフラインモードで辞書が保存されていない
+    #[error("The dictionary '{0}' is not available locally and offline mode is enabled.")]
+    Offline(String),
+
+    /// 不正なダウンロードURL
+    #[error("Invalid download URL: {0}")]
+    InvalidUrl(String),
 }
 
 impl From<std::num::TryFromIntError> for VibratoError {
@@ -0,0 +1,510 @@
+//! MeCab互換の出力フォーマット
+//!
+//! このモジュールは、MeCabの`--node-format`および`--eos-format`と互換性のある
+//! フォーマット文字列を解釈し、トークンを任意の形式で出力する機能を提供します。
+//!
+//! # サポートされる指定子
+//!
+//! | 指定子 | 内容 |
+//! |---|---|
+//! | `%m`, `%M` | 表層形 |
+//! | `%H` | 素性全体 |
+//! | `%f[N]` | 素性のN番目（0始まり）の項目 |
+//! | `%f[N,M,...]` | 素性の複数の項目を`,`区切りで連結したもの |
+//! | `%FC[N,M,...]` | 素性の複数の項目を文字`C`で区切って連結したもの |
+//! | `%s` | 単語の種類（`0`: 既知語、`1`: 未知語） |
+//! | `%L` | 由来する辞書のタイプ（`system`, `user`, `unknown`） |
+//! | `%c`, `%pw` | 単語コスト |
+//! | `%pc` | 文頭からの累積コスト |
+//! | `%phl` | 左文脈ID |
+//! | `%phr` | 右文脈ID |
+//! | `%ps` | 開始バイト位置 |
+//! | `%pe` | 終了バイト位置 |
+//! | `%pS` | 開始文字位置 |
+//! | `%pE` | 終了文字位置 |
+//! | `%%` | `%` |
+//!
+//! また、エスケープシーケンス`\t`, `\n`, `\s`（空白）, `\\`, `\0`を使用できます。
+//! 存在しない素性の項目は空文字列として出力されます。
+//!
+//! # 例
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use vibrato_rkyv::format::OutputFormatter;
+//! use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+//!
+//! let dict = SystemDictionaryBuilder::from_readers(
+//!     "走る,0,0,1,動詞,自立,*,*,五段・ラ行,基本形,走る".as_bytes(),
+//!     "1 1\n0 0 0".as_bytes(),
+//!     "DEFAULT 0 1 0".as_bytes(),
+//!     "DEFAULT,0,0,100,*".as_bytes(),
+//! )?;
+//! let tokenizer = Tokenizer::from_inner(dict);
+//! let mut worker = tokenizer.new_worker();
+//! worker.reset_sentence("走る");
+//! worker.tokenize();
+//!
+//! let formatter = OutputFormatter::new("%m\\t%f[6]\\n")?;
+//! assert_eq!(formatter.format(&worker.token(0)), "走る\t走る\n");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+use std::ops::Range;
+
+use crate::dictionary::LexType;
+use crate::errors::{Result, VibratoError};
+use crate::token::{NbestToken, Token};
+use crate::utils;
+
+/// フォーマット文字列を構成する要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Surface,
+    Feature,
+    FeatureFields(Vec<usize>, char),
+    Stat,
+    LexType,
+    WordCost,
+    TotalCost,
+    LeftId,
+    RightId,
+    StartByte,
+    EndByte,
+    StartChar,
+    EndChar,
+}
+
+impl Piece {
+    #[inline(always)]
+    const fn needs_fields(&self) -> bool {
+        matches!(self, Self::FeatureFields(..))
+    }
+}
+
+/// MeCab互換のフォーマット文字列に従ってトークンを整形するフォーマッター
+///
+/// 使用できる指定子については[モジュールのドキュメント](self)を参照してください。
+#[derive(Debug, Clone)]
+pub struct OutputFormatter {
+    node: Vec<Piece>,
+    eos: String,
+    needs_fields: bool,
+}
+
+impl OutputFormatter {
+    /// ノードのフォーマット文字列から新しいフォーマッターを作成します。
+    ///
+    /// EOSのフォーマットは`EOS\n`に設定されます。
+    ///
+    /// # 引数
+    ///
+    /// * `node_format` - 各トークンに適用するフォーマット文字列
+    ///
+    /// # エラー
+    ///
+    /// フォーマット文字列が不正な場合、[`VibratoError`]が返されます。
+    pub fn new(node_format: &str) -> Result<Self> {
+        let node = parse_format(node_format, "node_format")?;
+        let needs_fields = node.iter().any(Piece::needs_fields);
+        Ok(Self {
+            node,
+            eos: "EOS\n".to_string(),
+            needs_fields,
+        })
+    }
+
+    /// EOSのフォーマット文字列を設定します。
+    ///
+    /// EOSのフォーマットではエスケープシーケンスと`%%`のみが使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `eos_format` - 文末に出力するフォーマット文字列
+    ///
+    /// # エラー
+    ///
+    /// フォーマット文字列が不正な場合、またはトークンを参照する指定子が
+    /// 含まれる場合、[`VibratoError`]が返されます。
+    pub fn eos_format(mut self, eos_format: &str) -> Result<Self> {
+        let mut eos = String::new();
+        for piece in parse_format(eos_format, "eos_format")? {
+            match piece {
+                Piece::Literal(s) => eos.push_str(&s),
+                _ => {
+                    return Err(VibratoError::invalid_argument(
+                        "eos_format",
+                        "Token specifiers are not allowed in the EOS format.",
+                    ));
+                }
+            }
+        }
+        self.eos = eos;
+        Ok(self)
+    }
+
+    /// トークンを整形した文字列を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `token` - 整形するトークン
+    pub fn format(&self, token: &Token) -> String {
+        let mut buf = vec![];
+        self.write_token(&mut buf, token).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    /// トークンを整形して書き出します。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `token` - 整形するトークン
+    ///
+    /// # エラー
+    ///
+    /// 書き込みに失敗した場合、[`std::io::Error`]が返されます。
+    pub fn write_token<W>(&self, wtr: W, token: &Token) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        self.write_view(wtr, token)
+    }
+
+    /// N-bestパス中のトークンを整形して書き出します。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    /// * `token` - 整形するトークン
+    ///
+    /// # エラー
+    ///
+    /// 書き込みに失敗した場合、[`std::io::Error`]が返されます。
+    pub fn write_nbest_token<W>(&self, wtr: W, token: &NbestToken) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        self.write_view(wtr, token)
+    }
+
+    /// トークンを整形して書き出します（内部関数）
+    fn write_view<W, T>(&self, mut wtr: W, token: &T) -> std::io::Result<()>
+    where
+        W: Write,
+        T: TokenView,
+    {
+        let fields = if self.needs_fields {
+            utils::parse_csv_row(token.feature())
+        } else {
+            vec![]
+        };
+        for piece in &self.node {
+            match piece {
+                Piece::Literal(s) => wtr.write_all(s.as_bytes())?,
+                Piece::Surface => wtr.write_all(token.surface().as_bytes())?,
+                Piece::Feature => wtr.write_all(token.feature().as_bytes())?,
+                Piece::FeatureFields(indices, sep) => {
+                    for (i, &idx) in indices.iter().enumerate() {
+                        if i != 0 {
+                            write!(wtr, "{sep}")?;
+                        }
+                        if let Some(field) = fields.get(idx) {
+                            wtr.write_all(field.as_bytes())?;
+                        }
+                    }
+                }
+                Piece::Stat => {
+                    let stat = u8::from(token.lex_type() == LexType::Unknown);
+                    write!(wtr, "{stat}")?;
+                }
+                Piece::LexType => {
+                    let name = match token.lex_type() {
+                        LexType::System => "system",
+                        LexType::User => "user",
+                        LexType::Unknown => "unknown",
+                    };
+                    wtr.write_all(name.as_bytes())?;
+                }
+                Piece::WordCost => write!(wtr, "{}", token.word_cost())?,
+                Piece::TotalCost => write!(wtr, "{}", token.total_cost())?,
+                Piece::LeftId => write!(wtr, "{}", token.left_id())?,
+                Piece::RightId => write!(wtr, "{}", token.right_id())?,
+                Piece::StartByte => write!(wtr, "{}", token.range_byte().start)?,
+                Piece::EndByte => write!(wtr, "{}", token.range_byte().end)?,
+                Piece::StartChar => write!(wtr, "{}", token.range_char().start)?,
+                Piece::EndChar => write!(wtr, "{}", token.range_char().end)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// EOSの文字列を返します。
+    #[inline(always)]
+    pub fn eos(&self) -> &str {
+        &self.eos
+    }
+}
+
+/// 整形に必要なトークンの情報
+trait TokenView {
+    fn surface(&self) -> &str;
+    fn feature(&self) -> &str;
+    fn lex_type(&self) -> LexType;
+    fn word_cost(&self) -> i16;
+    fn total_cost(&self) -> i32;
+    fn left_id(&self) -> u16;
+    fn right_id(&self) -> u16;
+    fn range_byte(&self) -> Range<usize>;
+    fn range_char(&self) -> Range<usize>;
+}
+
+macro_rules! impl_token_view {
+    ($t:ident) => {
+        impl TokenView for $t<'_> {
+            #[inline(always)]
+            fn surface(&self) -> &str {
+                $t::surface(self)
+            }
+            #[inline(always)]
+            fn feature(&self) -> &str {
+                $t::feature(self)
+            }
+            #[inline(always)]
+            fn lex_type(&self) -> LexType {
+                $t::lex_type(self)
+            }
+            #[inline(always)]
+            fn word_cost(&self) -> i16 {
+                $t::word_cost(self)
+            }
+            #[inline(always)]
+            fn total_cost(&self) -> i32 {
+                $t::total_cost(self)
+            }
+            #[inline(always)]
+            fn left_id(&self) -> u16 {
+                $t::left_id(self)
+            }
+            #[inline(always)]
+            fn right_id(&self) -> u16 {
+                $t::right_id(self)
+            }
+            #[inline(always)]
+            fn range_byte(&self) -> Range<usize> {
+                $t::range_byte(self)
+            }
+            #[inline(always)]
+            fn range_char(&self) -> Range<usize> {
+                $t::range_char(self)
+            }
+        }
+    };
+}
+
+impl_token_view!(Token);
+impl_token_view!(NbestToken);
+
+/// フォーマット文字列を解析します（内部関数）
+fn parse_format(format: &str, arg: &'static str) -> Result<Vec<Piece>> {
+    let mut pieces = vec![];
+    let mut literal = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('s') => ' ',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some(c) => c,
+                    None => {
+                        return Err(VibratoError::invalid_argument(
+                            arg,
+                            "The format ends with an incomplete escape sequence.",
+                        ));
+                    }
+                };
+                literal.push(escaped);
+            }
+            '%' => {
+                let piece = match chars.next() {
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some('m' | 'M') => Piece::Surface,
+                    Some('H') => Piece::Feature,
+                    Some('s') => Piece::Stat,
+                    Some('L') => Piece::LexType,
+                    Some('c') => Piece::WordCost,
+                    Some('f') => Piece::FeatureFields(parse_indices(&mut chars, arg)?, ','),
+                    Some('F') => {
+                        let sep = chars.next().ok_or_else(|| {
+                            VibratoError::invalid_argument(arg, "%F requires a separator.")
+                        })?;
+                        Piece::FeatureFields(parse_indices(&mut chars, arg)?, sep)
+                    }
+                    Some('p') => match chars.next() {
+                        Some('w') => Piece::WordCost,
+                        Some('c') => Piece::TotalCost,
+                        Some('s') => Piece::StartByte,
+                        Some('e') => Piece::EndByte,
+                        Some('S') => Piece::StartChar,
+                        Some('E') => Piece::EndChar,
+                        Some('h') => match chars.next() {
+                            Some('l') => Piece::LeftId,
+                            Some('r') => Piece::RightId,
+                            _ => {
+                                return Err(VibratoError::invalid_argument(
+                                    arg,
+                                    "%ph must be followed by l or r.",
+                                ));
+                            }
+                        },
+                        c => {
+                            let msg = format!("Unsupported specifier: %p{}", c.unwrap_or(' '));
+                            return Err(VibratoError::invalid_argument(arg, msg));
+                        }
+                    },
+                    c => {
+                        let msg = format!("Unsupported specifier: %{}", c.unwrap_or(' '));
+                        return Err(VibratoError::invalid_argument(arg, msg));
+                    }
+                };
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(piece);
+            }
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+/// `[N,M,...]`形式のインデックス列を解析します（内部関数）
+fn parse_indices<I>(chars: &mut I, arg: &'static str) -> Result<Vec<usize>>
+where
+    I: Iterator<Item = char>,
+{
+    if chars.next() != Some('[') {
+        return Err(VibratoError::invalid_argument(
+            arg,
+            "Feature indices must be enclosed in brackets, e.g., %f[0].",
+        ));
+    }
+    let mut body = String::new();
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some(c) => body.push(c),
+            None => {
+                return Err(VibratoError::invalid_argument(arg, "Unclosed bracket."));
+            }
+        }
+    }
+    let mut indices = vec![];
+    for idx in body.split(',') {
+        let idx = idx.trim().parse().map_err(|_| {
+            let msg = format!("Invalid feature index: {idx}");
+            VibratoError::invalid_argument(arg, msg)
+        })?;
+        indices.push(idx);
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::SystemDictionaryBuilder;
+    use crate::Tokenizer;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("%m\\t%f[6]\\n", "test").unwrap(),
+            vec![
+                Piece::Surface,
+                Piece::Literal("\t".to_string()),
+                Piece::FeatureFields(vec![6], ','),
+                Piece::Literal("\n".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_format("%F-[0,1]%%", "test").unwrap(),
+            vec![
+                Piece::FeatureFields(vec![0, 1], '-'),
+                Piece::Literal("%".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_format_invalid() {
+        assert!(parse_format("%x", "test").is_err());
+        assert!(parse_format("%f6", "test").is_err());
+        assert!(parse_format("%f[6", "test").is_err());
+        assert!(parse_format("%f[a]", "test").is_err());
+        assert!(parse_format("\\", "test").is_err());
+    }
+
+    #[test]
+    fn test_format_token() {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,名詞,\"a,b\",シゼン".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語");
+        worker.tokenize();
+        assert_eq!(worker.num_tokens(), 2);
+
+        let formatter = OutputFormatter::new("%m %F/[0,2,5] %s %L %pS-%pE %ps-%pe %c").unwrap();
+        assert_eq!(
+            formatter.format(&worker.token(0)),
+            "自然 名詞/シゼン/ 0 system 0-2 0-6 1"
+        );
+        assert_eq!(
+            formatter.format(&worker.token(1)),
+            "言語 *// 1 unknown 2-4 6-12 100"
+        );
+        assert_eq!(
+            OutputFormatter::new("%f[1]").unwrap().format(&worker.token(0)),
+            "a,b"
+        );
+
+        worker.tokenize_nbest(1);
+        let token = worker.nbest_token_iter(0).unwrap().next().unwrap();
+        let mut buf = vec![];
+        formatter.write_nbest_token(&mut buf, &token).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "自然 名詞/シゼン/ 0 system 0-2 0-6 1"
+        );
+    }
+
+    #[test]
+    fn test_eos_format() {
+        let formatter = OutputFormatter::new("%m").unwrap();
+        assert_eq!(formatter.eos(), "EOS\n");
+        let formatter = formatter.eos_format("\\n").unwrap();
+        assert_eq!(formatter.eos(), "\n");
+        assert!(OutputFormatter::new("%m").unwrap().eos_format("%m").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! ふりがな（ルビ）の生成
+//!
+//! このモジュールは、トークンの表層形に含まれる漢字部分と、素性に含まれる読みとを
+//! 対応付けてルビを生成する機能を提供します。送り仮名を含む語（例: 「書き換える」）に
+//! 対しては、表層形中の仮名部分を読みの中で照合することで、漢字部分にのみ
+//! ルビを割り当てます。
+//!
+//! 通常は[`Worker::furigana()`](crate::tokenizer::worker::Worker::furigana)を通じて
+//! 利用します。
+
+use std::ops::Range;
+
+/// 入力文中の一区間と、そのルビ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Furigana {
+    /// ルビを振る区間の文字単位の位置範囲
+    pub range_char: Range<usize>,
+
+    /// ルビを振る区間のバイト単位の位置範囲
+    pub range_byte: Range<usize>,
+
+    /// ルビ（ひらがな）
+    pub ruby: String,
+}
+
+/// 表層形と読みを対応付け、ルビを振るべき区間とそのルビを返します。
+///
+/// 表層形を仮名部分とそれ以外の部分に分割し、仮名部分を読みの中で照合することで
+/// 残りの部分の読みを決定します。照合には最長一致を優先したバックトラックを用います。
+/// 読みはカタカナ・ひらがなのいずれでもよく、ルビはひらがなで返されます。
+///
+/// 表層形に漢字が含まれない場合は空のベクターを返します。仮名部分の照合に失敗した
+/// 場合は、表層形全体に読み全体を割り当てます。
+///
+/// # 引数
+///
+/// * `surface` - 表層形
+/// * `reading` - 表層形の読み
+///
+/// # 戻り値
+///
+/// 表層形内の文字単位の範囲とルビの組のベクター
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::furigana::align;
+///
+/// let pairs = align("書き換える", "カキカエル");
+/// assert_eq!(pairs, vec![(0..1, "か".to_string()), (2..3, "か".to_string())]);
+/// ```
+pub fn align(surface: &str, reading: &str) -> Vec<(Range<usize>, String)> {
+    let surface: Vec<char> = surface.chars().map(to_hiragana).collect();
+    let reading: Vec<char> = reading.chars().map(to_hiragana).collect();
+
+    if reading.is_empty() || !surface.iter().any(|&c| is_kanji(c)) {
+        return vec![];
+    }
+
+    let mut segments = vec![];
+    let mut start = 0;
+    for i in 1..=surface.len() {
+        if i == surface.len() || is_kana(surface[i]) != is_kana(surface[start]) {
+            segments.push((is_kana(surface[start]), start..i));
+            start = i;
+        }
+    }
+
+    let mut aligned = vec![];
+    if align_segments(&surface, &segments, &reading, 0, &mut aligned) {
+        aligned
+            .into_iter()
+            .map(|(range, r)| (range, reading[r].iter().collect()))
+            .collect()
+    } else {
+        vec![(0..surface.len(), reading.iter().collect())]
+    }
+}
+
+/// `segments[i..]`を`reading[pos..]`に再帰的に対応付けます（内部関数）
+fn align_segments(
+    surface: &[char],
+    segments: &[(bool, Range<usize>)],
+    reading: &[char],
+    pos: usize,
+    aligned: &mut Vec<(Range<usize>, Range<usize>)>,
+) -> bool {
+    let Some((is_kana, range)) = segments.first() else {
+        return pos == reading.len();
+    };
+    let rest = &segments[1..];
+
+    if *is_kana {
+        let end = pos + range.len();
+        return end <= reading.len()
+            && surface[range.clone()] == reading[pos..end]
+            && align_segments(surface, rest, reading, end, aligned);
+    }
+
+    if rest.is_empty() {
+        if pos == reading.len() {
+            return false;
+        }
+        aligned.push((range.clone(), pos..reading.len()));
+        return true;
+    }
+
+    // Prefers the longest reading for the non-kana segment.
+    for end in (pos + 1..=reading.len()).rev() {
+        aligned.push((range.clone(), pos..end));
+        if align_segments(surface, rest, reading, end, aligned) {
+            return true;
+        }
+        aligned.pop();
+    }
+    false
+}
+
+/// ルビ付きの HTML を生成します。
+///
+/// `furigana`の各区間を`<ruby>`要素で囲み、それ以外の部分はそのまま出力します。
+/// 入力文およびルビに含まれる HTML の特殊文字はエスケープされます。
+///
+/// # 引数
+///
+/// * `text` - 入力文
+/// * `furigana` - [`Worker::furigana()`](crate::tokenizer::worker::Worker::furigana)
+///   などで得られた、位置順に並んだルビのスライス
+///
+/// # 戻り値
+///
+/// HTML 文字列
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::furigana::{to_html, Furigana};
+///
+/// let furigana = vec![Furigana { range_char: 0..2, range_byte: 0..6, ruby: "とうきょう".to_string() }];
+/// assert_eq!(
+///     to_html("東京へ", &furigana),
+///     "<ruby>東京<rp>(</rp><rt>とうきょう</rt><rp>)</rp></ruby>へ",
+/// );
+/// ```
+pub fn to_html(text: &str, furigana: &[Furigana]) -> String {
+    let mut html = String::with_capacity(text.len() * 2);
+    let mut last = 0;
+    for f in furigana {
+        escape_html(&text[last..f.range_byte.start], &mut html);
+        html.push_str("<ruby>");
+        escape_html(&text[f.range_byte.clone()], &mut html);
+        html.push_str("<rp>(</rp><rt>");
+        escape_html(&f.ruby, &mut html);
+        html.push_str("</rt><rp>)</rp></ruby>");
+        last = f.range_byte.end;
+    }
+    escape_html(&text[last..], &mut html);
+    html
+}
+
+fn escape_html(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[inline(always)]
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap(),
+        _ => c,
+    }
+}
+
+#[inline(always)]
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}')
+}
+
+#[inline(always)]
+fn is_kanji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{20000}'..='\u{2FA1F}'
+            | '々'
+            | '〆'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_kanji_only() {
+        assert_eq!(align("東京", "トウキョウ"), vec![(0..2, "とうきょう".to_string())]);
+    }
+
+    #[test]
+    fn test_align_okurigana() {
+        assert_eq!(
+            align("取り扱い", "トリアツカイ"),
+            vec![(0..1, "と".to_string()), (2..3, "あつか".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_align_prefix_kana() {
+        assert_eq!(align("お茶", "オチャ"), vec![(1..2, "ちゃ".to_string())]);
+    }
+
+    #[test]
+    fn test_align_no_kanji() {
+        assert!(align("ひらがな", "ヒラガナ").is_empty());
+        assert!(align("kampersanda", "*").is_empty());
+    }
+
+    #[test]
+    fn test_align_mismatch() {
+        assert_eq!(align("書き", "ショ"), vec![(0..2, "しょ".to_string())]);
+    }
+
+    #[test]
+    fn test_to_html_escape() {
+        let furigana = vec![Furigana {
+            range_char: 2..3,
+            range_byte: 2..5,
+            ruby: "き".to_string(),
+        }];
+        assert_eq!(
+            to_html("<>木", &furigana),
+            "&lt;&gt;<ruby>木<rp>(</rp><rt>き</rt><rp>)</rp></ruby>"
+        );
+    }
+}
@@ -142,3 +142,243 @@ impl Dictionary {
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::legacy::dictionary::character::CharInfo;
+    use crate::legacy::dictionary::connector::raw_connector::scorer::{Scorer, U31x8};
+    use crate::legacy::dictionary::connector::{DualConnector, MatrixConnector, RawConnector};
+    use crate::legacy::dictionary::lexicon::feature::WordFeatures;
+    use crate::legacy::dictionary::lexicon::map::posting::Postings;
+    use crate::legacy::dictionary::lexicon::map::trie::Trie;
+    use crate::legacy::dictionary::lexicon::map::WordMap;
+    use crate::legacy::dictionary::lexicon::param::{WordParam, WordParams};
+    use crate::legacy::dictionary::unknown::UnkEntry;
+
+    use crate::dictionary::connector::{ConnectorCost, ConnectorView};
+    use crate::dictionary::LexType as NewLexType;
+    use crate::{Dictionary as NewDictionary, Tokenizer};
+
+    fn lexicon(words: &[(&str, i16, &str)], lex_type: LexType) -> Lexicon {
+        let mut records: Vec<(&str, Vec<u32>)> = vec![];
+        for (word_id, &(surface, _, _)) in words.iter().enumerate() {
+            let word_id = u32::try_from(word_id).unwrap();
+            match records.iter_mut().find(|(s, _)| *s == surface) {
+                Some((_, ids)) => ids.push(word_id),
+                None => records.push((surface, vec![word_id])),
+            }
+        }
+        let mut data = vec![];
+        let mut entries = vec![];
+        for (surface, ids) in records {
+            entries.push((surface, u32::try_from(data.len()).unwrap()));
+            data.push(u32::try_from(ids.len()).unwrap());
+            data.extend(ids);
+        }
+        Lexicon {
+            map: WordMap {
+                trie: Trie {
+                    da: crawdad::Trie::from_records(entries).unwrap(),
+                },
+                postings: Postings { data },
+            },
+            params: WordParams {
+                params: words
+                    .iter()
+                    .map(|&(_, word_cost, _)| WordParam {
+                        left_id: 0,
+                        right_id: 0,
+                        word_cost,
+                    })
+                    .collect(),
+            },
+            features: WordFeatures {
+                features: words.iter().map(|&(_, _, f)| f.to_string()).collect(),
+            },
+            lex_type,
+        }
+    }
+
+    fn legacy_dict(connector: ConnectorWrapper) -> DictionaryInner {
+        DictionaryInner {
+            system_lexicon: lexicon(
+                &[
+                    ("自然", 1, "sizen"),
+                    ("言語", 4, "gengo"),
+                    ("処理", 3, "shori"),
+                    ("処理", 2, "shori2"),
+                    ("自然言語", 6, "sizengengo"),
+                ],
+                LexType::System,
+            ),
+            user_lexicon: Some(lexicon(&[("言語処理", 5, "gengoshori")], LexType::User)),
+            connector,
+            mapper: None,
+            char_prop: CharProperty {
+                // DEFAULT 0 1 0
+                chr2inf: vec![CharInfo(1 | (1 << 27))],
+                categories: vec!["DEFAULT".to_string()],
+            },
+            unk_handler: UnkHandler {
+                offsets: vec![0, 1],
+                entries: vec![UnkEntry {
+                    cate_id: 0,
+                    left_id: 0,
+                    right_id: 0,
+                    word_cost: 100,
+                    feature: "*".to_string(),
+                }],
+            },
+        }
+    }
+
+    fn matrix_connector(data: Vec<i16>, num_right: usize, num_left: usize) -> MatrixConnector {
+        MatrixConnector {
+            data,
+            num_right,
+            num_left,
+        }
+    }
+
+    fn decode<T: Decode<()>>(data: impl Encode) -> T {
+        let config = common::bincode_config();
+        let bytes = bincode::encode_to_vec(data, config).unwrap();
+        bincode::decode_from_slice(&bytes, config).unwrap().0
+    }
+
+    fn feature_ids(lane0: u32) -> U31x8 {
+        decode([lane0, 0, 0, 0, 0, 0, 0, 0])
+    }
+
+    // Costs of the pairs of features (right, left): (1, 1) => 10, (2, 1) => -20.
+    fn scorer() -> Scorer {
+        decode((
+            vec![0u32, 0, 2],
+            vec![u32::MAX, 1, u32::MAX, 2],
+            vec![0i32, 10, 0, -20],
+        ))
+    }
+
+    fn tokenize(tokenizer: &Tokenizer, sentence: &str) -> Vec<(String, String, NewLexType)> {
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence(sentence);
+        worker.tokenize();
+        worker
+            .token_iter()
+            .map(|t| (t.surface().to_string(), t.feature().to_string(), t.lex_type()))
+            .collect()
+    }
+
+    #[test]
+    fn test_convert_dictionary() {
+        let dict = legacy_dict(ConnectorWrapper::Matrix(matrix_connector(vec![0], 1, 1)));
+        let dict = crate::dictionary::DictionaryInner::try_from(dict).unwrap();
+        let tokenizer = Tokenizer::from_inner(dict);
+
+        assert_eq!(
+            tokenize(&tokenizer, "自然言語処理"),
+            [
+                ("自然".to_string(), "sizen".to_string(), NewLexType::System),
+                ("言語処理".to_string(), "gengoshori".to_string(), NewLexType::User),
+            ]
+        );
+        assert_eq!(
+            tokenize(&tokenizer, "処理"),
+            [("処理".to_string(), "shori2".to_string(), NewLexType::System)]
+        );
+        assert_eq!(
+            tokenize(&tokenizer, "あ"),
+            [("あ".to_string(), "*".to_string(), NewLexType::Unknown)]
+        );
+    }
+
+    #[test]
+    fn test_from_legacy_reader() {
+        let dict = legacy_dict(ConnectorWrapper::Matrix(matrix_connector(vec![0], 1, 1)));
+        let mut bytes = MODEL_MAGIC.to_vec();
+        bytes.extend(bincode::encode_to_vec(dict, common::bincode_config()).unwrap());
+
+        let dict = NewDictionary::from_legacy_reader(bytes.as_slice()).unwrap();
+        let tokenizer = Tokenizer::new(dict);
+        assert_eq!(
+            tokenize(&tokenizer, "自然言語"),
+            [
+                ("自然".to_string(), "sizen".to_string(), NewLexType::System),
+                ("言語".to_string(), "gengo".to_string(), NewLexType::System),
+            ]
+        );
+
+        assert!(NewDictionary::from_legacy_reader(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_convert_matrix_connector() {
+        let connector = matrix_connector(vec![1, 2, 3, 4, 5, 6], 2, 3);
+
+        let connector =
+            crate::dictionary::connector::ConnectorWrapper::from(ConnectorWrapper::Matrix(connector));
+        assert_eq!(connector.num_right(), 2);
+        assert_eq!(connector.num_left(), 3);
+        assert_eq!(connector.cost(0, 0), 1);
+        assert_eq!(connector.cost(1, 0), 2);
+        assert_eq!(connector.cost(0, 1), 3);
+        assert_eq!(connector.cost(1, 1), 4);
+        assert_eq!(connector.cost(0, 2), 5);
+        assert_eq!(connector.cost(1, 2), 6);
+    }
+
+    #[test]
+    fn test_convert_raw_connector() {
+        let connector = RawConnector {
+            right_feat_ids: vec![feature_ids(1), feature_ids(2)],
+            left_feat_ids: vec![feature_ids(1), feature_ids(0)],
+            feat_template_size: 1,
+            scorer: scorer(),
+        };
+
+        let connector =
+            crate::dictionary::connector::ConnectorWrapper::from(ConnectorWrapper::Raw(connector));
+        assert_eq!(connector.num_right(), 2);
+        assert_eq!(connector.num_left(), 2);
+        assert_eq!(connector.cost(0, 0), 10);
+        assert_eq!(connector.cost(0, 1), 0);
+        assert_eq!(connector.cost(1, 0), -20);
+        assert_eq!(connector.cost(1, 1), 0);
+    }
+
+    #[test]
+    fn test_convert_dual_connector() {
+        let connector = DualConnector {
+            matrix_connector: matrix_connector(vec![100, 200, 300, 400], 2, 2),
+            right_conn_id_map: vec![1, 0],
+            left_conn_id_map: vec![0, 1],
+            right_feat_ids: vec![feature_ids(1), feature_ids(2)],
+            left_feat_ids: vec![feature_ids(1), feature_ids(0)],
+            raw_scorer: scorer(),
+        };
+
+        let connector =
+            crate::dictionary::connector::ConnectorWrapper::from(ConnectorWrapper::Dual(connector));
+        assert_eq!(connector.num_right(), 2);
+        assert_eq!(connector.num_left(), 2);
+        assert_eq!(connector.cost(0, 0), 200 + 10);
+        assert_eq!(connector.cost(0, 1), 400);
+        assert_eq!(connector.cost(1, 0), 100 - 20);
+        assert_eq!(connector.cost(1, 1), 300);
+    }
+
+    #[test]
+    fn test_convert_mapper() {
+        let mut dict = legacy_dict(ConnectorWrapper::Matrix(matrix_connector(vec![0], 1, 1)));
+        dict.mapper = Some(ConnIdMapper {
+            left: vec![0],
+            right: vec![0],
+        });
+        let dict = crate::dictionary::DictionaryInner::try_from(dict).unwrap();
+        let mapper = dict.mapper().unwrap();
+        assert_eq!(mapper.left(0), 0);
+        assert_eq!(mapper.right(0), 0);
+    }
+}
@@ -27,7 +27,7 @@ const BASE_ID_MASK: u32 = (1 << BASE_ID_BITS) - 1;
 ///     length =  4 ビット
 /// ```
 #[derive(Default, Clone, Copy, Decode, Encode)]
-pub struct CharInfo(u32);
+pub struct CharInfo(pub(crate) u32);
 
 impl fmt::Debug for CharInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -100,7 +100,7 @@ impl CharInfo {
 #[derive(Decode, Encode)]
 pub struct CharProperty {
     /// 文字（コードポイント）から文字情報へのマッピング
-    chr2inf: Vec<CharInfo>,
+    pub(crate) chr2inf: Vec<CharInfo>,
     /// カテゴリIDでインデックス化されたカテゴリ名のリスト
-    categories: Vec<String>,
+    pub(crate) categories: Vec<String>,
 }
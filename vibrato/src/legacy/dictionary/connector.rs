@@ -3,9 +3,9 @@
 //! このモジュールは、形態素間の接続コストを計算するための
 //! 各種コネクター実装を提供します。
 
-mod dual_connector;
-mod matrix_connector;
-mod raw_connector;
+pub(crate) mod dual_connector;
+pub(crate) mod matrix_connector;
+pub(crate) mod raw_connector;
 
 use bincode::{Decode, Encode};
 
@@ -17,15 +17,15 @@ use crate::legacy::dictionary::connector::MatrixConnector;
 #[derive(Decode, Encode)]
 pub struct DualConnector {
     /// 行列ベースのコネクター
-    matrix_connector: MatrixConnector,
+    pub(crate) matrix_connector: MatrixConnector,
     /// 右側接続IDマッピング
-    right_conn_id_map: Vec<u16>,
+    pub(crate) right_conn_id_map: Vec<u16>,
     /// 左側接続IDマッピング
-    left_conn_id_map: Vec<u16>,
+    pub(crate) left_conn_id_map: Vec<u16>,
     /// 右側特徴ID（SIMD最適化）
-    right_feat_ids: Vec<U31x8>,
+    pub(crate) right_feat_ids: Vec<U31x8>,
     /// 左側特徴ID（SIMD最適化）
-    left_feat_ids: Vec<U31x8>,
+    pub(crate) left_feat_ids: Vec<U31x8>,
     /// Rawスコアラー
-    raw_scorer: Scorer,
+    pub(crate) raw_scorer: Scorer,
 }
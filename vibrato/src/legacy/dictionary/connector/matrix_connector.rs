@@ -12,9 +12,9 @@ use bincode::{Decode, Encode};
 #[derive(Decode, Encode)]
 pub struct MatrixConnector {
     /// 接続コストデータの平坦化された配列
-    data: Vec<i16>,
+    pub(crate) data: Vec<i16>,
     /// 右側の品詞数
-    num_right: usize,
+    pub(crate) num_right: usize,
     /// 左側の品詞数
-    num_left: usize,
+    pub(crate) num_left: usize,
 }
@@ -19,11 +19,11 @@ use crate::legacy::dictionary::connector::raw_connector::scorer::{
 #[derive(Decode, Encode)]
 pub struct RawConnector {
     /// 右側特徴ID（SIMD最適化）
-    right_feat_ids: Vec<U31x8>,
+    pub(crate) right_feat_ids: Vec<U31x8>,
     /// 左側特徴ID（SIMD最適化）
-    left_feat_ids: Vec<U31x8>,
+    pub(crate) left_feat_ids: Vec<U31x8>,
     /// 特徴テンプレートのサイズ
-    feat_template_size: usize,
+    pub(crate) feat_template_size: usize,
     /// コスト計算用スコアラー
-    scorer: Scorer,
+    pub(crate) scorer: Scorer,
 }
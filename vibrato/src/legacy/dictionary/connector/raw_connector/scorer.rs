@@ -44,6 +44,22 @@ impl Default for U31x8 {
     }
 }
 
+impl U31x8 {
+    /// 8個の値を配列として取得します。
+    #[cfg(not(target_feature = "avx2"))]
+    pub(crate) fn to_array(self) -> [u32; SIMD_SIZE] {
+        self.0.map(U31::get)
+    }
+
+    /// 8個の値を配列として取得します（AVX2版）。
+    #[cfg(target_feature = "avx2")]
+    pub(crate) fn to_array(self) -> [u32; SIMD_SIZE] {
+        let mut data = [0; SIMD_SIZE];
+        unsafe { x86_64::_mm256_storeu_si256(data.as_mut_ptr() as *mut __m256i, self.0) };
+        data
+    }
+}
+
 impl<Context> Decode<Context> for U31x8 {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
         let data: [U31; 8] = Decode::decode(decoder)?;
@@ -90,11 +106,11 @@ impl Encode for U31x8 {
 /// SIMD最適化されたルックアップ機構を提供します。
 pub struct Scorer {
     /// ベース値の配列
-    bases: Vec<u32>,
+    pub(crate) bases: Vec<u32>,
     /// チェック値の配列
-    checks: Vec<u32>,
+    pub(crate) checks: Vec<u32>,
     /// コスト値の配列
-    costs: Vec<i32>,
+    pub(crate) costs: Vec<i32>,
 
     /// ベース配列の長さ（AVX2最適化用）
     #[cfg(target_feature = "avx2")]
@@ -3,9 +3,9 @@
 //! このモジュールは、単語の辞書データを管理します。
 //! 単語のマッピング、パラメータ、特徴情報を含みます。
 
-mod feature;
-mod map;
-mod param;
+pub(crate) mod feature;
+pub(crate) mod map;
+pub(crate) mod param;
 
 
 use bincode::{Decode, Encode};
@@ -23,11 +23,11 @@ use crate::legacy::dictionary::LexType;
 #[derive(Decode, Encode)]
 pub struct Lexicon {
     /// 単語マッピング（表層形からエントリへ）
-    map: WordMap,
+    pub(crate) map: WordMap,
     /// 単語パラメータ（コスト、品詞IDなど）
-    params: WordParams,
+    pub(crate) params: WordParams,
     /// 単語特徴（特徴文字列）
-    features: WordFeatures,
+    pub(crate) features: WordFeatures,
     /// 辞書種別
-    lex_type: LexType,
+    pub(crate) lex_type: LexType,
 }
@@ -11,5 +11,5 @@ use bincode::{Decode, Encode};
 #[derive(Default, Decode, Encode)]
 pub struct WordFeatures {
     /// 特徴文字列の配列
-    features: Vec<String>,
+    pub(crate) features: Vec<String>,
 }
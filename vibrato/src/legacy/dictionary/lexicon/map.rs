@@ -19,7 +19,7 @@ use crate::legacy::dictionary::lexicon::map::trie::Trie;
 #[derive(Decode, Encode)]
 pub struct WordMap {
     /// トライ構造（文字列検索用）
-    trie: Trie,
+    pub(crate) trie: Trie,
     /// ポスティングリスト（エントリリスト）
-    postings: Postings,
+    pub(crate) postings: Postings,
 }
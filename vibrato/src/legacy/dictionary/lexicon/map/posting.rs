@@ -18,5 +18,5 @@ use bincode::{Decode, Encode};
 #[derive(Decode, Encode)]
 pub struct Postings {
     /// エントリIDのデータ（長さと値を交互に格納）
-    data: Vec<u32>,
+    pub(crate) data: Vec<u32>,
 }
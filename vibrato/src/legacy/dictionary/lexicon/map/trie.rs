@@ -17,7 +17,7 @@ use crate::legacy::errors::Result;
 /// crawdadクレートの`Trie`実装をラップしています。
 pub struct Trie {
     /// ダブル配列トライの内部実装
-    da: crawdad::Trie,
+    pub(crate) da: crawdad::Trie,
 }
 
 impl Encode for Trie {
@@ -25,5 +25,5 @@ pub struct WordParam {
 #[derive(Decode, Encode)]
 pub struct WordParams {
     /// パラメータの配列
-    params: Vec<WordParam>,
+    pub(crate) params: Vec<WordParam>,
 }
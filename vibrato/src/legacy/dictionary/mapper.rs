@@ -11,7 +11,7 @@ use bincode::{Decode, Encode};
 #[derive(Decode, Encode)]
 pub struct ConnIdMapper {
     /// 左側接続IDのマッピングテーブル
-    left: Vec<u16>,
+    pub(crate) left: Vec<u16>,
     /// 右側接続IDのマッピングテーブル
-    right: Vec<u16>,
+    pub(crate) right: Vec<u16>,
 }
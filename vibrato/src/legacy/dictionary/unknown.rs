@@ -30,7 +30,7 @@ pub struct UnkEntry {
 #[derive(Decode, Encode)]
 pub struct UnkHandler {
     /// カテゴリIDでインデックス化されたオフセット配列
-    offsets: Vec<usize>,
+    pub(crate) offsets: Vec<usize>,
     /// 未知語エントリの配列
-    entries: Vec<UnkEntry>,
+    pub(crate) entries: Vec<UnkEntry>,
 }
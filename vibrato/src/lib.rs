@@ -16,6 +16,20 @@
 //! - **N-best解析**: 複数の解析候補の生成（実験的機能）
 //! - **学習機能**: 構造化パーセプトロンによるモデル学習（trainフィーチャー有効時）
 //!
+//! ## ログ出力
+//!
+//! ライブラリは標準エラー出力に直接書き込みません。辞書の読み込み時の警告や学習の
+//! 進捗などの診断メッセージは[`log`](https://docs.rs/log)クレートを通じて出力されるため、
+//! アプリケーション側で任意のロガーを設定して抑制したり転送したりできます。
+//!
+//! ## 最小構成
+//!
+//! 圧縮辞書の読み込み、展開キャッシュ、プルーフファイル、辞書のダイジェストの計算は
+//! `loaders`フィーチャー（デフォルトで有効）が提供します。`default-features = false`で
+//! 無効にすると、zstd、sha2、dirs、tempfileに依存せずに、非圧縮の辞書を
+//! [`Dictionary::from_path`]や[`Dictionary::read`]で読み込んでトークン化できます。
+//! 組み込み環境など、依存関係を最小限にしたい場合に使用します。
+//!
 //! ## 使用例
 //!
 //! ```
@@ -62,18 +76,46 @@
 #[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
 compile_error!("`target_pointer_width` must be 32 or 64");
 
+/// 文書単位の解析結果と解析パイプライン
+pub mod analysis;
+
+/// 本家vibratoとの互換レイヤー
+///
+/// `compat-vibrato`フィーチャーが有効な場合のみ利用可能です。
+#[cfg(feature = "compat-vibrato")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compat-vibrato")))]
+pub mod compat;
+
 /// 共通の型定義とユーティリティ
 pub mod common;
 
+/// CoNLL-U形式の出力
+pub mod conllu;
+
 /// 辞書データ構造とビルダー
 pub mod dictionary;
 
+/// 複数のトークナイザーによるアンサンブル解析
+pub mod ensemble;
+
 /// エラー型の定義
 pub mod errors;
 
+/// MeCab互換の出力フォーマット
+pub mod format;
+
+/// ふりがな（ルビ）の生成
+pub mod furigana;
+
 /// 数値型のユーティリティ
 pub mod num;
 
+/// トークン化の後処理
+pub mod postprocess;
+
+/// 品詞体系の対応付け
+pub mod posmap;
+
 /// 文の内部表現
 mod sentence;
 
@@ -111,7 +153,9 @@ mod test_utils;
 mod tests;
 
 // Re-exports
-pub use dictionary::{CacheStrategy, Dictionary, LoadMode, SystemDictionaryBuilder};
+pub use dictionary::{Dictionary, LoadMode, SystemDictionaryBuilder};
+#[cfg(feature = "loaders")]
+pub use dictionary::CacheStrategy;
 pub use tokenizer::Tokenizer;
 
 /// このライブラリのバージョン番号
@@ -3,14 +3,20 @@
 //! このモジュールは、MeCabモデルとの互換性を提供するための機能を含んでいます。
 //! MeCab形式のファイル(feature.def、left-id.def、right-id.def、model.def等)の
 //! 読み込みと書き込みを行い、バイグラム情報の生成などをサポートします。
+//!
+//! また、解析結果から学習用のコーパスやシード辞書を生成する関数も提供します。
+//! これにより、既存の解析器の出力を使った自己学習をクレート内で完結できます。
 
 use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use regex::Regex;
 
+use crate::dictionary::LexType;
 use crate::errors::{Result, VibratoError};
-use crate::trainer::TrainerConfig;
+use crate::sentence::Sentence;
+use crate::token::TokenBuf;
+use crate::trainer::{Example, TrainerConfig, Word};
 use crate::utils;
 
 /// MeCabモデルからバイグラム素性情報を生成します。
@@ -195,3 +201,180 @@ pub fn generate_bigram_info(
 
     Ok(())
 }
+
+/// トークン列から学習用の例文を作成します。
+///
+/// 各トークンの表層形と素性がそのまま例文の単語になります。
+///
+/// # 引数
+///
+/// * `tokens` - 解析結果のトークン列
+/// * `mask_unknown` - `true` の場合、未知語のトークンの素性を
+///   [`WILDCARD_FEATURE`](crate::trainer::WILDCARD_FEATURE)に置き換え、
+///   注釈されていないトークンとして扱います。
+///
+/// # 戻り値
+///
+/// 作成された例文
+pub fn example_from_tokens<'a, I>(tokens: I, mask_unknown: bool) -> Example
+where
+    I: IntoIterator<Item = &'a TokenBuf>,
+{
+    let mut input = String::new();
+    let mut words = vec![];
+    for token in tokens {
+        input.push_str(&token.surface);
+        if mask_unknown && token.lex_type == LexType::Unknown {
+            words.push(Word::unannotated(&token.surface));
+        } else {
+            words.push(Word::new(&token.surface, &token.feature));
+        }
+    }
+    let mut sentence = Sentence::new();
+    sentence.set_sentence(input);
+    Example {
+        sentence,
+        tokens: words,
+    }
+}
+
+/// 例文をコーパス形式で書き込みます。
+///
+/// 出力は[`Corpus::from_reader()`](crate::trainer::Corpus::from_reader)で読み込めます。
+/// 注釈されていないトークンを含む場合は、
+/// [`Corpus::from_reader_partial()`](crate::trainer::Corpus::from_reader_partial)で読み込みます。
+/// 単語を持たない例文は書き込まれません。
+///
+/// # 引数
+///
+/// * `wtr` - コーパスの書き込み先
+/// * `examples` - 書き込む例文
+///
+/// # 戻り値
+///
+/// 書き込み成功時は `Ok(())`
+///
+/// # エラー
+///
+/// 書き込みに失敗した場合、[`VibratoError`] が返されます。
+pub fn write_corpus<'a, W, I>(wtr: W, examples: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Example>,
+{
+    let mut wtr = BufWriter::new(wtr);
+    for example in examples {
+        if example.tokens().is_empty() {
+            continue;
+        }
+        for word in example.tokens() {
+            writeln!(&mut wtr, "{}\t{}", word.surface(), word.feature())?;
+        }
+        writeln!(&mut wtr, "EOS")?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// 例文に含まれる単語から、学習用のシード辞書を書き込みます。
+///
+/// 注釈された単語の表層形と素性の組を初出順に重複なく `lex.csv` 形式で書き込みます。
+/// 接続IDとコストはすべて0になるため、出力は
+/// [`TrainerConfig::from_readers()`]の語彙ファイルとして使用できます。
+///
+/// # 引数
+///
+/// * `wtr` - シード辞書の書き込み先
+/// * `examples` - 単語を収集する例文
+///
+/// # 戻り値
+///
+/// 書き込み成功時は `Ok(())`
+///
+/// # エラー
+///
+/// 書き込みに失敗した場合、[`VibratoError`] が返されます。
+pub fn write_seed_lexicon<'a, W, I>(wtr: W, examples: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Example>,
+{
+    let mut wtr = BufWriter::new(wtr);
+    let mut written = HashSet::new();
+    for example in examples {
+        for word in example.tokens() {
+            if !word.is_annotated() || !written.insert((word.surface(), word.feature())) {
+                continue;
+            }
+            utils::quote_csv_cell(&mut wtr, word.surface().as_bytes())?;
+            writeln!(&mut wtr, ",0,0,0,{}", word.feature())?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::WordIdx;
+    use crate::trainer::Corpus;
+
+    fn token(surface: &str, feature: &str, start: usize, lex_type: LexType) -> TokenBuf {
+        let len = surface.chars().count();
+        TokenBuf {
+            surface: surface.to_string(),
+            feature: feature.to_string(),
+            range_char: start..start + len,
+            range_byte: 0..surface.len(),
+            lex_type,
+            word_id: WordIdx::new(lex_type, 0),
+            left_id: 0,
+            right_id: 0,
+            word_cost: 0,
+            total_cost: 0,
+        }
+    }
+
+    #[test]
+    fn test_example_from_tokens() {
+        let tokens = [
+            token("火星", "名詞,カセイ", 0, LexType::System),
+            token("ニャン", "名詞,未知語", 2, LexType::Unknown),
+        ];
+
+        let example = example_from_tokens(&tokens, false);
+        assert_eq!("火星ニャン", example.sentence.raw());
+        assert_eq!("名詞,未知語", example.tokens()[1].feature());
+
+        let example = example_from_tokens(&tokens, true);
+        assert_eq!("名詞,カセイ", example.tokens()[0].feature());
+        assert!(!example.tokens()[1].is_annotated());
+    }
+
+    #[test]
+    fn test_write_corpus_and_seed_lexicon() {
+        let corpus_data = "\
+火星\t名詞,カセイ
+の\t助詞,ノ
+猫\t名詞,ネコ
+EOS
+猫\t名詞,ネコ
+1,2\t*
+EOS
+";
+        let corpus = Corpus::from_reader_partial(corpus_data.as_bytes()).unwrap();
+
+        let mut buf = vec![];
+        write_corpus(&mut buf, corpus.iter()).unwrap();
+        assert_eq!(corpus_data, String::from_utf8(buf).unwrap());
+
+        let mut buf = vec![];
+        write_seed_lexicon(&mut buf, corpus.iter()).unwrap();
+        assert_eq!(
+            "火星,0,0,0,名詞,カセイ\nの,0,0,0,助詞,ノ\n猫,0,0,0,名詞,ネコ\n",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! 品詞体系の対応付け
+//!
+//! このモジュールは、辞書ごとに異なる品詞の素性を、Universal Dependenciesの品詞タグ
+//! （UPOS）などの共通の品詞体系に対応付ける機能を提供します。対応付けは[`PosMap`]の
+//! 対応表として与えられ、IPADICとUniDicからUPOSへの表が組み込まれています。
+//! 独自の辞書や品詞体系には、[`PosMap::from_reader()`]で読み込んだ表を使用できます。
+//!
+//! 対応表の各行は、カンマ区切りの品詞の項目の並びと、対応するタグをタブで区切ったものです。
+//! 項目の`*`は任意の値に一致し、素性の先頭の項目に一致する規則のうち、項目数が最も多い
+//! 規則が適用されます。空行と`#`で始まる行は無視されます。
+//!
+//! ```text
+//! 名詞	NOUN
+//! 名詞,固有名詞	PROPN
+//! 助詞,*,引用	ADP
+//! ```
+
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use crate::dictionary::metadata::DictionaryMetadata;
+use crate::errors::{Result, VibratoError};
+
+/// IPADICからUPOSへの組み込みの対応表
+static IPADIC_UPOS: LazyLock<PosMap> = LazyLock::new(|| {
+    PosMap::from_reader(include_str!("posmap/ipadic.tsv").as_bytes()).unwrap()
+});
+
+/// UniDicからUPOSへの組み込みの対応表
+static UNIDIC_UPOS: LazyLock<PosMap> = LazyLock::new(|| {
+    PosMap::from_reader(include_str!("posmap/unidic.tsv").as_bytes()).unwrap()
+});
+
+/// Universal Dependenciesの品詞タグ（UPOS）
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Upos {
+    /// 形容詞
+    Adj,
+    /// 接置詞
+    Adp,
+    /// 副詞
+    Adv,
+    /// 助動詞
+    Aux,
+    /// 等位接続詞
+    Cconj,
+    /// 限定詞
+    Det,
+    /// 間投詞
+    Intj,
+    /// 名詞
+    Noun,
+    /// 数詞
+    Num,
+    /// 助詞
+    Part,
+    /// 代名詞
+    Pron,
+    /// 固有名詞
+    Propn,
+    /// 句読点
+    Punct,
+    /// 従位接続詞
+    Sconj,
+    /// 記号
+    Sym,
+    /// 動詞
+    Verb,
+    /// その他
+    X,
+}
+
+impl Upos {
+    /// UPOSの表記（`NOUN`など）を返します。
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Adj => "ADJ",
+            Self::Adp => "ADP",
+            Self::Adv => "ADV",
+            Self::Aux => "AUX",
+            Self::Cconj => "CCONJ",
+            Self::Det => "DET",
+            Self::Intj => "INTJ",
+            Self::Noun => "NOUN",
+            Self::Num => "NUM",
+            Self::Part => "PART",
+            Self::Pron => "PRON",
+            Self::Propn => "PROPN",
+            Self::Punct => "PUNCT",
+            Self::Sconj => "SCONJ",
+            Self::Sym => "SYM",
+            Self::Verb => "VERB",
+            Self::X => "X",
+        }
+    }
+}
+
+impl fmt::Display for Upos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Upos {
+    type Err = VibratoError;
+
+    fn from_str(tag: &str) -> Result<Self> {
+        let upos = match tag {
+            "ADJ" => Self::Adj,
+            "ADP" => Self::Adp,
+            "ADV" => Self::Adv,
+            "AUX" => Self::Aux,
+            "CCONJ" => Self::Cconj,
+            "DET" => Self::Det,
+            "INTJ" => Self::Intj,
+            "NOUN" => Self::Noun,
+            "NUM" => Self::Num,
+            "PART" => Self::Part,
+            "PRON" => Self::Pron,
+            "PROPN" => Self::Propn,
+            "PUNCT" => Self::Punct,
+            "SCONJ" => Self::Sconj,
+            "SYM" => Self::Sym,
+            "VERB" => Self::Verb,
+            "X" => Self::X,
+            _ => {
+                return Err(VibratoError::invalid_argument(
+                    "tag",
+                    format!("{tag} is not a UPOS tag."),
+                ));
+            }
+        };
+        Ok(upos)
+    }
+}
+
+/// 辞書の品詞体系
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PosTagset {
+    /// IPADIC（`mecab-ipadic`、`mecab-ipadic-NEologd`など）の品詞体系
+    Ipadic,
+    /// UniDic（`unidic-cwj`、`unidic-csj`など）の品詞体系
+    Unidic,
+}
+
+impl PosTagset {
+    /// 辞書のメタデータから品詞体系を判定します。
+    ///
+    /// [`KEY_POS_TAGSET`](crate::dictionary::metadata::KEY_POS_TAGSET)の項目があれば
+    /// その値に従い、なければ辞書の名前に`ipadic`または`unidic`が含まれるかで判定します。
+    ///
+    /// # 引数
+    ///
+    /// * `metadata` - 辞書のメタデータ
+    ///
+    /// # 戻り値
+    ///
+    /// 品詞体系を判定できない場合は`None`
+    pub fn from_metadata(metadata: &DictionaryMetadata) -> Option<Self> {
+        if let Some(tagset) = metadata.pos_tagset() {
+            return tagset.parse().ok();
+        }
+        let name = metadata.name()?.to_ascii_lowercase();
+        if name.contains("ipadic") {
+            Some(Self::Ipadic)
+        } else if name.contains("unidic") {
+            Some(Self::Unidic)
+        } else {
+            None
+        }
+    }
+
+    /// この品詞体系からUPOSへの組み込みの対応表を返します。
+    pub fn upos_map(self) -> &'static PosMap {
+        match self {
+            Self::Ipadic => &IPADIC_UPOS,
+            Self::Unidic => &UNIDIC_UPOS,
+        }
+    }
+}
+
+impl FromStr for PosTagset {
+    type Err = VibratoError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case("ipadic") {
+            Ok(Self::Ipadic)
+        } else if name.eq_ignore_ascii_case("unidic") {
+            Ok(Self::Unidic)
+        } else {
+            Err(VibratoError::invalid_argument(
+                "name",
+                format!("Unknown POS tagset: {name}. Choices are ipadic and unidic."),
+            ))
+        }
+    }
+}
+
+/// 対応表の1つの規則
+#[derive(Clone, Debug)]
+struct Rule {
+    fields: Vec<String>,
+    tag: String,
+}
+
+impl Rule {
+    /// 素性の項目が規則に一致するかを返します。
+    fn matches(&self, features: &[&str]) -> bool {
+        self.fields.len() <= features.len()
+            && self.fields.iter().zip(features).all(|(f, v)| f == "*" || f == v)
+    }
+}
+
+/// 品詞の素性から共通の品詞体系のタグへの対応表
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::posmap::{PosMap, Upos};
+///
+/// let map = PosMap::new().with("名詞", "NOUN").with("名詞,固有名詞", "PROPN");
+/// assert_eq!(map.get("名詞,固有名詞,地名,一般,*,*"), Some("PROPN"));
+/// assert_eq!(map.upos("名詞,一般,*,*,*,*"), Some(Upos::Noun));
+/// assert_eq!(map.get("動詞,自立,*,*"), None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PosMap {
+    rules: Vec<Rule>,
+}
+
+impl PosMap {
+    /// 空の対応表を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 規則を追加した対応表を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `pattern` - カンマ区切りの品詞の項目の並び。`*`は任意の値に一致します。
+    /// * `tag` - 対応するタグ
+    pub fn with<T>(mut self, pattern: &str, tag: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.rules.push(Rule {
+            fields: pattern.split(',').map(str::to_string).collect(),
+            tag: tag.into(),
+        });
+        self
+    }
+
+    /// 対応表をリーダーから読み込みます。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - 対応表のリーダー
+    ///
+    /// # エラー
+    ///
+    /// 読み込みに失敗した場合や、タブで区切られていない行がある場合に
+    /// [`VibratoError`]を返します。
+    pub fn from_reader<R>(rdr: R) -> Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut map = Self::new();
+        for (i, line) in rdr.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((pattern, tag)) = line.split_once('\t') else {
+                return Err(VibratoError::invalid_format(
+                    "rdr",
+                    format!("Line {} must be a POS pattern and a tag separated by a tab.", i + 1),
+                ));
+            };
+            map = map.with(pattern, tag);
+        }
+        Ok(map)
+    }
+
+    /// 素性に対応するタグを返します。
+    ///
+    /// 素性はカンマで区切られているものとして扱い、引用符は解釈しません。
+    ///
+    /// # 引数
+    ///
+    /// * `feature` - トークンの素性
+    ///
+    /// # 戻り値
+    ///
+    /// 一致する規則がない場合は`None`
+    pub fn get(&self, feature: &str) -> Option<&str> {
+        let features: Vec<&str> = feature.split(',').collect();
+        let mut best: Option<&Rule> = None;
+        for rule in &self.rules {
+            if rule.matches(&features) && best.is_none_or(|b| rule.fields.len() > b.fields.len()) {
+                best = Some(rule);
+            }
+        }
+        best.map(|rule| rule.tag.as_str())
+    }
+
+    /// 素性に対応するUPOSを返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 一致する規則がない場合や、タグがUPOSでない場合は`None`
+    pub fn upos(&self, feature: &str) -> Option<Upos> {
+        self.get(feature)?.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_ipadic() {
+        let map = PosTagset::Ipadic.upos_map();
+        let cases = [
+            ("名詞,一般,*,*,*,*,自然,シゼン,シゼン", Upos::Noun),
+            ("名詞,固有名詞,地域,一般,*,*,京都,キョウト,キョート", Upos::Propn),
+            ("名詞,数,*,*,*,*,一,イチ,イチ", Upos::Num),
+            ("動詞,自立,*,*,五段・カ行イ音便,基本形,書く,カク,カク", Upos::Verb),
+            ("動詞,非自立,*,*,一段,基本形,いる,イル,イル", Upos::Aux),
+            ("助詞,格助詞,一般,*,*,*,が,ガ,ガ", Upos::Adp),
+            ("助詞,接続助詞,*,*,*,*,て,テ,テ", Upos::Sconj),
+            ("記号,句点,*,*,*,*,。,。,。", Upos::Punct),
+            ("記号,一般,*,*,*,*,★,★,★", Upos::Sym),
+        ];
+        for (feature, upos) in cases {
+            assert_eq!(map.upos(feature), Some(upos), "{feature}");
+        }
+        assert_eq!(map.upos("*"), None);
+    }
+
+    #[test]
+    fn test_builtin_unidic() {
+        let map = PosTagset::Unidic.upos_map();
+        let cases = [
+            ("名詞,普通名詞,一般,*,*,*,シゼン", Upos::Noun),
+            ("名詞,固有名詞,地名,一般,*,*,キョウト", Upos::Propn),
+            ("代名詞,*,*,*,*,*,ワタクシ", Upos::Pron),
+            ("形状詞,一般,*,*,*,*,シズカ", Upos::Adj),
+            ("助詞,格助詞,*,*,*,*,ガ", Upos::Adp),
+            ("助詞,終助詞,*,*,*,*,ネ", Upos::Part),
+            ("補助記号,読点,*,*,*,*", Upos::Punct),
+        ];
+        for (feature, upos) in cases {
+            assert_eq!(map.upos(feature), Some(upos), "{feature}");
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let table = "# comment\n\n名詞\tN\n名詞,*,地名\tLOC\r\n動詞\tVERB\n";
+        let map = PosMap::from_reader(table.as_bytes()).unwrap();
+        assert_eq!(map.get("名詞,固有名詞,地名,一般"), Some("LOC"));
+        assert_eq!(map.get("名詞,一般"), Some("N"));
+        assert_eq!(map.upos("名詞,一般"), None);
+        assert_eq!(map.upos("動詞,自立"), Some(Upos::Verb));
+
+        assert!(PosMap::from_reader("名詞 NOUN".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_tagset_from_metadata() {
+        use crate::dictionary::metadata::{KEY_NAME, KEY_POS_TAGSET};
+
+        let metadata = DictionaryMetadata::new().with(KEY_NAME, "mecab-ipadic-NEologd");
+        assert_eq!(PosTagset::from_metadata(&metadata), Some(PosTagset::Ipadic));
+        let metadata = metadata.with(KEY_POS_TAGSET, "UniDic");
+        assert_eq!(PosTagset::from_metadata(&metadata), Some(PosTagset::Unidic));
+        assert_eq!(PosTagset::from_metadata(&DictionaryMetadata::new()), None);
+        assert_eq!(Upos::Propn.to_string(), "PROPN");
+        assert!("NOUNS".parse::<Upos>().is_err());
+    }
+}
@@ -0,0 +1,540 @@
+//! トークン化の後処理
+//!
+//! このモジュールは、数値や助数詞、日付のように辞書では細かく分割されるトークン列を
+//! 1つのトークンにまとめる[`TokenMerger`]を提供します。固有表現抽出などの下流の処理で、
+//! 「2024年」や「3,000円」を1語として扱いたい場合に使用します。
+//!
+//! # 規則の記法
+//!
+//! 規則は1行に1つずつ、`パターン => 素性`の形式で記述します。`#`で始まる行と空行は
+//! 無視されます。パターンはトークンの条件を空白区切りで並べたもので、次の条件を使えます。
+//!
+//! | 記法 | 一致するトークン |
+//! |------|------------------|
+//! | `num` | 表層形が数字（全角・漢数字を含む）のみからなるトークン |
+//! | `any` | 任意のトークン |
+//! | `"年"` | 表層形が`年`と一致するトークン |
+//! | `[名詞,数]` | 素性の先頭の項目が`名詞,数`と一致するトークン |
+//! | `(A B)` | パターン`A B`に一致するトークン列 |
+//! | `A \| B` | `A`または`B`に一致するトークン列 |
+//!
+//! 条件の後に`?`、`*`、`+`を付けると、それぞれ0回か1回、0回以上、1回以上の繰り返しを
+//! 表します。素性の中の`{surface}`は、まとめたトークンの表層形に置き換えられます。
+//!
+//! 各位置では、すべての規則の中で最も多くのトークンに一致する規則が適用されます。
+//! 一致するトークン数が同じ場合は、先に記述された規則が優先されます。
+//! 2つ以上のトークンに一致しない規則は適用されません。
+//!
+//! # 例
+//!
+//! ```
+//! use vibrato_rkyv::postprocess::TokenMerger;
+//! # use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+//! # let dict = SystemDictionaryBuilder::from_readers(
+//! #     "円,0,0,1,名詞,接尾\n".as_bytes(),
+//! #     "1 1\n0 0 0".as_bytes(),
+//! #     "DEFAULT 0 1 0\nNUMERIC 1 1 0\n0x0030..0x0039 NUMERIC".as_bytes(),
+//! #     "DEFAULT,0,0,100,記号\nNUMERIC,0,0,100,名詞,数".as_bytes(),
+//! # ).unwrap();
+//! # let tokenizer = Tokenizer::from_inner(dict);
+//!
+//! let merger = TokenMerger::builtin();
+//! let mut worker = tokenizer.new_worker();
+//! worker.reset_sentence("3,000円");
+//! worker.tokenize();
+//!
+//! let tokens = merger.merge(worker.token_iter().map(|t| t.to_buf()).collect());
+//! assert_eq!(tokens.len(), 1);
+//! assert_eq!(tokens[0].surface, "3,000円");
+//! assert_eq!(tokens[0].feature, "名詞,数詞,*,*,*,*,3,000円");
+//! ```
+
+use std::collections::BTreeSet;
+
+use crate::errors::{Result, VibratoError};
+use crate::token::TokenBuf;
+
+/// [`TokenMerger::builtin()`]で使用される規則
+pub const BUILTIN_RULES: &str = r#"# 日付
+num "年" num "月" num "日" => 名詞,数詞,日付,*,*,*,{surface}
+num "年" num "月" => 名詞,数詞,日付,*,*,*,{surface}
+num "月" num "日" => 名詞,数詞,日付,*,*,*,{surface}
+# 数量（桁区切りと小数点を含む）と助数詞
+num+ (("," | "，" | "." | "．") num+)* ("年" | "月" | "日" | "時" | "分" | "秒" | "円" | "ドル" | "%" | "％" | "個" | "人" | "回" | "件" | "歳" | "倍" | "本" | "枚" | "台")? => 名詞,数詞,*,*,*,*,{surface}
+"#;
+
+/// 漢数字として扱う文字
+const KANJI_DIGITS: &str = "〇一二三四五六七八九十百千万億兆";
+
+/// トークンの条件
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Pattern {
+    /// 数字のみからなるトークン
+    Num,
+    /// 任意のトークン
+    Any,
+    /// 表層形が一致するトークン
+    Surface(String),
+    /// 素性の先頭の項目が一致するトークン
+    Feature(String),
+    /// パターンの連接
+    Seq(Vec<Pattern>),
+    /// いずれかのパターン
+    Alt(Vec<Pattern>),
+    /// 0回か1回の繰り返し
+    Optional(Box<Pattern>),
+    /// 0回以上の繰り返し
+    Star(Box<Pattern>),
+    /// 1回以上の繰り返し
+    Plus(Box<Pattern>),
+}
+
+impl Pattern {
+    /// `start`番目のトークンから一致させた場合の、一致の終了位置の集合を返します。
+    fn ends(&self, tokens: &[TokenBuf], start: usize) -> BTreeSet<usize> {
+        let mut ends = BTreeSet::new();
+        match self {
+            Self::Num | Self::Any | Self::Surface(_) | Self::Feature(_) => {
+                if tokens.get(start).is_some_and(|t| self.accepts(t)) {
+                    ends.insert(start + 1);
+                }
+            }
+            Self::Seq(items) => {
+                ends.insert(start);
+                for item in items {
+                    ends = ends.iter().flat_map(|&i| item.ends(tokens, i)).collect();
+                }
+            }
+            Self::Alt(branches) => {
+                for branch in branches {
+                    ends.extend(branch.ends(tokens, start));
+                }
+            }
+            Self::Optional(inner) => {
+                ends.insert(start);
+                ends.extend(inner.ends(tokens, start));
+            }
+            Self::Star(inner) => {
+                ends.insert(start);
+                Self::close(inner, tokens, &mut ends);
+            }
+            Self::Plus(inner) => {
+                ends = inner.ends(tokens, start);
+                Self::close(inner, tokens, &mut ends);
+            }
+        }
+        ends
+    }
+
+    /// `ends`の各位置からの繰り返しで到達できる位置を`ends`に加えます。
+    fn close(inner: &Self, tokens: &[TokenBuf], ends: &mut BTreeSet<usize>) {
+        let mut frontier: Vec<_> = ends.iter().copied().collect();
+        while let Some(i) = frontier.pop() {
+            for j in inner.ends(tokens, i) {
+                // Progress is required to terminate on patterns matching no token.
+                if j > i && ends.insert(j) {
+                    frontier.push(j);
+                }
+            }
+        }
+    }
+
+    /// 1つのトークンに対する条件を満たす場合に`true`を返します。
+    fn accepts(&self, token: &TokenBuf) -> bool {
+        match self {
+            Self::Num => !token.surface.is_empty() && token.surface.chars().all(is_numeral),
+            Self::Any => true,
+            Self::Surface(surface) => token.surface == *surface,
+            Self::Feature(prefix) => token
+                .feature
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(',')),
+            _ => false,
+        }
+    }
+}
+
+/// 数字として扱う文字の場合に`true`を返します。
+fn is_numeral(c: char) -> bool {
+    c.is_ascii_digit() || ('０'..='９').contains(&c) || KANJI_DIGITS.contains(c)
+}
+
+/// パターンの構文解析器
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(src: &'a str) -> std::result::Result<Pattern, String> {
+        let mut parser = Self { src, pos: 0 };
+        let pattern = parser.alt()?;
+        parser.skip_ws();
+        if let Some(c) = parser.peek() {
+            return Err(format!("Unexpected character: {c}"));
+        }
+        Ok(pattern)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn alt(&mut self) -> std::result::Result<Pattern, String> {
+        let mut branches = vec![self.seq()?];
+        loop {
+            self.skip_ws();
+            if self.peek() != Some('|') {
+                break;
+            }
+            self.bump();
+            branches.push(self.seq()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Pattern::Alt(branches)
+        })
+    }
+
+    fn seq(&mut self) -> std::result::Result<Pattern, String> {
+        let mut items = vec![];
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None | Some('|' | ')') => break,
+                _ => items.push(self.item()?),
+            }
+        }
+        match items.len() {
+            0 => Err("Empty pattern.".to_string()),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Pattern::Seq(items)),
+        }
+    }
+
+    fn item(&mut self) -> std::result::Result<Pattern, String> {
+        let atom = self.atom()?;
+        Ok(match self.peek() {
+            Some('?') => {
+                self.bump();
+                Pattern::Optional(Box::new(atom))
+            }
+            Some('*') => {
+                self.bump();
+                Pattern::Star(Box::new(atom))
+            }
+            Some('+') => {
+                self.bump();
+                Pattern::Plus(Box::new(atom))
+            }
+            _ => atom,
+        })
+    }
+
+    fn atom(&mut self) -> std::result::Result<Pattern, String> {
+        match self.bump() {
+            Some('(') => {
+                let inner = self.alt()?;
+                self.skip_ws();
+                if self.bump() != Some(')') {
+                    return Err("Unclosed parenthesis.".to_string());
+                }
+                Ok(inner)
+            }
+            Some('"') => self.until('"').map(Pattern::Surface),
+            Some('[') => self.until(']').map(Pattern::Feature),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos - 1;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.bump();
+                }
+                match &self.src[start..self.pos] {
+                    "num" => Ok(Pattern::Num),
+                    "any" => Ok(Pattern::Any),
+                    name => Err(format!("Unknown token class: {name}")),
+                }
+            }
+            Some(c) => Err(format!("Unexpected character: {c}")),
+            None => Err("Unexpected end of the pattern.".to_string()),
+        }
+    }
+
+    fn until(&mut self, close: char) -> std::result::Result<String, String> {
+        let start = self.pos;
+        while let Some(c) = self.bump() {
+            if c == close {
+                let s = &self.src[start..self.pos - close.len_utf8()];
+                if s.is_empty() {
+                    return Err(format!("Empty condition before {close}"));
+                }
+                return Ok(s.to_string());
+            }
+        }
+        Err(format!("Missing {close}"))
+    }
+}
+
+/// トークン列をまとめる規則
+#[derive(Clone, Debug)]
+struct Rule {
+    pattern: Pattern,
+    feature: String,
+}
+
+/// 規則に従って連続するトークンを1つのトークンにまとめる後処理
+///
+/// まとめられたトークンの表層形と位置範囲は元のトークンを連結したものになり、素性は
+/// 規則で指定したものになります。語彙種別と単語インデックス、左文脈IDは先頭のトークン、
+/// 右文脈IDと累積コストは末尾のトークンのものを引き継ぎ、単語コストは元のトークンの
+/// 合計になります。間に空白などを挟んで隣接していないトークンはまとめられません。
+///
+/// 規則の記法は[モジュールのドキュメント](self)を参照してください。
+#[derive(Clone, Debug, Default)]
+pub struct TokenMerger {
+    rules: Vec<Rule>,
+}
+
+impl TokenMerger {
+    /// 規則を持たない新しいインスタンスを作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 組み込みの規則（[`BUILTIN_RULES`]）を持つインスタンスを作成します。
+    pub fn builtin() -> Self {
+        Self::new().add_rules(BUILTIN_RULES).unwrap()
+    }
+
+    /// 規則を追加します。
+    ///
+    /// 追加された規則は、既存の規則より後に記述されたものとして扱われます。
+    ///
+    /// # 引数
+    ///
+    /// * `rules` - 規則の記述
+    ///
+    /// # 戻り値
+    ///
+    /// 規則が追加された`TokenMerger`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// 規則の記述が不正な場合に[`VibratoError`]が返されます。
+    pub fn add_rules(mut self, rules: &str) -> Result<Self> {
+        for (i, line) in rules.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((pattern, feature)) = line.split_once("=>") else {
+                return Err(VibratoError::invalid_format(
+                    "rules",
+                    format!("Missing => at line {}", i + 1),
+                ));
+            };
+            let pattern = Parser::parse(pattern).map_err(|msg| {
+                VibratoError::invalid_format("rules", format!("{msg} at line {}", i + 1))
+            })?;
+            self.rules.push(Rule {
+                pattern,
+                feature: feature.trim().to_string(),
+            });
+        }
+        Ok(self)
+    }
+
+    /// 規則の数を返します。
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// トークン列に規則を適用します。
+    ///
+    /// # 引数
+    ///
+    /// * `tokens` - 出現順のトークン列
+    ///
+    /// # 戻り値
+    ///
+    /// 規則に従ってまとめられたトークン列
+    pub fn merge(&self, tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        if self.rules.is_empty() {
+            return tokens;
+        }
+        let mut merged = Vec::with_capacity(tokens.len());
+        let mut start = 0;
+        while start < tokens.len() {
+            // The longest run of adjacent tokens that a rule may span.
+            let mut limit = start + 1;
+            while limit < tokens.len()
+                && tokens[limit - 1].range_byte.end == tokens[limit].range_byte.start
+            {
+                limit += 1;
+            }
+            let run = &tokens[..limit];
+
+            let mut best: Option<(usize, &Rule)> = None;
+            for rule in &self.rules {
+                let Some(&end) = rule.pattern.ends(run, start).last() else {
+                    continue;
+                };
+                if end >= start + 2 && best.is_none_or(|(e, _)| end > e) {
+                    best = Some((end, rule));
+                }
+            }
+            match best {
+                Some((end, rule)) => {
+                    merged.push(merge_tokens(&tokens[start..end], &rule.feature));
+                    start = end;
+                }
+                None => {
+                    merged.push(tokens[start].clone());
+                    start += 1;
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// 隣接するトークンを1つのトークンにまとめます。
+fn merge_tokens(tokens: &[TokenBuf], feature: &str) -> TokenBuf {
+    let first = &tokens[0];
+    let last = &tokens[tokens.len() - 1];
+    let surface: String = tokens.iter().map(|t| t.surface.as_str()).collect();
+    TokenBuf {
+        feature: feature.replace("{surface}", &surface),
+        surface,
+        range_char: first.range_char.start..last.range_char.end,
+        range_byte: first.range_byte.start..last.range_byte.end,
+        lex_type: first.lex_type,
+        word_id: first.word_id,
+        left_id: first.left_id,
+        right_id: last.right_id,
+        word_cost: tokens
+            .iter()
+            .fold(0i16, |acc, t| acc.saturating_add(t.word_cost)),
+        total_cost: last.total_cost,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::{LexType, WordIdx};
+
+    fn tokens(surfaces: &[(&str, &str)]) -> Vec<TokenBuf> {
+        let mut char_pos = 0;
+        let mut byte_pos = 0;
+        surfaces
+            .iter()
+            .map(|&(surface, feature)| {
+                let len_char = surface.chars().count();
+                let token = TokenBuf {
+                    surface: surface.to_string(),
+                    feature: feature.to_string(),
+                    range_char: char_pos..char_pos + len_char,
+                    range_byte: byte_pos..byte_pos + surface.len(),
+                    lex_type: LexType::System,
+                    word_id: WordIdx::new(LexType::System, 0),
+                    left_id: 0,
+                    right_id: 0,
+                    word_cost: 10,
+                    total_cost: 0,
+                };
+                char_pos += len_char;
+                byte_pos += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    fn surfaces(tokens: &[TokenBuf]) -> Vec<&str> {
+        tokens.iter().map(|t| t.surface.as_str()).collect()
+    }
+
+    #[test]
+    fn test_builtin() {
+        let merger = TokenMerger::builtin();
+
+        let merged = merger.merge(tokens(&[
+            ("2024", "名詞"),
+            ("年", "名詞"),
+            ("3", "名詞"),
+            ("月", "名詞"),
+            ("1", "名詞"),
+            ("日", "名詞"),
+            ("に", "助詞"),
+            ("3", "名詞"),
+            (",", "記号"),
+            ("000", "名詞"),
+            ("円", "名詞"),
+        ]));
+        assert_eq!(surfaces(&merged), ["2024年3月1日", "に", "3,000円"]);
+        assert_eq!(merged[0].feature, "名詞,数詞,日付,*,*,*,2024年3月1日");
+        assert_eq!(merged[0].range_char, 0..9);
+        assert_eq!(merged[0].word_cost, 60);
+        assert_eq!(merged[2].feature, "名詞,数詞,*,*,*,*,3,000円");
+        assert_eq!(merged[2].range_byte, 18..26);
+
+        let merged = merger.merge(tokens(&[("三", "名詞"), ("千", "名詞"), ("人", "名詞")]));
+        assert_eq!(surfaces(&merged), ["三千人"]);
+
+        let merged = merger.merge(tokens(&[("年", "名詞"), ("3", "名詞")]));
+        assert_eq!(surfaces(&merged), ["年", "3"]);
+    }
+
+    #[test]
+    fn test_not_adjacent() {
+        let mut input = tokens(&[("3", "名詞"), ("円", "名詞")]);
+        input[1].range_byte = 2..5;
+        let merged = TokenMerger::builtin().merge(input);
+        assert_eq!(surfaces(&merged), ["3", "円"]);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let merger = TokenMerger::new()
+            .add_rules("# comment\n[名詞,固有名詞] (\"・\" [名詞,固有名詞])+ => 名詞,固有名詞,{surface}")
+            .unwrap();
+        assert_eq!(merger.num_rules(), 1);
+
+        let merged = merger.merge(tokens(&[
+            ("ジョン", "名詞,固有名詞"),
+            ("・", "記号"),
+            ("スミス", "名詞,固有名詞,人名"),
+            ("・", "記号"),
+        ]));
+        assert_eq!(surfaces(&merged), ["ジョン・スミス", "・"]);
+        assert_eq!(merged[0].feature, "名詞,固有名詞,ジョン・スミス");
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rules in [
+            "num num",
+            "num ( num => *",
+            "num \"年 => *",
+            "unknown => *",
+            "=> *",
+            "num | => *",
+            "[] => *",
+        ] {
+            assert!(TokenMerger::new().add_rules(rules).is_err(), "{rules}");
+        }
+    }
+}
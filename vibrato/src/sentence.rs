@@ -4,6 +4,8 @@
 //! 内部データ構造を提供します。入力文字列を文字単位に分割し、各文字の属性情報や
 //! バイト位置のマッピング、文字のグループ化可能性などを計算・保持します。
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::dictionary::character::{ArchivedCharProperty, CharInfo, CharProperty};
 
 /// 入力テキストの内部表現を保持する構造体
@@ -22,6 +24,11 @@ use crate::dictionary::character::{ArchivedCharProperty, CharInfo, CharProperty}
 /// * `c2b` - 文字位置からバイト位置へのマッピング配列
 /// * `cinfos` - 各文字の属性情報を保持する配列
 /// * `groupable` - 各文字位置からグループ化可能な文字数を保持する配列
+/// * `replacement` - U+FFFDに`char.def`の割り当ての代わりに使用する属性情報
+/// * `group_graphemes` - 拡張書記素クラスタを1文字として扱うかどうか
+/// * `grapheme_ends` - 各文字が属する拡張書記素クラスタの終了位置を保持する配列
+/// * `split_scripts` - 文字体系の境界でグループ化を打ち切るかどうか
+/// * `scripts` - 各文字の文字体系を保持する配列
 #[derive(Default, Clone, Debug)]
 pub struct Sentence {
     input: String,
@@ -29,6 +36,46 @@ pub struct Sentence {
     c2b: Vec<usize>,
     cinfos: Vec<CharInfo>,
     groupable: Vec<usize>,
+    replacement: Option<CharInfo>,
+    group_graphemes: bool,
+    grapheme_ends: Vec<usize>,
+    split_scripts: bool,
+    scripts: Vec<Script>,
+}
+
+/// グループ化の境界の判定に使用する文字体系
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Latin,
+    Digit,
+    Other,
+}
+
+impl Script {
+    /// 文字の文字体系を返します。
+    ///
+    /// 長音符や結合文字のように直前の文字の文字体系に従う文字の場合は`None`を返します。
+    pub(crate) fn of(c: char) -> Option<Self> {
+        let script = match c {
+            '\u{30FC}' | '\u{FF70}' | '\u{0300}'..='\u{036F}' | '\u{3099}'..='\u{309C}' => {
+                return None;
+            }
+            '\u{3005}' | '\u{3007}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{3FFFF}' => Self::Kanji,
+            '\u{3041}'..='\u{309F}' => Self::Hiragana,
+            '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+                Self::Katakana
+            }
+            'A'..='Z' | 'a'..='z' | '\u{00C0}'..='\u{024F}' | '\u{FF21}'..='\u{FF3A}'
+            | '\u{FF41}'..='\u{FF5A}' => Self::Latin,
+            '0'..='9' | '\u{FF10}'..='\u{FF19}' => Self::Digit,
+            _ => Self::Other,
+        };
+        Some(script)
+    }
 }
 
 impl Sentence {
@@ -52,6 +99,39 @@ impl Sentence {
         self.c2b.clear();
         self.cinfos.clear();
         self.groupable.clear();
+        self.grapheme_ends.clear();
+        self.scripts.clear();
+    }
+
+    /// 指定された文字数の入力文をメモリを再確保せずに処理できるように容量を確保します
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 入力文の文字数
+    pub fn reserve(&mut self, max_chars: usize) {
+        // A character takes at most 4 bytes in UTF-8.
+        self.input.reserve(max_chars.saturating_mul(4).saturating_sub(self.input.len()));
+        self.chars.reserve(max_chars.saturating_sub(self.chars.len()));
+        self.c2b.reserve((max_chars + 1).saturating_sub(self.c2b.len()));
+        self.cinfos.reserve(max_chars.saturating_sub(self.cinfos.len()));
+        self.groupable.reserve(max_chars.saturating_sub(self.groupable.len()));
+    }
+
+    /// 容量を指定された文字数の入力文に必要な大きさまで縮小します
+    ///
+    /// 設定済みの入力文の情報は保持されるため、容量は使用中の大きさより小さくなりません。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 入力文の文字数
+    pub fn shrink_to(&mut self, max_chars: usize) {
+        self.input.shrink_to(max_chars.saturating_mul(4));
+        self.chars.shrink_to(max_chars);
+        self.c2b.shrink_to(max_chars + 1);
+        self.cinfos.shrink_to(max_chars);
+        self.groupable.shrink_to(max_chars);
+        self.grapheme_ends.shrink_to(max_chars);
+        self.scripts.shrink_to(max_chars);
     }
 
     /// 入力文字列を設定します
@@ -74,6 +154,48 @@ impl Sentence {
         self.input.push_str(input.as_ref());
     }
 
+    /// U+FFFD（REPLACEMENT CHARACTER）に使用する属性情報を設定します
+    ///
+    /// この設定は[`clear`](Self::clear)では消去されず、以降の解析に適用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `cinfo` - U+FFFDに使用する属性情報。`None`の場合は`char.def`の割り当てに従います
+    #[inline(always)]
+    pub fn set_replacement_char_info(&mut self, cinfo: Option<CharInfo>) {
+        self.replacement = cinfo;
+    }
+
+    /// 拡張書記素クラスタを1文字として扱うかどうかを設定します
+    ///
+    /// 有効にすると、クラスタ内の2文字目以降は先頭の文字と同じ属性情報を持ち、
+    /// [`align_end`](Self::align_end)によって未知語の終了位置がクラスタの境界に揃えられます。
+    /// この設定は[`clear`](Self::clear)では消去されず、以降の解析に適用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `true`の場合、拡張書記素クラスタを1文字として扱います
+    #[inline(always)]
+    pub fn set_group_graphemes(&mut self, yes: bool) {
+        self.group_graphemes = yes;
+    }
+
+    /// 文字体系（漢字、ひらがな、カタカナ、ラテン文字、数字）の境界でグループ化を
+    /// 打ち切るかどうかを設定します
+    ///
+    /// 有効にすると、同じカテゴリに属する文字が連続していても、文字体系が変わる位置で
+    /// [`groupable`](Self::groupable)が区切られます。長音符や結合文字は直前の文字と
+    /// 同じ文字体系として扱われます。
+    /// この設定は[`clear`](Self::clear)では消去されず、以降の解析に適用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `true`の場合、文字体系の境界でグループ化を打ち切ります
+    #[inline(always)]
+    pub fn set_split_scripts(&mut self, yes: bool) {
+        self.split_scripts = yes;
+    }
+
     /// 入力文字列を解析し、内部データ構造を構築します
     ///
     /// 設定された入力文字列に対して以下の処理を実行します:
@@ -87,6 +209,8 @@ impl Sentence {
     pub fn compile(&mut self, char_prop: &CharProperty) {
         self.compute_basic();
         self.compute_categories(char_prop);
+        self.compute_graphemes();
+        self.compute_scripts();
         self.compute_groupable();
     }
 
@@ -104,6 +228,8 @@ impl Sentence {
     pub fn compile_archived(&mut self, char_prop: &ArchivedCharProperty) {
         self.compute_basic();
         self.compute_categories_archived(char_prop);
+        self.compute_graphemes();
+        self.compute_scripts();
         self.compute_groupable();
     }
 
@@ -134,6 +260,7 @@ impl Sentence {
         for &c in &self.chars {
             self.cinfos.push(char_prop.char_info(c));
         }
+        self.apply_replacement();
     }
 
     /// アーカイブされた文字属性を使用して各文字の属性情報を計算します（内部メソッド）
@@ -153,12 +280,64 @@ impl Sentence {
         for &c in &self.chars {
             self.cinfos.push(char_prop.char_info(c));
         }
+        self.apply_replacement();
+    }
+
+    /// U+FFFDの属性情報を設定された値で上書きします（内部メソッド）
+    fn apply_replacement(&mut self) {
+        if let Some(cinfo) = self.replacement {
+            for (&c, info) in self.chars.iter().zip(&mut self.cinfos) {
+                if c == char::REPLACEMENT_CHARACTER {
+                    *info = cinfo;
+                }
+            }
+        }
+    }
+
+    /// 拡張書記素クラスタの境界を計算します（内部メソッド）
+    ///
+    /// クラスタ内の2文字目以降の属性情報を先頭の文字のもので上書きし、
+    /// 各文字が属するクラスタの終了位置を記録します。
+    fn compute_graphemes(&mut self) {
+        if !self.group_graphemes {
+            return;
+        }
+        self.grapheme_ends.reserve(self.chars.len());
+        let mut start = 0;
+        for grapheme in self.input.graphemes(true) {
+            let end = start + grapheme.chars().count();
+            for i in start..end {
+                self.cinfos[i] = self.cinfos[start];
+                self.grapheme_ends.push(end);
+            }
+            start = end;
+        }
+        debug_assert_eq!(self.grapheme_ends.len(), self.chars.len());
+    }
+
+    /// 各文字の文字体系を計算します（内部メソッド）
+    ///
+    /// 拡張書記素クラスタを1文字として扱う場合、クラスタ内の2文字目以降は
+    /// 先頭の文字と同じ文字体系として扱います。
+    fn compute_scripts(&mut self) {
+        if !self.split_scripts {
+            return;
+        }
+        self.scripts.reserve(self.chars.len());
+        let mut prev = Script::Other;
+        for (i, &c) in self.chars.iter().enumerate() {
+            let in_cluster = self.group_graphemes && i > 0 && self.grapheme_ends[i - 1] > i;
+            let script = if in_cluster { None } else { Script::of(c) };
+            prev = script.unwrap_or(prev);
+            self.scripts.push(prev);
+        }
     }
 
     /// 各文字位置からグループ化可能な文字数を計算します（内部メソッド）
     ///
     /// 隣接する文字が同じカテゴリに属する場合、それらをグループ化できるとみなし、
     /// 各位置から連続してグループ化可能な文字数を計算します。
+    /// 文字体系の境界で打ち切る設定が有効な場合は、文字体系が異なる文字もグループ化しません。
     /// この情報は未知語処理において使用されます。
     fn compute_groupable(&mut self) {
         debug_assert!(!self.chars.is_empty());
@@ -169,7 +348,8 @@ impl Sentence {
 
         for i in (1..self.chars.len()).rev() {
             let lhs = self.cinfos[i - 1].cate_idset();
-            if (lhs & rhs) != 0 {
+            let same_script = !self.split_scripts || self.scripts[i - 1] == self.scripts[i];
+            if (lhs & rhs) != 0 && same_script {
                 self.groupable[i - 1] = self.groupable[i] + 1;
             }
             rhs = lhs;
@@ -227,6 +407,20 @@ impl Sentence {
         self.c2b[pos_char]
     }
 
+    /// 指定されたバイト位置に対応する文字位置を返します
+    ///
+    /// # 引数
+    ///
+    /// * `pos_byte` - バイト位置（0始まり）
+    ///
+    /// # 戻り値
+    ///
+    /// 対応する文字位置。`pos_byte`が文字の境界でない場合は`None`
+    #[inline(always)]
+    pub fn char_position(&self, pos_byte: usize) -> Option<usize> {
+        self.c2b.binary_search(&pos_byte).ok()
+    }
+
     /// 指定された文字位置の文字属性情報を返します
     ///
     /// 指定された位置の文字の属性情報（カテゴリIDセットなど）を返します。
@@ -243,6 +437,25 @@ impl Sentence {
         self.cinfos[pos_char]
     }
 
+    /// 未知語の終了位置を拡張書記素クラスタの境界に揃えます
+    ///
+    /// 拡張書記素クラスタを1文字として扱う設定が無効な場合は、そのままの位置を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `end_char` - 終了文字位置（0始まり、排他的）
+    ///
+    /// # 戻り値
+    ///
+    /// `end_char`以上で最も近いクラスタの境界
+    #[inline(always)]
+    pub fn align_end(&self, end_char: usize) -> usize {
+        end_char
+            .checked_sub(1)
+            .and_then(|i| self.grapheme_ends.get(i))
+            .map_or(end_char, |&end| end)
+    }
+
     /// 指定された文字位置からグループ化可能な文字数を返します
     ///
     /// 指定された位置から、同じカテゴリに属する文字が連続している数を返します。
@@ -274,5 +487,8 @@ mod tests {
         assert_eq!(sent.byte_position(0), 0);
         assert_eq!(sent.byte_position(1), 3);
         assert_eq!(sent.byte_position(2), 6);
+        assert_eq!(sent.char_position(3), Some(1));
+        assert_eq!(sent.char_position(6), Some(2));
+        assert_eq!(sent.char_position(4), None);
     }
 }
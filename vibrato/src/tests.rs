@@ -4,7 +4,9 @@
 //! 動作を検証するテストを含みます。
 
 mod connector;
+mod equivalence;
 mod lexicon;
+mod reproducibility;
 mod tokenizer;
 
 #[cfg(feature = "train")]
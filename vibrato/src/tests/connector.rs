@@ -13,3 +13,29 @@ fn test_matrix() {
     assert_eq!(conn.cost(1, 0), -3689);
     assert_eq!(conn.cost(9, 9), -2490);
 }
+
+/// 辞書から接続コストを取得するテスト
+#[test]
+fn test_dictionary_connection_cost() {
+    use crate::{Dictionary, SystemDictionaryBuilder};
+
+    let build = || {
+        SystemDictionaryBuilder::from_readers(
+            include_str!("./resources/lex.csv").as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            include_str!("./resources/char.def").as_bytes(),
+            include_str!("./resources/unk.def").as_bytes(),
+        )
+        .unwrap()
+    };
+    let mut buffer = vec![];
+    build().write(&mut buffer).unwrap();
+
+    for dict in [Dictionary::from_inner(build()), Dictionary::read(buffer.as_slice()).unwrap()] {
+        assert_eq!(dict.num_left_ids(), 10);
+        assert_eq!(dict.num_right_ids(), 10);
+        assert_eq!(dict.connection_cost(0, 1), 863);
+        assert_eq!(dict.connection_cost(1, 0), -3689);
+        assert_eq!(dict.connection_cost(9, 9), -2490);
+    }
+}
@@ -0,0 +1,242 @@
+//! 所有型とアーカイブ型の辞書の等価性テスト
+//!
+//! トークナイザーの多くの処理は、所有型（[`Dictionary::Owned`]）とアーカイブ型
+//! （[`Dictionary::Archived`]）の辞書それぞれに対して重複して実装されています。
+//! 同じソースから構築した辞書を、メモリ上の所有型、`write`/`read`による
+//! ラウンドトリップ、ファイルのメモリマップ、呼び出し側が提供したヒープバッファと
+//! `'static`なメモリ領域の5通りで読み込み、トークン化の結果がコストを含めて完全に
+//! 一致することを検証します。
+//! あわせて、[`Dictionary::content_hash`]が読み込み方法によらず辞書ファイルのダイジェストと
+//! 一致することと、[`Dictionary::write_zstd`]が読み込み方法によらず同じ辞書ファイルを
+//! 圧縮することも検証します。
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use rkyv::util::AlignedVec;
+
+use crate::dictionary::{DictionaryInner, LexType, LoadMode, SystemDictionaryBuilder};
+#[cfg(feature = "loaders")]
+use crate::dictionary::{CompressedHeader, WriteOptions};
+use crate::tokenizer::TieBreak;
+use crate::{Dictionary, Tokenizer};
+
+const LEX_CSV: &str = include_str!("./resources/lex.csv");
+const USER_CSV: &str = include_str!("./resources/user.csv");
+const MATRIX_DEF: &str = include_str!("./resources/matrix.def");
+const CHAR_DEF: &str = include_str!("./resources/char.def");
+const UNK_DEF: &str = include_str!("./resources/unk.def");
+
+const CORPUS: &[&str] = &[
+    "東京都に行った",
+    "京都東京都京都",
+    "京都 東京都  京都 ",
+    "  先頭と末尾の空白  ",
+    "アイアイウアイウ",
+    "特a特a な。な",
+    "kampersandaとヴェネツィア",
+    "0123456789一二三四五六七八九〇六三四",
+    "ｶﾀｶﾅとＡＢＣと１２３",
+    "未知語だらけの文章を解析する。",
+    "X",
+    "",
+];
+
+const NUM_CONFIGS: usize = 6;
+
+type TokenFields = (Range<usize>, Range<usize>, String, LexType, u32, u16, u16, i16, i32);
+
+/// [`Token`](crate::token::Token)と[`NbestToken`](crate::token::NbestToken)から比較対象の値を取り出します。
+macro_rules! token_fields {
+    ($t:expr) => {
+        (
+            $t.range_char(),
+            $t.range_byte(),
+            $t.feature().to_string(),
+            $t.lex_type(),
+            $t.word_idx().word_id,
+            $t.left_id(),
+            $t.right_id(),
+            $t.word_cost(),
+            $t.total_cost(),
+        )
+    };
+}
+
+fn build_inner() -> DictionaryInner {
+    SystemDictionaryBuilder::from_readers(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+    .unwrap()
+}
+
+/// 所有型、ラウンドトリップしたアーカイブ型、メモリマップしたアーカイブ型、
+/// 呼び出し側が提供したメモリ領域上のアーカイブ型の辞書を作成します。
+fn build_variants() -> (Vec<(&'static str, Arc<Dictionary>)>, tempfile::TempDir) {
+    let owned = Dictionary::from_inner(build_inner());
+    assert!(matches!(owned, Dictionary::Owned { .. }));
+
+    let mut buffer = vec![];
+    build_inner().write(&mut buffer).unwrap();
+    let read = Dictionary::read(buffer.as_slice()).unwrap();
+    assert!(matches!(read, Dictionary::Archived(_)));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("system.dic");
+    std::fs::write(&path, &buffer).unwrap();
+    let mmap = Dictionary::from_path(&path, LoadMode::Validate).unwrap();
+    assert!(matches!(mmap, Dictionary::Archived(_)));
+
+    let mut aligned = AlignedVec::<16>::with_capacity(buffer.len());
+    aligned.extend_from_slice(&buffer);
+    let aligned_owned = Dictionary::from_aligned_owned(aligned.clone()).unwrap();
+    assert!(matches!(aligned_owned, Dictionary::Archived(_)));
+
+    let static_bytes: &'static AlignedVec<16> = Box::leak(Box::new(aligned));
+    // SAFETY: The bytes were just written by `DictionaryInner::write`.
+    let static_dict = unsafe { Dictionary::from_static_bytes(static_bytes) }.unwrap();
+
+    let variants = vec![
+        ("owned", owned),
+        ("read", read),
+        ("mmap", mmap),
+        ("aligned_owned", aligned_owned),
+        ("static", static_dict),
+    ];
+    (variants.into_iter().map(|(name, dict)| (name, Arc::new(dict))).collect(), dir)
+}
+
+/// 各トークナイザー設定を適用します。
+fn configure(tokenizer: Tokenizer, config: usize) -> Tokenizer {
+    match config {
+        0 => tokenizer,
+        1 => tokenizer.ignore_space(true).unwrap(),
+        2 => tokenizer.max_grouping_len(2),
+        3 => tokenizer.lex_type_priority(true),
+        4 => tokenizer.tie_break(TieBreak::MecabCompatible),
+        5 => tokenizer.tie_break(TieBreak::FirstWins),
+        _ => unreachable!(),
+    }
+}
+
+fn tokenize(tokenizer: &Tokenizer, unk_grouping: bool) -> Vec<Vec<TokenFields>> {
+    let mut worker = tokenizer.new_worker();
+    worker.set_unk_grouping(unk_grouping);
+    CORPUS
+        .iter()
+        .map(|sentence| {
+            worker.reset_sentence(sentence);
+            worker.tokenize();
+            worker
+                .token_iter()
+                .map(|t| token_fields!(t))
+                .collect()
+        })
+        .collect()
+}
+
+fn tokenize_nbest(tokenizer: &Tokenizer, n: usize) -> Vec<Vec<(i32, Vec<TokenFields>)>> {
+    let mut worker = tokenizer.new_worker();
+    CORPUS
+        .iter()
+        .map(|sentence| {
+            worker.reset_sentence(sentence);
+            worker.tokenize_nbest(n);
+            (0..worker.num_nbest_paths())
+                .map(|i| {
+                    let tokens = worker
+                        .nbest_token_iter(i)
+                        .unwrap()
+                        .map(|t| token_fields!(t))
+                        .collect();
+                    (worker.path_cost(i).unwrap(), tokens)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_owned_and_archived_tokenize_identically() {
+    let (variants, _dir) = build_variants();
+    for config in 0..NUM_CONFIGS {
+        for unk_grouping in [true, false] {
+            let results: Vec<_> = variants
+                .iter()
+                .map(|(name, dict)| {
+                    let tokenizer =
+                        configure(Tokenizer::from_shared_dictionary(Arc::clone(dict)), config);
+                    (name, tokenize(&tokenizer, unk_grouping))
+                })
+                .collect();
+            let (_, expected) = &results[0];
+            for (name, result) in &results[1..] {
+                assert_eq!(
+                    result, expected,
+                    "{name} diverges from owned (config {config}, unk_grouping {unk_grouping})",
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_owned_and_archived_nbest_identically() {
+    let (variants, _dir) = build_variants();
+    for config in 0..NUM_CONFIGS {
+        let results: Vec<_> = variants
+            .iter()
+            .map(|(name, dict)| {
+                let tokenizer =
+                    configure(Tokenizer::from_shared_dictionary(Arc::clone(dict)), config);
+                (name, tokenize_nbest(&tokenizer, 5))
+            })
+            .collect();
+        let (_, expected) = &results[0];
+        for (name, result) in &results[1..] {
+            assert_eq!(result, expected, "{name} diverges from owned (config {config})");
+        }
+    }
+}
+
+#[cfg(feature = "loaders")]
+#[test]
+fn test_content_hash() {
+    let (variants, dir) = build_variants();
+    let expected = crate::dictionary::hash_file(dir.path().join("system.dic")).unwrap();
+    for (name, dict) in &variants[1..] {
+        assert_eq!(dict.content_hash().unwrap(), expected, "{name}");
+        // The second call returns the cached digest.
+        assert_eq!(dict.content_hash().unwrap(), expected, "{name}");
+    }
+
+    let (_, owned) = &variants[0];
+    let mut buffer = vec![];
+    owned.write(&mut buffer).unwrap();
+    let serialized = crate::dictionary::sha256_hex(buffer.as_slice()).unwrap();
+    assert_eq!(owned.content_hash().unwrap(), serialized);
+}
+
+#[cfg(feature = "loaders")]
+#[test]
+fn test_write_zstd() {
+    let (variants, dir) = build_variants();
+    let expected = std::fs::read(dir.path().join("system.dic")).unwrap();
+    let options = WriteOptions::new().level(3).workers(2);
+    for (name, dict) in &variants {
+        let mut compressed = vec![];
+        dict.write_zstd(&mut compressed, &options).unwrap();
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), expected, "{name}");
+
+        let mut rdr = compressed.as_slice();
+        let header = CompressedHeader::read(&mut rdr).unwrap().unwrap();
+        assert_eq!(header.content_hash(), dict.content_hash().unwrap(), "{name}");
+        assert_eq!(header.content_size(), expected.len() as u64, "{name}");
+        header.verify(rdr).unwrap();
+    }
+}
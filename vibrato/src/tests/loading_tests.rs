@@ -9,7 +9,9 @@ use std::sync::{Mutex, OnceLock};
 
 use tempfile::{tempdir, TempDir};
 
-use vibrato_rkyv::dictionary::{CacheStrategy, PresetDictionaryKind, GLOBAL_CACHE_DIR};
+use vibrato_rkyv::dictionary::{
+    BufferKind, CacheStrategy, PresetDictionaryKind, ProofLocation, GLOBAL_CACHE_DIR,
+};
 use vibrato_rkyv::{Dictionary, LoadMode};
 
 struct GlobalTestResources {
@@ -142,6 +144,31 @@ fn test_from_zstd_rkyv_creates_local_cache() {
     assert!(matches!(dict, Dictionary::Archived(_)));
 }
 
+/// 展開元の記録と一致しないキャッシュが作り直されることを確認
+#[test]
+fn test_from_zstd_regenerates_stale_cache() {
+    let _guard = TEST_MUTEX.lock().unwrap();
+    let env = TestEnv::new();
+    env.clear_vibrato_caches();
+
+    Dictionary::from_zstd(&env.rkyv_zst_path, CacheStrategy::Local).unwrap();
+    let cache_dir = env.work_dir.join(".cache");
+    let record_path = fs::read_dir(&cache_dir)
+        .unwrap()
+        .map(|r| r.unwrap().path())
+        .find(|p| p.extension().is_some_and(|e| e == "source"))
+        .unwrap();
+    let record = fs::read_to_string(&record_path).unwrap();
+    assert!(record.contains("format="));
+
+    // Simulates a source replaced by a file with identical size and mtime.
+    fs::write(&record_path, "content-sha256=stale\n").unwrap();
+
+    let dict = Dictionary::from_zstd(&env.rkyv_zst_path, CacheStrategy::Local).unwrap();
+    assert!(matches!(dict, Dictionary::Archived(_)));
+    assert_eq!(fs::read_to_string(&record_path).unwrap(), record);
+}
+
 /// レガシー形式の辞書がrkyv形式に変換されキャッシュされることを確認
 #[test]
 #[cfg(feature = "legacy")]
@@ -156,12 +183,38 @@ fn test_from_zstd_legacy_converts_and_caches_as_rkyv() {
 
     assert!(cache_dir.exists());
     let cached_files: Vec<_> = fs::read_dir(&cache_dir).unwrap().map(|r| r.unwrap().path()).collect();
-    assert_eq!(cached_files.len(), 2);
+    assert_eq!(cached_files.len(), 3);
 
     let dict_rkyv_from_cache = Dictionary::from_zstd(&env.legacy_zst_path, CacheStrategy::Local).unwrap();
     assert!(matches!(dict_rkyv_from_cache, Dictionary::Archived(_)));
 }
 
+/// xz圧縮された辞書が形式を自動判別して読み込まれることを確認
+#[test]
+#[cfg(feature = "codecs")]
+fn test_from_compressed_detects_xz() {
+    let _guard = TEST_MUTEX.lock().unwrap();
+    let env = TestEnv::new();
+    env.clear_vibrato_caches();
+
+    let dic_path = env.work_dir.join("system.dic");
+    Dictionary::decompress_zstd(&env.rkyv_zst_path, &dic_path).unwrap();
+    let xz_path = env.work_dir.join("system.dic.xz");
+    {
+        let mut encoder = xz2::write::XzEncoder::new(fs::File::create(&xz_path).unwrap(), 6);
+        std::io::copy(&mut fs::File::open(&dic_path).unwrap(), &mut encoder).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    let dict = Dictionary::from_compressed(&xz_path, CacheStrategy::Local).unwrap();
+    assert!(matches!(dict, Dictionary::Archived(_)));
+    assert!(env.work_dir.join(".cache").read_dir().unwrap().next().is_some());
+
+    // An uncompressed dictionary is loaded directly without caching.
+    let dict = Dictionary::from_compressed(&dic_path, CacheStrategy::Local).unwrap();
+    assert!(matches!(dict, Dictionary::Archived(_)));
+}
+
 /// TrustCacheモードでの辞書読み込みとキャッシュ動作のテスト
 #[test]
 fn test_from_path_trustcache_flow() {
@@ -199,4 +252,31 @@ fn test_from_path_validate_mode() {
     let dict = Dictionary::from_path(&dic_path, LoadMode::Validate).unwrap();
 
     assert!(matches!(dict, Dictionary::Archived(_)));
-}
\ No newline at end of file
+}
+
+/// 読み込みレポートに検証とプルーフファイルの状態が記録されることを確認
+#[test]
+fn test_from_path_with_report() {
+    let _guard = TEST_MUTEX.lock().unwrap();
+    let env = TestEnv::new();
+    env.clear_vibrato_caches();
+
+    let dic_path = env.work_dir.join("test.dic");
+    Dictionary::decompress_zstd(&env.rkyv_zst_path, &dic_path).unwrap();
+
+    let (_, report) = Dictionary::from_path_with_report(&dic_path, LoadMode::TrustCache).unwrap();
+    assert!(report.validated);
+    assert!(report.proof_created);
+    assert_eq!(report.proof_hit, None);
+    assert_eq!(report.file_size, fs::metadata(&dic_path).unwrap().len());
+    assert!(report.num_left_ids > 0 && report.num_right_ids > 0);
+    assert!(!report.has_user_lexicon);
+    assert!(report.total_duration >= report.validation_duration);
+
+    let (dict, report) = Dictionary::from_path_with_report(&dic_path, LoadMode::TrustCache).unwrap();
+    assert!(matches!(dict, Dictionary::Archived(_)));
+    assert!(!report.validated);
+    assert!(!report.proof_created);
+    assert_eq!(report.proof_hit, Some(ProofLocation::Global));
+    assert_eq!(report.buffer, BufferKind::Mmap);
+}
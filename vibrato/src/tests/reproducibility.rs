@@ -0,0 +1,115 @@
+//! 辞書のシリアライズの再現性テスト
+//!
+//! 同じ入力から構築した辞書が、構築の回数やスレッド数によらず同じバイト列に
+//! シリアライズされることを検証します。配布する辞書のハッシュ値を第三者が
+//! 再構築によって確認できることの前提となります。
+
+use std::fs;
+use std::path::Path;
+
+use crate::dictionary::SystemDictionaryBuilder;
+use crate::dictionary::builder::{BuildOptions, BuildSource};
+
+const LEX_CSV: &str = include_str!("./resources/lex.csv");
+const MATRIX_DEF: &str = include_str!("./resources/matrix.def");
+const CHAR_DEF: &str = include_str!("./resources/char.def");
+const UNK_DEF: &str = include_str!("./resources/unk.def");
+
+// Every feature template distinguishes the two connection IDs equally, so the greedy selection
+// of the templates for the dual connector has many ties.
+const BIGRAM_RIGHT: &str = "\
+1\tAB,*,CD,*,EF,*,GH,*,IJ,*,KL,*,MN,*,OP,*,QR,*,ST
+2\tUV,*,WX,*,YZ,*,12,*,34,*,56,*,78,*,90,*,*,*,*";
+const BIGRAM_LEFT: &str = "\
+1\tuv,*,wx,*,yz,*,12,*,34,*,56,*,78,*,90,*,*,*,*
+2\tab,*,cd,*,ef,*,gh,*,ij,*,kl,*,mn,*,op,*,qr,*,st";
+const BIGRAM_COST: &str = "\
+AB/ab\t-10
+CD/cd\t20
+EF/ef\t-30
+GH/gh\t40
+IJ/ij\t-50
+KL/kl\t60
+MN/mn\t-70
+OP/op\t80
+QR/qr\t-90
+ST/st\t100
+UV/uv\t-110
+WX/wx\t120
+YZ/yz\t-130
+12/12\t140
+34/34\t-150
+56/56\t160
+78/78\t-170
+90/90\t180";
+
+/// 構築した辞書をシリアライズしたバイト列を返します。
+fn build_bytes(source: &BuildSource, num_threads: usize) -> Vec<u8> {
+    let options = BuildOptions::new().num_threads(num_threads);
+    let dict = SystemDictionaryBuilder::from_source_with_options(source, &options).unwrap();
+    let mut bytes = vec![];
+    dict.write(&mut bytes).unwrap();
+    bytes
+}
+
+fn assert_reproducible(source: &BuildSource) {
+    let expected = build_bytes(source, 1);
+    for num_threads in [1, 1, 2, 4] {
+        assert_eq!(build_bytes(source, num_threads), expected, "num_threads = {num_threads}");
+    }
+}
+
+fn write_files(dir: &Path, files: &[(&str, &str)]) {
+    for (name, content) in files {
+        fs::write(dir.join(name), content).unwrap();
+    }
+}
+
+#[test]
+fn test_matrix_reproducible() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    write_files(
+        dir.path(),
+        &[
+            ("lex.csv", LEX_CSV),
+            ("matrix.def", MATRIX_DEF),
+            ("char.def", CHAR_DEF),
+            ("unk.def", UNK_DEF),
+        ],
+    );
+    assert_reproducible(&BuildSource::FromMatrix {
+        lexicon: path("lex.csv"),
+        matrix: path("matrix.def"),
+        char_def: path("char.def"),
+        unk_def: path("unk.def"),
+    });
+}
+
+#[test]
+fn test_bigram_reproducible() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    write_files(
+        dir.path(),
+        &[
+            ("lex.csv", "東京,1,2,100,名詞\n京都,2,1,200,名詞\n"),
+            ("bigram.right", BIGRAM_RIGHT),
+            ("bigram.left", BIGRAM_LEFT),
+            ("bigram.cost", BIGRAM_COST),
+            ("char.def", "DEFAULT 0 1 0"),
+            ("unk.def", "DEFAULT,0,0,100,*"),
+        ],
+    );
+    for dual_connector in [false, true] {
+        assert_reproducible(&BuildSource::FromBigram {
+            lexicon: path("lex.csv"),
+            bigram_right: path("bigram.right"),
+            bigram_left: path("bigram.left"),
+            bigram_cost: path("bigram.cost"),
+            char_def: path("char.def"),
+            unk_def: path("unk.def"),
+            dual_connector,
+        });
+    }
+}
@@ -3,7 +3,11 @@
 //! 様々な入力文字列に対する形態素解析の動作を検証します。
 //! 単語境界の認識、ユーザー辞書、空白処理、未知語処理などをテストします。
 
-use crate::dictionary::SystemDictionaryBuilder;
+use crate::dictionary::{LexType, SystemDictionaryBuilder};
+use crate::tokenizer::{
+    Augmentation, EdgeScorer, EdgeWord, LatticeAugmenter, TokenizerOptions, UnkInput,
+    UnknownFallback,
+};
 use crate::{Dictionary, Tokenizer};
 
 const LEX_CSV: &str = include_str!("./resources/lex.csv");
@@ -328,6 +332,55 @@ fn test_tokenize_tokyoto_with_spaces_ignored() {
     );
 }
 
+/// 空白トークンを出力する設定での形態素解析テスト
+#[test]
+fn test_tokenize_tokyoto_with_space_tokens() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+
+    let tokenizer = Tokenizer::new(dict)
+        .keep_space_tokens(true)
+        .unwrap()
+        .space_token_feature("空白,*");
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("  東京   都 ");
+    worker.tokenize();
+
+    let tokens: Vec<_> = worker
+        .token_iter()
+        .map(|t| (t.surface(), t.range_char(), t.lex_type()))
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            ("  ", 0..2, LexType::Unknown),
+            ("東京", 2..4, LexType::System),
+            ("   ", 4..7, LexType::Unknown),
+            ("都", 7..8, LexType::System),
+            (" ", 8..9, LexType::Unknown),
+        ]
+    );
+    assert_eq!(worker.token(0).feature(), "空白,*");
+    assert_eq!(worker.token(0).word_idx(), crate::dictionary::WordIdx::SPACE);
+    assert_eq!(worker.token(0).word_cost(), 0);
+    assert_eq!(worker.token(0).total_cost(), 0);
+    assert_eq!(worker.token(2).total_cost(), worker.token(1).total_cost());
+    assert_eq!(worker.token(4).total_cost(), worker.token(3).total_cost());
+
+    // Non-space tokens are the same as with ignore_space(true).
+    assert_eq!(worker.token(1).total_cost(), -79 + 2816);
+    assert_eq!(worker.token(3).total_cost(), -79 + 2816 - 390 + 2914);
+
+    worker.reset_sentence("   ");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).surface(), "   ");
+}
+
 /// 空白を無視する設定での形態素解析テスト(文頭の空白)
 #[test]
 fn test_tokenize_tokyoto_startswith_spaces_ignored() {
@@ -514,6 +567,387 @@ fn test_tokenize_kampersanda_with_max_grouping() {
     );
 }
 
+/// カテゴリごとの最大グルーピング長の設定テスト
+#[test]
+fn test_tokenize_kampersanda_with_category_grouping() {
+    let surfaces = |tokenizer: &Tokenizer| {
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("kampersanda");
+        worker.tokenize();
+        worker.token_iter().map(|t| t.surface().to_string()).collect::<Vec<_>>()
+    };
+    let build = || {
+        build_test_dictionary(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+    };
+
+    // Same as the global limit when overriding the category of the characters.
+    let tokenizer = Tokenizer::new(build()).category_grouping_len("ALPHA", 9).unwrap();
+    assert_eq!(surfaces(&tokenizer), ["k", "ampersanda"]);
+
+    // Overrides for other categories do not affect the result.
+    let tokenizer = Tokenizer::new(build())
+        .max_grouping_len(9)
+        .category_grouping_len("KATAKANA", 24)
+        .unwrap();
+    assert_eq!(surfaces(&tokenizer), ["k", "ampersanda"]);
+
+    // Lifts the global limit for the category.
+    let tokenizer = Tokenizer::new(build())
+        .max_grouping_len(9)
+        .category_grouping_len("ALPHA", 0)
+        .unwrap();
+    assert_eq!(surfaces(&tokenizer), surfaces(&Tokenizer::new(build())));
+
+    assert!(Tokenizer::new(build()).category_grouping_len("UNDEFINED", 1).is_err());
+}
+
+/// `TokenizerOptions`による設定の一括適用のテスト
+#[test]
+fn test_tokenize_with_options() {
+    let surfaces = |tokenizer: &Tokenizer, text: &str| {
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence(text);
+        worker.tokenize();
+        worker.token_iter().map(|t| t.surface().to_string()).collect::<Vec<_>>()
+    };
+    let build = || {
+        build_test_dictionary(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+    };
+
+    let options = TokenizerOptions::new()
+        .ignore_space(true)
+        .keep_space_tokens(true)
+        .max_grouping_len(9);
+    let chained = Tokenizer::new(build())
+        .keep_space_tokens(true)
+        .unwrap()
+        .max_grouping_len(9);
+    let tokenizer = Tokenizer::with_options(build(), &options).unwrap();
+    for text in ["kampersanda", "東京都  に住む"] {
+        assert_eq!(surfaces(&tokenizer, text), surfaces(&chained, text));
+    }
+
+    // Applying the default options restores the default behavior.
+    let tokenizer = tokenizer.apply_options(&TokenizerOptions::new()).unwrap();
+    let default = Tokenizer::new(build());
+    for text in ["kampersanda", "東京都  に住む"] {
+        assert_eq!(surfaces(&tokenizer, text), surfaces(&default, text));
+    }
+
+    let options = TokenizerOptions::new().keep_space_tokens(true);
+    assert!(Tokenizer::with_options(build(), &options).is_err());
+    let options = TokenizerOptions::new().category_grouping_len("UNDEFINED", 1);
+    assert!(Tokenizer::with_options(build(), &options).is_err());
+}
+
+/// 分かち書き専用の解析が通常の解析と同じ境界を返すことのテスト
+#[test]
+fn test_segment() {
+    let build = || {
+        build_test_dictionary(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+    };
+    let tokenizers = [
+        Tokenizer::new(build()),
+        Tokenizer::new(build()).ignore_space(true).unwrap(),
+        Tokenizer::new(build()).keep_space_tokens(true).unwrap(),
+    ];
+
+    // The last text is long enough to bypass the per-thread scratch space.
+    let long_text = "東京都に住む ".repeat(1000);
+    let mut ranges = vec![0..1];
+    for tokenizer in &tokenizers {
+        let mut worker = tokenizer.new_worker();
+        for text in ["東京都に住む", "kampersanda", "  東京都  に住む  ", "", &long_text] {
+            worker.reset_sentence(text);
+            worker.tokenize();
+            let expected: Vec<_> = worker.token_iter().map(|t| t.range_byte()).collect();
+            tokenizer.segment(text, &mut ranges);
+            assert_eq!(ranges, expected);
+        }
+    }
+}
+
+/// 入力文への追記によるトークン化が、文全体のトークン化と同じ結果になることのテスト
+#[test]
+fn test_extend_sentence() {
+    let build = || {
+        let dict_inner = SystemDictionaryBuilder::from_readers(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+        .unwrap()
+        .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+        .unwrap();
+        Dictionary::from_inner(dict_inner)
+    };
+    let tokenizers = [
+        Tokenizer::new(build()),
+        Tokenizer::new(build()).keep_space_tokens(true).unwrap(),
+        Tokenizer::new(build()).strict_user_lexicon(true),
+        Tokenizer::new(build()).max_grouping_len(3).group_graphemes(true),
+        Tokenizer::new(build()).tie_break(crate::tokenizer::TieBreak::MecabCompatible),
+    ];
+    let texts = [
+        "京都東京都京都",
+        "東京都に住む kampersanda",
+        "  東京  京都  ",
+        "ヴェネツィアへ行った👨‍👩‍👧",
+    ];
+
+    let tokens = |worker: &crate::tokenizer::worker::Worker| {
+        worker
+            .token_iter()
+            .map(|t| (t.range_byte(), t.word_idx(), t.total_cost()))
+            .collect::<Vec<_>>()
+    };
+    for tokenizer in &tokenizers {
+        let mut incremental = tokenizer.new_worker();
+        let mut full = tokenizer.new_worker();
+        for text in texts {
+            incremental.reset_sentence("");
+            let mut prefix = String::new();
+            for c in text.chars() {
+                prefix.push(c);
+                incremental.extend_sentence(c.to_string());
+                incremental.tokenize();
+                full.reset_sentence(&prefix);
+                full.tokenize();
+                assert_eq!(tokens(&incremental), tokens(&full), "{prefix}");
+            }
+
+            // Appends several characters at once.
+            let mid = text.char_indices().nth(text.chars().count() / 2).unwrap().0;
+            let (head, tail) = text.split_at(mid);
+            incremental.reset_sentence(head);
+            incremental.tokenize();
+            incremental.extend_sentence(tail);
+            incremental.tokenize();
+            full.reset_sentence(text);
+            full.tokenize();
+            assert_eq!(tokens(&incremental), tokens(&full), "{head}|{tail}");
+        }
+    }
+}
+
+/// N-bestパスのコストの内訳のテスト
+#[test]
+fn test_nbest_path_details() {
+    let dict_inner = SystemDictionaryBuilder::from_readers(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+    .unwrap();
+    let tokenizer = Tokenizer::new(Dictionary::from_inner(dict_inner)).user_cost_bonus(100);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("京都東京都京都に行った");
+    worker.tokenize_nbest(5);
+    assert!(worker.num_nbest_paths() > 1);
+
+    for i in 0..worker.num_nbest_paths() {
+        let details = worker.path_details(i).unwrap();
+        let tokens: Vec<_> = worker.nbest_token_iter(i).unwrap().collect();
+        assert_eq!(details.cost, worker.path_cost(i).unwrap());
+        assert_eq!(details.word_costs.len(), tokens.len());
+        assert_eq!(details.connection_costs.len(), tokens.len() + 1);
+        let total: i32 = details.word_costs.iter().chain(&details.connection_costs).sum();
+        assert_eq!(total, details.cost);
+        for (token, &word_cost) in tokens.iter().zip(&details.word_costs) {
+            let bonus = if token.lex_type() == LexType::User { 100 } else { 0 };
+            assert_eq!(word_cost, i32::from(token.word_cost()) - bonus);
+        }
+    }
+    assert_eq!(worker.path_details(worker.num_nbest_paths()), None);
+}
+
+/// エッジのスコアラーによる接続コストの補正のテスト
+#[test]
+fn test_edge_scorer() {
+    struct Boost;
+
+    impl EdgeScorer for Boost {
+        fn score(&self, left: Option<&EdgeWord>, right: Option<&EdgeWord>) -> i32 {
+            match (left, right) {
+                (Some(l), Some(r)) if l.surface == "東京" && r.surface == "都" => {
+                    assert_eq!(l.range_char.end, r.range_char.start);
+                    -10000
+                }
+                _ => 0,
+            }
+        }
+    }
+
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict).edge_scorer(Boost);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京都に行った");
+    worker.tokenize();
+    let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces[..2], ["東京", "都"]);
+
+    let mut ranges = vec![];
+    tokenizer.segment("東京都に行った", &mut ranges);
+    assert_eq!(ranges[..2], [0..6, 6..9]);
+
+    worker.tokenize_nbest(3);
+    let surfaces: Vec<_> = worker.nbest_token_iter(0).unwrap().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces[..2], ["東京", "都"]);
+    for i in 0..worker.num_nbest_paths() {
+        let details = worker.path_details(i).unwrap();
+        let total: i32 = details.word_costs.iter().chain(&details.connection_costs).sum();
+        assert_eq!(total, details.cost);
+    }
+
+    worker.reset_sentence("東京");
+    worker.tokenize();
+    worker.extend_sentence("都");
+    worker.tokenize();
+    let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, ["東京", "都"]);
+}
+
+/// ラティスへの候補の追加のテスト
+#[test]
+fn test_lattice_augmenter() {
+    struct Variants;
+
+    impl LatticeAugmenter for Variants {
+        fn augment(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(Augmentation)) {
+            if input.chars()[start_char..].starts_with(&['と', 'う', 'き', 'ょ', 'う']) {
+                let end_char = start_char + 5;
+                emit(Augmentation { end_char, surface: "東京", penalty: -10000 });
+                // Ignored because no word matches exactly or the span is invalid.
+                emit(Augmentation { end_char, surface: "東京タワー", penalty: -20000 });
+                emit(Augmentation { end_char, surface: "存在しない", penalty: -20000 });
+                emit(Augmentation { end_char: start_char, surface: "東京", penalty: -20000 });
+                emit(Augmentation { end_char: 100, surface: "東京", penalty: -20000 });
+            }
+        }
+    }
+
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict).lattice_augmenter(Variants);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("とうきょうに行った");
+    worker.tokenize();
+    let t = worker.token(0);
+    assert_eq!(t.surface(), "とうきょう");
+    assert_eq!(t.range_char(), 0..5);
+    assert_eq!(t.lex_type(), LexType::System);
+    assert!(t.feature().starts_with("東京,名詞,固有名詞"));
+
+    worker.tokenize_nbest(3);
+    let t = worker.nbest_token_iter(0).unwrap().next().unwrap();
+    assert_eq!(t.surface(), "とうきょう");
+    assert!(t.feature().starts_with("東京,名詞,固有名詞"));
+
+    worker.reset_sentence("とう");
+    worker.tokenize();
+    worker.extend_sentence("きょう");
+    worker.tokenize();
+    assert_eq!(worker.token(0).surface(), "とうきょう");
+}
+
+/// ラティスへ追加した候補の罰則がN-best解析のコストに反映されることのテスト
+#[test]
+fn test_lattice_augmenter_penalty() {
+    struct Variants;
+
+    impl LatticeAugmenter for Variants {
+        fn augment(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(Augmentation)) {
+            if start_char == 0 && input.chars().len() == 5 {
+                emit(Augmentation { end_char: 5, surface: "東京", penalty: 250 });
+            }
+        }
+    }
+
+    let lexicon_csv = "東京,0,0,100,tokyo\nとうきょう,0,0,300,kana";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1000,unknown";
+    let dict = build_test_dictionary(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict).lattice_augmenter(Variants);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("とうきょう");
+    worker.tokenize();
+    assert_eq!(worker.token(0).feature(), "kana");
+
+    worker.tokenize_nbest(3);
+    assert_eq!(worker.num_nbest_paths(), 2);
+    assert_eq!(worker.path_cost(0), Some(300));
+    assert_eq!(worker.path_cost(1), Some(350));
+
+    let t = worker.nbest_token_iter(1).unwrap().next().unwrap();
+    assert_eq!(t.surface(), "とうきょう");
+    assert_eq!(t.feature(), "tokyo");
+    assert_eq!(t.word_cost(), 350);
+    assert_eq!(t.total_cost(), 350);
+    assert_eq!(worker.path_details(1).unwrap().word_costs, [350]);
+}
+
+/// 未知語のグループ化を無効にした形態素解析テスト
+#[test]
+fn test_tokenize_kampersanda_without_unk_grouping() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+    worker.set_unk_grouping(false);
+    worker.reset_sentence("kampersanda");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 11);
+
+    for (i, t) in worker.token_iter().enumerate() {
+        assert_eq!(t.range_char(), i..i + 1);
+        assert_eq!(t.feature(), "名詞,普通名詞,一般,*,*,*");
+    }
+
+    worker.set_unk_grouping(true);
+    worker.reset_sentence("kampersanda");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+}
+
 /// 未登録の地名を含む文字列の形態素解析テスト
 #[test]
 fn test_tokenize_tokyoken() {
@@ -602,3 +1036,950 @@ fn test_tokenize_repeat() {
     worker.tokenize();
     assert_eq!(worker.num_tokens(), 1);
 }
+
+#[test]
+fn test_tokenize_from_parts() {
+    use crate::dictionary::{
+        CharProperty, ConnectorWrapper, DictionaryInner, Lexicon, MatrixConnector, UnkHandler,
+    };
+
+    let char_prop = CharProperty::from_reader(CHAR_DEF.as_bytes()).unwrap();
+    let unk_handler = UnkHandler::from_reader(UNK_DEF.as_bytes(), &char_prop).unwrap();
+    let dict_inner = DictionaryInner::from_parts(
+        Lexicon::from_reader(LEX_CSV.as_bytes(), LexType::System).unwrap(),
+        None,
+        ConnectorWrapper::Matrix(MatrixConnector::from_reader(MATRIX_DEF.as_bytes()).unwrap()),
+        char_prop,
+        unk_handler,
+    )
+    .unwrap();
+    let tokenizer = Tokenizer::from_inner(dict_inner);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("京都東京都");
+    worker.tokenize();
+
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut expected = tokenizer.new_worker();
+    expected.reset_sentence("京都東京都");
+    expected.tokenize();
+
+    assert_eq!(worker.num_tokens(), expected.num_tokens());
+    for (t, e) in worker.token_iter().zip(expected.token_iter()) {
+        assert_eq!(t.surface(), e.surface());
+        assert_eq!(t.feature(), e.feature());
+        assert_eq!(t.total_cost(), e.total_cost());
+    }
+}
+
+#[test]
+fn test_from_parts_wrong_lex_type() {
+    use crate::dictionary::{
+        CharProperty, ConnectorWrapper, DictionaryInner, Lexicon, MatrixConnector, UnkHandler,
+    };
+
+    let char_prop = CharProperty::from_reader(CHAR_DEF.as_bytes()).unwrap();
+    let unk_handler = UnkHandler::from_reader(UNK_DEF.as_bytes(), &char_prop).unwrap();
+    let result = DictionaryInner::from_parts(
+        Lexicon::from_reader(LEX_CSV.as_bytes(), LexType::User).unwrap(),
+        None,
+        ConnectorWrapper::Matrix(MatrixConnector::from_reader(MATRIX_DEF.as_bytes()).unwrap()),
+        char_prop,
+        unk_handler,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_tokenize_lex_type_priority() {
+    let lexicon_csv = "自然,0,0,1,system";
+    let user_csv = "自然,0,0,1,user";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1,unknown";
+
+    let dict_inner = SystemDictionaryBuilder::from_readers(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(user_csv.as_bytes()))
+    .unwrap();
+    let dict = Dictionary::from_inner(dict_inner);
+
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("自然");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).feature(), "system");
+
+    let tokenizer = tokenizer.lex_type_priority(true);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("自然");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).feature(), "user");
+    assert_eq!(worker.token(0).lex_type(), LexType::User);
+}
+
+#[test]
+fn test_tokenize_tie_break() {
+    use crate::tokenizer::TieBreak;
+
+    let tokenize = |lexicon_csv: &str, user_csv: Option<&str>, char_def: &str, tie_break| {
+        let mut dict_inner = SystemDictionaryBuilder::from_readers(
+            lexicon_csv.as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            char_def.as_bytes(),
+            "DEFAULT,0,0,1,unknown".as_bytes(),
+        )
+        .unwrap();
+        if let Some(user_csv) = user_csv {
+            dict_inner = dict_inner
+                .reset_user_lexicon_from_reader(Some(user_csv.as_bytes()))
+                .unwrap();
+        }
+        let tokenizer = Tokenizer::from_inner(dict_inner).tie_break(tie_break);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然");
+        worker.tokenize();
+        worker.token_iter().map(|t| t.feature().to_string()).collect::<Vec<_>>()
+    };
+
+    // Homographs in the same lexicon.
+    let homographs = "自然,0,0,1,first\n自然,0,0,1,second";
+    // System vs. user lexicon.
+    let system = "自然,0,0,1,system";
+    let user = Some("自然,0,0,1,user");
+    // System lexicon vs. unknown words, which are always invoked here.
+    let invoke = "DEFAULT 1 1 0";
+
+    let cases = [
+        (TieBreak::LastWins, ["second", "system", "unknown"]),
+        (TieBreak::FirstWins, ["first", "user", "system"]),
+        (TieBreak::LexTypePriority, ["second", "user", "system"]),
+        (TieBreak::MecabCompatible, ["first", "system", "system"]),
+    ];
+    for (tie_break, expected) in cases {
+        assert_eq!(
+            tokenize(homographs, None, "DEFAULT 0 1 0", tie_break),
+            [expected[0]],
+            "{tie_break:?}",
+        );
+        assert_eq!(
+            tokenize(system, user, "DEFAULT 0 1 0", tie_break),
+            [expected[1]],
+            "{tie_break:?}",
+        );
+        assert_eq!(
+            tokenize(system, None, invoke, tie_break),
+            [expected[2]],
+            "{tie_break:?}",
+        );
+    }
+    assert_eq!(TieBreak::default(), TieBreak::LastWins);
+}
+
+#[test]
+fn test_tokenize_user_priority() {
+    let lexicon_csv = "自然,0,0,1,自然\n言語,0,0,1,言語\n自然言語,0,0,10,自然言語";
+    let user_csv = "自然言語処理,0,0,100,user\n言語処理,0,0,100,user";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1,unknown";
+
+    let dict_inner = SystemDictionaryBuilder::from_readers(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(user_csv.as_bytes()))
+    .unwrap();
+    let tokenizer = Tokenizer::from_inner(dict_inner);
+    let surfaces = |tokenizer: &Tokenizer| {
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語処理");
+        worker.tokenize();
+        worker.token_iter().map(|t| t.surface().to_string()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(surfaces(&tokenizer), ["自然", "言語", "処理"]);
+
+    let tokenizer = tokenizer.user_cost_bonus(100);
+    assert_eq!(surfaces(&tokenizer), ["自然言語処理"]);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("自然言語処理");
+    worker.tokenize();
+    assert_eq!(worker.token(0).word_cost(), 100);
+    assert_eq!(worker.token(0).total_cost(), 0);
+    worker.tokenize_nbest(1);
+    assert_eq!(worker.path_cost(0), Some(0));
+
+    let tokenizer = tokenizer.user_cost_bonus(0).strict_user_lexicon(true);
+    assert_eq!(surfaces(&tokenizer), ["自然言語処理"]);
+}
+
+#[test]
+fn test_tokenize_unk_provider() {
+    use crate::tokenizer::{UnkEntry, UnkInput, UnkMatch, UnkProvider};
+
+    struct EmailProvider;
+
+    impl UnkProvider for EmailProvider {
+        fn entries(&self) -> Vec<UnkEntry> {
+            vec![UnkEntry::new(0, 0, 10, "メール")]
+        }
+
+        fn provide(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(UnkMatch)) {
+            let start = input.byte_position(start_char);
+            let text = &input.text()[start..];
+            let local = text.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(text.len());
+            if local == 0 || !text[local..].starts_with('@') {
+                return;
+            }
+            let domain = &text[local + 1..];
+            let len = domain
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .unwrap_or(domain.len());
+            if len == 0 {
+                return;
+            }
+            let end_char = input.char_position(start + local + 1 + len).unwrap();
+            emit(UnkMatch { end_char, entry: 0 });
+        }
+    }
+
+    let lexicon_csv = "連絡,0,0,1,連絡";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 0 1";
+    let unk_def = "DEFAULT,0,0,100,unknown";
+
+    let dict = build_test_dictionary(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict).unk_provider(EmailProvider).unwrap();
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("連絡a@b.jp");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 2);
+
+    let t = worker.token(1);
+    assert_eq!(t.surface(), "a@b.jp");
+    assert_eq!(t.lex_type(), LexType::Unknown);
+    assert_eq!(t.feature(), "メール");
+    assert_eq!(t.word_cost(), 10);
+
+    worker.tokenize_nbest(1);
+    let path = worker.nbest_token_iter(0).unwrap().collect::<Vec<_>>();
+    assert_eq!(path.len(), 2);
+    assert_eq!(path[1].feature(), "メール");
+
+    struct InvalidProvider;
+
+    impl UnkProvider for InvalidProvider {
+        fn entries(&self) -> Vec<UnkEntry> {
+            vec![UnkEntry::new(1, 0, 0, "invalid")]
+        }
+
+        fn provide(&self, _: &UnkInput, _: usize, _: &mut dyn FnMut(UnkMatch)) {}
+    }
+
+    assert!(tokenizer.unk_provider(InvalidProvider).is_err());
+}
+
+#[test]
+fn test_tokenize_approximate_user_lexicon() {
+    let lexicon_csv = "自然,0,0,1,system";
+    let user_csv = "形態素解析,0,0,-100,user";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1,unknown";
+
+    let dict_inner = SystemDictionaryBuilder::from_readers(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(user_csv.as_bytes()))
+    .unwrap();
+    let tokenizer = Tokenizer::from_inner(dict_inner);
+
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("形熊素解析");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).feature(), "unknown");
+
+    let fuzzy = tokenizer
+        .clone()
+        .approximate_user_lexicon(user_csv.as_bytes(), 3, 50)
+        .unwrap();
+    let mut worker = fuzzy.new_worker();
+    worker.reset_sentence("形熊素解析");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).surface(), "形熊素解析");
+    assert_eq!(worker.token(0).feature(), "user");
+    assert_eq!(worker.token(0).total_cost(), -50);
+    assert_eq!(worker.token(0).word_cost(), -50);
+
+    worker.tokenize_nbest(2);
+    assert_eq!(worker.path_cost(0), Some(-50));
+    let t = worker.nbest_token_iter(0).unwrap().next().unwrap();
+    assert_eq!(t.feature(), "user");
+    assert_eq!(t.word_cost(), -50);
+    assert_eq!(t.total_cost(), -50);
+
+    // The penalty makes the approximate match lose against the unknown word.
+    let fuzzy = tokenizer
+        .clone()
+        .approximate_user_lexicon(user_csv.as_bytes(), 3, 200)
+        .unwrap();
+    let mut worker = fuzzy.new_worker();
+    worker.reset_sentence("形熊素解析");
+    worker.tokenize();
+    assert_eq!(worker.token(0).feature(), "unknown");
+
+    assert!(tokenizer
+        .clone()
+        .approximate_user_lexicon("形態素解析,0,0,100,user".as_bytes(), 3, 50)
+        .is_err());
+    assert!(tokenizer
+        .approximate_user_lexicon("形態素分析,0,0,-100,user".as_bytes(), 3, 50)
+        .is_err());
+}
+
+#[test]
+fn test_tokenize_fold_latin() {
+    let lexicon_csv = "ABC,0,0,-100,system\nです,0,0,-100,desu";
+    let user_csv = "vibrato,0,0,-100,user";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1,unknown";
+
+    let dict_inner = SystemDictionaryBuilder::from_readers(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    )
+    .unwrap()
+    .reset_user_lexicon_from_reader(Some(user_csv.as_bytes()))
+    .unwrap();
+    let tokenizer = Tokenizer::from_inner(dict_inner);
+
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("ＡＢＣです");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+    assert_eq!(worker.token(0).feature(), "unknown");
+
+    let folding = tokenizer.clone().fold_latin(true).unwrap();
+    let mut worker = folding.new_worker();
+    for (input, surface, feature) in [
+        ("ＡＢＣです", "ＡＢＣ", "system"),
+        ("abcです", "abc", "system"),
+        ("ABCです", "ABC", "system"),
+        ("ＶｉＢＲＡＴＯです", "ＶｉＢＲＡＴＯ", "user"),
+    ] {
+        worker.reset_sentence(input);
+        worker.tokenize();
+        assert_eq!(worker.num_tokens(), 2);
+        assert_eq!(worker.token(0).surface(), surface);
+        assert_eq!(worker.token(0).feature(), feature);
+        assert_eq!(worker.token(1).feature(), "desu");
+    }
+
+    let mut ranges = vec![];
+    folding.segment("ａｂｃです", &mut ranges);
+    assert_eq!(ranges, [0..9, 9..15]);
+
+    let options = TokenizerOptions::new().fold_latin(true);
+    let folding = tokenizer.clone().apply_options(&options).unwrap();
+    let mut worker = folding.new_worker();
+    worker.reset_sentence("ａｂｃです");
+    worker.tokenize();
+    assert_eq!(worker.token(0).feature(), "system");
+
+    let unfolded = folding.fold_latin(false).unwrap();
+    let mut worker = unfolded.new_worker();
+    worker.reset_sentence("ａｂｃです");
+    worker.tokenize();
+    assert_eq!(worker.token(0).feature(), "unknown");
+}
+
+#[test]
+fn test_unknown_fallback_script_boundary() {
+    let lexicon_csv = "自然,0,0,1,system";
+    let matrix_def = "1 1\n0 0 0";
+    let char_def = "DEFAULT 0 1 0";
+    let unk_def = "DEFAULT,0,0,1,unknown";
+
+    let dict = build_test_dictionary(
+        lexicon_csv.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京タワーはabc１２３です");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+
+    let tokenizer = tokenizer.unknown_fallback(UnknownFallback::ScriptBoundary);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京タワーはabc１２３です");
+    worker.tokenize();
+    let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, ["東京", "タワー", "は", "abc", "１２３", "です"]);
+
+    let mut ranges = vec![];
+    tokenizer.segment("ＡＢＣで", &mut ranges);
+    assert_eq!(ranges, [0..9, 9..12]);
+
+    let options = TokenizerOptions::new().unknown_fallback(UnknownFallback::Grouped);
+    let tokenizer = tokenizer.apply_options(&options).unwrap();
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京タワーはabc１２３です");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 1);
+}
+
+#[test]
+fn test_tokenize_stepwise() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict).ignore_space(true).unwrap();
+
+    let mut expected = tokenizer.new_worker();
+    expected.reset_sentence("京都 東京都  京都 ");
+    expected.tokenize();
+
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("京都 東京都  京都 ");
+    let mut stepwise = worker.tokenize_stepwise();
+    let mut num_steps = 0;
+    while !stepwise.step(1) {
+        num_steps += 1;
+        assert!(!stepwise.is_finished());
+        assert!(stepwise.progress().0 < stepwise.progress().1);
+    }
+    assert!(num_steps > 1);
+    assert_eq!(stepwise.progress(), (11, 11));
+    assert!(stepwise.step(1));
+    drop(stepwise);
+
+    assert_eq!(worker.num_tokens(), expected.num_tokens());
+    for (t, e) in worker.token_iter().zip(expected.token_iter()) {
+        assert_eq!(t.range_char(), e.range_char());
+        assert_eq!(t.feature(), e.feature());
+        assert_eq!(t.total_cost(), e.total_cost());
+    }
+
+    worker.reset_sentence("");
+    assert!(worker.tokenize_stepwise().is_finished());
+    assert_eq!(worker.num_tokens(), 0);
+}
+
+#[test]
+fn test_reset_sentence_lossy() {
+    use crate::errors::VibratoError;
+    use crate::tokenizer::worker::InvalidUtf8;
+
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    let input = [&b"\xFF"[..], "京都".as_bytes(), b"\xE4"].concat();
+    worker.reset_sentence_lossy(&input);
+    worker.tokenize();
+
+    let replacements = worker.utf8_replacements();
+    assert_eq!(replacements.len(), 2);
+    assert_eq!((replacements[0].input.clone(), replacements[0].output.clone()), (0..1, 0..3));
+    assert_eq!((replacements[1].input.clone(), replacements[1].output.clone()), (7..8, 9..12));
+
+    let kyoto = worker.token_iter().find(|t| t.surface() == "京都").unwrap();
+    let range = kyoto.range_byte();
+    assert_eq!(worker.input_offset(range.start)..worker.input_offset(range.end), 1..7);
+    assert_eq!(worker.input_offset(1), 0);
+    assert_eq!(worker.input_offset(12), 8);
+
+    let err = worker.reset_sentence_bytes(&input, InvalidUtf8::Reject).unwrap_err();
+    assert!(matches!(err, VibratoError::Utf8(e) if e.valid_up_to() == 0));
+    assert_eq!(worker.num_tokens(), 0);
+
+    worker.reset_sentence_bytes("京都".as_bytes(), InvalidUtf8::Reject).unwrap();
+    assert!(worker.utf8_replacements().is_empty());
+}
+
+#[test]
+fn test_replacement_char_category() {
+    let char_def = format!("{CHAR_DEF}\nREPLACEMENT 1 1 0\n");
+    let unk_def = format!("{UNK_DEF}REPLACEMENT,7,7,5000,補助記号,置換文字,*,*,*,*\n");
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        char_def.as_bytes(),
+        unk_def.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict)
+        .replacement_char_category(Some("REPLACEMENT"))
+        .unwrap();
+    let mut worker = tokenizer.new_worker();
+
+    let input = ["京都".as_bytes(), b"\xFF\xFE", "京都".as_bytes()].concat();
+    worker.reset_sentence_lossy(&input);
+    worker.tokenize();
+
+    let t = worker.token_iter().find(|t| t.surface().starts_with('\u{FFFD}')).unwrap();
+    assert_eq!(t.surface(), "\u{FFFD}\u{FFFD}");
+    assert_eq!(t.feature(), "補助記号,置換文字,*,*,*,*");
+    let range = t.range_byte();
+    assert_eq!(worker.input_offset(range.start)..worker.input_offset(range.end), 6..8);
+
+    let tokenizer = tokenizer.replacement_char_category(None).unwrap();
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence_lossy(&input);
+    worker.tokenize();
+    assert!(worker.token_iter().all(|t| t.feature() != "補助記号,置換文字,*,*,*,*"));
+
+    assert!(tokenizer.clone().replacement_char_category(Some("UNDEFINED")).is_err());
+    // SYMBOL is defined in char.def but has no entries in unk.def.
+    assert!(tokenizer.replacement_char_category(Some("SYMBOL")).is_err());
+}
+
+#[test]
+fn test_group_graphemes() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    // A kanji with an ideographic variation selector, followed by "a" with a combining accent.
+    let input = "葛\u{E0100}葛\u{E0100}a\u{301}";
+
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence(input);
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 6);
+
+    let tokenizer = tokenizer.group_graphemes(true);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence(input);
+    worker.tokenize();
+    let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, ["葛\u{E0100}", "葛\u{E0100}", "a\u{301}"]);
+    assert_eq!(worker.token(1).range_char(), 2..4);
+}
+
+#[test]
+fn test_tokenize_document() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    let text = "京都に行った。\n\n東京都に行く";
+    let doc = worker.tokenize_document(text);
+    assert_eq!(doc.sentences.len(), 2);
+    assert_eq!(doc.sentence_text(0), "京都に行った。");
+    assert_eq!(doc.sentence_text(1), "東京都に行く");
+    assert_eq!(doc.sentences[1].range_char, 9..15);
+
+    let chars: Vec<_> = text.chars().collect();
+    for token in doc.tokens() {
+        assert_eq!(&text[token.range_byte.clone()], token.surface);
+        assert_eq!(chars[token.range_char.clone()].iter().collect::<String>(), token.surface);
+    }
+    assert_eq!(doc.tokens().last().unwrap().surface, "行く");
+    assert_eq!(doc.num_tokens(), doc.tokens().count());
+
+    let annotations = worker.annotate_document(text);
+    assert_eq!(annotations.len(), doc.num_tokens());
+    for (annotation, token) in annotations.iter().zip(doc.tokens()) {
+        assert_eq!(annotation.range_byte, token.range_byte);
+        assert_eq!(annotation.range_char, token.range_char);
+        assert_eq!(annotation.word_idx, token.word_id);
+        assert_eq!(annotation.feature, token.feature);
+    }
+    assert_eq!(annotations[0].sentence, 0);
+    assert_eq!(annotations.last().unwrap().sentence, 1);
+}
+
+/// ラティスのDOT形式での書き出しのテスト
+#[test]
+fn test_dump_lattice_dot() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    worker.reset_sentence("東京都");
+    assert!(worker.dump_lattice_dot(vec![]).is_err());
+
+    for nbest in [false, true] {
+        if nbest {
+            worker.tokenize_nbest(3);
+        } else {
+            worker.tokenize();
+        }
+        let mut dot = vec![];
+        worker.dump_lattice_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+
+        assert!(dot.starts_with("digraph lattice {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("  n0_0 [label=\"BOS\""));
+        assert!(dot.contains("[label=\"東京都\\n東京都\\n5320 ("));
+        // The best path is 東京都 alone.
+        let best_edges = dot.lines().filter(|l| l.contains("color=red")).count();
+        assert_eq!(best_edges, 2);
+    }
+
+    worker.reset_sentence("");
+    let mut dot = vec![];
+    worker.dump_lattice_dot(&mut dot).unwrap();
+    assert_eq!(dot, b"digraph lattice {\n  graph [rankdir=LR];\n  node [shape=box];\n}\n");
+}
+
+/// 単語境界の確率のテスト
+#[test]
+fn test_boundary_confidences() {
+    let dict = build_test_dictionary(
+        "a,0,0,10,x\nb,0,0,10,x\nab,0,0,20,x\nc,0,0,10,x".as_bytes(),
+        "1 1\n0 0 0".as_bytes(),
+        "DEFAULT 0 1 0".as_bytes(),
+        "DEFAULT,0,0,100,x".as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    worker.reset_sentence("abc");
+    assert!(worker.boundary_confidences(1.0).is_err());
+    worker.tokenize();
+    assert!(worker.boundary_confidences(0.0).is_err());
+    assert!(worker.boundary_confidences(f64::NAN).is_err());
+
+    // "a b" and "ab" have the same cost.
+    let confidences = worker.boundary_confidences(1.0).unwrap();
+    assert_eq!(confidences.len(), 2);
+    assert!((confidences[0] - 0.5).abs() < 1e-9);
+    assert!((confidences[1] - 1.0).abs() < 1e-9);
+
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    worker.reset_sentence("東京都");
+    worker.tokenize_nbest(3);
+    // The best path is 東京都 alone, so the inner boundaries are unlikely.
+    let sharp = worker.boundary_confidences(1.0).unwrap();
+    let flat = worker.boundary_confidences(1e6).unwrap();
+    assert_eq!(sharp.len(), 2);
+    for (&s, &f) in sharp.iter().zip(&flat) {
+        assert!(s < 0.5);
+        assert!((0.0..=1.0).contains(&f));
+    }
+
+    worker.reset_sentence("");
+    worker.tokenize();
+    assert!(worker.boundary_confidences(1.0).unwrap().is_empty());
+}
+
+/// トークン化の統計情報のテスト
+#[cfg(feature = "stats")]
+#[test]
+fn test_worker_stats() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    worker.reset_sentence("東京都ab");
+    worker.tokenize();
+    let stats = *worker.stats();
+    // 東, 東京, 東京都, 京都, 都 and the unknown words for "ab".
+    assert!(stats.num_nodes > 5);
+    assert!(stats.num_unk_words > 0);
+    assert!(stats.num_cost_evaluations >= stats.num_nodes);
+
+    worker.tokenize_nbest(2);
+    assert_eq!(worker.stats().num_nodes, stats.num_nodes);
+    assert_eq!(worker.stats().num_unk_words, stats.num_unk_words);
+
+    let mut stepwise = worker.tokenize_stepwise();
+    while !stepwise.step(1) {}
+    drop(stepwise);
+    assert_eq!(worker.stats().num_nodes, stats.num_nodes);
+    assert_eq!(worker.stats().num_cost_evaluations, stats.num_cost_evaluations);
+
+    worker.reset_sentence("");
+    worker.tokenize();
+    assert_eq!(*worker.stats(), Default::default());
+}
+
+/// 素性を切り離した辞書の読み書きテスト
+#[test]
+fn test_tokenize_sharded_features() {
+    let build = || {
+        SystemDictionaryBuilder::from_readers(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+        .unwrap()
+        .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+        .unwrap()
+    };
+    let features = |dict: Dictionary| {
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("京都東京都に行くkampersanda");
+        worker.tokenize();
+        worker
+            .token_iter()
+            .map(|t| (t.lex_type(), t.feature().to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut plain = vec![];
+    build().write(&mut plain).unwrap();
+    let mut sharded = vec![];
+    build().shard_features().write(&mut sharded).unwrap();
+
+    let expected = features(Dictionary::read(plain.as_slice()).unwrap());
+    assert!(expected.iter().any(|(lex_type, _)| *lex_type == LexType::User));
+    assert_eq!(features(Dictionary::read(sharded.as_slice()).unwrap()), expected);
+    assert_eq!(features(Dictionary::from_inner(build().shard_features())), expected);
+
+    // Reading into an owned dictionary restores the features, and writing it again keeps them
+    // detached.
+    let owned = crate::dictionary::DictionaryInner::read(sharded.as_slice()).unwrap();
+    let mut rewritten = vec![];
+    owned.write(&mut rewritten).unwrap();
+    assert!(rewritten.windows(16).any(|w| w == b"VibratoFeatures\n"));
+    assert_eq!(features(Dictionary::read(rewritten.as_slice()).unwrap()), expected);
+}
+
+/// 重複を除いて圧縮した素性を持つ辞書の読み書きテスト
+#[test]
+fn test_tokenize_compressed_features() {
+    let build = || {
+        SystemDictionaryBuilder::from_readers(
+            LEX_CSV.as_bytes(),
+            MATRIX_DEF.as_bytes(),
+            CHAR_DEF.as_bytes(),
+            UNK_DEF.as_bytes(),
+        )
+        .unwrap()
+        .reset_user_lexicon_from_reader(Some(USER_CSV.as_bytes()))
+        .unwrap()
+    };
+    let features = |dict: Dictionary| {
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("京都東京都に行くkampersanda");
+        worker.tokenize();
+        worker
+            .token_iter()
+            .map(|t| (t.lex_type(), t.feature().to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut plain = vec![];
+    build().write(&mut plain).unwrap();
+    let expected = features(Dictionary::read(plain.as_slice()).unwrap());
+
+    let mut dedup = vec![];
+    build().dedup_features().write(&mut dedup).unwrap();
+    assert_eq!(features(Dictionary::read(dedup.as_slice()).unwrap()), expected);
+
+    #[cfg(feature = "loaders")]
+    {
+        let mut compressed = vec![];
+        build().compress_features(3).write(&mut compressed).unwrap();
+        assert_eq!(features(Dictionary::read(compressed.as_slice()).unwrap()), expected);
+
+        // Writing an owned dictionary again keeps the encoding.
+        let owned = crate::dictionary::DictionaryInner::read(compressed.as_slice()).unwrap();
+        let mut rewritten = vec![];
+        owned.write(&mut rewritten).unwrap();
+        assert!(rewritten.windows(16).any(|w| w == b"VibratoFeatDedup"));
+        assert_eq!(features(Dictionary::read(rewritten.as_slice()).unwrap()), expected);
+    }
+}
+
+/// 容量のヒントとメモリの解放のテスト
+#[test]
+fn test_worker_capacity_hints_and_shrink() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+
+    let surfaces = |worker: &crate::tokenizer::worker::Worker| {
+        worker.token_iter().map(|t| t.surface().to_string()).collect::<Vec<_>>()
+    };
+
+    let mut expected = tokenizer.new_worker();
+    expected.reset_sentence("京都東京都京都");
+    expected.tokenize();
+
+    let long = "京都東京都".repeat(200);
+    let mut worker = tokenizer.new_worker().with_capacity_hints(8, 4);
+    worker.reset_sentence(&long);
+    worker.tokenize();
+    let before = surfaces(&worker);
+
+    // The current result survives shrinking.
+    worker.shrink_to_fit();
+    assert_eq!(surfaces(&worker), before);
+    assert_eq!(before.concat(), long);
+
+    worker.reset_sentence("京都東京都京都");
+    worker.tokenize();
+    worker.shrink_to_fit();
+    assert_eq!(surfaces(&worker), surfaces(&expected));
+
+    worker.tokenize_nbest(2);
+    worker.shrink_to_fit();
+    let best: Vec<_> = worker.nbest_token_iter(0).unwrap().map(|t| t.surface().to_string()).collect();
+    assert_eq!(best, surfaces(&expected));
+
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京都");
+    worker.tokenize();
+    worker.shrink_to_fit();
+    assert_eq!(worker.token(0).surface(), "東京都");
+}
+
+/// N-best解析用ラティスの大きさの上限のテスト
+#[test]
+fn test_tokenize_nbest_budget() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+
+    let mut expected = tokenizer.new_worker();
+    expected.reset_sentence("京都東京都京都");
+    expected.tokenize();
+    let best: Vec<_> = expected.token_iter().map(|t| t.surface().to_string()).collect();
+
+    let mut worker = tokenizer.new_worker().with_nbest_budget(1 << 20);
+    worker.reset_sentence("京都東京都京都");
+    worker.tokenize_nbest(3);
+    assert!(!worker.nbest_budget_exceeded());
+    assert!(worker.num_nbest_paths() > 1);
+
+    let mut worker = tokenizer.new_worker().with_nbest_budget(64);
+    worker.reset_sentence("京都東京都京都");
+    worker.tokenize_nbest(3);
+    assert!(worker.nbest_budget_exceeded());
+    assert_eq!(worker.num_nbest_paths(), 1);
+    let surfaces: Vec<_> = worker.nbest_token_iter(0).unwrap().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, best);
+
+    let err = worker.try_tokenize_nbest(3).unwrap_err();
+    assert!(matches!(err, crate::errors::VibratoError::ResourceLimit(ref e) if e.limit() == 64));
+    assert_eq!(worker.num_nbest_paths(), 0);
+
+    // The lattice fits in the budget, but the A* search exceeds what is left of it.
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("京都東京都京都");
+    worker.tokenize_nbest(3);
+    let crate::tokenizer::lattice::LatticeKind::ForNBest(lattice) = &worker.lattice else {
+        panic!("the worker must hold an N-best lattice");
+    };
+    let budget = lattice.used_bytes() + 1;
+    let mut worker = tokenizer.new_worker().with_nbest_budget(budget);
+    worker.reset_sentence("京都東京都京都");
+    let err = worker.try_tokenize_nbest(3).unwrap_err();
+    assert!(matches!(err, crate::errors::VibratoError::ResourceLimit(ref e) if e.limit() == budget));
+    assert!(worker.nbest_budget_exceeded());
+    worker.tokenize_nbest(3);
+    assert_eq!(worker.num_nbest_paths(), 1);
+    let surfaces: Vec<_> = worker.nbest_token_iter(0).unwrap().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, best);
+}
+
+#[test]
+fn test_tokenize_tsv_line() {
+    let dict = build_test_dictionary(
+        LEX_CSV.as_bytes(),
+        MATRIX_DEF.as_bytes(),
+        CHAR_DEF.as_bytes(),
+        UNK_DEF.as_bytes(),
+    );
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+
+    let tsv = worker.tokenize_tsv_line("doc1\t京都東京都\tnote", 1).unwrap();
+    assert_eq!((tsv.prefix, tsv.text, tsv.suffix), ("doc1\t", "京都東京都", "\tnote"));
+    let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface().to_string()).collect();
+    assert_eq!(surfaces, ["京都", "東京都"]);
+
+    let tsv = worker.tokenize_tsv_line("東京都\tdoc2", 0).unwrap();
+    assert_eq!((tsv.prefix, tsv.text, tsv.suffix), ("", "東京都", "\tdoc2"));
+    assert_eq!(worker.num_tokens(), 1);
+
+    // An empty column is tokenized as an empty sentence.
+    let tsv = worker.tokenize_tsv_line("doc3\t", 1).unwrap();
+    assert_eq!((tsv.prefix, tsv.text, tsv.suffix), ("doc3\t", "", ""));
+    assert_eq!(worker.num_tokens(), 0);
+
+    assert!(worker.tokenize_tsv_line("doc4", 1).is_err());
+    assert_eq!(worker.num_tokens(), 0);
+}
@@ -5,7 +5,9 @@
 
 use std::io::BufRead;
 
-use crate::trainer::{Corpus, Trainer, TrainerConfig};
+use crate::trainer::{
+    evaluate, Corpus, CorpusStats, FeatureKind, Regularization, Trainer, TrainerConfig,
+};
 use crate::utils;
 
 const TRAIN_LEX_CSV: &[u8] = include_bytes!("./resources/train_lex.csv");
@@ -249,3 +251,354 @@ fn test_user_lex_format() {
         assert_eq!(result_row[4..], ["名詞", "固有名詞", "地名", "一般"]);
     }
 }
+
+/// 枝刈りと量子化によって接続行列が小さくなることを確認
+#[test]
+fn test_prune_and_quantize() {
+    let config = TrainerConfig::from_readers(
+        TRAIN_LEX_CSV,
+        CHAR_DEF,
+        TRAIN_UNK_DEF,
+        FEATURE_DEF,
+        REWRITE_DEF,
+    )
+    .unwrap();
+    let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    let trainer = Trainer::new(config).unwrap().max_iter(5);
+    let mut model = trainer.train(corpus).unwrap();
+
+    assert!(model.prune(-1.0).is_err());
+    assert!(model.quantize(0).is_err());
+
+    let report = model.prune(f64::MAX).unwrap();
+    assert_eq!(report.num_matrix_entries_after, 0);
+    assert!(report.num_matrix_entries_before > 0);
+    assert_eq!(report.num_distinct_weights, 1);
+
+    let report = model.quantize(4).unwrap();
+    assert_eq!(report.num_changed, 0);
+
+    let mut lex = vec![];
+    let mut matrix = vec![];
+    let mut unk = vec![];
+    let mut user_lex = vec![];
+    model.read_user_lexicon(USER_CSV).unwrap();
+    model
+        .write_dictionary(&mut lex, &mut matrix, &mut unk, &mut user_lex)
+        .unwrap();
+
+    // Only the header remains because pruning is replayed after re-merging.
+    assert_eq!(matrix.lines().count(), 1);
+}
+
+/// 量子化後の重みの異なり数が段階数で抑えられることを確認
+#[test]
+fn test_quantize() {
+    let config = TrainerConfig::from_readers(
+        TRAIN_LEX_CSV,
+        CHAR_DEF,
+        TRAIN_UNK_DEF,
+        FEATURE_DEF,
+        REWRITE_DEF,
+    )
+    .unwrap();
+    let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    let trainer = Trainer::new(config).unwrap().max_iter(5);
+    let mut model = trainer.train(corpus).unwrap();
+
+    let report = model.quantize(2).unwrap();
+    assert!(report.num_distinct_weights <= 5);
+    assert!(report.num_matrix_entries_after <= report.num_matrix_entries_before);
+    assert!(report.max_abs_error >= report.mean_abs_error);
+}
+
+/// 学習済みの素性と重みを参照できることを確認
+#[test]
+fn test_top_features() {
+    let config = TrainerConfig::from_readers(
+        TRAIN_LEX_CSV,
+        CHAR_DEF,
+        TRAIN_UNK_DEF,
+        FEATURE_DEF,
+        REWRITE_DEF,
+    )
+    .unwrap();
+    let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    let trainer = Trainer::new(config).unwrap().max_iter(5);
+    let model = trainer.train(corpus).unwrap();
+
+    let top = model.top_features(10);
+    assert!(!top.is_empty() && top.len() <= 10);
+    for pair in top.windows(2) {
+        assert!(pair[0].weight.abs() >= pair[1].weight.abs());
+    }
+    assert!(top.iter().all(|f| f.weight != 0.0));
+    assert!(model.top_features(usize::MAX).iter().any(|f| f.kind == FeatureKind::Bigram));
+
+    for f in &top {
+        let found = model.feature_weight(&f.name).unwrap();
+        assert_eq!(found.name, f.name);
+        if found.kind == f.kind {
+            assert_eq!(found.weight, f.weight);
+        }
+    }
+    assert!(model.feature_weight("no such feature").is_none());
+    assert!(model.top_features(0).is_empty());
+    assert_eq!(model.num_features(), model.top_features(usize::MAX).len());
+}
+
+/// 部分的に注釈されたコーパスで、未注釈の区間が制約から外されることを確認
+#[test]
+fn test_partially_annotated_corpus() {
+    let config = TrainerConfig::from_readers(
+        TRAIN_LEX_CSV,
+        CHAR_DEF,
+        TRAIN_UNK_DEF,
+        FEATURE_DEF,
+        REWRITE_DEF,
+    )
+    .unwrap();
+    let corpus_txt = "\
+東京都\t*
+選挙\t名詞,普通名詞,サ変可能,*
+管理委員会\t*
+EOS
+";
+    let trainer = Trainer::new(config).unwrap();
+    let edges = |corpus: Corpus| {
+        let mut example = corpus[0].clone();
+        example.sentence.compile(trainer.config.dict.char_prop());
+        trainer.collect_edges(&example)
+    };
+
+    // Each unannotated span is covered by a single featureless edge, and no negative edge
+    // starts or ends inside it.
+    let partial = edges(Corpus::from_reader_partial(corpus_txt.as_bytes()).unwrap());
+    assert_eq!(partial.positive.len(), 3);
+    assert_eq!(partial.positive[0], (0, 3, None));
+    assert!(matches!(partial.positive[1], (3, 5, Some(_))));
+    assert_eq!(partial.positive[2], (5, 10, None));
+    assert!(!partial.negative.is_empty());
+    for &(pos, target, _) in &partial.negative {
+        assert!(3 <= pos && target <= 5, "{pos}..{target}");
+    }
+
+    // Without opting in, `*` is an ordinary feature and the whole sentence is constrained.
+    let full = edges(Corpus::from_reader(corpus_txt.as_bytes()).unwrap());
+    assert!(full.negative.iter().any(|&(pos, _, _)| pos == 0));
+    assert!(full.negative.iter().any(|&(pos, _, _)| pos >= 5));
+}
+
+/// 交差検証の各分割でモデルを評価できることを確認
+#[test]
+fn test_cross_validation() {
+    let mut corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    corpus.shuffle(0);
+    let folds = corpus.split(2).unwrap();
+
+    for i in 0..folds.len() {
+        let config = TrainerConfig::from_readers(
+            TRAIN_LEX_CSV,
+            CHAR_DEF,
+            TRAIN_UNK_DEF,
+            FEATURE_DEF,
+            REWRITE_DEF,
+        )
+        .unwrap();
+        let trainer = Trainer::new(config).unwrap().max_iter(5);
+        let train = Corpus::concat(
+            folds
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, c)| c.clone()),
+        );
+        let mut model = trainer.train(train).unwrap();
+
+        let score = evaluate(&mut model, &folds[i], &[0]).unwrap();
+        assert!(score.num_ref > 0);
+        assert!(score.num_cor <= score.num_ref.min(score.num_sys));
+    }
+}
+
+/// ラティスの構築を分割しても学習結果が変わらないことを確認
+#[test]
+fn test_preprocess_batch_size() {
+    let train = |batch_size| {
+        let config = TrainerConfig::from_readers(
+            TRAIN_LEX_CSV,
+            CHAR_DEF,
+            TRAIN_UNK_DEF,
+            FEATURE_DEF,
+            REWRITE_DEF,
+        )
+        .unwrap();
+        let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+        let trainer = Trainer::new(config)
+            .unwrap()
+            .max_iter(5)
+            .preprocess_batch_size(batch_size);
+        let mut model = trainer.train(corpus).unwrap();
+        let (mut lex, mut matrix, mut unk, mut user_lex) = (vec![], vec![], vec![], vec![]);
+        model
+            .write_dictionary(&mut lex, &mut matrix, &mut unk, &mut user_lex)
+            .unwrap();
+        (lex, matrix, unk)
+    };
+    assert_eq!(train(1), train(10_000));
+}
+
+/// 逐次的に読み込むコーパスでの学習結果が、メモリ上のコーパスでの結果と一致することを確認
+#[test]
+fn test_train_streaming() {
+    let train = |streaming: bool| {
+        let config = TrainerConfig::from_readers(
+            TRAIN_LEX_CSV,
+            CHAR_DEF,
+            TRAIN_UNK_DEF,
+            FEATURE_DEF,
+            REWRITE_DEF,
+        )
+        .unwrap();
+        let trainer = Trainer::new(config)
+            .unwrap()
+            .max_iter(5)
+            .preprocess_batch_size(3);
+        let mut model = if streaming {
+            let path = std::env::temp_dir().join(format!(
+                "vibrato-train-streaming-{}.txt",
+                std::process::id()
+            ));
+            std::fs::write(&path, CORPUS_TXT).unwrap();
+            let corpus = Corpus::open_streaming(&path).unwrap();
+            let model = trainer.train_streaming(&corpus).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            model
+        } else {
+            trainer.train(Corpus::from_reader(CORPUS_TXT).unwrap()).unwrap()
+        };
+        let (mut lex, mut matrix, mut unk, mut user_lex) = (vec![], vec![], vec![], vec![]);
+        model
+            .write_dictionary(&mut lex, &mut matrix, &mut unk, &mut user_lex)
+            .unwrap();
+        (lex, matrix, unk)
+    };
+    assert_eq!(train(false), train(true));
+}
+
+/// 正則化の種類を変更して学習できることを確認
+#[test]
+fn test_regularization() {
+    let train = |regularization| {
+        let config = TrainerConfig::from_readers(
+            TRAIN_LEX_CSV,
+            CHAR_DEF,
+            TRAIN_UNK_DEF,
+            FEATURE_DEF,
+            REWRITE_DEF,
+        )
+        .unwrap();
+        let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+        let trainer = Trainer::new(config)
+            .unwrap()
+            .max_iter(5)
+            .regularization(regularization);
+        let mut model = trainer.train(corpus).unwrap();
+        let (mut lex, mut matrix, mut unk, mut user_lex) = (vec![], vec![], vec![], vec![]);
+        model
+            .write_dictionary(&mut lex, &mut matrix, &mut unk, &mut user_lex)
+            .unwrap();
+        lex
+    };
+
+    let l1 = train(Regularization::L1);
+    assert_eq!(l1, train(Regularization::default()));
+    assert_ne!(l1, train(Regularization::L2));
+}
+
+/// コーパスの統計情報と、素性の項目数の確認
+#[test]
+fn test_corpus_stats() {
+    let config = || {
+        TrainerConfig::from_readers(
+            TRAIN_LEX_CSV,
+            CHAR_DEF,
+            TRAIN_UNK_DEF,
+            FEATURE_DEF,
+            REWRITE_DEF,
+        )
+        .unwrap()
+    };
+
+    let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    let stats = corpus.stats(&config());
+    assert_eq!(
+        stats,
+        CorpusStats {
+            num_sentences: 4,
+            num_tokens: 26,
+            num_unannotated: 0,
+            num_oov: 0,
+            feature_columns: vec![(4, 26)],
+            lexicon_feature_columns: Some(4),
+        }
+    );
+    assert_eq!(stats.oov_rate(), 0.0);
+    assert_eq!(stats.num_inconsistent_columns(), 0);
+
+    let corpus_txt = "\
+東京\t名詞,固有名詞,地名,一般
+都\t*
+選挙\t名詞,普通名詞
+EOS
+";
+    let corpus = Corpus::from_reader(corpus_txt.as_bytes()).unwrap();
+    let stats = corpus.stats(&config());
+    assert_eq!(stats.num_tokens, 3);
+    assert_eq!(stats.num_unannotated, 1);
+    assert_eq!(stats.num_oov, 1);
+    assert_eq!(stats.feature_columns, [(2, 1), (4, 1)]);
+    assert_eq!(stats.oov_rate(), 0.5);
+    assert_eq!(stats.num_inconsistent_columns(), 1);
+
+    // Most tokens have a different number of columns from the seed lexicon.
+    let corpus_txt = "\
+東京\t名詞,固有名詞
+都\t名詞,普通名詞
+EOS
+";
+    let corpus = Corpus::from_reader(corpus_txt.as_bytes()).unwrap();
+    let trainer = Trainer::new(config()).unwrap().max_iter(5);
+    assert!(trainer.train(corpus).is_err());
+}
+
+/// メモリ上で直接構築した辞書が、CSVを経由して構築した辞書と一致することを確認
+#[test]
+fn test_build_system_dictionary() {
+    let config = TrainerConfig::from_readers(
+        TRAIN_LEX_CSV,
+        CHAR_DEF,
+        TRAIN_UNK_DEF,
+        FEATURE_DEF,
+        REWRITE_DEF,
+    )
+    .unwrap();
+    let corpus = Corpus::from_reader(CORPUS_TXT).unwrap();
+    let trainer = Trainer::new(config).unwrap().max_iter(5);
+    let mut model = trainer.train(corpus).unwrap();
+
+    let (mut lex, mut matrix, mut unk, mut user_lex) = (vec![], vec![], vec![], vec![]);
+    model
+        .write_dictionary(&mut lex, &mut matrix, &mut unk, &mut user_lex)
+        .unwrap();
+    let expected = crate::SystemDictionaryBuilder::from_readers(&*lex, &*matrix, CHAR_DEF, &*unk)
+        .unwrap();
+
+    let dict = model.build_system_dictionary().unwrap();
+
+    let mut expected_bytes = vec![];
+    expected.write(&mut expected_bytes).unwrap();
+    let mut bytes = vec![];
+    dict.write(&mut bytes).unwrap();
+    assert!(bytes == expected_bytes);
+}
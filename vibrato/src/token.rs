@@ -3,11 +3,18 @@
 //! このモジュールは、形態素解析の結果として得られるトークンを表現する型を提供します。
 //! トークンは辞書内の単語への参照を保持し、表層形、品詞情報、位置情報などへの
 //! アクセスを提供します。
+//! トークン列からテキストを再構成する処理は[`detokenize`](mod@detokenize)モジュールが提供します。
 
+pub mod detokenize;
+
+pub use detokenize::{detokenize, Detokenizer, LatinSpacing, SpacingRule};
+
+use std::borrow::Cow;
+use std::iter::FusedIterator;
 use std::ops::Range;
 
-use crate::dictionary::DictionaryInnerRef;
 use crate::dictionary::{word_idx::WordIdx, LexType};
+use crate::posmap::{PosTagset, Upos};
 use crate::tokenizer::lattice::Node;
 use crate::tokenizer::worker::Worker;
 
@@ -94,13 +101,23 @@ impl<'w> Token<'w> {
     ///
     /// Gets the feature string of the token.
     #[inline(always)]
-    pub fn feature(&self) -> &str {
-        match self.worker.tokenizer.dictionary() {
-            DictionaryInnerRef::Archived(dict) => dict
-                .word_feature(self.word_idx()),
-            DictionaryInnerRef::Owned(dict) => dict
-                .word_feature(self.word_idx()),
-        }
+    pub fn feature(&self) -> &'w str {
+        self.worker.tokenizer.word_feature(self.word_idx())
+    }
+
+    /// トークンの品詞をUniversal Dependenciesの品詞タグ（UPOS）に変換します。
+    ///
+    /// 辞書の品詞体系は[`PosTagset::from_metadata()`]で辞書のメタデータから判定できます。
+    ///
+    /// # 引数
+    ///
+    /// * `tagset` - 辞書の品詞体系
+    ///
+    /// # 戻り値
+    ///
+    /// 対応表に一致する規則がない場合は[`Upos::X`]を返します。
+    pub fn upos(&self, tagset: PosTagset) -> Upos {
+        tagset.upos_map().upos(self.feature()).unwrap_or(Upos::X)
     }
 
     /// トークンが由来する辞書のタイプを取得します。
@@ -146,17 +163,14 @@ impl<'w> Token<'w> {
     /// # 戻り値
     ///
     /// 単語の生起コストを返します。値が低いほど出現しやすい単語です。
+    /// 近似照合や候補の追加で加えられた罰則を含み、
+    /// [`Tokenizer::user_cost_bonus()`](crate::Tokenizer::user_cost_bonus)による補正は含みません。
     ///
     /// Gets the word cost of the token's node.
     #[inline(always)]
     pub fn word_cost(&self) -> i16 {
         let (_, node) = &self.worker.top_nodes[self.index];
-        match self.worker.tokenizer.dictionary() {
-            DictionaryInnerRef::Archived(dict) => dict
-                .word_param(node.word_idx()).word_cost,
-            DictionaryInnerRef::Owned(dict) => dict
-                .word_param(node.word_idx()).word_cost,
-        }
+        self.worker.tokenizer.word_cost(node)
     }
 
     /// 文頭からこのトークンノードまでの累積コストを取得します。
@@ -179,13 +193,25 @@ impl<'w> Token<'w> {
     /// このトークンのすべての情報を含む所有型の[`TokenBuf`]を返します。
     /// スレッド間でトークン情報を送信したり、長期保存する際に有用です。
     pub fn to_buf(&self) -> TokenBuf {
-        TokenBuf {
-            surface: self.surface().to_string(),
-            feature: self.feature().to_string(),
+        self.to_ref().into_owned()
+    }
+
+    /// このトークンビューを、表層形と素性を借用する[`TokenRef`]に変換します。
+    ///
+    /// 文字列を複製しないため、[`to_buf()`](Self::to_buf)より低コストです。
+    ///
+    /// # 戻り値
+    ///
+    /// [`Worker`]の入力文と辞書から表層形と素性を借用する[`TokenRef`]を返します。
+    pub fn to_ref(&self) -> TokenRef<'w> {
+        let word_idx = self.word_idx();
+        TokenRef {
+            surface: Cow::Borrowed(self.surface()),
+            feature: Cow::Borrowed(self.feature()),
             range_char: self.range_char(),
             range_byte: self.range_byte(),
-            word_id: self.word_idx(),
-            lex_type: self.lex_type(),
+            word_id: word_idx,
+            lex_type: word_idx.lex_type,
             left_id: self.left_id(),
             right_id: self.right_id(),
             word_cost: self.word_cost(),
@@ -228,30 +254,28 @@ pub struct NbestToken<'w> {
 }
 
 impl<'w> NbestToken<'w> {
-    /// Gets a raw pointer to the underlying `Node` for this token.
+    /// Gets the lattice indices of the nodes in the path containing this token.
     #[inline(always)]
-    fn node_ptr(&self) -> *const Node {
-        // This relies on bounds checks performed in NbestTokenIter::new
-        // and NbestTokenIter::next, so it should be safe within the iterator context.
-        self.worker.nbest_paths[self.path_idx].0[self.token_idx]
+    fn path(&self) -> &'w [u32] {
+        &self.worker.nbest_paths[self.path_idx].0
     }
 
-    /// Gets a safe reference to the underlying `Node`.
+    /// Gets the underlying `Node` for this token.
+    ///
+    /// Both the path and the node are looked up with bounds checks.
     #[inline(always)]
     fn node(&self) -> &'w Node {
-        unsafe { &*self.node_ptr() }
+        self.worker.nbest_node(self.path()[self.token_idx])
     }
 
     /// Gets the end position (in characters) of this token.
     #[inline(always)]
     fn end_word(&self) -> usize {
-        let path = &self.worker.nbest_paths[self.path_idx].0;
-        if self.token_idx + 1 < path.len() {
+        match self.path().get(self.token_idx + 1) {
             // If there is a next token, its start position is our end position.
-            unsafe { (*path[self.token_idx + 1]).start_word }
-        } else {
+            Some(&next) => self.worker.nbest_node(next).start_word,
             // If this is the last token in the path, the sentence end is our end.
-            self.worker.sent.len_char()
+            None => self.worker.sent.len_char(),
         }
     }
 
@@ -277,12 +301,22 @@ impl<'w> NbestToken<'w> {
     /// Gets the feature string of the token.
     #[inline(always)]
     pub fn feature(&self) -> &'w str {
-        match self.worker.tokenizer.dictionary() {
-            DictionaryInnerRef::Archived(dict) => dict
-                .word_feature(self.word_idx()),
-            DictionaryInnerRef::Owned(dict) => dict
-                .word_feature(self.word_idx()),
-        }
+        self.worker.tokenizer.word_feature(self.word_idx())
+    }
+
+    /// トークンの品詞をUniversal Dependenciesの品詞タグ（UPOS）に変換します。
+    ///
+    /// 辞書の品詞体系は[`PosTagset::from_metadata()`]で辞書のメタデータから判定できます。
+    ///
+    /// # 引数
+    ///
+    /// * `tagset` - 辞書の品詞体系
+    ///
+    /// # 戻り値
+    ///
+    /// 対応表に一致する規則がない場合は[`Upos::X`]を返します。
+    pub fn upos(&self, tagset: PosTagset) -> Upos {
+        tagset.upos_map().upos(self.feature()).unwrap_or(Upos::X)
     }
 
     /// トークンの文字単位の位置範囲を取得します。
@@ -363,12 +397,13 @@ impl<'w> NbestToken<'w> {
     /// # 戻り値
     ///
     /// 単語の生起コストを返します。値が低いほど出現しやすい単語です。
+    /// 近似照合や候補の追加で加えられた罰則を含み、
+    /// [`Tokenizer::user_cost_bonus()`](crate::Tokenizer::user_cost_bonus)による補正は含みません。
     ///
     /// Gets the word cost of the token's node.
     #[inline(always)]
     pub fn word_cost(&self) -> i16 {
-        let dict = self.worker.tokenizer.dictionary();
-        dict.word_param(self.word_idx()).word_cost
+        self.worker.tokenizer.word_cost(self.node())
     }
 
     /// 文頭からこのトークンノードまでの累積コストを取得します。
@@ -463,6 +498,12 @@ impl<'w> Iterator for TokenIter<'w> {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
 }
 
 impl<'w> DoubleEndedIterator for TokenIter<'w> {
@@ -478,6 +519,143 @@ impl<'w> DoubleEndedIterator for TokenIter<'w> {
     }
 }
 
+impl ExactSizeIterator for TokenIter<'_> {}
+
+impl FusedIterator for TokenIter<'_> {}
+
+impl<'w> TokenIter<'w> {
+    /// 各トークンの表層形を返すイテレータに変換します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    /// # let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("自然言語処理");
+    /// worker.tokenize();
+    /// let surfaces: Vec<&str> = worker.token_iter().surfaces().collect();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn surfaces(self) -> impl DoubleEndedIterator<Item = &'w str> {
+        self.map(|t| t.surface())
+    }
+
+    /// 各トークンの品詞を、`schema`に従って素性から取り出すイテレータに変換します。
+    ///
+    /// 品詞は素性文字列の一部を借用して返されるため、文字列の割り当ては行われません。
+    /// 素性はカンマで区切られているものとして扱い、引用符は解釈しません。
+    ///
+    /// # 引数
+    ///
+    /// * `schema` - 素性から品詞を取り出す方法
+    pub fn pos_tags(self, schema: PosSchema) -> impl DoubleEndedIterator<Item = &'w str> {
+        self.map(move |t| schema.extract(t.feature()))
+    }
+
+    /// 連続する`n`個のトークンの表層形を空白で連結した文字列を返すイテレータに変換します。
+    ///
+    /// トークン数が`n`未満の場合は何も返しません。
+    ///
+    /// # 引数
+    ///
+    /// * `n` - n-gramの長さ
+    ///
+    /// # パニック
+    ///
+    /// `n`が0の場合にパニックします。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// # use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    /// # let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    /// worker.reset_sentence("自然言語処理");
+    /// worker.tokenize();
+    /// // e.g., ["自然 言語", "言語 処理"]
+    /// let bigrams: Vec<String> = worker.token_iter().ngrams(2).collect();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ngrams(self, n: usize) -> impl Iterator<Item = String> + 'w {
+        assert_ne!(n, 0, "n must be positive");
+        let worker = self.worker;
+        let (front, back) = (self.front, self.back);
+        (front..back.saturating_sub(n - 1).max(front)).map(move |start| {
+            let mut ngram = String::new();
+            for i in start..start + n {
+                if i != start {
+                    ngram.push(' ');
+                }
+                ngram.push_str(worker.token(i).surface());
+            }
+            ngram
+        })
+    }
+
+    /// 各トークンに、文書中のバイト単位の位置範囲を添えて返すイテレータに変換します。
+    ///
+    /// 文書を文ごとにトークン化する場合に、[`Token::range_byte()`]の文中の位置を
+    /// 文書中の位置に変換するために使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `sentence_offset` - 文書中での入力文の開始バイト位置
+    pub fn with_sentence_offset(
+        self,
+        sentence_offset: usize,
+    ) -> impl DoubleEndedIterator<Item = (Range<usize>, Token<'w>)> {
+        self.map(move |t| {
+            let range = t.range_byte();
+            (range.start + sentence_offset..range.end + sentence_offset, t)
+        })
+    }
+}
+
+/// 素性から品詞を取り出す方法
+///
+/// [`TokenIter::pos_tags()`]で使用します。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PosSchema {
+    /// 素性の先頭の項目（品詞の大分類）のみを取り出します。
+    Major,
+
+    /// IPADICやUniDicの品詞階層（素性の先頭4項目）を取り出します。
+    ///
+    /// 末尾の`*`の項目は省略されます。例えばIPADICの`名詞,一般,*,*,*,*,...`は
+    /// `名詞,一般`となります。
+    Hierarchy,
+
+    /// 素性の先頭から指定された数の項目を取り出します。
+    Fields(usize),
+}
+
+impl PosSchema {
+    /// 素性から品詞を取り出します。
+    pub(crate) fn extract(self, feature: &str) -> &str {
+        let num_fields = match self {
+            Self::Major => 1,
+            Self::Hierarchy => 4,
+            Self::Fields(n) => n,
+        };
+        let Some(last) = num_fields.checked_sub(1) else {
+            return "";
+        };
+        let mut pos = match feature.match_indices(',').nth(last) {
+            Some((i, _)) => &feature[..i],
+            None => feature,
+        };
+        if self == Self::Hierarchy {
+            while let Some(rest) = pos.strip_suffix(",*") {
+                pos = rest;
+            }
+        }
+        pos
+    }
+}
+
 /// 特定のN-best解析パス内のトークンをイテレートするイテレータ
 ///
 /// N-best解析で得られた複数の候補パスのうち、特定のパス（`path_idx`で指定）に
@@ -512,6 +690,88 @@ impl<'w> Iterator for NbestTokenIter<'w> {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.worker.nbest_paths[self.path_idx].0.len() - self.current_token_idx;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for NbestTokenIter<'_> {}
+
+impl FusedIterator for NbestTokenIter<'_> {}
+
+/// トークン化結果のトークン列へのビュー
+///
+/// [`Worker::tokens()`]で取得します。スライスと同様に長さを取得したり、
+/// 位置を指定してトークンを取得したりできます。
+#[derive(Clone, Copy)]
+pub struct Tokens<'w> {
+    worker: &'w Worker,
+}
+
+impl<'w> Tokens<'w> {
+    #[inline(always)]
+    pub(crate) const fn new(worker: &'w Worker) -> Self {
+        Self { worker }
+    }
+
+    /// トークン数を取得します。
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.worker.num_tokens()
+    }
+
+    /// トークンが存在しない場合に`true`を返します。
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `i`番目のトークンを取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// `i`が範囲外の場合は`None`
+    #[inline(always)]
+    pub fn get(&self, i: usize) -> Option<Token<'w>> {
+        (i < self.len()).then(|| self.worker.token(i))
+    }
+
+    /// 先頭のトークンを取得します。
+    #[inline(always)]
+    pub fn first(&self) -> Option<Token<'w>> {
+        self.get(0)
+    }
+
+    /// 末尾のトークンを取得します。
+    #[inline(always)]
+    pub fn last(&self) -> Option<Token<'w>> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// トークンのイテレータを作成します。
+    #[inline(always)]
+    pub fn iter(&self) -> TokenIter<'w> {
+        TokenIter::new(self.worker)
+    }
+}
+
+impl<'w> IntoIterator for Tokens<'w> {
+    type Item = Token<'w>;
+    type IntoIter = TokenIter<'w>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl std::fmt::Debug for Tokens<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
 }
 
 /// 所有型の自己完結したトークン
@@ -526,6 +786,7 @@ impl<'w> Iterator for NbestTokenIter<'w> {
 /// It is useful for storing tokenization results or
 /// sending them across threads.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TokenBuf {
     /// トークンの表層形（元のテキスト中の文字列）
     ///
@@ -584,6 +845,94 @@ impl<'w> From<Token<'w>> for TokenBuf {
     }
 }
 
+/// 表層形と素性を借用するトークン
+///
+/// [`TokenBuf`]と同じ情報を持ちますが、表層形と素性は[`Cow`]として保持され、
+/// [`Token::to_ref()`]で作成した場合は[`Worker`]の入力文と辞書から借用されます。
+/// 位置やコストなどの情報は所有するため、トークン化の結果を一時的に保持したり、
+/// スコープ付きスレッドに渡したりする際に、文字列の割り当てを避けられます。
+/// 借用元より長く保持する場合は[`into_owned()`](Self::into_owned)で[`TokenBuf`]に
+/// 変換してください。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenRef<'a> {
+    /// トークンの表層形（元のテキスト中の文字列）
+    pub surface: Cow<'a, str>,
+
+    /// トークンの素性情報（品詞など）
+    pub feature: Cow<'a, str>,
+
+    /// トークンの文字単位の位置範囲
+    pub range_char: Range<usize>,
+
+    /// トークンのバイト単位の位置範囲
+    pub range_byte: Range<usize>,
+
+    /// トークンが由来する辞書のタイプ
+    pub lex_type: LexType,
+
+    /// トークンの単語インデックス
+    pub word_id: WordIdx,
+
+    /// トークンノードの左文脈ID
+    pub left_id: u16,
+
+    /// トークンノードの右文脈ID
+    pub right_id: u16,
+
+    /// トークンノードの単語コスト
+    pub word_cost: i16,
+
+    /// 文頭からこのトークンノードまでの累積コスト
+    pub total_cost: i32,
+}
+
+impl TokenRef<'_> {
+    /// 借用している文字列を複製し、所有型の[`TokenBuf`]に変換します。
+    pub fn into_owned(self) -> TokenBuf {
+        TokenBuf {
+            surface: self.surface.into_owned(),
+            feature: self.feature.into_owned(),
+            range_char: self.range_char,
+            range_byte: self.range_byte,
+            lex_type: self.lex_type,
+            word_id: self.word_id,
+            left_id: self.left_id,
+            right_id: self.right_id,
+            word_cost: self.word_cost,
+            total_cost: self.total_cost,
+        }
+    }
+}
+
+impl<'a> From<Token<'a>> for TokenRef<'a> {
+    fn from(token: Token<'a>) -> Self {
+        token.to_ref()
+    }
+}
+
+impl From<TokenRef<'_>> for TokenBuf {
+    fn from(token: TokenRef<'_>) -> Self {
+        token.into_owned()
+    }
+}
+
+impl<'a> From<&'a TokenBuf> for TokenRef<'a> {
+    fn from(token: &'a TokenBuf) -> Self {
+        Self {
+            surface: Cow::Borrowed(&token.surface),
+            feature: Cow::Borrowed(&token.feature),
+            range_char: token.range_char.clone(),
+            range_byte: token.range_byte.clone(),
+            lex_type: token.lex_type,
+            word_id: token.word_id,
+            left_id: token.left_id,
+            right_id: token.right_id,
+            word_cost: token.word_cost,
+            total_cost: token.total_cost,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dictionary::*;
@@ -627,4 +976,109 @@ mod tests {
         }
         assert!(it.next().is_none());
     }
+
+    #[test]
+    fn test_token_refs() {
+        let dict_inner = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict_inner);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語");
+        worker.tokenize();
+
+        let refs = worker.token_refs();
+        let bufs = worker.to_token_bufs();
+        assert_eq!(refs.len(), 2);
+        assert_eq!(bufs.len(), 2);
+        assert!(matches!(refs[0].surface, std::borrow::Cow::Borrowed("自然")));
+        assert!(matches!(refs[1].feature, std::borrow::Cow::Borrowed("gengo")));
+        for (r, b) in refs.iter().zip(&bufs) {
+            assert_eq!(*r, crate::token::TokenRef::from(b));
+            assert_eq!(r.clone().into_owned().surface, b.surface);
+        }
+    }
+
+    #[test]
+    fn test_iter_adapters() {
+        let dict_inner = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,名詞,一般,*,*\n言語,0,0,4,名詞,*,*,*\n処理,0,0,3,名詞,サ変接続,*,*"
+                .as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict_inner);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語処理");
+        worker.tokenize();
+
+        assert_eq!(
+            worker.token_iter().surfaces().collect::<Vec<_>>(),
+            ["自然", "言語", "処理"],
+        );
+        assert_eq!(
+            worker.token_iter().pos_tags(crate::token::PosSchema::Hierarchy).collect::<Vec<_>>(),
+            ["名詞,一般", "名詞", "名詞,サ変接続"],
+        );
+        assert_eq!(
+            worker.token_iter().pos_tags(crate::token::PosSchema::Fields(2)).collect::<Vec<_>>(),
+            ["名詞,一般", "名詞,*", "名詞,サ変接続"],
+        );
+        assert_eq!(
+            worker.token_iter().ngrams(2).collect::<Vec<_>>(),
+            ["自然 言語", "言語 処理"],
+        );
+        assert_eq!(worker.token_iter().ngrams(4).count(), 0);
+        assert_eq!(
+            worker
+                .token_iter()
+                .with_sentence_offset(10)
+                .map(|(range, _)| range)
+                .collect::<Vec<_>>(),
+            [10..16, 16..22, 22..28],
+        );
+        assert_eq!(
+            worker
+                .token_iter()
+                .map(|t| t.upos(crate::posmap::PosTagset::Ipadic))
+                .collect::<Vec<_>>(),
+            [crate::posmap::Upos::Noun; 3],
+        );
+    }
+
+    #[test]
+    fn test_tokens_view() {
+        let dict_inner = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict_inner);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語");
+        worker.tokenize();
+
+        let mut it = worker.token_iter();
+        assert_eq!(it.len(), 2);
+        it.next_back();
+        assert_eq!(it.size_hint(), (1, Some(1)));
+        it.next();
+        assert_eq!(it.len(), 0);
+        assert!(it.next().is_none());
+
+        let tokens = worker.tokens();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens.first().unwrap().surface(), "自然");
+        assert_eq!(tokens.last().unwrap().surface(), "言語");
+        assert!(tokens.get(2).is_none());
+        assert_eq!(tokens.into_iter().count(), 2);
+    }
 }
@@ -0,0 +1,271 @@
+//! トークン列からのテキストの再構成
+//!
+//! このモジュールは、トークン列の表層形を連結してテキストを再構成する[`Detokenizer`]を
+//! 提供します。トークン化した結果を編集してから文に戻す機械翻訳の後処理などでの
+//! 利用を想定しています。
+//!
+//! 元のテキストでの位置が分かるトークンの間では、元のテキストに空白があった場合にのみ
+//! 空白を挿入します。編集で追加や書き換えがされたトークンの前後では、[`SpacingRule`]で
+//! 空白を挿入するかを決めます。既定の規則[`LatinSpacing`]は、英単語の間には空白を挿入し、
+//! 日本語の語の間には挿入しません。
+
+use crate::sentence::Script;
+use crate::token::TokenBuf;
+
+/// 隣接する2つのトークンの間に空白を挿入するかを決める規則
+///
+/// `Fn(&TokenBuf, &TokenBuf) -> bool`を満たすクロージャも規則として使えます。
+pub trait SpacingRule: Send + Sync {
+    /// `left`と`right`の間に空白を挿入する場合は`true`を返します。
+    fn needs_space(&self, left: &TokenBuf, right: &TokenBuf) -> bool;
+}
+
+impl<F> SpacingRule for F
+where
+    F: Fn(&TokenBuf, &TokenBuf) -> bool + Send + Sync,
+{
+    fn needs_space(&self, left: &TokenBuf, right: &TokenBuf) -> bool {
+        self(left, right)
+    }
+}
+
+/// ラテン文字や数字で終わる語と、ラテン文字や数字で始まる語の間に空白を挿入する規則
+///
+/// 英文の句読点（`,`、`.`、`;`、`:`、`!`、`?`）とそれに続くラテン文字や数字の間にも
+/// 空白を挿入します。仮名や漢字を含む語の間には空白を挿入しません。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatinSpacing;
+
+impl SpacingRule for LatinSpacing {
+    fn needs_space(&self, left: &TokenBuf, right: &TokenBuf) -> bool {
+        let Some(first) = right.surface.chars().find_map(Script::of) else {
+            return false;
+        };
+        if !is_word_script(first) {
+            return false;
+        }
+        match left.surface.chars().next_back() {
+            Some(',' | '.' | ';' | ':' | '!' | '?') => true,
+            _ => left
+                .surface
+                .chars()
+                .rev()
+                .find_map(Script::of)
+                .is_some_and(is_word_script),
+        }
+    }
+}
+
+/// 空白で区切って書く語の文字体系かを返します。
+const fn is_word_script(script: Script) -> bool {
+    matches!(script, Script::Latin | Script::Digit)
+}
+
+/// トークン列からテキストを再構成する処理
+///
+/// # 例
+///
+/// ```
+/// use vibrato_rkyv::token::Detokenizer;
+/// use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+///
+/// let dict = SystemDictionaryBuilder::from_readers(
+///     "京都,0,0,5,名詞".as_bytes(),
+///     "1 1\n0 0 0".as_bytes(),
+///     "DEFAULT 0 1 0\nSPACE 0 1 0\nALPHA 1 1 0\n0x0020 SPACE\n0x0041..0x007A ALPHA".as_bytes(),
+///     "DEFAULT,0,0,100,*\nSPACE,0,0,100,*\nALPHA,0,0,100,*".as_bytes(),
+/// )?;
+/// let tokenizer = Tokenizer::from_inner(dict).ignore_space(true)?;
+/// let mut worker = tokenizer.new_worker();
+/// worker.reset_sentence("Kyoto in 京都");
+/// worker.tokenize();
+///
+/// let mut tokens = worker.to_token_bufs();
+/// assert_eq!(Detokenizer::new().detokenize(&tokens), "Kyoto in 京都");
+///
+/// tokens[0].surface = "Nara".to_string();
+/// assert_eq!(Detokenizer::new().detokenize(&tokens), "Nara in 京都");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Detokenizer {
+    rule: Box<dyn SpacingRule>,
+    use_ranges: bool,
+}
+
+impl Default for Detokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Detokenizer {
+    /// [`LatinSpacing`]を規則とする新しいインスタンスを作成します。
+    pub fn new() -> Self {
+        Self {
+            rule: Box::new(LatinSpacing),
+            use_ranges: true,
+        }
+    }
+
+    /// 空白を挿入するかを決める規則を設定します。
+    ///
+    /// # 引数
+    ///
+    /// * `rule` - 空白を挿入するかを決める規則
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新された`Detokenizer`インスタンス。
+    pub fn spacing_rule<R>(mut self, rule: R) -> Self
+    where
+        R: SpacingRule + 'static,
+    {
+        self.rule = Box::new(rule);
+        self
+    }
+
+    /// トークンの位置範囲から元のテキストの空白を復元するかを設定します。
+    ///
+    /// 有効な場合（既定）、元のテキストでの位置が分かる2つのトークンの間では、
+    /// 元のテキストで隣接していれば空白を挿入せず、間に文字（[`Tokenizer::ignore_space()`]
+    /// で読み飛ばされた空白など）があれば空白を1つ挿入します。バイト単位の位置範囲の
+    /// 長さが表層形の長さと異なるトークンは、編集されたものとみなして規則を適用します。
+    /// 無効な場合は、すべてのトークンの間に規則を適用します。
+    ///
+    /// [`Tokenizer::ignore_space()`]: crate::Tokenizer::ignore_space
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - 位置範囲を使用するかどうか
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新された`Detokenizer`インスタンス。
+    pub const fn use_ranges(mut self, yes: bool) -> Self {
+        self.use_ranges = yes;
+        self
+    }
+
+    /// トークンの表層形を連結し、必要な箇所に空白を挿入したテキストを返します。
+    ///
+    /// # 引数
+    ///
+    /// * `tokens` - 出現順のトークン列
+    ///
+    /// # 戻り値
+    ///
+    /// 再構成したテキスト
+    pub fn detokenize(&self, tokens: &[TokenBuf]) -> String {
+        let mut text = String::with_capacity(tokens.iter().map(|t| t.surface.len() + 1).sum());
+        let Some((first, rest)) = tokens.split_first() else {
+            return text;
+        };
+        text.push_str(&first.surface);
+        let mut left = first;
+        for right in rest {
+            if self.needs_space(left, right) {
+                text.push(' ');
+            }
+            text.push_str(&right.surface);
+            left = right;
+        }
+        text
+    }
+
+    /// `left`と`right`の間に空白を挿入するかを返します。
+    fn needs_space(&self, left: &TokenBuf, right: &TokenBuf) -> bool {
+        if self.use_ranges && is_unedited(left) && is_unedited(right) {
+            let (end, start) = (left.range_byte.end, right.range_byte.start);
+            if end == start {
+                return false;
+            }
+            if end < start {
+                return true;
+            }
+        }
+        self.rule.needs_space(left, right)
+    }
+}
+
+/// トークンの位置範囲が表層形と対応しているかを返します。
+fn is_unedited(token: &TokenBuf) -> bool {
+    token.range_byte.len() == token.surface.len()
+}
+
+/// [`LatinSpacing`]を規則として、トークン列からテキストを再構成します。
+///
+/// [`Detokenizer::new()`]で作成したインスタンスの[`Detokenizer::detokenize()`]と同じです。
+///
+/// # 引数
+///
+/// * `tokens` - 出現順のトークン列
+///
+/// # 戻り値
+///
+/// 再構成したテキスト
+pub fn detokenize(tokens: &[TokenBuf]) -> String {
+    Detokenizer::new().detokenize(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::{LexType, WordIdx};
+
+    fn token(surface: &str, range_byte: std::ops::Range<usize>) -> TokenBuf {
+        TokenBuf {
+            surface: surface.to_string(),
+            feature: String::new(),
+            range_char: range_byte.clone(),
+            range_byte,
+            lex_type: LexType::System,
+            word_id: WordIdx::new(LexType::System, 0),
+            left_id: 0,
+            right_id: 0,
+            word_cost: 0,
+            total_cost: 0,
+        }
+    }
+
+    #[test]
+    fn test_detokenize_ranges() {
+        // "I have a pen" tokenized with ignore_space(true)
+        let tokens = [token("I", 0..1), token("have", 2..6), token("a", 7..8), token("pen", 9..12)];
+        assert_eq!(detokenize(&tokens), "I have a pen");
+
+        // Adjacent tokens in the original text are not separated.
+        let tokens = [token("3", 0..1), token(".", 1..2), token("14", 2..4)];
+        assert_eq!(detokenize(&tokens), "3.14");
+    }
+
+    #[test]
+    fn test_detokenize_edited() {
+        let tokens = [
+            token("Hello", 0..0),
+            token(",", 0..0),
+            token("world", 0..0),
+            token("!", 0..0),
+            token("東京", 0..0),
+            token("へ", 0..0),
+            token("GO", 0..0),
+            token("2", 0..0),
+        ];
+        assert_eq!(detokenize(&tokens), "Hello, world!東京へGO 2");
+
+        let tokens = [token("New", 0..3), token("York", 0..0)];
+        assert_eq!(detokenize(&tokens), "New York");
+        let no_ranges = Detokenizer::new().use_ranges(false);
+        assert_eq!(no_ranges.detokenize(&[token("a", 0..1), token("b", 1..2)]), "a b");
+    }
+
+    #[test]
+    fn test_detokenize_custom_rule() {
+        let detokenizer = Detokenizer::new().use_ranges(false).spacing_rule(
+            |_: &TokenBuf, right: &TokenBuf| right.surface != "。",
+        );
+        let tokens = [token("今日", 0..6), token("は", 6..9), token("。", 9..12)];
+        assert_eq!(detokenizer.detokenize(&tokens), "今日 は。");
+        assert_eq!(detokenize(&[]), "");
+    }
+}
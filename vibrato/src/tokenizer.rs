@@ -26,20 +26,43 @@
 //! }
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
+mod augmenter;
+mod edge_scorer;
+mod fold;
+mod fuzzy;
+mod incremental;
 pub(crate) mod lattice;
 mod nbest_generator;
+mod options;
+mod pool;
+mod segment;
+mod unk_provider;
 pub mod worker;
 
+use std::io::Read;
 use std::sync::Arc;
 
 use crate::Dictionary;
+use crate::dictionary::character::CharInfo;
 use crate::dictionary::connector::{ArchivedConnectorWrapper, ConnectorCost, ConnectorWrapper};
-use crate::dictionary::{ArchivedDictionaryInner, DictionaryInner, DictionaryInnerRef};
+use crate::dictionary::{
+    ArchivedDictionary, DictionaryInner, DictionaryInnerRef, LexType, Lexicon, WordIdx,
+};
 use crate::errors::{Result, VibratoError};
 use crate::sentence::Sentence;
-use crate::tokenizer::lattice::{Lattice, LatticeNBest};
+use crate::tokenizer::edge_scorer::{EdgeCost, ScoredConnector};
+use crate::tokenizer::fold::FoldedIndex;
+use crate::tokenizer::fuzzy::FuzzyMatcher;
+use crate::tokenizer::lattice::{Lattice, LatticeNBest, Node};
+use crate::tokenizer::unk_provider::UnkProviders;
 use crate::tokenizer::worker::Worker;
 
+pub use crate::tokenizer::augmenter::{Augmentation, LatticeAugmenter};
+pub use crate::tokenizer::edge_scorer::{EdgeScorer, EdgeWord};
+pub use crate::tokenizer::options::TokenizerOptions;
+pub use crate::tokenizer::pool::{PooledWorker, WorkerPool};
+pub use crate::tokenizer::unk_provider::{UnkEntry, UnkInput, UnkMatch, UnkProvider};
+
 /// 形態素解析を行うトークナイザー。
 ///
 /// `Tokenizer`は、Viterbiアルゴリズムを使用して日本語テキストを形態素に分割します。
@@ -50,6 +73,22 @@ use crate::tokenizer::worker::Worker;
 /// - `dict`: 形態素解析に使用する辞書データへの参照
 /// - `space_cateset`: MeCab互換モードでのスペース文字のカテゴリセット
 /// - `max_grouping_len`: 未知語の最大グルーピング長
+/// - `tie_break`: 同コストの候補の選び方
+/// - `user_cost_bonus`: ユーザー辞書の語の単語コストから引く値
+/// - `strict_user_lexicon`: ユーザー辞書の語が一致した位置で他の候補を抑制するか
+/// - `keep_space_tokens`: スペースを空白トークンとして出力するか
+/// - `fuzzy_user`: ユーザー辞書の近似照合器
+/// - `folded`: 幅と大文字・小文字を区別しない照合のための索引
+/// - `unk_providers`: アプリケーション定義の未知語のプロバイダ
+/// - `edge_scorer`: エッジのコストを補正するスコアラー
+/// - `augmenters`: 解析時にラティスへ候補を追加する拡張
+/// - `replacement_cinfo`: U+FFFD（REPLACEMENT CHARACTER）に割り当てる文字情報
+/// - `group_graphemes`: 拡張書記素クラスタを1文字として扱うか
+/// - `category_grouping_lens`: カテゴリごとの未知語の最大グルーピング長
+/// - `unknown_fallback`: 未知語を生成する際のグループ化の方法
+///
+/// 解析の設定は、各メソッドを連鎖して指定するほか、[`TokenizerOptions`]にまとめて
+/// [`Tokenizer::with_options()`]で一度に適用できます。
 ///
 /// # 例
 ///
@@ -70,8 +109,94 @@ pub struct Tokenizer {
     // For the MeCab compatibility
     space_cateset: Option<u32>,
     max_grouping_len: Option<usize>,
+    // Overridden per worker via `Worker::set_unk_grouping()`
+    pub(crate) unk_grouping: bool,
+    tie_break: TieBreak,
+    user_cost_bonus: i16,
+    strict_user_lexicon: bool,
+    fuzzy_user: Option<Arc<FuzzyMatcher>>,
+    folded: Option<Arc<FoldedIndex>>,
+    unk_providers: Option<Arc<UnkProviders>>,
+    edge_scorer: Option<Arc<dyn EdgeScorer>>,
+    augmenters: Vec<Arc<dyn LatticeAugmenter>>,
+    keep_space_tokens: bool,
+    space_feature: Arc<str>,
+    replacement_cinfo: Option<CharInfo>,
+    group_graphemes: bool,
+    // Pairs of a category id and its maximum grouping length, overriding `max_grouping_len`
+    category_grouping_lens: Vec<(u32, Option<usize>)>,
+    unknown_fallback: UnknownFallback,
+}
+
+/// 同コストの候補が複数ある場合の選び方。
+///
+/// ラティス上の各ノードについて、累積コストが最小となる左側のノードが複数ある場合に
+/// どれを選ぶかを表します。ラティスへのノードの挿入順は、開始位置の昇順で、
+/// 同じ開始位置ではユーザー辞書、システム辞書、未知語の順です。同じ辞書内では
+/// 単語IDの昇順に挿入されます。
+///
+/// [`Tokenizer::tie_break()`]で指定します。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TieBreak {
+    /// 後から挿入された候補を優先します。
+    ///
+    /// デフォルトの動作です。開始位置が後の候補が優先される点はMeCabと一致しますが、
+    /// 同じ開始位置ではMeCabと異なり、未知語や単語IDの大きい候補が選ばれます。
+    #[default]
+    LastWins,
+
+    /// 先に挿入された候補を優先します。
+    FirstWins,
+
+    /// ユーザー辞書 > システム辞書 > 未知語 の順に優先し、同じ種別の候補同士では
+    /// 後から挿入された候補を優先します。
+    ///
+    /// [`Tokenizer::lex_type_priority()`]で有効にした場合と同じです。
+    LexTypePriority,
+
+    /// MeCabと同じ規則で候補を選びます。
+    ///
+    /// MeCabのViterbi探索は、後に接続されたノードから順に左側のノードを調べ、
+    /// コストが真に小さい場合のみ最良ノードを更新します。その結果、次の順に優先されます。
+    ///
+    /// 1. 開始位置が後の候補
+    /// 2. システム辞書 > ユーザー辞書 > 未知語
+    /// 3. 単語IDの小さい候補
+    ///
+    /// MeCabとビット単位で同じ結果を得るには、同じソースから構築した辞書を使用し、
+    /// [`Tokenizer::ignore_space()`]と[`Tokenizer::max_grouping_len()`]をMeCabの
+    /// 動作に合わせてください。単語IDはMeCabと同様に語彙CSVの行の順序で割り当てられます。
+    MecabCompatible,
+}
+
+/// 辞書に一致しない文字列から未知語を生成する際のグループ化の方法。
+///
+/// [`Tokenizer::unknown_fallback()`]で指定します。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnknownFallback {
+    /// `char.def`の定義に従い、同じカテゴリに属する連続した文字をまとめます。
+    ///
+    /// デフォルトの動作です。
+    #[default]
+    Grouped,
+
+    /// `char.def`の定義に加えて、漢字、ひらがな、カタカナ、ラテン文字、数字の
+    /// 文字体系の境界で未知語を区切ります。
+    ///
+    /// 多くの文字を同じカテゴリに割り当てた辞書で、ノイズの多いテキストが
+    /// 1つの長い未知語になるのを防ぎます。長音符（`ー`）や結合文字は直前の文字と
+    /// 同じ文字体系として扱われます。
+    ScriptBoundary,
 }
 
+/// [`Tokenizer::keep_space_tokens()`]で出力される空白トークンのデフォルトの素性
+pub const DEFAULT_SPACE_FEATURE: &str = "空白";
+
+/// [`Tokenizer::unk_provider()`]で登録できる語の定義の最大数
+const MAX_PROVIDED_ENTRIES: usize = 1 << 16;
+
 impl Tokenizer {
     /// 新しいトークナイザーを作成します。
     ///
@@ -105,6 +230,21 @@ impl Tokenizer {
             dict: Arc::new(dict),
             space_cateset: None,
             max_grouping_len: None,
+            unk_grouping: true,
+            tie_break: TieBreak::LastWins,
+            user_cost_bonus: 0,
+            strict_user_lexicon: false,
+            fuzzy_user: None,
+            folded: None,
+            unk_providers: None,
+            edge_scorer: None,
+            augmenters: vec![],
+            keep_space_tokens: false,
+            space_feature: Arc::from(DEFAULT_SPACE_FEATURE),
+            replacement_cinfo: None,
+            group_graphemes: false,
+            category_grouping_lens: vec![],
+            unknown_fallback: UnknownFallback::Grouped,
         }
     }
 
@@ -119,9 +259,24 @@ impl Tokenizer {
     /// 新しい`Tokenizer`インスタンス
     pub fn from_inner(dict: DictionaryInner) -> Self {
         Self {
-            dict: Arc::new(Dictionary::Owned { dict: Arc::new(dict), _caching_handle: None }),
+            dict: Arc::new(Dictionary::from_inner(dict)),
             space_cateset: None,
             max_grouping_len: None,
+            unk_grouping: true,
+            tie_break: TieBreak::LastWins,
+            user_cost_bonus: 0,
+            strict_user_lexicon: false,
+            fuzzy_user: None,
+            folded: None,
+            unk_providers: None,
+            edge_scorer: None,
+            augmenters: vec![],
+            keep_space_tokens: false,
+            space_feature: Arc::from(DEFAULT_SPACE_FEATURE),
+            replacement_cinfo: None,
+            group_graphemes: false,
+            category_grouping_lens: vec![],
+            unknown_fallback: UnknownFallback::Grouped,
         }
     }
 
@@ -154,6 +309,21 @@ impl Tokenizer {
             dict,
             space_cateset: None,
             max_grouping_len: None,
+            unk_grouping: true,
+            tie_break: TieBreak::LastWins,
+            user_cost_bonus: 0,
+            strict_user_lexicon: false,
+            fuzzy_user: None,
+            folded: None,
+            unk_providers: None,
+            edge_scorer: None,
+            augmenters: vec![],
+            keep_space_tokens: false,
+            space_feature: Arc::from(DEFAULT_SPACE_FEATURE),
+            replacement_cinfo: None,
+            group_graphemes: false,
+            category_grouping_lens: vec![],
+            unknown_fallback: UnknownFallback::Grouped,
         }
     }
 
@@ -203,6 +373,260 @@ impl Tokenizer {
     }
 
 
+    /// スペースを独立した空白トークンとして出力するかどうかを設定します。
+    ///
+    /// 有効にすると、[`ignore_space()`](Self::ignore_space)と同様にスペースを無視して
+    /// 解析したうえで、スペースの連続を1つの空白トークンとして結果に挿入します。
+    /// 空白以外のトークンは`ignore_space(true)`の場合と同じになり、すべてのトークンの
+    /// 表層形を連結すると元の入力文が復元されます。
+    ///
+    /// 空白トークンの語彙種別は[`LexType::Unknown`]、単語インデックスは[`WordIdx::SPACE`]、
+    /// 接続IDと単語コストは`0`、累積コストは直前のトークンと同じ値です。素性は
+    /// [`space_token_feature()`](Self::space_token_feature)で変更できます
+    /// （デフォルトは[`DEFAULT_SPACE_FEATURE`]）。
+    /// この設定はN-best解析の結果には影響しません。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `true`の場合、空白トークンを出力します。`false`の場合、空白トークンの
+    ///   出力のみを無効にし、スペースを無視する設定はそのまま残ります。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// 入力辞書に`SPACE`カテゴリが定義されていない場合、[`VibratoError`]が返されます。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict).keep_space_tokens(true)?;
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("東京  京都");
+    /// worker.tokenize();
+    /// let text: String = worker.token_iter().map(|t| t.surface()).collect();
+    /// assert_eq!(text, "東京  京都");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn keep_space_tokens(mut self, yes: bool) -> Result<Self> {
+        if yes {
+            self = self.ignore_space(true)?;
+        }
+        self.keep_space_tokens = yes;
+        Ok(self)
+    }
+
+    /// 空白トークンの素性を設定します。
+    ///
+    /// [`keep_space_tokens()`](Self::keep_space_tokens)が有効な場合に使用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `feature` - 空白トークンの素性
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    pub fn space_token_feature<S: AsRef<str>>(mut self, feature: S) -> Self {
+        self.space_feature = Arc::from(feature.as_ref());
+        self
+    }
+
+    /// 空白トークンを出力するかどうかを返します。
+    #[inline(always)]
+    pub(crate) const fn keeps_space_tokens(&self) -> bool {
+        self.keep_space_tokens && self.space_cateset.is_some()
+    }
+
+    /// 空白トークンの素性を取得します。
+    #[inline(always)]
+    pub(crate) fn space_feature(&self) -> &str {
+        &self.space_feature
+    }
+
+    /// U+FFFD（REPLACEMENT CHARACTER）を指定した文字カテゴリの未知語として扱います。
+    ///
+    /// [`Worker::reset_sentence_lossy()`](crate::tokenizer::worker::Worker::reset_sentence_lossy)で
+    /// 不正なバイト列を置換した文字が、`char.def`での割り当てに関わらず指定したカテゴリに
+    /// 分類されます。置換文字は常に未知語処理の対象となり、連続する置換文字は1つの未知語に
+    /// まとめられるため、前後の記号などと結合されることはありません。
+    ///
+    /// # 引数
+    ///
+    /// * `category` - 置換文字に割り当てるカテゴリ名。`char.def`と`unk.def`の両方で
+    ///   定義されている必要があります。`None`の場合、`char.def`での割り当てに戻します。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// カテゴリが`char.def`に定義されていない場合、または`unk.def`にそのカテゴリの
+    /// エントリがない場合、[`VibratoError`]が返されます。
+    pub fn replacement_char_category(mut self, category: Option<&str>) -> Result<Self> {
+        let Some(category) = category else {
+            self.replacement_cinfo = None;
+            return Ok(self);
+        };
+        let (cate_id, has_entries) = match &*self.dict {
+            Dictionary::Archived(archived_dict) => {
+                let cate_id = archived_dict.char_prop().cate_id(category);
+                (cate_id, cate_id.is_some_and(|id| archived_dict.unk_handler().has_entries(id)))
+            }
+            Dictionary::Owned { dict, .. } => {
+                let cate_id = dict.char_prop().cate_id(category);
+                (cate_id, cate_id.is_some_and(|id| dict.unk_handler().has_entries(id)))
+            }
+        };
+        let cate_id = cate_id.ok_or_else(|| {
+            VibratoError::invalid_argument(
+                "category",
+                format!("{category} is not defined in the input dictionary (i.e., char.def)."),
+            )
+        })?;
+        if !has_entries {
+            return Err(VibratoError::invalid_argument(
+                "category",
+                format!("{category} has no entries in the input dictionary (i.e., unk.def)."),
+            ));
+        }
+        let cinfo = CharInfo::new(1 << cate_id, cate_id, true, true, 0).ok_or_else(|| {
+            VibratoError::invalid_argument("category", format!("{category} has too large ID."))
+        })?;
+        self.replacement_cinfo = Some(cinfo);
+        Ok(self)
+    }
+
+    /// U+FFFDに割り当てる文字情報を取得します。
+    #[inline(always)]
+    pub(crate) const fn replacement_cinfo(&self) -> Option<CharInfo> {
+        self.replacement_cinfo
+    }
+
+    /// 拡張書記素クラスタを1文字として扱って未知語を生成するかどうかを設定します。
+    ///
+    /// 有効にすると、ZWJで結合された絵文字の並びや異体字セレクタ付きの文字など、
+    /// Unicodeの拡張書記素クラスタ（UAX #29）を分割する未知語が生成されなくなります。
+    /// クラスタ内の2文字目以降は先頭の文字と同じ文字種として扱われます。
+    /// 辞書の単語との照合は従来どおり文字単位で行われます。
+    ///
+    /// デフォルトでは無効です。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `true`の場合、拡張書記素クラスタを1文字として扱います
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    pub const fn group_graphemes(mut self, yes: bool) -> Self {
+        self.group_graphemes = yes;
+        self
+    }
+
+    /// 拡張書記素クラスタを1文字として扱うかどうかを返します。
+    #[inline(always)]
+    pub(crate) const fn groups_graphemes(&self) -> bool {
+        self.group_graphemes
+    }
+
+    /// ラテン文字の幅と大文字・小文字を区別せずに辞書の単語を照合するかどうかを設定します。
+    ///
+    /// 有効にすると、全角英数字と半角英数字、およびASCIIの大文字と小文字を同一視して
+    /// システム辞書とユーザー辞書の単語を照合します。例えば、辞書に`ABC`のみが登録されて
+    /// いる場合でも、入力の`ＡＢＣ`や`abc`がその単語として解析されます。トークンの表層形は
+    /// 入力のまま出力されます。入力と表層形が完全に一致する単語は通常どおり照合されます。
+    ///
+    /// 有効にした時点で、英数字を含む表層形から照合用の索引を構築します。
+    /// デフォルトでは無効です。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `true`の場合、幅と大文字・小文字を区別せずに照合します
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// 辞書が表層形を持たない場合、[`VibratoError`]が返されます。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict).fold_latin(true)?;
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("ＧｉｔＨｕｂ");
+    /// worker.tokenize();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn fold_latin(mut self, yes: bool) -> Result<Self> {
+        if !yes {
+            self.folded = None;
+            return Ok(self);
+        }
+        if self.folded.is_none() {
+            let dict = self.dictionary();
+            let num_system_words = match &dict {
+                DictionaryInnerRef::Archived(dict) => dict.system_lexicon().num_words(),
+                DictionaryInnerRef::Owned(dict) => dict.system_lexicon().num_words(),
+            };
+            let num_user_words = dict.num_user_words().unwrap_or(0);
+            let folded = FoldedIndex::new(&self.dict, num_system_words, num_user_words)?;
+            self.folded = Some(Arc::new(folded));
+        }
+        Ok(self)
+    }
+
+    /// ラテン文字の幅と大文字・小文字を区別せずに照合するかどうかを返します。
+    #[inline(always)]
+    pub(crate) const fn folds_latin(&self) -> bool {
+        self.folded.is_some()
+    }
+
+    /// 辞書に一致しない文字列から未知語を生成する際のグループ化の方法を指定します。
+    ///
+    /// [`UnknownFallback::ScriptBoundary`]を指定すると、`char.def`で同じカテゴリに
+    /// 属する文字の連続であっても、文字体系が変わる位置で未知語が区切られます。
+    /// 辞書を再学習せずに、ノイズの多いテキストの解析結果を改善する場合に使用します。
+    ///
+    /// デフォルトは[`UnknownFallback::Grouped`]です。
+    ///
+    /// # 引数
+    ///
+    /// * `fallback` - 未知語のグループ化の方法
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    /// use vibrato_rkyv::tokenizer::UnknownFallback;
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict).unknown_fallback(UnknownFallback::ScriptBoundary);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn unknown_fallback(mut self, fallback: UnknownFallback) -> Self {
+        self.unknown_fallback = fallback;
+        self
+    }
+
     /// 未知語の最大グルーピング長を指定します。
     ///
     /// デフォルトでは、長さは無限です。
@@ -238,6 +662,418 @@ impl Tokenizer {
         self
     }
 
+    /// 指定された文字カテゴリの未知語の最大グルーピング長を指定します。
+    ///
+    /// [`max_grouping_len()`](Self::max_grouping_len)の設定を、基本カテゴリが
+    /// `category`である文字から始まる未知語についてのみ上書きします。Webのテキストに
+    /// 含まれる長いカタカナの連続が一つの未知語になるのを防ぐ場合などに使用します。
+    /// 同じカテゴリに複数回指定した場合は、最後の指定が使用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `category` - `char.def`で定義されたカテゴリ名
+    /// * `max_grouping_len` - 未知語の最大グルーピング長。0は無限の長さを示します。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// `category`が入力辞書に定義されていない場合、[`VibratoError`]が返されます。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict)
+    ///     .max_grouping_len(24)
+    ///     .category_grouping_len("KATAKANA", 12)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn category_grouping_len(mut self, category: &str, max_grouping_len: usize) -> Result<Self> {
+        let cate_id = match &*self.dict {
+            Dictionary::Archived(archived_dict) => archived_dict.char_prop().cate_id(category),
+            Dictionary::Owned { dict, .. } => dict.char_prop().cate_id(category),
+        }
+        .ok_or_else(|| {
+            VibratoError::invalid_argument(
+                "category",
+                format!("{category} is not defined in the input dictionary (i.e., char.def)."),
+            )
+        })?;
+        let max_grouping_len = (max_grouping_len != 0).then_some(max_grouping_len);
+        self.category_grouping_lens.retain(|&(id, _)| id != cate_id);
+        self.category_grouping_lens.push((cate_id, max_grouping_len));
+        Ok(self)
+    }
+
+    /// 指定された文字から始まる未知語の最大グルーピング長を返します。
+    #[inline(always)]
+    fn grouping_len_of(&self, cinfo: CharInfo) -> Option<usize> {
+        self.category_grouping_lens
+            .iter()
+            .find(|&&(cate_id, _)| cate_id == cinfo.base_id())
+            .map_or(self.max_grouping_len, |&(_, len)| len)
+    }
+
+    /// 同コストの候補が複数ある場合に、辞書種別による優先順位を適用するかを指定します。
+    ///
+    /// ラティス上で同じ位置に終わる候補の累積コストが完全に一致した場合、
+    /// デフォルトではラティスに後から挿入された候補が選ばれます。挿入順はユーザー辞書、
+    /// システム辞書、未知語の順であるため、ユーザー辞書に同コストの語を追加しても
+    /// システム辞書の語が選ばれることがあります。
+    ///
+    /// このオプションを有効にすると、同コストの候補間では
+    /// ユーザー辞書 > システム辞書 > 未知語 の順に優先されます。
+    /// 同じ種別の候補同士では、従来どおり後から挿入された候補が選ばれます。
+    ///
+    /// `tie_break(TieBreak::LexTypePriority)`と同じです。`false`を指定した場合は
+    /// [`TieBreak::LastWins`]に戻ります。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - 優先順位を適用する場合は`true`
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict).lex_type_priority(true);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn lex_type_priority(self, yes: bool) -> Self {
+        self.tie_break(if yes { TieBreak::LexTypePriority } else { TieBreak::LastWins })
+    }
+
+    /// 同コストの候補が複数ある場合の選び方を指定します。
+    ///
+    /// ラティス上で同じ位置に終わる左側の候補の累積コストが完全に一致した場合に、
+    /// どの候補を最良パスに含めるかを決めます。各モードの意味は[`TieBreak`]を
+    /// 参照してください。デフォルトは[`TieBreak::LastWins`]です。
+    ///
+    /// MeCabの出力と差分をとる場合は[`TieBreak::MecabCompatible`]を指定してください。
+    ///
+    /// # 引数
+    ///
+    /// * `tie_break` - 同コストの候補の選び方
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    /// use vibrato_rkyv::tokenizer::TieBreak;
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict)
+    ///     .ignore_space(true)?
+    ///     .max_grouping_len(24)
+    ///     .tie_break(TieBreak::MecabCompatible);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// ユーザー辞書の語の単語コストから一律に値を引きます。
+    ///
+    /// ユーザー辞書に追加した語は、単語コストを適切に設定しないとシステム辞書の語を
+    /// 組み合わせた解析結果に負けることがあります。このオプションを使用すると、
+    /// ユーザー辞書の語をラティスに追加する際に、単語コストから`bonus`を引きます。
+    /// 辞書自体は変更されないため、トークンの[`word_cost()`](crate::token::Token::word_cost)は
+    /// 辞書に登録されたコストのままです。
+    ///
+    /// # 引数
+    ///
+    /// * `bonus` - 単語コストから引く値。負の値を指定するとユーザー辞書の語が
+    ///   選ばれにくくなります。デフォルトは`0`です。
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict).user_cost_bonus(5000);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn user_cost_bonus(mut self, bonus: i16) -> Self {
+        self.user_cost_bonus = bonus;
+        self
+    }
+
+    /// ユーザー辞書の語が一致した位置で、他の候補を抑制するかを指定します。
+    ///
+    /// 有効にすると、ユーザー辞書の語が完全一致した開始位置からは、システム辞書の語と
+    /// 未知語をラティスに追加しません。その位置からはユーザー辞書の語のみが候補となるため、
+    /// ユーザー辞書に語を追加することで確実に区切りを変えられます。
+    ///
+    /// 他の開始位置から始まりユーザー辞書の語にまたがる候補は抑制されないため、
+    /// そのような候補が選ばれる場合は[`Self::user_cost_bonus()`]と組み合わせてください。
+    /// [`Self::approximate_user_lexicon()`]による近似照合の一致は対象外です。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - 他の候補を抑制する場合は`true`
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    pub const fn strict_user_lexicon(mut self, yes: bool) -> Self {
+        self.strict_user_lexicon = yes;
+        self
+    }
+
+    /// ユーザー辞書の表層形に対する近似照合を有効にします。
+    ///
+    /// 有効にすると、入力文中の文字列がユーザー辞書の表層形と編集距離1
+    /// （1文字の置換・挿入・削除）で一致する場合にも、その単語がラティスの候補として
+    /// 追加されます。近似照合された単語のコストには`penalty`が加算されるため、
+    /// 完全一致する候補や他の解析結果と競合した上で採用されるかが決まります。
+    /// トークンの表層形は入力文の文字列、素性はユーザー辞書の単語のものになります。
+    /// OCRや音声認識結果の後処理で、わずかに誤った専門用語を拾う用途を想定しています。
+    ///
+    /// 辞書に登録したものと同じユーザー辞書ファイルを再度読み込んで照合用のトライを
+    /// 構築します。入力は登録済みのユーザー辞書と語数、表層形、単語コストが一致する
+    /// 必要があります。表層形を保持しない旧形式から変換した辞書では、表層形は比較されません。
+    ///
+    /// # 引数
+    ///
+    /// * `user_lexicon_rdr` - 辞書に登録済みのユーザー辞書ファイル `*.csv` のリーダー
+    /// * `min_len` - 近似照合の対象とする表層形の最小文字数。短い語は誤照合が
+    ///   多くなるため、`3`以上を推奨します
+    /// * `penalty` - 近似照合された単語のコストに加算するペナルティ
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// 辞書にユーザー辞書が登録されていない場合や、入力が登録済みのユーザー辞書と
+    /// 一致しない場合に[`VibratoError`]が返されます。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use vibrato_rkyv::Tokenizer;
+    /// use vibrato_rkyv::dictionary::SystemDictionaryBuilder;
+    ///
+    /// let dict = SystemDictionaryBuilder::from_readers(
+    ///     File::open("lex.csv")?,
+    ///     File::open("matrix.def")?,
+    ///     File::open("char.def")?,
+    ///     File::open("unk.def")?,
+    /// )?
+    /// .reset_user_lexicon_from_reader(Some(File::open("user.csv")?))?;
+    /// let tokenizer = Tokenizer::from_inner(dict)
+    ///     .approximate_user_lexicon(File::open("user.csv")?, 3, 3000)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn approximate_user_lexicon<R>(
+        mut self,
+        mut user_lexicon_rdr: R,
+        min_len: usize,
+        penalty: i16,
+    ) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut buf = vec![];
+        user_lexicon_rdr.read_to_end(&mut buf)?;
+        let entries = Lexicon::parse_csv(&buf, "user.csv")?;
+
+        // Connection ids may have been remapped, so only surfaces and costs are compared.
+        // Dictionaries converted from the legacy format have no surfaces to compare.
+        let dict = self.dictionary();
+        let consistent = dict.num_user_words().map(|num_words| {
+            num_words == entries.len()
+                && entries.iter().enumerate().all(|(i, e)| {
+                    let word_idx = WordIdx::new(LexType::User, i as u32);
+                    dict.word_param(word_idx).word_cost == e.param.word_cost
+                        && self.dict.word_surface(word_idx).is_none_or(|s| s == e.surface)
+                })
+        });
+        match consistent {
+            None => {
+                return Err(VibratoError::invalid_state(
+                    "The dictionary has no user lexicon.",
+                    "Register it with reset_user_lexicon_from_reader() first.",
+                ));
+            }
+            Some(false) => {
+                return Err(VibratoError::invalid_argument(
+                    "user_lexicon_rdr",
+                    "The input mismatches the user lexicon registered in the dictionary.",
+                ));
+            }
+            Some(true) => {}
+        }
+
+        self.fuzzy_user = Some(Arc::new(FuzzyMatcher::new(
+            entries.iter().map(|e| &e.surface),
+            min_len,
+            penalty,
+        )));
+        Ok(self)
+    }
+
+    /// 未知語のプロバイダを登録します。
+    ///
+    /// 登録したプロバイダは、`unk.def`による未知語の生成と並行してラティスの各開始位置で
+    /// 呼び出され、生成された語は辞書の語や他の未知語とコストで競合します。
+    /// 正規表現で見つけたURLやメールアドレスのように、複数の文字種にまたがる語を
+    /// 1語として扱う用途を想定しています。複数のプロバイダを登録できます。
+    ///
+    /// [`Self::strict_user_lexicon()`]でユーザー辞書の語が優先される開始位置では
+    /// 呼び出されません。
+    ///
+    /// # 引数
+    ///
+    /// * `provider` - 未知語のプロバイダ
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// [`UnkProvider::entries()`]が返す定義の接続IDが辞書の範囲外の場合や、
+    /// 定義の総数が多すぎる場合に[`VibratoError`]が返されます。
+    pub fn unk_provider<P>(mut self, provider: P) -> Result<Self>
+    where
+        P: UnkProvider + 'static,
+    {
+        let entries = provider.entries();
+        let num_left_ids = self.dict.num_left_ids();
+        let num_right_ids = self.dict.num_right_ids();
+        if entries.iter().any(|e| {
+            usize::from(e.left_id) >= num_left_ids || usize::from(e.right_id) >= num_right_ids
+        }) {
+            return Err(VibratoError::invalid_argument(
+                "provider",
+                "The connection ids of the entries must be within the matrix.",
+            ));
+        }
+
+        let mut providers = self.unk_providers.take().unwrap_or_default();
+        let providers_mut = Arc::make_mut(&mut providers);
+        let num_entries = providers_mut.num_entries() + entries.len();
+        if num_entries > MAX_PROVIDED_ENTRIES {
+            return Err(VibratoError::invalid_argument(
+                "provider",
+                format!("The number of entries must be no more than {MAX_PROVIDED_ENTRIES}."),
+            ));
+        }
+        providers_mut.push(Arc::new(provider), entries);
+        self.unk_providers = Some(providers);
+        Ok(self)
+    }
+
+    /// エッジのコストを補正するスコアラーを登録します。
+    ///
+    /// 登録したスコアラーは、ラティスの構築時に候補となるすべてのエッジについて
+    /// 呼び出され、返した値が接続コストに加えられます。外部の言語モデルとの
+    /// シャローフュージョンや、特定の語の連接の優遇に使用します。N-best解析や
+    /// [`Tokenizer::segment()`]でも同じ補正が使用されます。
+    ///
+    /// スコアラーを登録しない場合、解析の速度には影響しません。登録し直すと、
+    /// 以前のスコアラーは置き換えられます。
+    ///
+    /// # 引数
+    ///
+    /// * `scorer` - エッジのコストを補正するスコアラー
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    pub fn edge_scorer<S>(mut self, scorer: S) -> Self
+    where
+        S: EdgeScorer + 'static,
+    {
+        self.edge_scorer = Some(Arc::new(scorer));
+        self
+    }
+
+    /// 解析時にラティスへ候補を追加する拡張を登録します。
+    ///
+    /// 登録した拡張は、ラティスの各開始位置で呼び出されます。拡張が返した表層形と
+    /// 完全に一致する辞書の語が、入力文の同じ範囲を覆うノードとして追加されます。
+    /// ユーザー辞書の語が一致した場合は、[`Self::user_cost_bonus()`]も適用されます。
+    /// 複数の拡張を登録できます。
+    ///
+    /// [`Self::strict_user_lexicon()`]でユーザー辞書の語が優先される開始位置では
+    /// 呼び出されません。
+    ///
+    /// # 引数
+    ///
+    /// * `augmenter` - ラティスに候補を追加する拡張
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    pub fn lattice_augmenter<A>(mut self, augmenter: A) -> Self
+    where
+        A: LatticeAugmenter + 'static,
+    {
+        self.augmenters.push(Arc::new(augmenter));
+        self
+    }
+
+    /// 登録されたエッジのスコアラーを返します。
+    #[inline(always)]
+    pub(crate) fn edge_scorer_ref(&self) -> Option<&dyn EdgeScorer> {
+        self.edge_scorer.as_deref()
+    }
+
+    /// 単語の素性を取得します。
+    ///
+    /// 辞書の語に加えて、空白トークンとプロバイダが生成した語を扱います。
+    #[inline(always)]
+    pub(crate) fn word_feature(&self, word_idx: WordIdx) -> &str {
+        if word_idx == WordIdx::SPACE {
+            return self.space_feature();
+        }
+        if let Some(e) = self.unk_providers.as_ref().and_then(|p| p.entry(word_idx)) {
+            return &e.feature;
+        }
+        match self.dictionary() {
+            DictionaryInnerRef::Archived(dict) => dict.word_feature(word_idx),
+            DictionaryInnerRef::Owned(dict) => dict.word_feature(word_idx),
+        }
+    }
+
+    /// ノードの単語コストを取得します。
+    ///
+    /// ラティスの構築時に加えた近似照合や候補の追加の罰則を含みます。
+    /// [`Self::user_cost_bonus()`]による補正は含みません。
+    #[inline(always)]
+    pub(crate) fn word_cost(&self, node: &Node) -> i16 {
+        if node.lex_type == LexType::User {
+            node.word_cost.saturating_add(self.user_cost_bonus)
+        } else {
+            node.word_cost
+        }
+    }
+
     /// 辞書への参照を取得します。
     ///
     /// # 戻り値
@@ -250,6 +1086,23 @@ impl Tokenizer {
         }
     }
 
+    /// 入力文を設定し、辞書の文字定義に従って文字情報を計算します。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 設定先の文
+    /// * `input` - 空でない入力文字列
+    pub(crate) fn compile_sentence(&self, sent: &mut Sentence, input: &str) {
+        sent.set_sentence(input);
+        sent.set_replacement_char_info(self.replacement_cinfo());
+        sent.set_group_graphemes(self.groups_graphemes());
+        sent.set_split_scripts(self.unknown_fallback == UnknownFallback::ScriptBoundary);
+        match self.dictionary() {
+            DictionaryInnerRef::Archived(dict) => sent.compile_archived(dict.char_prop()),
+            DictionaryInnerRef::Owned(dict) => sent.compile(dict.char_prop()),
+        }
+    }
+
     /// 新しいワーカーを作成します。
     ///
     /// ワーカーは実際の形態素解析処理を実行するために使用されます。
@@ -286,16 +1139,57 @@ impl Tokenizer {
     /// * `sent` - 入力文
     /// * `lattice` - 構築するラティス構造
     pub(crate) fn build_lattice(&self, sent: &Sentence, lattice: &mut Lattice) {
+        let mut cursor = self.start_lattice(sent, lattice);
+        self.extend_lattice(sent, lattice, &mut cursor, usize::MAX);
+    }
+
+    /// ラティスを初期化し、構築の開始位置を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 入力文
+    /// * `lattice` - 構築するラティス構造
+    ///
+    /// # 戻り値
+    ///
+    /// 文頭を指す[`LatticeCursor`]
+    pub(crate) fn start_lattice(&self, sent: &Sentence, lattice: &mut Lattice) -> LatticeCursor {
+        lattice.reset(sent.len_char());
+        lattice.set_tie_break(self.tie_break);
+        LatticeCursor::default()
+    }
+
+    /// ラティスの構築を最大`max_columns`文字位置分だけ進めます。
+    ///
+    /// 文末まで到達した場合はEOSノードを挿入し、`true`を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 入力文
+    /// * `lattice` - 構築中のラティス構造
+    /// * `cursor` - 構築の進行状況
+    /// * `max_columns` - この呼び出しで処理する文字位置の最大数
+    ///
+    /// # 戻り値
+    ///
+    /// ラティスの構築が完了した場合は`true`
+    pub(crate) fn extend_lattice(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        cursor: &mut LatticeCursor,
+        max_columns: usize,
+    ) -> bool {
         match &*self.dict {
             Dictionary::Archived(archived_dict) => match archived_dict.connector() {
-                ArchivedConnectorWrapper::Matrix(c) => self.build_lattice_inner(sent, lattice, c),
-                ArchivedConnectorWrapper::Raw(c) => self.build_lattice_inner(sent, lattice, c),
-                ArchivedConnectorWrapper::Dual(c) => self.build_lattice_inner(sent, lattice, c),
+                ArchivedConnectorWrapper::Matrix(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
+                ArchivedConnectorWrapper::Raw(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
+                ArchivedConnectorWrapper::Dual(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
             },
             Dictionary::Owned{ dict, .. } => match dict.connector() {
-                ConnectorWrapper::Matrix(c) => self.build_lattice_inner(sent, lattice, c),
-                ConnectorWrapper::Raw(c) => self.build_lattice_inner(sent, lattice, c),
-                ConnectorWrapper::Dual(c) => self.build_lattice_inner(sent, lattice, c),
+                ConnectorWrapper::Matrix(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
+                ConnectorWrapper::Raw(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
+                ConnectorWrapper::Dual(c) => self.extend_lattice_with(sent, lattice, cursor, max_columns, c),
             },
         }
     }
@@ -312,18 +1206,57 @@ impl Tokenizer {
     pub(crate) fn build_lattice_nbest(&self, sent: &Sentence, lattice: &mut LatticeNBest) {
         match &*self.dict {
             Dictionary::Archived(archived_dict) => match archived_dict.connector() {
-                ArchivedConnectorWrapper::Matrix(c) => self.build_lattice_inner_nbest(sent, lattice, c),
-                ArchivedConnectorWrapper::Raw(c) => self.build_lattice_inner_nbest(sent, lattice, c),
-                ArchivedConnectorWrapper::Dual(c) => self.build_lattice_inner_nbest(sent, lattice, c),
+                ArchivedConnectorWrapper::Matrix(c) => self.build_lattice_nbest_with(sent, lattice, c),
+                ArchivedConnectorWrapper::Raw(c) => self.build_lattice_nbest_with(sent, lattice, c),
+                ArchivedConnectorWrapper::Dual(c) => self.build_lattice_nbest_with(sent, lattice, c),
             },
             Dictionary::Owned{ dict, .. } => match dict.connector() {
-                ConnectorWrapper::Matrix(c) => self.build_lattice_inner_nbest(sent, lattice, c),
-                ConnectorWrapper::Raw(c) => self.build_lattice_inner_nbest(sent, lattice, c),
-                ConnectorWrapper::Dual(c) => self.build_lattice_inner_nbest(sent, lattice, c),
+                ConnectorWrapper::Matrix(c) => self.build_lattice_nbest_with(sent, lattice, c),
+                ConnectorWrapper::Raw(c) => self.build_lattice_nbest_with(sent, lattice, c),
+                ConnectorWrapper::Dual(c) => self.build_lattice_nbest_with(sent, lattice, c),
             },
         }
     }
 
+    /// 登録されたスコアラーの補正を加えて、ラティスの構築を進めます。
+    ///
+    /// スコアラーが登録されていない場合は、コネクタをそのまま使用します。
+    pub(crate) fn extend_lattice_with<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        cursor: &mut LatticeCursor,
+        max_columns: usize,
+        connector: &C,
+    ) -> bool
+    where
+        C: ConnectorCost,
+    {
+        match self.edge_scorer.as_deref() {
+            Some(scorer) => {
+                let connector = ScoredConnector::new(connector, scorer, sent);
+                self.extend_lattice_inner(sent, lattice, cursor, max_columns, &connector)
+            }
+            None => self.extend_lattice_inner(sent, lattice, cursor, max_columns, connector),
+        }
+    }
+
+    /// 登録されたスコアラーの補正を加えて、N-best解析用のラティス構造を構築します。
+    ///
+    /// スコアラーが登録されていない場合は、コネクタをそのまま使用します。
+    fn build_lattice_nbest_with<C>(&self, sent: &Sentence, lattice: &mut LatticeNBest, connector: &C)
+    where
+        C: ConnectorCost,
+    {
+        match self.edge_scorer.as_deref() {
+            Some(scorer) => {
+                let connector = ScoredConnector::new(connector, scorer, sent);
+                self.build_lattice_inner_nbest(sent, lattice, &connector)
+            }
+            None => self.build_lattice_inner_nbest(sent, lattice, connector),
+        }
+    }
+
     /// ラティス構造の内部構築処理。
     ///
     /// コネクタの型に応じてラティスを構築します。
@@ -333,23 +1266,36 @@ impl Tokenizer {
     ///
     /// * `sent` - 入力文
     /// * `lattice` - 構築するラティス構造
+    /// * `cursor` - 構築の進行状況
+    /// * `max_columns` - この呼び出しで処理する文字位置の最大数
     /// * `connector` - 接続コスト計算用のコネクタ
-    fn build_lattice_inner<C>(&self, sent: &Sentence, lattice: &mut Lattice, connector: &C)
+    fn extend_lattice_inner<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        cursor: &mut LatticeCursor,
+        max_columns: usize,
+        connector: &C,
+    ) -> bool
     where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
-        lattice.reset(sent.len_char());
-
         // These variables indicate the starting character positions of words currently stored
         // in the lattice. If ignore_space() is unset, these always have the same values, and
         // start_node is practically non-functional. If ignore_space() is set, start_node and
         // start_word indicate the starting positions containing and ignoring a space character,
         // respectively. Suppose handle sentence "mens second" at position 4. start_node indicates
         // position 4, and start_word indicates position 5.
-        let mut start_node = 0;
-        let mut start_word = 0;
+        let LatticeCursor { mut start_node, mut start_word } = *cursor;
+        let mut num_columns = 0;
 
         while start_word < sent.len_char() {
+            if num_columns == max_columns {
+                *cursor = LatticeCursor { start_node, start_word };
+                return false;
+            }
+            num_columns += 1;
+
             if !lattice.has_previous_node(start_node) {
                 start_word += 1;
                 start_node = start_word;
@@ -379,6 +1325,8 @@ impl Tokenizer {
         }
 
         lattice.insert_eos(start_node, connector);
+        *cursor = LatticeCursor { start_node, start_word };
+        true
     }
 
     /// N-best解析用ラティス構造の内部構築処理。
@@ -393,9 +1341,10 @@ impl Tokenizer {
     /// * `connector` - 接続コスト計算用のコネクタ
     fn build_lattice_inner_nbest<C>(&self, sent: &Sentence, lattice: &mut LatticeNBest, connector: &C)
     where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         lattice.reset(sent.len_char());
+        lattice.set_tie_break(self.tie_break);
 
         // These variables indicate the starting character positions of words currently stored
         // in the lattice. If ignore_space() is unset, these always have the same values, and
@@ -439,6 +1388,23 @@ impl Tokenizer {
     }
 }
 
+/// ラティス構築の進行状況。
+///
+/// [`Tokenizer::extend_lattice()`]で構築を中断・再開するために使用します。
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LatticeCursor {
+    start_node: usize,
+    start_word: usize,
+}
+
+impl LatticeCursor {
+    /// 次に処理する文字位置を返します。
+    #[inline(always)]
+    pub(crate) const fn position(&self) -> usize {
+        self.start_word
+    }
+}
+
 macro_rules! add_lattice_edges_logic {
     (
         // self is required to access max_grouping_len
@@ -453,9 +1419,13 @@ macro_rules! add_lattice_edges_logic {
         let mut has_matched = false;
         let suffix = &$sent.chars()[$start_word..];
 
-        if let Some(user_lexicon) = $dict.user_lexicon().as_ref() {
-            for m in user_lexicon.common_prefix_iterator(suffix) {
+        // User lexicons are consulted in priority order.
+        for (offset, user_lexicon) in $dict.user_lexicons() {
+            for mut m in user_lexicon.common_prefix_iterator(suffix) {
                 debug_assert!($start_word + m.end_char <= $sent.len_char());
+                m.word_idx.word_id += offset;
+                m.word_param.word_cost =
+                    m.word_param.word_cost.saturating_sub($self.user_cost_bonus);
                 $lattice.insert_node(
                     $start_node,
                     $start_word,
@@ -467,36 +1437,153 @@ macro_rules! add_lattice_edges_logic {
                 has_matched = true;
             }
         }
+        if let Some(folded) = $self.folded.as_ref() {
+            folded.for_each_user_match(suffix, |word_idx, end_char| {
+                let mut word_param = $dict.word_param(word_idx);
+                word_param.word_cost = word_param.word_cost.saturating_sub($self.user_cost_bonus);
+                $lattice.insert_node(
+                    $start_node,
+                    $start_word,
+                    $start_word + end_char,
+                    word_idx,
+                    word_param,
+                    $connector,
+                );
+                has_matched = true;
+            });
+        }
 
-        for m in $dict.system_lexicon().common_prefix_iterator(suffix) {
-            debug_assert!($start_word + m.end_char <= $sent.len_char());
-            $lattice.insert_node(
-                $start_node,
-                $start_word,
-                $start_word + m.end_char,
-                m.word_idx,
-                m.word_param,
-                $connector,
-            );
-            has_matched = true;
+        // Approximate matches do not suppress unknown words because they are less
+        // reliable than exact ones.
+        if let Some(fuzzy) = $self.fuzzy_user.as_ref() {
+            fuzzy.for_each_match(suffix, |word_id, end_char| {
+                let word_idx = WordIdx::new(LexType::User, word_id);
+                let mut word_param = $dict.word_param(word_idx);
+                word_param.word_cost = word_param
+                    .word_cost
+                    .saturating_sub($self.user_cost_bonus)
+                    .saturating_add(fuzzy.penalty());
+                $lattice.insert_node(
+                    $start_node,
+                    $start_word,
+                    $start_word + end_char,
+                    word_idx,
+                    word_param,
+                    $connector,
+                );
+            });
         }
 
-        $dict.unk_handler().gen_unk_words(
-            $sent,
-            $start_word,
-            has_matched,
-            $self.max_grouping_len,
-            |w| {
+        // Only user words start here in the strict mode.
+        if !($self.strict_user_lexicon && has_matched) {
+            for m in $dict.system_lexicon().common_prefix_iterator(suffix) {
+                debug_assert!($start_word + m.end_char <= $sent.len_char());
                 $lattice.insert_node(
                     $start_node,
-                    w.start_char(),
-                    w.end_char(),
-                    w.word_idx(),
-                    w.word_param(),
+                    $start_word,
+                    $start_word + m.end_char,
+                    m.word_idx,
+                    m.word_param,
                     $connector,
                 );
-            },
-        );
+                has_matched = true;
+            }
+            if let Some(folded) = $self.folded.as_ref() {
+                folded.for_each_system_match(suffix, |word_idx, end_char| {
+                    $lattice.insert_node(
+                        $start_node,
+                        $start_word,
+                        $start_word + end_char,
+                        word_idx,
+                        $dict.word_param(word_idx),
+                        $connector,
+                    );
+                    has_matched = true;
+                });
+            }
+
+            $dict.unk_handler().gen_unk_words(
+                $sent,
+                $start_word,
+                has_matched,
+                $self.grouping_len_of($sent.char_info($start_word)),
+                $self.unk_grouping,
+                |w| {
+                    $lattice.insert_node(
+                        $start_node,
+                        w.start_char(),
+                        w.end_char(),
+                        w.word_idx(),
+                        w.word_param(),
+                        $connector,
+                    );
+                },
+            );
+
+            if let Some(providers) = $self.unk_providers.as_ref() {
+                providers.for_each_match($sent, $start_word, |end_char, word_idx, word_param| {
+                    $lattice.insert_node(
+                        $start_node,
+                        $start_word,
+                        end_char,
+                        word_idx,
+                        word_param,
+                        $connector,
+                    );
+                });
+            }
+
+            // Augmented candidates reuse the dictionary words exactly matching the variants,
+            // spanning the original input.
+            if !$self.augmenters.is_empty() {
+                let input = UnkInput::new($sent);
+                let mut variant = vec![];
+                for augmenter in &$self.augmenters {
+                    augmenter.augment(&input, $start_word, &mut |a| {
+                        if a.end_char <= $start_word || a.end_char > $sent.len_char() {
+                            return;
+                        }
+                        variant.clear();
+                        variant.extend(a.surface.chars());
+                        for (offset, user_lexicon) in $dict.user_lexicons() {
+                            for mut m in user_lexicon.common_prefix_iterator(&variant) {
+                                if m.end_char != variant.len() {
+                                    continue;
+                                }
+                                m.word_idx.word_id += offset;
+                                m.word_param.word_cost = m
+                                    .word_param
+                                    .word_cost
+                                    .saturating_sub($self.user_cost_bonus)
+                                    .saturating_add(a.penalty);
+                                $lattice.insert_node(
+                                    $start_node,
+                                    $start_word,
+                                    a.end_char,
+                                    m.word_idx,
+                                    m.word_param,
+                                    $connector,
+                                );
+                            }
+                        }
+                        for mut m in $dict.system_lexicon().common_prefix_iterator(&variant) {
+                            if m.end_char != variant.len() {
+                                continue;
+                            }
+                            m.word_param.word_cost = m.word_param.word_cost.saturating_add(a.penalty);
+                            $lattice.insert_node(
+                                $start_node,
+                                $start_word,
+                                a.end_char,
+                                m.word_idx,
+                                m.word_param,
+                                $connector,
+                            );
+                        }
+                    });
+                }
+            }
+        }
     }};
 }
 
@@ -520,7 +1607,7 @@ impl Tokenizer {
         start_word: usize,
         connector: &C,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         match self.dictionary() {
             DictionaryInnerRef::Archived(dict) => {
@@ -551,7 +1638,7 @@ impl Tokenizer {
         start_word: usize,
         connector: &C,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         match self.dictionary() {
             DictionaryInnerRef::Archived(dict) => {
@@ -583,9 +1670,9 @@ impl Tokenizer {
         start_node: usize,
         start_word: usize,
         connector: &C,
-        dict: &ArchivedDictionaryInner,
+        dict: &ArchivedDictionary,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         add_lattice_edges_logic!(
             self,
@@ -620,7 +1707,7 @@ impl Tokenizer {
         connector: &C,
         dict: &DictionaryInner,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         add_lattice_edges_logic!(
             self,
@@ -653,9 +1740,9 @@ impl Tokenizer {
         start_node: usize,
         start_word: usize,
         connector: &C,
-        dict: &ArchivedDictionaryInner,
+        dict: &ArchivedDictionary,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         add_lattice_edges_logic!(
             self,
@@ -690,7 +1777,7 @@ impl Tokenizer {
         connector: &C,
         dict: &DictionaryInner,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         add_lattice_edges_logic!(
             self,
@@ -974,4 +2061,81 @@ mod tests {
         assert_eq!(tokens.next().unwrap().surface(), "言語");
         assert!(tokens.next().is_none());
     }
-}
+
+    #[test]
+    fn test_nbest_paths_invalidated() {
+        let lexicon_csv = "自然,0,0,1,sizen
+言語,0,0,4,gengo
+自然言語,0,0,6,sizengengo";
+        let dict = build_test_dictionary(
+            lexicon_csv.as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        );
+
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+
+        worker.reset_sentence("自然言語");
+        worker.tokenize_nbest(2);
+        assert_eq!(worker.num_nbest_paths(), 2);
+        let surfaces: Vec<_> = worker
+            .nbest_token_iter(1)
+            .unwrap()
+            .map(|t| (t.surface(), t.range_char()))
+            .collect();
+        assert_eq!(surfaces, vec![("自然言語", 0..4)]);
+
+        // Rebuilding the lattice for 1-best discards the paths referring to the old one.
+        worker.tokenize();
+        assert_eq!(worker.num_nbest_paths(), 0);
+        assert!(worker.nbest_token_iter(0).is_none());
+
+        worker.tokenize_nbest(2);
+        assert_eq!(worker.num_nbest_paths(), 2);
+        worker.reset_sentence("言語");
+        assert_eq!(worker.num_nbest_paths(), 0);
+    }
+
+    #[test]
+    fn test_nbest_feature_sequences() {
+        let lexicon_csv = "自然,0,0,1,名詞,sizen
+言語,0,0,4,名詞,gengo
+言語,0,0,5,名詞,kotoba
+自然言語,0,0,6,名詞,sizengengo";
+        let matrix_def = "1 1\n0 0 0";
+        let char_def = "DEFAULT 0 1 0";
+        let unk_def = "DEFAULT,0,0,100,*";
+
+        let dict = build_test_dictionary(
+            lexicon_csv.as_bytes(),
+            matrix_def.as_bytes(),
+            char_def.as_bytes(),
+            unk_def.as_bytes(),
+        );
+
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        worker.reset_sentence("自然言語");
+
+        let sequences = worker.nbest_feature_sequences(5, &[]);
+        assert_eq!(sequences.num_paths(), 3);
+        assert_eq!(sequences.num_features(), 4);
+        let path = sequences.path(0);
+        assert_eq!(path.cost, 5);
+        assert_eq!(path.tokens.len(), 2);
+        assert_eq!(path.tokens[0].0, 0..2);
+        assert_eq!(sequences.features(path.tokens[0].1), ["名詞", "sizen"]);
+        assert_eq!(sequences.features(path.tokens[1].1), ["名詞", "gengo"]);
+
+        // The two paths through 言語 collapse when only the POS is selected.
+        let sequences = worker.nbest_feature_sequences(5, &[0, 3]);
+        assert_eq!(sequences.num_paths(), 2);
+        assert_eq!(sequences.num_features(), 1);
+        assert_eq!(sequences.features(0), ["名詞", "*"]);
+        let costs: Vec<_> = sequences.paths().map(|p| p.cost).collect();
+        assert_eq!(costs, [5, 6]);
+        assert_eq!(sequences.path(1).tokens, [(0..4, 0)]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,69 @@
+//! 解析時のラティスへの候補の追加
+//!
+//! このモジュールは、表記の正規化や同義語の展開のために、解析時にラティスへ候補を
+//! 追加する[`LatticeAugmenter`]トレイトを提供します。追加した候補は入力文の同じ範囲を
+//! 覆う別の辞書の語として扱われるため、辞書を再構築せずに表記の揺れを吸収できます。
+
+use crate::tokenizer::unk_provider::UnkInput;
+
+/// ラティスに追加する候補
+///
+/// 入力文の`start_char..end_char`の範囲を覆うノードとして、`surface`と完全に一致する
+/// 辞書の語を追加します。ノードの素性と接続IDは一致した語のものが使用され、
+/// トークンの表層形は入力文の該当範囲となります。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Augmentation<'a> {
+    /// 候補の終了位置（文字単位、排他的）
+    pub end_char: usize,
+    /// 辞書を引く表層形
+    pub surface: &'a str,
+    /// 一致した語の単語コストに加える値
+    pub penalty: i16,
+}
+
+/// 解析時にラティスへ候補を追加する拡張
+///
+/// [`Tokenizer::lattice_augmenter()`](crate::Tokenizer::lattice_augmenter)で登録します。
+/// 追加された候補は辞書の語や未知語とコストで競合します。検索エンジンで正規化した
+/// 表記を索引に加えるなど、N-best解析と組み合わせて複数の表記を得る用途を想定しています。
+///
+/// # 例
+///
+/// 全角の「ｔｏｋｙｏ」に辞書の「東京」の候補を追加する拡張です。
+///
+/// ```
+/// use vibrato_rkyv::tokenizer::{Augmentation, LatticeAugmenter, UnkInput};
+///
+/// struct Synonyms(Vec<(Vec<char>, String)>);
+///
+/// impl LatticeAugmenter for Synonyms {
+///     fn augment(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(Augmentation)) {
+///         let chars = &input.chars()[start_char..];
+///         for (key, variant) in &self.0 {
+///             if chars.starts_with(key) {
+///                 emit(Augmentation {
+///                     end_char: start_char + key.len(),
+///                     surface: variant,
+///                     penalty: 500,
+///                 });
+///             }
+///         }
+///     }
+/// }
+///
+/// let synonyms = Synonyms(vec![("ｔｏｋｙｏ".chars().collect(), "東京".to_string())]);
+/// ```
+pub trait LatticeAugmenter: Send + Sync {
+    /// 指定された位置から始まる候補を列挙します。
+    ///
+    /// 候補ごとに`emit`を呼び出します。終了位置が`start_char`以下または文字数を
+    /// 超える候補と、辞書に完全に一致する語がない候補は無視されます。
+    /// 一致する語が複数ある場合は、そのすべてが追加されます。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - 入力文
+    /// * `start_char` - 候補の開始位置（文字単位）
+    /// * `emit` - 候補を受け取る関数
+    fn augment(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(Augmentation));
+}
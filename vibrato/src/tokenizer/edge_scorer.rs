@@ -0,0 +1,144 @@
+//! エッジコストの外部補正
+//!
+//! このモジュールは、ラティスの各エッジ（隣接する2語の接続）のコストに
+//! アプリケーション定義の補正値を加える[`EdgeScorer`]トレイトを提供します。
+//! 外部の言語モデルとのシャローフュージョンや、分野ごとの語の連接の優遇などに使用します。
+//!
+//! スコアラーが登録されていない場合、ラティスの構築は補正を含まない実装に
+//! 単相化されるため、追加のコストは発生しません。
+
+use std::ops::Range;
+
+use crate::dictionary::WordIdx;
+use crate::dictionary::connector::ConnectorCost;
+use crate::sentence::Sentence;
+use crate::tokenizer::lattice::Node;
+
+/// エッジの端の語
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EdgeWord<'a> {
+    /// 単語インデックス
+    pub word_idx: WordIdx,
+    /// 語の範囲（文字単位）
+    pub range_char: Range<usize>,
+    /// 語の表層形
+    pub surface: &'a str,
+}
+
+/// エッジのコストを補正するスコアラー
+///
+/// [`Tokenizer::edge_scorer()`](crate::Tokenizer::edge_scorer)で登録します。
+/// ラティスの構築時に、候補となるすべてのエッジについて呼び出され、返した値が
+/// 接続コストに加えられます。負の値はその連接を優遇し、正の値は抑制します。
+///
+/// 同じ入力文に対しては、同じエッジについて常に同じ値を返す必要があります。
+///
+/// # 例
+///
+/// 「東京」の後に「都」が続く連接を優遇するスコアラーです。
+///
+/// ```
+/// use vibrato_rkyv::tokenizer::{EdgeScorer, EdgeWord};
+///
+/// struct Boost;
+///
+/// impl EdgeScorer for Boost {
+///     fn score(&self, left: Option<&EdgeWord>, right: Option<&EdgeWord>) -> i32 {
+///         match (left, right) {
+///             (Some(l), Some(r)) if l.surface == "東京" && r.surface == "都" => -1000,
+///             _ => 0,
+///         }
+///     }
+/// }
+/// ```
+pub trait EdgeScorer: Send + Sync {
+    /// エッジのコストの補正値を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `left` - 左側（文頭側）の語。文頭（BOS）からのエッジの場合は`None`
+    /// * `right` - 右側（文末側）の語。文末（EOS）へのエッジの場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// 接続コストに加える値
+    fn score(&self, left: Option<&EdgeWord<'_>>, right: Option<&EdgeWord<'_>>) -> i32;
+}
+
+/// ラティスのエッジのコスト。
+///
+/// 左側のノードは、右側のノードの開始位置（`right.start_node`）で終了します。
+pub(crate) trait EdgeCost {
+    /// エッジのコストを返します。
+    ///
+    /// # 引数
+    ///
+    /// * `left` - 左側のノード
+    /// * `right` - 右側のノード
+    /// * `right_end` - 右側のノードの終了位置（文字単位）。EOSの場合は使用されません。
+    fn edge_cost(&self, left: &Node, right: &Node, right_end: usize) -> i32;
+}
+
+impl<C> EdgeCost for C
+where
+    C: ConnectorCost + ?Sized,
+{
+    #[inline(always)]
+    fn edge_cost(&self, left: &Node, right: &Node, _right_end: usize) -> i32 {
+        self.cost(left.right_id, right.left_id)
+    }
+}
+
+/// スコアラーの補正値を接続コストに加えるコネクタ
+pub(crate) struct ScoredConnector<'a, C: ?Sized> {
+    connector: &'a C,
+    scorer: &'a dyn EdgeScorer,
+    sent: &'a Sentence,
+}
+
+impl<'a, C> ScoredConnector<'a, C>
+where
+    C: ConnectorCost + ?Sized,
+{
+    /// 新しいインスタンスを作成します。
+    pub(crate) const fn new(connector: &'a C, scorer: &'a dyn EdgeScorer, sent: &'a Sentence) -> Self {
+        Self { connector, scorer, sent }
+    }
+}
+
+impl<C> EdgeCost for ScoredConnector<'_, C>
+where
+    C: ConnectorCost + ?Sized,
+{
+    #[inline(always)]
+    fn edge_cost(&self, left: &Node, right: &Node, right_end: usize) -> i32 {
+        let conn_cost = self.connector.cost(left.right_id, right.left_id);
+        conn_cost.saturating_add(edge_delta(self.scorer, self.sent, left, right, right_end))
+    }
+}
+
+/// スコアラーによるエッジのコストの補正値を計算します。
+///
+/// # 引数
+///
+/// * `scorer` - スコアラー
+/// * `sent` - 入力文
+/// * `left` - 左側のノード
+/// * `right` - 右側のノード
+/// * `right_end` - 右側のノードの終了位置（文字単位）
+pub(crate) fn edge_delta(
+    scorer: &dyn EdgeScorer,
+    sent: &Sentence,
+    left: &Node,
+    right: &Node,
+    right_end: usize,
+) -> i32 {
+    let edge_word = |node: &Node, end: usize| EdgeWord {
+        word_idx: node.word_idx(),
+        range_char: node.start_word..end,
+        surface: &sent.raw()[sent.byte_position(node.start_word)..sent.byte_position(end)],
+    };
+    let left = (!left.is_bos()).then(|| edge_word(left, right.start_node));
+    let right = (!right.is_eos()).then(|| edge_word(right, right_end));
+    scorer.score(left.as_ref(), right.as_ref())
+}
@@ -0,0 +1,211 @@
+//! ラテン文字の幅と大文字・小文字を区別しない照合
+//!
+//! このモジュールは、全角英数字（`ＡＢＣ`）と半角英数字（`ABC`）、大文字と小文字を
+//! 同一視して辞書の単語を照合する[`FoldedIndex`]を提供します。辞書の表層形を正規化して
+//! 構築したトライを、入力文の文字を同じ規則で正規化しながらたどります。
+//! 入力文そのものは書き換えないため、トークンの表層形は入力のまま出力されます。
+
+use crate::Dictionary;
+use crate::dictionary::{LexType, WordIdx};
+use crate::errors::{Result, VibratoError};
+
+/// 照合のために文字を正規化します。
+///
+/// 全角のASCII文字（U+FF01〜U+FF5E）を対応する半角文字に変換し、
+/// ASCIIの大文字を小文字に変換します。それ以外の文字はそのまま返します。
+#[inline(always)]
+pub(crate) fn fold_char(c: char) -> char {
+    let c = match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(u32::from(c) - 0xFEE0).unwrap_or(c),
+        _ => c,
+    };
+    c.to_ascii_lowercase()
+}
+
+/// トライのノード
+#[derive(Default)]
+struct Node {
+    /// 子ノード（文字順にソート済み）
+    children: Vec<(char, u32)>,
+    /// このノードで終わる単語の番号
+    entries: Vec<u32>,
+}
+
+/// 1つの辞書種別の正規化された表層形のトライ
+#[derive(Default)]
+struct FoldedTrie {
+    nodes: Vec<Node>,
+    /// 単語のインデックス
+    words: Vec<WordIdx>,
+    /// 正規化前の表層形
+    surfaces: Vec<Box<[char]>>,
+}
+
+impl FoldedTrie {
+    /// 単語を追加します。
+    fn push(&mut self, word_idx: WordIdx, surface: &str) {
+        if self.nodes.is_empty() {
+            self.nodes.push(Node::default());
+        }
+        let mut node_id = 0;
+        for c in surface.chars().map(fold_char) {
+            let children = &self.nodes[node_id].children;
+            node_id = match children.binary_search_by_key(&c, |&(k, _)| k) {
+                Ok(i) => children[i].1 as usize,
+                Err(i) => {
+                    let child_id = self.nodes.len();
+                    self.nodes[node_id].children.insert(i, (c, child_id as u32));
+                    self.nodes.push(Node::default());
+                    child_id
+                }
+            };
+        }
+        self.nodes[node_id].entries.push(self.words.len() as u32);
+        self.words.push(word_idx);
+        self.surfaces.push(surface.chars().collect());
+    }
+
+    /// 正規化した入力の接頭辞と一致する単語を列挙します。
+    fn for_each_match<F>(&self, input: &[char], mut f: F)
+    where
+        F: FnMut(WordIdx, usize),
+    {
+        let mut node_id = 0;
+        for (i, &c) in input.iter().enumerate() {
+            let Some(node) = self.nodes.get(node_id) else {
+                return;
+            };
+            let c = fold_char(c);
+            node_id = match node.children.binary_search_by_key(&c, |&(k, _)| k) {
+                Ok(j) => node.children[j].1 as usize,
+                Err(_) => return,
+            };
+            let end_char = i + 1;
+            for &entry in &self.nodes[node_id].entries {
+                let entry = entry as usize;
+                // Words with the same surface as the input are found by the exact lookup.
+                if *self.surfaces[entry] != input[..end_char] {
+                    f(self.words[entry], end_char);
+                }
+            }
+        }
+    }
+}
+
+/// 幅と大文字・小文字を区別しない照合のための索引
+///
+/// ASCIIの英数字・記号、または全角のASCII文字を含む表層形の単語のみを保持します。
+pub(crate) struct FoldedIndex {
+    system: FoldedTrie,
+    user: FoldedTrie,
+}
+
+impl FoldedIndex {
+    /// 辞書の表層形から新しいインスタンスを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `dict` - 辞書
+    /// * `num_system_words` - システム辞書の単語数
+    /// * `num_user_words` - ユーザー辞書の単語数
+    ///
+    /// # エラー
+    ///
+    /// 辞書が表層形を持たない場合、[`VibratoError`]が返されます。
+    pub fn new(dict: &Dictionary, num_system_words: usize, num_user_words: usize) -> Result<Self> {
+        let mut system = FoldedTrie::default();
+        let mut user = FoldedTrie::default();
+        let words = (0..num_system_words)
+            .map(|i| WordIdx::new(LexType::System, i as u32))
+            .chain((0..num_user_words).map(|i| WordIdx::new(LexType::User, i as u32)));
+        for word_idx in words {
+            let surface = dict.word_surface(word_idx).ok_or_else(|| {
+                VibratoError::invalid_state(
+                    "The dictionary has no surface forms.",
+                    "Rebuild the dictionary with the current version.",
+                )
+            })?;
+            if !surface.chars().any(|c| fold_char(c).is_ascii_graphic()) {
+                continue;
+            }
+            match word_idx.lex_type {
+                LexType::User => user.push(word_idx, surface),
+                _ => system.push(word_idx, surface),
+            }
+        }
+        Ok(Self { system, user })
+    }
+
+    /// 入力の接頭辞と正規化後に一致するシステム辞書の単語を列挙します。
+    ///
+    /// 入力と表層形が完全に一致する単語は通常の辞書引きで得られるため、列挙しません。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - 入力文字列
+    /// * `f` - 単語のインデックスと入力上の終了位置（文字単位）を受け取るコールバック
+    #[inline(always)]
+    pub fn for_each_system_match<F>(&self, input: &[char], f: F)
+    where
+        F: FnMut(WordIdx, usize),
+    {
+        self.system.for_each_match(input, f);
+    }
+
+    /// 入力の接頭辞と正規化後に一致するユーザー辞書の単語を列挙します。
+    ///
+    /// [`for_each_system_match()`](Self::for_each_system_match)を参照してください。
+    #[inline(always)]
+    pub fn for_each_user_match<F>(&self, input: &[char], f: F)
+    where
+        F: FnMut(WordIdx, usize),
+    {
+        self.user.for_each_match(input, f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(trie: &FoldedTrie, input: &str) -> Vec<(u32, usize)> {
+        let input: Vec<_> = input.chars().collect();
+        let mut results = vec![];
+        trie.for_each_match(&input, |word_idx, end_char| {
+            results.push((word_idx.word_id, end_char));
+        });
+        results
+    }
+
+    #[test]
+    fn test_fold_char() {
+        assert_eq!(fold_char('Ａ'), 'a');
+        assert_eq!(fold_char('ｚ'), 'z');
+        assert_eq!(fold_char('０'), '0');
+        assert_eq!(fold_char('！'), '!');
+        assert_eq!(fold_char('B'), 'b');
+        assert_eq!(fold_char('あ'), 'あ');
+        assert_eq!(fold_char('　'), '　');
+    }
+
+    #[test]
+    fn test_for_each_match() {
+        let mut trie = FoldedTrie::default();
+        trie.push(WordIdx::new(LexType::System, 0), "ABC");
+        trie.push(WordIdx::new(LexType::System, 1), "ab");
+        trie.push(WordIdx::new(LexType::System, 2), "Ｘ線");
+
+        assert_eq!(matches(&trie, "ａｂｃです"), vec![(1, 2), (0, 3)]);
+        // Exact matches are left to the lexicon.
+        assert_eq!(matches(&trie, "ABC"), vec![(1, 2)]);
+        assert_eq!(matches(&trie, "ab"), vec![]);
+        assert_eq!(matches(&trie, "x線"), vec![(2, 2)]);
+        assert_eq!(matches(&trie, "線"), vec![]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let trie = FoldedTrie::default();
+        assert_eq!(matches(&trie, "abc"), vec![]);
+    }
+}
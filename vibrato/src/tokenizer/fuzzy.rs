@@ -0,0 +1,167 @@
+//! ユーザー辞書の近似照合
+//!
+//! このモジュールは、ユーザー辞書の表層形に対して編集距離1以内の照合を行う
+//! [`FuzzyMatcher`]を提供します。表層形から構築したトライ上でレーベンシュタイン・
+//! オートマトンを模擬し、入力文の各位置から近似的に一致する単語を列挙します。
+
+/// 編集距離1以内の照合のみを扱うため、これ以上の距離は区別しません。
+const MAX_DISTANCE: u8 = 1;
+
+/// トライのノード
+#[derive(Default)]
+struct Node {
+    /// 子ノード（文字順にソート済み）
+    children: Vec<(char, u32)>,
+    /// このノードで終わる単語のID
+    word_ids: Vec<u32>,
+}
+
+/// ユーザー辞書の表層形に対する近似照合器
+pub(crate) struct FuzzyMatcher {
+    nodes: Vec<Node>,
+    max_len: usize,
+    penalty: i16,
+}
+
+impl FuzzyMatcher {
+    /// 表層形のイテレータから新しいインスタンスを作成します。
+    ///
+    /// `min_len`文字未満の表層形は近似照合の対象外となります。
+    ///
+    /// # 引数
+    ///
+    /// * `surfaces` - 単語IDの順に並んだ表層形
+    /// * `min_len` - 近似照合の対象とする表層形の最小文字数
+    /// * `penalty` - 近似照合された単語のコストに加算するペナルティ
+    pub fn new<I, S>(surfaces: I, min_len: usize, penalty: i16) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut nodes = vec![Node::default()];
+        let mut max_len = 0;
+        for (word_id, surface) in surfaces.into_iter().enumerate() {
+            let surface = surface.as_ref();
+            let len = surface.chars().count();
+            if len < min_len.max(1) {
+                continue;
+            }
+            max_len = max_len.max(len);
+            let mut node_id = 0;
+            for c in surface.chars() {
+                let children = &nodes[node_id].children;
+                node_id = match children.binary_search_by_key(&c, |&(k, _)| k) {
+                    Ok(i) => children[i].1 as usize,
+                    Err(i) => {
+                        let child_id = nodes.len();
+                        nodes[node_id].children.insert(i, (c, child_id as u32));
+                        nodes.push(Node::default());
+                        child_id
+                    }
+                };
+            }
+            nodes[node_id].word_ids.push(word_id as u32);
+        }
+        Self {
+            nodes,
+            max_len,
+            penalty,
+        }
+    }
+
+    /// 近似照合された単語のコストに加算するペナルティを取得します。
+    #[inline(always)]
+    pub const fn penalty(&self) -> i16 {
+        self.penalty
+    }
+
+    /// 入力の接頭辞と編集距離がちょうど1である単語を列挙します。
+    ///
+    /// 完全一致する単語は通常の辞書引きで得られるため、ここでは列挙しません。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - 入力文字列
+    /// * `f` - 単語IDと入力上の終了位置（文字単位）を受け取るコールバック
+    pub fn for_each_match<F>(&self, input: &[char], mut f: F)
+    where
+        F: FnMut(u32, usize),
+    {
+        if self.max_len == 0 || input.is_empty() {
+            return;
+        }
+        // Only prefixes up to one character longer than the longest surface can be
+        // within distance 1.
+        let input = &input[..input.len().min(self.max_len + usize::from(MAX_DISTANCE))];
+        let width = input.len() + 1;
+        let mut rows = vec![0u8; width * (self.max_len + 1)];
+        for (j, d) in rows[..width].iter_mut().enumerate() {
+            *d = (j as u8).min(MAX_DISTANCE + 1);
+        }
+        self.search(0, 0, input, &mut rows, &mut f);
+    }
+
+    /// DP行を更新しながらトライを深さ優先で探索します。
+    fn search<F>(&self, node_id: usize, depth: usize, input: &[char], rows: &mut [u8], f: &mut F)
+    where
+        F: FnMut(u32, usize),
+    {
+        let width = input.len() + 1;
+        let node = &self.nodes[node_id];
+        if !node.word_ids.is_empty() {
+            let row = &rows[depth * width..(depth + 1) * width];
+            for (end_char, &d) in row.iter().enumerate().skip(1) {
+                if d == MAX_DISTANCE {
+                    for &word_id in &node.word_ids {
+                        f(word_id, end_char);
+                    }
+                }
+            }
+        }
+        for &(c, child_id) in &node.children {
+            let (prev, cur) = rows.split_at_mut((depth + 1) * width);
+            let prev = &prev[depth * width..];
+            let cur = &mut cur[..width];
+            cur[0] = (depth + 1).min(usize::from(MAX_DISTANCE) + 1) as u8;
+            let mut min = cur[0];
+            for j in 1..width {
+                let sub = prev[j - 1] + u8::from(input[j - 1] != c);
+                let d = sub.min(prev[j] + 1).min(cur[j - 1] + 1).min(MAX_DISTANCE + 1);
+                cur[j] = d;
+                min = min.min(d);
+            }
+            if min <= MAX_DISTANCE {
+                self.search(child_id as usize, depth + 1, input, rows, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(matcher: &FuzzyMatcher, input: &str) -> Vec<(u32, usize)> {
+        let input: Vec<_> = input.chars().collect();
+        let mut results = vec![];
+        matcher.for_each_match(&input, |word_id, end_char| results.push((word_id, end_char)));
+        results.sort_unstable();
+        results
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let matcher = FuzzyMatcher::new(["形態素解析", "解析", "自然言語"], 3, 0);
+
+        // substitution
+        assert_eq!(matches(&matcher, "形熊素解析器"), vec![(0, 5)]);
+        // deletion
+        assert_eq!(matches(&matcher, "形態解析です"), vec![(0, 4)]);
+        // insertion
+        assert_eq!(matches(&matcher, "自然の言語"), vec![(2, 5)]);
+        // exact matches are not reported
+        assert_eq!(matches(&matcher, "自然言語"), vec![(2, 3)]);
+        // "解析" is shorter than min_len
+        assert_eq!(matches(&matcher, "解折"), vec![]);
+    }
+}
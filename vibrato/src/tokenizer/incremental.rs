@@ -0,0 +1,295 @@
+//! 入力文への追記に対するラティスの再利用
+//!
+//! かな漢字変換や入力中の逐次解析では、1文字入力するたびに文全体を解析し直すことに
+//! なります。このモジュールは、入力文の末尾に文字列が追記された場合に、追記前の
+//! 入力文に対して構築したラティスのうち影響を受けない部分を残し、末尾側のみを
+//! 構築し直す機能を提供します。
+//!
+//! ある開始位置から始まるノードの集合は、次のいずれかの場合にのみ追記によって変化します。
+//!
+//! - 辞書の単語の一致が追記前の文末を越えて伸びる場合。これは新しい終了位置への
+//!   ノードの追加のみで、既存のノードは変化しません。ただし、一致の有無によって
+//!   未知語の生成やシステム辞書の照合が抑制される場合は除きます。
+//! - 未知語のグループ化や長さ指定による生成が追記前の文末に達している場合
+//! - 追記前の文末に空白が続いている場合
+//!
+//! 最初に変化する開始位置より前のノードは残し、そこから先のみを通常どおり構築し直します。
+
+use crate::Dictionary;
+use crate::dictionary::connector::{ArchivedConnectorWrapper, ConnectorCost, ConnectorWrapper};
+use crate::dictionary::{ArchivedDictionary, DictionaryInner, DictionaryInnerRef};
+use crate::sentence::Sentence;
+use crate::tokenizer::edge_scorer::{EdgeCost, ScoredConnector};
+use crate::tokenizer::lattice::Lattice;
+use crate::tokenizer::{LatticeCursor, Tokenizer};
+
+macro_rules! extend_prefix_logic {
+    (
+        $self:expr,
+        $sent:expr,
+        $lattice:expr,
+        $prefix_len:expr,
+        $connector:expr,
+        $dict:expr,
+    ) => {{
+        let prefix_len = $prefix_len;
+        // Unknown words reaching this position may change because the grapheme cluster at the
+        // end of the previous input may be extended by the suffix.
+        let mut tail = prefix_len;
+        while tail > 0 && $sent.align_end(tail) > prefix_len {
+            tail -= 1;
+        }
+
+        let mut start_node = 0;
+        let mut start_word = 0;
+        let mut restart = prefix_len;
+
+        while start_word < prefix_len {
+            if !$lattice.has_previous_node(start_node) {
+                start_word += 1;
+                start_node = start_word;
+                continue;
+            }
+
+            if let Some(space_cateset) = $self.space_cateset {
+                let is_space = ($sent.char_info(start_node).cate_idset() & space_cateset) != 0;
+                if is_space {
+                    // The previous input ends with spaces that the suffix follows.
+                    if start_node + $sent.groupable(start_node) >= prefix_len {
+                        restart = start_node;
+                        break;
+                    }
+                    start_word += $sent.groupable(start_node);
+                }
+            }
+
+            let suffix = &$sent.chars()[start_word..];
+            let is_new = |end_char: usize| prefix_len < start_word + end_char;
+
+            let (mut old_user, mut new_user) = (false, false);
+            for (_, user_lexicon) in $dict.user_lexicons() {
+                for m in user_lexicon.common_prefix_iterator(suffix) {
+                    if is_new(m.end_char) {
+                        new_user = true;
+                    } else {
+                        old_user = true;
+                    }
+                }
+            }
+            let old_suppressed = $self.strict_user_lexicon && old_user;
+            let new_suppressed = $self.strict_user_lexicon && (old_user || new_user);
+
+            let (mut old_system, mut new_system) = (false, false);
+            if !new_suppressed {
+                for m in $dict.system_lexicon().common_prefix_iterator(suffix) {
+                    if is_new(m.end_char) {
+                        new_system = true;
+                    } else {
+                        old_system = true;
+                    }
+                }
+            }
+
+            let cinfo = $sent.char_info(start_word);
+            let old_matched = old_user || old_system;
+            let new_matched = old_matched || new_user || new_system;
+            let old_unk = !old_suppressed && (!old_matched || cinfo.invoke());
+            let new_unk = !new_suppressed && (!new_matched || cinfo.invoke());
+            let group_len = if cinfo.group() { $sent.groupable(start_word) } else { 0 };
+            let unk_len = group_len.max(usize::from(cinfo.length())).max(1);
+            let unk_reaches_end = tail <= start_word + unk_len;
+
+            if old_suppressed != new_suppressed
+                || old_unk != new_unk
+                || (old_unk && unk_reaches_end)
+            {
+                restart = start_node;
+                break;
+            }
+
+            // Only words longer than the previous input are added.
+            if new_user {
+                for (offset, user_lexicon) in $dict.user_lexicons() {
+                    for mut m in user_lexicon.common_prefix_iterator(suffix) {
+                        if is_new(m.end_char) {
+                            m.word_idx.word_id += offset;
+                            m.word_param.word_cost =
+                                m.word_param.word_cost.saturating_sub($self.user_cost_bonus);
+                            $lattice.insert_node(
+                                start_node,
+                                start_word,
+                                start_word + m.end_char,
+                                m.word_idx,
+                                m.word_param,
+                                $connector,
+                            );
+                        }
+                    }
+                }
+            }
+            if new_system {
+                for m in $dict.system_lexicon().common_prefix_iterator(suffix) {
+                    if is_new(m.end_char) {
+                        $lattice.insert_node(
+                            start_node,
+                            start_word,
+                            start_word + m.end_char,
+                            m.word_idx,
+                            m.word_param,
+                            $connector,
+                        );
+                    }
+                }
+            }
+
+            start_word += 1;
+            start_node = start_word;
+        }
+
+        restart
+    }};
+}
+
+impl Tokenizer {
+    /// 末尾に文字列が追記された入力文に合わせて、構築済みのラティスを更新します。
+    ///
+    /// 追記の影響を受けないノードを残し、影響を受ける開始位置以降のみを構築し直します。
+    /// 結果は[`Tokenizer::build_lattice()`]で構築し直した場合と同じになります。
+    /// ユーザー辞書の近似照合、幅と大文字・小文字を区別しない照合、アプリケーション定義の
+    /// 未知語、ラティスへ候補を追加する拡張を使用している場合は、影響範囲を判定できないため、
+    /// ラティス全体を構築し直します。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 追記後の入力文
+    /// * `lattice` - 追記前の入力文に対して構築済みのラティス
+    /// * `prefix_len` - 追記前の入力文の文字数
+    pub(crate) fn extend_lattice_for_append(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        prefix_len: usize,
+    ) {
+        if self.fuzzy_user.is_some()
+            || self.folds_latin()
+            || self.unk_providers.is_some()
+            || !self.augmenters.is_empty()
+            || prefix_len == 0
+            || sent.len_char() <= prefix_len
+            || lattice.len_char() != prefix_len
+        {
+            self.build_lattice(sent, lattice);
+            return;
+        }
+        lattice.grow(sent.len_char());
+        match &*self.dict {
+            Dictionary::Archived(archived_dict) => match archived_dict.connector() {
+                ArchivedConnectorWrapper::Matrix(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+                ArchivedConnectorWrapper::Raw(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+                ArchivedConnectorWrapper::Dual(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+            },
+            Dictionary::Owned{ dict, .. } => match dict.connector() {
+                ConnectorWrapper::Matrix(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+                ConnectorWrapper::Raw(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+                ConnectorWrapper::Dual(c) => self.extend_lattice_for_append_inner(sent, lattice, prefix_len, c),
+            },
+        }
+    }
+
+    /// ラティスの更新の内部処理。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 追記後の入力文
+    /// * `lattice` - 追記後の文字数に拡張されたラティス
+    /// * `prefix_len` - 追記前の入力文の文字数
+    /// * `connector` - 接続コスト計算用のコネクタ
+    fn extend_lattice_for_append_inner<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        prefix_len: usize,
+        connector: &C,
+    ) where
+        C: ConnectorCost,
+    {
+        let restart = match self.edge_scorer_ref() {
+            Some(scorer) => {
+                let connector = ScoredConnector::new(connector, scorer, sent);
+                self.extend_prefix(sent, lattice, prefix_len, &connector)
+            }
+            None => self.extend_prefix(sent, lattice, prefix_len, connector),
+        };
+        lattice.remove_nodes_from(restart, prefix_len);
+        let mut cursor = LatticeCursor {
+            start_node: restart,
+            start_word: restart,
+        };
+        self.extend_lattice_with(sent, lattice, &mut cursor, usize::MAX, connector);
+    }
+
+    /// 追記前の範囲から始まる長い単語を追加します。
+    ///
+    /// 辞書の型（アーカイブ版または所有版）に応じて適切な内部メソッドを呼び出します。
+    ///
+    /// # 戻り値
+    ///
+    /// 構築し直す必要がある最初の開始位置
+    fn extend_prefix<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        prefix_len: usize,
+        connector: &C,
+    ) -> usize
+    where
+        C: EdgeCost,
+    {
+        match self.dictionary() {
+            DictionaryInnerRef::Archived(dict) => {
+                self.extend_prefix_archived(sent, lattice, prefix_len, connector, dict)
+            }
+            DictionaryInnerRef::Owned(dict) => {
+                self.extend_prefix_owned(sent, lattice, prefix_len, connector, dict)
+            }
+        }
+    }
+
+    /// アーカイブ版辞書を使用して、追記前の範囲から始まる長い単語を追加します。
+    ///
+    /// # 戻り値
+    ///
+    /// 構築し直す必要がある最初の開始位置
+    fn extend_prefix_archived<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        prefix_len: usize,
+        connector: &C,
+        dict: &ArchivedDictionary,
+    ) -> usize
+    where
+        C: EdgeCost,
+    {
+        extend_prefix_logic!(self, sent, lattice, prefix_len, connector, dict,)
+    }
+
+    /// 所有版辞書を使用して、追記前の範囲から始まる長い単語を追加します。
+    ///
+    /// # 戻り値
+    ///
+    /// 構築し直す必要がある最初の開始位置
+    fn extend_prefix_owned<C>(
+        &self,
+        sent: &Sentence,
+        lattice: &mut Lattice,
+        prefix_len: usize,
+        connector: &C,
+        dict: &DictionaryInner,
+    ) -> usize
+    where
+        C: EdgeCost,
+    {
+        extend_prefix_logic!(self, sent, lattice, prefix_len, connector, dict,)
+    }
+}
@@ -3,17 +3,87 @@
 //! このモジュールは、形態素解析におけるViterbiアルゴリズムのための
 //! ラティス構造を提供します。ラティスはノードとパスから構成され、
 //! 最適なトークン分割を見つけるために使用されます。
-use crate::dictionary::connector::ConnectorCost;
 use crate::dictionary::lexicon::WordParam;
 use crate::dictionary::mapper::ConnIdCounter;
 use crate::dictionary::word_idx::WordIdx;
 use crate::dictionary::LexType;
+use crate::tokenizer::TieBreak;
+use crate::tokenizer::edge_scorer::EdgeCost;
 
 use crate::common::{BOS_EOS_CONNECTION_ID, MAX_SENTENCE_LENGTH};
 
 const MAX_COST: i32 = i32::MAX;
 const INVALID_IDX: u16 = u16::MAX;
 
+/// パスの連結リストの終端を表すインデックス。
+pub const NO_PATH: u32 = u32::MAX;
+
+/// ラティスの構築中に収集される統計情報。
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatticeCounters {
+    /// 挿入されたノード数（BOS/EOSを除く）。
+    pub num_nodes: usize,
+    /// 挿入された未知語のノード数。
+    pub num_unk_words: usize,
+    /// 接続コストの評価回数。
+    pub num_cost_evaluations: usize,
+}
+
+#[cfg(feature = "stats")]
+impl LatticeCounters {
+    /// ノードの挿入を記録します。
+    #[inline(always)]
+    fn record_node(&mut self, lex_type: LexType) {
+        self.num_nodes += 1;
+        if lex_type == LexType::Unknown {
+            self.num_unk_words += 1;
+        }
+    }
+}
+
+/// 同コストの候補間での辞書種別の優先順位（小さいほど優先）を返します。
+#[inline(always)]
+const fn lex_type_rank(lex_type: LexType) -> u8 {
+    match lex_type {
+        LexType::User => 0,
+        LexType::System => 1,
+        LexType::Unknown => 2,
+    }
+}
+
+/// MeCabの辞書引きの順序における辞書種別の順位（小さいほど優先）を返します。
+#[inline(always)]
+const fn mecab_lookup_rank(lex_type: LexType) -> u8 {
+    match lex_type {
+        LexType::System => 0,
+        LexType::User => 1,
+        LexType::Unknown => 2,
+    }
+}
+
+/// 同コストの左側ノードのうち、後から挿入された`new`を現在の最良ノード`cur`より
+/// 優先するかを判定します。
+///
+/// 各モードの意味は[`TieBreak`]を参照してください。
+#[inline(always)]
+fn wins_tie(new: &Node, cur: &Node, tie_break: TieBreak) -> bool {
+    match tie_break {
+        TieBreak::LastWins => true,
+        TieBreak::FirstWins => false,
+        TieBreak::LexTypePriority => {
+            lex_type_rank(new.lex_type) <= lex_type_rank(cur.lex_type)
+        }
+        TieBreak::MecabCompatible => {
+            // Nodes are inserted in ascending order of their starting positions, so a later
+            // start always means a later insertion.
+            new.start_word != cur.start_word
+                || (mecab_lookup_rank(new.lex_type), new.word_id)
+                    < (mecab_lookup_rank(cur.lex_type), cur.word_id)
+        }
+    }
+}
+
 /// ラティス内のノード。
 ///
 /// 各ノードは単語の候補を表し、位置情報、接続ID、最小コストなどを保持します。
@@ -32,25 +102,30 @@ pub struct Node {
     pub left_id: u16,
     /// 右側の接続ID。
     pub right_id: u16,
+    /// ラティスの構築時に使用した単語コスト。
+    ///
+    /// 辞書の単語コストに、ユーザー辞書の語への補正や近似照合などの罰則を加えた値です。
+    pub word_cost: i16,
     /// 最小コストを持つ左側ノードのインデックス。
     pub min_idx: u16,
     /// BOSからこのノードまでの最小コスト。
     pub min_cost: i32,
-    /// 左側から接続するパスの連結リストの先頭へのポインタ。
-    /// パスがない場合はnull。
-    pub lpath: *const Path,
+    /// 左側から接続するパスの連結リストの先頭のインデックス。
+    /// パスがない場合は[`NO_PATH`]。1-best用のラティスでは使用されません。
+    pub lpath: u32,
 }
 
 /// ラティス内の2つのノード間の接続を表します。
 ///
-/// パスは連結リストとして保存され、N-best探索に使用されます。
+/// パスは[`LatticeNBest`]のアリーナ内の連結リストとして保存され、N-best探索に使用されます。
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Path {
-    /// 左側のノードへのポインタ（BOSに近い方）。
-    pub lnode: *const Node,
-    /// 右側のノードから発生する次のパス（連結リスト）。
-    pub lnext: *const Path,
+    /// 左側のノードのインデックス（BOSに近い方）。
+    pub lnode: u32,
+    /// 右側のノードから発生する次のパスのインデックス（連結リスト）。
+    /// 次のパスがない場合は[`NO_PATH`]。
+    pub lnext: u32,
 }
 
 impl Default for Node {
@@ -62,9 +137,10 @@ impl Default for Node {
             start_word: 0,
             left_id: 0,
             right_id: 0,
+            word_cost: 0,
             min_idx: 0,
             min_cost: i32::MAX,
-            lpath: std::ptr::null(),
+            lpath: NO_PATH,
         }
     }
 }
@@ -114,6 +190,9 @@ pub struct Lattice {
     ends: Vec<Vec<Node>>,
     eos: Option<Node>,
     len_char: usize, // needed for avoiding to free ends
+    tie_break: TieBreak,
+    #[cfg(feature = "stats")]
+    counters: LatticeCounters,
 }
 
 impl LatticeKind {
@@ -162,6 +241,61 @@ impl LatticeKind {
             }
         }
     }
+
+    /// 指定された大きさの文をメモリを再確保せずに処理できるように容量を確保します。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn reserve(&mut self, max_chars: usize, avg_edges: usize) {
+        match self {
+            LatticeKind::For1Best(l) => l.reserve(max_chars, avg_edges),
+            LatticeKind::ForNBest(l) => l.reserve(max_chars, avg_edges),
+        }
+    }
+
+    /// 容量を指定された大きさまで縮小します。
+    ///
+    /// 構築済みのノードは保持されるため、容量は使用中の大きさより小さくなりません。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn shrink_to(&mut self, max_chars: usize, avg_edges: usize) {
+        match self {
+            LatticeKind::For1Best(l) => l.shrink_to(max_chars, avg_edges),
+            LatticeKind::ForNBest(l) => l.shrink_to(max_chars, avg_edges),
+        }
+    }
+
+    /// 直前に構築されたラティスの統計情報を返します。
+    #[cfg(feature = "stats")]
+    pub fn counters(&self) -> LatticeCounters {
+        match self {
+            LatticeKind::For1Best(l) => l.counters,
+            LatticeKind::ForNBest(l) => l.counters,
+        }
+    }
+
+    /// 構築済みのラティスのノードを終了位置ごとに複製して返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 終了位置ごとのノードとEOSノードの組。EOSノードが挿入されていない場合は`None`
+    pub fn snapshot(&self) -> Option<(Vec<Vec<Node>>, Node)> {
+        match self {
+            LatticeKind::For1Best(l) => {
+                let eos = l.eos?;
+                Some((l.ends[..=l.len_char].to_vec(), eos))
+            }
+            LatticeKind::ForNBest(l) => {
+                let eos = *l.eos_node()?;
+                Some((l.ends_snapshot(), eos))
+            }
+        }
+    }
 }
 
 impl Lattice {
@@ -174,9 +308,71 @@ impl Lattice {
         Self::reset_vec(&mut self.ends, len_char + 1);
         self.len_char = len_char;
         self.eos = None;
+        #[cfg(feature = "stats")]
+        {
+            self.counters = LatticeCounters::default();
+        }
         self.insert_bos();
     }
 
+    /// 入力文の末尾への追記に合わせて、構築済みのノードを残したままラティスを拡張します。
+    ///
+    /// EOSノードは取り除かれます。
+    ///
+    /// # 引数
+    ///
+    /// * `len_char` - 追記後の文の文字数
+    pub fn grow(&mut self, len_char: usize) {
+        debug_assert!(self.len_char <= len_char);
+        for _ in self.ends.len()..=len_char {
+            self.ends.push(Vec::with_capacity(16));
+        }
+        self.len_char = len_char;
+        self.eos = None;
+        #[cfg(feature = "stats")]
+        {
+            self.counters = LatticeCounters::default();
+        }
+    }
+
+    /// `start_node`以降から始まるノードを、`end_char`までの終了位置から削除します。
+    ///
+    /// 各終了位置のノードは開始位置の昇順に挿入されているため、残るノードの順序と
+    /// 最小コストの左側ノードのインデックスは変わりません。
+    ///
+    /// # 引数
+    ///
+    /// * `start_node` - 削除するノードの開始位置の下限
+    /// * `end_char` - 削除対象とする終了位置の上限
+    pub fn remove_nodes_from(&mut self, start_node: usize, end_char: usize) {
+        for column in &mut self.ends[start_node + 1..=end_char] {
+            let len = column.partition_point(|node| node.start_node < start_node);
+            column.truncate(len);
+        }
+    }
+
+    /// 指定された大きさの文をメモリを再確保せずに処理できるように容量を確保します。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn reserve(&mut self, max_chars: usize, avg_edges: usize) {
+        reserve_columns(&mut self.ends, max_chars + 1, avg_edges);
+    }
+
+    /// 容量を指定された大きさまで縮小します。
+    ///
+    /// 構築済みのノードは保持されるため、容量は使用中の大きさより小さくなりません。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn shrink_to(&mut self, max_chars: usize, avg_edges: usize) {
+        shrink_columns(&mut self.ends, max_chars.max(self.len_char) + 1, avg_edges);
+    }
+
     fn reset_vec<T>(data: &mut Vec<Vec<T>>, new_len: usize) {
         for v in data.iter_mut() {
             v.clear();
@@ -200,6 +396,18 @@ impl Lattice {
         self.len_char
     }
 
+    /// 同コストの候補が複数ある場合の選び方を設定します。
+    ///
+    /// 詳細は[`Tokenizer::tie_break()`](crate::Tokenizer::tie_break)を参照してください。
+    ///
+    /// # 引数
+    ///
+    /// * `tie_break` - 同コストの候補の選び方
+    #[inline(always)]
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+    }
+
     /// BOS（文頭）ノードを挿入します。
     fn insert_bos(&mut self) {
         self.ends[0].push(Node {
@@ -209,9 +417,10 @@ impl Lattice {
             start_word: MAX_SENTENCE_LENGTH,
             left_id: u16::MAX,
             right_id: BOS_EOS_CONNECTION_ID,
+            word_cost: 0,
             min_idx: INVALID_IDX,
             min_cost: 0,
-            lpath: std::ptr::null(),
+            lpath: NO_PATH,
         });
     }
 
@@ -223,21 +432,26 @@ impl Lattice {
     /// * `connector` - 接続コスト計算用のコネクタ
     pub fn insert_eos<C>(&mut self, start_node: usize, connector: &C)
     where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
-        let (min_idx, min_cost) =
-            self.search_min_node(start_node, BOS_EOS_CONNECTION_ID, connector);
-        self.eos = Some(Node {
+        #[cfg(feature = "stats")]
+        {
+            self.counters.num_cost_evaluations += self.ends[start_node].len();
+        }
+        let mut eos = Node {
             word_id: u32::MAX,
             lex_type: LexType::default(),
             start_node,
             start_word: self.len_char(),
             left_id: BOS_EOS_CONNECTION_ID,
             right_id: u16::MAX,
-            min_idx,
-            min_cost,
-            lpath: std::ptr::null(),
-        });
+            word_cost: 0,
+            min_idx: INVALID_IDX,
+            min_cost: MAX_COST,
+            lpath: NO_PATH,
+        };
+        (eos.min_idx, eos.min_cost) = self.search_min_node(&eos, self.len_char(), connector);
+        self.eos = Some(eos);
     }
 
     /// ラティスに新しいノードを挿入します。
@@ -259,39 +473,56 @@ impl Lattice {
         word_param: WordParam,
         connector: &C,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         debug_assert!(start_node <= start_word);
         debug_assert!(start_word < end_word);
-        let (min_idx, min_cost) = self.search_min_node(start_node, word_param.left_id, connector);
-        self.ends[end_word].push(Node {
+        #[cfg(feature = "stats")]
+        {
+            self.counters.num_cost_evaluations += self.ends[start_node].len();
+            self.counters.record_node(word_idx.lex_type);
+        }
+        let mut node = Node {
             word_id: word_idx.word_id,
             lex_type: word_idx.lex_type,
             start_node,
             start_word,
             left_id: word_param.left_id,
             right_id: word_param.right_id,
-            min_idx,
-            min_cost: min_cost + i32::from(word_param.word_cost),
-            lpath: std::ptr::null(),
-        });
+            word_cost: word_param.word_cost,
+            min_idx: INVALID_IDX,
+            min_cost: MAX_COST,
+            lpath: NO_PATH,
+        };
+        let (min_idx, min_cost) = self.search_min_node(&node, end_word, connector);
+        node.min_idx = min_idx;
+        node.min_cost = min_cost + i32::from(word_param.word_cost);
+        self.ends[end_word].push(node);
     }
 
-    fn search_min_node<C>(&self, start_node: usize, left_id: u16, connector: &C) -> (u16, i32)
+    fn search_min_node<C>(&self, right: &Node, end_word: usize, connector: &C) -> (u16, i32)
     where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
+        let start_node = right.start_node;
         debug_assert!(!self.ends[start_node].is_empty());
 
         let mut min_idx = INVALID_IDX;
         let mut min_cost = MAX_COST;
         for (i, left_node) in self.ends[start_node].iter().enumerate() {
             debug_assert!(left_node.is_connected_to_bos());
-            let conn_cost = connector.cost(left_node.right_id, left_id);
+            let conn_cost = connector.edge_cost(left_node, right, end_word);
             let new_cost = left_node.min_cost + conn_cost;
-            // Depending on the order of tie-breaking, the result can be different from MeCab.
-            // Using <= (not <) will produce results identical to MeCab in most case (empirically).
-            if new_cost <= min_cost {
+            // Ties are resolved by wins_tie(). See TieBreak for how each mode relates to MeCab.
+            if new_cost < min_cost
+                || (new_cost == min_cost
+                    && (min_idx == INVALID_IDX
+                        || wins_tie(
+                            left_node,
+                            &self.ends[start_node][usize::from(min_idx)],
+                            self.tie_break,
+                        )))
+            {
                 min_idx = i as u16;
                 min_cost = new_cost;
             }
@@ -354,28 +585,63 @@ impl Lattice {
     }
 }
 
+/// 先頭の`len`個の終了位置について、それぞれ`capacity`個のノードの容量を確保します。
+fn reserve_columns<T>(data: &mut Vec<Vec<T>>, len: usize, capacity: usize) {
+    if data.len() < len {
+        data.resize_with(len, Vec::new);
+    }
+    for v in &mut data[..len] {
+        v.reserve(capacity.saturating_sub(v.len()));
+    }
+}
+
+/// 終了位置の数を`len`個までに減らし、各終了位置の容量を`capacity`個まで縮小します。
+fn shrink_columns<T>(data: &mut Vec<Vec<T>>, len: usize, capacity: usize) {
+    data.truncate(len);
+    data.shrink_to(len);
+    for v in data.iter_mut() {
+        v.shrink_to(capacity);
+    }
+}
+
 /// N-best解用のラティス構造体。
 ///
 /// 複数の候補パスを保持するために、各ノード間のすべての接続を保存します。
+/// ノードと接続はそれぞれ`Vec`によるアリーナに格納され、`u32`のインデックスで
+/// 参照されます。生ポインタを保持しないため、アクセスはすべて境界チェックされます。
 /// この実装はsudachi.rsにインスパイアされています。
+///
+/// 接続の数はノード数と各位置のノード数の積に比例するため、長い入力や候補の多い入力では
+/// アリーナが大きくなります。[`set_budget()`](Self::set_budget)で上限を設定すると、
+/// 上限を超えた時点でノードの挿入を打ち切ります。
 #[derive(Default)]
 pub struct LatticeNBest {
-    arena: bumpalo::Bump,
-    ends: Vec<Vec<*mut Node>>,
-    eos: *mut Node,
+    nodes: Vec<Node>,
+    paths: Vec<Path>,
+    ends: Vec<Vec<u32>>,
+    eos: Option<u32>,
     len_char: usize, // needed for avoiding to free ends
+    tie_break: TieBreak,
+    // Upper bound of the arena size in bytes
+    budget: Option<usize>,
+    // Whether only the connection to the best left node is stored for each node
+    best_only: bool,
+    budget_exceeded: bool,
+    #[cfg(feature = "stats")]
+    counters: LatticeCounters,
 }
 
 impl LatticeNBest {
     /// ラティスをリセットし、新しい文の処理を準備します。
     ///
-    /// アリーナアロケータもリセットされます。
+    /// ノードと接続のアリーナもリセットされます。
     ///
     /// # 引数
     ///
     /// * `len_char` - 新しい文の文字数
     pub fn reset(&mut self, len_char: usize) {
-        self.arena.reset();
+        self.nodes.clear();
+        self.paths.clear();
 
         let new_len = len_char + 1;
 
@@ -391,11 +657,174 @@ impl LatticeNBest {
             }
         }
 
-        self.eos = std::ptr::null_mut();
+        self.eos = None;
         self.len_char = len_char;
+        self.budget_exceeded = false;
+        #[cfg(feature = "stats")]
+        {
+            self.counters = LatticeCounters::default();
+        }
         self.insert_bos();
     }
 
+    /// ラティスの大きさの上限をバイト単位で設定します。
+    ///
+    /// 大きさは[`used_bytes()`](Self::used_bytes)で数えます。
+    /// 上限を超えると以降のノードは挿入されず、[`budget_exceeded()`](Self::budget_exceeded)
+    /// が`true`を返します。最良接続のみを保存する場合は上限は適用されません。
+    ///
+    /// # 引数
+    ///
+    /// * `budget` - 上限のバイト数。`None`の場合は上限を設けません。
+    #[inline(always)]
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// 各ノードについて最良の左側のノードとの接続のみを保存するかを設定します。
+    ///
+    /// 有効にすると、接続の数がノード数以下に抑えられる代わりに、N-best探索では
+    /// 1-best解のみが得られます。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - 最良接続のみを保存する場合は`true`
+    #[inline(always)]
+    pub fn set_best_only(&mut self, yes: bool) {
+        self.best_only = yes;
+    }
+
+    /// 直前の構築でラティスの大きさが上限を超えたかを返します。
+    ///
+    /// 上限を超えた場合、ラティスは構築途中の状態であり、EOSノードは挿入されていません。
+    #[inline(always)]
+    pub const fn budget_exceeded(&self) -> bool {
+        self.budget_exceeded
+    }
+
+    /// 構築済みのラティスが使用しているおおよそのバイト数を返します。
+    ///
+    /// ノードと接続のアリーナに加えて、終了位置ごとのノードのリストを数えます。
+    /// 各ノードのインデックスは、いずれか1つの終了位置のリストに格納されます。
+    #[inline(always)]
+    pub fn used_bytes(&self) -> usize {
+        self.nodes.len() * (std::mem::size_of::<Node>() + std::mem::size_of::<u32>())
+            + self.paths.len() * std::mem::size_of::<Path>()
+            + (self.len_char + 1) * std::mem::size_of::<Vec<u32>>()
+    }
+
+    /// ラティスの大きさが上限を超えているかを判定し、超えている場合は記録します。
+    #[inline(always)]
+    fn check_budget(&mut self) -> bool {
+        if !self.budget_exceeded && !self.best_only {
+            if let Some(budget) = self.budget {
+                self.budget_exceeded = self.used_bytes() > budget;
+            }
+        }
+        self.budget_exceeded
+    }
+
+    /// 指定された大きさの文をメモリを再確保せずに処理できるように容量を確保します。
+    ///
+    /// 接続のアリーナは、各ノードが平均して`avg_edges`個の左側のノードと接続されるものとして
+    /// 確保します。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn reserve(&mut self, max_chars: usize, avg_edges: usize) {
+        reserve_columns(&mut self.ends, max_chars + 1, avg_edges);
+        let num_nodes = (max_chars + 2).saturating_mul(avg_edges);
+        self.nodes.reserve(num_nodes.saturating_sub(self.nodes.len()));
+        let num_paths = num_nodes.saturating_mul(avg_edges);
+        self.paths.reserve(num_paths.saturating_sub(self.paths.len()));
+    }
+
+    /// 容量を指定された大きさまで縮小します。
+    ///
+    /// 構築済みのノードと接続は保持されるため、容量は使用中の大きさより小さくなりません。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 文の文字数
+    /// * `avg_edges` - 1つの終了位置あたりのノード数
+    pub fn shrink_to(&mut self, max_chars: usize, avg_edges: usize) {
+        shrink_columns(&mut self.ends, max_chars.max(self.len_char) + 1, avg_edges);
+        let num_nodes = (max_chars + 2).saturating_mul(avg_edges);
+        self.nodes.shrink_to(num_nodes);
+        self.paths.shrink_to(num_nodes.saturating_mul(avg_edges));
+    }
+
+    /// ノードをアリーナに追加し、そのインデックスを返します。
+    #[inline(always)]
+    fn alloc_node(&mut self, node: Node) -> u32 {
+        let idx = u32::try_from(self.nodes.len()).expect("too many nodes in the lattice");
+        self.nodes.push(node);
+        idx
+    }
+
+    /// 接続をアリーナに追加し、そのインデックスを返します。
+    #[inline(always)]
+    fn alloc_path(&mut self, path: Path) -> u32 {
+        let idx = u32::try_from(self.paths.len()).expect("too many paths in the lattice");
+        self.paths.push(path);
+        idx
+    }
+
+    /// インデックスからノードを取得します。
+    ///
+    /// # パニック
+    ///
+    /// `idx`がこのラティスのノードを指していない場合
+    #[inline(always)]
+    pub fn node(&self, idx: u32) -> &Node {
+        &self.nodes[idx as usize]
+    }
+
+    /// インデックスからノードを取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// `idx`が範囲外の場合は`None`
+    #[inline(always)]
+    pub fn get_node(&self, idx: u32) -> Option<&Node> {
+        self.nodes.get(idx as usize)
+    }
+
+    /// ノードに左側から接続するノードのインデックスを列挙します。
+    ///
+    /// # 引数
+    ///
+    /// * `node` - このラティスのノード
+    pub fn left_nodes<'a>(&'a self, node: &Node) -> impl Iterator<Item = u32> + 'a {
+        let mut cur = node.lpath;
+        std::iter::from_fn(move || {
+            if cur == NO_PATH {
+                return None;
+            }
+            let path = &self.paths[cur as usize];
+            cur = path.lnext;
+            Some(path.lnode)
+        })
+    }
+
+    /// BOSノードを取得します。
+    #[inline(always)]
+    pub fn bos_node(&self) -> &Node {
+        self.node(self.ends[0][0])
+    }
+
+    /// EOSノードのインデックスを取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// EOSノードが存在する場合は`Some(idx)`、存在しない場合は`None`
+    #[inline(always)]
+    pub const fn eos_idx(&self) -> Option<u32> {
+        self.eos
+    }
+
     /// EOSノードを取得します。
     ///
     /// # 戻り値
@@ -403,7 +832,7 @@ impl LatticeNBest {
     /// EOSノードが存在する場合は`Some(&Node)`、存在しない場合は`None`
     #[inline(always)]
     pub fn eos_node(&self) -> Option<&Node> {
-        unsafe { self.eos.as_ref() }
+        self.eos.map(|idx| self.node(idx))
     }
 
     /// 設定された文の文字数を返します。
@@ -416,20 +845,33 @@ impl LatticeNBest {
         self.len_char
     }
 
+    /// 同コストの候補が複数ある場合の選び方を設定します。
+    ///
+    /// 詳細は[`Tokenizer::tie_break()`](crate::Tokenizer::tie_break)を参照してください。
+    ///
+    /// # 引数
+    ///
+    /// * `tie_break` - 同コストの候補の選び方
+    #[inline(always)]
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+    }
+
     /// BOS（文頭）ノードを挿入します。
     fn insert_bos(&mut self) {
-        let bos_node = self.arena.alloc(Node {
+        let bos_idx = self.alloc_node(Node {
             word_id: u32::MAX,
             lex_type: LexType::default(),
             start_node: MAX_SENTENCE_LENGTH,
             start_word: MAX_SENTENCE_LENGTH,
             left_id: u16::MAX,
             right_id: BOS_EOS_CONNECTION_ID,
+            word_cost: 0,
             min_idx: INVALID_IDX,
             min_cost: 0,
-            lpath: std::ptr::null(),
+            lpath: NO_PATH,
         });
-        self.ends[0].push(bos_node);
+        self.ends[0].push(bos_idx);
     }
 
     /// EOS（文末）ノードを挿入し、すべての可能な接続を保存します。
@@ -438,8 +880,11 @@ impl LatticeNBest {
     ///
     /// * `start_node` - EOSノードの開始位置
     /// * `connector` - 接続コスト計算用のコネクタ
-    pub fn insert_eos<C: ConnectorCost>(&mut self, start_node: usize, connector: &C) {
-        let eos_node = self.arena.alloc(Node {
+    pub fn insert_eos<C: EdgeCost>(&mut self, start_node: usize, connector: &C) {
+        if self.check_budget() {
+            return;
+        }
+        let mut eos_node = Node {
             word_id: u32::MAX,
             lex_type: LexType::default(),
             start_node,
@@ -447,25 +892,41 @@ impl LatticeNBest {
             left_id: BOS_EOS_CONNECTION_ID,
             right_id: u16::MAX,
             ..Default::default()
-        });
+        };
 
         let mut min_cost = MAX_COST;
-        eos_node.lpath = std::ptr::null();
+        #[cfg(feature = "stats")]
+        {
+            self.counters.num_cost_evaluations += self.ends[start_node].len();
+        }
 
-        for (i, &lnode_ptr) in self.ends[start_node].iter().enumerate() {
-            let lnode = unsafe { &*lnode_ptr };
-            let conn_cost = connector.cost(lnode.right_id, BOS_EOS_CONNECTION_ID);
+        for i in 0..self.ends[start_node].len() {
+            let lnode_idx = self.ends[start_node][i];
+            let lnode = &self.nodes[lnode_idx as usize];
+            let conn_cost = connector.edge_cost(lnode, &eos_node, self.len_char);
             let new_cost = lnode.min_cost + conn_cost;
 
-            if new_cost <= min_cost {
+            if new_cost < min_cost
+                || (new_cost == min_cost
+                    && wins_tie(
+                        lnode,
+                        self.node(self.ends[start_node][usize::from(eos_node.min_idx)]),
+                        self.tie_break,
+                    ))
+            {
                 min_cost = new_cost;
                 eos_node.min_idx = i as u16;
             }
-            let new_path = self.arena.alloc(Path { lnode: lnode_ptr, lnext: eos_node.lpath });
-            eos_node.lpath = new_path;
+            if !self.best_only {
+                eos_node.lpath = self.alloc_path(Path { lnode: lnode_idx, lnext: eos_node.lpath });
+            }
+        }
+        if self.best_only && !self.ends[start_node].is_empty() {
+            let lnode = self.ends[start_node][usize::from(eos_node.min_idx)];
+            eos_node.lpath = self.alloc_path(Path { lnode, lnext: NO_PATH });
         }
         eos_node.min_cost = min_cost;
-        self.eos = eos_node;
+        self.eos = Some(self.alloc_node(eos_node));
     }
 
     /// ラティスに新しいノードを挿入し、すべての可能な接続パスを保存します。
@@ -487,53 +948,76 @@ impl LatticeNBest {
         word_param: WordParam,
         connector: &C,
     ) where
-        C: ConnectorCost,
+        C: EdgeCost,
     {
         debug_assert!(start_node_pos <= start_word);
         debug_assert!(start_word < end_word);
 
-        let rnode_ptr = self.arena.alloc(Node {
+        if self.check_budget() {
+            return;
+        }
+
+        let mut rnode = Node {
             word_id: word_idx.word_id,
             lex_type: word_idx.lex_type,
             start_node: start_node_pos,
             start_word,
             left_id: word_param.left_id,
             right_id: word_param.right_id,
+            word_cost: word_param.word_cost,
             ..Default::default()
-        });
-        let rnode = &mut *rnode_ptr;
+        };
 
         let mut min_cost = MAX_COST;
         let mut min_idx = INVALID_IDX;
+        let num_paths = self.paths.len();
 
-        rnode.lpath = std::ptr::null();
-
-        for (i, &lnode_ptr) in self.ends[start_node_pos].iter().enumerate() {
-            let lnode = unsafe { &*lnode_ptr };
+        for i in 0..self.ends[start_node_pos].len() {
+            let lnode_idx = self.ends[start_node_pos][i];
+            let lnode = &self.nodes[lnode_idx as usize];
             if !lnode.is_connected_to_bos() {
                 continue;
             }
 
-            let conn_cost = connector.cost(lnode.right_id, rnode.left_id);
+            #[cfg(feature = "stats")]
+            {
+                self.counters.num_cost_evaluations += 1;
+            }
+            let conn_cost = connector.edge_cost(lnode, &rnode, end_word);
             let new_cost = lnode.min_cost.saturating_add(conn_cost);
-            // Depending on the order of tie-breaking, the result can be different from MeCab.
-            // Using <= (not <) will produce results identical to MeCab in most case (empirically).
-            if new_cost <= min_cost {
+            // Ties are resolved by wins_tie(). See TieBreak for how each mode relates to MeCab.
+            if new_cost < min_cost
+                || (new_cost == min_cost
+                    && (min_idx == INVALID_IDX
+                        || wins_tie(
+                            lnode,
+                            self.node(self.ends[start_node_pos][usize::from(min_idx)]),
+                            self.tie_break,
+                        )))
+            {
                 min_cost = new_cost;
                 min_idx = i as u16;
             }
 
-            let new_path = self.arena.alloc(Path {
-                lnode: lnode_ptr,
-                lnext: rnode.lpath,
-            });
-            rnode.lpath = new_path;
+            if !self.best_only {
+                rnode.lpath = self.alloc_path(Path { lnode: lnode_idx, lnext: rnode.lpath });
+            }
         }
 
         if min_idx != INVALID_IDX {
+            if self.best_only {
+                let lnode = self.ends[start_node_pos][usize::from(min_idx)];
+                rnode.lpath = self.alloc_path(Path { lnode, lnext: NO_PATH });
+            }
+            #[cfg(feature = "stats")]
+            self.counters.record_node(word_idx.lex_type);
             rnode.min_idx = min_idx;
             rnode.min_cost = min_cost.saturating_add(i32::from(word_param.word_cost));
-            self.ends[end_word].push(rnode_ptr);
+            let rnode_idx = self.alloc_node(rnode);
+            self.ends[end_word].push(rnode_idx);
+        } else {
+            // The node is unreachable, so the connections allocated for it are discarded.
+            self.paths.truncate(num_paths);
         }
     }
 
@@ -551,6 +1035,14 @@ impl LatticeNBest {
         self.ends.get(i).map(|d| !d.is_empty()).unwrap_or(false)
     }
 
+    /// 終了位置ごとのノードを複製して返します。
+    pub(crate) fn ends_snapshot(&self) -> Vec<Vec<Node>> {
+        self.ends[..=self.len_char]
+            .iter()
+            .map(|nodes| nodes.iter().map(|&idx| *self.node(idx)).collect())
+            .collect()
+    }
+
     /// 接続IDの出現回数をカウンタに追加します。
     ///
     /// # 引数
@@ -558,22 +1050,21 @@ impl LatticeNBest {
     /// * `counter` - 接続IDカウンタ
     pub fn add_connid_counts(&self, counter: &mut ConnIdCounter) {
         for end_char in 1..=self.len_char() {
-            for &r_node_ptr in &self.ends[end_char] {
-                let r_node = unsafe { &*r_node_ptr };
+            for &r_node_idx in &self.ends[end_char] {
+                let r_node = self.node(r_node_idx);
                 let start_node = r_node.start_node;
 
-                for &l_node_ptr in &self.ends[start_node] {
-                    let l_node = unsafe { &*l_node_ptr };
+                for &l_node_idx in &self.ends[start_node] {
+                    let l_node = self.node(l_node_idx);
                     counter.add(r_node.left_id, l_node.right_id, 1);
                 }
             }
         }
 
-        if !self.eos.is_null() {
-            let r_node = unsafe { &*self.eos };
+        if let Some(r_node) = self.eos_node() {
             if let Some(last_nodes) = self.ends.get(r_node.start_node) {
-                for &l_node_ptr in last_nodes {
-                    let l_node = unsafe { &*l_node_ptr };
+                for &l_node_idx in last_nodes {
+                    let l_node = self.node(l_node_idx);
                     counter.add(r_node.left_id, l_node.right_id, 1);
                 }
             }
@@ -6,13 +6,15 @@ use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::rc::Rc;
 
-use super::lattice::Node;
 use crate::dictionary::connector::ConnectorCost;
-use crate::dictionary::DictionaryInnerRef;
+use crate::sentence::Sentence;
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::edge_scorer::edge_delta;
 use crate::tokenizer::lattice::LatticeNBest;
 
 // The following structs are designed to reconstruct paths from the A* search result.
-// A path is stored as a linked list, which is pointed to by a QueueItem.
+// A path is stored as a linked list, which is pointed to by a QueueItem. Nodes are referred
+// to by their indices in the lattice arena.
 //
 // QueueItem -> Path (node: EOS) -> Path (node: n-1) -> ... -> Path (node: BOS)
 
@@ -21,8 +23,8 @@ use crate::tokenizer::lattice::LatticeNBest;
 /// 文の終端から始端への連結リストを形成します。
 #[derive(Debug)]
 struct SearchPath {
-    /// パスの現在位置にあるノード。
-    node: *const Node,
+    /// パスの現在位置にあるノードのインデックス。
+    node: u32,
     /// パス内の次のノードへのポインタ（BOS方向）。
     prev: Option<Rc<SearchPath>>,
     /// EOSからこのノードまでの総コスト（後方コスト）。
@@ -40,6 +42,13 @@ struct QueueItem {
     priority: i32,
 }
 
+/// 探索中の部分パス1つあたりのおおよそのバイト数。
+///
+/// `Rc`の参照カウントと、キュー内のアイテムを含みます。
+const SEARCH_ITEM_BYTES: usize = std::mem::size_of::<SearchPath>()
+    + 2 * std::mem::size_of::<usize>()
+    + std::mem::size_of::<QueueItem>();
+
 impl PartialEq for QueueItem { fn eq(&self, other: &Self) -> bool { self.priority == other.priority } }
 impl Eq for QueueItem {}
 impl PartialOrd for QueueItem { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
@@ -53,8 +62,15 @@ impl Ord for QueueItem {
 /// トークン化パスを生成するイテレータとして機能します。
 pub struct NbestGenerator<'a> {
     queue: BinaryHeap<QueueItem>,
+    lattice: &'a LatticeNBest,
     connector: &'a dyn ConnectorCost,
-    dictionary: DictionaryInnerRef<'a>,
+    tokenizer: &'a Tokenizer,
+    sent: &'a Sentence,
+    // Upper bound of the bytes allocated for partial paths
+    budget: Option<usize>,
+    // Bytes allocated for partial paths so far, including the ones already freed
+    used: usize,
+    budget_exceeded: bool,
 }
 
 impl<'a> NbestGenerator<'a> {
@@ -64,7 +80,8 @@ impl<'a> NbestGenerator<'a> {
     ///
     /// * `lattice` - N-best用のラティス
     /// * `connector` - 接続コスト計算用のコネクタ
-    /// * `dictionary` - 辞書への参照
+    /// * `tokenizer` - ラティスを構築したトークナイザー
+    /// * `sent` - ラティスを構築した入力文
     ///
     /// # 戻り値
     ///
@@ -72,12 +89,14 @@ impl<'a> NbestGenerator<'a> {
     pub fn new(
         lattice: &'a LatticeNBest,
         connector: &'a dyn ConnectorCost,
-        dictionary: DictionaryInnerRef<'a>,
+        tokenizer: &'a Tokenizer,
+        sent: &'a Sentence,
     ) -> Self {
         let mut queue = BinaryHeap::new();
-        if let Some(eos_node) = lattice.eos_node() {
+        if let Some(eos_idx) = lattice.eos_idx() {
+            let eos_node = lattice.node(eos_idx);
             let initial_path = Rc::new(SearchPath {
-                node: eos_node as *const Node,
+                node: eos_idx,
                 prev: None,
                 backward_cost: 0,
             });
@@ -86,15 +105,41 @@ impl<'a> NbestGenerator<'a> {
                 path: initial_path,
             });
         }
-        Self { queue, connector, dictionary }
+        Self {
+            queue,
+            lattice,
+            connector,
+            tokenizer,
+            sent,
+            budget: None,
+            used: SEARCH_ITEM_BYTES,
+            budget_exceeded: false,
+        }
+    }
+
+    /// 探索中の部分パスに確保するメモリの上限をバイト単位で設定します。
+    ///
+    /// 部分パスとキュー内のアイテムの大きさを、解放されたものも含めて累計で数えます。
+    /// 上限を超えると探索を打ち切り、以降は`None`を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `budget` - 上限のバイト数。`None`の場合は上限を設けません。
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// 探索中に確保したメモリが上限を超えたかを返します。
+    pub const fn budget_exceeded(&self) -> bool {
+        self.budget_exceeded
     }
 }
 
 impl<'a> Iterator for NbestGenerator<'a> {
     /// イテレータが返す要素の型。
     ///
-    /// ノードポインタのベクトルとパスの総コストのタプル。
-    type Item = (Vec<*const Node>, i32);
+    /// ラティス内のノードのインデックスのベクトルとパスの総コストのタプル。
+    type Item = (Vec<u32>, i32);
 
     /// 次のN-bestパスを取得します。
     ///
@@ -107,14 +152,14 @@ impl<'a> Iterator for NbestGenerator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(item) = self.queue.pop() {
             let current_path = &item.path;
-            let current_node = unsafe { &*current_path.node };
+            let current_node = self.lattice.node(current_path.node);
 
             // If we reached the BOS, a full path has been found.
             if current_node.is_bos() {
                 let mut path_nodes = Vec::new();
                 let mut p = Some(Rc::clone(current_path));
                 while let Some(seg) = p {
-                    let node = unsafe { &*seg.node };
+                    let node = self.lattice.node(seg.node);
                     if !node.is_bos() && !node.is_eos() {
                         path_nodes.push(seg.node);
                     }
@@ -123,30 +168,41 @@ impl<'a> Iterator for NbestGenerator<'a> {
                 return Some((path_nodes, item.priority));
             }
 
-            let mut lpath_ptr = current_node.lpath;
             // Expand to previous nodes.
-            while !lpath_ptr.is_null() {
-                let lpath = unsafe { &*lpath_ptr };
-                let prev_node_ptr = lpath.lnode;
-                let prev_node = unsafe { &*prev_node_ptr };
-
-                let conn_cost = self.connector.cost(prev_node.right_id, current_node.left_id);
-                let word_cost = if current_node.is_bos() || current_node.is_eos() {
-                    0
-                } else {
-                    self.dictionary.word_param(current_node.word_idx()).word_cost
-                };
-                let new_backward_cost = current_path.backward_cost + conn_cost + i32::from(word_cost);
+            for prev_node_idx in self.lattice.left_nodes(current_node) {
+                let prev_node = self.lattice.node(prev_node_idx);
+
+                let mut conn_cost = self.connector.cost(prev_node.right_id, current_node.left_id);
+                if let Some(scorer) = self.tokenizer.edge_scorer_ref() {
+                    // The current node ends where the next node toward EOS starts.
+                    let end = current_path
+                        .prev
+                        .as_ref()
+                        .map_or(self.lattice.len_char(), |p| self.lattice.node(p.node).start_node);
+                    conn_cost = conn_cost.saturating_add(edge_delta(
+                        scorer,
+                        self.sent,
+                        prev_node,
+                        current_node,
+                        end,
+                    ));
+                }
+                let new_backward_cost =
+                    current_path.backward_cost + conn_cost + i32::from(current_node.word_cost);
                 let new_priority = new_backward_cost + prev_node.min_cost; // f(x) = g(x) + h(x)
 
+                self.used += SEARCH_ITEM_BYTES;
+                if self.budget.is_some_and(|budget| self.used > budget) {
+                    self.budget_exceeded = true;
+                    self.queue.clear();
+                    return None;
+                }
                 let new_path = Rc::new(SearchPath {
-                    node: prev_node_ptr,
+                    node: prev_node_idx,
                     prev: Some(Rc::clone(current_path)),
                     backward_cost: new_backward_cost,
                 });
                 self.queue.push(QueueItem { path: new_path, priority: new_priority });
-
-                lpath_ptr = lpath.lnext;
             }
         }
         None
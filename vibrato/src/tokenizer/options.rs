@@ -0,0 +1,326 @@
+//! トークナイザーの設定
+//!
+//! このモジュールは、[`Tokenizer`]の解析に関する設定をまとめた[`TokenizerOptions`]を
+//! 提供します。設定は辞書から独立した値として保持されるため、複数のトークナイザーに
+//! 同じ設定を適用したり、`serde`フィーチャーを有効にして設定ファイルから読み込んだり
+//! できます。
+
+use std::collections::BTreeMap;
+
+use crate::Dictionary;
+use crate::errors::{Result, VibratoError};
+use crate::tokenizer::{DEFAULT_SPACE_FEATURE, TieBreak, Tokenizer, UnknownFallback};
+
+/// トークナイザーの解析に関する設定
+///
+/// [`Tokenizer::with_options()`]または[`Tokenizer::apply_options()`]に渡して使用します。
+/// 各項目は同名の[`Tokenizer`]のメソッドに対応し、項目間の組み合わせと辞書に依存する
+/// 値は適用時に一度だけ検証されます。
+///
+/// `serde`フィーチャーを有効にすると、設定ファイルから読み込めます。省略された項目は
+/// 既定値になり、未知の項目はエラーになります。
+///
+/// ```toml
+/// ignore_space = true
+/// max_grouping_len = 24
+/// tie_break = "MecabCompatible"
+///
+/// [category_grouping_lens]
+/// KATAKANA = 12
+/// ```
+///
+/// # 例
+///
+/// ```no_run
+/// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+/// use vibrato_rkyv::tokenizer::{TieBreak, TokenizerOptions};
+///
+/// let options = TokenizerOptions::new()
+///     .ignore_space(true)
+///     .max_grouping_len(24)
+///     .tie_break(TieBreak::MecabCompatible);
+///
+/// let ipadic = Dictionary::from_path("path/to/ipadic", LoadMode::Validate)?;
+/// let unidic = Dictionary::from_path("path/to/unidic", LoadMode::Validate)?;
+/// let tokenizer1 = Tokenizer::with_options(ipadic, &options)?;
+/// let tokenizer2 = Tokenizer::with_options(unidic, &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct TokenizerOptions {
+    ignore_space: bool,
+    keep_space_tokens: bool,
+    space_token_feature: String,
+    replacement_char_category: Option<String>,
+    group_graphemes: bool,
+    max_grouping_len: usize,
+    category_grouping_lens: BTreeMap<String, usize>,
+    unknown_fallback: UnknownFallback,
+    tie_break: TieBreak,
+    user_cost_bonus: i16,
+    strict_user_lexicon: bool,
+    fold_latin: bool,
+}
+
+impl Default for TokenizerOptions {
+    fn default() -> Self {
+        Self {
+            ignore_space: false,
+            keep_space_tokens: false,
+            space_token_feature: DEFAULT_SPACE_FEATURE.to_string(),
+            replacement_char_category: None,
+            group_graphemes: false,
+            max_grouping_len: 0,
+            category_grouping_lens: BTreeMap::new(),
+            unknown_fallback: UnknownFallback::Grouped,
+            tie_break: TieBreak::LastWins,
+            user_cost_bonus: 0,
+            strict_user_lexicon: false,
+            fold_latin: false,
+        }
+    }
+}
+
+impl TokenizerOptions {
+    /// デフォルトの設定を作成します。
+    ///
+    /// [`Tokenizer::new()`]で作成したトークナイザーと同じ設定です。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// トークンからスペースを無視するかどうかを設定します。
+    ///
+    /// [`Tokenizer::ignore_space()`]を参照してください。
+    pub const fn ignore_space(mut self, yes: bool) -> Self {
+        self.ignore_space = yes;
+        self
+    }
+
+    /// スペースを独立した空白トークンとして出力するかどうかを設定します。
+    ///
+    /// 有効にする場合は[`ignore_space()`](Self::ignore_space)も有効にしてください。
+    /// [`Tokenizer::keep_space_tokens()`]を参照してください。
+    pub const fn keep_space_tokens(mut self, yes: bool) -> Self {
+        self.keep_space_tokens = yes;
+        self
+    }
+
+    /// 空白トークンの素性を設定します。
+    ///
+    /// [`Tokenizer::space_token_feature()`]を参照してください。
+    pub fn space_token_feature<S: Into<String>>(mut self, feature: S) -> Self {
+        self.space_token_feature = feature.into();
+        self
+    }
+
+    /// U+FFFD（REPLACEMENT CHARACTER）に割り当てる文字カテゴリを設定します。
+    ///
+    /// [`Tokenizer::replacement_char_category()`]を参照してください。
+    pub fn replacement_char_category<S: Into<String>>(mut self, category: Option<S>) -> Self {
+        self.replacement_char_category = category.map(Into::into);
+        self
+    }
+
+    /// 拡張書記素クラスタを1文字として扱って未知語を生成するかどうかを設定します。
+    ///
+    /// [`Tokenizer::group_graphemes()`]を参照してください。
+    pub const fn group_graphemes(mut self, yes: bool) -> Self {
+        self.group_graphemes = yes;
+        self
+    }
+
+    /// 未知語の最大グルーピング長を設定します。0は無限の長さを示します。
+    ///
+    /// [`Tokenizer::max_grouping_len()`]を参照してください。
+    pub const fn max_grouping_len(mut self, max_grouping_len: usize) -> Self {
+        self.max_grouping_len = max_grouping_len;
+        self
+    }
+
+    /// 指定された文字カテゴリの未知語の最大グルーピング長を設定します。
+    ///
+    /// 同じカテゴリに複数回指定した場合は、最後の指定が使用されます。
+    /// [`Tokenizer::category_grouping_len()`]を参照してください。
+    pub fn category_grouping_len<S: Into<String>>(
+        mut self,
+        category: S,
+        max_grouping_len: usize,
+    ) -> Self {
+        self.category_grouping_lens.insert(category.into(), max_grouping_len);
+        self
+    }
+
+    /// 辞書に一致しない文字列から未知語を生成する際のグループ化の方法を設定します。
+    ///
+    /// [`Tokenizer::unknown_fallback()`]を参照してください。
+    pub const fn unknown_fallback(mut self, fallback: UnknownFallback) -> Self {
+        self.unknown_fallback = fallback;
+        self
+    }
+
+    /// 同コストの候補が複数ある場合の選び方を設定します。
+    ///
+    /// [`Tokenizer::tie_break()`]を参照してください。
+    pub const fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// ユーザー辞書の語の単語コストから引く値を設定します。
+    ///
+    /// [`Tokenizer::user_cost_bonus()`]を参照してください。
+    pub const fn user_cost_bonus(mut self, bonus: i16) -> Self {
+        self.user_cost_bonus = bonus;
+        self
+    }
+
+    /// ユーザー辞書の語が一致した位置で、他の候補を抑制するかを設定します。
+    ///
+    /// [`Tokenizer::strict_user_lexicon()`]を参照してください。
+    pub const fn strict_user_lexicon(mut self, yes: bool) -> Self {
+        self.strict_user_lexicon = yes;
+        self
+    }
+
+    /// ラテン文字の幅と大文字・小文字を区別せずに辞書の単語を照合するかどうかを設定します。
+    ///
+    /// [`Tokenizer::fold_latin()`]を参照してください。
+    pub const fn fold_latin(mut self, yes: bool) -> Self {
+        self.fold_latin = yes;
+        self
+    }
+
+    /// 辞書に依存しない項目の組み合わせを検証します。
+    ///
+    /// [`Tokenizer::with_options()`]と[`Tokenizer::apply_options()`]は適用前にこの検証を
+    /// 行います。設定ファイルを読み込んだ直後に誤りを報告する場合などに使用します。
+    ///
+    /// # エラー
+    ///
+    /// 次の場合に[`VibratoError`]が返されます。
+    ///
+    /// - `keep_space_tokens`が有効で、`ignore_space`が無効な場合
+    /// - `space_token_feature`が空の場合
+    pub fn validate(&self) -> Result<()> {
+        if self.keep_space_tokens && !self.ignore_space {
+            return Err(VibratoError::invalid_argument(
+                "options",
+                "keep_space_tokens requires ignore_space to be enabled.",
+            ));
+        }
+        if self.space_token_feature.is_empty() {
+            return Err(VibratoError::invalid_argument(
+                "options",
+                "space_token_feature must not be empty.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Tokenizer {
+    /// 設定を指定して新しいトークナイザーを作成します。
+    ///
+    /// `Tokenizer::new(dict).apply_options(options)`と同じです。
+    ///
+    /// # 引数
+    ///
+    /// * `dict` - 形態素解析に使用する辞書
+    /// * `options` - 適用する設定
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// 設定が[`TokenizerOptions::validate()`]の検証に失敗した場合、設定で指定された
+    /// カテゴリが辞書に定義されていない場合、または`fold_latin`が有効で辞書が表層形を
+    /// 持たない場合に[`VibratoError`]が返されます。
+    pub fn with_options(dict: Dictionary, options: &TokenizerOptions) -> Result<Self> {
+        Self::new(dict).apply_options(options)
+    }
+
+    /// 設定をトークナイザーに適用します。
+    ///
+    /// [`TokenizerOptions`]に含まれるすべての項目が上書きされます。
+    /// [`approximate_user_lexicon()`](Self::approximate_user_lexicon)や
+    /// [`unk_provider()`](Self::unk_provider)の設定はそのまま残ります。
+    ///
+    /// # 引数
+    ///
+    /// * `options` - 適用する設定
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が適用された`Tokenizer`インスタンス
+    ///
+    /// # エラー
+    ///
+    /// [`Tokenizer::with_options()`]と同じ条件で[`VibratoError`]が返されます。
+    pub fn apply_options(mut self, options: &TokenizerOptions) -> Result<Self> {
+        options.validate()?;
+        self.category_grouping_lens.clear();
+        for (category, &len) in &options.category_grouping_lens {
+            self = self.category_grouping_len(category, len)?;
+        }
+        self
+            .ignore_space(options.ignore_space)?
+            .keep_space_tokens(options.keep_space_tokens)?
+            .space_token_feature(&options.space_token_feature)
+            .replacement_char_category(options.replacement_char_category.as_deref())?
+            .group_graphemes(options.group_graphemes)
+            .max_grouping_len(options.max_grouping_len)
+            .unknown_fallback(options.unknown_fallback)
+            .tie_break(options.tie_break)
+            .user_cost_bonus(options.user_cost_bonus)
+            .strict_user_lexicon(options.strict_user_lexicon)
+            .fold_latin(options.fold_latin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(TokenizerOptions::new().validate().is_ok());
+        assert!(TokenizerOptions::new().keep_space_tokens(true).validate().is_err());
+        assert!(
+            TokenizerOptions::new()
+                .ignore_space(true)
+                .keep_space_tokens(true)
+                .validate()
+                .is_ok()
+        );
+        assert!(TokenizerOptions::new().space_token_feature("").validate().is_err());
+    }
+
+    #[cfg(all(feature = "serde", feature = "download"))]
+    #[test]
+    fn test_deserialize() {
+        let options: TokenizerOptions = toml::from_str(
+            r#"
+            ignore_space = true
+            max_grouping_len = 24
+            tie_break = "MecabCompatible"
+
+            [category_grouping_lens]
+            KATAKANA = 12
+            "#,
+        )
+        .unwrap();
+        let expected = TokenizerOptions::new()
+            .ignore_space(true)
+            .max_grouping_len(24)
+            .tie_break(TieBreak::MecabCompatible)
+            .category_grouping_len("KATAKANA", 12);
+        assert_eq!(options, expected);
+
+        assert!(toml::from_str::<TokenizerOptions>("unknown = 1").is_err());
+    }
+}
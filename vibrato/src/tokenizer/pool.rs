@@ -0,0 +1,258 @@
+//! スレッド間で共有するワーカーのプール
+//!
+//! [`Worker`]は[`Send`]かつ[`Sync`]であり、生ポインタを保持しません。そのため
+//! ワーカーをスレッド間で移動させても安全です。ただしワーカーは可変な内部状態を
+//! 持つため、同時に複数のスレッドから使うことはできません。[`WorkerPool`]は
+//! 使われていないワーカーを保持し、必要なスレッドに一つずつ貸し出します。
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::worker::Worker;
+
+/// スレッド間で共有できるワーカーのプール
+///
+/// [`get()`](Self::get)で取得した[`PooledWorker`]は、ドロップ時に自動的に
+/// プールへ返却されます。返却されたワーカーは内部のバッファを保持したまま
+/// 再利用されるため、リクエストごとにワーカーを作成するよりもメモリ割り当てが
+/// 少なくなります。プールが空の場合は新しいワーカーが作成されます。
+///
+/// # 例
+///
+/// ```
+/// use std::sync::Arc;
+/// use vibrato_rkyv::{SystemDictionaryBuilder, Tokenizer};
+/// use vibrato_rkyv::tokenizer::WorkerPool;
+///
+/// let dict = SystemDictionaryBuilder::from_readers(
+///     "京都,0,0,1,名詞".as_bytes(),
+///     "1 1\n0 0 0".as_bytes(),
+///     "DEFAULT 0 1 0".as_bytes(),
+///     "DEFAULT,0,0,100,*".as_bytes(),
+/// )?;
+/// let pool = Arc::new(WorkerPool::new(Tokenizer::from_inner(dict)));
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let pool = Arc::clone(&pool);
+///         std::thread::spawn(move || {
+///             let mut worker = pool.get();
+///             worker.reset_sentence("京都");
+///             worker.tokenize();
+///             worker.num_tokens()
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     assert_eq!(handle.join().unwrap(), 1);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct WorkerPool {
+    tokenizer: Tokenizer,
+    idle: Mutex<Vec<Worker>>,
+    max_idle: usize,
+}
+
+impl WorkerPool {
+    /// 新しいプールを作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `tokenizer` - ワーカーの作成に使用するトークナイザー
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        Self {
+            tokenizer,
+            idle: Mutex::new(vec![]),
+            max_idle: usize::MAX,
+        }
+    }
+
+    /// プールに保持する未使用のワーカーの最大数を設定します。
+    ///
+    /// 上限を超えて返却されたワーカーは破棄されます。デフォルトは無制限です。
+    ///
+    /// # 引数
+    ///
+    /// * `max_idle` - 保持するワーカーの最大数
+    pub fn max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// 指定した数のワーカーを事前に作成します。
+    ///
+    /// # 引数
+    ///
+    /// * `num_workers` - 作成するワーカーの数
+    pub fn prefill(self, num_workers: usize) -> Self {
+        {
+            let mut idle = self.lock_idle();
+            while idle.len() < num_workers.min(self.max_idle) {
+                idle.push(self.tokenizer.new_worker());
+            }
+        }
+        self
+    }
+
+    /// プールからワーカーを取得します。
+    ///
+    /// 未使用のワーカーがない場合は新しく作成します。
+    ///
+    /// # 戻り値
+    ///
+    /// ドロップ時にプールへ返却されるワーカー
+    pub fn get(&self) -> PooledWorker<'_> {
+        let worker = self.lock_idle().pop();
+        let worker = worker.unwrap_or_else(|| self.tokenizer.new_worker());
+        PooledWorker { pool: self, worker: Some(worker) }
+    }
+
+    /// プールに保持されている未使用のワーカーの数を返します。
+    pub fn num_idle(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    /// ワーカーの作成に使用するトークナイザーを返します。
+    #[inline(always)]
+    pub const fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, Vec<Worker>> {
+        // A worker is pushed or popped atomically, so the list is consistent even if a
+        // thread panicked while holding the lock.
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn release(&self, mut worker: Worker) {
+        // Undo the per-worker settings so that the next borrower sees the defaults.
+        worker.set_unk_grouping(self.tokenizer.unk_grouping);
+        let mut idle = self.lock_idle();
+        if idle.len() < self.max_idle {
+            idle.push(worker);
+        }
+    }
+}
+
+/// [`WorkerPool`]から貸し出されたワーカー
+///
+/// [`Worker`]として使用でき、ドロップ時にプールへ返却されます。
+pub struct PooledWorker<'p> {
+    pool: &'p WorkerPool,
+    worker: Option<Worker>,
+}
+
+impl PooledWorker<'_> {
+    /// プールに返却せずにワーカーを取り出します。
+    pub fn detach(mut self) -> Worker {
+        self.worker.take().unwrap()
+    }
+}
+
+impl Deref for PooledWorker<'_> {
+    type Target = Worker;
+
+    #[inline(always)]
+    fn deref(&self) -> &Worker {
+        self.worker.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledWorker<'_> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Worker {
+        self.worker.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledWorker<'_> {
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.pool.release(worker);
+        }
+    }
+}
+
+// Workers are moved between threads by the pool, so these must hold by construction.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Tokenizer>();
+    assert_send_sync::<Worker>();
+    assert_send_sync::<WorkerPool>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::SystemDictionaryBuilder;
+
+    fn test_pool() -> WorkerPool {
+        let dict = SystemDictionaryBuilder::from_readers(
+            "自然,0,0,1,sizen\n言語,0,0,4,gengo\n自然言語,0,0,6,sizengengo".as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,*".as_bytes(),
+        )
+        .unwrap();
+        WorkerPool::new(Tokenizer::from_inner(dict))
+    }
+
+    #[test]
+    fn test_reuse() {
+        let pool = test_pool().max_idle(1).prefill(3);
+        assert_eq!(pool.num_idle(), 1);
+        {
+            let mut w1 = pool.get();
+            let mut w2 = pool.get();
+            assert_eq!(pool.num_idle(), 0);
+            w1.set_unk_grouping(false);
+            w1.reset_sentence("自然言語");
+            w1.tokenize();
+            w2.reset_sentence("言語");
+            w2.tokenize();
+            assert_eq!(w1.num_tokens(), 2);
+            assert_eq!(w2.num_tokens(), 1);
+        }
+        assert_eq!(pool.num_idle(), 1);
+        assert!(pool.get().tokenizer.unk_grouping);
+
+        let worker = pool.get().detach();
+        assert_eq!(pool.num_idle(), 0);
+        drop(worker);
+    }
+
+    #[test]
+    fn test_move_between_threads() {
+        let pool = test_pool();
+        let mut worker = pool.get().detach();
+        worker.reset_sentence("自然言語");
+        worker.tokenize_nbest(2);
+
+        // The N-best results stay valid after the worker is moved to another thread.
+        let worker = std::thread::spawn(move || {
+            assert_eq!(worker.num_nbest_paths(), 2);
+            let surfaces: Vec<_> =
+                worker.nbest_token_iter(1).unwrap().map(|t| t.surface().to_string()).collect();
+            assert_eq!(surfaces, ["自然言語"]);
+            worker
+        })
+        .join()
+        .unwrap();
+        assert_eq!(worker.num_nbest_paths(), 2);
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut worker = pool.get();
+                    worker.reset_sentence("自然言語");
+                    worker.tokenize();
+                    assert_eq!(worker.token(0).surface(), "自然");
+                });
+            }
+        });
+        assert!(pool.num_idle() >= 1);
+    }
+}
@@ -0,0 +1,109 @@
+//! 分かち書き専用の解析
+//!
+//! このモジュールは、[`Worker`](crate::tokenizer::worker::Worker)やトークンを作成せずに、
+//! 最良パスの境界のみを求める[`Tokenizer::segment()`]を提供します。IMEでの入力ごとの
+//! 分割など、呼び出し頻度の高い処理に組み込むことを想定しています。
+
+use std::cell::RefCell;
+use std::ops::Range;
+
+use crate::sentence::Sentence;
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::lattice::{Lattice, Node};
+
+/// [`Tokenizer::segment()`]が再利用する作業領域
+#[derive(Default)]
+struct Scratch {
+    sent: Sentence,
+    lattice: Lattice,
+    top_nodes: Vec<(usize, Node)>,
+}
+
+/// スレッドごとの作業領域を使用する入力文の長さの上限（バイト数）
+///
+/// 作業領域は処理した最長の文に合わせて大きくなったまま保持されるため、これより長い文は
+/// 呼び出しごとに確保して解放する作業領域で処理し、スレッドごとのメモリ使用量を抑えます。
+const SCRATCH_MAX_BYTES: usize = 16 << 10;
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::default();
+}
+
+impl Tokenizer {
+    /// 入力文を分かち書きし、最良パスの各単語のバイト範囲を求めます。
+    ///
+    /// [`Worker`](crate::tokenizer::worker::Worker)を使用した解析と同じ最良パスを求めますが、
+    /// ワーカーやトークンを作成せず、素性文字列にもアクセスしません。ラティスなどの
+    /// 作業領域はスレッドごとに保持されて再利用されるため、同じスレッドから繰り返し
+    /// 呼び出す場合、`ranges`の容量が足りていれば新たなメモリ確保は行われません。
+    ///
+    /// 作業領域は処理した文の長さに合わせて大きくなり、縮小されません。一度の長い入力で
+    /// すべてのスレッドのメモリ使用量が大きいままにならないよう、16KiBを超える入力は
+    /// スレッドごとの作業領域を使わず、呼び出しのたびに確保して解放する作業領域で処理します。
+    ///
+    /// [`keep_space_tokens()`](Self::keep_space_tokens)が有効な場合は、空白の範囲も
+    /// 含まれます。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 分かち書きする入力文
+    /// * `ranges` - 単語のバイト範囲を格納するベクトル。呼び出し時に内容はクリアされます。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    ///
+    /// let mut ranges = vec![];
+    /// for text in ["き", "きょ", "きょう"] {
+    ///     tokenizer.segment(text, &mut ranges);
+    ///     let words: Vec<_> = ranges.iter().map(|r| &text[r.clone()]).collect();
+    ///     println!("{words:?}");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn segment(&self, text: &str, ranges: &mut Vec<Range<usize>>) {
+        ranges.clear();
+        if text.is_empty() {
+            return;
+        }
+        if text.len() > SCRATCH_MAX_BYTES {
+            self.segment_with(text, ranges, &mut Scratch::default());
+            return;
+        }
+        SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+            Ok(mut scratch) => self.segment_with(text, ranges, &mut scratch),
+            // Called again from an unknown word provider on the same thread.
+            Err(_) => self.segment_with(text, ranges, &mut Scratch::default()),
+        });
+    }
+
+    /// 作業領域を指定して分かち書きします。
+    fn segment_with(&self, text: &str, ranges: &mut Vec<Range<usize>>, scratch: &mut Scratch) {
+        let Scratch { sent, lattice, top_nodes } = scratch;
+        self.compile_sentence(sent, text);
+        self.build_lattice(sent, lattice);
+        top_nodes.clear();
+        lattice.append_top_nodes(top_nodes);
+
+        let keeps_space = self.keeps_space_tokens();
+        let len_char = sent.len_char();
+        if keeps_space {
+            let end = top_nodes.first().map_or(0, |&(end, _)| end);
+            if end < len_char {
+                ranges.push(sent.byte_position(end)..text.len());
+            }
+        }
+        for &(end_word, ref node) in top_nodes.iter() {
+            ranges.push(sent.byte_position(node.start_word)..sent.byte_position(end_word));
+            if keeps_space && node.start_node < node.start_word {
+                ranges.push(sent.byte_position(node.start_node)..sent.byte_position(node.start_word));
+            }
+        }
+        // The best path is traced from the end of the sentence.
+        ranges.reverse();
+    }
+}
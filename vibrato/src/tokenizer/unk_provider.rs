@@ -0,0 +1,211 @@
+//! アプリケーション定義の未知語生成
+//!
+//! このモジュールは、URLやメールアドレス、数値表現のように複数の文字種にまたがる
+//! 語を未知語として生成するための[`UnkProvider`]トレイトを提供します。
+//! プロバイダは`unk.def`による未知語の生成と並行してラティスの構築時に呼び出され、
+//! 生成された語は他の候補とコストで競合します。
+
+use std::sync::Arc;
+
+use crate::dictionary::{LexType, WordIdx};
+use crate::dictionary::lexicon::WordParam;
+use crate::sentence::Sentence;
+
+/// プロバイダが生成する語の定義
+///
+/// 接続IDは辞書の`matrix.def`の範囲内である必要があります。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnkEntry {
+    /// 左文脈ID
+    pub left_id: u16,
+    /// 右文脈ID
+    pub right_id: u16,
+    /// 単語コスト
+    pub word_cost: i16,
+    /// 素性
+    pub feature: String,
+}
+
+impl UnkEntry {
+    /// 新しいインスタンスを作成します。
+    pub fn new<S>(left_id: u16, right_id: u16, word_cost: i16, feature: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            left_id,
+            right_id,
+            word_cost,
+            feature: feature.into(),
+        }
+    }
+}
+
+/// プロバイダが見つけた語
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnkMatch {
+    /// 語の終了位置（文字単位、排他的）
+    pub end_char: usize,
+    /// [`UnkProvider::entries()`]が返す定義のインデックス
+    pub entry: usize,
+}
+
+/// プロバイダに渡される入力文
+pub struct UnkInput<'a> {
+    sent: &'a Sentence,
+}
+
+impl<'a> UnkInput<'a> {
+    pub(crate) const fn new(sent: &'a Sentence) -> Self {
+        Self { sent }
+    }
+
+    /// 入力文の文字列を返します。
+    #[inline(always)]
+    pub fn text(&self) -> &'a str {
+        self.sent.raw()
+    }
+
+    /// 入力文の文字配列を返します。
+    #[inline(always)]
+    pub fn chars(&self) -> &'a [char] {
+        self.sent.chars()
+    }
+
+    /// 文字位置に対応するバイト位置を返します。
+    ///
+    /// # パニック
+    ///
+    /// `pos_char`が文字数を超える場合にパニックします。
+    #[inline(always)]
+    pub fn byte_position(&self, pos_char: usize) -> usize {
+        self.sent.byte_position(pos_char)
+    }
+
+    /// バイト位置に対応する文字位置を返します。
+    ///
+    /// # 戻り値
+    ///
+    /// `pos_byte`が文字の境界でない場合は`None`
+    #[inline(always)]
+    pub fn char_position(&self, pos_byte: usize) -> Option<usize> {
+        self.sent.char_position(pos_byte)
+    }
+}
+
+/// 未知語を生成するプロバイダ
+///
+/// [`Tokenizer::unk_provider()`](crate::Tokenizer::unk_provider)で登録します。
+/// 生成された語のトークンの語彙種別は[`LexType::Unknown`]となり、素性には
+/// [`UnkEntry::feature`]が使用されます。
+///
+/// # 例
+///
+/// ASCII数字とカンマ・ピリオドからなる数値表現を1語として生成するプロバイダです。
+///
+/// ```
+/// use vibrato_rkyv::tokenizer::{UnkEntry, UnkInput, UnkMatch, UnkProvider};
+///
+/// struct NumberProvider;
+///
+/// impl UnkProvider for NumberProvider {
+///     fn entries(&self) -> Vec<UnkEntry> {
+///         vec![UnkEntry::new(0, 0, 100, "名詞,数")]
+///     }
+///
+///     fn provide(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(UnkMatch)) {
+///         let chars = &input.chars()[start_char..];
+///         if !chars.first().is_some_and(char::is_ascii_digit) {
+///             return;
+///         }
+///         let len = chars
+///             .iter()
+///             .take_while(|c| c.is_ascii_digit() || **c == ',' || **c == '.')
+///             .count();
+///         emit(UnkMatch { end_char: start_char + len, entry: 0 });
+///     }
+/// }
+/// ```
+pub trait UnkProvider: Send + Sync {
+    /// プロバイダが生成する語の定義を返します。
+    ///
+    /// プロバイダの登録時に一度だけ呼び出されます。
+    fn entries(&self) -> Vec<UnkEntry>;
+
+    /// 指定された位置から始まる語を列挙します。
+    ///
+    /// 見つけた語ごとに`emit`を呼び出します。終了位置が`start_char`以下または文字数を
+    /// 超える語と、存在しない定義を指す語は無視されます。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - 入力文
+    /// * `start_char` - 語の開始位置（文字単位）
+    /// * `emit` - 見つけた語を受け取る関数
+    fn provide(&self, input: &UnkInput, start_char: usize, emit: &mut dyn FnMut(UnkMatch));
+}
+
+/// 登録されたプロバイダと、それらが生成する語の定義
+///
+/// 語には[`WordIdx::SPACE`]の直前から降順に未知語の単語IDを割り当てます。
+#[derive(Clone, Default)]
+pub(crate) struct UnkProviders {
+    providers: Vec<(Arc<dyn UnkProvider>, usize, usize)>,
+    entries: Vec<UnkEntry>,
+}
+
+impl UnkProviders {
+    /// プロバイダを追加します。
+    pub fn push(&mut self, provider: Arc<dyn UnkProvider>, entries: Vec<UnkEntry>) {
+        self.providers.push((provider, self.entries.len(), entries.len()));
+        self.entries.extend(entries);
+    }
+
+    /// 登録された語の定義の数を返します。
+    #[inline(always)]
+    pub fn num_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 語の定義を参照します。
+    ///
+    /// # 戻り値
+    ///
+    /// `word_idx`がプロバイダの生成する語でない場合は`None`
+    #[inline(always)]
+    pub fn entry(&self, word_idx: WordIdx) -> Option<&UnkEntry> {
+        if word_idx.lex_type != LexType::Unknown || word_idx == WordIdx::SPACE {
+            return None;
+        }
+        let i = (WordIdx::SPACE.word_id - 1).checked_sub(word_idx.word_id)?;
+        self.entries.get(i as usize)
+    }
+
+    /// 指定された位置から始まる語を列挙します。
+    ///
+    /// # 引数
+    ///
+    /// * `sent` - 入力文
+    /// * `start_word` - 語の開始位置（文字単位）
+    /// * `f` - 語の終了位置、単語インデックス、パラメータを受け取る関数
+    pub fn for_each_match<F>(&self, sent: &Sentence, start_word: usize, mut f: F)
+    where
+        F: FnMut(usize, WordIdx, WordParam),
+    {
+        let input = UnkInput::new(sent);
+        for &(ref provider, offset, num_entries) in &self.providers {
+            provider.provide(&input, start_word, &mut |m| {
+                if m.end_char <= start_word || m.end_char > sent.len_char() {
+                    return;
+                }
+                if m.entry >= num_entries {
+                    return;
+                }
+                let i = offset + m.entry;
+                let e = &self.entries[i];
+                let word_idx = WordIdx::new(LexType::Unknown, WordIdx::SPACE.word_id - 1 - i as u32);
+                f(m.end_char, word_idx, WordParam::new(e.left_id, e.right_id, e.word_cost));
+            });
+        }
+    }
+}
@@ -2,20 +2,35 @@
 //!
 //! このモジュールは、形態素解析のための主要なワーカー構造体を提供します。
 //! ワーカーは内部データ構造を保持し、再利用することで不要なメモリアロケーションを避けます。
-use crate::dictionary::{ConnectorKindRef, DictionaryInnerRef};
-use crate::dictionary::connector::ConnectorView;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::ops::Range;
+#[cfg(feature = "stats")]
+use std::time::{Duration, Instant};
+
+use crate::analysis::{self, Document, SpanAnnotation};
+use crate::dictionary::{ConnectorKindRef, DictionaryInnerRef, WordIdx};
+use crate::dictionary::connector::{ConnectorCost, ConnectorView};
 use crate::dictionary::mapper::{ConnIdCounter, ConnIdProbs};
+use crate::errors::{Result, VibratoError};
+use crate::furigana::{self, Furigana};
 use crate::sentence::Sentence;
-use crate::token::{NbestTokenIter, Token, TokenIter};
-use crate::tokenizer::lattice::{Lattice, LatticeKind, Node};
-use crate::tokenizer::Tokenizer;
+use crate::token::{NbestTokenIter, Token, TokenBuf, TokenIter, TokenRef, Tokens};
+use crate::tokenizer::edge_scorer::{EdgeCost, ScoredConnector};
+use crate::tokenizer::lattice::{Lattice, LatticeKind, LatticeNBest, Node, NO_PATH};
+use crate::tokenizer::{LatticeCursor, Tokenizer};
 use crate::tokenizer::nbest_generator::NbestGenerator;
+use crate::utils;
 
 /// トークン化処理のためのルーチンを提供する構造体。
 ///
 /// トークン化に使用される内部データ構造を保持し、それらを再利用することで
 /// 不要なメモリ再割り当てを回避します。
 ///
+/// ワーカーは[`Send`]かつ[`Sync`]で、生ポインタを保持しないため、スレッド間で
+/// 移動させても安全です。スレッドごとにワーカーを用意するか、複数のスレッドで
+/// 共有する場合は[`WorkerPool`](crate::tokenizer::WorkerPool)を使用してください。
+///
 /// # 例
 ///
 /// ```ignore
@@ -32,7 +47,17 @@ pub struct Worker {
     pub(crate) lattice: LatticeKind,
     pub(crate) top_nodes: Vec<(usize, Node)>,
     pub(crate) counter: Option<ConnIdCounter>,
-    pub(crate) nbest_paths: Vec<(Vec<*const Node>, i32)>,
+    pub(crate) nbest_paths: Vec<(Vec<u32>, i32)>,
+    pub(crate) replacements: Vec<Utf8Replacement>,
+    // Number of leading characters of the sentence covered by the 1-best lattice, which can be
+    // reused after `extend_sentence()`
+    pub(crate) lattice_len: Option<usize>,
+    // (max_chars, avg_edges) given to `with_capacity_hints()`
+    pub(crate) capacity_hints: Option<(usize, usize)>,
+    pub(crate) nbest_budget: Option<usize>,
+    pub(crate) nbest_budget_exceeded: bool,
+    #[cfg(feature = "stats")]
+    pub(crate) stats: WorkerStats,
 }
 
 impl Worker {
@@ -49,9 +74,113 @@ impl Worker {
             top_nodes: vec![],
             counter: None,
             nbest_paths: Vec::with_capacity(0),
+            replacements: vec![],
+            lattice_len: None,
+            capacity_hints: None,
+            nbest_budget: None,
+            nbest_budget_exceeded: false,
+            #[cfg(feature = "stats")]
+            stats: WorkerStats::default(),
         }
     }
 
+    /// 想定する入力文の大きさに合わせて、あらかじめ容量を確保します。
+    ///
+    /// 指定した大きさまでの文は、メモリを再確保せずにトークン化できます。
+    /// 指定した値は[`shrink_to_fit()`](Self::shrink_to_fit)で縮小する際の目安としても
+    /// 使用されるため、長時間動作するサービスでワーカーごとのメモリ使用量を抑えられます。
+    ///
+    /// ラティスは1-best解とN-best解で種類が異なり、切り替え時に作り直されます。
+    /// 容量は呼び出した時点の種類のラティス（作成直後は1-best解用）にのみ確保されます。
+    ///
+    /// # 引数
+    ///
+    /// * `max_chars` - 入力文の最大文字数
+    /// * `avg_edges` - 1つの終了位置あたりの平均ノード数
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker().with_capacity_hints(256, 8);
+    ///
+    /// worker.reset_sentence("とても長い文章…");
+    /// worker.tokenize();
+    /// // Releases the memory allocated for the long sentence.
+    /// worker.shrink_to_fit();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_capacity_hints(mut self, max_chars: usize, avg_edges: usize) -> Self {
+        self.capacity_hints = Some((max_chars, avg_edges));
+        self.sent.reserve(max_chars);
+        self.lattice.reserve(max_chars, avg_edges);
+        self
+    }
+
+    /// N-best解析に使用するメモリの上限をバイト単位で設定します。
+    ///
+    /// N-best解析では各ノード間のすべての接続を保存するため、非常に長い入力や候補の多い
+    /// 入力ではメモリ使用量が大きくなります。上限は、ラティスのノードと接続、終了位置ごとの
+    /// ノードのリスト、A*探索の部分パスとキューの大きさの合計に適用されます。
+    /// 見積もりはおおよそのもので、N-best解として返すパスのノード列は含みません。
+    /// 上限を超えた場合、
+    /// [`tokenize_nbest()`](Self::tokenize_nbest)は最良接続のみを保存するラティスを構築し直し、
+    /// 1-best解のみを返します。[`try_tokenize_nbest()`](Self::try_tokenize_nbest)は
+    /// 代わりにエラーを返します。
+    ///
+    /// # 引数
+    ///
+    /// * `budget` - N-best解析に使用するメモリの上限（バイト数）
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker().with_nbest_budget(64 << 20);
+    ///
+    /// worker.reset_sentence("形態素解析");
+    /// worker.tokenize_nbest(10);
+    /// if worker.nbest_budget_exceeded() {
+    ///     assert_eq!(worker.num_nbest_paths(), 1);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn with_nbest_budget(mut self, budget: usize) -> Self {
+        self.nbest_budget = Some(budget);
+        self
+    }
+
+    /// 直前のN-best解析でラティスの大きさが上限を超えたかを返します。
+    ///
+    /// 詳細は[`with_nbest_budget()`](Self::with_nbest_budget)を参照してください。
+    pub const fn nbest_budget_exceeded(&self) -> bool {
+        self.nbest_budget_exceeded
+    }
+
+    /// 内部のバッファを縮小し、確保済みのメモリを解放します。
+    ///
+    /// ラティスなどのバッファは再利用のため縮小されないので、一度でも長い文を処理すると
+    /// ワーカーのメモリ使用量は大きいままになります。このメソッドはバッファの容量を
+    /// [`with_capacity_hints()`](Self::with_capacity_hints)で指定した大きさ（指定していない
+    /// 場合は0）まで縮小します。
+    ///
+    /// 現在の入力文とトークン化の結果は保持されるため、容量はそれらに必要な大きさより
+    /// 小さくなりません。
+    pub fn shrink_to_fit(&mut self) {
+        let (max_chars, avg_edges) = self.capacity_hints.unwrap_or((0, 0));
+        self.sent.shrink_to(max_chars);
+        self.lattice.shrink_to(max_chars, avg_edges);
+        self.top_nodes.shrink_to(max_chars);
+        self.nbest_paths.shrink_to_fit();
+        self.replacements.shrink_to_fit();
+    }
+
     /// トークン化する入力文をリセットします。
     ///
     /// 新しい文を設定し、以前の状態をクリアします。
@@ -65,18 +194,312 @@ impl Worker {
     {
         self.sent.clear();
         self.top_nodes.clear();
+        self.nbest_paths.clear();
+        self.replacements.clear();
+        self.lattice_len = None;
         let input = input.as_ref();
         if !input.is_empty() {
-            self.sent.set_sentence(input);
-            match self.tokenizer.dictionary() {
-                DictionaryInnerRef::Archived(dict) => {
-                    self.sent.compile_archived(dict.char_prop());
-                },
-                DictionaryInnerRef::Owned(dict) => {
-                    self.sent.compile(dict.char_prop());
-                },
+            self.tokenizer.compile_sentence(&mut self.sent, input);
+        }
+    }
+
+    /// 現在の入力文の末尾に文字列を追記します。
+    ///
+    /// 直前に[`tokenize()`](Self::tokenize)で入力文をトークン化していた場合、次の
+    /// [`tokenize()`](Self::tokenize)では構築済みのラティスのうち追記の影響を受けない
+    /// 部分が再利用され、影響を受ける末尾側のみが構築し直されます。キー入力ごとに
+    /// 解析し直すかな漢字変換や入力中の逐次解析で、文全体を解析するコストを避けられます。
+    /// トークン化の結果は、追記後の文を[`reset_sentence()`](Self::reset_sentence)で
+    /// 設定した場合と同じです。
+    ///
+    /// [`Tokenizer::approximate_user_lexicon()`]や[`Tokenizer::unk_provider()`]を
+    /// 使用している場合、ラティスは再利用されません。
+    ///
+    /// # 引数
+    ///
+    /// * `suffix` - 追記する文字列
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("");
+    /// for c in ["き", "ょ", "う", "は"] {
+    ///     worker.extend_sentence(c);
+    ///     worker.tokenize();
+    ///     let surfaces: Vec<_> = worker.token_iter().map(|t| t.surface()).collect();
+    ///     println!("{surfaces:?}");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn extend_sentence<S>(&mut self, suffix: S)
+    where
+        S: AsRef<str>,
+    {
+        let suffix = suffix.as_ref();
+        if suffix.is_empty() {
+            return;
+        }
+        self.top_nodes.clear();
+        self.nbest_paths.clear();
+        let mut input = String::with_capacity(self.sent.raw().len() + suffix.len());
+        input.push_str(self.sent.raw());
+        input.push_str(suffix);
+        self.tokenizer.compile_sentence(&mut self.sent, &input);
+    }
+
+    /// 文書を文に分割してトークン化します。
+    ///
+    /// 文への分割は[`analysis::split_sentences()`]で行います。結果のトークンの位置範囲は
+    /// 文書の先頭からの位置で表されます。呼び出し後、ワーカーには最後の文の
+    /// トークン化結果が残ります。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - トークン化する文書
+    ///
+    /// # 戻り値
+    ///
+    /// 文書の解析結果
+    pub fn tokenize_document(&mut self, text: &str) -> Document {
+        let mut sentences = vec![];
+        self.for_each_sentence(text, |worker, range_byte, offset_char| {
+            let offset_byte = range_byte.start;
+            let tokens = worker
+                .token_iter()
+                .map(|token| {
+                    let mut token = token.to_buf();
+                    token.range_byte =
+                        token.range_byte.start + offset_byte..token.range_byte.end + offset_byte;
+                    token.range_char =
+                        token.range_char.start + offset_char..token.range_char.end + offset_char;
+                    token
+                })
+                .collect();
+            let len_char = worker.sent.len_char();
+            sentences.push(analysis::Sentence {
+                range_char: offset_char..offset_char + len_char,
+                range_byte,
+                tokens,
+            });
+        });
+        Document {
+            text: text.to_string(),
+            sentences,
+        }
+    }
+
+    /// 文書をトークン化し、各トークンのスタンドオフ注釈を返します。
+    ///
+    /// 文への分割は[`tokenize_document()`](Self::tokenize_document)と同じです。
+    /// 注釈の位置範囲は文の境界をまたいで文書の先頭からの位置で表されるため、
+    /// 固有表現抽出などの注釈ツールで位置を再計算せずにそのまま保存できます。
+    /// 文書のテキストやトークンの表層形は複製されません。呼び出し後、ワーカーには
+    /// 最後の文のトークン化結果が残ります。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - トークン化する文書
+    ///
+    /// # 戻り値
+    ///
+    /// 出現順のトークンの注釈
+    pub fn annotate_document(&mut self, text: &str) -> Vec<SpanAnnotation> {
+        let mut annotations = vec![];
+        let mut sentence = 0;
+        self.for_each_sentence(text, |worker, range_byte, offset_char| {
+            let offset_byte = range_byte.start;
+            annotations.extend(worker.token_iter().map(|token| {
+                let range_byte = token.range_byte();
+                let range_char = token.range_char();
+                SpanAnnotation {
+                    range_byte: range_byte.start + offset_byte..range_byte.end + offset_byte,
+                    range_char: range_char.start + offset_char..range_char.end + offset_char,
+                    sentence,
+                    word_idx: token.word_idx(),
+                    feature: token.feature().to_string(),
+                }
+            }));
+            sentence += 1;
+        });
+        annotations
+    }
+
+    /// TSV形式の行の指定された列をトークン化します。
+    ///
+    /// 行をタブで列に分割し、`text_column`番目の列のみを入力文としてトークン化します。
+    /// IDなどの他の列には触れないため、返された[`TsvLine`]の`prefix`と`suffix`を
+    /// トークン化結果の前後に書き出すことで、コーパスの行との対応を保ったまま出力できます。
+    ///
+    /// # 引数
+    ///
+    /// * `line` - 改行を含まないTSV形式の行
+    /// * `text_column` - トークン化する列の番号（0始まり）
+    ///
+    /// # 戻り値
+    ///
+    /// トークン化した列とその前後の部分
+    ///
+    /// # エラー
+    ///
+    /// 行の列数が`text_column`以下の場合、[`VibratoError`]を返します。このとき入力文は空になります。
+    pub fn tokenize_tsv_line<'a>(&mut self, line: &'a str, text_column: usize) -> Result<TsvLine<'a>> {
+        let Some(tsv) = TsvLine::split(line, text_column) else {
+            self.reset_sentence("");
+            return Err(VibratoError::invalid_argument(
+                "text_column",
+                format!(
+                    "The line has only {} column(s), but column {text_column} was requested.",
+                    line.split('\t').count(),
+                ),
+            ));
+        };
+        self.reset_sentence(tsv.text);
+        self.tokenize();
+        Ok(tsv)
+    }
+
+    /// 文書を文に分割し、各文をトークン化して`f`を呼び出します。
+    ///
+    /// `f`には、文のトークン化結果を保持したワーカー、文のバイト単位の位置範囲、
+    /// 文書の先頭から文の先頭までの文字数が渡されます。
+    fn for_each_sentence<F>(&mut self, text: &str, mut f: F)
+    where
+        F: FnMut(&Self, Range<usize>, usize),
+    {
+        let mut offset_byte = 0;
+        let mut offset_char = 0;
+        for range_byte in analysis::split_sentences(text) {
+            offset_char += text[offset_byte..range_byte.start].chars().count();
+            offset_byte = range_byte.start;
+            self.reset_sentence(&text[range_byte.clone()]);
+            self.tokenize();
+            f(self, range_byte, offset_char);
+        }
+    }
+
+    /// UTF-8として不正なバイト列を含みうる入力文をリセットします。
+    ///
+    /// 不正なバイト列の扱いは`policy`で指定します。[`InvalidUtf8::Replace`]の場合、
+    /// 不正なバイト列はそれぞれU+FFFD（REPLACEMENT CHARACTER）に置換され、置換箇所は
+    /// [`utf8_replacements()`](Self::utf8_replacements)で取得できます。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - トークン化する入力バイト列
+    /// * `policy` - 不正なバイト列の扱い
+    ///
+    /// # エラー
+    ///
+    /// `policy`が[`InvalidUtf8::Reject`]で入力が不正なUTF-8を含む場合、
+    /// [`VibratoError::Utf8`](crate::errors::VibratoError::Utf8)を返します。
+    /// エラーから最初の不正なバイトの位置を取得できます。このとき入力文は空になります。
+    pub fn reset_sentence_bytes(&mut self, input: &[u8], policy: InvalidUtf8) -> Result<()> {
+        match policy {
+            InvalidUtf8::Replace => self.reset_sentence_lossy(input),
+            InvalidUtf8::Reject => match std::str::from_utf8(input) {
+                Ok(input) => self.reset_sentence(input),
+                Err(e) => {
+                    self.reset_sentence("");
+                    return Err(e.into());
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// UTF-8として不正なバイト列を置換して入力文をリセットします。
+    ///
+    /// 不正なバイト列はそれぞれU+FFFD（REPLACEMENT CHARACTER）に置換されます。
+    /// 置換によってトークンのバイト位置は入力と一致しなくなるため、
+    /// 入力上の位置が必要な場合は[`input_offset()`](Self::input_offset)で変換してください。
+    /// 置換文字を専用の未知語として扱うには
+    /// [`Tokenizer::replacement_char_category()`]を使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `input` - トークン化する入力バイト列
+    pub fn reset_sentence_lossy(&mut self, input: &[u8]) {
+        if let Ok(input) = std::str::from_utf8(input) {
+            self.reset_sentence(input);
+            return;
+        }
+        let mut decoded = String::with_capacity(input.len() + 2);
+        let mut replacements = vec![];
+        let mut offset = 0;
+        for chunk in input.utf8_chunks() {
+            decoded.push_str(chunk.valid());
+            offset += chunk.valid().len();
+            let invalid = chunk.invalid();
+            if !invalid.is_empty() {
+                let start = decoded.len();
+                decoded.push(char::REPLACEMENT_CHARACTER);
+                replacements.push(Utf8Replacement {
+                    input: offset..offset + invalid.len(),
+                    output: start..decoded.len(),
+                });
+                offset += invalid.len();
             }
         }
+        self.reset_sentence(decoded);
+        self.replacements = replacements;
+    }
+
+    /// 直前の[`reset_sentence_lossy()`](Self::reset_sentence_lossy)で置換された箇所を返します。
+    ///
+    /// 置換がなかった場合や、他の方法で入力文を設定した場合は空です。
+    #[inline(always)]
+    pub fn utf8_replacements(&self) -> &[Utf8Replacement] {
+        &self.replacements
+    }
+
+    /// 入力文のバイト位置を、置換前の入力バイト列上の位置に変換します。
+    ///
+    /// 置換文字の内側を指す位置は、置換された不正なバイト列の先頭に変換されます。
+    ///
+    /// # 引数
+    ///
+    /// * `pos` - [`Token::range_byte()`]などで得られる入力文のバイト位置
+    ///
+    /// # 戻り値
+    ///
+    /// 置換前の入力バイト列上の位置
+    pub fn input_offset(&self, pos: usize) -> usize {
+        let i = self.replacements.partition_point(|r| r.output.end <= pos);
+        if let Some(r) = self.replacements.get(i)
+            && r.output.start < pos
+        {
+            return r.input.start;
+        }
+        match i.checked_sub(1) {
+            Some(j) => {
+                let r = &self.replacements[j];
+                pos - r.output.end + r.input.end
+            }
+            None => pos,
+        }
+    }
+
+    /// 未知語のグループ化を有効にするかどうかを設定します。
+    ///
+    /// `false`を指定すると、char.defのグループ化指定や長さ指定、および
+    /// [`Tokenizer::max_grouping_len()`]の設定にかかわらず、未知語は1文字単位
+    /// （各未知語テンプレートにつき1ノード）でのみ生成されます。
+    /// グループ化によって内部の境界が隠れてしまうのを避けたいアノテーション用途に有用です。
+    ///
+    /// 設定はこのワーカーで以降に行うトークン化に適用されます。デフォルトは`true`です。
+    ///
+    /// # 引数
+    ///
+    /// * `yes` - `false`の場合、未知語のグループ化を無効にします
+    pub fn set_unk_grouping(&mut self, yes: bool) {
+        self.tokenizer.unk_grouping = yes;
+        self.lattice_len = None;
     }
 
     /// 設定された入力文をトークン化します。
@@ -84,13 +507,122 @@ impl Worker {
     /// トークン化結果は内部状態に保存され、`token_iter()`や`token()`メソッドで
     /// アクセスできます。空の文が設定されている場合は何も行いません。
     pub fn tokenize(&mut self) {
+        #[cfg(feature = "stats")]
+        let start = self.reset_stats();
         if self.sent.chars().is_empty() {
             return;
         }
-        let lattice_1best = self.lattice.prepare_for_1best(self.sent.len_char());
-
-        self.tokenizer.build_lattice(&self.sent, lattice_1best);
+        // The N-best paths refer to nodes of the lattice being replaced.
+        self.nbest_paths.clear();
+        self.top_nodes.clear();
+        let len_char = self.sent.len_char();
+        let lattice_1best = match (self.lattice_len.take(), &mut self.lattice) {
+            (Some(prefix_len), LatticeKind::For1Best(lattice)) => {
+                self.tokenizer.extend_lattice_for_append(&self.sent, lattice, prefix_len);
+                lattice
+            }
+            _ => {
+                let lattice = self.lattice.prepare_for_1best(len_char);
+                self.tokenizer.build_lattice(&self.sent, lattice);
+                lattice
+            }
+        };
         lattice_1best.append_top_nodes(&mut self.top_nodes);
+        self.lattice_len = Some(len_char);
+        self.insert_space_nodes();
+        #[cfg(feature = "stats")]
+        self.record_stats(start.elapsed());
+    }
+
+    /// [`Tokenizer::keep_space_tokens()`]が有効な場合、最良パスに空白トークンを挿入します。
+    ///
+    /// スペースを無視して構築したラティスでは、各ノードの`start_node`から`start_word`までが
+    /// 読み飛ばされたスペースです。`top_nodes`は文末側から並んでいることに注意してください。
+    fn insert_space_nodes(&mut self) {
+        if !self.tokenizer.keeps_space_tokens() {
+            return;
+        }
+        let space_node = |start: usize, min_cost: i32| Node {
+            word_id: WordIdx::SPACE.word_id,
+            lex_type: WordIdx::SPACE.lex_type,
+            start_node: start,
+            start_word: start,
+            left_id: 0,
+            right_id: 0,
+            word_cost: 0,
+            min_idx: 0,
+            min_cost,
+            lpath: NO_PATH,
+        };
+        let len_char = self.sent.len_char();
+        let mut nodes = Vec::with_capacity(self.top_nodes.len() * 2 + 1);
+        let (end, cost) = self.top_nodes.first().map_or((0, 0), |(end, node)| (*end, node.min_cost));
+        if end < len_char {
+            nodes.push((len_char, space_node(end, cost)));
+        }
+        for (i, &(end_word, node)) in self.top_nodes.iter().enumerate() {
+            nodes.push((end_word, node));
+            if node.start_node < node.start_word {
+                let cost = self.top_nodes.get(i + 1).map_or(0, |(_, prev)| prev.min_cost);
+                nodes.push((node.start_word, space_node(node.start_node, cost)));
+            }
+        }
+        self.top_nodes = nodes;
+    }
+
+    /// 設定された入力文を、処理を分割しながらトークン化するための状態機械を返します。
+    ///
+    /// 返された[`StepwiseTokenization`]の[`step()`](StepwiseTokenization::step)を
+    /// 呼び出すたびに、指定された文字位置数だけラティスの構築が進みます。
+    /// WASMやシングルスレッドの非同期ランタイムなど、スレッドを使用できない環境で
+    /// 長い文を解析する際に、呼び出しの合間にイベントループへ制御を戻すことができます。
+    ///
+    /// 構築が完了すると、結果は[`Self::tokenize()`]と同様に`token_iter()`や`token()`で
+    /// アクセスできます。完了前に状態機械を破棄した場合、トークン化結果は空になります。
+    ///
+    /// # 戻り値
+    ///
+    /// このワーカーを借用する[`StepwiseTokenization`]
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("とても長い文章");
+    /// let mut stepwise = worker.tokenize_stepwise();
+    /// while !stepwise.step(64) {
+    ///     // Yield to the event loop here.
+    /// }
+    /// drop(stepwise);
+    ///
+    /// for token in worker.token_iter() {
+    ///     println!("{}", token.surface());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn tokenize_stepwise(&mut self) -> StepwiseTokenization<'_> {
+        #[cfg(feature = "stats")]
+        self.reset_stats();
+        self.top_nodes.clear();
+        self.nbest_paths.clear();
+        self.lattice_len = None;
+        let finished = self.sent.chars().is_empty();
+        let cursor = if finished {
+            LatticeCursor::default()
+        } else {
+            let lattice_1best = self.lattice.prepare_for_1best(self.sent.len_char());
+            self.tokenizer.start_lattice(&self.sent, lattice_1best)
+        };
+        StepwiseTokenization {
+            worker: self,
+            cursor,
+            finished,
+        }
     }
 
     /// 文をトークン化し、上位N個の最良結果を内部に保存します。
@@ -101,23 +633,158 @@ impl Worker {
     /// # 引数
     ///
     /// * `n` - 取得する候補パスの最大数
+    ///
+    /// [`with_nbest_budget()`](Self::with_nbest_budget)で設定した上限を超えた場合は、
+    /// 1-best解のみが保存されます。
     pub fn tokenize_nbest(&mut self, n: usize) {
+        // The result is always Ok when falling back to the 1-best solution.
+        let _ = self.tokenize_nbest_inner(n, true);
+    }
+
+    /// 文をトークン化し、上位N個の最良結果を内部に保存します。
+    ///
+    /// [`tokenize_nbest()`](Self::tokenize_nbest)と異なり、
+    /// [`with_nbest_budget()`](Self::with_nbest_budget)で設定した上限を超えた場合は
+    /// 1-best解に切り替えずにエラーを返します。
+    ///
+    /// # 引数
+    ///
+    /// * `n` - 取得する候補パスの最大数
+    ///
+    /// # エラー
+    ///
+    /// ラティスの大きさが上限を超えた場合、[`VibratoError::ResourceLimit`]が返されます。
+    /// このとき、N-bestパスは保存されません。
+    pub fn try_tokenize_nbest(&mut self, n: usize) -> Result<()> {
+        self.tokenize_nbest_inner(n, false)
+    }
+
+    fn tokenize_nbest_inner(&mut self, n: usize, fallback: bool) -> Result<()> {
+        #[cfg(feature = "stats")]
+        let start = self.reset_stats();
         self.nbest_paths.clear();
+        self.lattice_len = None;
+        self.nbest_budget_exceeded = false;
         if self.sent.chars().is_empty() {
-            return;
+            return Ok(());
         }
         let lattice_nbest = self.lattice.prepare_for_nbest(self.sent.len_char());
+        lattice_nbest.set_budget(self.nbest_budget);
+        lattice_nbest.set_best_only(false);
 
         self.tokenizer.build_lattice_nbest(&self.sent, lattice_nbest);
 
-        let dict_ref = self.tokenizer.dictionary();
+        // The search gets whatever the lattice left of the budget.
+        let paths = if lattice_nbest.budget_exceeded() {
+            None
+        } else {
+            let search_budget = self
+                .nbest_budget
+                .map(|budget| budget.saturating_sub(lattice_nbest.used_bytes()));
+            Self::search_nbest(lattice_nbest, &self.tokenizer, &self.sent, n, search_budget)
+        };
+        self.nbest_paths = match paths {
+            Some(paths) => paths,
+            None => {
+                self.nbest_budget_exceeded = true;
+                if !fallback {
+                    return Err(VibratoError::resource_limit(
+                        "N-best lattice",
+                        self.nbest_budget.unwrap_or(usize::MAX),
+                    ));
+                }
+                // Only the best connections are kept, so the arena grows as in the 1-best
+                // lattice and the search finds a single path.
+                lattice_nbest.reset(self.sent.len_char());
+                lattice_nbest.set_best_only(true);
+                self.tokenizer.build_lattice_nbest(&self.sent, lattice_nbest);
+                Self::search_nbest(lattice_nbest, &self.tokenizer, &self.sent, n, None)
+                    .unwrap_or_default()
+            }
+        };
+        #[cfg(feature = "stats")]
+        self.record_stats(start.elapsed());
+        Ok(())
+    }
+
+    /// 構築済みのラティスからA*探索でコストの小さい順に最大`n`個のパスを求めます。
+    ///
+    /// # 戻り値
+    ///
+    /// 見つかったパス。探索中に確保したメモリが`budget`を超えた場合は`None`
+    fn search_nbest(
+        lattice_nbest: &LatticeNBest,
+        tokenizer: &Tokenizer,
+        sent: &Sentence,
+        n: usize,
+        budget: Option<usize>,
+    ) -> Option<Vec<(Vec<u32>, i32)>> {
+        let dict_ref = tokenizer.dictionary();
         let connector_ref = dict_ref.connector();
 
-        let generator = match connector_ref {
-            ConnectorKindRef::Archived(connector) => NbestGenerator::new(lattice_nbest, connector, dict_ref),
-            ConnectorKindRef::Owned(connector) => NbestGenerator::new(lattice_nbest, connector, dict_ref),
+        let mut generator = match connector_ref {
+            ConnectorKindRef::Archived(connector) => NbestGenerator::new(lattice_nbest, connector, tokenizer, sent),
+            ConnectorKindRef::Owned(connector) => NbestGenerator::new(lattice_nbest, connector, tokenizer, sent),
         };
-        self.nbest_paths = generator.take(n).collect();
+        generator.set_budget(budget);
+        let paths = generator.by_ref().take(n).collect();
+        (!generator.budget_exceeded()).then_some(paths)
+    }
+
+    /// 文をN-best解析し、各パスを選択した素性列の系列として返します。
+    ///
+    /// リランカーやCRFの学習データを作成する用途を想定しています。素性のCSVは
+    /// 単語ごとに一度だけ解析され、選択された列の組は[`FeatureSequences`]内で
+    /// 共有されます。区間と選択された素性の系列が同一になるパスは、コストが最小のもの
+    /// のみが残されます。
+    ///
+    /// 内部で[`Self::tokenize_nbest()`]を呼び出すため、この関数の呼び出し後は
+    /// `nbest_token_iter()`などでも同じN-best解にアクセスできます。
+    ///
+    /// # 引数
+    ///
+    /// * `k` - 取得する候補パスの最大数（重複の除去前）
+    /// * `feature_indices` - 選択する素性のインデックス。空の場合はすべての素性を選択します。
+    ///   存在しない列は`*`として扱われます。
+    ///
+    /// # 戻り値
+    ///
+    /// コストの昇順に並んだパスの[`FeatureSequences`]
+    pub fn nbest_feature_sequences(&mut self, k: usize, feature_indices: &[usize]) -> FeatureSequences {
+        self.tokenize_nbest(k);
+
+        let mut result = FeatureSequences::default();
+        let mut feature_ids = HashMap::new();
+        let mut feature_table = HashMap::new();
+        let mut seen = HashSet::new();
+        for path_idx in 0..self.num_nbest_paths() {
+            let mut tokens = vec![];
+            for token in self.nbest_token_iter(path_idx).unwrap() {
+                let feature_id = *feature_ids.entry(token.word_idx()).or_insert_with(|| {
+                    let features = utils::parse_csv_row(token.feature());
+                    let selected: Vec<_> = if feature_indices.is_empty() {
+                        features
+                    } else {
+                        feature_indices
+                            .iter()
+                            .map(|&i| features.get(i).map_or_else(|| "*".to_string(), Clone::clone))
+                            .collect()
+                    };
+                    *feature_table.entry(selected.clone()).or_insert_with(|| {
+                        result.features.push(selected);
+                        u32::try_from(result.features.len() - 1).unwrap()
+                    })
+                });
+                tokens.push((token.range_char(), feature_id));
+            }
+            if seen.insert(tokens.clone()) {
+                result.paths.push(FeatureSequence {
+                    cost: self.path_cost(path_idx).unwrap(),
+                    tokens,
+                });
+            }
+        }
+        result
     }
 
     /// トークン化結果のトークン数を取得します。
@@ -155,6 +822,84 @@ impl Worker {
         TokenIter::new(self)
     }
 
+    /// トークン化結果のトークン列へのビューを取得します。
+    ///
+    /// # 戻り値
+    ///
+    /// 長さの取得や位置を指定したアクセスができる[`Tokens`]
+    #[inline(always)]
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens::new(self)
+    }
+
+    /// トークン化結果を所有型の[`TokenBuf`]のベクターに変換します。
+    ///
+    /// 結果のベクターはトークン数に合わせて一度だけ確保されます。
+    /// 表層形と素性の複製が不要な場合は[`token_refs()`](Self::token_refs)を使用してください。
+    ///
+    /// # 戻り値
+    ///
+    /// 先頭から順に並んだトークンのベクター
+    pub fn to_token_bufs(&self) -> Vec<TokenBuf> {
+        let mut tokens = Vec::with_capacity(self.num_tokens());
+        tokens.extend(self.token_iter().map(|t| t.to_buf()));
+        tokens
+    }
+
+    /// トークン化結果を、表層形と素性を借用する[`TokenRef`]のベクターに変換します。
+    ///
+    /// 文字列を複製しないため、[`to_token_bufs()`](Self::to_token_bufs)より低コストです。
+    /// 結果はこのワーカーを借用するため、次の入力文を設定する前に使用してください。
+    ///
+    /// # 戻り値
+    ///
+    /// 先頭から順に並んだトークンのベクター
+    pub fn token_refs(&self) -> Vec<TokenRef<'_>> {
+        let mut tokens = Vec::with_capacity(self.num_tokens());
+        tokens.extend(self.token_iter().map(|t| t.to_ref()));
+        tokens
+    }
+
+    /// トークン化結果からふりがな（ルビ）を生成します。
+    ///
+    /// 各トークンの素性の`reading_field`番目（0始まり）の項目を読みとして扱い、
+    /// 表層形中の漢字部分に対応する読みを[`furigana::align()`]で求めます。
+    /// 漢字を含まないトークンや、読みが存在しない（または`*`の）トークンは無視されます。
+    ///
+    /// HTML の`<ruby>`要素として出力するには[`furigana::to_html()`]を使用してください。
+    ///
+    /// # 引数
+    ///
+    /// * `reading_field` - 素性中の読みの位置（例: IPADICでは`7`）
+    ///
+    /// # 戻り値
+    ///
+    /// 入力文中の位置順に並んだ[`Furigana`]のベクター
+    pub fn furigana(&self, reading_field: usize) -> Vec<Furigana> {
+        let mut results = vec![];
+        for token in self.token_iter() {
+            let features = utils::parse_csv_row(token.feature());
+            let Some(reading) = features.get(reading_field) else {
+                continue;
+            };
+            if reading == "*" {
+                continue;
+            }
+            let offset = token.range_char().start;
+            for (range, ruby) in furigana::align(token.surface(), reading) {
+                let start_char = offset + range.start;
+                let end_char = offset + range.end;
+                results.push(Furigana {
+                    range_char: start_char..end_char,
+                    range_byte: self.sent.byte_position(start_char)
+                        ..self.sent.byte_position(end_char),
+                    ruby,
+                });
+            }
+        }
+        results
+    }
+
     /// `path_idx`で指定されたN-bestパスのトークンイテレータを返します。
     ///
     /// # 引数
@@ -172,6 +917,21 @@ impl Worker {
         }
     }
 
+    /// N-bestパスが参照するラティス内のノードを取得します。
+    ///
+    /// # パニック
+    ///
+    /// N-best用のラティスが構築されていない場合、または`idx`が範囲外の場合
+    #[inline(always)]
+    pub(crate) fn nbest_node(&self, idx: u32) -> &Node {
+        match &self.lattice {
+            LatticeKind::ForNBest(lattice) => lattice.node(idx),
+            LatticeKind::For1Best(_) => {
+                unreachable!("N-best paths are cleared when the lattice is prepared for 1-best")
+            }
+        }
+    }
+
     /// 接続IDの出現確率を計算するためのカウンタを初期化します。
     ///
     /// この関数は、接続IDの統計情報を収集する前に呼び出す必要があります。
@@ -234,4 +994,643 @@ impl Worker {
     pub fn path_cost(&self, path_idx: usize) -> Option<i32> {
         self.nbest_paths.get(path_idx).map(|(_, cost)| *cost)
     }
+
+    /// `path_idx`で指定されたパスのコストの内訳を返します。
+    ///
+    /// 音声認識や言語モデルの仮説を再順位付けする際に、各トークンの単語コストと
+    /// トークン間の接続コストを独自の特徴量と組み合わせる用途を想定しています。
+    ///
+    /// # 引数
+    ///
+    /// * `path_idx` - パスのインデックス
+    ///
+    /// # 戻り値
+    ///
+    /// パスが存在する場合は`Some(内訳)`、存在しない場合は`None`
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("形態素解析");
+    /// worker.tokenize_nbest(3);
+    /// for i in 0..worker.num_nbest_paths() {
+    ///     let details = worker.path_details(i).unwrap();
+    ///     let word_cost: i32 = details.word_costs.iter().sum();
+    ///     let conn_cost: i32 = details.connection_costs.iter().sum();
+    ///     assert_eq!(word_cost + conn_cost, details.cost);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn path_details(&self, path_idx: usize) -> Option<PathDetails> {
+        let (nodes, cost) = self.nbest_paths.get(path_idx)?;
+        let LatticeKind::ForNBest(lattice) = &self.lattice else {
+            unreachable!("N-best paths are cleared when the lattice is prepared for 1-best")
+        };
+        let connector: &dyn ConnectorCost = match self.tokenizer.dictionary().connector() {
+            ConnectorKindRef::Archived(connector) => connector,
+            ConnectorKindRef::Owned(connector) => connector,
+        };
+
+        let path: Vec<&Node> = std::iter::once(lattice.bos_node())
+            .chain(nodes.iter().map(|&idx| lattice.node(idx)))
+            .chain(lattice.eos_node())
+            .collect();
+        let mut word_costs = Vec::with_capacity(nodes.len());
+        let mut connection_costs = Vec::with_capacity(nodes.len() + 1);
+        for (i, pair) in path.windows(2).enumerate() {
+            let (left, right) = (pair[0], pair[1]);
+            // The right node ends where the next node starts.
+            let end = path.get(i + 2).map_or(lattice.len_char(), |next| next.start_node);
+            let conn_cost = match self.tokenizer.edge_scorer_ref() {
+                Some(scorer) => ScoredConnector::new(connector, scorer, &self.sent)
+                    .edge_cost(left, right, end),
+                None => connector.edge_cost(left, right, end),
+            };
+            connection_costs.push(conn_cost);
+            if right.is_eos() {
+                break;
+            }
+            word_costs.push(i32::from(right.word_cost));
+        }
+
+        Some(PathDetails {
+            cost: *cost,
+            word_costs,
+            connection_costs,
+        })
+    }
+
+    /// 隣接する文字の間に単語境界がある確率を、ラティス上の前向き・後ろ向きアルゴリズムで
+    /// 計算します。
+    ///
+    /// パスの確率は`exp(-cost / temperature)`を全パスで正規化したものです。N-best解を
+    /// 列挙せずにラティス全体を周辺化するため、境界ごとの信頼度を低コストで求められます。
+    /// コストの尺度は辞書ごとに異なるため、`temperature`は辞書に合わせて選んでください。
+    ///
+    /// [`Self::tokenize()`]または[`Self::tokenize_nbest()`]の呼び出し後に使用してください。
+    ///
+    /// # 引数
+    ///
+    /// * `temperature` - コストを確率に変換する際の温度。大きいほど確率が平坦になります
+    ///
+    /// # 戻り値
+    ///
+    /// 長さが文字数より1小さいベクトル。`i`番目の要素は`i`番目と`i+1`番目の文字の間に
+    /// 境界がある確率です。
+    ///
+    /// # エラー
+    ///
+    /// `temperature`が正の有限値でない場合や、設定された文に対するラティスが構築されて
+    /// いない場合に[`VibratoError`]を返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, LoadMode, Tokenizer};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("東京都に行った");
+    /// worker.tokenize();
+    /// for (i, p) in worker.boundary_confidences(500.0)?.iter().enumerate() {
+    ///     println!("{}: {p:.3}", i + 1);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn boundary_confidences(&self, temperature: f64) -> Result<Vec<f64>> {
+        if !temperature.is_finite() || temperature <= 0.0 {
+            return Err(VibratoError::invalid_argument(
+                "temperature",
+                "temperature must be positive and finite.",
+            ));
+        }
+        let len_char = self.sent.len_char();
+        if len_char == 0 {
+            return Ok(vec![]);
+        }
+
+        let (ends, eos) = self
+            .lattice
+            .snapshot()
+            .filter(|(ends, _)| ends.len() == len_char + 1)
+            .ok_or_else(|| {
+                VibratoError::invalid_state(
+                    "The lattice has not been built for the sentence.",
+                    "Call tokenize() or tokenize_nbest() before computing boundary confidences.",
+                )
+            })?;
+
+        let connector: &dyn ConnectorCost = match self.tokenizer.dictionary().connector() {
+            ConnectorKindRef::Archived(connector) => connector,
+            ConnectorKindRef::Owned(connector) => connector,
+        };
+        let scored = self
+            .tokenizer
+            .edge_scorer_ref()
+            .map(|scorer| ScoredConnector::new(connector, scorer, &self.sent));
+        // Negative cost of the edge from `left` to `right` including the word cost of `right`,
+        // scaled by the temperature.
+        let log_weight = |left: &Node, right: &Node, end: usize| {
+            let conn_cost = match &scored {
+                Some(scored) => scored.edge_cost(left, right, end),
+                None => connector.edge_cost(left, right, end),
+            };
+            -f64::from(conn_cost + i32::from(right.word_cost)) / temperature
+        };
+
+        // Forward pass: log-sum of the weights of all paths from BOS to each node.
+        let mut alpha: Vec<Vec<f64>> =
+            ends.iter().map(|nodes| vec![f64::NEG_INFINITY; nodes.len()]).collect();
+        alpha[0].fill(0.0);
+        for end in 1..=len_char {
+            for (i, node) in ends[end].iter().enumerate() {
+                if !node.is_connected_to_bos() {
+                    continue;
+                }
+                let start = node.start_node;
+                alpha[end][i] = log_sum_exp(
+                    ends[start]
+                        .iter()
+                        .zip(&alpha[start])
+                        .map(|(left, &a)| a + log_weight(left, node, end)),
+                );
+            }
+        }
+        let log_z = log_sum_exp(
+            ends[eos.start_node]
+                .iter()
+                .zip(&alpha[eos.start_node])
+                .map(|(left, &a)| a + log_weight(left, &eos, len_char)),
+        );
+        if log_z == f64::NEG_INFINITY {
+            return Err(VibratoError::invalid_state(
+                "The lattice has no path from BOS to EOS.",
+                "",
+            ));
+        }
+
+        // Backward pass: log-sum of the weights of all paths from each node to EOS.
+        let mut beta: Vec<Vec<f64>> =
+            ends.iter().map(|nodes| vec![f64::NEG_INFINITY; nodes.len()]).collect();
+        for (i, left) in ends[eos.start_node].iter().enumerate() {
+            beta[eos.start_node][i] = log_weight(left, &eos, len_char);
+        }
+        for end in (1..=len_char).rev() {
+            for (i, node) in ends[end].iter().enumerate() {
+                if beta[end][i] == f64::NEG_INFINITY {
+                    continue;
+                }
+                let start = node.start_node;
+                for (j, left) in ends[start].iter().enumerate() {
+                    let b = beta[end][i] + log_weight(left, node, end);
+                    beta[start][j] = log_sum_exp([beta[start][j], b]);
+                }
+            }
+        }
+
+        // A boundary at position p exists unless a node spans it, so accumulate the marginal
+        // probabilities of the nodes over the inner positions of their spans.
+        let mut spanned = vec![0.0; len_char + 1];
+        for end in 1..=len_char {
+            for (i, node) in ends[end].iter().enumerate() {
+                let log_marginal = alpha[end][i] + beta[end][i] - log_z;
+                if log_marginal == f64::NEG_INFINITY || node.start_word + 1 >= end {
+                    continue;
+                }
+                let marginal = log_marginal.exp();
+                spanned[node.start_word + 1] += marginal;
+                spanned[end] -= marginal;
+            }
+        }
+        let mut confidences = Vec::with_capacity(len_char - 1);
+        let mut acc = 0.0;
+        for &delta in &spanned[1..len_char] {
+            acc += delta;
+            confidences.push((1.0 - acc).clamp(0.0, 1.0));
+        }
+        Ok(confidences)
+    }
+
+    /// 直前のトークン化の統計情報を返します。
+    ///
+    /// [`Self::tokenize()`]、[`Self::tokenize_nbest()`]、[`Self::tokenize_stepwise()`]の
+    /// 呼び出しごとにリセットされます。段階的なトークン化では、それまでに実行された
+    /// ステップの合計が反映されます。
+    ///
+    /// この関数は`stats`フィーチャーが有効な場合のみ利用できます。
+    ///
+    /// # 戻り値
+    ///
+    /// ラティスのノード数、未知語数、接続コストの評価回数、所要時間を含む[`WorkerStats`]
+    #[cfg(feature = "stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+    #[inline(always)]
+    pub const fn stats(&self) -> &WorkerStats {
+        &self.stats
+    }
+
+    /// 統計情報をリセットし、計測の開始時刻を返します。
+    #[cfg(feature = "stats")]
+    fn reset_stats(&mut self) -> Instant {
+        self.stats = WorkerStats::default();
+        Instant::now()
+    }
+
+    /// ラティスの統計情報を取り込み、所要時間を加算します。
+    #[cfg(feature = "stats")]
+    fn record_stats(&mut self, elapsed: Duration) {
+        let counters = self.lattice.counters();
+        self.stats.num_nodes = counters.num_nodes;
+        self.stats.num_unk_words = counters.num_unk_words;
+        self.stats.num_cost_evaluations = counters.num_cost_evaluations;
+        self.stats.elapsed += elapsed;
+    }
+
+    /// 直前のトークン化で構築されたラティスをGraphViz DOT形式で書き出します。
+    ///
+    /// 各ノードには表層形、素性の先頭の項目（品詞）、単語コスト、BOSからの累積コストが、
+    /// 各エッジには接続コストが付与されます。最良パスのノードとエッジは強調表示されます。
+    /// 出力は`dot -Tsvg`などで画像に変換できます。
+    ///
+    /// [`Self::tokenize()`]または[`Self::tokenize_nbest()`]の呼び出し後に使用してください。
+    /// [`Tokenizer::keep_space_tokens()`]で挿入される空白トークンはラティスに含まれません。
+    ///
+    /// # 引数
+    ///
+    /// * `wtr` - 書き込み先
+    ///
+    /// # エラー
+    ///
+    /// 設定された文に対するラティスが構築されていない場合や、書き込みに失敗した場合に
+    /// [`VibratoError`]を返します。
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use vibrato_rkyv::{Dictionary, LoadMode, Tokenizer};
+    ///
+    /// let dict = Dictionary::from_path("path/to/dict", LoadMode::Validate)?;
+    /// let tokenizer = Tokenizer::new(dict);
+    /// let mut worker = tokenizer.new_worker();
+    ///
+    /// worker.reset_sentence("東京都に行った");
+    /// worker.tokenize();
+    /// worker.dump_lattice_dot(std::fs::File::create("lattice.dot")?)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dump_lattice_dot<W>(&self, mut wtr: W) -> Result<()>
+    where
+        W: Write,
+    {
+        writeln!(wtr, "digraph lattice {{")?;
+        writeln!(wtr, "  graph [rankdir=LR];")?;
+        writeln!(wtr, "  node [shape=box];")?;
+        if self.sent.chars().is_empty() {
+            writeln!(wtr, "}}")?;
+            return Ok(());
+        }
+
+        let (ends, eos) = self
+            .lattice
+            .snapshot()
+            .filter(|(ends, _)| ends.len() == self.sent.len_char() + 1)
+            .ok_or_else(|| {
+                VibratoError::invalid_state(
+                    "The lattice has not been built for the sentence.",
+                    "Call tokenize() or tokenize_nbest() before dumping the lattice.",
+                )
+            })?;
+
+        // Positions of the best-path nodes, traced back from EOS.
+        let mut best = HashSet::new();
+        best.insert((0, 0));
+        let (mut end, mut idx) = (eos.start_node, usize::from(eos.min_idx));
+        while end != 0 {
+            best.insert((end, idx));
+            let node = &ends[end][idx];
+            (end, idx) = (node.start_node, usize::from(node.min_idx));
+        }
+
+        let dict = &self.tokenizer.dict;
+        let best_node = ", style=\"bold,filled\", fillcolor=\"#ffe4b5\"";
+        let best_edge = ", style=bold, color=red";
+
+        writeln!(wtr, "  n0_0 [label=\"BOS\"{best_node}];")?;
+        for (end, nodes) in ends.iter().enumerate().skip(1) {
+            for (i, node) in nodes.iter().enumerate() {
+                let word_idx = node.word_idx();
+                let surface = &self.sent.raw()
+                    [self.sent.byte_position(node.start_word)..self.sent.byte_position(end)];
+                let feature = utils::parse_csv_row(self.tokenizer.word_feature(word_idx));
+                let pos = feature.first().map_or("", String::as_str);
+                let word_cost = node.word_cost;
+                let on_best = best.contains(&(end, i));
+                writeln!(
+                    wtr,
+                    "  n{end}_{i} [label=\"{}\\n{}\\n{word_cost} ({})\"{}];",
+                    escape_dot(surface),
+                    escape_dot(pos),
+                    node.min_cost,
+                    if on_best { best_node } else { "" },
+                )?;
+                for (j, left) in ends[node.start_node].iter().enumerate() {
+                    if !left.is_connected_to_bos() {
+                        continue;
+                    }
+                    let conn_cost = dict.connection_cost(left.right_id, node.left_id);
+                    let on_best = on_best && usize::from(node.min_idx) == j;
+                    writeln!(
+                        wtr,
+                        "  n{}_{j} -> n{end}_{i} [label=\"{conn_cost}\"{}];",
+                        node.start_node,
+                        if on_best { best_edge } else { "" },
+                    )?;
+                }
+            }
+        }
+
+        writeln!(wtr, "  eos [label=\"EOS\\n({})\"{best_node}];", eos.min_cost)?;
+        for (j, left) in ends[eos.start_node].iter().enumerate() {
+            if !left.is_connected_to_bos() {
+                continue;
+            }
+            let conn_cost = dict.connection_cost(left.right_id, eos.left_id);
+            let on_best = usize::from(eos.min_idx) == j;
+            writeln!(
+                wtr,
+                "  n{}_{j} -> eos [label=\"{conn_cost}\"{}];",
+                eos.start_node,
+                if on_best { best_edge } else { "" },
+            )?;
+        }
+        writeln!(wtr, "}}")?;
+        Ok(())
+    }
+}
+
+/// 対数で表された値の和の対数を、オーバーフローしないように計算します。
+///
+/// 空の場合は負の無限大を返します。
+fn log_sum_exp<I>(values: I) -> f64
+where
+    I: IntoIterator<Item = f64>,
+{
+    // Single pass that rescales the running sum whenever the maximum changes.
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.0;
+    for v in values {
+        if v == f64::NEG_INFINITY {
+            continue;
+        }
+        if v > max {
+            sum = sum * (max - v).exp() + 1.0;
+            max = v;
+        } else {
+            sum += (v - max).exp();
+        }
+    }
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + sum.ln()
+}
+
+/// DOT形式の文字列リテラル中で特別な意味を持つ文字をエスケープします。
+fn escape_dot(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 直前のトークン化の統計情報。
+///
+/// [`Worker::stats()`]で取得します。本番環境でのレイテンシの外れ値の原因を
+/// 調べる用途を想定しています。
+#[cfg(feature = "stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// ラティスに挿入されたノード数（BOS/EOSを除く）。
+    pub num_nodes: usize,
+
+    /// ラティスに挿入された未知語のノード数。
+    pub num_unk_words: usize,
+
+    /// 接続コストの評価回数。
+    pub num_cost_evaluations: usize,
+
+    /// トークン化に要した時間。
+    pub elapsed: Duration,
+}
+
+/// 処理を分割して進めるトークン化の状態機械。
+///
+/// [`Worker::tokenize_stepwise()`]によって作成されます。
+pub struct StepwiseTokenization<'w> {
+    worker: &'w mut Worker,
+    cursor: LatticeCursor,
+    finished: bool,
+}
+
+impl StepwiseTokenization<'_> {
+    /// ラティスの構築を最大`max_columns`文字位置分だけ進めます。
+    ///
+    /// 文末まで到達した場合は最良パスを求めてワーカーに保存し、`true`を返します。
+    /// 完了後に呼び出した場合は何もせずに`true`を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `max_columns` - この呼び出しで処理する文字位置の最大数。`0`の場合は`1`とみなします。
+    ///
+    /// # 戻り値
+    ///
+    /// トークン化が完了した場合は`true`
+    pub fn step(&mut self, max_columns: usize) -> bool {
+        if self.finished {
+            return true;
+        }
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
+        let LatticeKind::For1Best(lattice) = &mut self.worker.lattice else {
+            unreachable!("the lattice is prepared for 1-best in tokenize_stepwise()");
+        };
+        self.finished = self.worker.tokenizer.extend_lattice(
+            &self.worker.sent,
+            lattice,
+            &mut self.cursor,
+            max_columns.max(1),
+        );
+        if self.finished {
+            lattice.append_top_nodes(&mut self.worker.top_nodes);
+            self.worker.insert_space_nodes();
+        }
+        #[cfg(feature = "stats")]
+        self.worker.record_stats(start.elapsed());
+        self.finished
+    }
+
+    /// トークン化が完了したかどうかを返します。
+    #[inline(always)]
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 処理済みの文字数と入力文の文字数を返します。
+    ///
+    /// 進捗の表示などに使用できます。
+    ///
+    /// # 戻り値
+    ///
+    /// `(処理済みの文字数, 入力文の文字数)`
+    pub fn progress(&self) -> (usize, usize) {
+        let len_char = self.worker.sent.len_char();
+        if self.finished {
+            (len_char, len_char)
+        } else {
+            (self.cursor.position(), len_char)
+        }
+    }
+}
+
+/// [`Worker::nbest_feature_sequences()`]の結果。
+///
+/// 選択された素性列の組は重複なく保持され、各パスからはIDで参照されます。
+#[derive(Clone, Debug, Default)]
+pub struct FeatureSequences {
+    features: Vec<Vec<String>>,
+    paths: Vec<FeatureSequence>,
+}
+
+impl FeatureSequences {
+    /// パスの数を返します。
+    #[inline(always)]
+    pub fn num_paths(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// `i`番目のパスを返します。
+    ///
+    /// # パニック
+    ///
+    /// `i`がパスの数以上の場合、パニックします。
+    #[inline(always)]
+    pub fn path(&self, i: usize) -> &FeatureSequence {
+        &self.paths[i]
+    }
+
+    /// パスのイテレータを返します。
+    #[inline(always)]
+    pub fn paths(&self) -> impl Iterator<Item = &FeatureSequence> {
+        self.paths.iter()
+    }
+
+    /// 異なる素性列の組の数を返します。
+    #[inline(always)]
+    pub fn num_features(&self) -> usize {
+        self.features.len()
+    }
+
+    /// IDに対応する素性列の組を返します。
+    ///
+    /// # パニック
+    ///
+    /// `feature_id`が素性列の組の数以上の場合、パニックします。
+    #[inline(always)]
+    pub fn features(&self, feature_id: u32) -> &[String] {
+        &self.features[feature_id as usize]
+    }
+}
+
+/// N-bestパスの1つを表す、区間と素性列IDの系列。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureSequence {
+    /// パスの総コスト
+    pub cost: i32,
+    /// 文字単位の区間と、[`FeatureSequences::features()`]で参照する素性列IDの組の系列
+    pub tokens: Vec<(Range<usize>, u32)>,
+}
+
+/// N-bestパスのコストの内訳。
+///
+/// [`Worker::path_details()`]で取得します。すべての単語コストと接続コストの和は
+/// パスの総コストに一致します。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathDetails {
+    /// パスの総コスト
+    pub cost: i32,
+    /// 各トークンの単語コスト。ユーザー辞書の語には
+    /// [`Tokenizer::user_cost_bonus()`]による調整が適用され、近似照合や
+    /// [`Tokenizer::lattice_augmenter()`]で追加された候補には罰則が含まれます。
+    pub word_costs: Vec<i32>,
+    /// 各トークンとその左側（BOSまたは直前のトークン）の間の接続コストと、最後の
+    /// トークンとEOSの間の接続コスト。要素数はトークン数より1つ多くなります。
+    /// [`Tokenizer::edge_scorer()`]による補正値を含みます。
+    pub connection_costs: Vec<i32>,
+}
+
+/// UTF-8として不正なバイト列の扱い。
+///
+/// [`Worker::reset_sentence_bytes()`]で使用します。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum InvalidUtf8 {
+    /// 不正なバイト列をU+FFFDに置換します。
+    #[default]
+    Replace,
+    /// 不正なバイト列を含む入力をエラーとします。
+    Reject,
+}
+
+/// 不正なバイト列を置換した箇所。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utf8Replacement {
+    /// 置換前の入力バイト列上の区間
+    pub input: Range<usize>,
+    /// 入力文（置換後）上の区間
+    pub output: Range<usize>,
+}
+
+/// [`Worker::tokenize_tsv_line()`]で分割したTSV形式の行。
+///
+/// `prefix`、`text`、`suffix`をこの順に連結すると元の行になります。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TsvLine<'a> {
+    /// トークン化した列より前の列と、直後のタブ
+    pub prefix: &'a str,
+    /// トークン化した列
+    pub text: &'a str,
+    /// トークン化した列の直後のタブと、それ以降の列
+    pub suffix: &'a str,
+}
+
+impl<'a> TsvLine<'a> {
+    /// 行を`column`番目の列とその前後に分割します。列が存在しない場合は`None`を返します。
+    fn split(line: &'a str, column: usize) -> Option<Self> {
+        let mut start = 0;
+        for _ in 0..column {
+            start += line[start..].find('\t')? + 1;
+        }
+        let end = line[start..].find('\t').map_or(line.len(), |i| start + i);
+        Some(Self {
+            prefix: &line[..start],
+            text: &line[start..end],
+            suffix: &line[end..],
+        })
+    }
 }
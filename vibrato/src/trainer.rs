@@ -79,6 +79,7 @@
 
 mod config;
 mod corpus;
+mod evaluator;
 mod feature_extractor;
 mod feature_rewriter;
 mod model;
@@ -86,25 +87,61 @@ mod model;
 use std::num::NonZeroU32;
 
 use hashbrown::{HashMap, HashSet};
+use rayon::prelude::*;
 use rucrf_rkyv::{Edge, FeatureProvider, FeatureSet, Lattice};
 
+use crate::dictionary::character::CharProperty;
 use crate::dictionary::word_idx::WordIdx;
 use crate::dictionary::LexType;
-use crate::errors::Result;
+use crate::errors::{Result, VibratoError};
 pub use crate::trainer::config::TrainerConfig;
-pub use crate::trainer::corpus::{Corpus, Example, Word};
+pub use crate::trainer::corpus::{
+    Corpus, CorpusStats, Example, StreamingCorpus, StreamingExamples, Word, WILDCARD_FEATURE,
+};
+pub use crate::trainer::evaluator::{
+    evaluate, evaluate_detailed, evaluate_tokenizer, evaluate_tokenizer_detailed, Bootstrap,
+    Comparison, ConfidenceInterval, Evaluation, Score,
+};
+use crate::trainer::corpus::most_common_columns;
 use crate::trainer::feature_extractor::FeatureExtractor;
 use crate::trainer::feature_rewriter::FeatureRewriter;
-pub use crate::trainer::model::Model;
+pub use crate::trainer::model::{CompressionReport, FeatureKind, FeatureWeight, Model};
 use crate::trainer::model::ModelData;
 use crate::utils::{self, FromU32};
 
+/// 学習時の正則化の種類。
+///
+/// いずれの場合も、正則化の強さは [`Trainer::regularization_cost()`] で指定します。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Regularization {
+    /// L1正則化。
+    ///
+    /// 多くの重みが0になるため、疎で小さいモデルが得られます。
+    #[default]
+    L1,
+
+    /// L2正則化。
+    ///
+    /// 密なモデルになりますが、収束が速く、小規模なコーパスでは精度が向上する傾向があります。
+    L2,
+}
+
+impl Regularization {
+    /// 学習器の正則化の種類に変換します。
+    const fn to_rucrf(self) -> rucrf_rkyv::Regularization {
+        match self {
+            Self::L1 => rucrf_rkyv::Regularization::L1,
+            Self::L2 => rucrf_rkyv::Regularization::L2,
+        }
+    }
+}
+
 /// 形態素解析器のトレーナー。
 ///
 /// 構造化パーセプトロンアルゴリズムを使用して、コーパスから形態素解析モデルを学習します。
 /// 学習では、単語の素性と接続コストを最適化し、正しい形態素分割を実現します。
 pub struct Trainer {
-    config: TrainerConfig,
+    pub(crate) config: TrainerConfig,
     max_grouping_len: Option<usize>,
     provider: FeatureProvider,
 
@@ -113,9 +150,52 @@ pub struct Trainer {
     label_id_map: HashMap<String, HashMap<char, NonZeroU32>>,
 
     label_id_map_unk: Vec<NonZeroU32>,
+    regularization: Regularization,
     regularization_cost: f64,
     max_iter: u64,
     num_threads: usize,
+    batch_size: usize,
+}
+
+/// ラティスの構築に使用する辺の一覧。
+///
+/// 辺の列挙は並列に行い、ラベルIDの割り当てが必要な仮想エッジを含むラティスの
+/// 組み立ては元の順序で逐次的に行います。
+pub(crate) struct LatticeEdges {
+    pub(crate) len: usize,
+    /// 正例の辺。ラベルIDが`None`の辺は、素性を持たない仮想エッジです。
+    pub(crate) positive: Vec<(usize, usize, Option<NonZeroU32>)>,
+    /// 負例の辺
+    pub(crate) negative: Vec<(usize, usize, NonZeroU32)>,
+}
+
+/// [`Trainer::preprocess_batch_size()`]のデフォルト値
+const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// 同じ位置に最初に追加された辺と一致しない場合に`true`を返します。
+///
+/// その位置にまだ辺がない場合は、指定された辺を最初の辺として記録します。
+fn is_new_edge(
+    first_edge: &mut Option<(usize, Option<NonZeroU32>)>,
+    target: usize,
+    label_id: NonZeroU32,
+) -> bool {
+    match first_edge {
+        Some(first) => *first != (target, Some(label_id)),
+        None => {
+            *first_edge = Some((target, Some(label_id)));
+            true
+        }
+    }
+}
+
+/// 項目数ごとの数をログ出力用の文字列に整形します。
+fn format_column_counts(counts: &[(usize, usize)]) -> String {
+    counts
+        .iter()
+        .map(|(columns, n)| format!("{n} with {columns} columns"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl Trainer {
@@ -132,6 +212,7 @@ impl Trainer {
     /// * `right_rewriter` - right素性の書き換え器
     /// * `feature_str` - 素性文字列
     /// * `cate_id` - カテゴリID
+    /// * `char_types` - 表層形の各文字の文字種（未知語の場合は `None`）
     ///
     /// # 戻り値
     ///
@@ -143,12 +224,17 @@ impl Trainer {
         right_rewriter: &FeatureRewriter,
         feature_str: &str,
         cate_id: u32,
+        char_types: Option<&[u32]>,
     ) -> FeatureSet {
         let features = utils::parse_csv_row(feature_str);
         let unigram_features = if let Some(rewrite) = unigram_rewriter.rewrite(&features) {
-            feature_extractor.extract_unigram_feature_ids(&rewrite, cate_id)
+            feature_extractor.extract_unigram_feature_ids_with_char_types(
+                &rewrite, cate_id, char_types,
+            )
         } else {
-            feature_extractor.extract_unigram_feature_ids(&features, cate_id)
+            feature_extractor.extract_unigram_feature_ids_with_char_types(
+                &features, cate_id, char_types,
+            )
         };
         let left_features = if let Some(rewrite) = left_rewriter.rewrite(&features) {
             feature_extractor.extract_left_feature_ids(&rewrite)
@@ -163,9 +249,27 @@ impl Trainer {
         FeatureSet::new(&unigram_features, &right_features, &left_features)
     }
 
+    /// 表層形の各文字の文字種を返します。
+    ///
+    /// # 引数
+    ///
+    /// * `char_prop` - 文字の定義
+    /// * `surface` - 表層形
+    ///
+    /// # 戻り値
+    ///
+    /// 各文字の基本カテゴリIDのリスト
+    fn surface_char_types(char_prop: &CharProperty, surface: &str) -> Vec<u32> {
+        surface
+            .chars()
+            .map(|c| char_prop.char_info(c).base_id())
+            .collect()
+    }
+
     /// 指定された設定を使用して新しい [`Trainer`] を作成します。
     ///
     /// 辞書内の全単語と未知語に対して素性セットを抽出し、ラベルIDを割り当てます。
+    /// シード辞書の語の素性の項目数が揃っていない場合は、警告のログを出力します。
     ///
     /// # 引数
     ///
@@ -191,6 +295,10 @@ impl Trainer {
                 .next()
                 .unwrap();
             let cate_id = config.dict.char_prop().char_info(first_char).base_id();
+            let char_types = Self::surface_char_types(
+                config.dict.char_prop(),
+                &config.surfaces[usize::from_u32(word_id)],
+            );
             let feature_set = Self::extract_feature_set(
                 &mut config.feature_extractor,
                 &config.unigram_rewriter,
@@ -198,6 +306,7 @@ impl Trainer {
                 &config.right_rewriter,
                 feature_str,
                 cate_id,
+                Some(&char_types),
             );
             let label_id = provider.add_feature_set(feature_set)?;
             label_id_map
@@ -218,23 +327,50 @@ impl Trainer {
                 &config.right_rewriter,
                 feature_str,
                 cate_id,
+                None,
             );
             label_id_map_unk.push(provider.add_feature_set(feature_set)?);
         }
 
+        let column_counts = config.feature_column_counts();
+        if column_counts.len() > 1 {
+            log::warn!(
+                "[vibrato-rkyv] The features in the seed lexicon have inconsistent numbers of columns: {}",
+                format_column_counts(&column_counts),
+            );
+        }
+
         Ok(Self {
             config,
             max_grouping_len: None,
             provider,
             label_id_map,
             label_id_map_unk,
+            regularization: Regularization::L1,
             regularization_cost: 0.01,
             max_iter: 100,
             num_threads: 1,
+            batch_size: DEFAULT_BATCH_SIZE,
         })
     }
 
-    /// L1正則化のコストを変更します。
+    /// 正則化の種類を変更します。
+    ///
+    /// デフォルト値は [`Regularization::L1`] です。
+    ///
+    /// # 引数
+    ///
+    /// * `regularization` - 正則化の種類
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新されたトレーナー
+    pub const fn regularization(mut self, regularization: Regularization) -> Self {
+        self.regularization = regularization;
+        self
+    }
+
+    /// 正則化のコストを変更します。
     ///
     /// この値が大きいほど、正則化が強くなります。
     /// デフォルト値は 0.01 です。
@@ -279,6 +415,7 @@ impl Trainer {
 
     /// マルチスレッドを有効化します。
     ///
+    /// 指定したスレッド数は、学習そのものに加えて、学習前のラティスの構築にも使用されます。
     /// デフォルト値は 1（シングルスレッド）です。
     ///
     /// # 引数
@@ -298,6 +435,30 @@ impl Trainer {
         self
     }
 
+    /// 学習前のラティスの構築を一度に行う例文の数を変更します。
+    ///
+    /// ラティスはこの数の例文ごとにまとめて構築され、構築に使用した例文のデータは
+    /// 次のまとまりの処理の前に解放されます。値を小さくすると大規模なコーパスでの
+    /// ピークメモリ使用量が減り、大きくすると並列処理の効率が上がります。
+    /// デフォルト値は 10000 です。
+    ///
+    /// # 引数
+    ///
+    /// * `n` - 一度に処理する例文の数（1以上の値）
+    ///
+    /// # 戻り値
+    ///
+    /// 設定が更新されたトレーナー
+    ///
+    /// # パニック
+    ///
+    /// 値が1未満の場合、パニックします。
+    pub fn preprocess_batch_size(mut self, n: usize) -> Self {
+        assert!(n >= 1);
+        self.batch_size = n;
+        self
+    }
+
     /// 未知語の最大グルーピング長を指定します。
     ///
     /// デフォルトでは、長さは無制限です。
@@ -322,22 +483,22 @@ impl Trainer {
         self
     }
 
-    /// 訓練例からラティスを構築します。
+    /// 訓練例からラティスを構築するための辺を列挙します。
+    ///
+    /// 正解パスのエッジ（正例）と辞書に含まれる全ての候補エッジ（負例）を列挙します。
     ///
-    /// 正解パスのエッジ（正例）と辞書に含まれる全ての候補エッジ（負例）を追加します。
+    /// 未注釈のトークンが連続する区間は、素性を持たない1つの仮想エッジで覆われ、
+    /// その区間と重なる負例は追加されません。学習器は正解パスを1本しか扱えないため、
+    /// このようにして未注釈の区間を学習の制約から外します。
     ///
     /// # 引数
     ///
-    /// * `example` - 訓練例
+    /// * `example` - 訓練例。文はコンパイル済みである必要があります。
     ///
     /// # 戻り値
     ///
-    /// 構築されたラティス
-    ///
-    /// # エラー
-    ///
-    /// ラティスの構築に失敗した場合、[`VibratoError`](crate::errors::VibratoError) が返されます。
-    fn build_lattice(&mut self, example: &Example) -> Result<Lattice> {
+    /// 列挙された辺の一覧
+    pub(crate) fn collect_edges(&self, example: &Example) -> LatticeEdges {
         let Example { sentence, tokens } = example;
 
         let input_chars = sentence.chars();
@@ -348,50 +509,69 @@ impl Trainer {
         // 2. If the word is not found in the dictionary:
         //   a) If a compatible unknown word is found, add the unknown word edge instead.
         //   b) If there is no available word, add a virtual edge, which does not have any features.
-        let mut edges = vec![];
+        //
+        // Runs of unannotated tokens are covered by a single virtual edge instead.
+        let mut positive = vec![];
+        let mut unannotated = vec![false; input_len];
         let mut pos = 0;
-        for token in tokens {
+        let mut tokens = tokens.iter().peekable();
+        while let Some(token) = tokens.next() {
             let len = token.surface().chars().count();
+            if !token.is_annotated() {
+                let start = pos;
+                pos += len;
+                while let Some(token) = tokens.next_if(|t| !t.is_annotated()) {
+                    pos += token.surface().chars().count();
+                }
+                unannotated[start..pos].fill(true);
+                positive.push((start, pos, None));
+                continue;
+            }
             let first_char = input_chars[pos];
             let label_id = self
                 .label_id_map
                 .get(token.feature())
                 .and_then(|hm| hm.get(&first_char))
                 .cloned()
-                .map(Ok)
-                .unwrap_or_else(|| {
-                    self.config
+                .or_else(|| {
+                    let unk_index = self
+                        .config
                         .dict
                         .unk_handler()
-                        .compatible_unk_index(sentence, pos, pos + len, token.feature())
-                        .map_or_else(
-                            || {
-                                eprintln!(
-                                    "adding virtual edge: {} {}",
-                                    token.surface(),
-                                    token.feature()
-                                );
-                                self.provider
-                                    .add_feature_set(FeatureSet::new(&[], &[], &[]))
-                            },
-                            |unk_index| {
-                                Ok(self.label_id_map_unk[usize::from_u32(unk_index.word_id)])
-                            },
-                        )
-                })?;
-            edges.push((pos, Edge::new(pos + len, label_id)));
+                        .compatible_unk_index(sentence, pos, pos + len, token.feature());
+                    if unk_index.is_none() {
+                        log::warn!(
+                            "[vibrato-rkyv] Adding a virtual edge at position {pos}: surface={:?}, feature={:?}",
+                            token.surface(),
+                            token.feature()
+                        );
+                    }
+                    unk_index
+                        .map(|unk_index| self.label_id_map_unk[usize::from_u32(unk_index.word_id)])
+                });
+            positive.push((pos, pos + len, label_id));
             pos += len;
         }
         assert_eq!(pos, input_len);
 
-        let mut lattice = Lattice::new(input_len).unwrap();
+        // first_edges[i] is the first edge added at position i, used to skip duplicates.
+        let mut first_edges: Vec<Option<(usize, Option<NonZeroU32>)>> = vec![None; input_len];
+        for &(pos, target, label_id) in &positive {
+            first_edges[pos].get_or_insert((target, label_id));
+        }
+        let mut negative = vec![];
 
-        for (pos, edge) in edges {
-            lattice.add_edge(pos, edge).unwrap();
+        // next_unannotated[i] is the first unannotated position at or after i.
+        let mut next_unannotated = vec![input_len; input_len + 1];
+        for i in (0..input_len).rev() {
+            next_unannotated[i] = if unannotated[i] { i } else { next_unannotated[i + 1] };
         }
 
         // Add negative edges
         for start_word in 0..input_len {
+            if unannotated[start_word] {
+                continue;
+            }
             let mut has_matched = false;
 
             let suffix = &input_chars[start_word..];
@@ -406,13 +586,14 @@ impl Trainer {
                 let label_id = NonZeroU32::new(m.word_idx.word_id + 1).unwrap();
                 let pos = start_word;
                 let target = pos + m.end_char;
-                let edge = Edge::new(target, label_id);
+                if next_unannotated[pos] < target {
+                    continue;
+                }
                 // Skips adding if the edge is already added as a positive edge.
-                if let Some(first_edge) = lattice.nodes()[pos].edges().first()
-                    && edge == *first_edge {
-                        continue;
-                    }
-                lattice.add_edge(pos, edge).unwrap();
+                if !is_new_edge(&mut first_edges[pos], target, label_id) {
+                    continue;
+                }
+                negative.push((pos, target, label_id));
             }
 
             self.config.dict.unk_handler().gen_unk_words(
@@ -420,25 +601,164 @@ impl Trainer {
                 start_word,
                 has_matched,
                 self.max_grouping_len,
+                true,
                 |w| {
                     let id_offset = u32::try_from(self.config.surfaces.len()).unwrap();
                     let label_id = NonZeroU32::new(id_offset + w.word_idx().word_id + 1).unwrap();
                     let pos = start_word;
                     let target = w.end_char();
-                    let edge = Edge::new(target, label_id);
+                    if next_unannotated[pos] < target {
+                        return;
+                    }
                     // Skips adding if the edge is already added as a positive edge.
-                    if let Some(first_edge) = lattice.nodes()[pos].edges().first()
-                        && edge == *first_edge {
-                            return;
-                        }
-                    lattice.add_edge(pos, edge).unwrap();
+                    if !is_new_edge(&mut first_edges[pos], target, label_id) {
+                        return;
+                    }
+                    negative.push((pos, target, label_id));
                 },
             );
         }
 
+        LatticeEdges {
+            len: input_len,
+            positive,
+            negative,
+        }
+    }
+
+    /// 列挙された辺からラティスを組み立てます。
+    ///
+    /// 仮想エッジには、ここで新しいラベルIDを割り当てます。
+    ///
+    /// # 引数
+    ///
+    /// * `edges` - [`collect_edges()`](Self::collect_edges)で列挙された辺
+    ///
+    /// # 戻り値
+    ///
+    /// 構築されたラティス
+    ///
+    /// # エラー
+    ///
+    /// ラベルIDの割り当てに失敗した場合、[`VibratoError`] が返されます。
+    fn assemble_lattice(&mut self, edges: LatticeEdges) -> Result<Lattice> {
+        let mut lattice = Lattice::new(edges.len).unwrap();
+        for (pos, target, label_id) in edges.positive {
+            let label_id = match label_id {
+                Some(label_id) => label_id,
+                None => self.provider.add_feature_set(FeatureSet::new(&[], &[], &[]))?,
+            };
+            lattice.add_edge(pos, Edge::new(target, label_id)).unwrap();
+        }
+        for (pos, target, label_id) in edges.negative {
+            lattice.add_edge(pos, Edge::new(target, label_id)).unwrap();
+        }
         Ok(lattice)
     }
 
+    /// 全例文からラティスを構築します。
+    ///
+    /// 例文は[`preprocess_batch_size()`](Self::preprocess_batch_size)ごとにまとめて
+    /// イテレータから取り出され、処理されます。
+    /// 各まとまりの中では、文のコンパイルと辺の列挙を[`num_threads()`](Self::num_threads)で
+    /// 指定したスレッド数で並列に行い、ラティスの組み立てを元の順序で行います。
+    /// そのため、結果はスレッド数やまとまりの大きさに依存しません。
+    ///
+    /// # 引数
+    ///
+    /// * `examples` - 学習に使用する例文のイテレータ
+    ///
+    /// # 戻り値
+    ///
+    /// 例文と同じ順序のラティス
+    ///
+    /// # エラー
+    ///
+    /// 例文の読み込み、スレッドプールの作成、ラティスの構築に失敗した場合や、
+    /// コーパスの素性の項目数がシード辞書と異なる場合、[`VibratoError`] が返されます。
+    fn build_lattices<I>(&mut self, examples: I) -> Result<Vec<Lattice>>
+    where
+        I: IntoIterator<Item = Result<Example>>,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .build()
+            .map_err(|e| {
+                VibratoError::invalid_state("Failed to create a thread pool.", e.to_string())
+            })?;
+
+        let mut examples = examples.into_iter();
+        let mut lattices = Vec::with_capacity(examples.size_hint().0);
+        let mut column_counts = HashMap::new();
+        loop {
+            let mut batch: Vec<_> = examples
+                .by_ref()
+                .take(self.batch_size)
+                .collect::<Result<_>>()?;
+            if batch.is_empty() {
+                break;
+            }
+            for token in batch.iter().flat_map(|e| e.tokens()).filter(|t| t.is_annotated()) {
+                *column_counts
+                    .entry(utils::parse_csv_row(token.feature()).len())
+                    .or_insert(0) += 1;
+            }
+            let this = &*self;
+            let edges: Vec<_> = pool.install(|| {
+                batch
+                    .par_iter_mut()
+                    .map(|example| {
+                        example.sentence.compile(this.config.dict.char_prop());
+                        this.collect_edges(example)
+                    })
+                    .collect()
+            });
+            drop(batch);
+            for edges in edges {
+                lattices.push(self.assemble_lattice(edges)?);
+            }
+        }
+        let mut column_counts: Vec<_> = column_counts.into_iter().collect();
+        column_counts.sort_unstable();
+        self.check_column_counts(&column_counts)?;
+        Ok(lattices)
+    }
+
+    /// コーパスの素性の項目数がシード辞書と揃っているかを確認します。
+    ///
+    /// 項目数が異なるトークンは辞書の語と一致しないため、警告のログを出力します。
+    ///
+    /// # 引数
+    ///
+    /// * `column_counts` - 注釈されたトークンの素性の項目数と、その項目数のトークンの数の組
+    ///
+    /// # エラー
+    ///
+    /// コーパスで最も多い項目数がシード辞書と異なる場合、[`VibratoError`] が返されます。
+    fn check_column_counts(&self, column_counts: &[(usize, usize)]) -> Result<()> {
+        let Some(expected) = most_common_columns(&self.config.feature_column_counts()) else {
+            return Ok(());
+        };
+        let Some(actual) = most_common_columns(column_counts) else {
+            return Ok(());
+        };
+        if actual != expected {
+            return Err(VibratoError::invalid_format(
+                "corpus",
+                format!(
+                    "The features in the corpus have {actual} columns, but those in the seed lexicon have {expected} columns.",
+                ),
+            ));
+        }
+        if column_counts.len() > 1 {
+            log::warn!(
+                "[vibrato-rkyv] Some features in the corpus do not have {expected} columns as in the seed lexicon: {}",
+                format_column_counts(column_counts),
+            );
+        }
+        Ok(())
+    }
+
     /// 学習を開始し、モデルを返します。
     ///
     /// コーパス内の各例文からラティスを構築し、構造化パーセプトロンによって
@@ -454,17 +774,48 @@ impl Trainer {
     ///
     /// # エラー
     ///
-    /// 文のコンパイルやラティスの構築に失敗した場合、
-    /// [`VibratoError`](crate::errors::VibratoError) が返されます。
-    pub fn train(mut self, mut corpus: Corpus) -> Result<Model> {
-        let mut lattices = vec![];
-        for example in &mut corpus.examples {
-            example.sentence.compile(self.config.dict.char_prop());
-            lattices.push(self.build_lattice(example)?);
-        }
+    /// 文のコンパイルやラティスの構築に失敗した場合や、コーパスの素性の項目数が
+    /// シード辞書と異なる場合、[`VibratoError`](crate::errors::VibratoError) が返されます。
+    pub fn train(self, corpus: Corpus) -> Result<Model> {
+        self.train_examples(corpus.examples.into_iter().map(Ok))
+    }
+
+    /// 逐次的に読み込むコーパスで学習を開始し、モデルを返します。
+    ///
+    /// 例文はファイルから[`preprocess_batch_size()`](Self::preprocess_batch_size)ずつ
+    /// 読み込まれ、ラティスの構築後に解放されます。[`Trainer::train()`]と比べて、
+    /// コーパスの文字列と例文の分だけピークメモリ使用量が減ります。
+    ///
+    /// 学習の反復は構築済みのラティスを走査するため、全例文のラティスはメモリ上に
+    /// 保持されます。ラティスはコーパスの文字列よりはるかに大きいため、必要なメモリ量は
+    /// ラティスの大きさで決まり、メモリに収まらない大きさのコーパスは学習できません。
+    ///
+    /// # 引数
+    ///
+    /// * `corpus` - 学習に使用するコーパス
+    ///
+    /// # 戻り値
+    ///
+    /// 学習済みモデル
+    ///
+    /// # エラー
+    ///
+    /// コーパスの読み込みやラティスの構築に失敗した場合や、コーパスの素性の項目数が
+    /// シード辞書と異なる場合、[`VibratoError`](crate::errors::VibratoError) が返されます。
+    pub fn train_streaming(self, corpus: &StreamingCorpus) -> Result<Model> {
+        self.train_examples(corpus.iter()?)
+    }
+
+    /// 例文のイテレータで学習を開始し、モデルを返します。
+    fn train_examples<I>(mut self, examples: I) -> Result<Model>
+    where
+        I: IntoIterator<Item = Result<Example>>,
+    {
+        let regularization = self.regularization.to_rucrf();
+        let lattices = self.build_lattices(examples)?;
 
         let trainer = rucrf_rkyv::Trainer::new()
-            .regularization(rucrf_rkyv::Regularization::L1, self.regularization_cost)
+            .regularization(regularization, self.regularization_cost)
             .unwrap()
             .max_iter(self.max_iter)
             .unwrap()
@@ -548,6 +899,7 @@ impl Trainer {
             },
             merged_model: None,
             user_entries: vec![],
+            compressions: vec![],
         })
     }
 }
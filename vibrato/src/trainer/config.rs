@@ -2,6 +2,7 @@
 //!
 //! このモジュールは、形態素解析モデルの学習に必要な設定を管理します。
 
+use std::collections::BTreeMap;
 use std::io::{BufRead, BufReader, Read};
 
 use rkyv::{Archive, Deserialize, Serialize};
@@ -10,10 +11,12 @@ use crate::dictionary::character::CharProperty;
 use crate::dictionary::connector::{ConnectorWrapper, MatrixConnector};
 use crate::dictionary::lexicon::Lexicon;
 use crate::dictionary::unknown::UnkHandler;
-use crate::dictionary::{DictionaryInner, SystemDictionaryBuilder};
+use crate::dictionary::word_idx::WordIdx;
+use crate::dictionary::{DictionaryInner, LexType, SystemDictionaryBuilder};
 use crate::errors::{Result, VibratoError};
 use crate::trainer::feature_extractor::FeatureExtractor;
 use crate::trainer::feature_rewriter::{FeatureRewriter, FeatureRewriterBuilder};
+use crate::utils;
 
 /// トレーナーの設定。
 ///
@@ -234,6 +237,21 @@ impl TrainerConfig {
             surfaces,
         })
     }
+
+    /// シード辞書の語の素性の項目数ごとに語数を数えます。
+    ///
+    /// # 戻り値
+    ///
+    /// 項目数と語数の組のリスト（項目数の昇順）
+    pub(crate) fn feature_column_counts(&self) -> Vec<(usize, usize)> {
+        let mut counts = BTreeMap::new();
+        for word_id in 0..u32::try_from(self.surfaces.len()).unwrap() {
+            let word_idx = WordIdx::new(LexType::System, word_id);
+            let feature = self.dict.system_lexicon().word_feature(word_idx);
+            *counts.entry(utils::parse_csv_row(feature).len()).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    }
 }
 
 #[cfg(test)]
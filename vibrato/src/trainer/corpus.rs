@@ -2,21 +2,37 @@
 //!
 //! このモジュールは、学習用コーパスの読み込みと管理に必要なデータ構造を提供します。
 
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Read, Write};
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 
+use crate::dictionary::word_idx::WordIdx;
+use crate::dictionary::LexType;
 use crate::errors::{Result, VibratoError};
 use crate::sentence::Sentence;
+use crate::trainer::TrainerConfig;
+use crate::utils::{self, FromU32};
+
+/// 部分的に注釈されたコーパスで、注釈されていないトークンを表す素性。
+///
+/// [`Corpus::from_reader_partial()`]などで部分的な注釈を有効にした場合にのみ特別に扱われます。
+/// それ以外の読み込み方法では、通常の素性文字列として扱われます。
+pub const WILDCARD_FEATURE: &str = "*";
 
 /// 表層形と素性のペアの表現。
 ///
 /// 学習データの単語を表します。
+#[derive(Clone)]
 pub struct Word {
     surface: String,
 
     // Since a vector of strings consumes massive memory, a single string is stored and divided as
     // needed.
     feature: String,
+
+    annotated: bool,
 }
 
 impl Word {
@@ -34,6 +50,26 @@ impl Word {
         Self {
             surface: surface.to_string(),
             feature: feature.to_string(),
+            annotated: true,
+        }
+    }
+
+    /// 注釈されていない単語を作成します。
+    ///
+    /// 素性は[`WILDCARD_FEATURE`]になります。
+    ///
+    /// # 引数
+    ///
+    /// * `surface` - 表層形
+    ///
+    /// # 戻り値
+    ///
+    /// 作成された単語
+    pub(crate) fn unannotated(surface: &str) -> Self {
+        Self {
+            surface: surface.to_string(),
+            feature: WILDCARD_FEATURE.to_string(),
+            annotated: false,
         }
     }
 
@@ -54,11 +90,22 @@ impl Word {
     pub fn feature(&self) -> &str {
         &self.feature
     }
+
+    /// 素性が注釈されているかどうかを返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 部分的な注釈を有効にして読み込んだコーパスで、素性が[`WILDCARD_FEATURE`]だった場合は
+    /// `false`。それ以外の場合は `true`
+    pub fn is_annotated(&self) -> bool {
+        self.annotated
+    }
 }
 
 /// 文の表現。
 ///
 /// 学習データの1つの例文を表します。
+#[derive(Clone)]
 pub struct Example {
     /// トークンの連結。
     pub(crate) sentence: Sentence,
@@ -106,6 +153,7 @@ impl Example {
 /// コーパスの表現。
 ///
 /// 学習データの例文集合を表します。
+#[derive(Clone)]
 pub struct Corpus {
     /// 例文のリスト。
     pub(crate) examples: Vec<Example>,
@@ -117,6 +165,9 @@ impl Corpus {
     /// コーパスファイルは、各行が「表層形\t素性」の形式で、
     /// 文の終わりに「EOS」が含まれる形式を想定しています。
     ///
+    /// すべてのトークンは注釈されたものとして扱われます。素性が`*`のトークンも、
+    /// `*`という素性を持つ語として学習されます。
+    ///
     /// # 引数
     ///
     /// * `rdr` - コーパスのリーダー
@@ -132,22 +183,375 @@ impl Corpus {
     where
         R: Read,
     {
-        let buf = BufReader::new(rdr);
+        let examples = ExampleReader::new(BufReader::new(rdr), false).collect::<Result<_>>()?;
+        Ok(Self { examples })
+    }
+
+    /// 指定されたシンクから、部分的に注釈されたコーパスを読み込みます。
+    ///
+    /// ファイル形式は[`Corpus::from_reader()`]と同じですが、素性に[`WILDCARD_FEATURE`]（`*`）を
+    /// 指定したトークンは注釈されていないものとして扱われます。連続する未注釈のトークンは
+    /// 1つの区間にまとめられ、その区間内の分割と素性は学習時に制約されません。
+    /// これにより、一部の語のみを注釈した安価なデータを学習に利用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `rdr` - コーパスのリーダー
+    ///
+    /// # 戻り値
+    ///
+    /// 読み込まれたコーパス
+    ///
+    /// # エラー
+    ///
+    /// 入力形式が不正な場合、[`VibratoError`] が返されます。
+    pub fn from_reader_partial<R>(rdr: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let examples = ExampleReader::new(BufReader::new(rdr), true).collect::<Result<_>>()?;
+        Ok(Self { examples })
+    }
+
+    /// コーパスファイルを開き、例文を逐次的に読み込むコーパスを返します。
+    ///
+    /// [`Corpus::from_reader()`]と異なり、ファイル全体をメモリに読み込みません。
+    /// 例文は[`StreamingCorpus::iter()`]を呼び出すたびにファイルの先頭から読み直されます。
+    /// ファイル形式は[`Corpus::from_reader()`]と同じです。
+    ///
+    /// [`Trainer::train_streaming()`](crate::trainer::Trainer::train_streaming)で学習する場合も、
+    /// 全例文のラティスはメモリ上に構築されます。ラティスはコーパスの文字列より大きいため、
+    /// メモリに収まらない大きさのコーパスは学習できません。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - コーパスファイルのパス
+    ///
+    /// # 戻り値
+    ///
+    /// 逐次的に読み込むコーパス
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合、[`VibratoError`] が返されます。
+    pub fn open_streaming<P>(path: P) -> Result<StreamingCorpus>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        File::open(&path)?;
+        Ok(StreamingCorpus {
+            path,
+            partial: false,
+        })
+    }
+
+    /// 部分的に注釈されたコーパスファイルを開き、例文を逐次的に読み込むコーパスを返します。
+    ///
+    /// 未注釈のトークンは[`Corpus::from_reader_partial()`]と同様に扱われます。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - コーパスファイルのパス
+    ///
+    /// # 戻り値
+    ///
+    /// 逐次的に読み込むコーパス
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合、[`VibratoError`] が返されます。
+    pub fn open_streaming_partial<P>(path: P) -> Result<StreamingCorpus>
+    where
+        P: AsRef<Path>,
+    {
+        let mut corpus = Self::open_streaming(path)?;
+        corpus.partial = true;
+        Ok(corpus)
+    }
+
+    /// 例文の順序をシャッフルします。
+    ///
+    /// 同じシードを与えると常に同じ順序になるため、再現可能な実験に使用できます。
+    ///
+    /// # 引数
+    ///
+    /// * `seed` - 乱数のシード
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut rng = SplitMix64(seed);
+        for i in (1..self.examples.len()).rev() {
+            let j = usize::try_from(rng.next_u64() % (i as u64 + 1)).unwrap();
+            self.examples.swap(i, j);
+        }
+    }
+
+    /// コーパスを`k`個の部分コーパスに分割します。
+    ///
+    /// `i`番目の例文は`i % k`番目の部分コーパスに割り当てられるため、各部分コーパスの
+    /// 大きさの差は高々1です。交差検証では、事前に[`Corpus::shuffle()`]で
+    /// 順序を乱してから分割してください。
+    ///
+    /// # 引数
+    ///
+    /// * `k` - 分割数
+    ///
+    /// # 戻り値
+    ///
+    /// 部分コーパスのベクター
+    ///
+    /// # エラー
+    ///
+    /// `k`が0、または例文の数より大きい場合、[`VibratoError`] が返されます。
+    pub fn split(self, k: usize) -> Result<Vec<Self>> {
+        if k == 0 || k > self.examples.len() {
+            return Err(VibratoError::invalid_argument(
+                "k",
+                "must be in the range of 1 to the number of examples.",
+            ));
+        }
+        let mut folds: Vec<_> = (0..k).map(|_| Self { examples: vec![] }).collect();
+        for (i, example) in self.examples.into_iter().enumerate() {
+            folds[i % k].examples.push(example);
+        }
+        Ok(folds)
+    }
+
+    /// コーパスの統計情報を計算します。
+    ///
+    /// 例文とトークンの数に加えて、シード辞書に対する未知語率と、素性の項目数が
+    /// シード辞書と揃っているかを調べます。素性の項目数が異なるトークンは辞書の語と
+    /// 一致しないため、そのまま学習すると精度の低いモデルになります。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - シード辞書を含む学習設定
+    ///
+    /// # 戻り値
+    ///
+    /// コーパスの統計情報
+    pub fn stats(&self, config: &TrainerConfig) -> CorpusStats {
+        let lexicon = config.dict.system_lexicon();
+        let words: HashSet<(&str, &str)> = (0..u32::try_from(config.surfaces.len()).unwrap())
+            .map(|word_id| {
+                let word_idx = WordIdx::new(LexType::System, word_id);
+                (config.surfaces[usize::from_u32(word_id)].as_str(), lexicon.word_feature(word_idx))
+            })
+            .collect();
+
+        let mut stats = CorpusStats {
+            num_sentences: self.examples.len(),
+            lexicon_feature_columns: most_common_columns(&config.feature_column_counts()),
+            ..CorpusStats::default()
+        };
+        let mut columns = BTreeMap::new();
+        for token in self.examples.iter().flat_map(|e| &e.tokens) {
+            stats.num_tokens += 1;
+            if !token.is_annotated() {
+                stats.num_unannotated += 1;
+                continue;
+            }
+            if !words.contains(&(token.surface(), token.feature())) {
+                stats.num_oov += 1;
+            }
+            *columns.entry(utils::parse_csv_row(token.feature()).len()).or_insert(0) += 1;
+        }
+        stats.feature_columns = columns.into_iter().collect();
+        stats
+    }
+
+    /// 複数のコーパスを連結します。
+    ///
+    /// 交差検証で、評価に使用しない部分コーパスを学習用にまとめる際に使用します。
+    ///
+    /// # 引数
+    ///
+    /// * `corpora` - 連結するコーパス
+    ///
+    /// # 戻り値
+    ///
+    /// 連結されたコーパス
+    pub fn concat<I>(corpora: I) -> Self
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        Self {
+            examples: corpora.into_iter().flat_map(|c| c.examples).collect(),
+        }
+    }
+}
+
+/// コーパスの統計情報。
+///
+/// [`Corpus::stats()`]で取得します。学習の前に、コーパスとシード辞書の素性の形式が
+/// 揃っているかを確認する用途を想定しています。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorpusStats {
+    /// 例文の数
+    pub num_sentences: usize,
+
+    /// トークンの数
+    pub num_tokens: usize,
+
+    /// 注釈されていないトークンの数
+    pub num_unannotated: usize,
+
+    /// 表層形と素性の組がシード辞書に含まれない、注釈されたトークンの数
+    pub num_oov: usize,
+
+    /// 注釈されたトークンの素性の項目数と、その項目数のトークンの数の組（項目数の昇順）
+    pub feature_columns: Vec<(usize, usize)>,
+
+    /// シード辞書の語の素性の項目数として最も多いもの。辞書が空の場合は`None`
+    pub lexicon_feature_columns: Option<usize>,
+}
+
+impl CorpusStats {
+    /// 注釈されたトークンのうち、シード辞書に含まれないものの割合を返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 未知語率。注釈されたトークンがない場合は 0.0
+    pub fn oov_rate(&self) -> f64 {
+        let num_annotated = self.num_tokens - self.num_unannotated;
+        if num_annotated == 0 {
+            return 0.0;
+        }
+        self.num_oov as f64 / num_annotated as f64
+    }
+
+    /// 素性の項目数がシード辞書と異なる、注釈されたトークンの数を返します。
+    ///
+    /// 素性の項目数が異なるトークンは辞書の語と一致しないため、学習では未知語または
+    /// 素性を持たない仮想エッジとして扱われます。
+    ///
+    /// # 戻り値
+    ///
+    /// トークンの数。シード辞書が空の場合は 0
+    pub fn num_inconsistent_columns(&self) -> usize {
+        let Some(columns) = self.lexicon_feature_columns else {
+            return 0;
+        };
+        self.feature_columns
+            .iter()
+            .filter(|&&(c, _)| c != columns)
+            .map(|&(_, n)| n)
+            .sum()
+    }
+}
+
+/// 項目数ごとの数から、最も多い項目数を返します。
+///
+/// 同数の場合は項目数の小さい方を返します。
+pub(crate) fn most_common_columns(counts: &[(usize, usize)]) -> Option<usize> {
+    counts
+        .iter()
+        .rev()
+        .max_by_key(|&&(_, n)| n)
+        .map(|&(c, _)| c)
+}
+
+/// 例文を逐次的に読み込むコーパス。
+///
+/// [`Corpus::open_streaming()`]または[`Corpus::open_streaming_partial()`]で作成します。
+#[derive(Clone, Debug)]
+pub struct StreamingCorpus {
+    path: PathBuf,
+    partial: bool,
+}
 
-        let mut examples = vec![];
+impl StreamingCorpus {
+    /// コーパスファイルのパスを返します。
+    ///
+    /// # 戻り値
+    ///
+    /// コーパスファイルのパス
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// ファイルの先頭から例文を読み込むイテレータを返します。
+    ///
+    /// # 戻り値
+    ///
+    /// 例文のイテレータ
+    ///
+    /// # エラー
+    ///
+    /// ファイルを開けない場合、[`VibratoError`] が返されます。
+    pub fn iter(&self) -> Result<StreamingExamples> {
+        let file = File::open(&self.path)?;
+        Ok(StreamingExamples {
+            inner: ExampleReader::new(BufReader::new(file), self.partial),
+        })
+    }
+}
+
+/// [`StreamingCorpus`]の例文を順に読み込むイテレータ。
+///
+/// 入力形式が不正な場合や読み込みに失敗した場合は、エラーを返して終了します。
+pub struct StreamingExamples {
+    inner: ExampleReader<BufReader<File>>,
+}
+
+impl Iterator for StreamingExamples {
+    type Item = Result<Example>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// 行単位のコーパスから例文を読み込むイテレータ。
+struct ExampleReader<R> {
+    lines: Lines<R>,
+    /// [`WILDCARD_FEATURE`]を未注釈のトークンとして扱うかどうか
+    partial: bool,
+    failed: bool,
+}
+
+impl<R> ExampleReader<R>
+where
+    R: BufRead,
+{
+    fn new(rdr: R, partial: bool) -> Self {
+        Self {
+            lines: rdr.lines(),
+            partial,
+            failed: false,
+        }
+    }
+}
+
+impl<R> Iterator for ExampleReader<R>
+where
+    R: BufRead,
+{
+    type Item = Result<Example>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
         let mut tokens = vec![];
-        for line in buf.lines() {
-            let line = line?;
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e.into()));
+                }
+            };
             let mut spl = line.split('\t');
             let surface = spl.next();
             let feature = spl.next();
             let rest = spl.next();
             match (surface, feature, rest) {
                 (Some(surface), Some(feature), None) => {
-                    tokens.push(Word {
-                        surface: surface.to_string(),
-                        feature: feature.to_string(),
-                    });
+                    if self.partial && feature == WILDCARD_FEATURE {
+                        tokens.push(Word::unannotated(surface));
+                    } else {
+                        tokens.push(Word::new(surface, feature));
+                    }
                 }
                 (Some("EOS"), None, None) => {
                     let mut sentence = Sentence::new();
@@ -157,20 +561,33 @@ impl Corpus {
                     }
                     if !input.is_empty() {
                         sentence.set_sentence(input);
-                        examples.push(Example { sentence, tokens });
+                        return Some(Ok(Example { sentence, tokens }));
                     }
                     tokens = vec![];
                 }
                 _ => {
-                    return Err(VibratoError::invalid_format(
+                    self.failed = true;
+                    return Some(Err(VibratoError::invalid_format(
                         "rdr",
                         "Each line must be a pair of a surface and features or `EOS`",
-                    ))
+                    )));
                 }
             }
         }
+        None
+    }
+}
 
-        Ok(Self { examples })
+/// シャッフルや再標本化に使用する疑似乱数生成器（SplitMix64）。
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
     }
 }
 
@@ -233,4 +650,85 @@ EOS
         assert_eq!("猫", sentence2.tokens[1].surface());
         assert_eq!("名詞,ネコ", sentence2.tokens[1].feature());
     }
+
+    #[test]
+    fn test_load_partial_corpus() {
+        let corpus_data = "\
+トスカーナ地方\t*
+に\t助詞,ニ
+行く\t*
+EOS
+";
+
+        let corpus = Corpus::from_reader_partial(corpus_data.as_bytes()).unwrap();
+
+        assert_eq!(1, corpus.examples.len());
+
+        let sentence1 = &corpus.examples[0];
+
+        assert_eq!("トスカーナ地方に行く", sentence1.sentence.raw());
+
+        assert_eq!(3, sentence1.tokens.len());
+        assert!(!sentence1.tokens[0].is_annotated());
+        assert!(sentence1.tokens[1].is_annotated());
+        assert!(!sentence1.tokens[2].is_annotated());
+
+        // Without opting in, `*` is an ordinary feature.
+        let corpus = Corpus::from_reader(corpus_data.as_bytes()).unwrap();
+        assert!(corpus.examples[0].tokens.iter().all(Word::is_annotated));
+        assert_eq!(WILDCARD_FEATURE, corpus.examples[0].tokens[0].feature());
+    }
+
+    #[test]
+    fn test_shuffle_and_split() {
+        let corpus_data = "a\tA\nEOS\nb\tB\nEOS\nc\tC\nEOS\nd\tD\nEOS\ne\tE\nEOS\n";
+
+        let mut corpus1 = Corpus::from_reader(corpus_data.as_bytes()).unwrap();
+        let mut corpus2 = corpus1.clone();
+        corpus1.shuffle(42);
+        corpus2.shuffle(42);
+        let surfaces1: Vec<_> = corpus1.iter().map(|e| e.tokens[0].surface().to_string()).collect();
+        let surfaces2: Vec<_> = corpus2.iter().map(|e| e.tokens[0].surface().to_string()).collect();
+        assert_eq!(surfaces1, surfaces2);
+
+        let folds = corpus1.split(2).unwrap();
+        assert_eq!(2, folds.len());
+        assert_eq!(3, folds[0].len());
+        assert_eq!(2, folds[1].len());
+
+        let merged = Corpus::concat(folds);
+        let mut surfaces: Vec<_> = merged.iter().map(|e| e.tokens[0].surface().to_string()).collect();
+        surfaces.sort();
+        assert_eq!(surfaces, ["a", "b", "c", "d", "e"]);
+
+        assert!(merged.clone().split(0).is_err());
+        assert!(merged.split(6).is_err());
+    }
+
+    #[test]
+    fn test_open_streaming() {
+        let corpus_data = "a\tA\nEOS\nb\tB\nc\tC\nEOS\n";
+        let path = std::env::temp_dir().join(format!(
+            "vibrato-streaming-corpus-{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, corpus_data).unwrap();
+
+        let corpus = Corpus::open_streaming(&path).unwrap();
+        for _ in 0..2 {
+            let examples: Vec<_> = corpus.iter().unwrap().collect::<Result<_>>().unwrap();
+            assert_eq!(2, examples.len());
+            assert_eq!("a", examples[0].sentence.raw());
+            assert_eq!("bc", examples[1].sentence.raw());
+        }
+
+        std::fs::write(&path, "a\tA\tB\nEOS\nb\tB\nEOS\n").unwrap();
+        let mut examples = corpus.iter().unwrap();
+        assert!(examples.next().unwrap().is_err());
+        assert!(examples.next().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(corpus.iter().is_err());
+        assert!(Corpus::open_streaming(&path).is_err());
+    }
 }
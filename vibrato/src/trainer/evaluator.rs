@@ -0,0 +1,718 @@
+//! 学習済みモデルの精度評価モジュール。
+//!
+//! このモジュールは、正解コーパスとトークナイザーの出力を比較して、
+//! 適合率（Precision）、再現率（Recall）、F1スコアを計算する機能を提供します。
+//! [`evaluate_tokenizer_detailed()`]を使用すると、品詞ごとの内訳、境界のみの評価、
+//! 誤りの傾向もあわせて集計できます。[`Bootstrap`]を使用すると、F1スコアの信頼区間や、
+//! 2つの辞書やモデルの差の有意性を求められます。
+
+use std::collections::{BTreeMap, HashMap};
+
+use hashbrown::HashSet;
+
+use crate::errors::{Result, VibratoError};
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::worker::Worker;
+use crate::trainer::corpus::{Corpus, Example, SplitMix64};
+use crate::trainer::model::Model;
+use crate::utils;
+
+/// 評価結果の集計値。
+///
+/// 区間と素性の組が一致したトークンを正解として数えます。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    /// 正解コーパスのトークン数。
+    pub num_ref: usize,
+    /// トークナイザーが出力したトークン数。
+    pub num_sys: usize,
+    /// 正解と一致したトークン数。
+    pub num_cor: usize,
+}
+
+impl Score {
+    /// 適合率を返します。
+    ///
+    /// トークナイザーが出力したトークンがない場合は 0.0 を返します。
+    pub fn precision(&self) -> f64 {
+        if self.num_sys == 0 {
+            return 0.0;
+        }
+        self.num_cor as f64 / self.num_sys as f64
+    }
+
+    /// 再現率を返します。
+    ///
+    /// 正解コーパスのトークンがない場合は 0.0 を返します。
+    pub fn recall(&self) -> f64 {
+        if self.num_ref == 0 {
+            return 0.0;
+        }
+        self.num_cor as f64 / self.num_ref as f64
+    }
+
+    /// F1スコアを返します。
+    ///
+    /// 適合率と再現率がともに 0.0 の場合は 0.0 を返します。
+    pub fn f1(&self) -> f64 {
+        let precision = self.precision();
+        let recall = self.recall();
+        if precision + recall == 0.0 {
+            return 0.0;
+        }
+        2.0 * precision * recall / (precision + recall)
+    }
+}
+
+impl std::ops::AddAssign for Score {
+    fn add_assign(&mut self, other: Self) {
+        self.num_ref += other.num_ref;
+        self.num_sys += other.num_sys;
+        self.num_cor += other.num_cor;
+    }
+}
+
+/// 詳細な評価結果。
+///
+/// [`evaluate_tokenizer_detailed()`]によって作成されます。
+#[derive(Clone, Debug, Default)]
+pub struct Evaluation {
+    /// 区間と素性の組による評価値。
+    pub total: Score,
+    /// 区間のみによる評価値（分かち書きの精度）。
+    pub boundary: Score,
+    /// 品詞ごとの評価値。
+    ///
+    /// 正解トークン数は正解の品詞、出力トークン数と正解数はトークナイザーが出力した品詞で
+    /// 数えます。
+    pub per_pos: BTreeMap<String, Score>,
+    /// 区間は一致したが素性が異なったトークンの、(正解の素性, 出力の素性) の組ごとの出現数。
+    pub confusions: HashMap<(String, String), usize>,
+}
+
+impl Evaluation {
+    /// 出現数の多い順に誤りの組を最大`n`件返します。
+    ///
+    /// 出現数が同じ組は素性の辞書順に並びます。
+    ///
+    /// # 引数
+    ///
+    /// * `n` - 返す組の最大数
+    ///
+    /// # 戻り値
+    ///
+    /// (正解の素性, 出力の素性, 出現数) のベクター
+    pub fn top_confusions(&self, n: usize) -> Vec<(&str, &str, usize)> {
+        let mut confusions: Vec<_> = self
+            .confusions
+            .iter()
+            .map(|((r, s), &c)| (r.as_str(), s.as_str(), c))
+            .collect();
+        confusions.sort_unstable_by(|a, b| b.2.cmp(&a.2).then_with(|| (a.0, a.1).cmp(&(b.0, b.1))));
+        confusions.truncate(n);
+        confusions
+    }
+}
+
+impl std::ops::AddAssign for Evaluation {
+    fn add_assign(&mut self, other: Self) {
+        self.total += other.total;
+        self.boundary += other.boundary;
+        for (pos, score) in other.per_pos {
+            *self.per_pos.entry(pos).or_default() += score;
+        }
+        for (pair, count) in other.confusions {
+            *self.confusions.entry(pair).or_default() += count;
+        }
+    }
+}
+
+/// 学習済みモデルをコーパスで評価します。
+///
+/// モデルから辞書を構築し、[`evaluate_tokenizer()`]で評価します。
+///
+/// # 引数
+///
+/// * `model` - 評価するモデル
+/// * `corpus` - 正解コーパス
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+///
+/// # 戻り値
+///
+/// 評価結果
+///
+/// # エラー
+///
+/// 辞書の構築に失敗した場合、[`VibratoError`](crate::errors::VibratoError) が返されます。
+pub fn evaluate(model: &mut Model, corpus: &Corpus, feature_indices: &[usize]) -> Result<Score> {
+    let tokenizer = model.build_tokenizer()?;
+    Ok(evaluate_tokenizer(&tokenizer, corpus, feature_indices))
+}
+
+/// 学習済みモデルをコーパスで評価し、詳細な評価結果を返します。
+///
+/// モデルから辞書を構築し、[`evaluate_tokenizer_detailed()`]で評価します。
+///
+/// # 引数
+///
+/// * `model` - 評価するモデル
+/// * `corpus` - 正解コーパス
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+/// * `pos_index` - 品詞として扱う素性のインデックス
+///
+/// # 戻り値
+///
+/// 詳細な評価結果
+///
+/// # エラー
+///
+/// 辞書の構築に失敗した場合、[`VibratoError`](crate::errors::VibratoError) が返されます。
+pub fn evaluate_detailed(
+    model: &mut Model,
+    corpus: &Corpus,
+    feature_indices: &[usize],
+    pos_index: usize,
+) -> Result<Evaluation> {
+    let tokenizer = model.build_tokenizer()?;
+    Ok(evaluate_tokenizer_detailed(&tokenizer, corpus, feature_indices, pos_index))
+}
+
+/// トークナイザーをコーパスで評価します。
+///
+/// [`Corpus::from_reader_partial()`]などで読み込んだ正解コーパス中の未注釈のトークンと、
+/// それに重なるトークナイザーの出力は評価から除外されます。
+///
+/// # 引数
+///
+/// * `tokenizer` - 評価するトークナイザー
+/// * `corpus` - 正解コーパス
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+///
+/// # 戻り値
+///
+/// 評価結果
+pub fn evaluate_tokenizer(tokenizer: &Tokenizer, corpus: &Corpus, feature_indices: &[usize]) -> Score {
+    evaluate_tokenizer_detailed(tokenizer, corpus, feature_indices, 0).total
+}
+
+/// トークナイザーをコーパスで評価し、詳細な評価結果を返します。
+///
+/// 評価対象のトークンは[`evaluate_tokenizer()`]と同じです。
+///
+/// # 引数
+///
+/// * `tokenizer` - 評価するトークナイザー
+/// * `corpus` - 正解コーパス
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+/// * `pos_index` - 品詞として扱う素性のインデックス。`feature_indices`とは独立に、
+///   元の素性列に対するインデックスを指定します。
+///
+/// # 戻り値
+///
+/// 詳細な評価結果
+pub fn evaluate_tokenizer_detailed(
+    tokenizer: &Tokenizer,
+    corpus: &Corpus,
+    feature_indices: &[usize],
+    pos_index: usize,
+) -> Evaluation {
+    let mut worker = tokenizer.new_worker();
+    let mut result = Evaluation::default();
+    for example in corpus.iter() {
+        evaluate_example(&mut worker, example, feature_indices, pos_index, &mut result);
+    }
+    result
+}
+
+/// 1つの例文を評価し、評価結果に加算します。
+///
+/// # 引数
+///
+/// * `worker` - 評価するトークナイザーのワーカー
+/// * `example` - 正解の例文
+/// * `feature_indices` - 正誤の判定に使用する素性のインデックス
+/// * `pos_index` - 品詞として扱う素性のインデックス
+/// * `result` - 加算先の評価結果
+fn evaluate_example(
+    worker: &mut Worker,
+    example: &Example,
+    feature_indices: &[usize],
+    pos_index: usize,
+    result: &mut Evaluation,
+) {
+    let choose = |feature: &str| {
+        let features = utils::parse_csv_row(feature);
+        if feature_indices.is_empty() {
+            features
+        } else {
+            feature_indices
+                .iter()
+                .map(|&i| features.get(i).map_or_else(|| "*".to_string(), |x| x.to_string()))
+                .collect()
+        }
+    };
+
+    let pos = |feature: &str| {
+        utils::parse_csv_row(feature)
+            .into_iter()
+            .nth(pos_index)
+            .unwrap_or_else(|| "*".to_string())
+    };
+
+    let mut input_str = String::new();
+    let mut refs = HashSet::new();
+    let mut syss = HashSet::new();
+    let mut ref_pos = HashMap::new();
+    let mut sys_pos = HashMap::new();
+    let mut unannotated = vec![];
+    let mut start = 0;
+    for token in example.tokens() {
+        input_str.push_str(token.surface());
+        let len = token.surface().chars().count();
+        if token.is_annotated() {
+            refs.insert((start..start + len, choose(token.feature())));
+            ref_pos.insert(start..start + len, pos(token.feature()));
+        } else {
+            unannotated.push(start..start + len);
+        }
+        start += len;
+    }
+    worker.reset_sentence(input_str);
+    worker.tokenize();
+    for token in worker.token_iter() {
+        let range = token.range_char();
+        if unannotated.iter().any(|r| r.start < range.end && range.start < r.end) {
+            continue;
+        }
+        sys_pos.insert(range.clone(), pos(token.feature()));
+        syss.insert((range, choose(token.feature())));
+    }
+
+    result.total.num_ref += refs.len();
+    result.total.num_sys += syss.len();
+    result.total.num_cor += refs.intersection(&syss).count();
+
+    result.boundary.num_ref += ref_pos.len();
+    result.boundary.num_sys += sys_pos.len();
+
+    for p in ref_pos.values() {
+        result.per_pos.entry(p.clone()).or_default().num_ref += 1;
+    }
+    for p in sys_pos.values() {
+        result.per_pos.entry(p.clone()).or_default().num_sys += 1;
+    }
+
+    let sys_features: HashMap<_, _> = syss.iter().map(|(r, f)| (r, f)).collect();
+    for (range, ref_feature) in &refs {
+        let Some(&sys_feature) = sys_features.get(range) else {
+            continue;
+        };
+        result.boundary.num_cor += 1;
+        if sys_feature == ref_feature {
+            result.per_pos.entry(sys_pos[range].clone()).or_default().num_cor += 1;
+        } else {
+            *result
+                .confusions
+                .entry((ref_feature.join(","), sys_feature.join(",")))
+                .or_default() += 1;
+        }
+    }
+}
+
+/// 例文ごとの評価値を求めます。
+fn sentence_scores(tokenizer: &Tokenizer, corpus: &Corpus, feature_indices: &[usize]) -> Vec<Score> {
+    let mut worker = tokenizer.new_worker();
+    corpus
+        .iter()
+        .map(|example| {
+            let mut result = Evaluation::default();
+            evaluate_example(&mut worker, example, feature_indices, 0, &mut result);
+            result.total
+        })
+        .collect()
+}
+
+/// ブートストラップ法によるF1スコアの信頼区間。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    /// コーパス全体でのF1スコア（点推定値）。
+    pub f1: f64,
+    /// 信頼区間の下限。
+    pub lower: f64,
+    /// 信頼区間の上限。
+    pub upper: f64,
+}
+
+/// 2つのトークナイザーの対応のあるブートストラップ法による比較結果。
+///
+/// [`Bootstrap::compare()`]によって作成されます。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    /// 基準となるトークナイザーのF1スコアの信頼区間。
+    pub baseline: ConfidenceInterval,
+    /// 比較対象のトークナイザーのF1スコアの信頼区間。
+    pub candidate: ConfidenceInterval,
+    /// F1スコアの差（比較対象 − 基準）の信頼区間。
+    pub difference: ConfidenceInterval,
+    /// 比較対象のF1スコアが基準以下となった再標本の割合。
+    ///
+    /// 「比較対象は基準より優れていない」という帰無仮説に対する片側のp値で、
+    /// 例えば0.05未満であれば、比較対象の改善は有意水準5%で有意とみなせます。
+    pub p_value: f64,
+}
+
+/// 例文を単位としたブートストラップ法による評価の設定。
+///
+/// 正解コーパスの例文を復元抽出した再標本を繰り返し作成し、F1スコアのばらつきを
+/// 推定します。2つの辞書やモデルを比較する場合は、同じ再標本を両方の評価に使用する
+/// 対応のあるブートストラップ法により、F1スコアの差が偶然によるものかを判定できます。
+///
+/// # 例
+///
+/// ```no_run
+/// use vibrato_rkyv::{Dictionary, Tokenizer, LoadMode};
+/// use vibrato_rkyv::trainer::{Bootstrap, Corpus};
+///
+/// let corpus = Corpus::from_reader(std::fs::File::open("test.txt")?)?;
+/// let baseline = Tokenizer::new(Dictionary::from_path("old.dic", LoadMode::Validate)?);
+/// let candidate = Tokenizer::new(Dictionary::from_path("new.dic", LoadMode::Validate)?);
+///
+/// let comparison = Bootstrap::new().num_samples(1000).compare(&baseline, &candidate, &corpus, &[])?;
+/// println!(
+///     "{:.4} -> {:.4} (p = {:.3})",
+///     comparison.baseline.f1, comparison.candidate.f1, comparison.p_value,
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bootstrap {
+    num_samples: usize,
+    confidence: f64,
+    seed: u64,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Self {
+            num_samples: 1000,
+            confidence: 0.95,
+            seed: 0,
+        }
+    }
+}
+
+impl Bootstrap {
+    /// デフォルトの設定を作成します。
+    ///
+    /// 再標本の数は1000、信頼水準は0.95、乱数のシードは0です。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 再標本の数を設定します。
+    pub const fn num_samples(mut self, num_samples: usize) -> Self {
+        self.num_samples = num_samples;
+        self
+    }
+
+    /// 信頼区間の信頼水準を設定します。
+    pub const fn confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// 再標本の作成に使用する乱数のシードを設定します。
+    ///
+    /// 同じシードでは常に同じ結果が得られます。
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// トークナイザーのF1スコアの信頼区間を求めます。
+    ///
+    /// 評価対象のトークンは[`evaluate_tokenizer()`]と同じです。
+    ///
+    /// # 引数
+    ///
+    /// * `tokenizer` - 評価するトークナイザー
+    /// * `corpus` - 正解コーパス
+    /// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+    ///
+    /// # 戻り値
+    ///
+    /// F1スコアの信頼区間
+    ///
+    /// # エラー
+    ///
+    /// 次の場合に[`VibratoError`]が返されます。
+    ///
+    /// - 再標本の数が0の場合
+    /// - 信頼水準が0より大きく1より小さい値でない場合
+    /// - コーパスが空の場合
+    pub fn interval(
+        &self,
+        tokenizer: &Tokenizer,
+        corpus: &Corpus,
+        feature_indices: &[usize],
+    ) -> Result<ConfidenceInterval> {
+        self.validate(corpus)?;
+        let scores = sentence_scores(tokenizer, corpus, feature_indices);
+        let mut samples = Vec::with_capacity(self.num_samples);
+        self.resample(scores.len(), |indices| {
+            samples.push(sum_scores(&scores, indices.iter().copied()).f1());
+        });
+        Ok(self.confidence_interval(sum_scores(&scores, 0..scores.len()).f1(), samples))
+    }
+
+    /// 2つのトークナイザーを対応のあるブートストラップ法で比較します。
+    ///
+    /// 評価対象のトークンは[`evaluate_tokenizer()`]と同じです。
+    ///
+    /// # 引数
+    ///
+    /// * `baseline` - 基準となるトークナイザー
+    /// * `candidate` - 比較対象のトークナイザー
+    /// * `corpus` - 正解コーパス
+    /// * `feature_indices` - 正誤の判定に使用する素性のインデックス。空の場合はすべての素性を使用します。
+    ///
+    /// # 戻り値
+    ///
+    /// 比較結果
+    ///
+    /// # エラー
+    ///
+    /// [`Bootstrap::interval()`]と同じ条件で[`VibratoError`]が返されます。
+    pub fn compare(
+        &self,
+        baseline: &Tokenizer,
+        candidate: &Tokenizer,
+        corpus: &Corpus,
+        feature_indices: &[usize],
+    ) -> Result<Comparison> {
+        self.validate(corpus)?;
+        let baseline_scores = sentence_scores(baseline, corpus, feature_indices);
+        let candidate_scores = sentence_scores(candidate, corpus, feature_indices);
+        let mut baseline_samples = Vec::with_capacity(self.num_samples);
+        let mut candidate_samples = Vec::with_capacity(self.num_samples);
+        let mut difference_samples = Vec::with_capacity(self.num_samples);
+        let mut num_not_better = 0;
+        self.resample(corpus.len(), |indices| {
+            let b = sum_scores(&baseline_scores, indices.iter().copied()).f1();
+            let c = sum_scores(&candidate_scores, indices.iter().copied()).f1();
+            if c <= b {
+                num_not_better += 1;
+            }
+            baseline_samples.push(b);
+            candidate_samples.push(c);
+            difference_samples.push(c - b);
+        });
+
+        let all = 0..corpus.len();
+        let baseline_f1 = sum_scores(&baseline_scores, all.clone()).f1();
+        let candidate_f1 = sum_scores(&candidate_scores, all).f1();
+        Ok(Comparison {
+            baseline: self.confidence_interval(baseline_f1, baseline_samples),
+            candidate: self.confidence_interval(candidate_f1, candidate_samples),
+            difference: self.confidence_interval(candidate_f1 - baseline_f1, difference_samples),
+            p_value: num_not_better as f64 / self.num_samples as f64,
+        })
+    }
+
+    fn validate(&self, corpus: &Corpus) -> Result<()> {
+        if self.num_samples == 0 {
+            return Err(VibratoError::invalid_argument(
+                "num_samples",
+                "must be at least 1.",
+            ));
+        }
+        if !(self.confidence > 0.0 && self.confidence < 1.0) {
+            return Err(VibratoError::invalid_argument(
+                "confidence",
+                "must be in the range (0, 1).",
+            ));
+        }
+        if corpus.is_empty() {
+            return Err(VibratoError::invalid_argument("corpus", "must not be empty."));
+        }
+        Ok(())
+    }
+
+    /// 例文のインデックスを復元抽出した再標本を作成し、それぞれについて`f`を呼び出します。
+    fn resample<F>(&self, num_examples: usize, mut f: F)
+    where
+        F: FnMut(&[usize]),
+    {
+        let mut rng = SplitMix64(self.seed);
+        let mut indices = Vec::with_capacity(num_examples);
+        for _ in 0..self.num_samples {
+            indices.clear();
+            for _ in 0..num_examples {
+                indices.push(usize::try_from(rng.next_u64() % num_examples as u64).unwrap());
+            }
+            f(&indices);
+        }
+    }
+
+    /// 再標本の値からパーセンタイル法で信頼区間を求めます。
+    fn confidence_interval(&self, point: f64, mut samples: Vec<f64>) -> ConfidenceInterval {
+        samples.sort_unstable_by(f64::total_cmp);
+        let alpha = (1.0 - self.confidence) / 2.0;
+        let last = samples.len() - 1;
+        let lower = ((alpha * samples.len() as f64).floor() as usize).min(last);
+        let upper = (((1.0 - alpha) * samples.len() as f64).ceil() as usize)
+            .saturating_sub(1)
+            .min(last);
+        ConfidenceInterval {
+            f1: point,
+            lower: samples[lower],
+            upper: samples[upper],
+        }
+    }
+}
+
+/// 指定された例文の評価値の合計を求めます。
+fn sum_scores<I>(scores: &[Score], indices: I) -> Score
+where
+    I: IntoIterator<Item = usize>,
+{
+    let mut total = Score::default();
+    for i in indices {
+        total += scores[i];
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dictionary::SystemDictionaryBuilder;
+
+    #[test]
+    fn test_score_zero_denominators() {
+        let empty = Score::default();
+        assert_eq!(empty.precision(), 0.0);
+        assert_eq!(empty.recall(), 0.0);
+        assert_eq!(empty.f1(), 0.0);
+
+        let no_output = Score { num_ref: 3, num_sys: 0, num_cor: 0 };
+        assert_eq!(no_output.precision(), 0.0);
+        assert_eq!(no_output.recall(), 0.0);
+        assert_eq!(no_output.f1(), 0.0);
+
+        let no_correct = Score { num_ref: 3, num_sys: 2, num_cor: 0 };
+        assert_eq!(no_correct.f1(), 0.0);
+
+        let score = Score { num_ref: 4, num_sys: 2, num_cor: 2 };
+        assert_eq!(score.precision(), 1.0);
+        assert_eq!(score.recall(), 0.5);
+        assert!((score.f1() - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_evaluate_tokenizer_detailed() {
+        let lexicon_csv = "\
+自然,0,0,1,名詞,一般
+言語,0,0,1,名詞,一般
+処理,0,0,1,動詞,一般
+自然言語,0,0,5,名詞,固有";
+        let dict = SystemDictionaryBuilder::from_readers(
+            lexicon_csv.as_bytes(),
+            "1 1\n0 0 0".as_bytes(),
+            "DEFAULT 0 1 0".as_bytes(),
+            "DEFAULT,0,0,100,未知".as_bytes(),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::from_inner(dict);
+
+        // The tokenizer outputs 自然|言語|処理.
+        let corpus = Corpus::from_reader(
+            "\
+自然\t名詞,一般
+言語\t名詞,一般
+処理\t名詞,サ変
+EOS
+自然言語\t名詞,固有
+EOS
+"
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let result = evaluate_tokenizer_detailed(&tokenizer, &corpus, &[], 0);
+        assert_eq!(result.total, Score { num_ref: 4, num_sys: 5, num_cor: 2 });
+        assert_eq!(result.boundary, Score { num_ref: 4, num_sys: 5, num_cor: 3 });
+        assert_eq!(result.per_pos["名詞"], Score { num_ref: 4, num_sys: 4, num_cor: 2 });
+        assert_eq!(result.per_pos["動詞"], Score { num_ref: 0, num_sys: 1, num_cor: 0 });
+        assert_eq!(
+            result.top_confusions(10),
+            vec![("名詞,サ変", "動詞,一般", 1)]
+        );
+        assert_eq!(
+            evaluate_tokenizer(&tokenizer, &corpus, &[]),
+            result.total
+        );
+    }
+
+    #[test]
+    fn test_bootstrap() {
+        let lexicon_csv = "\
+自然,0,0,1,名詞,一般
+言語,0,0,1,名詞,一般
+処理,0,0,1,名詞,サ変
+自然言語,0,0,5,名詞,固有";
+        let build = |lexicon_csv: &str| {
+            let dict = SystemDictionaryBuilder::from_readers(
+                lexicon_csv.as_bytes(),
+                "1 1\n0 0 0".as_bytes(),
+                "DEFAULT 0 1 0".as_bytes(),
+                "DEFAULT,0,0,100,未知".as_bytes(),
+            )
+            .unwrap();
+            Tokenizer::from_inner(dict)
+        };
+        let baseline = build(&lexicon_csv.replace("処理,0,0,1,名詞,サ変", "処理,0,0,1,動詞,一般"));
+        let candidate = build(lexicon_csv);
+
+        let corpus = Corpus::from_reader(
+            "\
+自然\t名詞,一般
+言語\t名詞,一般
+処理\t名詞,サ変
+EOS
+言語\t名詞,一般
+EOS
+処理\t名詞,サ変
+EOS
+"
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let bootstrap = Bootstrap::new().num_samples(200).seed(1);
+        let interval = bootstrap.interval(&candidate, &corpus, &[]).unwrap();
+        assert_eq!(interval.f1, 1.0);
+        assert_eq!((interval.lower, interval.upper), (1.0, 1.0));
+
+        let interval = bootstrap.interval(&baseline, &corpus, &[]).unwrap();
+        assert_eq!(interval.f1, evaluate_tokenizer(&baseline, &corpus, &[]).f1());
+        assert!(interval.lower <= interval.f1 && interval.f1 <= interval.upper);
+        assert_eq!(bootstrap.interval(&baseline, &corpus, &[]).unwrap(), interval);
+
+        let comparison = bootstrap.compare(&baseline, &candidate, &corpus, &[]).unwrap();
+        assert!(comparison.difference.f1 > 0.0);
+        assert!(comparison.difference.lower >= 0.0);
+        assert!(comparison.p_value < 0.5);
+
+        // A tokenizer is never better than itself.
+        let comparison = bootstrap.compare(&candidate, &candidate, &corpus, &[]).unwrap();
+        assert_eq!(comparison.difference.f1, 0.0);
+        assert_eq!(comparison.p_value, 1.0);
+
+        assert!(Bootstrap::new().num_samples(0).interval(&candidate, &corpus, &[]).is_err());
+        assert!(Bootstrap::new().confidence(1.0).interval(&candidate, &corpus, &[]).is_err());
+        let empty = Corpus::from_reader("".as_bytes()).unwrap();
+        assert!(Bootstrap::new().interval(&candidate, &empty, &[]).is_err());
+    }
+}
@@ -1,6 +1,15 @@
 //! 素性抽出モジュール。
 //!
 //! このモジュールは、テンプレートに基づいた素性の抽出機能を提供します。
+//!
+//! unigramテンプレートでは以下のマクロを使用できます。
+//!
+//! * `%F[i]`, `%F?[i]` - `i`番目の素性値（`%F?`は値が`*`の場合に素性を生成しない）
+//! * `%t` - 語の先頭文字の文字種
+//! * `%t[i]` - 語の`i`番目の文字の文字種（負の値は末尾からの位置、`-1`は末尾の文字）
+//! * `%l` - 語の文字数
+//!
+//! 表層形を持たない未知語の場合や、位置が範囲外の場合、`%t[i]`と`%l`は`*`に展開されます。
 
 use std::num::NonZeroU32;
 use std::ops::Range;
@@ -13,6 +22,8 @@ use rkyv::{Archive, Deserialize, Serialize};
 enum FeatureType {
     Index(usize),
     CharacterType,
+    CharacterTypeAt(isize),
+    Length,
 }
 
 #[derive(Debug, Archive, Serialize, Deserialize)]
@@ -56,7 +67,7 @@ impl FeatureExtractor {
     where
         S: ToString,
     {
-        let unigram_feature_pattern = Regex::new(r"%((F|F\?)\[([0-9]+)\]|t)").unwrap();
+        let unigram_feature_pattern = Regex::new(r"%((F|F\?)\[([0-9]+)\]|t\[(-?[0-9]+)\]|t|l)").unwrap();
         let left_feature_pattern = Regex::new(r"%(L|L\?)\[([0-9]+)\]").unwrap();
         let right_feature_pattern = Regex::new(r"%(R|R\?)\[([0-9]+)\]").unwrap();
 
@@ -69,6 +80,14 @@ impl FeatureExtractor {
                 let pattern = m.get(0).unwrap();
                 if m.get(1).unwrap().as_str() == "t" {
                     captures.push((pattern.start()..pattern.end(), FeatureType::CharacterType));
+                } else if m.get(1).unwrap().as_str() == "l" {
+                    captures.push((pattern.start()..pattern.end(), FeatureType::Length));
+                } else if let Some(pos) = m.get(4) {
+                    let pos: isize = pos.as_str().parse().unwrap();
+                    captures.push((
+                        pattern.start()..pattern.end(),
+                        FeatureType::CharacterTypeAt(pos),
+                    ));
                 } else {
                     let idx: usize = m.get(3).unwrap().as_str().parse().unwrap();
                     match m.get(2).unwrap().as_str() {
@@ -176,6 +195,7 @@ impl FeatureExtractor {
     /// * `feature_ids` - 素性IDのマップ
     /// * `next_id` - 次のID
     /// * `category_id` - カテゴリID
+    /// * `char_types` - 表層形の各文字の文字種（表層形が不明な場合は `None`）
     ///
     /// # 戻り値
     ///
@@ -186,6 +206,7 @@ impl FeatureExtractor {
         feature_ids: &mut HashMap<String, NonZeroU32>,
         next_id: &mut u32,
         category_id: u32,
+        char_types: Option<&[u32]>,
     ) -> Vec<Option<NonZeroU32>>
     where
         S: AsRef<str>,
@@ -209,6 +230,24 @@ impl FeatureExtractor {
                     FeatureType::CharacterType => {
                         feature_string.push_str(&category_id.to_string());
                     }
+                    FeatureType::CharacterTypeAt(pos) => {
+                        let char_type = char_types.and_then(|char_types| {
+                            let i = if *pos < 0 {
+                                char_types.len().checked_sub(pos.unsigned_abs())?
+                            } else {
+                                pos.unsigned_abs()
+                            };
+                            char_types.get(i)
+                        });
+                        match char_type {
+                            Some(char_type) => feature_string.push_str(&char_type.to_string()),
+                            None => feature_string.push('*'),
+                        }
+                    }
+                    FeatureType::Length => match char_types {
+                        Some(char_types) => feature_string.push_str(&char_types.len().to_string()),
+                        None => feature_string.push('*'),
+                    },
                 }
                 start = range.end;
             }
@@ -225,6 +264,8 @@ impl FeatureExtractor {
 
     /// unigram素性IDを抽出します。
     ///
+    /// 表層形が不明なため、`%t[i]` と `%l` は `*` に展開されます。
+    ///
     /// # 引数
     ///
     /// * `features` - 素性値
@@ -238,6 +279,29 @@ impl FeatureExtractor {
         features: &[S],
         category_id: u32,
     ) -> Vec<NonZeroU32>
+    where
+        S: AsRef<str>,
+    {
+        self.extract_unigram_feature_ids_with_char_types(features, category_id, None)
+    }
+
+    /// 表層形の文字種を使用してunigram素性IDを抽出します。
+    ///
+    /// # 引数
+    ///
+    /// * `features` - 素性値
+    /// * `category_id` - カテゴリID
+    /// * `char_types` - 表層形の各文字の文字種（表層形が不明な場合は `None`）
+    ///
+    /// # 戻り値
+    ///
+    /// 抽出されたunigram素性IDのリスト
+    pub fn extract_unigram_feature_ids_with_char_types<S>(
+        &mut self,
+        features: &[S],
+        category_id: u32,
+        char_types: Option<&[u32]>,
+    ) -> Vec<NonZeroU32>
     where
         S: AsRef<str>,
     {
@@ -247,6 +311,7 @@ impl FeatureExtractor {
             &mut self.unigram_feature_ids,
             &mut self.unigram_next_id,
             category_id,
+            char_types,
         )
         .into_iter()
         .flatten()
@@ -272,6 +337,7 @@ impl FeatureExtractor {
             &mut self.left_feature_ids,
             &mut self.left_next_id,
             0,
+            None,
         )
     }
 
@@ -294,6 +360,7 @@ impl FeatureExtractor {
             &mut self.right_feature_ids,
             &mut self.right_next_id,
             0,
+            None,
         )
     }
 
@@ -523,4 +590,46 @@ mod test {
             extractor.unigram_feature_ids
         );
     }
+
+    #[test]
+    fn test_char_type_ngram_extraction() {
+        let unigram_templates = vec![
+            "type2:%t[0]%t[1]",
+            "type-last:%F[1],%t[-1]",
+            "len:%F[1],%l",
+        ];
+        let mut extractor = FeatureExtractor::new(&unigram_templates, &[] as &[(&str, &str)]);
+
+        let feature_ids = extractor.extract_unigram_feature_ids_with_char_types(
+            &["東京都", "名詞"],
+            2,
+            Some(&[2, 2, 2]),
+        );
+        assert_eq!(
+            vec![
+                NonZeroU32::new(1).unwrap(),
+                NonZeroU32::new(2).unwrap(),
+                NonZeroU32::new(3).unwrap()
+            ],
+            feature_ids
+        );
+
+        extractor.extract_unigram_feature_ids_with_char_types(&["A", "名詞"], 5, Some(&[5]));
+        extractor.extract_unigram_feature_ids(&["*", "名詞"], 2);
+
+        assert_eq!(
+            hashmap![
+                "type2:22".to_string() => NonZeroU32::new(1).unwrap(),
+                "type-last:名詞,2".to_string() => NonZeroU32::new(2).unwrap(),
+                "len:名詞,3".to_string() => NonZeroU32::new(3).unwrap(),
+                "type2:5*".to_string() => NonZeroU32::new(4).unwrap(),
+                "type-last:名詞,5".to_string() => NonZeroU32::new(5).unwrap(),
+                "len:名詞,1".to_string() => NonZeroU32::new(6).unwrap(),
+                "type2:**".to_string() => NonZeroU32::new(7).unwrap(),
+                "type-last:名詞,*".to_string() => NonZeroU32::new(8).unwrap(),
+                "len:名詞,*".to_string() => NonZeroU32::new(9).unwrap(),
+            ],
+            extractor.unigram_feature_ids
+        );
+    }
 }
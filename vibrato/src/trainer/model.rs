@@ -15,10 +15,14 @@ use rkyv::ser::writer::IoWriter;
 use rkyv::util::with_arena;
 use rkyv::{Archive, Deserialize, Serialize, from_bytes};
 
-use crate::dictionary::lexicon::Lexicon;
+use crate::dictionary::lexicon::{Lexicon, RawWordEntry};
+use crate::dictionary::unknown::UnkEntry;
 use crate::dictionary::word_idx::WordIdx;
-use crate::dictionary::{LexType, WordParam};
+use crate::dictionary::{
+    ConnectorWrapper, DictionaryInner, LexType, MatrixConnector, UnkHandler, WordParam,
+};
 use crate::errors::{Result, VibratoError};
+use crate::tokenizer::Tokenizer;
 pub use crate::trainer::config::TrainerConfig;
 use crate::trainer::corpus::Word;
 pub use crate::trainer::Trainer;
@@ -47,9 +51,375 @@ pub struct Model {
     pub(crate) merged_model: Option<rucrf_rkyv::MergedModel>,
 
     pub(crate) user_entries: Vec<(Word, WordParam, NonZeroU32)>,
+
+    // Replayed on the merged model whenever it is recomputed.
+    pub(crate) compressions: Vec<Compression>,
+}
+
+/// 学習済みの辞書の各要素。
+///
+/// [`Model::write_dictionary()`]と[`Model::build_system_dictionary()`]で共有されます。
+struct TrainedParts<'a> {
+    lexicon: Vec<RawWordEntry<'a>>,
+    unk_entries: Vec<UnkEntry>,
+    num_right: usize,
+    num_left: usize,
+    /// 右接続ID、左接続ID、接続コストの組
+    connection_costs: Vec<(usize, usize, i16)>,
+    user_lexicon: Vec<RawWordEntry<'a>>,
+}
+
+/// 単語エントリをCSV形式で書き込みます。
+fn write_lexicon<W>(mut wtr: W, entries: &[RawWordEntry]) -> Result<()>
+where
+    W: Write,
+{
+    for e in entries {
+        // writes surface
+        utils::quote_csv_cell(&mut wtr, e.surface.as_bytes())?;
+
+        // writes others
+        writeln!(
+            &mut wtr,
+            ",{},{},{},{}",
+            e.param.left_id, e.param.right_id, e.param.word_cost, e.feature,
+        )?;
+    }
+    Ok(())
+}
+
+/// マージ済みモデルに適用する圧縮処理。
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Compression {
+    Prune(f64),
+    Quantize(u16),
+}
+
+/// [`Model::prune()`]や[`Model::quantize()`]による圧縮の結果。
+///
+/// 重みはすべてマージ済みモデル上の値（コストに変換する前の値）で表されます。
+/// 圧縮による解析精度の変化は、出力した辞書を評価用コーパスで検証してください。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompressionReport {
+    /// 対象となった重みの数。
+    pub num_weights: usize,
+    /// 値が変化した重みの数。
+    pub num_changed: usize,
+    /// 圧縮前の接続行列の非ゼロ要素数。
+    pub num_matrix_entries_before: usize,
+    /// 圧縮後の接続行列の非ゼロ要素数。`matrix.def`の行数に相当します。
+    pub num_matrix_entries_after: usize,
+    /// 圧縮後の重みの異なり数。
+    pub num_distinct_weights: usize,
+    /// 重みの変化量の絶対値の最大値。
+    pub max_abs_error: f64,
+    /// 重みの変化量の絶対値の平均値。
+    pub mean_abs_error: f64,
+}
+
+/// 素性の種類。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeatureKind {
+    /// 単語ごとの素性（unigram素性）。
+    Unigram,
+    /// 隣接する単語の組に対する素性（bigram素性）。
+    Bigram,
+}
+
+/// 学習済みの素性とその重み。
+///
+/// 重みは学習で得られた生の値で、[`Model::prune()`]や[`Model::quantize()`]による
+/// 圧縮は反映されません。正の重みはその素性を持つ解析を優先させ、辞書のコストを
+/// 下げる方向に働きます。
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureWeight {
+    /// 素性の種類。
+    pub kind: FeatureKind,
+    /// 素性の文字列。bigram素性では、左側と右側の素性を`/`で連結した文字列で、
+    /// [`Model::write_bigram_details()`]が出力する`.cost`ファイルと同じ形式です。
+    /// 文頭・文末の側は空文字列になります。
+    pub name: String,
+    /// 重み。
+    pub weight: f64,
+}
+
+impl Compression {
+    fn apply(self, merged_model: &mut rucrf_rkyv::MergedModel) -> CompressionReport {
+        let num_matrix_entries_before = merged_model.matrix.iter().map(|hm| hm.len()).sum();
+
+        let step = match self {
+            Self::Prune(_) => None,
+            Self::Quantize(num_levels) => {
+                let mut weight_abs_max = 0f64;
+                for feature_set in &merged_model.feature_sets {
+                    weight_abs_max = weight_abs_max.max(feature_set.weight.abs());
+                }
+                for hm in &merged_model.matrix {
+                    for &w in hm.values() {
+                        weight_abs_max = weight_abs_max.max(w.abs());
+                    }
+                }
+                (weight_abs_max != 0.0).then(|| weight_abs_max / f64::from(num_levels))
+            }
+        };
+        let transform = |w: f64| match self {
+            Self::Prune(threshold) => {
+                if w.abs() < threshold {
+                    0.0
+                } else {
+                    w
+                }
+            }
+            Self::Quantize(_) => step.map_or(w, |step| (w / step).round() * step),
+        };
+
+        let mut report = CompressionReport {
+            num_matrix_entries_before,
+            ..Default::default()
+        };
+        let mut distinct = hashbrown::HashSet::new();
+        let mut error_sum = 0f64;
+        let mut update = |w: &mut f64| {
+            let new_w = transform(*w);
+            let error = (new_w - *w).abs();
+            report.num_weights += 1;
+            if new_w != *w {
+                report.num_changed += 1;
+            }
+            report.max_abs_error = report.max_abs_error.max(error);
+            error_sum += error;
+            distinct.insert(new_w.to_bits());
+            *w = new_w;
+        };
+        for feature_set in &mut merged_model.feature_sets {
+            update(&mut feature_set.weight);
+        }
+        for hm in &mut merged_model.matrix {
+            for w in hm.values_mut() {
+                update(w);
+            }
+            // Missing entries are treated as zero costs in matrix.def.
+            hm.retain(|_, w| *w != 0.0);
+        }
+
+        report.num_matrix_entries_after = merged_model.matrix.iter().map(|hm| hm.len()).sum();
+        report.num_distinct_weights = distinct.len();
+        if report.num_weights != 0 {
+            report.mean_abs_error = error_sum / report.num_weights as f64;
+        }
+        report
+    }
 }
 
 impl Model {
+    /// 必要に応じてモデルをマージし、登録済みの圧縮処理を適用します。
+    fn merge_if_needed(&mut self) -> Result<()> {
+        if self.merged_model.is_none() {
+            let mut merged_model = self.data.raw_model.merge()?;
+            for compression in &self.compressions {
+                compression.apply(&mut merged_model);
+            }
+            self.merged_model = Some(merged_model);
+        }
+        Ok(())
+    }
+
+    /// 絶対値が閾値未満の重みを0にして、モデルを枝刈りします。
+    ///
+    /// 接続行列からは該当する要素が削除されるため、[`Model::write_dictionary()`]で
+    /// 出力される`matrix.def`が小さくなります。単語の重みは0（コスト0）になります。
+    /// 枝刈りはこのモデルに記録され、[`Model::read_user_lexicon()`]の呼び出し後にも
+    /// 再適用されますが、[`Model::write_model()`]で出力されるモデルには含まれません。
+    ///
+    /// # 引数
+    ///
+    /// * `threshold` - 重みの絶対値の閾値
+    ///
+    /// # 戻り値
+    ///
+    /// 枝刈りによる変化をまとめた[`CompressionReport`]
+    ///
+    /// # エラー
+    ///
+    /// 以下の場合に [`VibratoError`](crate::errors::VibratoError) が返されます：
+    ///
+    /// - `threshold`が非負の有限値でない場合
+    /// - モデルのマージに失敗した場合
+    pub fn prune(&mut self, threshold: f64) -> Result<CompressionReport> {
+        if !threshold.is_finite() || threshold < 0.0 {
+            return Err(VibratoError::invalid_argument(
+                "threshold",
+                "must be a non-negative finite number.",
+            ));
+        }
+        self.compress(Compression::Prune(threshold))
+    }
+
+    /// 重みを等間隔の値に丸めて、モデルを量子化します。
+    ///
+    /// 重みの絶対値の最大値を`num_levels`等分した刻みで各重みを丸めるため、
+    /// 量子化後の重みは高々`2 * num_levels + 1`種類になります。量子化によって0に
+    /// 丸められた接続行列の要素は削除されます。量子化はこのモデルに記録され、
+    /// [`Model::read_user_lexicon()`]の呼び出し後にも再適用されます。
+    ///
+    /// # 引数
+    ///
+    /// * `num_levels` - 正の側の段階数
+    ///
+    /// # 戻り値
+    ///
+    /// 量子化による変化をまとめた[`CompressionReport`]
+    ///
+    /// # エラー
+    ///
+    /// 以下の場合に [`VibratoError`](crate::errors::VibratoError) が返されます：
+    ///
+    /// - `num_levels`が0の場合
+    /// - モデルのマージに失敗した場合
+    pub fn quantize(&mut self, num_levels: u16) -> Result<CompressionReport> {
+        if num_levels == 0 {
+            return Err(VibratoError::invalid_argument(
+                "num_levels",
+                "must be at least 1.",
+            ));
+        }
+        self.compress(Compression::Quantize(num_levels))
+    }
+
+    fn compress(&mut self, compression: Compression) -> Result<CompressionReport> {
+        self.merge_if_needed()?;
+        let report = compression.apply(self.merged_model.as_mut().unwrap());
+        self.compressions.push(compression);
+        Ok(report)
+    }
+
+    /// 学習済みの素性を列挙します。
+    ///
+    /// 重みが0の素性は含まれません。
+    fn features(&self) -> Vec<FeatureWeight> {
+        let raw_model = &self.data.raw_model;
+        let weights = raw_model.weights();
+        let feature_extractor = &self.data.config.feature_extractor;
+        let mut features = vec![];
+
+        let unigram_weight_indices = raw_model.unigram_weight_indices();
+        for (name, feat_id) in &feature_extractor.unigram_feature_ids {
+            let widx = unigram_weight_indices
+                .get(usize::from_u32(feat_id.get() - 1))
+                .copied()
+                .flatten();
+            if let Some(widx) = widx {
+                let weight = weights[usize::from_u32(widx.get() - 1)];
+                if weight != 0.0 {
+                    features.push(FeatureWeight {
+                        kind: FeatureKind::Unigram,
+                        name: name.clone(),
+                        weight,
+                    });
+                }
+            }
+        }
+
+        let mut left_features = HashMap::new();
+        for (feature, idx) in feature_extractor.left_feature_ids() {
+            left_features.insert(idx.get(), feature.as_str());
+        }
+        let mut right_features = HashMap::new();
+        for (feature, idx) in feature_extractor.right_feature_ids() {
+            right_features.insert(idx.get(), feature.as_str());
+        }
+        for (left_feat_id, hm) in raw_model.bigram_weight_indices().iter().enumerate() {
+            let left_feat_str = left_features
+                .get(&u32::try_from(left_feat_id).unwrap())
+                .copied()
+                .unwrap_or("");
+            for (right_feat_id, widx) in hm {
+                let weight = weights[usize::from_u32(*widx)];
+                if weight != 0.0 {
+                    let right_feat_str = right_features.get(right_feat_id).copied().unwrap_or("");
+                    features.push(FeatureWeight {
+                        kind: FeatureKind::Bigram,
+                        name: format!("{left_feat_str}/{right_feat_str}"),
+                        weight,
+                    });
+                }
+            }
+        }
+        features
+    }
+
+    /// 重みの絶対値が大きい順に素性を返します。
+    ///
+    /// 辞書を出力してコストを比較することなく、どの素性が解析結果に強く影響して
+    /// いるかを確認できます。重みの絶対値が等しい素性は、種類と文字列の順に並びます。
+    /// 重みが0の素性は含まれません。
+    ///
+    /// # 引数
+    ///
+    /// * `n` - 返す素性の最大数
+    ///
+    /// # 戻り値
+    ///
+    /// 素性とその重みのベクタ
+    ///
+    /// # 例
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use vibrato_rkyv::trainer::Model;
+    ///
+    /// let model = Model::read_model(zstd::Decoder::new(File::open("model.zst")?)?)?;
+    /// for f in model.top_features(10) {
+    ///     println!("{:?}\t{}\t{}", f.kind, f.name, f.weight);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn top_features(&self, n: usize) -> Vec<FeatureWeight> {
+        let mut features = self.features();
+        features.sort_unstable_by(|a, b| {
+            b.weight
+                .abs()
+                .total_cmp(&a.weight.abs())
+                .then_with(|| (a.kind == FeatureKind::Bigram).cmp(&(b.kind == FeatureKind::Bigram)))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        features.truncate(n);
+        features
+    }
+
+    /// 重みが0でない素性の数を返します。
+    ///
+    /// unigram素性とbigram素性の合計です。学習の反復やL1正則化の強さによって
+    /// モデルの大きさがどう変わるかを調べる用途を想定しています。
+    ///
+    /// # 戻り値
+    ///
+    /// 素性の数
+    pub fn num_features(&self) -> usize {
+        self.features().len()
+    }
+
+    /// 指定された文字列の素性の重みを返します。
+    ///
+    /// unigram素性を優先して検索し、見つからない場合は`左側の素性/右側の素性`の形式の
+    /// bigram素性として検索します。
+    ///
+    /// # 引数
+    ///
+    /// * `name` - 素性の文字列
+    ///
+    /// # 戻り値
+    ///
+    /// 素性とその重み。素性が存在しない場合や、重みが0の場合は`None`
+    pub fn feature_weight(&self, name: &str) -> Option<FeatureWeight> {
+        let features = self.features();
+        features
+            .iter()
+            .find(|f| f.kind == FeatureKind::Unigram && f.name == name)
+            .or_else(|| features.iter().find(|f| f.kind == FeatureKind::Bigram && f.name == name))
+            .cloned()
+    }
+
     /// ユーザー定義辞書ファイルを読み込みます。
     ///
     /// ユーザー定義辞書ファイルにパラメータを割り当てたい場合は、
@@ -86,6 +456,8 @@ impl Model {
                 .char_prop()
                 .char_info(first_char)
                 .base_id();
+            let char_types =
+                Trainer::surface_char_types(self.data.config.dict.char_prop(), &entry.surface);
             let feature_set = Trainer::extract_feature_set(
                 &mut self.data.config.feature_extractor,
                 &self.data.config.unigram_rewriter,
@@ -93,6 +465,7 @@ impl Model {
                 &self.data.config.right_rewriter,
                 entry.feature,
                 cate_id,
+                Some(&char_types),
             );
             let label_id = self
                 .data
@@ -139,9 +512,7 @@ impl Model {
         R: Write,
         C: Write,
     {
-        if self.merged_model.is_none() {
-            self.merged_model = Some(self.data.raw_model.merge()?);
-        }
+        self.merge_if_needed()?;
         let merged_model = self.merged_model.as_ref().unwrap();
 
         // scales weights.
@@ -215,7 +586,11 @@ impl Model {
             let left_feat_str = left_features
                 .get(&u32::try_from(left_feat_id).unwrap())
                 .map_or("", |x| x.as_str());
-            for (right_feat_id, widx) in hm {
+            // Sort the entries so that the generated file, and thus the feature IDs of the
+            // dictionary built from it, do not depend on the iteration order of the hash map.
+            let mut entries: Vec<_> = hm.iter().collect();
+            entries.sort_unstable_by_key(|&(right_feat_id, _)| right_feat_id);
+            for (right_feat_id, widx) in entries {
                 let right_feat_str = right_features.get(right_feat_id).map_or("", |x| x.as_str());
                 let w = self.data.raw_model.weights()[usize::from_u32(*widx)];
                 let cost = (-w * weight_scale_factor) as i32;
@@ -225,6 +600,104 @@ impl Model {
         Ok(())
     }
 
+    /// マージ済みモデルから辞書の各要素を作成します。
+    ///
+    /// 重みは`i16`で表現できるようにスケーリングされます。
+    /// 事前に[`merge_if_needed()`](Self::merge_if_needed)を呼び出す必要があります。
+    ///
+    /// # エラー
+    ///
+    /// 接続IDが`u16`で表現できない場合に [`VibratoError`] が返されます。
+    fn trained_parts(&self) -> Result<TrainedParts<'_>> {
+        let merged_model = self.merged_model.as_ref().unwrap();
+
+        // scales weights to represent them in i16.
+        let mut weight_abs_max = 0f64;
+        for feature_set in &merged_model.feature_sets {
+            weight_abs_max = weight_abs_max.max(feature_set.weight.abs());
+        }
+        for hm in &merged_model.matrix {
+            for &w in hm.values() {
+                weight_abs_max = weight_abs_max.max(w.abs());
+            }
+        }
+        let weight_scale_factor = f64::from(i16::MAX) / weight_abs_max;
+        let scale = |w: f64| (-w * weight_scale_factor) as i16;
+        let conn_id = |id| {
+            u16::try_from(id).map_err(|_| {
+                VibratoError::invalid_state("Too many connection IDs.", format!("{id}"))
+            })
+        };
+
+        let config = &self.data.config;
+
+        let mut lexicon = Vec::with_capacity(config.surfaces.len());
+        for i in 0..config.surfaces.len() {
+            let feature_set = merged_model.feature_sets[i];
+            let word_idx = WordIdx::new(LexType::System, u32::try_from(i).unwrap());
+            lexicon.push(RawWordEntry {
+                surface: config.surfaces[i].clone(),
+                param: WordParam::new(
+                    conn_id(feature_set.left_id)?,
+                    conn_id(feature_set.right_id)?,
+                    scale(feature_set.weight),
+                ),
+                feature: config.dict.system_lexicon().word_feature(word_idx),
+            });
+        }
+
+        let mut unk_entries = Vec::with_capacity(config.dict.unk_handler().len());
+        for i in 0..config.dict.unk_handler().len() {
+            let word_idx = WordIdx::new(LexType::Unknown, u32::try_from(i).unwrap());
+            let feature_set = merged_model.feature_sets[config.surfaces.len() + i];
+            unk_entries.push(UnkEntry {
+                cate_id: config.dict.unk_handler().word_cate_id(word_idx),
+                left_id: conn_id(feature_set.left_id)?,
+                right_id: conn_id(feature_set.right_id)?,
+                word_cost: scale(feature_set.weight),
+                feature: config.dict.unk_handler().word_feature(word_idx).to_string(),
+            });
+        }
+
+        let mut connection_costs = vec![];
+        for (right_conn_id, hm) in merged_model.matrix.iter().enumerate() {
+            let mut pairs: Vec<_> = hm.iter().map(|(&j, &w)| (j, w)).collect();
+            pairs.sort_unstable_by_key(|&(k, _)| k);
+            for (left_conn_id, w) in pairs {
+                let left_conn_id = usize::try_from(left_conn_id).unwrap();
+                connection_costs.push((right_conn_id, left_conn_id, scale(w)));
+            }
+        }
+
+        let mut user_lexicon = Vec::with_capacity(self.user_entries.len());
+        for (word, param, label_id) in &self.user_entries {
+            let feature_set = merged_model.feature_sets[usize::from_u32(label_id.get() - 1)];
+            let param = if *param == WordParam::default() {
+                WordParam::new(
+                    conn_id(feature_set.left_id)?,
+                    conn_id(feature_set.right_id)?,
+                    scale(feature_set.weight),
+                )
+            } else {
+                *param
+            };
+            user_lexicon.push(RawWordEntry {
+                surface: word.surface().to_string(),
+                param,
+                feature: word.feature(),
+            });
+        }
+
+        Ok(TrainedParts {
+            lexicon,
+            unk_entries,
+            num_right: merged_model.right_conn_to_left_feats.len() + 1,
+            num_left: merged_model.left_conn_to_right_feats.len() + 1,
+            connection_costs,
+            user_lexicon,
+        })
+    }
+
     /// 辞書を書き込みます。
     ///
     /// # 引数
@@ -258,119 +731,82 @@ impl Model {
         U: Write,
         S: Write,
     {
-        if self.merged_model.is_none() {
-            self.merged_model = Some(self.data.raw_model.merge()?);
-        }
-        let merged_model = self.merged_model.as_ref().unwrap();
+        self.merge_if_needed()?;
+        let parts = self.trained_parts()?;
 
         let mut lexicon_wtr = BufWriter::new(lexicon_wtr);
         let mut unk_handler_wtr = BufWriter::new(unk_handler_wtr);
         let mut connector_wtr = BufWriter::new(connector_wtr);
         let mut user_lexicon_wtr = BufWriter::new(user_lexicon_wtr);
 
-        // scales weights to represent them in i16.
-        let mut weight_abs_max = 0f64;
-        for feature_set in &merged_model.feature_sets {
-            weight_abs_max = weight_abs_max.max(feature_set.weight.abs());
-        }
-        for hm in &merged_model.matrix {
-            for &w in hm.values() {
-                weight_abs_max = weight_abs_max.max(w.abs());
-            }
-        }
-        let weight_scale_factor = f64::from(i16::MAX) / weight_abs_max;
-
-        let config = &self.data.config;
-
-        for i in 0..config.surfaces.len() {
-            let feature_set = merged_model.feature_sets[i];
-            let word_idx = WordIdx::new(LexType::System, u32::try_from(i).unwrap());
-            let feature = config.dict.system_lexicon().word_feature(word_idx);
-
-            // writes surface
-            utils::quote_csv_cell(&mut lexicon_wtr, config.surfaces[i].as_bytes())?;
+        write_lexicon(&mut lexicon_wtr, &parts.lexicon)?;
+        write_lexicon(&mut user_lexicon_wtr, &parts.user_lexicon)?;
 
-            // writes others
-            writeln!(
-                &mut lexicon_wtr,
-                ",{},{},{},{}",
-                feature_set.left_id,
-                feature_set.right_id,
-                (-feature_set.weight * weight_scale_factor) as i16,
-                feature,
-            )?;
-        }
-
-        for i in 0..config.dict.unk_handler().len() {
-            let word_idx = WordIdx::new(LexType::Unknown, u32::try_from(i).unwrap());
-            let cate_id = config.dict.unk_handler().word_cate_id(word_idx);
-            let feature = config.dict.unk_handler().word_feature(word_idx);
-            let cate_string = config
-                .dict
-                .char_prop()
-                .cate_str(u32::from(cate_id))
-                .unwrap();
-            let feature_set = merged_model.feature_sets[config.surfaces.len() + i];
+        let char_prop = self.data.config.dict.char_prop();
+        for e in &parts.unk_entries {
+            let cate_string = char_prop.cate_str(u32::from(e.cate_id)).unwrap();
             writeln!(
                 &mut unk_handler_wtr,
                 "{},{},{},{},{}",
-                cate_string,
-                feature_set.left_id,
-                feature_set.right_id,
-                (-feature_set.weight * weight_scale_factor) as i16,
-                feature,
+                cate_string, e.left_id, e.right_id, e.word_cost, e.feature,
             )?;
         }
 
-        writeln!(
-            &mut connector_wtr,
-            "{} {}",
-            merged_model.right_conn_to_left_feats.len() + 1,
-            merged_model.left_conn_to_right_feats.len() + 1,
-        )?;
-        for (right_conn_id, hm) in merged_model.matrix.iter().enumerate() {
-            let mut pairs: Vec<_> = hm.iter().map(|(&j, &w)| (j, w)).collect();
-            pairs.sort_unstable_by_key(|&(k, _)| k);
-            for (left_conn_id, w) in pairs {
-                writeln!(
-                    &mut connector_wtr,
-                    "{} {} {}",
-                    right_conn_id,
-                    left_conn_id,
-                    (-w * weight_scale_factor) as i16
-                )?;
-            }
+        writeln!(&mut connector_wtr, "{} {}", parts.num_right, parts.num_left)?;
+        for &(right_conn_id, left_conn_id, cost) in &parts.connection_costs {
+            writeln!(&mut connector_wtr, "{right_conn_id} {left_conn_id} {cost}")?;
         }
 
-        for (word, param, label_id) in &self.user_entries {
-            let feature_set = merged_model.feature_sets[usize::from_u32(label_id.get() - 1)];
+        Ok(())
+    }
 
-            // writes surface
-            utils::quote_csv_cell(&mut user_lexicon_wtr, word.surface().as_bytes())?;
-
-            // writes others
-            if *param == WordParam::default() {
-                writeln!(
-                    &mut user_lexicon_wtr,
-                    ",{},{},{},{}",
-                    feature_set.left_id,
-                    feature_set.right_id,
-                    (-feature_set.weight * weight_scale_factor) as i16,
-                    word.feature(),
-                )?;
-            } else {
-                writeln!(
-                    &mut user_lexicon_wtr,
-                    ",{},{},{},{}",
-                    param.left_id,
-                    param.right_id,
-                    param.word_cost,
-                    word.feature(),
-                )?;
-            }
+    /// 学習済みのシステム辞書をメモリ上で直接構築します。
+    ///
+    /// [`write_dictionary()`](Self::write_dictionary)で書き出したファイルを
+    /// [`SystemDictionaryBuilder::from_readers()`](crate::SystemDictionaryBuilder::from_readers)で
+    /// 読み込み直した場合と同じ辞書を、CSVを経由せずに作成します。接続コストには行列形式の
+    /// コネクタが使用されます。[`read_user_lexicon()`](Self::read_user_lexicon)で
+    /// ユーザー定義辞書を読み込んでいる場合は、その単語がユーザー辞書として含まれます。
+    ///
+    /// # 戻り値
+    ///
+    /// 構築された辞書
+    ///
+    /// # エラー
+    ///
+    /// 以下の場合に [`VibratoError`](crate::errors::VibratoError) が返されます：
+    ///
+    /// - コストのマージに失敗した場合
+    /// - 辞書の構築に失敗した場合
+    pub fn build_system_dictionary(&mut self) -> Result<DictionaryInner> {
+        self.merge_if_needed()?;
+        let parts = self.trained_parts()?;
+
+        let mut data = vec![0; parts.num_right * parts.num_left];
+        for &(right_conn_id, left_conn_id, cost) in &parts.connection_costs {
+            data[left_conn_id * parts.num_right + right_conn_id] = cost;
         }
+        let connector = MatrixConnector::new(data, parts.num_right, parts.num_left);
+
+        let char_prop = self.data.config.dict.char_prop().clone();
+        let unk_handler = UnkHandler::from_entries(parts.unk_entries, char_prop.num_categories());
+        let user_lexicon = if parts.user_lexicon.is_empty() {
+            None
+        } else {
+            Some(Lexicon::from_entries(&parts.user_lexicon, LexType::User)?)
+        };
+        DictionaryInner::from_parts(
+            Lexicon::from_entries(&parts.lexicon, LexType::System)?,
+            user_lexicon,
+            ConnectorWrapper::Matrix(connector),
+            char_prop,
+            unk_handler,
+        )
+    }
 
-        Ok(())
+    /// 学習済みの辞書を構築し、トークナイザーを作成します（評価用）。
+    pub(crate) fn build_tokenizer(&mut self) -> Result<Tokenizer> {
+        Ok(Tokenizer::from_inner(self.build_system_dictionary()?))
     }
 
     /// モデルデータをエクスポートします。
@@ -433,6 +869,7 @@ impl Model {
             data,
             merged_model: None,
             user_entries: vec![],
+            compressions: vec![],
         })
     }
 }
@@ -5,6 +5,7 @@
 //!
 //! - `FromU32`: u32からの型変換トレイト
 //! - CSV行の解析と引用符処理
+//! - 処理の並行実行
 //! - テスト用のマクロ
 
 #[cfg(feature = "train")]
@@ -136,6 +137,59 @@ pub fn parse_csv_row(row: &str) -> Vec<String> {
     features
 }
 
+/// CSV形式の文字列の先頭から、指定された数のフィールドのみを残した部分文字列を返します。
+///
+/// 引用符で囲まれたフィールド内のカンマは区切りとして扱いません。
+/// フィールドの数が`num_fields`以下の場合は、`row`全体を返します。
+pub(crate) fn truncate_csv_row(row: &str, num_fields: usize) -> &str {
+    if num_fields == 0 {
+        return "";
+    }
+    let mut quoted = false;
+    let mut num_commas = 0;
+    for (i, b) in row.bytes().enumerate() {
+        match b {
+            b'"' => quoted = !quoted,
+            b',' if !quoted => {
+                num_commas += 1;
+                if num_commas == num_fields {
+                    return &row[..i];
+                }
+            }
+            _ => (),
+        }
+    }
+    row
+}
+
+/// 2つの処理を実行し、両方の結果を返します。
+///
+/// `parallel`が`true`の場合、`fb`は別のスレッドで`fa`と並行して実行されます。
+/// `false`の場合は`fa`、`fb`の順に現在のスレッドで実行されます。
+///
+/// # パニック
+///
+/// いずれかの処理がパニックした場合、そのパニックを呼び出し元に伝播します。
+pub(crate) fn join<A, B, FA, FB>(parallel: bool, fa: FA, fb: FB) -> (A, B)
+where
+    FA: FnOnce() -> A,
+    FB: FnOnce() -> B + Send,
+    B: Send,
+{
+    if !parallel {
+        let a = fa();
+        return (a, fb());
+    }
+    std::thread::scope(|s| {
+        let handle = s.spawn(fb);
+        let a = fa();
+        let b = handle
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e));
+        (a, b)
+    })
+}
+
 #[cfg(test)]
 /// HashMapリテラルを簡潔に記述するためのマクロ
 ///
@@ -192,4 +246,14 @@ mod tests {
             parse_csv_row("名詞,\"1,2-ジクロロエタン\"").as_slice()
         );
     }
+
+    #[test]
+    fn test_truncate_csv_row() {
+        let row = "名詞,\"1,2-ジクロロエタン\",*,読み";
+        assert_eq!(truncate_csv_row(row, 0), "");
+        assert_eq!(truncate_csv_row(row, 1), "名詞");
+        assert_eq!(truncate_csv_row(row, 2), "名詞,\"1,2-ジクロロエタン\"");
+        assert_eq!(truncate_csv_row(row, 4), row);
+        assert_eq!(truncate_csv_row(row, 10), row);
+    }
 }